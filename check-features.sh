@@ -0,0 +1,52 @@
+#!/usr/bin/env sh
+# Builds every crate with each of its optional features on its own and with none,
+# so a feature that only compiles alongside another one is caught before release
+set -e
+
+# the crates are not a workspace, each is checked through its own manifest
+manifest() {
+	case "$1" in
+		mangol) echo "Cargo.toml" ;;
+		mangol-*) echo "src/${1#mangol-}/Cargo.toml" ;;
+	esac
+}
+
+check() {
+	echo "cargo check $1 $2"
+	cargo check --all-targets --manifest-path "$(manifest "$1")" $2
+}
+
+# library only, tests and benches pull in the native dependencies
+check_wasm() {
+	echo "cargo check $1 $2 (wasm32)"
+	rustup target add wasm32-unknown-unknown >/dev/null 2>&1 || true
+	cargo check --lib --target wasm32-unknown-unknown --manifest-path "$(manifest "$1")" $2
+}
+
+check mangol-common "--no-default-features"
+check mangol-common ""
+
+check mangol-solana ""
+for feature in tpu geyser fault-injection; do
+	check mangol-solana "--features $feature"
+done
+
+# account decoding and health math only, what the wasm32 consumers build
+check mangol-mango "--no-default-features"
+check_wasm mangol-common "--no-default-features"
+check_wasm mangol-mango "--no-default-features"
+check mangol-mango "--no-default-features --features client"
+check mangol-mango ""
+
+check mangol-mailer "--no-default-features"
+for feature in telegram metrics; do
+	check mangol-mailer "--no-default-features --features $feature"
+done
+
+check mangol-strategies "--no-default-features"
+for feature in liquidator backtest mailer metrics geyser; do
+	check mangol-strategies "--no-default-features --features $feature"
+done
+check mangol-strategies ""
+
+check mangol ""
@@ -0,0 +1,73 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Source of time for strategies and order expiry, so tests and the backtester can fast-forward
+pub trait Clock: Send + Sync {
+	/// Milliseconds since the unix epoch
+	fn now_millis(&self) -> u64;
+	fn sleep(&self, duration: Duration);
+
+	fn now_ts(&self) -> u64 {
+		self.now_millis() / 1000
+	}
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+	fn now_millis(&self) -> u64 {
+		SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+	}
+
+	fn sleep(&self, duration: Duration) {
+		std::thread::sleep(duration)
+	}
+}
+
+/// Only moves when advanced or slept on, sleeping returns immediately
+#[derive(Debug, Default)]
+pub struct SimulatedClock {
+	now_millis: AtomicU64,
+}
+
+impl SimulatedClock {
+	pub fn new(now_ts: u64) -> Self {
+		Self {
+			now_millis: AtomicU64::new(now_ts * 1000),
+		}
+	}
+
+	pub fn advance(&self, duration: Duration) {
+		self.now_millis.fetch_add(duration.as_millis() as u64, Ordering::SeqCst);
+	}
+
+	pub fn set_ts(&self, now_ts: u64) {
+		self.now_millis.store(now_ts * 1000, Ordering::SeqCst);
+	}
+}
+
+impl Clock for SimulatedClock {
+	fn now_millis(&self) -> u64 {
+		self.now_millis.load(Ordering::SeqCst)
+	}
+
+	fn sleep(&self, duration: Duration) {
+		self.advance(duration)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+	use crate::clock::{Clock, SimulatedClock};
+
+	#[test]
+	fn simulated_sleep_fast_forwards() {
+		let clock = SimulatedClock::new(1_000);
+		clock.sleep(Duration::from_secs(43));
+		assert_eq!(clock.now_ts(), 1_043);
+		clock.advance(Duration::from_millis(500));
+		assert_eq!(clock.now_millis(), 1_043_500);
+	}
+}
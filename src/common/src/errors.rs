@@ -1,3 +1,4 @@
+#[cfg(feature = "client")]
 use solana_client::client_error::{ClientError, ClientErrorKind};
 use thiserror::Error;
 
@@ -8,18 +9,38 @@ pub enum MangolError {
 	#[error("Solana Error")]
 	SolanaError(#[from] SolanaError),
 	#[error("Swap Service Error")]
-	SwapServiceError(#[from] SwapServiceError)
+	SwapServiceError(#[from] SwapServiceError),
+	#[error("IO Error {0}")]
+	IoError(#[from] std::io::Error),
+	#[error("Serialization Error {0}")]
+	SerializationError(String),
+	#[error("Keystore Error {0}")]
+	KeyStoreError(String),
+	#[error("Mango Error {0}")]
+	MangoError(String),
+	#[error("Order Sizing Error {0}")]
+	SizingError(#[from] SizingError),
+	#[error("Order already in flight for market {0}")]
+	OrderInFlight(usize),
+	#[error("Fee payer {0} is in use by another process")]
+	FeePayerBusy(String)
 }
 #[derive(Error, Debug)]
 pub enum SolanaError {
 	#[error("Token mint not found")]
 	TokenMintNotFound,
+	#[cfg(feature = "client")]
 	#[error("RpcClient error {0}")]
 	RpcClientError(ClientErrorKind),
 	#[error("No program accounts exist that match the specified config")]
 	ProgramAccountsNotFound,
 	#[error("Failed to get status of transaction for the given commitment")]
 	TransactionStatusUnknown,
+	#[error("Transaction simulation failed {0}")]
+	SimulationFailed(String),
+	/// The cluster rejected the signature of `{0}`, resending with the same key won't help
+	#[error("Signature verification failed for {0}")]
+	SignatureVerificationFailed(String),
 	
 	
 }
@@ -30,6 +51,19 @@ pub enum SwapServiceError {
 	MarketNotFound(String, String, String)
 }
 
+#[derive(Error, Debug, PartialEq)]
+pub enum SizingError {
+	#[error("Price {0} is not a valid order price")]
+	InvalidPrice(f64),
+	#[error("{0} rounds to zero lots")]
+	ZeroSize(String),
+	#[error("{0} overflows i64 lots")]
+	Overflow(String),
+	#[error("{0} base lots is below the minimum order of {1} base lots")]
+	BelowMinimum(i64, i64)
+}
+
+#[cfg(feature = "client")]
 impl From<ClientError> for MangolError {
 	fn from(e: ClientError) -> Self {
 			MangolError::SolanaError(SolanaError::RpcClientError(e.kind))
@@ -3,6 +3,8 @@ use solana_program::account_info::AccountInfo;
 use solana_program::program_error::ProgramError;
 use std::cell::{Ref, RefMut};
 pub mod errors;
+pub mod clock;
+pub mod venue;
 pub trait Loadable: Pod {
     fn load_mut<'a>(account: &'a AccountInfo) -> Result<RefMut<'a, Self>, ProgramError> {
         Ok(RefMut::map(account.try_borrow_mut_data()?, |data| from_bytes_mut(data)))
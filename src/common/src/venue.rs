@@ -0,0 +1,63 @@
+use crate::errors::MangolResult;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OrderSide {
+	Buy,
+	Sell,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OrderKind {
+	Limit,
+	/// Rests on the book or is dropped, never takes
+	PostOnly,
+	ImmediateOrCancel,
+	Market,
+}
+
+/// An order in ui units, each venue converts to its own lots and prices
+#[derive(Clone, Debug, PartialEq)]
+pub struct VenueOrder {
+	pub market: String,
+	pub side: OrderSide,
+	/// Quote per base, ignored for market orders
+	pub price: f64,
+	/// Base amount
+	pub size: f64,
+	pub kind: OrderKind,
+	pub reduce_only: bool,
+	/// Seconds the order may rest before the venue drops it
+	pub expiry_secs: Option<u64>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct VenuePosition {
+	pub market: String,
+	/// Signed base amount, negative for shorts
+	pub base_size: f64,
+	/// Quote the position is carried at, including unsettled pnl
+	pub quote_position: f64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct VenueBalance {
+	/// Venue specific asset id, the mint for on-chain venues
+	pub asset: String,
+	/// Signed ui amount, negative when borrowed
+	pub amount: f64,
+}
+
+/// Where orders go. Strategies written against this instead of a venue's client
+/// can run on any venue that implements it
+pub trait ExecutionVenue {
+	fn name(&self) -> &str;
+	/// Refreshes whatever state positions and balances are read from
+	fn refresh(&mut self) -> MangolResult<()>;
+	/// Returns the transaction or order id
+	fn place_order(&self, order: &VenueOrder) -> MangolResult<String>;
+	fn cancel_all(&self, market: &str) -> MangolResult<String>;
+	fn positions(&self) -> MangolResult<Vec<VenuePosition>>;
+	fn balances(&self) -> MangolResult<Vec<VenueBalance>>;
+	/// Account value in quote ui units
+	fn equity(&self) -> MangolResult<f64>;
+}
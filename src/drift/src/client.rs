@@ -0,0 +1,97 @@
+use std::str::FromStr;
+
+use mangol_common::errors::{MangolError, MangolResult};
+use mangol_solana::connection::SolanaConnection;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::transaction::Transaction;
+
+use crate::instructions::{cancel_perp_orders, place_perp_order, OrderParams};
+use crate::types::{spot_market_pk, user_pk, DriftPerpMarketData, DriftUser, SpotInterest, DRIFT_PROGRAM_ID, QUOTE_SPOT_MARKET_INDEX};
+
+/// Trades Drift v2 perps from one sub account, positions valued at the markets' pyth prices
+pub struct DriftClient {
+	pub solana_connection: SolanaConnection,
+	pub program_id: Pubkey,
+	pub signer: Keypair,
+	pub sub_account_id: u16,
+	pub user_pk: Pubkey,
+	pub user: DriftUser,
+	/// Interest indexes of the quote spot market, refreshed by update
+	pub quote_interest: SpotInterest,
+	pub markets: Vec<DriftPerpMarketData>,
+	/// Ui oracle price of every market in `markets`, refreshed by update
+	pub prices: Vec<f64>,
+}
+
+impl DriftClient {
+	pub fn new(solana_connection: &SolanaConnection, signer: Keypair, sub_account_id: u16, markets: Vec<DriftPerpMarketData>) -> MangolResult<Self> {
+		let program_id = Pubkey::from_str(DRIFT_PROGRAM_ID).unwrap();
+		let user_pk = user_pk(&program_id, &signer.pubkey(), sub_account_id);
+		let solana_connection = solana_connection.try_clone()?;
+		let user = DriftUser::decode(&solana_connection.rpc_client.get_account_data(&user_pk)?)?;
+		let mut client = Self {
+			solana_connection,
+			program_id,
+			signer,
+			sub_account_id,
+			user_pk,
+			user,
+			quote_interest: SpotInterest::default(),
+			prices: vec![0.0; markets.len()],
+			markets,
+		};
+		client.update()?;
+		Ok(client)
+	}
+
+	pub fn market(&self, name: &str) -> MangolResult<&DriftPerpMarketData> {
+		self.markets.iter().find(|market| market.name == name)
+			  .ok_or_else(|| MangolError::MangoError(format!("Unknown drift perp market {}", name)))
+	}
+
+	pub fn update(&mut self) -> MangolResult<()> {
+		let quote_market_pk = spot_market_pk(&self.program_id, QUOTE_SPOT_MARKET_INDEX);
+		let mut keys = vec![self.user_pk, quote_market_pk];
+		keys.extend(self.markets.iter().map(|market| Pubkey::from_str(&market.oracle).unwrap()));
+		let accounts = self.solana_connection.rpc_client.get_multiple_accounts(&keys)?;
+		let user = accounts[0].as_ref().ok_or_else(|| MangolError::MangoError(format!("Drift user {} not found", self.user_pk)))?;
+		self.user = DriftUser::decode(&user.data)?;
+		let quote_market = accounts[1].as_ref().ok_or_else(|| MangolError::MangoError(format!("Drift quote spot market {} not found", quote_market_pk)))?;
+		self.quote_interest = SpotInterest::decode(&quote_market.data)?;
+		for (i, oracle) in accounts[2..].iter().enumerate() {
+			let oracle = oracle.as_ref().ok_or_else(|| MangolError::MangoError(format!("Oracle of {} not found", self.markets[i].name)))?;
+			let price = pyth_client::load_price(&oracle.data).map_err(|e| MangolError::SerializationError(format!("{:?}", e)))?;
+			self.prices[i] = price.agg.price as f64 * 10_f64.powi(price.expo);
+		}
+		Ok(())
+	}
+
+	fn oracle(&self, market_index: u16) -> MangolResult<Pubkey> {
+		self.markets.iter().find(|market| market.market_index == market_index)
+			  .map(|market| Pubkey::from_str(&market.oracle).unwrap())
+			  .ok_or_else(|| MangolError::MangoError(format!("Unknown drift perp market index {}", market_index)))
+	}
+
+	pub fn place_perp_order(&self, params: &OrderParams) -> MangolResult<String> {
+		let instruction = place_perp_order(&self.program_id, &self.user_pk, &self.signer.pubkey(), &self.oracle(params.market_index)?, params);
+		let transaction = Transaction::new_with_payer(&[instruction], Some(&self.signer.pubkey()));
+		self.solana_connection.try_tx_once(transaction, &self.signer)
+	}
+
+	pub fn cancel_perp_orders(&self, market_index: u16) -> MangolResult<String> {
+		let instruction = cancel_perp_orders(&self.program_id, &self.user_pk, &self.signer.pubkey(), &self.oracle(market_index)?, market_index);
+		let transaction = Transaction::new_with_payer(&[instruction], Some(&self.signer.pubkey()));
+		self.solana_connection.try_tx_once(transaction, &self.signer)
+	}
+
+	/// Ui quote equity at the last oracle prices
+	pub fn equity(&self) -> f64 {
+		self.user.equity(&self.quote_interest, &self.markets, &self.prices)
+	}
+
+	/// Equity over maintenance margin, the account is liquidatable below 0
+	pub fn health(&self) -> f64 {
+		self.user.maintenance_health(&self.quote_interest, &self.markets, &self.prices)
+	}
+}
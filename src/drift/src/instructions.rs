@@ -0,0 +1,126 @@
+use borsh::BorshSerialize;
+use solana_program::hash::hash;
+use solana_program::instruction::{AccountMeta, Instruction};
+use solana_program::pubkey::Pubkey;
+
+use crate::types::{perp_market_pk, spot_market_pk, state_pk, QUOTE_SPOT_MARKET_INDEX};
+
+#[derive(BorshSerialize, Copy, Clone, Debug, PartialEq)]
+pub enum OrderType {
+	Market,
+	Limit,
+	TriggerMarket,
+	TriggerLimit,
+	Oracle,
+}
+
+#[derive(BorshSerialize, Copy, Clone, Debug, PartialEq)]
+pub enum MarketType {
+	Spot,
+	Perp,
+}
+
+#[derive(BorshSerialize, Copy, Clone, Debug, PartialEq)]
+pub enum PositionDirection {
+	Long,
+	Short,
+}
+
+#[derive(BorshSerialize, Copy, Clone, Debug, PartialEq)]
+pub enum PostOnlyParam {
+	None,
+	MustPostOnly,
+	TryPostOnly,
+	Slide,
+}
+
+#[derive(BorshSerialize, Copy, Clone, Debug, PartialEq)]
+pub enum OrderTriggerCondition {
+	Above,
+	Below,
+	TriggeredAbove,
+	TriggeredBelow,
+}
+
+/// Drift v2 `OrderParams`, field order is the borsh layout the program expects
+#[derive(BorshSerialize, Clone, Debug, PartialEq)]
+pub struct OrderParams {
+	pub order_type: OrderType,
+	pub market_type: MarketType,
+	pub direction: PositionDirection,
+	pub user_order_id: u8,
+	/// In BASE_PRECISION
+	pub base_asset_amount: u64,
+	/// In PRICE_PRECISION, 0 for market orders
+	pub price: u64,
+	pub market_index: u16,
+	pub reduce_only: bool,
+	pub post_only: PostOnlyParam,
+	pub immediate_or_cancel: bool,
+	/// Unix timestamp after which the order is cancelled
+	pub max_ts: Option<i64>,
+	pub trigger_price: Option<u64>,
+	pub trigger_condition: OrderTriggerCondition,
+	pub oracle_price_offset: Option<i32>,
+	pub auction_duration: Option<u8>,
+	pub auction_start_price: Option<i64>,
+	pub auction_end_price: Option<i64>,
+}
+
+/// Anchor instruction discriminator, the first 8 bytes of sha256("global:<name>")
+fn discriminator(name: &str) -> [u8; 8] {
+	let mut discriminator = [0u8; 8];
+	discriminator.copy_from_slice(&hash(format!("global:{}", name).as_bytes()).to_bytes()[..8]);
+	discriminator
+}
+
+/// Accounts the program needs to value the user: the oracle, the quote spot market and the perp market
+fn market_accounts(program_id: &Pubkey, oracle: &Pubkey, market_index: u16) -> Vec<AccountMeta> {
+	vec![
+		AccountMeta::new_readonly(*oracle, false),
+		AccountMeta::new_readonly(spot_market_pk(program_id, QUOTE_SPOT_MARKET_INDEX), false),
+		AccountMeta::new(perp_market_pk(program_id, market_index), false),
+	]
+}
+
+pub fn place_perp_order(program_id: &Pubkey, user_pk: &Pubkey, authority_pk: &Pubkey, oracle: &Pubkey, params: &OrderParams) -> Instruction {
+	let mut accounts = vec![
+		AccountMeta::new_readonly(state_pk(program_id), false),
+		AccountMeta::new(*user_pk, false),
+		AccountMeta::new_readonly(*authority_pk, true),
+	];
+	accounts.extend(market_accounts(program_id, oracle, params.market_index));
+	let mut data = discriminator("place_perp_order").to_vec();
+	data.extend(params.try_to_vec().unwrap());
+	Instruction { program_id: *program_id, accounts, data }
+}
+
+/// Cancels every open perp order of the user in `market_index`
+pub fn cancel_perp_orders(program_id: &Pubkey, user_pk: &Pubkey, authority_pk: &Pubkey, oracle: &Pubkey, market_index: u16) -> Instruction {
+	let mut accounts = vec![
+		AccountMeta::new_readonly(state_pk(program_id), false),
+		AccountMeta::new(*user_pk, false),
+		AccountMeta::new_readonly(*authority_pk, true),
+	];
+	accounts.extend(market_accounts(program_id, oracle, market_index));
+	let mut data = discriminator("cancel_orders").to_vec();
+	data.extend((Some(MarketType::Perp), Some(market_index), None::<PositionDirection>).try_to_vec().unwrap());
+	Instruction { program_id: *program_id, accounts, data }
+}
+
+#[cfg(test)]
+mod tests {
+	use solana_program::pubkey::Pubkey;
+	use crate::instructions::{cancel_perp_orders, discriminator};
+
+	#[test]
+	fn encodes_cancel_with_anchor_discriminator() {
+		let program_id = Pubkey::new_unique();
+		let instruction = cancel_perp_orders(&program_id, &Pubkey::new_unique(), &Pubkey::new_unique(), &Pubkey::new_unique(), 2);
+		assert_eq!(&instruction.data[..8], &discriminator("cancel_orders"));
+		// Some(Perp), Some(2u16), None
+		assert_eq!(&instruction.data[8..], &[1, 1, 1, 2, 0, 0]);
+		assert_eq!(instruction.accounts.len(), 6);
+		assert!(instruction.accounts[2].is_signer);
+	}
+}
@@ -0,0 +1,4 @@
+pub mod types;
+pub mod instructions;
+pub mod client;
+pub mod venue;
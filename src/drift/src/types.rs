@@ -0,0 +1,207 @@
+use std::convert::TryInto;
+
+use mangol_common::errors::{MangolError, MangolResult};
+use serde::{Deserialize, Serialize};
+use solana_program::pubkey::Pubkey;
+
+pub const DRIFT_PROGRAM_ID: &str = "dRiftyHA39MWEi3m9aunc5MzRF1JYuBsbn6VPcn33UH";
+pub const BASE_PRECISION: f64 = 1e9;
+pub const QUOTE_PRECISION: f64 = 1e6;
+pub const PRICE_PRECISION: f64 = 1e6;
+pub const MARGIN_PRECISION: f64 = 1e4;
+pub const QUOTE_SPOT_MARKET_INDEX: u16 = 0;
+pub const QUOTE_DECIMALS: u32 = 6;
+/// Scaled balances times the cumulative interest are token amounts in 10^(19 - decimals)
+const SPOT_BALANCE_AND_INTEREST_DECIMALS: u32 = 19;
+
+const USER_SIZE: usize = 4376;
+const SPOT_POSITIONS_OFFSET: usize = 8 + 32 + 32 + 32;
+const SPOT_POSITION_SIZE: usize = 40;
+const PERP_POSITIONS_OFFSET: usize = SPOT_POSITIONS_OFFSET + MAX_SPOT_POSITIONS * SPOT_POSITION_SIZE;
+const PERP_POSITION_SIZE: usize = 96;
+pub const MAX_SPOT_POSITIONS: usize = 8;
+pub const MAX_PERP_POSITIONS: usize = 8;
+const SPOT_MARKET_CUMULATIVE_DEPOSIT_INTEREST_OFFSET: usize = 464;
+const SPOT_MARKET_CUMULATIVE_BORROW_INTEREST_OFFSET: usize = 480;
+
+/// A Drift perp market the bot trades, loaded from a json registry like PerpMarketData
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DriftPerpMarketData {
+	pub name: String,
+	pub market_index: u16,
+	/// Pyth price account of the market
+	pub oracle: String,
+	/// Maintenance margin ratio in MARGIN_PRECISION
+	pub margin_ratio_maintenance: u32,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct SpotPosition {
+	pub scaled_balance: u64,
+	/// Net deposits in token precision, interest excluded
+	pub cumulative_deposits: i64,
+	pub market_index: u16,
+	pub is_borrow: bool,
+}
+
+/// Interest indexes of a spot market, what scaled balances grow by as interest accrues
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct SpotInterest {
+	pub cumulative_deposit_interest: u128,
+	pub cumulative_borrow_interest: u128,
+}
+
+impl SpotInterest {
+	/// From a Drift v2 `SpotMarket` account
+	pub fn decode(data: &[u8]) -> MangolResult<Self> {
+		if data.len() < SPOT_MARKET_CUMULATIVE_BORROW_INTEREST_OFFSET + 16 {
+			return Err(MangolError::SerializationError(format!("Drift spot market data is {} bytes", data.len())));
+		}
+		Ok(Self {
+			cumulative_deposit_interest: u128::from_le_bytes(read(data, SPOT_MARKET_CUMULATIVE_DEPOSIT_INTEREST_OFFSET)),
+			cumulative_borrow_interest: u128::from_le_bytes(read(data, SPOT_MARKET_CUMULATIVE_BORROW_INTEREST_OFFSET)),
+		})
+	}
+
+	/// Ui amount of `position` with interest, negative for borrows
+	pub fn ui_amount(&self, position: &SpotPosition, decimals: u32) -> f64 {
+		let (interest, sign) = if position.is_borrow { (self.cumulative_borrow_interest, -1.0) } else { (self.cumulative_deposit_interest, 1.0) };
+		let native = position.scaled_balance as f64 * interest as f64 / 10_f64.powi((SPOT_BALANCE_AND_INTEREST_DECIMALS - decimals) as i32);
+		sign * native / 10_f64.powi(decimals as i32)
+	}
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct PerpPosition {
+	/// In BASE_PRECISION, negative for shorts
+	pub base_asset_amount: i64,
+	/// In QUOTE_PRECISION
+	pub quote_asset_amount: i64,
+	pub open_orders: u8,
+	pub market_index: u16,
+}
+
+impl PerpPosition {
+	pub fn is_open(&self) -> bool {
+		self.base_asset_amount != 0 || self.quote_asset_amount != 0 || self.open_orders != 0
+	}
+}
+
+/// The parts of a Drift v2 `User` account the venue needs, decoded from fixed offsets
+#[derive(Clone, Debug, PartialEq)]
+pub struct DriftUser {
+	pub authority: Pubkey,
+	pub delegate: Pubkey,
+	pub spot_positions: Vec<SpotPosition>,
+	pub perp_positions: Vec<PerpPosition>,
+}
+
+fn read<const N: usize>(data: &[u8], offset: usize) -> [u8; N] {
+	data[offset..offset + N].try_into().unwrap()
+}
+
+impl DriftUser {
+	pub fn decode(data: &[u8]) -> MangolResult<Self> {
+		if data.len() != USER_SIZE {
+			return Err(MangolError::SerializationError(format!("Drift user data is {} bytes, expected {}", data.len(), USER_SIZE)));
+		}
+		let spot_positions = (0..MAX_SPOT_POSITIONS).map(|i| {
+			let offset = SPOT_POSITIONS_OFFSET + i * SPOT_POSITION_SIZE;
+			SpotPosition {
+				scaled_balance: u64::from_le_bytes(read(data, offset)),
+				cumulative_deposits: i64::from_le_bytes(read(data, offset + 24)),
+				market_index: u16::from_le_bytes(read(data, offset + 32)),
+				is_borrow: data[offset + 34] == 1,
+			}
+		}).filter(|position| position.scaled_balance != 0).collect();
+		let perp_positions = (0..MAX_PERP_POSITIONS).map(|i| {
+			let offset = PERP_POSITIONS_OFFSET + i * PERP_POSITION_SIZE;
+			PerpPosition {
+				base_asset_amount: i64::from_le_bytes(read(data, offset + 8)),
+				quote_asset_amount: i64::from_le_bytes(read(data, offset + 16)),
+				market_index: u16::from_le_bytes(read(data, offset + 92)),
+				open_orders: data[offset + 94],
+			}
+		}).filter(PerpPosition::is_open).collect();
+		Ok(Self {
+			authority: Pubkey::new_from_array(read(data, 8)),
+			delegate: Pubkey::new_from_array(read(data, 40)),
+			spot_positions,
+			perp_positions,
+		})
+	}
+
+	pub fn perp_position(&self, market_index: u16) -> Option<&PerpPosition> {
+		self.perp_positions.iter().find(|position| position.market_index == market_index)
+	}
+
+	/// Quote collateral with its interest, less quote borrows, plus unrealized perp pnl at `prices`, ui quote
+	pub fn equity(&self, quote_interest: &SpotInterest, markets: &[DriftPerpMarketData], prices: &[f64]) -> f64 {
+		let quote = self.spot_positions.iter()
+			  .filter(|position| position.market_index == QUOTE_SPOT_MARKET_INDEX)
+			  .map(|position| quote_interest.ui_amount(position, QUOTE_DECIMALS))
+			  .sum::<f64>();
+		let unrealized = markets.iter().zip(prices).filter_map(|(market, price)| {
+			let position = self.perp_position(market.market_index)?;
+			Some(position.base_asset_amount as f64 / BASE_PRECISION * price + position.quote_asset_amount as f64 / QUOTE_PRECISION)
+		}).sum::<f64>();
+		quote + unrealized
+	}
+
+	/// Equity left after the maintenance margin of every open perp position, liquidatable below 0
+	pub fn maintenance_health(&self, quote_interest: &SpotInterest, markets: &[DriftPerpMarketData], prices: &[f64]) -> f64 {
+		let margin = markets.iter().zip(prices).filter_map(|(market, price)| {
+			let position = self.perp_position(market.market_index)?;
+			Some((position.base_asset_amount as f64 / BASE_PRECISION * price).abs() * market.margin_ratio_maintenance as f64 / MARGIN_PRECISION)
+		}).sum::<f64>();
+		self.equity(quote_interest, markets, prices) - margin
+	}
+}
+
+pub fn user_pk(program_id: &Pubkey, authority: &Pubkey, sub_account_id: u16) -> Pubkey {
+	Pubkey::find_program_address(&[b"user", authority.as_ref(), &sub_account_id.to_le_bytes()], program_id).0
+}
+
+pub fn state_pk(program_id: &Pubkey) -> Pubkey {
+	Pubkey::find_program_address(&[b"drift_state"], program_id).0
+}
+
+pub fn perp_market_pk(program_id: &Pubkey, market_index: u16) -> Pubkey {
+	Pubkey::find_program_address(&[b"perp_market", &market_index.to_le_bytes()], program_id).0
+}
+
+pub fn spot_market_pk(program_id: &Pubkey, market_index: u16) -> Pubkey {
+	Pubkey::find_program_address(&[b"spot_market", &market_index.to_le_bytes()], program_id).0
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::types::{DriftPerpMarketData, DriftUser, SpotInterest, PERP_POSITIONS_OFFSET, SPOT_MARKET_CUMULATIVE_BORROW_INTEREST_OFFSET, SPOT_MARKET_CUMULATIVE_DEPOSIT_INTEREST_OFFSET, SPOT_POSITIONS_OFFSET, SPOT_POSITION_SIZE, USER_SIZE};
+
+	#[test]
+	fn decodes_positions_and_health() {
+		let mut data = vec![0u8; USER_SIZE];
+		// 1000 USDC deposited, 5% interest accrued on it since
+		data[SPOT_POSITIONS_OFFSET..SPOT_POSITIONS_OFFSET + 8].copy_from_slice(&1_000_000_000_000u64.to_le_bytes());
+		data[SPOT_POSITIONS_OFFSET + 24..SPOT_POSITIONS_OFFSET + 32].copy_from_slice(&1_000_000_000i64.to_le_bytes());
+		// 100 USDC borrowed, 10% interest owed on it
+		let borrow = SPOT_POSITIONS_OFFSET + SPOT_POSITION_SIZE;
+		data[borrow..borrow + 8].copy_from_slice(&100_000_000_000u64.to_le_bytes());
+		data[borrow + 34] = 1;
+		// short 10 SOL opened at 40
+		let perp = PERP_POSITIONS_OFFSET;
+		data[perp + 8..perp + 16].copy_from_slice(&(-10_000_000_000i64).to_le_bytes());
+		data[perp + 16..perp + 24].copy_from_slice(&400_000_000i64.to_le_bytes());
+		data[perp + 92..perp + 94].copy_from_slice(&2u16.to_le_bytes());
+		let user = DriftUser::decode(&data).unwrap();
+		assert_eq!(user.perp_position(2).unwrap().base_asset_amount, -10_000_000_000);
+		let mut spot_market = vec![0u8; SPOT_MARKET_CUMULATIVE_BORROW_INTEREST_OFFSET + 16];
+		spot_market[SPOT_MARKET_CUMULATIVE_DEPOSIT_INTEREST_OFFSET..SPOT_MARKET_CUMULATIVE_DEPOSIT_INTEREST_OFFSET + 16].copy_from_slice(&10_500_000_000u128.to_le_bytes());
+		spot_market[SPOT_MARKET_CUMULATIVE_BORROW_INTEREST_OFFSET..SPOT_MARKET_CUMULATIVE_BORROW_INTEREST_OFFSET + 16].copy_from_slice(&11_000_000_000u128.to_le_bytes());
+		let quote_interest = SpotInterest::decode(&spot_market).unwrap();
+		let markets = vec![DriftPerpMarketData { name: "SOL-PERP".to_string(), market_index: 2, oracle: String::new(), margin_ratio_maintenance: 500 }];
+		assert_eq!(user.equity(&quote_interest, &markets, &[30.0]), 1_040.0);
+		assert_eq!(user.maintenance_health(&quote_interest, &markets, &[30.0]), 1_025.0);
+		assert!(DriftUser::decode(&data[1..]).is_err());
+	}
+}
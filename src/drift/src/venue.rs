@@ -0,0 +1,107 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use mangol_common::errors::MangolResult;
+use mangol_common::venue::{ExecutionVenue, OrderKind, OrderSide, VenueBalance, VenueOrder, VenuePosition};
+
+use crate::client::DriftClient;
+use crate::instructions::{MarketType, OrderParams, OrderTriggerCondition, OrderType, PositionDirection, PostOnlyParam};
+use crate::types::{BASE_PRECISION, PRICE_PRECISION, QUOTE_DECIMALS, QUOTE_PRECISION, QUOTE_SPOT_MARKET_INDEX};
+
+/// Maps a ui VenueOrder onto Drift `OrderParams`, `now_ts` anchors the expiry
+pub fn order_params(order: &VenueOrder, market_index: u16, now_ts: i64) -> OrderParams {
+	OrderParams {
+		order_type: match order.kind {
+			OrderKind::Market => OrderType::Market,
+			_ => OrderType::Limit,
+		},
+		market_type: MarketType::Perp,
+		direction: match order.side {
+			OrderSide::Buy => PositionDirection::Long,
+			OrderSide::Sell => PositionDirection::Short,
+		},
+		user_order_id: 0,
+		base_asset_amount: (order.size * BASE_PRECISION).floor() as u64,
+		price: match order.kind {
+			OrderKind::Market => 0,
+			_ => (order.price * PRICE_PRECISION).round() as u64,
+		},
+		market_index,
+		reduce_only: order.reduce_only,
+		post_only: match order.kind {
+			OrderKind::PostOnly => PostOnlyParam::MustPostOnly,
+			_ => PostOnlyParam::None,
+		},
+		immediate_or_cancel: order.kind == OrderKind::ImmediateOrCancel,
+		max_ts: order.expiry_secs.map(|secs| now_ts + secs as i64),
+		trigger_price: None,
+		trigger_condition: OrderTriggerCondition::Above,
+		oracle_price_offset: None,
+		auction_duration: None,
+		auction_start_price: None,
+		auction_end_price: None,
+	}
+}
+
+impl ExecutionVenue for DriftClient {
+	fn name(&self) -> &str {
+		"drift"
+	}
+
+	fn refresh(&mut self) -> MangolResult<()> {
+		self.update()
+	}
+
+	fn place_order(&self, order: &VenueOrder) -> MangolResult<String> {
+		let market_index = self.market(&order.market)?.market_index;
+		let now_ts = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+		self.place_perp_order(&order_params(order, market_index, now_ts))
+	}
+
+	fn cancel_all(&self, market: &str) -> MangolResult<String> {
+		self.cancel_perp_orders(self.market(market)?.market_index)
+	}
+
+	fn positions(&self) -> MangolResult<Vec<VenuePosition>> {
+		Ok(self.markets.iter().filter_map(|market| {
+			let position = self.user.perp_position(market.market_index)?;
+			Some(VenuePosition {
+				market: market.name.clone(),
+				base_size: position.base_asset_amount as f64 / BASE_PRECISION,
+				quote_position: position.quote_asset_amount as f64 / QUOTE_PRECISION,
+			})
+		}).collect())
+	}
+
+	fn balances(&self) -> MangolResult<Vec<VenueBalance>> {
+		// the quote balance with its interest and borrows, other spot markets as net deposits
+		Ok(self.user.spot_positions.iter().map(|position| VenueBalance {
+			asset: format!("spot-{}", position.market_index),
+			amount: if position.market_index == QUOTE_SPOT_MARKET_INDEX {
+				self.quote_interest.ui_amount(position, QUOTE_DECIMALS)
+			} else {
+				position.cumulative_deposits as f64 / QUOTE_PRECISION
+			},
+		}).collect())
+	}
+
+	fn equity(&self) -> MangolResult<f64> {
+		Ok(DriftClient::equity(self))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use mangol_common::venue::{OrderKind, OrderSide, VenueOrder};
+	use crate::instructions::{OrderType, PositionDirection, PostOnlyParam};
+	use crate::venue::order_params;
+
+	#[test]
+	fn maps_post_only_sell_to_drift_params() {
+		let order = VenueOrder { market: "SOL-PERP".to_string(), side: OrderSide::Sell, price: 40.5, size: 1.25, kind: OrderKind::PostOnly, reduce_only: true, expiry_secs: Some(30) };
+		let params = order_params(&order, 0, 1_000);
+		assert_eq!(params.order_type, OrderType::Limit);
+		assert_eq!(params.direction, PositionDirection::Short);
+		assert_eq!(params.post_only, PostOnlyParam::MustPostOnly);
+		assert_eq!((params.base_asset_amount, params.price, params.max_ts), (1_250_000_000, 40_500_000, Some(1_030)));
+	}
+}
@@ -0,0 +1,45 @@
+//! Mango Markets v3 trading and liquidation toolkit.
+//!
+//! The workspace crates can be used on their own, `prelude` re-exports the stable types
+//! most consumers need to load accounts, place orders and run strategies:
+//!
+//! ```ignore
+//! use mangol::prelude::*;
+//!
+//! fn run(mut strategy: impl Strategy) -> MangolResult<()> {
+//! 	strategy.init()?;
+//! 	strategy.run()
+//! }
+//! ```
+
+pub use mangol_common as common;
+pub use mangol_drift as drift;
+pub use mangol_mango as mango;
+pub use mangol_solana as solana;
+pub use mangol_strategies as strategies;
+
+#[cfg(feature = "tui")]
+pub mod tui;
+
+pub mod prelude {
+	pub use mangol_common::clock::{Clock, SimulatedClock, SystemClock};
+	pub use mangol_common::venue::{ExecutionVenue, OrderKind, OrderSide, VenueBalance, VenueOrder, VenuePosition};
+	pub use mangol_common::errors::{MangolError, MangolResult, SizingError, SolanaError};
+	pub use mangol_mango::book::{BookOrder, OrderBook};
+	pub use mangol_mango::client::{MangoClient, MangoClientApi};
+	pub use mangol_mango::fees::FeeModel;
+	pub use mangol_mango::guards::PriceBands;
+	pub use mangol_mango::registry::MarketRegistry;
+	pub use mangol_mango::sizing::{OrderSizer, Rounding, SizingPolicy};
+	pub use mangol_mango::types::{
+		HealthType, MangoAccount, MangoCache, MangoGroup, OrderType, PerpAccount, PerpMarketData, PerpMarketInfo, Side,
+	};
+	pub use mangol_solana::connection::SolanaConnection;
+	pub use mangol_solana::keystore::KeyStore;
+	pub use mangol_strategies::fib_trader::{EntryImpactLimit, FibStrat, PriceSide, ReferencePrice, TradeAmount};
+	pub use mangol_strategies::kill_switch::KillSwitch;
+	pub use mangol_strategies::dead_man::DeadMansSwitch;
+	pub use mangol_strategies::risk::{RiskLimits, RiskManager};
+	pub use mangol_strategies::schedule::TradingSchedule;
+	pub use mangol_strategies::strategy::Strategy;
+}
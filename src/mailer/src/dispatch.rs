@@ -0,0 +1,162 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DispatchConfig {
+	/// Messages waiting to be sent, the oldest is dropped to make room past it
+	pub capacity: usize,
+	/// Attempts per message before giving up on it
+	pub max_attempts: u32,
+	/// Wait before the first retry, doubled on every following one
+	pub retry_backoff: Duration,
+}
+
+impl Default for DispatchConfig {
+	fn default() -> Self {
+		Self { capacity: 256, max_attempts: 5, retry_backoff: Duration::from_secs(2) }
+	}
+}
+
+#[derive(Debug, Default)]
+struct Queue {
+	messages: VecDeque<String>,
+	/// Set while the sender thread holds a message, flushing waits on it too
+	in_flight: bool,
+	dropped: u64,
+}
+
+/// Hands messages to a dedicated sender thread so callers never wait on the provider.
+/// Failed sends are retried with backoff while new messages queue up behind them, and
+/// once the queue is full the oldest message is dropped for the newest
+#[derive(Clone, Debug)]
+pub struct Dispatcher {
+	pub config: DispatchConfig,
+	queue: Arc<(Mutex<Queue>, Condvar)>,
+}
+
+impl Dispatcher {
+	/// A queue without a sender, `start` attaches one
+	pub fn new(config: DispatchConfig) -> Self {
+		Self { config, queue: Arc::new((Mutex::new(Queue::default()), Condvar::new())) }
+	}
+
+	pub fn start<F: Fn(&str) -> Result<(), String> + Send + 'static>(config: DispatchConfig, send: F) -> Self {
+		let dispatcher = Self::new(config);
+		let sender = dispatcher.clone();
+		std::thread::spawn(move || sender.run(send));
+		dispatcher
+	}
+
+	/// Queues `message`, false when the oldest queued message was dropped for it
+	pub fn enqueue(&self, message: String) -> bool {
+		let (lock, condvar) = &*self.queue;
+		let mut queue = lock.lock().unwrap();
+		let mut kept_all = true;
+		while queue.messages.len() >= self.config.capacity.max(1) {
+			queue.messages.pop_front();
+			queue.dropped += 1;
+			kept_all = false;
+		}
+		queue.messages.push_back(message);
+		condvar.notify_all();
+		kept_all
+	}
+
+	pub fn pending(&self) -> usize {
+		self.queue.0.lock().unwrap().messages.len()
+	}
+
+	/// Messages dropped under backpressure since the start
+	pub fn dropped(&self) -> u64 {
+		self.queue.0.lock().unwrap().dropped
+	}
+
+	/// Waits up to `timeout` for everything queued to be sent or given up on, returns whether it was
+	pub fn flush(&self, timeout: Duration) -> bool {
+		let deadline = Instant::now() + timeout;
+		let (lock, condvar) = &*self.queue;
+		let mut queue = lock.lock().unwrap();
+		while !queue.messages.is_empty() || queue.in_flight {
+			let now = Instant::now();
+			if now >= deadline {
+				return false;
+			}
+			queue = condvar.wait_timeout(queue, deadline - now).unwrap().0;
+		}
+		true
+	}
+
+	fn next(&self) -> String {
+		let (lock, condvar) = &*self.queue;
+		let mut queue = lock.lock().unwrap();
+		loop {
+			if let Some(message) = queue.messages.pop_front() {
+				queue.in_flight = true;
+				return message;
+			}
+			queue = condvar.wait(queue).unwrap();
+		}
+	}
+
+	fn sent(&self) {
+		let (lock, condvar) = &*self.queue;
+		lock.lock().unwrap().in_flight = false;
+		condvar.notify_all();
+	}
+
+	fn run<F: Fn(&str) -> Result<(), String>>(&self, send: F) {
+		loop {
+			let message = self.next();
+			let mut backoff = self.config.retry_backoff;
+			for attempt in 1..=self.config.max_attempts.max(1) {
+				match send(&message) {
+					Ok(()) => break,
+					Err(e) if attempt == self.config.max_attempts.max(1) => eprintln!("[-] Notification dropped after {} attempts {}", attempt, e),
+					Err(e) => {
+						eprintln!("[-] Notification attempt {} failed, retrying in {:?} {}", attempt, backoff, e);
+						std::thread::sleep(backoff);
+						backoff *= 2;
+					}
+				}
+			}
+			self.sent();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::{Arc, Mutex};
+	use std::time::Duration;
+	use crate::dispatch::{DispatchConfig, Dispatcher};
+
+	#[test]
+	fn drops_oldest_and_retries_in_the_background() {
+		let queued = Dispatcher::new(DispatchConfig { capacity: 2, ..DispatchConfig::default() });
+		assert!(queued.enqueue("first".to_string()));
+		assert!(queued.enqueue("second".to_string()));
+		assert!(!queued.enqueue("third".to_string()));
+		assert_eq!((queued.pending(), queued.dropped()), (2, 1));
+
+		let sent = Arc::new(Mutex::new(vec![]));
+		let attempts = Arc::new(Mutex::new(0));
+		let (sent_by_thread, attempts_by_thread) = (sent.clone(), attempts.clone());
+		let config = DispatchConfig { capacity: 8, max_attempts: 3, retry_backoff: Duration::from_millis(1) };
+		let dispatcher = Dispatcher::start(config, move |message| {
+			let mut attempts = attempts_by_thread.lock().unwrap();
+			*attempts += 1;
+			// the provider fails the first attempt only
+			if *attempts == 1 {
+				return Err("timed out".to_string());
+			}
+			sent_by_thread.lock().unwrap().push(message.to_string());
+			Ok(())
+		});
+		dispatcher.enqueue("filled".to_string());
+		dispatcher.enqueue("reset".to_string());
+		assert!(dispatcher.flush(Duration::from_secs(5)));
+		assert_eq!(*sent.lock().unwrap(), vec!["filled".to_string(), "reset".to_string()]);
+		assert_eq!(*attempts.lock().unwrap(), 3);
+	}
+}
@@ -1,8 +1,64 @@
+pub mod notification;
+pub mod shipping;
+pub mod dispatch;
 
+use std::time::Duration;
+
+#[cfg(feature = "telegram")]
+use once_cell::sync::Lazy;
+
+#[cfg(feature = "telegram")]
+use crate::dispatch::{DispatchConfig, Dispatcher};
+
+#[cfg(feature = "telegram")]
 const BOT_TOKEN: &str = "5542140231:AAHBAyDnQbK2Q44GoWaYDtxQaFogF0qMJA0";
+#[cfg(feature = "telegram")]
+const CHAT_ID: i64 = 359883518;
+
+#[cfg(feature = "telegram")]
+static DISPATCHER: Lazy<Dispatcher> = Lazy::new(|| Dispatcher::start(DispatchConfig::default(), send_telegram));
+
+/// Sends `content` to the bot's chat right away, erroring on anything telegram didn't accept
+#[cfg(feature = "telegram")]
+pub fn send_telegram(content: &str) -> Result<(), String> {
+	let response = reqwest::blocking::Client::new()
+		  .post(format!("https://api.telegram.org/bot{}/sendMessage", BOT_TOKEN))
+		  .json(&serde_json::json!({ "chat_id": CHAT_ID, "text": content }))
+		  .timeout(Duration::from_secs(10))
+		  .send()
+		  .map_err(|e| e.to_string())?;
+	if !response.status().is_success() {
+		return Err(format!("telegram answered {}", response.status()));
+	}
+	Ok(())
+}
+
+/// Queues `content` for the dispatcher thread, false when an older message was dropped for it
+#[cfg(feature = "telegram")]
+pub fn send_text_with_content(content: String) -> bool {
+	DISPATCHER.enqueue(content)
+}
+
+#[cfg(not(feature = "telegram"))]
 pub fn send_text_with_content(content: String) -> bool {
-   telegram_notifyrs::send_message(content, BOT_TOKEN, 359883518);
-    true
+	println!("[notification] {}", content);
+	true
+}
+
+/// Waits up to `timeout` for queued notifications to go out, before exiting the process
+#[cfg(feature = "telegram")]
+pub fn flush(timeout: Duration) -> bool {
+	DISPATCHER.flush(timeout)
+}
+
+#[cfg(not(feature = "telegram"))]
+pub fn flush(_timeout: Duration) -> bool {
+	true
+}
+
+/// Sends `notification` with the default templates
+pub fn notify(notification: &notification::Notification) -> bool {
+	notification::Notifier::default().send(notification)
 }
 
 #[cfg(test)]
@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::shipping::LogShipper;
+
+/// How amounts are written out. Callers convert to UI amounts and registry market names before
+/// building a notification, this only fixes precision and the quote symbol
+#[derive(Clone, Debug, PartialEq)]
+pub struct Units {
+	pub quote_symbol: String,
+	pub price_decimals: usize,
+	pub size_decimals: usize,
+}
+
+impl Default for Units {
+	fn default() -> Self {
+		Self { quote_symbol: "USDC".to_string(), price_decimals: 4, size_decimals: 4 }
+	}
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+	Text(String),
+	/// UI quote per UI base
+	Price(f64),
+	/// UI base amount
+	Size(f64),
+	/// UI quote amount
+	Quote(f64),
+}
+
+impl Value {
+	fn display(&self, units: &Units) -> String {
+		match self {
+			Value::Text(text) => text.clone(),
+			Value::Price(price) => format!("{:.*} {}", units.price_decimals, price, units.quote_symbol),
+			Value::Size(size) => format!("{:.*}", units.size_decimals, size),
+			Value::Quote(amount) => format!("{:.2} {}", amount, units.quote_symbol),
+		}
+	}
+
+	/// Unformatted, for the machine readable line
+	fn raw(&self) -> String {
+		match self {
+			Value::Text(text) => format!("{:?}", text),
+			Value::Price(value) | Value::Size(value) | Value::Quote(value) => value.to_string(),
+		}
+	}
+}
+
+/// Everything the bots alert on, rendered through `Templates` so the same event always reads the same
+#[derive(Clone, Debug, PartialEq)]
+pub enum Notification {
+	OrderFilled { market: String, side: String, size: f64, price: f64 },
+	/// A fill moved the position against the order's intent, e.g. a take profit that added exposure
+	PartialFillReversed { market: String, position_before: f64, position_after: f64 },
+	PositionReset { market: String, position: f64 },
+	Liquidatable { account: String, init_health: f64, maint_health: f64, equity: f64 },
+	/// Inventory from liquidating `liqee` was disposed of, `realized_profit` in UI quote
+	LiquidationUnwound { liqee: String, realized_profit: f64, unwound: String },
+	/// The liquidator took over `base_transfer` base lots of `liqee`'s perp position, `expected_profit` in UI quote
+	Liquidated { liqee: String, market_index: u32, base_transfer: i64, expected_profit: f64, signature: String },
+	CircuitBreakerTripped { market: String, breaker: String, reason: String },
+	CircuitBreakerCleared { market: String, breaker: String },
+	PerformanceReport { market: String, summary: String },
+	RiskParametersChanged { changes: Vec<String> },
+	/// A spot borrow was repaid, `repaid` in UI token and `daily_interest` it cost in UI quote
+	BorrowRepaid { token: String, repaid: f64, daily_interest: f64 },
+	/// The strategy now signs with `new_signer`, on request or after `old_signer` stopped verifying
+	SignerRotated { market: String, old_signer: String, new_signer: String, reason: String },
+	/// A loop stopped beating for `silent_secs`, `action` is what the watchdog did about it
+	SubsystemStalled { subsystem: String, silent_secs: u64, action: String },
+	SubsystemRecovered { subsystem: String },
+	/// The strategy's ledger and the on chain perp account disagree past tolerance, UI units
+	LedgerDiverged { market: String, ledger_position: f64, chain_position: f64, ledger_quote: f64, chain_quote: f64, action: String },
+	LedgerReconciled { market: String },
+	/// An order failed to land `attempts` times and is no longer sent
+	OrderQuarantined { market: String, order: String, attempts: u32, error: String },
+	/// `instance` took the trading lease after `previous` stopped renewing it
+	FailedOver { market: String, instance: String, previous: String },
+	/// `instance` found the lease held by `holder` and stopped trading
+	LeadershipLost { market: String, instance: String, holder: String },
+}
+
+impl Notification {
+	pub fn kind(&self) -> &'static str {
+		match self {
+			Notification::OrderFilled { .. } => "order_filled",
+			Notification::PartialFillReversed { .. } => "partial_fill_reversed",
+			Notification::PositionReset { .. } => "position_reset",
+			Notification::Liquidatable { .. } => "liquidatable",
+			Notification::LiquidationUnwound { .. } => "liquidation_unwound",
+			Notification::Liquidated { .. } => "liquidated",
+			Notification::CircuitBreakerTripped { .. } => "circuit_breaker_tripped",
+			Notification::CircuitBreakerCleared { .. } => "circuit_breaker_cleared",
+			Notification::PerformanceReport { .. } => "performance_report",
+			Notification::RiskParametersChanged { .. } => "risk_parameters_changed",
+			Notification::BorrowRepaid { .. } => "borrow_repaid",
+			Notification::SignerRotated { .. } => "signer_rotated",
+			Notification::SubsystemStalled { .. } => "subsystem_stalled",
+			Notification::SubsystemRecovered { .. } => "subsystem_recovered",
+			Notification::LedgerDiverged { .. } => "ledger_diverged",
+			Notification::LedgerReconciled { .. } => "ledger_reconciled",
+			Notification::OrderQuarantined { .. } => "order_quarantined",
+			Notification::FailedOver { .. } => "failed_over",
+			Notification::LeadershipLost { .. } => "leadership_lost",
+		}
+	}
+
+	pub fn fields(&self) -> Vec<(&'static str, Value)> {
+		let text = |value: &String| Value::Text(value.clone());
+		match self {
+			Notification::OrderFilled { market, side, size, price } => vec![("market", text(market)), ("side", text(side)), ("size", Value::Size(*size)), ("price", Value::Price(*price))],
+			Notification::PartialFillReversed { market, position_before, position_after } => vec![("market", text(market)), ("position_before", Value::Size(*position_before)), ("position_after", Value::Size(*position_after))],
+			Notification::PositionReset { market, position } => vec![("market", text(market)), ("position", Value::Size(*position))],
+			Notification::Liquidatable { account, init_health, maint_health, equity } => vec![("account", text(account)), ("init_health", Value::Quote(*init_health)), ("maint_health", Value::Quote(*maint_health)), ("equity", Value::Quote(*equity))],
+			Notification::LiquidationUnwound { liqee, realized_profit, unwound } => vec![("liqee", text(liqee)), ("realized_profit", Value::Quote(*realized_profit)), ("unwound", text(unwound))],
+			Notification::Liquidated { liqee, market_index, base_transfer, expected_profit, signature } => vec![("liqee", text(liqee)), ("market_index", Value::Text(market_index.to_string())), ("base_transfer", Value::Text(base_transfer.to_string())), ("expected_profit", Value::Quote(*expected_profit)), ("signature", text(signature))],
+			Notification::CircuitBreakerTripped { market, breaker, reason } => vec![("market", text(market)), ("breaker", text(breaker)), ("reason", text(reason))],
+			Notification::CircuitBreakerCleared { market, breaker } => vec![("market", text(market)), ("breaker", text(breaker))],
+			Notification::PerformanceReport { market, summary } => vec![("market", text(market)), ("summary", text(summary))],
+			Notification::RiskParametersChanged { changes } => vec![("changes", Value::Text(changes.join("\n")))],
+			Notification::BorrowRepaid { token, repaid, daily_interest } => vec![("token", text(token)), ("repaid", Value::Size(*repaid)), ("daily_interest", Value::Quote(*daily_interest))],
+			Notification::SignerRotated { market, old_signer, new_signer, reason } => vec![("market", text(market)), ("old_signer", text(old_signer)), ("new_signer", text(new_signer)), ("reason", text(reason))],
+			Notification::SubsystemStalled { subsystem, silent_secs, action } => vec![("subsystem", text(subsystem)), ("silent_secs", Value::Text(silent_secs.to_string())), ("action", text(action))],
+			Notification::SubsystemRecovered { subsystem } => vec![("subsystem", text(subsystem))],
+			Notification::LedgerDiverged { market, ledger_position, chain_position, ledger_quote, chain_quote, action } => vec![("market", text(market)), ("ledger_position", Value::Size(*ledger_position)), ("chain_position", Value::Size(*chain_position)), ("ledger_quote", Value::Quote(*ledger_quote)), ("chain_quote", Value::Quote(*chain_quote)), ("action", text(action))],
+			Notification::LedgerReconciled { market } => vec![("market", text(market))],
+			Notification::OrderQuarantined { market, order, attempts, error } => vec![("market", text(market)), ("order", text(order)), ("attempts", Value::Text(attempts.to_string())), ("error", text(error))],
+			Notification::FailedOver { market, instance, previous } => vec![("market", text(market)), ("instance", text(instance)), ("previous", text(previous))],
+			Notification::LeadershipLost { market, instance, holder } => vec![("market", text(market)), ("instance", text(instance)), ("holder", text(holder))],
+		}
+	}
+}
+
+/// Message template per notification kind, `{field}` is replaced by the field in `units`
+#[derive(Clone, Debug)]
+pub struct Templates {
+	pub templates: HashMap<String, String>,
+	pub units: Units,
+}
+
+impl Default for Templates {
+	fn default() -> Self {
+		let templates = [
+			("order_filled", "{market} {side} filled {size} at {price}"),
+			("partial_fill_reversed", "{market} fill moved the position the wrong way, {position_before} -> {position_after}"),
+			("position_reset", "{market} position of {position} reset to neutral"),
+			("liquidatable", "Account {account} is liquidatable, init health {init_health} maint health {maint_health} equity {equity}"),
+			("liquidation_unwound", "Liquidation of {liqee} unwound, realized {realized_profit}\n{unwound}"),
+			("liquidated", "Liquidated {base_transfer} base lots of market {market_index} from {liqee}, expected profit {expected_profit}\n{signature}"),
+			("circuit_breaker_tripped", "{market} {breaker} tripped, pausing: {reason}"),
+			("circuit_breaker_cleared", "{market} {breaker} cleared, resuming"),
+			("performance_report", "{market} {summary}"),
+			("risk_parameters_changed", "Mango group risk parameters changed\n{changes}"),
+			("borrow_repaid", "Repaid {repaid} of borrowed {token}, it cost {daily_interest} a day"),
+			("signer_rotated", "{market} signer rotated from {old_signer} to {new_signer}: {reason}"),
+			("subsystem_stalled", "{subsystem} has not made progress in {silent_secs}s, {action}"),
+			("subsystem_recovered", "{subsystem} is making progress again"),
+			("ledger_diverged", "{market} ledger diverged from chain, position {ledger_position} vs {chain_position}, quote {ledger_quote} vs {chain_quote}, {action}"),
+			("ledger_reconciled", "{market} ledger matches the chain again"),
+			("order_quarantined", "{market} {order} quarantined after {attempts} failed attempts, {error}"),
+			("failed_over", "{market} failed over to {instance}, {previous} stopped renewing the lease"),
+			("leadership_lost", "{market} {instance} lost the lease to {holder}, standing by"),
+		];
+		Self {
+			templates: templates.iter().map(|(kind, template)| (kind.to_string(), template.to_string())).collect(),
+			units: Units::default(),
+		}
+	}
+}
+
+impl Templates {
+	/// Defaults overridden by a file of `kind = template` lines, `#` starts a comment and `\n` is a newline
+	pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+		let mut templates = Self::default();
+		for line in std::fs::read_to_string(path)?.lines() {
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+			if let Some((kind, template)) = line.split_once('=') {
+				templates.templates.insert(kind.trim().to_string(), template.trim().replace("\\n", "\n"));
+			}
+		}
+		Ok(templates)
+	}
+
+	pub fn with_units(mut self, units: Units) -> Self {
+		self.units = units;
+		self
+	}
+
+	/// The templated text followed by a `kind field=value` line with unformatted values
+	pub fn render(&self, notification: &Notification) -> String {
+		let fields = notification.fields();
+		let mut text = self.templates.get(notification.kind()).cloned().unwrap_or_else(|| format!("{{{}}}", notification.kind()));
+		for (name, value) in &fields {
+			text = text.replace(&format!("{{{}}}", name), &value.display(&self.units));
+		}
+		let machine: Vec<String> = fields.iter().map(|(name, value)| format!("{}={}", name, value.raw())).collect();
+		format!("{}\n{} {}", text, notification.kind(), machine.join(" "))
+	}
+}
+
+/// Renders and sends notifications
+#[derive(Clone, Debug, Default)]
+pub struct Notifier {
+	pub templates: Templates,
+	/// Also ships every notification as a log line
+	pub shipper: Option<LogShipper>,
+}
+
+impl Notifier {
+	pub fn new(templates: Templates) -> Self {
+		Self { templates, shipper: None }
+	}
+
+	pub fn with_shipper(mut self, shipper: LogShipper) -> Self {
+		self.shipper = Some(shipper);
+		self
+	}
+
+	/// Queues the rendered notification, never waits on the provider
+	pub fn send(&self, notification: &Notification) -> bool {
+		let text = self.templates.render(notification);
+		if let Some(shipper) = &self.shipper {
+			shipper.log("notice", &text);
+		}
+		crate::send_text_with_content(text)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::notification::{Notification, Templates, Units};
+
+	#[test]
+	fn renders_template_and_machine_line() {
+		let fill = Notification::OrderFilled { market: "SOL-PERP".to_string(), side: "sell".to_string(), size: 0.37, price: 40.5 };
+		let templates = Templates::default().with_units(Units { quote_symbol: "USDC".to_string(), price_decimals: 2, size_decimals: 2 });
+		assert_eq!(templates.render(&fill), "SOL-PERP sell filled 0.37 at 40.50 USDC\norder_filled market=\"SOL-PERP\" side=\"sell\" size=0.37 price=40.5");
+	}
+}
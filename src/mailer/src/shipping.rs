@@ -0,0 +1,240 @@
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+#[cfg(feature = "metrics")]
+use std::sync::mpsc::RecvTimeoutError;
+#[cfg(feature = "metrics")]
+use std::time::Instant;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+
+/// Body the batches are pushed as
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SinkFormat {
+	/// Loki's `/loki/api/v1/push`, one stream per label set and level
+	Loki,
+	/// `{"labels": {..}, "records": [..]}` for any https endpoint that takes json
+	Json,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShippingConfig {
+	pub url: String,
+	pub format: SinkFormat,
+	/// Attached to every record, e.g. host and bot so instances can be told apart once aggregated
+	pub labels: Vec<(String, String)>,
+	pub batch_size: usize,
+	pub flush_interval: Duration,
+	/// Records kept while the sink is unreachable, the oldest are dropped past it
+	pub max_buffered: usize,
+}
+
+impl ShippingConfig {
+	pub fn new(url: &str, format: SinkFormat) -> Self {
+		Self {
+			url: url.to_string(),
+			format,
+			labels: vec![],
+			batch_size: 100,
+			flush_interval: Duration::from_secs(5),
+			max_buffered: 10_000,
+		}
+	}
+
+	pub fn with_label(mut self, name: &str, value: &str) -> Self {
+		self.labels.push((name.to_string(), value.to_string()));
+		self
+	}
+
+	pub fn with_batch_size(mut self, batch_size: usize, flush_interval: Duration) -> Self {
+		self.batch_size = batch_size.max(1);
+		self.flush_interval = flush_interval;
+		self
+	}
+
+	/// MANGOL_LOG_SHIP_URL enables shipping, MANGOL_LOG_SHIP_FORMAT is `loki` (default) or `json` and
+	/// MANGOL_LOG_SHIP_LABELS adds `name=value` pairs separated by commas
+	pub fn from_env() -> Option<Self> {
+		let url = std::env::var("MANGOL_LOG_SHIP_URL").ok()?;
+		let format = match std::env::var("MANGOL_LOG_SHIP_FORMAT").as_deref() {
+			Ok("json") => SinkFormat::Json,
+			_ => SinkFormat::Loki
+		};
+		let mut config = Self::new(&url, format);
+		for label in std::env::var("MANGOL_LOG_SHIP_LABELS").unwrap_or_default().split(',') {
+			if let Some((name, value)) = label.split_once('=') {
+				config = config.with_label(name.trim(), value.trim());
+			}
+		}
+		Some(config)
+	}
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Record {
+	Log { timestamp_ns: u128, level: String, message: String },
+	Metric { timestamp_ns: u128, name: String, value: f64 },
+}
+
+impl Record {
+	fn timestamp_ns(&self) -> u128 {
+		match self {
+			Record::Log { timestamp_ns, .. } | Record::Metric { timestamp_ns, .. } => *timestamp_ns,
+		}
+	}
+
+	fn level(&self) -> &str {
+		match self {
+			Record::Log { level, .. } => level,
+			Record::Metric { .. } => "metric",
+		}
+	}
+
+	/// The line Loki stores, metrics as `metric name=value` so LogQL can unwrap them
+	fn line(&self) -> String {
+		match self {
+			Record::Log { message, .. } => message.clone(),
+			Record::Metric { name, value, .. } => format!("metric name={} value={}", name, value),
+		}
+	}
+}
+
+fn now_ns() -> u128 {
+	SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos()
+}
+
+/// Request body of one batch
+pub fn batch_body(config: &ShippingConfig, records: &[Record]) -> Value {
+	let labels: serde_json::Map<String, Value> = config.labels.iter().map(|(name, value)| (name.clone(), json!(value))).collect();
+	match config.format {
+		SinkFormat::Loki => {
+			let mut levels: Vec<&str> = records.iter().map(|record| record.level()).collect();
+			levels.sort_unstable();
+			levels.dedup();
+			let streams: Vec<Value> = levels.iter().map(|level| {
+				let mut stream = labels.clone();
+				stream.insert("level".to_string(), json!(level));
+				let values: Vec<Value> = records.iter()
+					  .filter(|record| record.level() == *level)
+					  .map(|record| json!([record.timestamp_ns().to_string(), record.line()]))
+					  .collect();
+				json!({ "stream": stream, "values": values })
+			}).collect();
+			json!({ "streams": streams })
+		}
+		SinkFormat::Json => {
+			let records: Vec<Value> = records.iter().map(|record| match record {
+				Record::Log { timestamp_ns, level, message } => json!({ "type": "log", "timestamp_ns": timestamp_ns.to_string(), "level": level, "message": message }),
+				Record::Metric { timestamp_ns, name, value } => json!({ "type": "metric", "timestamp_ns": timestamp_ns.to_string(), "name": name, "value": value }),
+			}).collect();
+			json!({ "labels": labels, "records": records })
+		}
+	}
+}
+
+/// Batches log lines and metrics on a background thread and pushes them to a remote sink, so bots
+/// on different hosts end up in one place. Never blocks the caller, records are dropped while the
+/// queue is full
+#[derive(Clone, Debug)]
+pub struct LogShipper {
+	sender: SyncSender<Record>,
+}
+
+impl LogShipper {
+	pub fn start(config: ShippingConfig) -> Self {
+		let (sender, receiver) = sync_channel(config.max_buffered);
+		std::thread::spawn(move || ship(config, receiver));
+		Self { sender }
+	}
+
+	pub fn log(&self, level: &str, message: &str) {
+		self.push(Record::Log { timestamp_ns: now_ns(), level: level.to_string(), message: message.to_string() });
+	}
+
+	pub fn metric(&self, name: &str, value: f64) {
+		if value.is_finite() {
+			self.push(Record::Metric { timestamp_ns: now_ns(), name: name.to_string(), value });
+		}
+	}
+
+	fn push(&self, record: Record) {
+		if let Err(TrySendError::Full(_)) = self.sender.try_send(record) {
+			eprintln!("[-] Log shipping queue full, dropping record");
+		}
+	}
+}
+
+#[cfg(not(feature = "metrics"))]
+fn ship(config: ShippingConfig, receiver: Receiver<Record>) {
+	eprintln!("[-] Built without the metrics feature, records for {} are discarded", config.url);
+	for _ in receiver {}
+}
+
+#[cfg(feature = "metrics")]
+fn ship(config: ShippingConfig, receiver: Receiver<Record>) {
+	let client = reqwest::blocking::Client::new();
+	let mut buffered: Vec<Record> = vec![];
+	let mut last_flush = Instant::now();
+	loop {
+		let disconnected = match receiver.recv_timeout(config.flush_interval) {
+			Ok(record) => {
+				buffered.push(record);
+				false
+			}
+			Err(RecvTimeoutError::Timeout) => false,
+			Err(RecvTimeoutError::Disconnected) => true,
+		};
+		if buffered.len() < config.batch_size && last_flush.elapsed() < config.flush_interval && !disconnected {
+			continue;
+		}
+		last_flush = Instant::now();
+		while !buffered.is_empty() {
+			let batch = buffered.len().min(config.batch_size);
+			match client.post(&config.url).json(&batch_body(&config, &buffered[..batch])).send() {
+				Ok(response) if response.status().is_success() => {
+					buffered.drain(..batch);
+				}
+				Ok(response) => {
+					eprintln!("[-] Log shipping to {} rejected {}", config.url, response.status());
+					break;
+				}
+				Err(e) => {
+					eprintln!("[-] Log shipping to {} failed {:?}", config.url, e);
+					break;
+				}
+			}
+		}
+		// keep what failed for the next flush, without growing past what the queue holds
+		if buffered.len() > config.max_buffered {
+			let dropped = buffered.len() - config.max_buffered;
+			buffered.drain(..dropped);
+		}
+		if disconnected {
+			return;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+	use crate::shipping::{batch_body, Record, ShippingConfig, SinkFormat};
+
+	#[test]
+	fn builds_loki_and_json_batches() {
+		let records = vec![
+			Record::Log { timestamp_ns: 1, level: "info".to_string(), message: "placed order".to_string() },
+			Record::Metric { timestamp_ns: 2, name: "equity".to_string(), value: 12.5 },
+			Record::Log { timestamp_ns: 3, level: "info".to_string(), message: "filled".to_string() },
+		];
+		let config = ShippingConfig::new("http://loki:3100/loki/api/v1/push", SinkFormat::Loki).with_label("host", "a");
+		assert_eq!(batch_body(&config, &records), json!({ "streams": [
+			{ "stream": { "host": "a", "level": "info" }, "values": [["1", "placed order"], ["3", "filled"]] },
+			{ "stream": { "host": "a", "level": "metric" }, "values": [["2", "metric name=equity value=12.5"]] },
+		]}));
+
+		let config = ShippingConfig { format: SinkFormat::Json, ..config };
+		let body = batch_body(&config, &records);
+		assert_eq!(body["labels"], json!({ "host": "a" }));
+		assert_eq!(body["records"][1], json!({ "type": "metric", "timestamp_ns": "2", "name": "equity", "value": 12.5 }));
+	}
+}
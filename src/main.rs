@@ -1,38 +1,476 @@
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use std::path::PathBuf;
 use solana_sdk::pubkey::Pubkey;
-use mangol_mango::types::{MangoAccount, MangoCache, MangoGroup, PerpMarketData};
+use mangol_mango::registry::MarketRegistry;
+use mangol_mango::types::{HealthType, MangoAccount, MangoCache, MangoGroup, QUOTE_INDEX};
 use mangol_solana::connection::SolanaConnection;
-use mangol_common::errors::MangolResult;
-use solana_sdk::signature::Keypair;
-use mangol_mango::client::MangoClient;
-use mangol_strategies::fib_trader::{FibStrat, PriceSide};
+use mangol_solana::keystore::KeyStore;
+use mangol_solana::network::NetworkMonitor;
+use mangol_solana::audit::AuditLog;
+use mangol_solana::endpoints::EndpointConfig;
+use mangol_solana::cluster_time::ClusterClock;
+use mangol_solana::payer_lock::FeePayerLock;
+use mangol_solana::disk_cache::DiskCache;
+use mangol_common::clock::{Clock, SystemClock};
+use mangol_common::errors::{MangolError, MangolResult};
+use solana_sdk::signature::{Keypair, Signer};
+use mangol_mango::client::{EventConsumption, MangoClient};
+use mangol_mango::accounts::{AccountManager, AccountsConfig};
+use mangol_mango::snapshot::{diff_snapshots, GroupSnapshot};
+use mangol_strategies::fib_trader::{EntryImpactLimit, FibParams, FibStrat, PriceSide, ReferencePrice, TradeAmount, FIB_STRATEGY_NAME};
+use mangol_strategies::kill_switch::KillSwitch;
+use mangol_strategies::borrow_repay::{BorrowRepayer, BORROW_REPAYER_NAME};
+use mangol_strategies::mngo_maintenance::{MngoDepositMaintainer, MNGO_MAINTAINER_NAME};
+use mangol_strategies::pnl_settlement::{PnlSettler, PNL_SETTLER_NAME};
+use mangol_strategies::watchdog::{Heartbeats, Watchdog};
+use mangol_strategies::signer_rotation::SignerRotation;
+use mangol_strategies::optimizer::{optimize, price_series, BacktestMarket, FibCandidate, ParameterGrid, WalkForward};
+use mangol_strategies::shadow::ShadowRun;
+use mangol_strategies::risk_of_ruin::RuinConfig;
+use mangol_strategies::market_data::load_snapshots;
+use mangol_strategies::replay::SessionRecorder;
+use mangol_strategies::accounting::CostBasis;
+use mangol_strategies::halt::HaltDetector;
+use mangol_strategies::reconcile::ReconciliationAlarm;
+use mangol_strategies::activity::{DeadMarketFilter, MarketStats, MarketStatsTracker};
+use mangol_strategies::liquidity::LiquidityHistory;
+use mangol_strategies::risk::{parse_correlation_groups, RiskManager};
+use mangol_strategies::dashboard::StateBroadcaster;
+use mangol_strategies::bus::EventBus;
+use mangol_strategies::control_api::ControlApi;
+use mangol_strategies::explain::ExplanationLog;
+use mangol_mango::profiles::{GroupProfiles, DEFAULT_PROFILE};
+use mangol_mango::incentives::IncentivePlacer;
+use mangol_mango::history::{AccountHistory, ArchivalRpc, FallbackHistory, HistoricalState, SnapshotDir, SnapshotProvider, SnapshotRecorder};
+use mangol_mango::health::{decode_mango_account, decode_mango_group};
+use mangol_mango::guards::SelfTradePolicy;
+use mangol_strategies::quarantine::{QuarantineRelease, RetryBudget};
+use mangol_strategies::leader::{FileLeaseStore, LeaderElection};
+use mangol_mailer::notification::{Notification, Notifier, Templates};
+use mangol_mailer::shipping::{LogShipper, ShippingConfig};
+use mangol_strategies::dead_man::DeadMansSwitch;
+use mangol_strategies::expiry::ExpiryManager;
+use mangol_strategies::timing::RoundTiming;
+use mangol_strategies::position_transfer::PositionExport;
+use mangol_strategies::preflight::{abort_on_failures, check_fib_config, check_signer, Preflight};
+use mangol_strategies::schedule::TradingSchedule;
+use mangol_strategies::strategy::Strategy;
 
 fn main() -> MangolResult<()> {
-	
+	let result = run();
+	// whatever run returned with, error or not, send the notifications still queued
+	mangol_mailer::flush(Duration::from_secs(10));
+	result
+}
+
+fn run() -> MangolResult<()> {
+	let args: Vec<String> = std::env::args().collect();
+	// watches already running bots, needs no rpc or keys
+	#[cfg(feature = "tui")]
+	if args.get(1).map(|arg| arg.as_str()) == Some("tui") {
+		return mangol::tui::run(&args[2..]);
+	}
+	// backtests fib parameter sets on a SessionRecorder directory, needs no rpc either
+	if args.get(1).map(|arg| arg.as_str()) == Some("optimize") {
+		return run_optimize_command(&args[2..]);
+	}
+	// `rotate-signer [keystore]` hands the running trader a new key, or switches it to its backup
+	if args.get(1).map(|arg| arg.as_str()) == Some("rotate-signer") {
+		SignerRotation::new(FIB_STRATEGY_NAME, PathBuf::from(".")).request(args.get(2).map(|keystore| keystore.as_str()))?;
+		println!("Rotation requested, the trader switches signers on its next round");
+		return Ok(());
+	}
 	
 	/*
 	Fib trader
 	 */
-	let mango_program = Pubkey::from_str("mv3ekLzLbnVPNxjSKvqBpU3ZeZXPQdEC3bp5MDEBG68").unwrap();
-	let mango_account = Pubkey::from_str("CdYzrgPCiyopyKPPa4xpYz8DCdmeeNNkZe7CzVjmYX5S").unwrap();
+	// MANGOL_PROFILE picks the group to run against, mainnet.1 (default) or devnet.2, MANGOL_PROFILES adds
+	// or overrides profiles from a json list
+	let profiles = match std::env::var("MANGOL_PROFILES") {
+		Ok(profiles_path) => GroupProfiles::load(&profiles_path)?,
+		Err(_) => GroupProfiles::default()
+	};
+	let profile = profiles.get(&std::env::var("MANGOL_PROFILE").unwrap_or(DEFAULT_PROFILE.to_string()))?;
+	println!("Running against {} group {}", profile.name, profile.mango_group);
+	let mango_program = profile.mango_program_id()?;
+	// MANGOL_MANGO_ACCOUNT is the traded account, it has to belong to the profile's group
+	let mango_account = Pubkey::from_str(&std::env::var("MANGOL_MANGO_ACCOUNT").unwrap_or("CdYzrgPCiyopyKPPa4xpYz8DCdmeeNNkZe7CzVjmYX5S".to_string()))
+		  .map_err(|e| MangolError::MangoError(format!("MANGOL_MANGO_ACCOUNT {}", e)))?;
 	
-	let mango_mainnet_group = Pubkey::from_str("98pjRuQjK3qA6gXts96PqZT4Ze5QmnCmt3QYjhbUSPue").unwrap();
-	let connection = SolanaConnection::new("https://ninja.genesysgo.net").unwrap();
-	let mango_account_info = connection.rpc_client.get_account(&mango_account).unwrap();
-	let decoded_mango_account = MangoAccount::load_checked(mango_account_info, &mango_program).unwrap();
-	let signer  = Keypair::from_base58_string(&std::fs::read_to_string("./key.txt").unwrap());
+	let mango_group_pk = profile.mango_group_pk()?;
+	// MANGOL_ENDPOINTS points at an EndpointConfig json routing scans, submissions and subscriptions
+	let mut connection = match std::env::var("MANGOL_ENDPOINTS") {
+		Ok(endpoints_path) => SolanaConnection::from_endpoints(EndpointConfig::load(&endpoints_path)?)?,
+		Err(_) => SolanaConnection::new(&profile.rpc_url)?
+	};
+	// MANGOL_PAYER_LOCK_DIR serializes sends with other processes paying fees from the same wallet
+	if let Ok(payer_lock_dir) = std::env::var("MANGOL_PAYER_LOCK_DIR") {
+		connection = connection.with_payer_lock(FeePayerLock::new(PathBuf::from(payer_lock_dir))?);
+	}
+	// MANGOL_RPC_CACHE_DIR keeps mint decimals, the epoch schedule and other lookups that never change across restarts
+	if let Ok(rpc_cache_dir) = std::env::var("MANGOL_RPC_CACHE_DIR") {
+		connection = connection.with_disk_cache(DiskCache::new(PathBuf::from(rpc_cache_dir))?);
+	}
+	// `history <account> <slot>` recomputes an account's health as it was at a past slot, for post-mortems.
+	// MANGOL_SNAPSHOT_DIR and MANGOL_SNAPSHOT_URL are tried after the archival rpc for accounts written since,
+	// `history record <dir>` keeps such a snapshot dir
+	if args.get(1).map(|arg| arg.as_str()) == Some("history") {
+		return run_history_command(&connection, &mango_group_pk, &mango_account, &args[2..]);
+	}
+	// MANGOL_ACCOUNTS is an AccountsConfig json of the accounts this runner operates and each one's owner or
+	// delegate keystore, reported together on the control api. MANGOL_ACCOUNT names the one the trader binds to,
+	// in place of MANGOL_MANGO_ACCOUNT and MANGOL_KEYSTORE
+	let account_manager = match std::env::var("MANGOL_ACCOUNTS") {
+		Ok(accounts_path) => {
+			let account_manager = AccountManager::new(&AccountsConfig::load(&accounts_path)?)?.with_connection(connection.try_clone()?);
+			if account_manager.mango_group_pk != mango_group_pk {
+				return Err(MangolError::MangoError(format!("{} accounts belong to group {}, not {}", accounts_path, account_manager.mango_group_pk, mango_group_pk)));
+			}
+			Some(Arc::new(account_manager))
+		}
+		Err(_) => None
+	};
 	
-	let mango_group_account_info = connection.rpc_client.get_account(&mango_mainnet_group).unwrap();
+	// the group carries admin-changeable risk parameters, group diff and maintenance read it before any update
+	let mango_group_account_info = connection.rpc_client.get_account(&mango_group_pk)?;
 	let decoded_mango_group = MangoGroup::load_checked(mango_group_account_info, &mango_program).unwrap();
 	let mango_cache_account_info = connection.rpc_client.get_account(&decoded_mango_group.mango_cache)?;
 	let decoded_mango_cache = MangoCache::load_checked(mango_cache_account_info, &mango_program, &decoded_mango_group).unwrap();
-	let mango_client = MangoClient::new(&connection, decoded_mango_group, mango_mainnet_group, mango_account, decoded_mango_group.mango_cache.clone(), decoded_mango_account, decoded_mango_cache, mango_program, signer)?;
-	let perp_markets = serde_json::from_str::<Vec<PerpMarketData>>(&std::fs::read_to_string("./files/perpMarkets.json").unwrap()).unwrap();
-	let perp_market = perp_markets.get(3).unwrap();
-	let mut fib_trader = FibStrat::new(10, 43, mango_client, PriceSide::Sell, perp_market.clone())?;
+	let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+	let audit_path = std::env::var("MANGOL_AUDIT_LOG").unwrap_or("./audit.jsonl".to_string());
+	if args.get(1).map(|arg| arg.as_str()) == Some("audit") {
+		println!("{} audited transactions, chain intact", mangol_solana::audit::verify(args.get(2).unwrap_or(&audit_path))?);
+		return Ok(());
+	}
+	let audit_log = AuditLog::open(&audit_path)?;
+	// order expiries are absolute timestamps the program checks against cluster time
+	let cluster_clock = ClusterClock::new(&connection.rpc_client.url(), clock.clone());
+	cluster_clock.start();
+	let mango_client = match (&account_manager, std::env::var("MANGOL_ACCOUNT")) {
+		(Some(account_manager), Ok(name)) => account_manager.client_for(&name)?,
+		(None, Ok(name)) => return Err(MangolError::MangoError(format!("MANGOL_ACCOUNT {} needs MANGOL_ACCOUNTS", name))),
+		_ => {
+			let mango_account_info = connection.rpc_client.get_account(&mango_account).unwrap();
+			let decoded_mango_account = MangoAccount::load_checked(mango_account_info, &mango_program).unwrap();
+			let signer = KeyStore::load(std::env::var("MANGOL_KEYSTORE").unwrap_or("./key.txt".to_string()))?;
+			MangoClient::new(&connection, decoded_mango_group, mango_group_pk, mango_account, decoded_mango_group.mango_cache.clone(), decoded_mango_account, decoded_mango_cache, mango_program, signer)?
+		}
+	};
+	// the clients of the tasks below trade the same account as the trader
+	let (mango_account, decoded_mango_account) = (mango_client.mango_account_pk, mango_client.mango_account);
+	let mut mango_client = mango_client
+		  .with_clock(Arc::new(cluster_clock))
+		  .with_audit_log(audit_log.clone());
+	// MANGOL_EVENT_CONSUMPTION is never (default), bundled or cranked, see EventConsumption
+	mango_client = mango_client.with_event_consumption(match std::env::var("MANGOL_EVENT_CONSUMPTION").as_deref() {
+		Ok("bundled") => EventConsumption::Bundled { limit: 14 },
+		Ok("cranked") => EventConsumption::Cranked,
+		_ => EventConsumption::default()
+	});
+	// MANGOL_SELF_TRADE is allow (default), cancel-first or price-adjust, for the taker orders of stop losses
+	// and unwinds that would otherwise fill against this account's own quotes
+	if let Some(self_trade_policy) = std::env::var("MANGOL_SELF_TRADE").ok().and_then(|policy| SelfTradePolicy::parse(&policy)) {
+		mango_client = mango_client.with_self_trade_policy(self_trade_policy);
+	}
+	// MANGOL_BACKUP_KEYSTORE is another owner or delegate key, failed over to when the primary stops verifying
+	if let Ok(backup_keystore) = std::env::var("MANGOL_BACKUP_KEYSTORE") {
+		mango_client = mango_client.with_backup_signer(KeyStore::load(backup_keystore)?);
+	}
+	if args.get(1).map(|arg| arg.as_str()) == Some("maintenance") {
+		return run_maintenance(&mango_client, args.get(2).map(|arg| arg.as_str()).unwrap_or(""));
+	}
+	if args.get(1).map(|arg| arg.as_str()) == Some("group") {
+		return run_group_command(&mango_client, &args[2..]);
+	}
+	let heartbeats = Heartbeats::default();
+	let mut watchdog = Watchdog::new(heartbeats.clone());
+	// MANGOL_MARKET picks the traded market by registry name, PERP-<index> on profiles reading their markets from the chain.
+	// Unset it is the profile's default market
+	let market_registry = if profile.markets_on_chain {
+		mango_client.load_market_registry()?
+	} else {
+		MarketRegistry::load(profile.markets_file())?
+	};
+	let perp_market = market_registry.get(&std::env::var("MANGOL_MARKET").unwrap_or(profile.default_market().to_string()))?;
+	// MANGOL_LIVE_BOOK=0 fetches the book from the rpc every round instead of keeping it from subscriptions
+	if std::env::var("MANGOL_LIVE_BOOK").map(|value| value != "0").unwrap_or(true) {
+		mango_client = mango_client.with_live_order_books(&connection.ws_url(), &[perp_market.clone()]);
+	}
+	// MANGOL_FIB_RATIO, MANGOL_PRICE_FIB_RATIO, MANGOL_MAX_POSITION_DEPTH and MANGOL_ACTION_INTERVAL_SECS
+	// take the snippet `mangol optimize` prints for its best parameter set
+	let env_f64 = |name: &str| std::env::var(name).ok().and_then(|value| value.parse::<f64>().ok());
+	let fib_params = FibParams {
+		fib_ratio: env_f64("MANGOL_FIB_RATIO").unwrap_or(FibParams::default().fib_ratio),
+		price_fib_ratio: env_f64("MANGOL_PRICE_FIB_RATIO").unwrap_or(FibParams::default().price_fib_ratio),
+	};
+	let max_position_depth = std::env::var("MANGOL_MAX_POSITION_DEPTH").ok().and_then(|depth| depth.parse::<u16>().ok()).unwrap_or(10);
+	let action_interval_secs = std::env::var("MANGOL_ACTION_INTERVAL_SECS").ok().and_then(|secs| secs.parse::<u64>().ok()).unwrap_or(43);
+	let mut fib_trader = FibStrat::new(max_position_depth, action_interval_secs, mango_client, PriceSide::Sell, perp_market.clone())?
+		  .with_fib_params(fib_params)
+		  .with_clock(clock)
+		  .with_audit_log(audit_log);
+	// MANGOL_TRADE_EQUITY_FRACTION sizes the first level from equity instead of a fixed amount
+	if let Some(fraction) = std::env::var("MANGOL_TRADE_EQUITY_FRACTION").ok().and_then(|fraction| fraction.parse::<f64>().ok()) {
+		fib_trader = fib_trader.with_trade_amount(TradeAmount::EquityFraction(fraction));
+	}
+	let state_file = std::env::var("MANGOL_STATE_FILE").unwrap_or("./fib-state.json".to_string());
+	fib_trader = fib_trader.with_state_file(PathBuf::from(&state_file));
+	if args.get(1).map(|arg| arg.as_str()) == Some("position") {
+		return run_position_command(&mut fib_trader, &state_file, &args[2..]);
+	}
+	if let Ok(schedule_path) = std::env::var("MANGOL_SCHEDULE") {
+		fib_trader = fib_trader.with_schedule(TradingSchedule::load(&schedule_path)?);
+	}
+	let network_monitor = NetworkMonitor::new(&connection.rpc_client.url());
+	network_monitor.start();
+	// MANGOL_DEAD_MANS_SWITCH_SECS cancels orders once rpc and websocket come back after that long without either
+	if let Some(max_blind_secs) = std::env::var("MANGOL_DEAD_MANS_SWITCH_SECS").ok().and_then(|secs| secs.parse::<u64>().ok()) {
+		let dead_mans_switch = DeadMansSwitch::new(network_monitor.clone(), &connection.ws_url(), Duration::from_secs(max_blind_secs))
+			  .with_degraded_expiry(10);
+		dead_mans_switch.start();
+		fib_trader = fib_trader.with_dead_mans_switch(dead_mans_switch);
+	}
+	fib_trader = fib_trader.with_network_monitor(network_monitor);
+	let account_events = fib_trader.mango_client.own_account_stream(&connection.ws_url(), vec![perp_market.clone()]);
+	fib_trader = fib_trader.with_account_events(account_events);
+	fib_trader = fib_trader.with_expiry_manager(ExpiryManager::default());
+	// MANGOL_ROUND_ALIGN_SECS ends rounds on wall clock boundaries, MANGOL_ROUND_JITTER_SECS randomizes them
+	let env_secs = |name: &str| std::env::var(name).ok().and_then(|secs| secs.parse::<u64>().ok());
+	let mut round_timing = RoundTiming::default().with_jitter(Duration::from_secs(env_secs("MANGOL_ROUND_JITTER_SECS").unwrap_or(0)));
+	if let Some(align_secs) = env_secs("MANGOL_ROUND_ALIGN_SECS") {
+		round_timing = round_timing.aligned(align_secs);
+	}
+	fib_trader = fib_trader.with_round_timing(round_timing);
+	// MANGOL_REFERENCE_PRICE=microprice computes fib targets from the book instead of the lagging oracle
+	if std::env::var("MANGOL_REFERENCE_PRICE").map(|reference| reference == "microprice").unwrap_or(false) {
+		fib_trader = fib_trader.with_reference_price(ReferencePrice::Microprice);
+	}
+	// MANGOL_ENTRY_MAX_IMPACT_BPS splits market entries and exits the book can't take at once
+	if let Some(max_impact_bps) = std::env::var("MANGOL_ENTRY_MAX_IMPACT_BPS").ok().and_then(|bps| bps.parse::<f64>().ok()) {
+		fib_trader = fib_trader.with_entry_impact(EntryImpactLimit { max_impact_bps, max_clips: 10, clip_interval: Duration::from_secs(2) });
+	}
+	// MANGOL_PROFIT_LIQUIDITY_ROUNDS pushes take profits deeper until their level held resting size over that many rounds
+	if let Some(rounds) = std::env::var("MANGOL_PROFIT_LIQUIDITY_ROUNDS").ok().and_then(|rounds| rounds.parse::<usize>().ok()) {
+		fib_trader = fib_trader.with_profit_liquidity(LiquidityHistory::new(rounds, 25.0, 1.0));
+	}
+	// pauses instead of resting orders in a book the keepers or traders abandoned
+	fib_trader = fib_trader.with_halt_detector(HaltDetector::default());
+	// MANGOL_DEAD_MARKET_MIN_VOLUME, base lots filled over MANGOL_DEAD_MARKET_HOURS (default 6), holds off opening
+	// a ladder on a market quieter than that
+	if let Some(min_volume) = std::env::var("MANGOL_DEAD_MARKET_MIN_VOLUME").ok().and_then(|volume| volume.parse::<i64>().ok()) {
+		let hours = std::env::var("MANGOL_DEAD_MARKET_HOURS").ok().and_then(|hours| hours.parse::<u64>().ok()).unwrap_or(6);
+		let stats_signer = Keypair::from_bytes(&fib_trader.mango_client.signer.to_bytes()).unwrap();
+		let stats_client = MangoClient::new(&connection, decoded_mango_group, mango_group_pk, mango_account, decoded_mango_group.mango_cache, decoded_mango_account, decoded_mango_cache, mango_program, stats_signer)?;
+		let market_stats = MarketStats::new(Duration::from_secs((hours + 1) * 60 * 60));
+		MarketStatsTracker::new(stats_client, vec![perp_market.clone()], market_stats.clone()).start();
+		fib_trader = fib_trader.with_market_stats(market_stats)
+			  .with_dead_market_filter(DeadMarketFilter { window: Duration::from_secs(hours * 60 * 60), min_volume, min_fills: 1 });
+	}
+	// MANGOL_ORDER_RETRY_ATTEMPTS quarantines an order after that many failed sends, retried after MANGOL_ORDER_RETRY_BACKOFF_SECS
+	// (default 60) doubling each time. A ./<strategy>.release file or POST /quarantine/release on the control api releases them
+	if let Some(max_attempts) = std::env::var("MANGOL_ORDER_RETRY_ATTEMPTS").ok().and_then(|attempts| attempts.parse::<u32>().ok()) {
+		let backoff_secs = std::env::var("MANGOL_ORDER_RETRY_BACKOFF_SECS").ok().and_then(|secs| secs.parse::<u64>().ok()).unwrap_or(60);
+		fib_trader = fib_trader.with_retry_budget(RetryBudget::new(max_attempts, Duration::from_secs(backoff_secs))
+			  .with_release(QuarantineRelease::new(FIB_STRATEGY_NAME, PathBuf::from("."))));
+	}
+	// MANGOL_INCENTIVE_EDGE_BPS moves take profits toward the depth earning the most MNGO before they fill, never nearer
+	// the mid than that many bps nor more than MANGOL_INCENTIVE_MAX_MOVE_BPS (default 25) from their fib target. Quotes
+	// rest MANGOL_INCENTIVE_REST_SECS (default 600) at most, filling at the rate the market stats measured or
+	// MANGOL_INCENTIVE_TRADED_LOTS_PER_SEC (default 1) base lots a second without them
+	if let Some(min_edge_bps) = env_f64("MANGOL_INCENTIVE_EDGE_BPS") {
+		let rest_secs = std::env::var("MANGOL_INCENTIVE_REST_SECS").ok().and_then(|secs| secs.parse::<u64>().ok()).unwrap_or(600);
+		let traded_lots_per_sec = env_f64("MANGOL_INCENTIVE_TRADED_LOTS_PER_SEC").unwrap_or(1.0);
+		let max_move_bps = env_f64("MANGOL_INCENTIVE_MAX_MOVE_BPS").unwrap_or(25.0);
+		fib_trader = fib_trader.with_incentive_placer(IncentivePlacer::new(rest_secs, min_edge_bps, traded_lots_per_sec).with_max_move_bps(max_move_bps));
+	}
+	// MANGOL_SHADOW_FIB_RATIO, MANGOL_SHADOW_PRICE_FIB_RATIO, MANGOL_SHADOW_MAX_POSITION_DEPTH and MANGOL_SHADOW_ACTION_INTERVAL_SECS
+	// decide a variant on paper next to the live ladder, unset ones take the live value. MANGOL_SHADOW_LOG keeps its decisions
+	let shadow_vars = ["MANGOL_SHADOW_FIB_RATIO", "MANGOL_SHADOW_PRICE_FIB_RATIO", "MANGOL_SHADOW_MAX_POSITION_DEPTH", "MANGOL_SHADOW_ACTION_INTERVAL_SECS"];
+	if shadow_vars.iter().any(|name| std::env::var(name).is_ok()) {
+		let live = FibCandidate { fib_params, max_position_depth, action_interval_secs };
+		let variant = FibCandidate {
+			fib_params: FibParams {
+				fib_ratio: env_f64("MANGOL_SHADOW_FIB_RATIO").unwrap_or(fib_params.fib_ratio),
+				price_fib_ratio: env_f64("MANGOL_SHADOW_PRICE_FIB_RATIO").unwrap_or(fib_params.price_fib_ratio),
+			},
+			max_position_depth: std::env::var("MANGOL_SHADOW_MAX_POSITION_DEPTH").ok().and_then(|depth| depth.parse::<u16>().ok()).unwrap_or(max_position_depth),
+			action_interval_secs: std::env::var("MANGOL_SHADOW_ACTION_INTERVAL_SECS").ok().and_then(|secs| secs.parse::<u64>().ok()).unwrap_or(action_interval_secs),
+		};
+		let perp_market_info = perp_market.perp_market_info(&fib_trader.mango_client.mango_group);
+		let market = BacktestMarket { market: perp_market.clone(), base_lot_size: perp_market_info.base_lot_size, quote_lot_size: perp_market_info.quote_lot_size, books: vec![] };
+		let mut shadow = ShadowRun::new(&market, live, variant);
+		if let Ok(path) = std::env::var("MANGOL_SHADOW_LOG") {
+			shadow = shadow.with_decision_log(PathBuf::from(path));
+		}
+		fib_trader = fib_trader.with_shadow(shadow);
+	}
+	// MANGOL_RECONCILE_MINUTES compares the ledger with the perp account that often, within MANGOL_RECONCILE_TOLERANCE_LOTS
+	// base lots (default 1) and MANGOL_RECONCILE_QUOTE_TOLERANCE of the quote. MANGOL_RECONCILE_HALT=1 pauses while they disagree
+	if let Some(minutes) = std::env::var("MANGOL_RECONCILE_MINUTES").ok().and_then(|minutes| minutes.parse::<u64>().ok()) {
+		let tolerance_lots = std::env::var("MANGOL_RECONCILE_TOLERANCE_LOTS").ok().and_then(|lots| lots.parse::<i64>().ok()).unwrap_or(1);
+		let mut alarm = ReconciliationAlarm::new(Duration::from_secs(minutes * 60), tolerance_lots)
+			  .with_halt(std::env::var("MANGOL_RECONCILE_HALT").map(|halt| halt == "1").unwrap_or(false));
+		if let Some(quote_tolerance) = env_f64("MANGOL_RECONCILE_QUOTE_TOLERANCE") {
+			alarm = alarm.with_quote_tolerance(quote_tolerance);
+		}
+		fib_trader = fib_trader.with_reconciliation_alarm(alarm);
+	}
+	// stops placing orders while the market's pyth confidence is wider than the program accepts
+	let oracle_confidence = fib_trader.mango_client.oracle_confidence(&connection.ws_url());
+	let mut risk_manager = RiskManager::default().with_oracle_confidence(oracle_confidence);
+	// MANGOL_CORRELATION_GROUPS caps correlated markets as one position, `name=index,index:max usd notional`
+	// separated by `;`, e.g. `SOL=3,12:250000` nets SOL-PERP against MSOL
+	if let Ok(spec) = std::env::var("MANGOL_CORRELATION_GROUPS") {
+		let quote_decimals = fib_trader.mango_client.mango_group.tokens[QUOTE_INDEX].decimals;
+		for correlation_group in parse_correlation_groups(&spec, quote_decimals)? {
+			risk_manager = risk_manager.with_correlation_group(correlation_group);
+		}
+	}
+	fib_trader = fib_trader.with_risk_manager(risk_manager);
+	// MANGOL_DASHBOARD_ADDR pushes the bot's state to dashboards over a websocket, e.g. 127.0.0.1:8901
+	if let Ok(dashboard_addr) = std::env::var("MANGOL_DASHBOARD_ADDR") {
+		let state_broadcaster = StateBroadcaster::new(&dashboard_addr);
+		state_broadcaster.start();
+		fib_trader = fib_trader.with_state_broadcaster(state_broadcaster);
+	}
+	// MANGOL_BUS_SOCKET publishes prices, fills, health and decisions on a unix socket for other processes
+	if let Ok(bus_socket) = std::env::var("MANGOL_BUS_SOCKET") {
+		let event_bus = EventBus::new();
+		event_bus.serve_socket(&bus_socket)?;
+		fib_trader = fib_trader.with_event_bus(event_bus);
+	}
+	// MANGOL_RECORD_DIR records every decision round, equity and fills there for replays, reports and `mangol optimize`.
+	// MANGOL_COST_BASIS is average (default) or fifo, how the reports realize pnl
+	if let Ok(record_dir) = std::env::var("MANGOL_RECORD_DIR") {
+		let cost_basis = match std::env::var("MANGOL_COST_BASIS") {
+			Ok(cost_basis) => CostBasis::parse(&cost_basis).ok_or_else(|| MangolError::MangoError(format!("MANGOL_COST_BASIS {} is not average or fifo", cost_basis)))?,
+			Err(_) => CostBasis::default()
+		};
+		fib_trader = fib_trader.with_recorder(SessionRecorder::new(&record_dir)?.with_cost_basis(cost_basis));
+	}
+	let kill_switch = KillSwitch::new(FIB_STRATEGY_NAME, PathBuf::from("."));
+	fib_trader = fib_trader.with_kill_switch(kill_switch.clone());
+	// MANGOL_CONTROL_ADDR serves the control api for the trader, GET /explanations lists its latest decisions,
+	// POST /quarantine/release releases its quarantined orders, POST and DELETE /kill/<strategy> engage and release
+	// its kill switch and GET /accounts reports the MANGOL_ACCOUNTS accounts.
+	// MANGOL_CONTROL_TOKEN is the bearer token it requires
+	if let Ok(control_addr) = std::env::var("MANGOL_CONTROL_ADDR") {
+		let explanations = ExplanationLog::new(100);
+		fib_trader = fib_trader.with_explanation_log(explanations.clone());
+		let mut control_api = ControlApi::default().with_explanations(explanations).with_kill_switch(kill_switch);
+		if let Some(account_manager) = &account_manager {
+			control_api = control_api.with_accounts(account_manager.clone());
+		}
+		if let Some(quarantine_release) = fib_trader.retry_budget.as_ref().and_then(|retry_budget| retry_budget.release.clone()) {
+			control_api = control_api.with_quarantine_release(quarantine_release);
+		}
+		if let Ok(token) = std::env::var("MANGOL_CONTROL_TOKEN") {
+			control_api = control_api.with_token(&token);
+		}
+		control_api.serve(&control_addr);
+	}
+	// MANGOL_NOTIFICATION_TEMPLATES overrides alert wording, one `kind = template` per line
+	if let Ok(templates_path) = std::env::var("MANGOL_NOTIFICATION_TEMPLATES") {
+		fib_trader = fib_trader.with_notifier(Notifier::new(Templates::load(&templates_path)?));
+	}
+	// MANGOL_LOG_SHIP_URL ships alerts and round metrics to Loki or a json endpoint, see ShippingConfig::from_env
+	if let Some(shipping_config) = ShippingConfig::from_env() {
+		let log_shipper = LogShipper::start(shipping_config);
+		log_shipper.log("info", &format!("{} started on {}", FIB_STRATEGY_NAME, perp_market.name));
+		fib_trader = fib_trader.with_log_shipper(log_shipper);
+	}
+	fib_trader = fib_trader.with_signer_rotation(SignerRotation::new(FIB_STRATEGY_NAME, PathBuf::from(".")));
+	fib_trader = fib_trader.with_heartbeats(heartbeats);
+	// MANGOL_LEASE_FILE, on the volume MANGOL_STATE_FILE is shared on, runs this as a hot instance or a warm
+	// standby: only the holder of the lease trades and a standby takes over once it expires. MANGOL_INSTANCE
+	// names this instance, the hostname by default, MANGOL_LEASE_TTL_SECS defaults to a few rounds
+	if let Ok(lease_file) = std::env::var("MANGOL_LEASE_FILE") {
+		let instance = std::env::var("MANGOL_INSTANCE").or_else(|_| std::env::var("HOSTNAME")).unwrap_or(format!("mangol-{}", std::process::id()));
+		let ttl_secs = std::env::var("MANGOL_LEASE_TTL_SECS").ok().and_then(|secs| secs.parse::<u64>().ok()).unwrap_or(action_interval_secs * 3 + 60);
+		fib_trader = fib_trader.with_leader_election(LeaderElection::new(&instance, Arc::new(FileLeaseStore::new(PathBuf::from(lease_file))), ttl_secs));
+	}
+	// checked before any task below sends a transaction
+	let mut preflight_failures = Preflight::default().run(&fib_trader.mango_client, &[perp_market.clone()], &connection.ws_url());
+	preflight_failures.extend(check_fib_config(&fib_trader));
+	if let Some(backup_signer) = &fib_trader.mango_client.backup_signer {
+		preflight_failures.extend(check_signer(&fib_trader.mango_client.mango_account, &backup_signer.pubkey()));
+	}
+	abort_on_failures(&preflight_failures)?;
+	// MANGOL_REPAY_BORROWS_OVER, ui quote a day, repays spot borrows whose interest costs more than that while this instance holds the lease
+	if let Some(max_daily_interest) = std::env::var("MANGOL_REPAY_BORROWS_OVER").ok().and_then(|interest| interest.parse::<f64>().ok()) {
+		let repay_signer = Keypair::from_bytes(&fib_trader.mango_client.signer.to_bytes()).unwrap();
+		let repay_client = MangoClient::new(&connection, decoded_mango_group, mango_group_pk, mango_account, decoded_mango_group.mango_cache, decoded_mango_account, decoded_mango_cache, mango_program, repay_signer)?
+			  .with_audit_log(audit_log.clone());
+		let quote_decimals = decoded_mango_group.tokens[QUOTE_INDEX].decimals as i32;
+		let mut repayer = BorrowRepayer::new(repay_client)
+			  .with_max_daily_interest(max_daily_interest * 10_f64.powi(quote_decimals))
+			  .with_heartbeats(heartbeats.clone());
+		if let Some(leader_election) = &fib_trader.leader_election {
+			repayer = repayer.with_leader_election(leader_election.clone());
+		}
+		watchdog = watchdog.watch(BORROW_REPAYER_NAME, repayer.check_interval * 2);
+		repayer.start();
+	}
+	// MANGOL_MNGO_QUOTE_MINT, usually the USDC mint, is swapped into MNGO to keep the deposits above the group's
+	// ref_mngo_required, below it every taker fill pays the referral surcharge
+	if let Ok(quote_mint) = std::env::var("MANGOL_MNGO_QUOTE_MINT") {
+		let quote_mint = Pubkey::from_str(&quote_mint).map_err(|e| MangolError::MangoError(format!("MANGOL_MNGO_QUOTE_MINT {}", e)))?;
+		let mngo_signer = Keypair::from_bytes(&fib_trader.mango_client.signer.to_bytes()).unwrap();
+		let mngo_client = MangoClient::new(&connection, decoded_mango_group, mango_group_pk, mango_account, decoded_mango_group.mango_cache, decoded_mango_account, decoded_mango_cache, mango_program, mngo_signer)?
+			  .with_audit_log(audit_log.clone());
+		let mut maintainer = MngoDepositMaintainer::new(mngo_client, quote_mint).with_heartbeats(heartbeats.clone());
+		if let Some(leader_election) = &fib_trader.leader_election {
+			maintainer = maintainer.with_leader_election(leader_election.clone());
+		}
+		watchdog = watchdog.watch(MNGO_MAINTAINER_NAME, maintainer.check_interval * 2);
+		maintainer.start();
+	}
+	// MANGOL_SETTLE_PNL_OVER, ui quote, settles the traded market's pnl once more than that is unsettled so it
+	// backs the next rung of the ladder
+	if let Some(settle_threshold) = env_f64("MANGOL_SETTLE_PNL_OVER") {
+		let settle_signer = Keypair::from_bytes(&fib_trader.mango_client.signer.to_bytes()).unwrap();
+		let settle_client = MangoClient::new(&connection, decoded_mango_group, mango_group_pk, mango_account, decoded_mango_group.mango_cache, decoded_mango_account, decoded_mango_cache, mango_program, settle_signer)?
+			  .with_audit_log(audit_log.clone());
+		let quote_decimals = decoded_mango_group.tokens[QUOTE_INDEX].decimals as i32;
+		let mut settler = PnlSettler::new(settle_client, vec![perp_market.market_index])
+			  .with_settle_threshold(settle_threshold * 10_f64.powi(quote_decimals))
+			  .with_heartbeats(heartbeats.clone());
+		if let Some(leader_election) = &fib_trader.leader_election {
+			settler = settler.with_leader_election(leader_election.clone());
+		}
+		watchdog = watchdog.watch(PNL_SETTLER_NAME, settler.check_interval * 2);
+		settler.start();
+	}
+	// the trading loop beats at least every round, a send stuck confirming stops it.
+	// MANGOL_WATCHDOG_EXIT_ON_STALL exits instead of only alerting, for a supervisor to restart the bot
+	let max_trading_silence = Duration::from_secs(action_interval_secs * 3 + 120);
+	watchdog = if std::env::var("MANGOL_WATCHDOG_EXIT_ON_STALL").is_ok() {
+		watchdog.watch_with_restart(FIB_STRATEGY_NAME, max_trading_silence, || {
+			mangol_mailer::flush(Duration::from_secs(10));
+			std::process::exit(1)
+		})
+	} else {
+		watchdog.watch(FIB_STRATEGY_NAME, max_trading_silence)
+	};
+	// MANGOL_HEARTBEAT_FILE and MANGOL_LIVENESS_ADDR, e.g. 127.0.0.1:8902, expose liveness to external probes
+	if let Ok(heartbeat_file) = std::env::var("MANGOL_HEARTBEAT_FILE") {
+		watchdog = watchdog.with_heartbeat_file(PathBuf::from(heartbeat_file));
+	}
+	if let Ok(liveness_addr) = std::env::var("MANGOL_LIVENESS_ADDR") {
+		watchdog = watchdog.with_probe(&liveness_addr);
+	}
+	watchdog.start();
+	fib_trader.await_leadership()?;
 	
-	fib_trader.init_position()?;
-	fib_trader.start_trading()?;
+	if std::path::Path::new(&state_file).exists() {
+		// resume the persisted or imported position instead of opening a new one
+		fib_trader.import_position(PositionExport::load(&state_file)?, false)?;
+	} else {
+		fib_trader.init()?;
+	}
+	fib_trader.run()?;
 
 	/*
 	Liquidator
@@ -54,9 +492,23 @@ fn main() -> MangolResult<()> {
 	// 	},
 	// 	with_context: None
 	// };
-	// let cached_mango_accounts: Vec<String>= serde_json::from_str(&std::fs::read_to_string("/home/y0h4n3s/dev/source/tests-node/mangoAccounts.json").unwrap()).unwrap();
-	// let cached_mango_accounts_pks: Vec<Pubkey> = cached_mango_accounts.into_iter().map(|pk | Pubkey::from_str(&pk).unwrap()).collect();
-	// let liquidator = MangoLiquidator::new(connection, cached_mango_accounts_pks)?;
+	// MANGOL_WATCH_LIST is the json array of accounts the liquidator watches, changed at runtime through the control api
+	// let watch_list = WatchList::load(PathBuf::from(std::env::var("MANGOL_WATCH_LIST").unwrap_or("./watch_list.json".to_string())))?;
+	// MANGOL_CONTROL_ADDR is where the control api listens, MANGOL_CONTROL_TOKEN the bearer token it requires
+	// let mut control_api = ControlApi::default().with_watch_list("liquidator", watch_list.clone());
+	// if let Ok(token) = std::env::var("MANGOL_CONTROL_TOKEN") {
+	// 	control_api = control_api.with_token(&token);
+	// }
+	// control_api.serve(&std::env::var("MANGOL_CONTROL_ADDR").unwrap_or("127.0.0.1:9185".to_string()));
+	// MANGOL_LIQUIDATION_MODE is alert-only (default), dry-run or execute, a ./liquidator.mode file switches it while running
+	// let mode = std::env::var("MANGOL_LIQUIDATION_MODE").ok().and_then(|mode| LiquidationMode::parse(&mode)).unwrap_or_default();
+	// let liqor_signer = KeyStore::load(std::env::var("MANGOL_KEYSTORE").unwrap_or("./key.txt".to_string()))?;
+	// the unwinder trades what a sent liquidation left the liqor holding back into quote
+	// let unwind_client = MangoClient::new(&connection, decoded_mango_group, mango_group_pk, mango_account, decoded_mango_group.mango_cache, decoded_mango_account, decoded_mango_cache, mango_program, Keypair::from_bytes(&liqor_signer.to_bytes()).unwrap())?;
+	// let liquidator = MangoLiquidator::new(connection, &profile, vec![])?
+	// 	  .with_watch_list(watch_list)?
+	// 	  .with_liqor(Liqor::new(liqor_signer, mango_account, mango_program, ModeSwitch::new(mode).with_dir(PathBuf::from(".")))
+	// 		  .with_unwinder(LiquidationUnwinder::new(unwind_client, registry.clone())));
 	//
 	// liquidator.watch_and_liquidate()?.join();
 	//
@@ -64,28 +516,11 @@ fn main() -> MangolResult<()> {
 		Account Watcher
 	 */
 // 	let connection = SolanaConnection::new("https://ninja.genesysgo.net").unwrap();
-// 	let mango_traders = vec![
-// 		"2XvNEzgaboHpG3g1v8rxoQ92eGxHygdShUwx4Z9W7oax",
-// //"4rm5QCgFPm4d37MCawNypngV4qPWv4D5tw57KE2qUcLE",
-// "8L3Hysad7Ss7tdKZ1MBqbqfE3uAPUaWPtamD7UKbvsDQ",
-// "AkYeCdjYsUG7CUYRxmCWHaqeEfJ96gJQsLANhPVgjHPw",
-// "8L3Hysad7Ss7tdKZ1MBqbqfE3uAPUaWPtamD7UKbvsDQ",
-// "66g7RM67Y5sHrpE5NQCAVLwPaiATHuVEi4xMHaV7Wa4B",
-// 		"958v4tZZCTiGE7kqaGT2PpRLsMWZWLHcYnhKuQ4LQtVF",
-// 		"FjMtun22344M2oxXXZN7ZVJABC6dJ39uvd3idqH5SdCq",
-// 		"DMnWGozFuqYNWZzT9T6vnWAajQNyVYdadBPQwXWuzQX3",
-// 		"H6R2zNZMmhGoXLMGweGPP4Q9RtZ6RprVu7Hc868pJVbp",
-// 		"4qgrNU7MXUDFbP8HArkkc2y44AJUPbSvqXw5WmCNuz36",
-//
-// 	];
-// 	let mut join_handles: Vec<JoinHandle<()>> = vec![];
-// 	for trader in &mango_traders {
-// 		let watcher = mangol_strategies::watch_mango_traders::TraderWatcher::new(Pubkey::from_str(trader).unwrap(), &connection);
-// 		join_handles.push(watcher.start_watch());
-// 	}
-// 	for join_handle in join_handles {
-// 		join_handle.join();
-// 	}
+// 	// MANGOL_TRADER_WATCH_LIST is the json array of traders to follow, changed at runtime through the control api
+// 	let traders = WatchList::load(PathBuf::from(std::env::var("MANGOL_TRADER_WATCH_LIST").unwrap_or("./traders.json".to_string())))?;
+// 	ControlApi::default().with_watch_list("traders", traders.clone())
+// 		  .serve(&std::env::var("MANGOL_CONTROL_ADDR").unwrap_or("127.0.0.1:9185".to_string()));
+// 	mangol_strategies::watch_mango_traders::watch_traders(traders, connection).join();
 //
 	
 	
@@ -130,3 +565,169 @@ fn main() -> MangolResult<()> {
 	// println!("{}", mango_accounts.len())
 	Ok(())
 }
+
+/// `position export <out>` copies the position the running bot persisted to its state file,
+/// `position import <path> [--force]` checks an export against this account and makes it the state the bot resumes
+fn run_position_command(fib_trader: &mut FibStrat, state_file: &str, args: &[String]) -> MangolResult<()> {
+	match (args.get(0).map(|arg| arg.as_str()), args.get(1)) {
+		(Some("export"), Some(path)) => {
+			let export = PositionExport::load(state_file)?;
+			export.save(path)?;
+			println!("[+] Exported {} position of {} base lots at average {} to {}", export.market, export.base_position, export.average_price, path);
+		}
+		(Some("import"), Some(path)) => {
+			let force = args.iter().any(|arg| arg == "--force");
+			fib_trader.import_position(PositionExport::load(path)?, force)?;
+			println!("[+] Imported {} position with {} levels into {}", fib_trader.market.name, fib_trader.position.state_history.len(), state_file);
+		}
+		_ => {
+			eprintln!("Usage: mangol position <export <out>|import <path> [--force]>");
+		}
+	}
+	Ok(())
+}
+
+/// `optimize <recording dir> [train days] [test days] [random samples]` backtests fib parameter sets
+/// walk-forward on the oracle prices a SessionRecorder kept and prints the leaderboard with the best config.
+/// MANGOL_RUIN_ACCOUNT_SIZE, ui quote, and MANGOL_RUIN_MAX_DRAWDOWN, a ratio, set the account the risk of ruin is estimated for
+fn run_optimize_command(args: &[String]) -> MangolResult<()> {
+	let dir = match args.get(0) {
+		Some(dir) => dir,
+		None => {
+			eprintln!("Usage: mangol optimize <recording dir> [train days] [test days] [random samples]");
+			return Ok(());
+		}
+	};
+	let days = |i: usize, default: u64| args.get(i).and_then(|days| days.parse::<u64>().ok()).map(|days| days * 86_400).unwrap_or(default);
+	let walk_forward = WalkForward { train_secs: days(1, WalkForward::default().train_secs), test_secs: days(2, WalkForward::default().test_secs) };
+	let sessions = SessionRecorder::new(dir)?.sessions()?;
+	let mut market = match sessions.first() {
+		Some(session) => BacktestMarket::from_session(session),
+		None => {
+			eprintln!("[-] No recorded sessions in {}", dir);
+			return Ok(());
+		}
+	};
+	// MANGOL_OPTIMIZE_BOOK_DIR matches resting orders against the book snapshots a BookRecorder kept there
+	if let Ok(book_dir) = std::env::var("MANGOL_OPTIMIZE_BOOK_DIR") {
+		market = market.with_books(load_snapshots(&book_dir, &market.market.name)?);
+	}
+	let grid = ParameterGrid::default();
+	let candidates = match args.get(3).and_then(|samples| samples.parse::<usize>().ok()) {
+		Some(samples) => grid.random_candidates(samples, 0),
+		None => grid.candidates()
+	};
+	let env_f64 = |name: &str| std::env::var(name).ok().and_then(|value| value.parse::<f64>().ok());
+	let account_size = env_f64("MANGOL_RUIN_ACCOUNT_SIZE").unwrap_or(1_000.0) * 10_f64.powi(market.market.quote_decimals as i32);
+	let ruin_config = RuinConfig::new(account_size).with_max_drawdown(env_f64("MANGOL_RUIN_MAX_DRAWDOWN").unwrap_or(0.2));
+	let optimization = optimize(&market, &price_series(&sessions), &candidates, &walk_forward, &ruin_config)?;
+	println!("{}", optimization.report(20));
+	Ok(())
+}
+
+/// `history <mango account> <slot>` prints the account's health at the slot, `history record <dir> [mango account]`
+/// keeps the group, cache, account and its open orders in a snapshot dir as they change, the traded account by default
+fn run_history_command(connection: &SolanaConnection, mango_group_pk: &Pubkey, mango_account_pk: &Pubkey, args: &[String]) -> MangolResult<()> {
+	if args.get(0).map(|arg| arg.as_str()) == Some("record") {
+		let dir = match args.get(1) {
+			Some(dir) => dir,
+			None => {
+				eprintln!("Usage: mangol history record <dir> [mango account]");
+				return Ok(());
+			}
+		};
+		let mango_account_pk = match args.get(2) {
+			Some(account) => Pubkey::from_str(account).map_err(|e| MangolError::MangoError(format!("mango account {}", e)))?,
+			None => *mango_account_pk
+		};
+		let mango_group = decode_mango_group(&connection.rpc_client.get_account(mango_group_pk)?.data)?;
+		let mango_account = decode_mango_account(&connection.rpc_client.get_account(&mango_account_pk)?.data)?;
+		let accounts = SnapshotRecorder::accounts_of(mango_group_pk, &mango_group, &mango_account_pk, &mango_account);
+		println!("[+] Recording {} accounts to {}", accounts.len(), dir);
+		SnapshotRecorder::new(SnapshotDir::new(PathBuf::from(dir)), &connection.rpc_client.url(), &connection.ws_url()).record(&accounts);
+		return Ok(());
+	}
+	let (account, slot) = match (args.get(0).map(|account| Pubkey::from_str(account)), args.get(1).and_then(|slot| slot.parse::<u64>().ok())) {
+		(Some(Ok(account)), Some(slot)) => (account, slot),
+		_ => {
+			eprintln!("Usage: mangol history <mango account> <slot>");
+			return Ok(());
+		}
+	};
+	let mut sources: Vec<Box<dyn AccountHistory + '_>> = vec![Box::new(ArchivalRpc::new(connection))];
+	if let Ok(dir) = std::env::var("MANGOL_SNAPSHOT_DIR") {
+		sources.push(Box::new(SnapshotDir::new(PathBuf::from(dir))));
+	}
+	if let Ok(url) = std::env::var("MANGOL_SNAPSHOT_URL") {
+		sources.push(Box::new(SnapshotProvider::new(&url)));
+	}
+	let state = HistoricalState::fetch(&FallbackHistory { sources }, mango_group_pk, &account, slot)?;
+	let epoch_schedule = connection.epoch_schedule()?;
+	println!("Slot {} is in epoch {}", slot, epoch_schedule.get_epoch(slot));
+	for (pubkey, captured) in &state.sources {
+		println!("{} as of slot {}", pubkey, captured);
+	}
+	println!("Maint health {}", state.health(HealthType::Maint)?);
+	println!("Init health {}", state.health(HealthType::Init)?);
+	println!("Equity {}", state.health(HealthType::Equity)?);
+	if state.staleness() > 0 {
+		println!("Oldest input lags slot {} by {} slots", slot, state.staleness());
+	}
+	Ok(())
+}
+
+/// `group snapshot <out>` stores the current group parameters, `group diff <before> [after]` compares
+/// against a stored snapshot or the live group and alerts on risk parameter changes
+fn run_group_command(mango_client: &MangoClient, args: &[String]) -> MangolResult<()> {
+	let now_ts = mango_client.clock.now_ts();
+	let live = || GroupSnapshot::capture(&mango_client.mango_group_pk, &mango_client.mango_group, now_ts);
+	match (args.get(0).map(|arg| arg.as_str()), args.get(1), args.get(2)) {
+		(Some("snapshot"), Some(path), _) => {
+			live().save(path)?;
+			println!("[+] Saved group snapshot to {}", path);
+		}
+		(Some("diff"), Some(before_path), after_path) => {
+			let before = GroupSnapshot::load(before_path)?;
+			let after = match after_path {
+				Some(after_path) => GroupSnapshot::load(after_path)?,
+				None => live()
+			};
+			let changes = diff_snapshots(&before, &after);
+			for change in &changes {
+				println!("{}{}: {} -> {}", if change.risk { "[!] " } else { "" }, change.path, change.before, change.after);
+			}
+			let risk_changes: Vec<String> = changes.iter().filter(|change| change.risk).map(|change| format!("{}: {} -> {}", change.path, change.before, change.after)).collect();
+			if !risk_changes.is_empty() {
+				mangol_mailer::notify(&Notification::RiskParametersChanged { changes: risk_changes });
+			}
+		}
+		_ => {
+			eprintln!("Usage: mangol group <snapshot <out>|diff <before> [after]>");
+		}
+	}
+	Ok(())
+}
+
+/// `mangol maintenance <close-open-orders|withdraw-dust|close-account>`
+fn run_maintenance(mango_client: &MangoClient, command: &str) -> MangolResult<()> {
+	match command {
+		"close-open-orders" => {
+			let signatures = mango_client.close_empty_spot_open_orders()?;
+			println!("[+] Closed {} spot open orders accounts {:?}", signatures.len(), signatures);
+		}
+		"withdraw-dust" => {
+			// anything worth less than one cent, in native quote
+			let quote_decimals = mango_client.mango_group.tokens[QUOTE_INDEX].decimals as i32;
+			let signatures = mango_client.withdraw_dust(10_f64.powi(quote_decimals - 2))?;
+			println!("[+] Withdrew {} dust balances {:?}", signatures.len(), signatures);
+		}
+		"close-account" => {
+			let signature = mango_client.close_mango_account()?;
+			println!("[+] Closed mango account https://explorer.solana.com/tx/{}", signature);
+		}
+		_ => {
+			eprintln!("Usage: mangol maintenance <close-open-orders|withdraw-dust|close-account>");
+		}
+	}
+	Ok(())
+}
@@ -0,0 +1,51 @@
+//! Throughput of the liquidator's per-update work: decoding a base64 account notification, its
+//! spot open orders and scoring its health. `allocating` is the path before the buffers were
+//! reused, `reusing` the current one
+use std::mem::size_of;
+use std::time::{Duration, Instant};
+
+use mangol_mango::health::{account_health_with, decode_mango_account, decode_mango_cache, decode_mango_group};
+use mangol_mango::types::{load_open_orders_from_bytes, HealthCache, HealthType, MangoAccount, MangoCache, MangoGroup, UserActiveAssets};
+use serum_dex::state::OpenOrders;
+
+const ACCOUNTS: usize = 10_000;
+const ROUNDS: usize = 5;
+
+fn bench(name: &str, mut process: impl FnMut(&str, &[u8])) {
+	let encoded = base64::encode(vec![0u8; size_of::<MangoAccount>()]);
+	let open_orders = vec![0u8; size_of::<OpenOrders>() + 12];
+	let mut best = Duration::MAX;
+	for _ in 0..ROUNDS {
+		let started = Instant::now();
+		for _ in 0..ACCOUNTS {
+			process(&encoded, &open_orders);
+		}
+		best = best.min(started.elapsed());
+	}
+	println!("{:<12} {:>12.0} accounts/s", name, ACCOUNTS as f64 / best.as_secs_f64());
+}
+
+fn main() {
+	let mango_group = decode_mango_group(&vec![0u8; size_of::<MangoGroup>()]).unwrap();
+	let mango_cache = decode_mango_cache(&vec![0u8; size_of::<MangoCache>()]).unwrap();
+
+	bench("allocating", |encoded, open_orders_data| {
+		let data = base64::decode(encoded).unwrap();
+		let mango_account = decode_mango_account(&data).unwrap();
+		let stripped = Vec::from(&open_orders_data[5..open_orders_data.len() - 7]);
+		let open_orders = *bytemuck::from_bytes::<OpenOrders>(&stripped);
+		let mut health_cache = HealthCache::new(UserActiveAssets::new(&mango_group, &mango_account, vec![]));
+		health_cache.init_vals_with_orders_vec(&mango_group, &mango_cache, &mango_account, &[Some(open_orders)]).unwrap();
+		std::hint::black_box(health_cache.get_health(&mango_group, HealthType::Maint));
+	});
+
+	let mut buffer = vec![];
+	let mut health_cache = HealthCache::default();
+	bench("reusing", |encoded, open_orders_data| {
+		buffer.clear();
+		base64::decode_config_buf(encoded, base64::STANDARD, &mut buffer).unwrap();
+		let mango_account = decode_mango_account(&buffer).unwrap();
+		let open_orders = load_open_orders_from_bytes(open_orders_data).unwrap();
+		std::hint::black_box(account_health_with(&mut health_cache, &mango_group, &mango_cache, &mango_account, &[Some(open_orders)], HealthType::Maint).unwrap());
+	});
+}
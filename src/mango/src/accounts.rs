@@ -0,0 +1,189 @@
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use fixed::types::I80F48;
+use mangol_common::errors::{MangolError, MangolResult};
+use mangol_solana::cache::AccountCache;
+use mangol_solana::connection::SolanaConnection;
+use mangol_solana::keystore::KeyStore;
+use serde::{Deserialize, Serialize};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+
+use crate::client::MangoClient;
+use crate::interest::TokenRates;
+use crate::types::{HealthType, MangoAccount, MangoCache, MangoGroup, ZERO_I80F48};
+
+/// One MangoAccount operated by the runner, signed for by its owner or delegate key
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AccountConfig {
+	pub name: String,
+	pub mango_account: String,
+	pub keystore: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AccountsConfig {
+	pub rpc_url: String,
+	pub mango_program: String,
+	pub mango_group: String,
+	pub accounts: Vec<AccountConfig>,
+}
+
+impl AccountsConfig {
+	pub fn load(path: &str) -> MangolResult<Self> {
+		serde_json::from_str(&std::fs::read_to_string(path)?).map_err(|e| MangolError::SerializationError(e.to_string()))
+	}
+}
+
+pub struct ManagedAccount {
+	pub name: String,
+	pub mango_account_pk: Pubkey,
+	pub signer: Keypair,
+}
+
+#[derive(Clone, Debug)]
+pub struct AccountSummary {
+	pub name: String,
+	pub mango_account_pk: Pubkey,
+	pub equity: I80F48,
+	pub init_health: I80F48,
+	pub maint_health: I80F48,
+}
+
+#[derive(Clone, Debug)]
+pub struct AccountsReport {
+	pub accounts: Vec<AccountSummary>,
+	pub total_equity: I80F48,
+	/// Current lending rates, so idle collateral yield shows next to equity
+	pub rates: Vec<TokenRates>,
+}
+
+fn load_mango_account(solana_connection: &SolanaConnection, mango_account_pk: &Pubkey, mango_program_id: &Pubkey) -> MangolResult<MangoAccount> {
+	let account_info = solana_connection.rpc_client.get_account(mango_account_pk)?;
+	MangoAccount::load_checked(account_info, mango_program_id)
+		  .map_err(|e| MangolError::MangoError(format!("Failed to decode mango account {} {}", mango_account_pk, e)))
+}
+
+/// Maps every configured MangoAccount to its signer and hands out clients bound to one account
+pub struct AccountManager {
+	pub solana_connection: SolanaConnection,
+	pub mango_program_id: Pubkey,
+	pub mango_group_pk: Pubkey,
+	pub accounts: Vec<ManagedAccount>,
+	/// Group and cache are fetched once for every account in a report
+	pub account_cache: Arc<AccountCache>,
+}
+
+/// Errors unless `signer` is the owner or delegate of the account configured as `name`
+pub fn check_account_signer(name: &str, mango_account: &MangoAccount, signer: &Pubkey) -> MangolResult<()> {
+	if mango_account.owner != *signer && mango_account.delegate != *signer {
+		return Err(MangolError::KeyStoreError(format!("{} is neither owner nor delegate of {}", signer, name)));
+	}
+	Ok(())
+}
+
+fn parse_pubkey(what: &str, pubkey: &str) -> MangolResult<Pubkey> {
+	Pubkey::from_str(pubkey).map_err(|e| MangolError::MangoError(format!("{} {} {}", what, pubkey, e)))
+}
+
+impl AccountManager {
+	pub fn new(config: &AccountsConfig) -> MangolResult<Self> {
+		let solana_connection = SolanaConnection::new(&config.rpc_url)?;
+		let mango_program_id = parse_pubkey("mango_program", &config.mango_program)?;
+		let mut accounts = vec![];
+		for account_config in &config.accounts {
+			let mango_account_pk = parse_pubkey(&account_config.name, &account_config.mango_account)?;
+			let signer = KeyStore::load(&account_config.keystore)?;
+			let mango_account = load_mango_account(&solana_connection, &mango_account_pk, &mango_program_id)?;
+			check_account_signer(&account_config.name, &mango_account, &signer.pubkey())?;
+			accounts.push(ManagedAccount {
+				name: account_config.name.clone(),
+				mango_account_pk,
+				signer,
+			});
+		}
+		Ok(Self {
+			solana_connection,
+			mango_program_id,
+			mango_group_pk: parse_pubkey("mango_group", &config.mango_group)?,
+			accounts,
+			account_cache: Arc::new(AccountCache::new(Duration::from_secs(5))),
+		})
+	}
+
+	/// Clients and reports go through `solana_connection` instead, e.g. one routed by an EndpointConfig
+	pub fn with_connection(mut self, solana_connection: SolanaConnection) -> Self {
+		self.solana_connection = solana_connection;
+		self
+	}
+
+	pub fn find(&self, name: &str) -> Option<&ManagedAccount> {
+		self.accounts.iter().find(|account| account.name == name)
+	}
+
+	/// A fresh client bound to the account called `name`, for handing to a strategy
+	pub fn client_for(&self, name: &str) -> MangolResult<MangoClient> {
+		let account = self.find(name).ok_or_else(|| MangolError::KeyStoreError(format!("Unknown account {}", name)))?;
+		let (mango_group, mango_cache) = self.load_group_and_cache()?;
+		let mango_account = load_mango_account(&self.solana_connection, &account.mango_account_pk, &self.mango_program_id)?;
+		let signer = Keypair::from_bytes(&account.signer.to_bytes()).map_err(|e| MangolError::KeyStoreError(format!("{} {}", name, e)))?;
+		MangoClient::new(&self.solana_connection, mango_group, self.mango_group_pk, account.mango_account_pk, mango_group.mango_cache, mango_account, mango_cache, self.mango_program_id, signer)
+	}
+
+	fn load_group_and_cache(&self) -> MangolResult<(MangoGroup, MangoCache)> {
+		let mango_group_info = self.account_cache.get_or_fetch(&self.solana_connection.rpc_client, &self.mango_group_pk)?;
+		let mango_group = MangoGroup::load_checked(mango_group_info, &self.mango_program_id)
+			  .map_err(|e| MangolError::MangoError(format!("Failed to decode mango group {}", e)))?;
+		let mango_cache_info = self.account_cache.get_or_fetch(&self.solana_connection.rpc_client, &mango_group.mango_cache)?;
+		let mango_cache = MangoCache::load_checked(mango_cache_info, &self.mango_program_id, &mango_group)
+			  .map_err(|e| MangolError::MangoError(format!("Failed to decode mango cache {}", e)))?;
+		Ok((mango_group, mango_cache))
+	}
+
+	/// Equity and health of every managed account, summed across accounts
+	pub fn report(&self) -> MangolResult<AccountsReport> {
+		let mut summaries = vec![];
+		let mut total_equity = ZERO_I80F48;
+		let mut rates = vec![];
+		for account in &self.accounts {
+			let client = self.client_for(&account.name)?;
+			if rates.is_empty() {
+				rates = client.interest_rates()?;
+			}
+			let equity = client.get_health(HealthType::Equity)?;
+			total_equity += equity;
+			summaries.push(AccountSummary {
+				name: account.name.clone(),
+				mango_account_pk: account.mango_account_pk,
+				equity,
+				init_health: client.get_health(HealthType::Init)?,
+				maint_health: client.get_health(HealthType::Maint)?,
+			});
+		}
+		Ok(AccountsReport { accounts: summaries, total_equity, rates })
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::mem::size_of;
+	use solana_program::pubkey::Pubkey;
+	use crate::accounts::check_account_signer;
+	use crate::health::decode_mango_account;
+	use crate::types::MangoAccount;
+
+	#[test]
+	fn signs_only_for_owner_or_delegate() {
+		let mut mango_account = decode_mango_account(&vec![0u8; size_of::<MangoAccount>()]).unwrap();
+		let (owner, delegate) = (Pubkey::new_unique(), Pubkey::new_unique());
+		mango_account.owner = owner;
+		mango_account.delegate = delegate;
+		assert!(check_account_signer("fib", &mango_account, &owner).is_ok());
+		assert!(check_account_signer("fib", &mango_account, &delegate).is_ok());
+		let stranger = Pubkey::new_unique();
+		let error = check_account_signer("fib", &mango_account, &stranger).unwrap_err();
+		assert!(error.to_string().contains(&stranger.to_string()));
+	}
+}
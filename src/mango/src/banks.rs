@@ -0,0 +1,27 @@
+use fixed::types::I80F48;
+use solana_program::pubkey::Pubkey;
+
+use crate::types::{NodeBank, RootBank, ZERO_I80F48};
+
+/// A token's RootBank together with every NodeBank it lists
+#[derive(Copy, Clone)]
+pub struct TokenBanks {
+	pub token_index: usize,
+	pub root_bank_pk: Pubkey,
+	pub root_bank: RootBank,
+	pub node_banks: [Option<(Pubkey, NodeBank)>; crate::types::MAX_NODE_BANKS],
+}
+
+impl TokenBanks {
+	/// The node bank deposits and withdrawals go through
+	pub fn first_node_bank(&self) -> Option<(Pubkey, NodeBank)> {
+		self.node_banks[0]
+	}
+
+	/// Native deposits and borrows summed over all node banks
+	pub fn native_totals(&self) -> (I80F48, I80F48) {
+		self.node_banks.iter().flatten().fold((ZERO_I80F48, ZERO_I80F48), |(deposits, borrows), (_, node_bank)| {
+			(deposits + node_bank.deposits * self.root_bank.deposit_index, borrows + node_bank.borrows * self.root_bank.borrow_index)
+		})
+	}
+}
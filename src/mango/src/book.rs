@@ -0,0 +1,375 @@
+use arrayref::{array_ref, array_refs};
+use solana_program::pubkey::Pubkey;
+
+use crate::error::MangoResult;
+use crate::types::Side;
+
+pub const MAX_BOOK_NODES: usize = 1024;
+pub const NODE_SIZE: usize = 88;
+pub const BOOK_SIDE_HEADER_SIZE: usize = 40;
+const LEAF_NODE_TAG: u32 = 2;
+
+/// A resting order decoded from a LeafNode of a perp BookSide
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BookOrder {
+	pub key: i128,
+	pub owner: Pubkey,
+	pub owner_slot: u8,
+	pub order_type: u8,
+	pub time_in_force: u8,
+	pub price: i64,    // quote lots per base lot
+	pub quantity: i64, // base lots
+	pub client_order_id: u64,
+	pub timestamp: u64,
+}
+
+impl BookOrder {
+	fn from_bytes(data: &[u8; NODE_SIZE]) -> Option<Self> {
+		let (tag, owner_slot, order_type, _version, time_in_force, key, owner, quantity, client_order_id, _best_initial, timestamp) =
+			  array_refs![data, 4, 1, 1, 1, 1, 16, 32, 8, 8, 8, 8];
+		if u32::from_le_bytes(*tag) != LEAF_NODE_TAG {
+			return None;
+		}
+		let key = i128::from_le_bytes(*key);
+		Some(Self {
+			key,
+			owner: Pubkey::new_from_array(*owner),
+			owner_slot: owner_slot[0],
+			order_type: order_type[0],
+			time_in_force: time_in_force[0],
+			price: (key >> 64) as i64,
+			quantity: i64::from_le_bytes(*quantity),
+			client_order_id: u64::from_le_bytes(*client_order_id),
+			timestamp: u64::from_le_bytes(*timestamp),
+		})
+	}
+
+	pub fn is_valid(&self, now_ts: u64) -> bool {
+		self.time_in_force == 0 || now_ts < self.timestamp + self.time_in_force as u64
+	}
+}
+
+fn node_count(data: &[u8]) -> usize {
+	(data.len().saturating_sub(BOOK_SIDE_HEADER_SIZE) / NODE_SIZE).min(MAX_BOOK_NODES)
+}
+
+fn sort_side(orders: &mut [BookOrder], side: Side) {
+	match side {
+		Side::Bid => orders.sort_by(|a, b| b.key.cmp(&a.key)),
+		Side::Ask => orders.sort_by(|a, b| a.key.cmp(&b.key)),
+	}
+}
+
+/// Decodes every unexpired order of a BookSide account, best price first
+pub fn load_book_side(data: &[u8], side: Side, now_ts: u64) -> MangoResult<Vec<BookOrder>> {
+	let mut orders: Vec<BookOrder> = (0..node_count(data))
+		  .filter_map(|i| BookOrder::from_bytes(array_ref![data, BOOK_SIDE_HEADER_SIZE + i * NODE_SIZE, NODE_SIZE]))
+		  .filter(|order| order.is_valid(now_ts))
+		  .collect();
+	sort_side(&mut orders, side);
+	Ok(orders)
+}
+
+/// A BookSide kept in memory across account updates. Each update only decodes the nodes whose
+/// bytes changed since the previous one, a fill or a new order touches a handful of the 1024
+#[derive(Clone, Debug)]
+pub struct BookSideState {
+	pub side: Side,
+	data: Vec<u8>,
+	/// Decoded leaf per node index, None for inner and free nodes
+	nodes: Vec<Option<BookOrder>>,
+	/// Every leaf including expired ones, best price first
+	orders: Vec<BookOrder>,
+}
+
+impl BookSideState {
+	pub fn new(side: Side) -> Self {
+		Self { side, data: vec![], nodes: vec![], orders: vec![] }
+	}
+
+	/// Takes the account's new data, returns how many nodes changed
+	pub fn apply(&mut self, data: &[u8]) -> usize {
+		let node_count = node_count(data);
+		self.nodes.resize(node_count, None);
+		let mut changed = 0;
+		for (i, node) in self.nodes.iter_mut().enumerate() {
+			let offset = BOOK_SIDE_HEADER_SIZE + i * NODE_SIZE;
+			let bytes = &data[offset..offset + NODE_SIZE];
+			if self.data.get(offset..offset + NODE_SIZE) == Some(bytes) {
+				continue;
+			}
+			*node = BookOrder::from_bytes(array_ref![data, offset, NODE_SIZE]);
+			changed += 1;
+		}
+		if changed > 0 || self.data.len() != data.len() {
+			self.orders = self.nodes.iter().flatten().copied().collect();
+			sort_side(&mut self.orders, self.side);
+		}
+		self.data.clear();
+		self.data.extend_from_slice(data);
+		changed
+	}
+
+	/// Unexpired orders at `now_ts`, best price first
+	pub fn orders(&self, now_ts: u64) -> Vec<BookOrder> {
+		self.orders.iter().filter(|order| order.is_valid(now_ts)).copied().collect()
+	}
+}
+
+/// Expected outcome of a market order walking one side of the book, prices in lots
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FillEstimate {
+	/// Base lots the book can fill, less than asked when the side runs out
+	pub filled: i64,
+	pub average_price: f64,
+	pub best_price: i64,
+	pub worst_price: i64,
+}
+
+impl FillEstimate {
+	/// How far the average fill is from the best price, in basis points
+	pub fn impact_bps(&self) -> f64 {
+		(self.average_price - self.best_price as f64).abs() / self.best_price as f64 * 10_000.0
+	}
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OrderBook {
+	pub bids: Vec<BookOrder>,
+	pub asks: Vec<BookOrder>,
+}
+
+impl OrderBook {
+	pub fn load(bids_data: &[u8], asks_data: &[u8], now_ts: u64) -> MangoResult<Self> {
+		Ok(Self {
+			bids: load_book_side(bids_data, Side::Bid, now_ts)?,
+			asks: load_book_side(asks_data, Side::Ask, now_ts)?,
+		})
+	}
+
+	pub fn from_sides(bids: &BookSideState, asks: &BookSideState, now_ts: u64) -> Self {
+		Self { bids: bids.orders(now_ts), asks: asks.orders(now_ts) }
+	}
+
+	pub fn best_bid(&self) -> Option<i64> {
+		self.bids.first().map(|order| order.price)
+	}
+
+	pub fn best_ask(&self) -> Option<i64> {
+		self.asks.first().map(|order| order.price)
+	}
+
+	/// Mid price in lots, None if either side is empty
+	pub fn mid(&self) -> Option<f64> {
+		Some((self.best_bid()? + self.best_ask()?) as f64 / 2.0)
+	}
+
+	/// Mid weighted toward the thinner side of the top of the book, where the next trade is more
+	/// likely to print. None if either side is empty
+	pub fn microprice(&self) -> Option<f64> {
+		let (bid_price, bid_size) = *self.levels(Side::Bid, 1).first()?;
+		let (ask_price, ask_size) = *self.levels(Side::Ask, 1).first()?;
+		Some((bid_price * ask_size + ask_price * bid_size) as f64 / (bid_size + ask_size) as f64)
+	}
+
+	/// (bid size, ask size) in base lots resting within `within_bps` of the mid
+	pub fn depth_within(&self, within_bps: f64) -> Option<(i64, i64)> {
+		let mid = self.mid()?;
+		let band = mid * within_bps / 10_000.0;
+		let bid_size = self.bids.iter().filter(|o| o.price as f64 >= mid - band).map(|o| o.quantity).sum();
+		let ask_size = self.asks.iter().filter(|o| o.price as f64 <= mid + band).map(|o| o.quantity).sum();
+		Some((bid_size, ask_size))
+	}
+
+	/// Closest lot price a PostOnly order on `side` can rest at, None when `lot_price` doesn't cross the book
+	pub fn post_only_lot_price(&self, side: Side, lot_price: i64) -> Option<i64> {
+		match side {
+			Side::Bid => self.best_ask().filter(|best_ask| lot_price >= *best_ask).map(|best_ask| best_ask - 1),
+			Side::Ask => self.best_bid().filter(|best_bid| lot_price <= *best_bid).map(|best_bid| best_bid + 1),
+		}
+	}
+
+	/// Top `depth` price levels of `side` as (lot price, base lots), best first
+	pub fn levels(&self, side: Side, depth: usize) -> Vec<(i64, i64)> {
+		let orders = match side {
+			Side::Bid => &self.bids,
+			Side::Ask => &self.asks,
+		};
+		let mut levels: Vec<(i64, i64)> = vec![];
+		for order in orders {
+			if let Some((price, quantity)) = levels.last_mut() {
+				if *price == order.price {
+					*quantity += order.quantity;
+					continue;
+				}
+			}
+			if levels.len() == depth {
+				break;
+			}
+			levels.push((order.price, order.quantity));
+		}
+		levels
+	}
+
+	/// Base lots resting at `lot_price` or better on `side`, which fill before a new order there
+	pub fn size_ahead(&self, side: Side, lot_price: i64) -> i64 {
+		match side {
+			Side::Bid => self.bids.iter().filter(|o| o.price >= lot_price).map(|o| o.quantity).sum(),
+			Side::Ask => self.asks.iter().filter(|o| o.price <= lot_price).map(|o| o.quantity).sum(),
+		}
+	}
+
+	/// (bids - asks) / (bids + asks) within `within_bps` of the mid, in [-1, 1].
+	/// Positive means buyers dominate the top of the book
+	pub fn imbalance(&self, within_bps: f64) -> Option<f64> {
+		let (bid_size, ask_size) = self.depth_within(within_bps)?;
+		if bid_size + ask_size == 0 {
+			return None;
+		}
+		Some((bid_size - ask_size) as f64 / (bid_size + ask_size) as f64)
+	}
+
+	/// Fill of a `size` base lot market order on `side`, a Bid takes the asks. None when the other side is empty
+	pub fn estimate_fill(&self, side: Side, size: i64) -> Option<FillEstimate> {
+		let orders = match side {
+			Side::Bid => &self.asks,
+			Side::Ask => &self.bids,
+		};
+		let best_price = orders.first()?.price;
+		let mut filled = 0;
+		let mut cost = 0i128;
+		let mut worst_price = best_price;
+		for order in orders {
+			if filled >= size {
+				break;
+			}
+			let quantity = order.quantity.min(size - filled);
+			filled += quantity;
+			cost += quantity as i128 * order.price as i128;
+			worst_price = order.price;
+		}
+		if filled == 0 {
+			return None;
+		}
+		Some(FillEstimate { filled, average_price: cost as f64 / filled as f64, best_price, worst_price })
+	}
+
+	/// Splits a `size` base lot market order on `side` into clips the book fills within
+	/// `max_impact_bps` of its best price, into no more than `max_clips` of them
+	pub fn clip_sizes(&self, side: Side, size: i64, max_impact_bps: f64, max_clips: usize) -> Vec<i64> {
+		if size <= 0 {
+			return vec![];
+		}
+		let within = |clip: i64| self.estimate_fill(side, clip)
+			  .map(|estimate| estimate.filled == clip && estimate.impact_bps() <= max_impact_bps)
+			  .unwrap_or(false);
+		let mut clip = size;
+		if !within(size) {
+			let (mut low, mut high) = (1, size);
+			while low < high {
+				let middle = (low + high + 1) / 2;
+				if within(middle) {
+					low = middle;
+				} else {
+					high = middle - 1;
+				}
+			}
+			clip = low;
+		}
+		let max_clips = max_clips.max(1) as i64;
+		let clip = clip.max((size + max_clips - 1) / max_clips);
+		let mut clips = vec![clip; (size / clip) as usize];
+		if size % clip != 0 {
+			clips.push(size % clip);
+		}
+		clips
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use solana_program::pubkey::Pubkey;
+	use crate::book::{BookOrder, BookSideState, OrderBook, BOOK_SIDE_HEADER_SIZE, LEAF_NODE_TAG, NODE_SIZE};
+	use crate::types::Side;
+
+	fn order(price: i64, quantity: i64) -> BookOrder {
+		BookOrder {
+			key: (price as i128) << 64,
+			owner: Pubkey::default(),
+			owner_slot: 0,
+			order_type: 0,
+			time_in_force: 0,
+			price,
+			quantity,
+			client_order_id: 0,
+			timestamp: 0,
+		}
+	}
+
+	#[test]
+	fn imbalance_only_counts_orders_near_mid() {
+		let book = OrderBook {
+			bids: vec![order(9_990, 30), order(9_000, 1_000)],
+			asks: vec![order(10_010, 10), order(11_000, 1_000)],
+		};
+		assert_eq!(book.mid(), Some(10_000.0));
+		assert_eq!(book.depth_within(50.0), Some((30, 10)));
+		assert_eq!(book.imbalance(50.0), Some(0.5));
+		// 30 bid against 10 ask leans the price toward the ask
+		assert_eq!(book.microprice(), Some(10_005.0));
+	}
+
+	#[test]
+	fn aggregates_levels_up_to_depth() {
+		let book = OrderBook {
+			bids: vec![order(9_990, 30), order(9_990, 5), order(9_980, 10), order(9_000, 1_000)],
+			asks: vec![],
+		};
+		assert_eq!(book.levels(Side::Bid, 2), vec![(9_990, 35), (9_980, 10)]);
+		assert!(book.levels(Side::Ask, 2).is_empty());
+	}
+
+	#[test]
+	fn estimates_fill_and_clips_large_orders() {
+		let book = OrderBook {
+			bids: vec![order(9_990, 10)],
+			asks: vec![order(10_000, 10), order(10_100, 10), order(10_500, 100)],
+		};
+		let estimate = book.estimate_fill(Side::Bid, 20).unwrap();
+		assert_eq!(estimate.filled, 20);
+		assert_eq!(estimate.average_price, 10_050.0);
+		assert_eq!((estimate.best_price, estimate.worst_price), (10_000, 10_100));
+		assert_eq!(estimate.impact_bps(), 50.0);
+		assert_eq!(book.estimate_fill(Side::Ask, 50).unwrap().filled, 10);
+		assert_eq!(book.clip_sizes(Side::Bid, 20, 100.0, 10), vec![20]);
+		assert_eq!(book.clip_sizes(Side::Bid, 50, 50.0, 10), vec![20, 20, 10]);
+		// capped clip count wins over the impact limit
+		assert_eq!(book.clip_sizes(Side::Bid, 50, 50.0, 2), vec![25, 25]);
+	}
+
+	fn write_leaf(data: &mut [u8], index: usize, price: i64, quantity: i64) {
+		let offset = BOOK_SIDE_HEADER_SIZE + index * NODE_SIZE;
+		data[offset..offset + 4].copy_from_slice(&LEAF_NODE_TAG.to_le_bytes());
+		data[offset + 8..offset + 24].copy_from_slice(&(((price as i128) << 64) + index as i128).to_le_bytes());
+		data[offset + 56..offset + 64].copy_from_slice(&quantity.to_le_bytes());
+	}
+
+	#[test]
+	fn applies_only_changed_nodes() {
+		let mut data = vec![0_u8; BOOK_SIDE_HEADER_SIZE + 8 * NODE_SIZE];
+		write_leaf(&mut data, 0, 9_990, 10);
+		write_leaf(&mut data, 3, 9_995, 5);
+		let mut bids = BookSideState::new(Side::Bid);
+		assert_eq!(bids.apply(&data), 8);
+		assert_eq!(bids.orders(0).iter().map(|o| (o.price, o.quantity)).collect::<Vec<_>>(), vec![(9_995, 5), (9_990, 10)]);
+		assert_eq!(bids.apply(&data), 0);
+
+		// a partial fill of one order and a new one behind it
+		write_leaf(&mut data, 3, 9_995, 2);
+		write_leaf(&mut data, 5, 9_980, 7);
+		assert_eq!(bids.apply(&data), 2);
+		let book = OrderBook::from_sides(&bids, &BookSideState::new(Side::Ask), 0);
+		assert_eq!(book.levels(Side::Bid, 3), vec![(9_995, 2), (9_990, 10), (9_980, 7)]);
+		assert_eq!(book.bids, OrderBook::load(&data, &[], 0).unwrap().bids);
+	}
+}
@@ -1,15 +1,96 @@
-use mangol_common::errors::MangolResult;
+use mangol_common::errors::{MangolError, MangolResult};
 use mangol_solana::connection::SolanaConnection;
+use mangol_solana::expenses::TxExpense;
+use mangol_solana::audit::AuditLog;
 use solana_program::pubkey::Pubkey;
 use solana_sdk::signature::Keypair;
 use solana_sdk::transaction::Transaction;
+use solana_sdk::instruction::Instruction;
+use crate::queue::{event_accounts, load_events};
 use std::str::FromStr;
-use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant};
 use solana_program::clock::UnixTimestamp;
 use solana_sdk::commitment_config::CommitmentConfig;
 use crate::types::{OrderType, PerpMarketData, Side, MangoGroup, MangoCache, MangoAccount, ExpiryType, PerpMarketInfo};
 use solana_sdk::signature::Signer;
-use crate::types::PerpMarket;
+use crate::incentives::IncentiveEstimator;
+use crate::types::{PerpMarket, RootBank, NodeBank, HealthType, load_open_orders, DUST_THRESHOLD, MAX_NODE_BANKS, MAX_PAIRS, MAX_TOKENS, QUOTE_INDEX};
+use crate::registry::MarketRegistry;
+use fixed::types::I80F48;
+use serum_dex::state::OpenOrders;
+use crate::utils::get_associated_token_address;
+use crate::book::OrderBook;
+use crate::banks::TokenBanks;
+use crate::guards::{PriceBands, SelfTradeAction, SelfTradePolicy};
+use crate::locks::MarketLocks;
+use crate::health::{account_health, account_healths, AccountHealths};
+use crate::sizing::{OrderSizer, Rounding, TickRounding};
+use crate::stream::{LiveOrderBook, OracleConfidenceStream, OwnAccountEvent, OwnAccountStream, PriceStream, PriceUpdated};
+use crate::oracle::OracleConfidence;
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+use std::collections::HashMap;
+use mangol_common::clock::{Clock, SystemClock};
+use crate::interest::{token_rates, TokenRates};
+use solana_sdk::signature::Signature;
+use solana_transaction_status::UiTransactionEncoding;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::{Memcmp, MemcmpEncodedBytes, RpcFilterType};
+use solana_account_decoder::UiAccountEncoding;
+
+/// The subset of MangoClient strategies depend on, so they can run against a mock
+pub trait MangoClientApi {
+	fn update(&mut self) -> MangolResult<()>;
+	fn mango_account(&self) -> &MangoAccount;
+	fn mango_account_pk(&self) -> Pubkey;
+	fn mango_cache(&self) -> &MangoCache;
+	fn mango_group(&self) -> &MangoGroup;
+	fn place_perp_order(&self, perp_market: &PerpMarketInfo, perp_market_data: &PerpMarketData, side: Side, price: f64, quantity: i64, order_type: OrderType, reduce_only: bool, expiry_timestamp: Option<u64>) -> MangolResult<String>;
+	fn place_perp_order_with_base(&self, perp_market: &PerpMarketInfo, perp_market_data: &PerpMarketData, side: Side, price: f64, quantity: i64, order_type: OrderType, reduce_only: bool, expiry_timestamp: Option<u64>) -> MangolResult<String>;
+	/// Log messages of a sent transaction
+	fn get_transaction_logs(&self, tx_hash: &str) -> MangolResult<Vec<String>>;
+	/// Fetches the latest finalized mango account without touching the cached state
+	fn fetch_mango_account(&self) -> MangolResult<Option<MangoAccount>>;
+	fn load_order_book(&self, perp_market_data: &PerpMarketData) -> MangolResult<OrderBook>;
+	fn cancel_all_perp_orders(&self, perp_market_data: &PerpMarketData) -> MangolResult<String>;
+	fn get_equity(&self) -> MangolResult<I80F48>;
+	/// Account health in native quote
+	fn get_health(&self, health_type: HealthType) -> MangolResult<I80F48>;
+	/// Init and maint health and equity of the same account state
+	fn get_healths(&self) -> MangolResult<AccountHealths> {
+		Ok(AccountHealths {
+			init: self.get_health(HealthType::Init)?,
+			maint: self.get_health(HealthType::Maint)?,
+			equity: self.get_equity()?,
+		})
+	}
+	/// Lamports a sent transaction cost the signer
+	fn get_transaction_expense(&self, tx_hash: &str) -> MangolResult<TxExpense>;
+	fn load_perp_market(&self, perp_market_data: &PerpMarketData) -> MangolResult<PerpMarket>;
+	fn signer(&self) -> Pubkey;
+	/// Signs with `signer` from now on, or with the backup signer when None, returns the new signer's key
+	fn rotate_signer(&mut self, signer: Option<Keypair>) -> MangolResult<Pubkey>;
+}
+
+/// Who consumes the fill and out events order transactions leave on a market's event queue
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum EventConsumption {
+	/// Left to the public keepers
+	Never,
+	/// Every order transaction also consumes up to `limit` events, costs compute and fails the
+	/// order when the queue head belongs to another account
+	Bundled { limit: usize },
+	/// A separate crank calls `MangoClient::consume_events` on its own schedule
+	Cranked,
+}
+
+impl Default for EventConsumption {
+	/// Never until a crank service runs next to the bots, then Cranked
+	fn default() -> Self {
+		EventConsumption::Never
+	}
+}
+
 pub struct MangoClient {
 	pub solana_connection: SolanaConnection,
 	pub mango_account: MangoAccount,
@@ -19,13 +100,31 @@ pub struct MangoClient {
 	pub mango_group: MangoGroup,
 	pub mango_group_pk: Pubkey,
 	pub mango_program_id: Pubkey,
-	pub signer: Keypair
+	pub signer: Keypair,
+	/// Another owner or delegate key to fail over to when `signer` stops verifying
+	pub backup_signer: Option<Keypair>,
+	/// Root and node banks of every token in the group, refreshed by update every `token_banks_refresh`
+	pub token_banks: Vec<TokenBanks>,
+	pub token_banks_updated: Option<Instant>,
+	pub token_banks_refresh: Duration,
+	pub price_bands: PriceBands,
+	/// Order expiry and book staleness are computed against this
+	pub clock: Arc<dyn Clock>,
+	/// Held while an order is sent, share them between clients trading the same account
+	pub order_locks: MarketLocks,
+	pub event_consumption: EventConsumption,
+	/// Books kept from subscriptions by market index, read instead of the rpc while they are warm
+	pub live_books: HashMap<usize, LiveOrderBook>,
+	/// Checked against the book before every order that can take
+	pub self_trade_policy: SelfTradePolicy,
+	/// How order prices are moved onto the market's tick
+	pub tick_rounding: TickRounding,
 }
 
 impl MangoClient {
 	pub fn new(solana_connection: &SolanaConnection, mango_group: MangoGroup, mango_group_pk: Pubkey, mango_account_pk: Pubkey, mango_cache_pk: Pubkey, mango_account: MangoAccount, mango_cache: MangoCache, program_id: Pubkey, signer: Keypair) ->
 	MangolResult<Self> {
-		let my_connection = SolanaConnection::new(&solana_connection.rpc_client.url())?;
+		let my_connection = solana_connection.try_clone()?;
 		Ok(Self {
 			solana_connection: my_connection,
 			mango_account,
@@ -35,33 +134,198 @@ impl MangoClient {
 			mango_account_pk,
 			mango_cache_pk,
 			mango_program_id: program_id,
-			signer
+			signer,
+			backup_signer: None,
+			token_banks: vec![],
+			token_banks_updated: None,
+			token_banks_refresh: Duration::from_secs(60),
+			price_bands: PriceBands::default(),
+			clock: Arc::new(SystemClock),
+			order_locks: MarketLocks::default(),
+			event_consumption: EventConsumption::default(),
+			live_books: HashMap::new(),
+			self_trade_policy: SelfTradePolicy::default(),
+			tick_rounding: TickRounding::default(),
 		})
 	}
 	
+	pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+		self.clock = clock;
+		self
+	}
+	
+	pub fn with_order_locks(mut self, order_locks: MarketLocks) -> Self {
+		self.order_locks = order_locks;
+		self
+	}
+	
+	pub fn with_event_consumption(mut self, event_consumption: EventConsumption) -> Self {
+		self.event_consumption = event_consumption;
+		self
+	}
+	
+	/// Set per strategy, e.g. the maker keeps Allow and the stop loss client trading the same account cancels first
+	pub fn with_self_trade_policy(mut self, self_trade_policy: SelfTradePolicy) -> Self {
+		self.self_trade_policy = self_trade_policy;
+		self
+	}
+	
+	pub fn with_tick_rounding(mut self, tick_rounding: TickRounding) -> Self {
+		self.tick_rounding = tick_rounding;
+		self
+	}
+	
+	/// Keeps the books of `perp_markets` in memory from their bids and asks subscriptions
+	pub fn with_live_order_books(mut self, ws_url: &str, perp_markets: &[PerpMarketData]) -> Self {
+		let rpc_url = self.solana_connection.rpc_client.url();
+		for perp_market_data in perp_markets {
+			self.live_books.insert(perp_market_data.market_index, LiveOrderBook::start(perp_market_data, &rpc_url, ws_url));
+		}
+		self
+	}
+	
+	pub fn with_backup_signer(mut self, backup_signer: Keypair) -> Self {
+		self.backup_signer = Some(backup_signer);
+		self
+	}
+	
+	/// Swaps in `signer`, or the backup signer when None after which the replaced key becomes the
+	/// backup. Errors, keeping the current signer, when the new key can't sign for the account
+	pub fn rotate_signer(&mut self, signer: Option<Keypair>) -> MangolResult<Pubkey> {
+		let failing_over = signer.is_none();
+		let signer = match signer.or_else(|| self.backup_signer.take()) {
+			Some(signer) => signer,
+			None => return Err(MangolError::KeyStoreError("No backup signer to rotate to".to_string()))
+		};
+		if self.mango_account.owner != signer.pubkey() && self.mango_account.delegate != signer.pubkey() {
+			let pubkey = signer.pubkey();
+			if failing_over {
+				self.backup_signer = Some(signer);
+			}
+			return Err(MangolError::KeyStoreError(format!("{} is neither owner nor delegate of {}", pubkey, self.mango_account_pk)));
+		}
+		let replaced = std::mem::replace(&mut self.signer, signer);
+		if failing_over {
+			self.backup_signer = Some(replaced);
+		}
+		Ok(self.signer.pubkey())
+	}
+	
+	/// Records every transaction this client signs
+	pub fn with_audit_log(mut self, audit_log: AuditLog) -> Self {
+		self.solana_connection.audit_log = Some(audit_log);
+		self
+	}
+	
 	pub fn update(&mut self) -> MangolResult<()> {
-		let mango_account_info = self.solana_connection.rpc_client.get_account_with_commitment(&self.mango_account_pk, CommitmentConfig::finalized()).unwrap().value.unwrap();
-		self.mango_account = MangoAccount::load_checked(mango_account_info, &self.mango_program_id).unwrap();
+		let mango_account_info = self.solana_connection.get_account_after_writes(&self.mango_account_pk, CommitmentConfig::finalized())?.value.unwrap();
+		self.mango_account = MangoAccount::load_checked(mango_account_info, &self.mango_program_id)
+			  .map_err(|e| MangolError::MangoError(format!("Failed to decode mango account {}", e)))?;
 		
-		let mango_group_account_info = self.solana_connection.rpc_client.get_account_with_commitment(&self.mango_group_pk, CommitmentConfig::finalized()).unwrap().value.unwrap();
+		let mango_group_account_info = self.solana_connection.get_account_after_writes(&self.mango_group_pk, CommitmentConfig::finalized())?.value.unwrap();
 		self.mango_group = MangoGroup::load_checked(mango_group_account_info, &self.mango_program_id).unwrap();
-		let mango_cache_account_info = self.solana_connection.rpc_client.get_account_with_commitment(&self.mango_group.mango_cache, CommitmentConfig::finalized())?.value.unwrap();
+		let mango_cache_account_info = self.solana_connection.get_account_after_writes(&self.mango_group.mango_cache, CommitmentConfig::finalized())?.value.unwrap();
 		self.mango_cache = MangoCache::load_checked(mango_cache_account_info, &self.mango_program_id, &self.mango_group).unwrap();
+		if self.token_banks_updated.map(|updated| updated.elapsed() > self.token_banks_refresh).unwrap_or(true) {
+			self.token_banks = self.load_root_banks()?;
+			self.token_banks_updated = Some(Instant::now());
+		}
 		Ok(())
 	}
 	
+	/// Resolves the root bank of every token in the group and the node banks it lists
+	pub fn load_root_banks(&self) -> MangolResult<Vec<TokenBanks>> {
+		let token_indexes: Vec<usize> = (0..MAX_TOKENS).filter(|i| !self.mango_group.tokens[*i].is_empty()).collect();
+		let root_bank_pks: Vec<Pubkey> = token_indexes.iter().map(|i| self.mango_group.tokens[*i].root_bank).collect();
+		let root_bank_accounts = self.solana_connection.get_multiple_accounts_after_writes(&root_bank_pks, self.solana_connection.rpc_client.commitment())?.value;
+		let mut token_banks = vec![];
+		for ((token_index, root_bank_pk), root_bank_account) in token_indexes.into_iter().zip(root_bank_pks).zip(root_bank_accounts) {
+			let root_bank = match root_bank_account {
+				Some(account) => RootBank::load_checked(account, &self.mango_program_id).unwrap(),
+				None => return Err(MangolError::MangoError(format!("Root bank {} not found", root_bank_pk)))
+			};
+			let node_bank_pks = &root_bank.node_banks[..root_bank.num_node_banks];
+			let node_bank_accounts = self.solana_connection.get_multiple_accounts_after_writes(node_bank_pks, self.solana_connection.rpc_client.commitment())?.value;
+			let mut node_banks = [None; MAX_NODE_BANKS];
+			for (i, (node_bank_pk, node_bank_account)) in node_bank_pks.iter().zip(node_bank_accounts).enumerate() {
+				node_banks[i] = node_bank_account.map(|account| (*node_bank_pk, NodeBank::load_checked(account, &self.mango_program_id).unwrap()));
+			}
+			token_banks.push(TokenBanks { token_index, root_bank_pk, root_bank, node_banks });
+		}
+		Ok(token_banks)
+	}
+	
+	/// Cached banks of `token_index`, loaded on demand before the first update
+	pub fn token_banks_for(&self, token_index: usize) -> MangolResult<TokenBanks> {
+		if let Some(banks) = self.token_banks.iter().find(|banks| banks.token_index == token_index) {
+			return Ok(*banks);
+		}
+		self.load_root_banks()?.into_iter().find(|banks| banks.token_index == token_index)
+			  .ok_or_else(|| MangolError::MangoError(format!("No banks for token {}", token_index)))
+	}
+	
+	/// Streams order, fill, position and health events of this account over websockets
+	pub fn own_account_stream(&self, ws_url: &str, markets: Vec<PerpMarketData>) -> Receiver<OwnAccountEvent> {
+		OwnAccountStream {
+			mango_account_pk: self.mango_account_pk,
+			mango_group_pk: self.mango_group_pk,
+			mango_program_id: self.mango_program_id,
+			rpc_url: self.solana_connection.rpc_client.url(),
+			ws_url: ws_url.to_string(),
+			markets,
+			health_type: HealthType::Init,
+		}.start()
+	}
+	
+	/// Streams oracle price changes of every market in the group from the MangoCache
+	pub fn price_stream(&self, ws_url: &str) -> Receiver<Vec<PriceUpdated>> {
+		PriceStream {
+			mango_cache_pk: self.mango_cache_pk,
+			num_oracles: self.mango_group.num_oracles,
+			rpc_url: self.solana_connection.rpc_client.url(),
+			ws_url: ws_url.to_string(),
+			account_cache: None,
+		}.start()
+	}
+	
+	/// Tracks the Pyth confidence of every market's oracle, see OracleConfidence
+	pub fn oracle_confidence(&self, ws_url: &str) -> OracleConfidence {
+		OracleConfidenceStream {
+			oracles: (0..self.mango_group.num_oracles).map(|i| (i, self.mango_group.oracles[i])).collect(),
+			rpc_url: self.solana_connection.rpc_client.url(),
+			ws_url: ws_url.to_string(),
+		}.start()
+	}
+	
+	/// Deposit and borrow APR of every token from the cached banks
+	pub fn interest_rates(&self) -> MangolResult<Vec<TokenRates>> {
+		let token_banks = if self.token_banks.is_empty() { self.load_root_banks()? } else { self.token_banks.clone() };
+		Ok(token_banks.iter().map(token_rates).collect())
+	}
+	
+	fn node_bank_for(&self, token_index: usize) -> MangolResult<(Pubkey, NodeBank)> {
+		self.token_banks_for(token_index)?.first_node_bank()
+			  .ok_or_else(|| MangolError::MangoError(format!("No node bank for token {}", token_index)))
+	}
+	
 	pub fn place_perp_order(&self, perp_market: &PerpMarketInfo, perp_market_data: &PerpMarketData, side: Side, price: f64, quantity: i64, order_type: OrderType, reduce_only: bool, expiry_timestamp: Option<u64>) -> MangolResult<String> {
-		let (native_price, native_quantity) = perp_market.lotToNativePriceQuantity(price, quantity.try_into().unwrap());
-		println!("Order price: {} Order quantity: {}", price * 1000.0, quantity / native_price);
+		self.price_bands.check(order_type, price, self.mango_cache.get_price(perp_market_data.market_index))?;
+		let _order_lock = self.order_locks.acquire(perp_market_data.market_index)?;
+		let sizer = OrderSizer::new(perp_market);
+		let price_lots = sizer.tick_price_lots(price, side, order_type, self.tick_rounding)?;
+		let (cancels, price_lots, order_type) = self.prevent_self_trade(perp_market_data, side, price_lots, order_type)?;
+		// quantity is in quote lots, never exceed it when converting to base
+		let max_base_quantity = sizer.base_lots_from_quote_lots(quantity, price_lots, Rounding::Down)?;
+		println!("Order price: {} Order quantity: {}", price * 1000.0, max_base_quantity);
 		let mut expires_at = None;
 		if expiry_timestamp.is_some() {
-			expires_at = Some(SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() + expiry_timestamp.unwrap());
+			expires_at = Some(self.clock.now_ts() + expiry_timestamp.unwrap());
 		}
 		let instruction = crate::instructions::place_perp_order2(
 			&self.mango_program_id,
 			&self.mango_group_pk,
 			&self.mango_account_pk,
-			&self.mango_account.owner,
+			&self.signer.pubkey(),
 			&self.mango_cache_pk,
 			&Pubkey::from_str(&perp_market_data.pubkey).unwrap(),
 			&Pubkey::from_str(&perp_market_data.bids_key.clone()).unwrap(),
@@ -70,8 +334,8 @@ impl MangoClient {
 			None,
 			&self.mango_account.spot_open_orders,
 			side,
-			native_price,
-			quantity / native_price,
+			price_lots,
+			max_base_quantity,
 			quantity,
 			0,
 			order_type,
@@ -79,34 +343,28 @@ impl MangoClient {
 			expires_at,
 			10,
 			ExpiryType::Absolute).unwrap();
-		// let mut mango_accounts_to_consume_events = [self.mango_account_pk.clone()];
-		// let consume_instruction = crate::instructions::consume_events(
-		// 	&self.mango_program_id,
-		// 	&self.mango_group_pk,
-		// 	&self.mango_group.mango_cache,
-		// 	&Pubkey::from_str(&perp_market_data.pubkey).unwrap(),
-		// 	&Pubkey::from_str(&perp_market_data.events_key.clone()).unwrap(),
-		// 	&mut mango_accounts_to_consume_events,
-		// 	14
-		// ).unwrap();
-		let mut transaction = Transaction::new_with_payer(&[instruction], Some(&self.signer.pubkey()));
-		self.solana_connection.try_tx_once(transaction, &self.signer)
+		self.send_order(cancels, instruction, perp_market_data)
 		
 	}
 	
 
 	
 	pub fn place_perp_order_with_base(&self, perp_market: &PerpMarketInfo, perp_market_data: &PerpMarketData, side: Side, price: f64, quantity: i64, order_type: OrderType, reduce_only: bool, expiry_timestamp: Option<u64>) -> MangolResult<String> {
-		let (native_price, native_quantity) = perp_market.lotToNativePriceQuantity(price, quantity.try_into().unwrap());
+		self.price_bands.check(order_type, price, self.mango_cache.get_price(perp_market_data.market_index))?;
+		let _order_lock = self.order_locks.acquire(perp_market_data.market_index)?;
+		let sizer = OrderSizer::new(perp_market);
+		let price_lots = sizer.tick_price_lots(price, side, order_type, self.tick_rounding)?;
+		let (cancels, price_lots, order_type) = self.prevent_self_trade(perp_market_data, side, price_lots, order_type)?;
+		let max_quote_quantity = sizer.quote_lots_from_base_lots(quantity, price_lots)?;
 		let mut expires_at = None;
 		if (expiry_timestamp.is_some()) {
-			expires_at = Some(SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() + expiry_timestamp.unwrap());
+			expires_at = Some(self.clock.now_ts() + expiry_timestamp.unwrap());
 		}
 		let instruction = crate::instructions::place_perp_order2(
 			&self.mango_program_id,
 			&self.mango_group_pk,
 			&self.mango_account_pk,
-			&self.mango_account.owner,
+			&self.signer.pubkey(),
 			&self.mango_cache_pk,
 			&Pubkey::from_str(&perp_market_data.pubkey).unwrap(),
 			&Pubkey::from_str(&perp_market_data.bids_key.clone()).unwrap(),
@@ -115,32 +373,458 @@ impl MangoClient {
 			None,
 			&self.mango_account.spot_open_orders,
 			side,
-			native_price,
+			price_lots,
 			quantity,
-			quantity * native_price,
+			max_quote_quantity,
 			0,
 			order_type,
 			reduce_only,
 			expires_at,
 			10,
 			ExpiryType::Absolute).unwrap();
-		// let mut mango_accounts_to_consume_events = [self.mango_account_pk.clone()];
-		//
-		// let consume_instruction = crate::instructions::consume_events(
-		// 	&self.mango_program_id,
-		// 	&self.mango_group_pk,
-		// 	&self.mango_group.mango_cache,
-		// 	&Pubkey::from_str(&perp_market_data.pubkey).unwrap(),
-		// 	&Pubkey::from_str(&perp_market_data.events_key.clone()).unwrap(),
-		// 	&mut mango_accounts_to_consume_events,
-		// 	14
-		// ).unwrap();
-		let mut transaction = Transaction::new_with_payer(&[instruction], Some(&self.signer.pubkey()));
-		self.solana_connection.try_tx_once(transaction, &self.signer)
+		self.send_order(cancels, instruction, perp_market_data)
 		
 	}
 	
-
+	/// Cancels of own orders, the new limit in lots and order type that keep an order on `side` from
+	/// filling against the account itself, following self_trade_policy
+	fn prevent_self_trade(&self, perp_market_data: &PerpMarketData, side: Side, price_lots: i64, order_type: OrderType) -> MangolResult<(Vec<Instruction>, i64, OrderType)> {
+		if self.self_trade_policy == SelfTradePolicy::Allow || matches!(order_type, OrderType::PostOnly | OrderType::PostOnlySlide) {
+			return Ok((vec![], price_lots, order_type));
+		}
+		let (_, book) = self.load_order_book_with_slot(perp_market_data)?;
+		match self.self_trade_policy.resolve(&book, &self.mango_account_pk, side, price_lots, order_type)? {
+			SelfTradeAction::Send => Ok((vec![], price_lots, order_type)),
+			SelfTradeAction::Cancel(order_ids) => {
+				println!("[?] Cancelling {} own orders the {:?} would fill against", order_ids.len(), side);
+				let cancels = order_ids.iter().map(|order_id| crate::instructions::cancel_perp_order(
+					&self.mango_program_id,
+					&self.mango_group_pk,
+					&self.mango_account_pk,
+					&self.signer.pubkey(),
+					&Pubkey::from_str(&perp_market_data.pubkey).unwrap(),
+					&Pubkey::from_str(&perp_market_data.bids_key).unwrap(),
+					&Pubkey::from_str(&perp_market_data.asks_key).unwrap(),
+					*order_id,
+					// filled or expired since the book was read
+					true,
+				).map_err(|e| MangolError::MangoError(format!("cancel_perp_order instruction {}", e)))).collect::<MangolResult<Vec<_>>>()?;
+				Ok((cancels, price_lots, order_type))
+			}
+			SelfTradeAction::Reprice(adjusted) => {
+				println!("[?] Limiting the {:?} to {} lots, in front of own orders", side, adjusted);
+				let order_type = if order_type == OrderType::Market { OrderType::ImmediateOrCancel } else { order_type };
+				Ok((vec![], adjusted, order_type))
+			}
+		}
+	}
+	
+	/// Sends `instruction` after `cancels`, with a consume_events of the account's own events when they are bundled
+	fn send_order(&self, cancels: Vec<Instruction>, instruction: Instruction, perp_market_data: &PerpMarketData) -> MangolResult<String> {
+		let mut instructions = cancels;
+		instructions.push(instruction);
+		if let EventConsumption::Bundled { limit } = self.event_consumption {
+			instructions.push(self.consume_events_instruction(perp_market_data, &mut [self.mango_account_pk], limit)?);
+		}
+		let transaction = Transaction::new_with_payer(&instructions, Some(&self.signer.pubkey()));
+		self.solana_connection.try_tx_once(transaction, &self.signer)
+	}
+	
+	fn consume_events_instruction(&self, perp_market_data: &PerpMarketData, mango_accounts: &mut [Pubkey], limit: usize) -> MangolResult<Instruction> {
+		crate::instructions::consume_events(
+			&self.mango_program_id,
+			&self.mango_group_pk,
+			&self.mango_group.mango_cache,
+			&Pubkey::from_str(&perp_market_data.pubkey).unwrap(),
+			&Pubkey::from_str(&perp_market_data.events_key).unwrap(),
+			mango_accounts,
+			limit,
+		).map_err(|e| MangolError::MangoError(format!("consume_events instruction {}", e)))
+	}
+	
+	/// Consumes up to `limit` events of the market, passing every account they touch, for a crank.
+	/// None when the queue is empty
+	pub fn consume_events(&self, perp_market_data: &PerpMarketData, limit: usize) -> MangolResult<Option<String>> {
+		let event_queue_pk = Pubkey::from_str(&perp_market_data.events_key).unwrap();
+		let event_queue = self.solana_connection.get_account_after_writes(&event_queue_pk, self.solana_connection.rpc_client.commitment())?.value
+			  .ok_or_else(|| MangolError::MangoError(format!("Event queue {} not found", event_queue_pk)))?;
+		let (_, events) = load_events(&event_queue.data).map_err(|e| MangolError::MangoError(format!("Failed to decode event queue {:?}", e)))?;
+		let mut mango_accounts = event_accounts(&events, limit);
+		if mango_accounts.is_empty() {
+			return Ok(None);
+		}
+		let instruction = self.consume_events_instruction(perp_market_data, &mut mango_accounts, limit)?;
+		let transaction = Transaction::new_with_payer(&[instruction], Some(&self.signer.pubkey()));
+		self.solana_connection.try_tx_once(transaction, &self.signer).map(Some)
+	}
+	
+	pub fn cancel_all_perp_orders(&self, perp_market_data: &PerpMarketData) -> MangolResult<String> {
+		let instruction = crate::instructions::cancel_all_perp_orders(
+			&self.mango_program_id,
+			&self.mango_group_pk,
+			&self.mango_account_pk,
+			&self.signer.pubkey(),
+			&Pubkey::from_str(&perp_market_data.pubkey).unwrap(),
+			&Pubkey::from_str(&perp_market_data.bids_key).unwrap(),
+			&Pubkey::from_str(&perp_market_data.asks_key).unwrap(),
+			20).unwrap();
+		let transaction = Transaction::new_with_payer(&[instruction], Some(&self.signer.pubkey()));
+		self.solana_connection.try_tx_once(transaction, &self.signer)
+	}
+	
+	/// Deposits `quantity` native tokens of `token_index` from the signer's associated token account
+	pub fn deposit(&self, token_index: usize, quantity: u64) -> MangolResult<String> {
+		let token_info = &self.mango_group.tokens[token_index];
+		let (node_bank_pk, node_bank) = self.node_bank_for(token_index)?;
+		let owner_token_account = get_associated_token_address(&self.signer.pubkey(), &token_info.mint);
+		let instruction = crate::instructions::deposit(
+			&self.mango_program_id,
+			&self.mango_group_pk,
+			&self.mango_account_pk,
+			&self.signer.pubkey(),
+			&self.mango_cache_pk,
+			&token_info.root_bank,
+			&node_bank_pk,
+			&node_bank.vault,
+			&owner_token_account,
+			quantity).unwrap();
+		let transaction = Transaction::new_with_payer(&[instruction], Some(&self.signer.pubkey()));
+		self.solana_connection.try_tx_once(transaction, &self.signer)
+	}
+	
+	/// Native balance of the signer's associated token account for `mint`, 0 if it doesn't exist
+	pub fn get_wallet_token_balance(&self, mint: &Pubkey) -> MangolResult<u64> {
+		let token_account = get_associated_token_address(&self.signer.pubkey(), mint);
+		match self.solana_connection.rpc_client.get_token_account_balance(&token_account) {
+			Ok(balance) => Ok(balance.amount.parse::<u64>().unwrap()),
+			Err(_) => Ok(0)
+		}
+	}
+	
+	pub fn load_open_orders(&self) -> MangolResult<Vec<Option<OpenOrders>>> {
+		let mut open_orders = vec![];
+		for open_orders_pk in &self.mango_account.spot_open_orders {
+			if *open_orders_pk == Pubkey::default() {
+				open_orders.push(None)
+			} else {
+				let open_orders_account = self.solana_connection.get_account_after_writes(open_orders_pk, self.solana_connection.rpc_client.commitment())?.value
+					  .ok_or_else(|| MangolError::MangoError(format!("Open orders {} not found", open_orders_pk)))?;
+				open_orders.push(Some(load_open_orders(open_orders_account).unwrap()))
+			}
+		}
+		Ok(open_orders)
+	}
+	
+	/// Health of the cached account state, HealthType::Equity gives the account equity in native quote
+	pub fn get_health(&self, health_type: HealthType) -> MangolResult<I80F48> {
+		let open_orders = self.load_open_orders()?;
+		account_health(&self.mango_group, &self.mango_cache, &self.mango_account, &open_orders, health_type)
+	}
+	
+	/// Every health type of the cached account state, loading its open orders once
+	pub fn get_healths(&self) -> MangolResult<AccountHealths> {
+		let open_orders = self.load_open_orders()?;
+		account_healths(&self.mango_group, &self.mango_cache, &self.mango_account, &open_orders)
+	}
+	
+	/// Closes spot open orders accounts that hold no funds or orders, reclaiming their rent
+	pub fn close_empty_spot_open_orders(&self) -> MangolResult<Vec<String>> {
+		let open_orders = self.load_open_orders()?;
+		let mut signatures = vec![];
+		for (i, open_orders_account) in open_orders.iter().enumerate() {
+			let is_empty = match open_orders_account {
+				Some(oo) => oo.native_coin_total == 0 && oo.native_pc_total == 0 && oo.free_slot_bits == u128::MAX,
+				None => false
+			};
+			if !is_empty {
+				continue;
+			}
+			let instruction = crate::instructions::close_spot_open_orders(
+				&self.mango_program_id,
+				&self.mango_group_pk,
+				&self.mango_account_pk,
+				&self.signer.pubkey(),
+				&self.mango_group.dex_program_id,
+				&self.mango_account.spot_open_orders[i],
+				&self.mango_group.spot_markets[i].spot_market,
+				&self.mango_group.signer_key).unwrap();
+			let transaction = Transaction::new_with_payer(&[instruction], Some(&self.signer.pubkey()));
+			signatures.push(self.solana_connection.try_tx_once(transaction, &self.signer)?);
+		}
+		Ok(signatures)
+	}
+	
+	/// Withdraws `quantity` native tokens of `token_index` to the signer's associated token account
+	pub fn withdraw(&self, token_index: usize, quantity: u64, allow_borrow: bool) -> MangolResult<String> {
+		let token_info = &self.mango_group.tokens[token_index];
+		let (node_bank_pk, node_bank) = self.node_bank_for(token_index)?;
+		let token_account = get_associated_token_address(&self.signer.pubkey(), &token_info.mint);
+		let instruction = crate::instructions::withdraw(
+			&self.mango_program_id,
+			&self.mango_group_pk,
+			&self.mango_account_pk,
+			&self.signer.pubkey(),
+			&self.mango_cache_pk,
+			&token_info.root_bank,
+			&node_bank_pk,
+			&node_bank.vault,
+			&token_account,
+			&self.mango_group.signer_key,
+			&self.mango_account.spot_open_orders,
+			quantity,
+			allow_borrow).unwrap();
+		let transaction = Transaction::new_with_payer(&[instruction], Some(&self.signer.pubkey()));
+		self.solana_connection.try_tx_once(transaction, &self.signer)
+	}
+	
+	/// Withdraws every deposit worth less than `max_value` native quote.
+	/// Whatever stays behind is below a whole native unit and counts as empty once under DUST_THRESHOLD
+	pub fn withdraw_dust(&self, max_value: f64) -> MangolResult<Vec<String>> {
+		let mut signatures = vec![];
+		for i in 0..MAX_TOKENS {
+			if self.mango_group.tokens[i].is_empty() {
+				continue;
+			}
+			let native_deposit = self.mango_account.get_native_deposit(&self.mango_cache.root_bank_cache[i], i).unwrap();
+			let withdrawable = native_deposit.checked_floor().unwrap().to_num::<u64>();
+			if withdrawable == 0 || withdrawable as f64 * self.mango_cache.get_price(i) >= max_value {
+				continue;
+			}
+			println!("[?] Withdrawing dust {} of token {}", withdrawable, self.mango_group.tokens[i].mint);
+			signatures.push(self.withdraw(i, withdrawable, false)?);
+		}
+		Ok(signatures)
+	}
+	
+	/// True when the account holds nothing and can be closed to reclaim rent
+	pub fn can_close_mango_account(&self) -> bool {
+		self.mango_account.meta_data.version > 0
+			  && !self.mango_account.not_upgradable
+			  && !self.mango_account.being_liquidated
+			  && !self.mango_account.is_bankrupt
+			  && self.mango_account.spot_open_orders.iter().all(|pk| *pk == Pubkey::default())
+			  && self.mango_account.perp_accounts.iter().all(|pa| !pa.is_active())
+			  && self.mango_account.advanced_orders_key == Pubkey::default()
+			  && (0..MAX_TOKENS).all(|i| self.mango_account.deposits[i] < DUST_THRESHOLD && self.mango_account.borrows[i] < DUST_THRESHOLD)
+	}
+	
+	pub fn close_mango_account(&self) -> MangolResult<String> {
+		if !self.can_close_mango_account() {
+			return Err(MangolError::MangoError("Mango account is not empty or not closeable".to_string()));
+		}
+		let instruction = crate::instructions::close_mango_account(
+			&self.mango_program_id,
+			&self.mango_group_pk,
+			&self.mango_account_pk,
+			&self.signer.pubkey()).unwrap();
+		let transaction = Transaction::new_with_payer(&[instruction], Some(&self.signer.pubkey()));
+		self.solana_connection.try_tx_once(transaction, &self.signer)
+	}
+	
+	/// Both book sides read in one call, with the slot they were read at
+	/// The live book when it has caught up with the last write, the rpc otherwise
+	pub fn load_order_book_with_slot(&self, perp_market_data: &PerpMarketData) -> MangolResult<(u64, OrderBook)> {
+		if let Some(live_book) = self.live_books.get(&perp_market_data.market_index) {
+			let min_slot = self.solana_connection.write_slot.min_context_slot().unwrap_or(0);
+			if let Some((slot, book)) = live_book.book(self.clock.now_ts()).filter(|(slot, _)| *slot >= min_slot) {
+				return Ok((slot, book));
+			}
+		}
+		let book_keys = [Pubkey::from_str(&perp_market_data.bids_key).unwrap(), Pubkey::from_str(&perp_market_data.asks_key).unwrap()];
+		let response = self.solana_connection.get_multiple_accounts_after_writes(&book_keys, self.solana_connection.rpc_client.commitment())?;
+		let now_ts = self.clock.now_ts();
+		match (&response.value[0], &response.value[1]) {
+			(Some(bids), Some(asks)) => Ok((response.context.slot, OrderBook::load(&bids.data, &asks.data, now_ts).unwrap())),
+			_ => Err(MangolError::MangoError(format!("Order book not found for {}", perp_market_data.name)))
+		}
+	}
+	
+	pub fn load_perp_market(&self, perp_market_data: &PerpMarketData) -> MangolResult<PerpMarket> {
+		let perp_market_pk = Pubkey::from_str(&perp_market_data.pubkey).unwrap();
+		let account = self.solana_connection.get_account_after_writes(&perp_market_pk, self.solana_connection.rpc_client.commitment())?.value
+			  .ok_or_else(|| MangolError::MangoError(format!("Perp market {} not found", perp_market_data.name)))?;
+		PerpMarket::load_checked(account, &self.mango_program_id, &self.mango_group_pk)
+			  .map_err(|e| MangolError::MangoError(format!("Failed to load perp market {} {:?}", perp_market_data.name, e)))
+	}
 	
+	/// Registry of the perp markets the group lists, read from the chain for groups without a registry file
+	pub fn load_market_registry(&self) -> MangolResult<MarketRegistry> {
+		let listed: Vec<(usize, Pubkey)> = (0..MAX_PAIRS)
+			  .map(|market_index| (market_index, self.mango_group.perp_markets[market_index].perp_market))
+			  .filter(|(_, perp_market_pk)| *perp_market_pk != Pubkey::default())
+			  .collect();
+		let perp_market_pks: Vec<Pubkey> = listed.iter().map(|(_, perp_market_pk)| *perp_market_pk).collect();
+		let accounts = self.solana_connection.get_multiple_accounts_after_writes(&perp_market_pks, self.solana_connection.rpc_client.commitment())?.value;
+		let mut perp_markets = vec![];
+		for ((market_index, perp_market_pk), account) in listed.into_iter().zip(accounts) {
+			let account = account.ok_or_else(|| MangolError::MangoError(format!("Perp market {} not found", perp_market_pk)))?;
+			let perp_market = PerpMarket::load_checked(account, &self.mango_program_id, &self.mango_group_pk)
+				  .map_err(|e| MangolError::MangoError(format!("Failed to load perp market {} {:?}", perp_market_pk, e)))?;
+			perp_markets.push((market_index, perp_market_pk, perp_market));
+		}
+		MarketRegistry::from_group(&self.mango_group, &perp_markets)
+	}
+	
+	/// Liquidity mining estimator for `perp_market_data` with the market's current incentive parameters
+	pub fn incentive_estimator(&self, perp_market_data: &PerpMarketData) -> MangolResult<IncentiveEstimator> {
+		Ok(IncentiveEstimator::new(self.load_perp_market(perp_market_data)?))
+	}
+	
+	/// Unsettled pnl of `market_index` in native quote, funding included
+	pub fn get_unsettled_pnl(&self, market_index: usize) -> I80F48 {
+		perp_unsettled_pnl(&self.mango_account, &self.mango_group, &self.mango_cache, market_index)
+	}
+	
+	/// The group's account with the largest pnl of the opposite sign to `pnl` in `market_index`
+	pub fn find_settle_counterparty(&self, market_index: usize, pnl: I80F48) -> MangolResult<Option<(Pubkey, I80F48)>> {
+		let config = RpcProgramAccountsConfig {
+			filters: Some(vec![
+				RpcFilterType::DataSize(std::mem::size_of::<MangoAccount>() as u64),
+				// mango_group follows the 8 byte MetaData
+				RpcFilterType::Memcmp(Memcmp { offset: 8, bytes: MemcmpEncodedBytes::Base58(self.mango_group_pk.to_string()), encoding: None }),
+			]),
+			account_config: RpcAccountInfoConfig {
+				encoding: Some(UiAccountEncoding::Base64),
+				data_slice: None,
+				commitment: Some(CommitmentConfig::confirmed()),
+				min_context_slot: None
+			},
+			with_context: None
+		};
+		let accounts = self.solana_connection.scan_program_accounts(&self.mango_program_id, &config)?;
+		let mut best: Option<(Pubkey, I80F48)> = None;
+		for scanned in accounts {
+			let (pubkey, account) = match scanned {
+				Ok(scanned) => scanned,
+				Err(e) => {
+					eprintln!("Failed to load a batch of mango accounts {:?}", e);
+					continue
+				}
+			};
+			if pubkey == self.mango_account_pk {
+				continue;
+			}
+			let mango_account = match MangoAccount::load_checked(account, &self.mango_program_id) {
+				Ok(mango_account) => mango_account,
+				Err(_) => continue
+			};
+			let other_pnl = perp_unsettled_pnl(&mango_account, &self.mango_group, &self.mango_cache, market_index);
+			if other_pnl.is_zero() || other_pnl.is_negative() == pnl.is_negative() {
+				continue;
+			}
+			if best.map(|(_, best_pnl)| other_pnl.abs() > best_pnl.abs()).unwrap_or(true) {
+				best = Some((pubkey, other_pnl));
+			}
+		}
+		Ok(best)
+	}
+	
+	/// Settles this account's pnl in `market_index` against `counterparty`, which must hold the opposite sign
+	pub fn settle_pnl(&self, market_index: usize, counterparty: &Pubkey) -> MangolResult<String> {
+		let quote_info = &self.mango_group.tokens[QUOTE_INDEX];
+		let (node_bank_pk, _) = self.node_bank_for(QUOTE_INDEX)?;
+		// settle_pnl expects the positive pnl account first
+		let (account_a, account_b) = if self.get_unsettled_pnl(market_index).is_positive() {
+			(self.mango_account_pk, *counterparty)
+		} else {
+			(*counterparty, self.mango_account_pk)
+		};
+		let instruction = crate::instructions::settle_pnl(
+			&self.mango_program_id,
+			&self.mango_group_pk,
+			&account_a,
+			&account_b,
+			&self.mango_cache_pk,
+			&quote_info.root_bank,
+			&node_bank_pk,
+			market_index).unwrap();
+		let transaction = Transaction::new_with_payer(&[instruction], Some(&self.signer.pubkey()));
+		self.solana_connection.try_tx_once(transaction, &self.signer)
+	}
+}
 
-}
\ No newline at end of file
+/// base_position valued at the cached price plus the funding adjusted quote position
+pub fn perp_unsettled_pnl(mango_account: &MangoAccount, mango_group: &MangoGroup, mango_cache: &MangoCache, market_index: usize) -> I80F48 {
+	let perp_account = &mango_account.perp_accounts[market_index];
+	let price = mango_cache.price_cache[market_index].price;
+	let base_native = I80F48::from_num(perp_account.base_position * mango_group.perp_markets[market_index].base_lot_size);
+	base_native * price + perp_account.get_quote_position(&mango_cache.perp_market_cache[market_index])
+}
+
+impl MangoClientApi for MangoClient {
+	fn update(&mut self) -> MangolResult<()> {
+		MangoClient::update(self)
+	}
+	
+	fn mango_account(&self) -> &MangoAccount {
+		&self.mango_account
+	}
+	
+	fn mango_account_pk(&self) -> Pubkey {
+		self.mango_account_pk
+	}
+	
+	fn mango_cache(&self) -> &MangoCache {
+		&self.mango_cache
+	}
+	
+	fn mango_group(&self) -> &MangoGroup {
+		&self.mango_group
+	}
+	
+	fn place_perp_order(&self, perp_market: &PerpMarketInfo, perp_market_data: &PerpMarketData, side: Side, price: f64, quantity: i64, order_type: OrderType, reduce_only: bool, expiry_timestamp: Option<u64>) -> MangolResult<String> {
+		MangoClient::place_perp_order(self, perp_market, perp_market_data, side, price, quantity, order_type, reduce_only, expiry_timestamp)
+	}
+	
+	fn place_perp_order_with_base(&self, perp_market: &PerpMarketInfo, perp_market_data: &PerpMarketData, side: Side, price: f64, quantity: i64, order_type: OrderType, reduce_only: bool, expiry_timestamp: Option<u64>) -> MangolResult<String> {
+		MangoClient::place_perp_order_with_base(self, perp_market, perp_market_data, side, price, quantity, order_type, reduce_only, expiry_timestamp)
+	}
+	
+	fn get_transaction_expense(&self, tx_hash: &str) -> MangolResult<TxExpense> {
+		self.solana_connection.get_transaction_expense(tx_hash)
+	}
+	
+	fn get_transaction_logs(&self, tx_hash: &str) -> MangolResult<Vec<String>> {
+		let order_tx = self.solana_connection.rpc_client.get_transaction(&Signature::from_str(tx_hash).unwrap(), UiTransactionEncoding::Base64)?;
+		Ok(order_tx.transaction.meta.and_then(|meta| meta.log_messages).unwrap_or_default())
+	}
+	
+	fn fetch_mango_account(&self) -> MangolResult<Option<MangoAccount>> {
+		let mango_account_info = self.solana_connection.get_account_after_writes(&self.mango_account_pk, CommitmentConfig::finalized())?;
+		mango_account_info.value
+			  .map(|account| MangoAccount::load_checked(account, &self.mango_program_id).map_err(|e| MangolError::MangoError(format!("Failed to decode mango account {}", e))))
+			  .transpose()
+	}
+	
+	fn load_order_book(&self, perp_market_data: &PerpMarketData) -> MangolResult<OrderBook> {
+		Ok(self.load_order_book_with_slot(perp_market_data)?.1)
+	}
+	
+	fn cancel_all_perp_orders(&self, perp_market_data: &PerpMarketData) -> MangolResult<String> {
+		MangoClient::cancel_all_perp_orders(self, perp_market_data)
+	}
+	
+	fn get_equity(&self) -> MangolResult<I80F48> {
+		MangoClient::get_health(self, HealthType::Equity)
+	}
+	
+	fn get_health(&self, health_type: HealthType) -> MangolResult<I80F48> {
+		MangoClient::get_health(self, health_type)
+	}
+	
+	fn get_healths(&self) -> MangolResult<AccountHealths> {
+		MangoClient::get_healths(self)
+	}
+	
+	fn load_perp_market(&self, perp_market_data: &PerpMarketData) -> MangolResult<PerpMarket> {
+		MangoClient::load_perp_market(self, perp_market_data)
+	}
+	
+	fn signer(&self) -> Pubkey {
+		self.signer.pubkey()
+	}
+	
+	fn rotate_signer(&mut self, signer: Option<Keypair>) -> MangolResult<Pubkey> {
+		MangoClient::rotate_signer(self, signer)
+	}
+}
@@ -36,6 +36,13 @@ pub enum MangoError {
 	ProgramError(#[from] ProgramError),
 	#[error("{mango_error_code}; {source_file_id}:{line}")]
 	MangoErrorCode { mango_error_code: MangoErrorCode, line: u32, source_file_id: SourceFileId },
+	/// The account is of a layout this crate doesn't decode, e.g. after a program upgrade
+	#[error("{account} data is {actual} bytes, expected {expected}")]
+	UnexpectedSize { account: &'static str, expected: usize, actual: usize },
+	#[error("{account} has data type {data_type}")]
+	UnexpectedDataType { account: &'static str, data_type: u8 },
+	#[error("{account} version {version} is not supported")]
+	UnsupportedVersion { account: &'static str, version: u8 },
 }
 
 #[derive(Debug, Error, Clone, Copy, PartialEq, Eq, IntoPrimitive)]
@@ -145,6 +152,7 @@ impl From<MangoError> for ProgramError {
 			MangoError::MangoErrorCode { mango_error_code, line: _, source_file_id: _ } => {
 				ProgramError::Custom(mango_error_code.into())
 			}
+			MangoError::UnexpectedSize { .. } | MangoError::UnexpectedDataType { .. } | MangoError::UnsupportedVersion { .. } => ProgramError::InvalidAccountData,
 		}
 	}
 }
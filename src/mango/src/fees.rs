@@ -0,0 +1,70 @@
+use fixed::types::I80F48;
+
+use crate::ids::mngo_token;
+use crate::types::{MangoAccount, MangoCache, MangoGroup, CENTIBPS_PER_UNIT, ZERO_I80F48};
+
+/// Fee rates of one perp market for a given account, as fractions of notional
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FeeModel {
+	pub maker_fee: f64,
+	pub taker_fee: f64,
+	/// Added to taker fees while the account holds less than `ref_mngo_required` MNGO
+	pub ref_surcharge: f64,
+}
+
+impl FeeModel {
+	/// Mirrors the taker fee logic of `HealthCache::get_health_after_sim_perp`, assuming no referrer
+	pub fn new(mango_group: &MangoGroup, mango_cache: &MangoCache, mango_account: &MangoAccount, market_index: usize) -> Self {
+		let info = &mango_group.perp_markets[market_index];
+		let mut ref_surcharge = ZERO_I80F48;
+		if let Some(mngo_index) = mango_group.find_token_index(&mngo_token::id()) {
+			let mngo_cache = &mango_cache.root_bank_cache[mngo_index];
+			let mngo_deposits = mango_account.get_native_deposit(mngo_cache, mngo_index).unwrap_or(ZERO_I80F48);
+			if mngo_deposits < I80F48::from_num(mango_group.ref_mngo_required) {
+				ref_surcharge = I80F48::from_num(mango_group.ref_surcharge_centibps) / CENTIBPS_PER_UNIT;
+			}
+		}
+		Self {
+			maker_fee: info.maker_fee.to_num::<f64>(),
+			taker_fee: info.taker_fee.to_num::<f64>(),
+			ref_surcharge: ref_surcharge.to_num::<f64>(),
+		}
+	}
+
+	pub fn effective_taker_fee(&self) -> f64 {
+		self.taker_fee + self.ref_surcharge
+	}
+
+	pub fn fee(&self, is_taker: bool) -> f64 {
+		if is_taker { self.effective_taker_fee() } else { self.maker_fee }
+	}
+
+	/// Highest price a short entered at `entry_price` can be bought back at without losing to fees
+	pub fn max_profitable_bid(&self, entry_price: f64, entry_is_taker: bool, exit_is_taker: bool) -> f64 {
+		entry_price * (1.0 - self.fee(entry_is_taker)) / (1.0 + self.fee(exit_is_taker))
+	}
+
+	/// Lowest price a long entered at `entry_price` can be sold at without losing to fees
+	pub fn min_profitable_ask(&self, entry_price: f64, entry_is_taker: bool, exit_is_taker: bool) -> f64 {
+		entry_price * (1.0 + self.fee(entry_is_taker)) / (1.0 - self.fee(exit_is_taker))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::fees::FeeModel;
+
+	#[test]
+	fn profit_targets_cover_round_trip_fees() {
+		let fee_model = FeeModel { maker_fee: -0.0004, taker_fee: 0.0005, ref_surcharge: 0.0001 };
+		let bid = fee_model.max_profitable_bid(100.0, true, false);
+		assert!(bid < 100.0);
+		// pnl of a short entered as taker and closed as maker is exactly zero at the target
+		let pnl = 100.0 - bid - 100.0 * fee_model.effective_taker_fee() - bid * fee_model.maker_fee;
+		assert!(pnl.abs() < 1e-9);
+
+		let ask = fee_model.min_profitable_ask(100.0, true, true);
+		let pnl = ask - 100.0 - 100.0 * fee_model.effective_taker_fee() - ask * fee_model.effective_taker_fee();
+		assert!(pnl.abs() < 1e-9);
+	}
+}
@@ -0,0 +1,174 @@
+use mangol_common::errors::{MangolError, MangolResult};
+use solana_program::pubkey::Pubkey;
+
+use crate::book::{BookOrder, OrderBook};
+use crate::types::{OrderType, Side};
+
+/// Largest allowed relative distance between an order price and the cached oracle price,
+/// catches fat fingers and lots vs native mixups before they reach the book
+#[derive(Copy, Clone, Debug)]
+pub struct PriceBands {
+	pub limit: f64,
+	pub post_only: f64,
+	/// Market orders ignore the price on chain but it still sizes max_base_quantity
+	pub taker: f64,
+}
+
+impl Default for PriceBands {
+	fn default() -> Self {
+		Self {
+			limit: 0.05,
+			// deep fib rungs rest ~20% away from the oracle
+			post_only: 0.25,
+			taker: 0.02,
+		}
+	}
+}
+
+impl PriceBands {
+	pub fn band(&self, order_type: OrderType) -> f64 {
+		match order_type {
+			OrderType::Limit => self.limit,
+			OrderType::PostOnly | OrderType::PostOnlySlide => self.post_only,
+			OrderType::ImmediateOrCancel | OrderType::Market => self.taker,
+		}
+	}
+
+	pub fn check(&self, order_type: OrderType, price: f64, oracle_price: f64) -> MangolResult<()> {
+		if !price.is_finite() || price <= 0.0 || oracle_price <= 0.0 {
+			return Err(MangolError::MangoError(format!("Refusing {:?} order at price {} with oracle {}", order_type, price, oracle_price)));
+		}
+		let distance = (price - oracle_price).abs() / oracle_price;
+		if distance > self.band(order_type) {
+			return Err(MangolError::MangoError(format!("Refusing {:?} order at {}, {:.2}% away from oracle {}", order_type, price, distance * 100.0, oracle_price)));
+		}
+		Ok(())
+	}
+}
+
+/// What an order that can take liquidity does about the account's own resting orders it would fill
+/// against, e.g. a stop loss hitting the maker quotes of the same account, paying fees on both sides for nothing
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SelfTradePolicy {
+	/// Send the order as is
+	Allow,
+	/// Cancel the crossed own orders in the same transaction, before the order
+	CancelFirst,
+	/// Pull the order's limit back to just before the best own order, it only takes what rests in front
+	PriceAdjust,
+}
+
+impl Default for SelfTradePolicy {
+	fn default() -> Self {
+		SelfTradePolicy::Allow
+	}
+}
+
+/// How to send an order that would trade with its own account
+#[derive(Clone, Debug, PartialEq)]
+pub enum SelfTradeAction {
+	Send,
+	/// Order ids to cancel first
+	Cancel(Vec<i128>),
+	/// New limit in lots, an ImmediateOrCancel in place of a Market order
+	Reprice(i64),
+}
+
+/// Own orders on the other side of the book an order on `side` limited at `lot_price` fills against,
+/// best first. A None limit, a Market order, reaches all of them
+pub fn own_crossed_orders(book: &OrderBook, owner: &Pubkey, side: Side, lot_price: Option<i64>) -> Vec<BookOrder> {
+	let (resting, crosses): (&Vec<BookOrder>, fn(i64, i64) -> bool) = match side {
+		Side::Bid => (&book.asks, |resting, limit| resting <= limit),
+		Side::Ask => (&book.bids, |resting, limit| resting >= limit),
+	};
+	resting.iter()
+		  .filter(|order| order.owner == *owner)
+		  .filter(|order| lot_price.map(|limit| crosses(order.price, limit)).unwrap_or(true))
+		  .copied()
+		  .collect()
+}
+
+impl SelfTradePolicy {
+	pub fn parse(policy: &str) -> Option<Self> {
+		match policy.trim() {
+			"allow" => Some(SelfTradePolicy::Allow),
+			"cancel-first" => Some(SelfTradePolicy::CancelFirst),
+			"price-adjust" => Some(SelfTradePolicy::PriceAdjust),
+			_ => None
+		}
+	}
+
+	/// Post only orders never take, they are always sent as is
+	pub fn resolve(&self, book: &OrderBook, owner: &Pubkey, side: Side, lot_price: i64, order_type: OrderType) -> MangolResult<SelfTradeAction> {
+		let limit = match order_type {
+			OrderType::PostOnly | OrderType::PostOnlySlide => return Ok(SelfTradeAction::Send),
+			OrderType::Market => None,
+			OrderType::Limit | OrderType::ImmediateOrCancel => Some(lot_price),
+		};
+		let crossed = own_crossed_orders(book, owner, side, limit);
+		let best_own = match crossed.first() {
+			Some(best_own) if *self != SelfTradePolicy::Allow => best_own.price,
+			_ => return Ok(SelfTradeAction::Send)
+		};
+		match self {
+			SelfTradePolicy::CancelFirst => Ok(SelfTradeAction::Cancel(crossed.iter().map(|order| order.key).collect())),
+			_ => {
+				let adjusted = match side {
+					Side::Bid => limit.unwrap_or(i64::MAX).min(best_own - 1),
+					Side::Ask => limit.unwrap_or(0).max(best_own + 1),
+				};
+				if adjusted <= 0 {
+					return Err(MangolError::MangoError(format!("No price left for a {:?} in front of own order at {}", side, best_own)));
+				}
+				Ok(SelfTradeAction::Reprice(adjusted))
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use solana_program::pubkey::Pubkey;
+	use crate::book::{BookOrder, OrderBook};
+	use crate::guards::{PriceBands, SelfTradeAction, SelfTradePolicy};
+	use crate::types::{OrderType, Side};
+
+	#[test]
+	fn rejects_prices_outside_band() {
+		let bands = PriceBands::default();
+		assert!(bands.check(OrderType::PostOnly, 0.042, 0.04).is_ok());
+		assert!(bands.check(OrderType::Market, 0.042, 0.04).is_err());
+		// a native price passed where lots were expected
+		assert!(bands.check(OrderType::Limit, 4_000.0, 0.04).is_err());
+		assert!(bands.check(OrderType::Limit, 0.0, 0.04).is_err());
+	}
+
+	fn order(owner: Pubkey, price: i64, side: Side) -> BookOrder {
+		BookOrder {
+			key: ((price as i128) << 64) | if side == Side::Bid { 1 } else { 2 },
+			owner,
+			owner_slot: 0,
+			order_type: 0,
+			time_in_force: 0,
+			price,
+			quantity: 10,
+			client_order_id: 0,
+			timestamp: 0,
+		}
+	}
+
+	#[test]
+	fn takers_stop_short_of_or_cancel_own_orders() {
+		let (own, other) = (Pubkey::new_unique(), Pubkey::new_unique());
+		let book = OrderBook {
+			bids: vec![order(other, 99, Side::Bid)],
+			asks: vec![order(other, 101, Side::Ask), order(own, 102, Side::Ask), order(own, 104, Side::Ask)],
+		};
+		assert_eq!(SelfTradePolicy::CancelFirst.resolve(&book, &own, Side::Bid, 101, OrderType::ImmediateOrCancel).unwrap(), SelfTradeAction::Send);
+		assert_eq!(SelfTradePolicy::CancelFirst.resolve(&book, &own, Side::Bid, 103, OrderType::Limit).unwrap(), SelfTradeAction::Cancel(vec![book.asks[1].key]));
+		assert_eq!(SelfTradePolicy::CancelFirst.resolve(&book, &own, Side::Bid, 0, OrderType::Market).unwrap(), SelfTradeAction::Cancel(vec![book.asks[1].key, book.asks[2].key]));
+		assert_eq!(SelfTradePolicy::PriceAdjust.resolve(&book, &own, Side::Bid, 0, OrderType::Market).unwrap(), SelfTradeAction::Reprice(101));
+		assert_eq!(SelfTradePolicy::PriceAdjust.resolve(&book, &own, Side::Bid, 110, OrderType::PostOnly).unwrap(), SelfTradeAction::Send);
+		assert_eq!(SelfTradePolicy::Allow.resolve(&book, &own, Side::Bid, 110, OrderType::Limit).unwrap(), SelfTradeAction::Send);
+	}
+}
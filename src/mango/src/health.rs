@@ -0,0 +1,88 @@
+use std::mem::size_of;
+
+use fixed::types::I80F48;
+use mangol_common::errors::{MangolError, MangolResult};
+use mangol_common::Loadable;
+use serum_dex::state::OpenOrders;
+
+use crate::types::{HealthCache, HealthType, MangoAccount, MangoCache, MangoGroup, UserActiveAssets};
+
+/// Decodes raw account data, erroring on a size mismatch instead of panicking in bytemuck
+fn decode<T: Loadable + Copy>(data: &[u8], name: &str) -> MangolResult<T> {
+	if data.len() != size_of::<T>() {
+		return Err(MangolError::MangoError(format!("{} data is {} bytes, expected {}", name, data.len(), size_of::<T>())));
+	}
+	T::load_from_bytes(data).map(|value| *value).map_err(|e| MangolError::MangoError(format!("{} {:?}", name, e)))
+}
+
+pub fn decode_mango_group(data: &[u8]) -> MangolResult<MangoGroup> {
+	decode(data, "MangoGroup")
+}
+
+pub fn decode_mango_cache(data: &[u8]) -> MangolResult<MangoCache> {
+	decode(data, "MangoCache")
+}
+
+pub fn decode_mango_account(data: &[u8]) -> MangolResult<MangoAccount> {
+	decode(data, "MangoAccount")
+}
+
+/// Health the way the program computes it. Works on decoded accounts only, so it builds without
+/// the rpc client (`--no-default-features`) and runs in a browser as well as in the bot.
+/// `open_orders` is indexed by spot market, None where the account has none
+pub fn account_health(mango_group: &MangoGroup, mango_cache: &MangoCache, mango_account: &MangoAccount, open_orders: &[Option<OpenOrders>], health_type: HealthType) -> MangolResult<I80F48> {
+	account_health_with(&mut HealthCache::default(), mango_group, mango_cache, mango_account, open_orders, health_type)
+}
+
+/// `account_health` reusing `health_cache`, for loops scoring many accounts
+pub fn account_health_with(health_cache: &mut HealthCache, mango_group: &MangoGroup, mango_cache: &MangoCache, mango_account: &MangoAccount, open_orders: &[Option<OpenOrders>], health_type: HealthType) -> MangolResult<I80F48> {
+	health_cache.reset(UserActiveAssets::new(mango_group, mango_account, vec![]));
+	health_cache.init_vals_with_orders_vec(mango_group, mango_cache, mango_account, open_orders)
+		  .map_err(|e| MangolError::MangoError(format!("{:?}", e)))?;
+	Ok(health_cache.get_health(mango_group, health_type))
+}
+
+/// Init and maint health and equity of one account state, in native quote
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AccountHealths {
+	pub init: I80F48,
+	pub maint: I80F48,
+	pub equity: I80F48,
+}
+
+/// Every health type from one health cache, its values are computed once and only weighted per type
+pub fn account_healths(mango_group: &MangoGroup, mango_cache: &MangoCache, mango_account: &MangoAccount, open_orders: &[Option<OpenOrders>]) -> MangolResult<AccountHealths> {
+	let mut health_cache = HealthCache::default();
+	let init = account_health_with(&mut health_cache, mango_group, mango_cache, mango_account, open_orders, HealthType::Init)?;
+	Ok(AccountHealths {
+		init,
+		maint: health_cache.get_health(mango_group, HealthType::Maint),
+		equity: health_cache.get_health(mango_group, HealthType::Equity),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use std::mem::size_of;
+	use fixed::types::I80F48;
+	use crate::health::{account_health, account_healths, decode_mango_account, decode_mango_cache, decode_mango_group};
+	use crate::types::{HealthType, MangoAccount, MangoCache, MangoGroup, QUOTE_INDEX};
+
+	#[test]
+	fn rejects_wrong_sized_account_data() {
+		assert!(decode_mango_account(&[0u8; 16]).is_err());
+	}
+
+	#[test]
+	fn computes_every_health_type_at_once() {
+		let mango_group = decode_mango_group(&vec![0u8; size_of::<MangoGroup>()]).unwrap();
+		let mut mango_cache = decode_mango_cache(&vec![0u8; size_of::<MangoCache>()]).unwrap();
+		mango_cache.root_bank_cache[QUOTE_INDEX].deposit_index = I80F48::from_num(1);
+		let mut mango_account = decode_mango_account(&vec![0u8; size_of::<MangoAccount>()]).unwrap();
+		mango_account.deposits[QUOTE_INDEX] = I80F48::from_num(1_000_000);
+		let healths = account_healths(&mango_group, &mango_cache, &mango_account, &[]).unwrap();
+		assert_eq!(healths.equity, account_health(&mango_group, &mango_cache, &mango_account, &[], HealthType::Equity).unwrap());
+		assert_eq!(healths.maint, account_health(&mango_group, &mango_cache, &mango_account, &[], HealthType::Maint).unwrap());
+		assert_eq!(healths.init, account_health(&mango_group, &mango_cache, &mango_account, &[], HealthType::Init).unwrap());
+	}
+}
@@ -0,0 +1,281 @@
+use std::path::PathBuf;
+use std::sync::mpsc::channel;
+
+use fixed::types::I80F48;
+use mangol_common::errors::{MangolError, MangolResult};
+use mangol_solana::connection::SolanaConnection;
+use mangol_solana::endpoints::OperationClass;
+use mangol_solana::subscription::ResilientSubscription;
+use serde::Deserialize;
+use serum_dex::state::OpenOrders;
+use solana_client::rpc_client::GetConfirmedSignaturesForAddress2Config;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::commitment_config::CommitmentConfig;
+
+use crate::health::{account_health, decode_mango_account, decode_mango_cache, decode_mango_group};
+use crate::stream::forward;
+use crate::types::{load_open_orders_from_bytes, HealthType, MangoAccount, MangoCache, MangoGroup};
+
+/// Account data as it was at a past slot, `slot` being where that data was captured or last written
+#[derive(Clone, Debug, PartialEq)]
+pub struct HistoricalAccount {
+	pub slot: u64,
+	pub data: Vec<u8>,
+}
+
+/// Where the state of an account at a past slot comes from
+pub trait AccountHistory {
+	/// The account as of `slot`, None when the source has nothing at or before it
+	fn account_at(&self, pubkey: &Pubkey, slot: u64) -> MangolResult<Option<HistoricalAccount>>;
+}
+
+/// An archival rpc node. Transactions don't carry account data, so the current data only stands for
+/// `slot` when no transaction has touched the account since, which getSignaturesForAddress tells.
+/// Fine for the group and idle accounts, the cache is written every few slots and needs snapshots
+pub struct ArchivalRpc<'a> {
+	pub connection: &'a SolanaConnection,
+}
+
+impl<'a> ArchivalRpc<'a> {
+	pub fn new(connection: &'a SolanaConnection) -> Self {
+		Self { connection }
+	}
+}
+
+impl<'a> AccountHistory for ArchivalRpc<'a> {
+	fn account_at(&self, pubkey: &Pubkey, slot: u64) -> MangolResult<Option<HistoricalAccount>> {
+		let rpc = self.connection.rpc(OperationClass::Scan);
+		let account = match rpc.get_account_with_commitment(pubkey, CommitmentConfig::finalized())?.value {
+			Some(account) => account,
+			None => return Ok(None)
+		};
+		let latest = rpc.get_signatures_for_address_with_config(pubkey, GetConfirmedSignaturesForAddress2Config {
+			before: None,
+			until: None,
+			limit: Some(1),
+			commitment: Some(CommitmentConfig::finalized()),
+		})?;
+		match latest.first() {
+			Some(signature) if signature.slot > slot => Err(MangolError::MangoError(format!(
+				"{} was touched at slot {} after {}, its state then needs a snapshot provider", pubkey, signature.slot, slot
+			))),
+			Some(signature) => Ok(Some(HistoricalAccount { slot: signature.slot, data: account.data })),
+			// never touched by a transaction the node kept, the data predates its history
+			None => Ok(Some(HistoricalAccount { slot: 0, data: account.data }))
+		}
+	}
+}
+
+#[derive(Deserialize)]
+struct SnapshotResponse {
+	slot: u64,
+	/// base64
+	data: String,
+}
+
+/// A snapshot service answering `GET <url>/accounts/<pubkey>?slot=<slot>` with
+/// `{"slot": .., "data": "<base64>"}`, the latest capture at or before the slot, or 404
+pub struct SnapshotProvider {
+	pub url: String,
+}
+
+impl SnapshotProvider {
+	pub fn new(url: &str) -> Self {
+		Self { url: url.trim_end_matches('/').to_string() }
+	}
+}
+
+impl AccountHistory for SnapshotProvider {
+	fn account_at(&self, pubkey: &Pubkey, slot: u64) -> MangolResult<Option<HistoricalAccount>> {
+		let response = reqwest::blocking::get(format!("{}/accounts/{}?slot={}", self.url, pubkey, slot))
+			  .map_err(|e| MangolError::MangoError(format!("Snapshot provider {}", e)))?;
+		if response.status() == reqwest::StatusCode::NOT_FOUND {
+			return Ok(None);
+		}
+		let snapshot: SnapshotResponse = response.error_for_status()
+			  .and_then(|response| response.json())
+			  .map_err(|e| MangolError::MangoError(format!("Snapshot provider {}", e)))?;
+		let data = base64::decode(&snapshot.data).map_err(|e| MangolError::SerializationError(e.to_string()))?;
+		Ok(Some(HistoricalAccount { slot: snapshot.slot, data }))
+	}
+}
+
+/// Captures kept on disk as `<dir>/<pubkey>/<slot>`, e.g. by a process storing accounts it
+/// already streams, so incidents can be replayed without a snapshot service
+pub struct SnapshotDir {
+	pub dir: PathBuf,
+}
+
+impl SnapshotDir {
+	pub fn new(dir: PathBuf) -> Self {
+		Self { dir }
+	}
+
+	pub fn store(&self, pubkey: &Pubkey, slot: u64, data: &[u8]) -> MangolResult<()> {
+		let account_dir = self.dir.join(pubkey.to_string());
+		std::fs::create_dir_all(&account_dir)?;
+		std::fs::write(account_dir.join(slot.to_string()), data)?;
+		Ok(())
+	}
+}
+
+impl AccountHistory for SnapshotDir {
+	fn account_at(&self, pubkey: &Pubkey, slot: u64) -> MangolResult<Option<HistoricalAccount>> {
+		let account_dir = self.dir.join(pubkey.to_string());
+		if !account_dir.exists() {
+			return Ok(None);
+		}
+		let mut latest = None;
+		for entry in std::fs::read_dir(&account_dir)? {
+			let captured = entry?.file_name().to_str().and_then(|name| name.parse::<u64>().ok());
+			if let Some(captured) = captured.filter(|captured| *captured <= slot) {
+				latest = latest.max(Some(captured));
+			}
+		}
+		match latest {
+			Some(captured) => Ok(Some(HistoricalAccount { slot: captured, data: std::fs::read(account_dir.join(captured.to_string()))? })),
+			None => Ok(None)
+		}
+	}
+}
+
+/// Streams accounts into a SnapshotDir, each update stored at the slot it was seen at, so
+/// `history` answers for slots the archival rpc can't once the accounts were written again
+pub struct SnapshotRecorder {
+	pub snapshot_dir: SnapshotDir,
+	pub rpc_url: String,
+	pub ws_url: String,
+}
+
+impl SnapshotRecorder {
+	pub fn new(snapshot_dir: SnapshotDir, rpc_url: &str, ws_url: &str) -> Self {
+		Self { snapshot_dir, rpc_url: rpc_url.to_string(), ws_url: ws_url.to_string() }
+	}
+
+	/// What a HistoricalState of `mango_account` is built from, its open orders as of now
+	pub fn accounts_of(mango_group_pk: &Pubkey, mango_group: &MangoGroup, mango_account_pk: &Pubkey, mango_account: &MangoAccount) -> Vec<Pubkey> {
+		let mut accounts = vec![*mango_group_pk, mango_group.mango_cache, *mango_account_pk];
+		accounts.extend(mango_account.spot_open_orders.iter().filter(|open_orders_pk| **open_orders_pk != Pubkey::default()));
+		accounts
+	}
+
+	/// Stores every update of `accounts` until the subscriptions stop
+	pub fn record(&self, accounts: &[Pubkey]) {
+		let (sender, updates) = channel();
+		for pubkey in accounts {
+			forward(ResilientSubscription::new(*pubkey, &self.rpc_url, &self.ws_url), sender.clone());
+		}
+		drop(sender);
+		for update in updates {
+			if let Err(e) = self.snapshot_dir.store(&update.pubkey, update.slot, &update.account.data) {
+				eprintln!("[-] Failed to store {} at slot {} {:?}", update.pubkey, update.slot, e);
+			}
+		}
+	}
+}
+
+/// Sources tried in order, the first with an answer wins. An error is only returned when none answered
+pub struct FallbackHistory<'a> {
+	pub sources: Vec<Box<dyn AccountHistory + 'a>>,
+}
+
+impl<'a> AccountHistory for FallbackHistory<'a> {
+	fn account_at(&self, pubkey: &Pubkey, slot: u64) -> MangolResult<Option<HistoricalAccount>> {
+		let mut last_error = None;
+		for source in &self.sources {
+			match source.account_at(pubkey, slot) {
+				Ok(Some(account)) => return Ok(Some(account)),
+				Ok(None) => {}
+				Err(e) => last_error = Some(e)
+			}
+		}
+		match last_error {
+			Some(e) => Err(e),
+			None => Ok(None)
+		}
+	}
+}
+
+/// Group, cache, account and open orders as they were at `slot`, for recomputing health and
+/// decisions in a post-mortem
+pub struct HistoricalState {
+	pub slot: u64,
+	pub mango_group: MangoGroup,
+	pub mango_cache: MangoCache,
+	pub mango_account: MangoAccount,
+	/// Indexed by spot market
+	pub open_orders: Vec<Option<OpenOrders>>,
+	/// Slot each account was captured or last written at, the cache's shows how stale prices were
+	pub sources: Vec<(Pubkey, u64)>,
+}
+
+fn required(history: &dyn AccountHistory, pubkey: &Pubkey, slot: u64, name: &str) -> MangolResult<HistoricalAccount> {
+	history.account_at(pubkey, slot)?.ok_or_else(|| MangolError::MangoError(format!("No {} {} at or before slot {}", name, pubkey, slot)))
+}
+
+impl HistoricalState {
+	pub fn fetch(history: &dyn AccountHistory, mango_group_pk: &Pubkey, mango_account_pk: &Pubkey, slot: u64) -> MangolResult<Self> {
+		let group = required(history, mango_group_pk, slot, "mango group")?;
+		let mango_group = decode_mango_group(&group.data)?;
+		let account = required(history, mango_account_pk, slot, "mango account")?;
+		let mango_account = decode_mango_account(&account.data)?;
+		let cache = required(history, &mango_group.mango_cache, slot, "mango cache")?;
+		let mango_cache = decode_mango_cache(&cache.data)?;
+		let mut sources = vec![(*mango_group_pk, group.slot), (*mango_account_pk, account.slot), (mango_group.mango_cache, cache.slot)];
+		let mut open_orders = vec![];
+		for open_orders_pk in &mango_account.spot_open_orders {
+			if *open_orders_pk == Pubkey::default() {
+				open_orders.push(None);
+				continue;
+			}
+			let captured = required(history, open_orders_pk, slot, "open orders")?;
+			let decoded = load_open_orders_from_bytes(&captured.data).map_err(|e| MangolError::MangoError(format!("Open orders {} {:?}", open_orders_pk, e)))?;
+			sources.push((*open_orders_pk, captured.slot));
+			open_orders.push(Some(decoded));
+		}
+		Ok(Self { slot, mango_group, mango_cache, mango_account, open_orders, sources })
+	}
+
+	pub fn health(&self, health_type: HealthType) -> MangolResult<I80F48> {
+		account_health(&self.mango_group, &self.mango_cache, &self.mango_account, &self.open_orders, health_type)
+	}
+
+	/// Slots the oldest input lags `slot` by
+	pub fn staleness(&self) -> u64 {
+		self.sources.iter().map(|(_, captured)| self.slot.saturating_sub(*captured)).max().unwrap_or(0)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use bytemuck::Zeroable;
+	use solana_program::pubkey::Pubkey;
+	use crate::history::{AccountHistory, FallbackHistory, SnapshotDir, SnapshotRecorder};
+	use crate::types::{MangoAccount, MangoGroup};
+
+	#[test]
+	fn picks_the_latest_capture_at_or_before_the_slot() {
+		let dir = std::env::temp_dir().join(format!("mangol-history-{}", std::process::id()));
+		let snapshots = SnapshotDir::new(dir.clone());
+		let pubkey = Pubkey::new_unique();
+		snapshots.store(&pubkey, 100, &[1]).unwrap();
+		snapshots.store(&pubkey, 200, &[2]).unwrap();
+		assert_eq!(snapshots.account_at(&pubkey, 150).unwrap().unwrap().data, vec![1]);
+		assert_eq!(snapshots.account_at(&pubkey, 200).unwrap().unwrap().slot, 200);
+		assert!(snapshots.account_at(&pubkey, 99).unwrap().is_none());
+		let fallback = FallbackHistory { sources: vec![Box::new(SnapshotDir::new(dir.join("empty"))), Box::new(SnapshotDir::new(dir.clone()))] };
+		assert_eq!(fallback.account_at(&pubkey, 250).unwrap().unwrap().data, vec![2]);
+		std::fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn records_what_the_state_is_built_from() {
+		let mut mango_group = MangoGroup::zeroed();
+		mango_group.mango_cache = Pubkey::new_unique();
+		let mut mango_account = MangoAccount::zeroed();
+		mango_account.spot_open_orders[2] = Pubkey::new_unique();
+		let (mango_group_pk, mango_account_pk) = (Pubkey::new_unique(), Pubkey::new_unique());
+		let accounts = SnapshotRecorder::accounts_of(&mango_group_pk, &mango_group, &mango_account_pk, &mango_account);
+		assert_eq!(accounts, vec![mango_group_pk, mango_group.mango_cache, mango_account_pk, mango_account.spot_open_orders[2]]);
+	}
+}
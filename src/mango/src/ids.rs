@@ -22,6 +22,12 @@ pub mod mngo_token {
 	declare_id!("MangoCzJ36AjZyKwVj3VnYU4GTonjfVEnJmvvWaxLac");
 }
 
+/// The native mint, the same on every cluster
+pub mod wrapped_sol {
+	use solana_program::declare_id;
+	declare_id!("So11111111111111111111111111111111111111112");
+}
+
 pub mod luna_spot_market {
 	use solana_program::declare_id;
 	declare_id!("HBTu8hNaoT3VyiSSzJYa8jwt9sDGKtJviSwFa11iXdmE");
@@ -42,4 +48,9 @@ pub mod luna_root_bank {
 pub mod luna_pyth_oracle {
 	use solana_program::declare_id;
 	declare_id!("5bmWuR1dgP4avtGYMNKLuxumZTVKGgoN2BCMXWDNL9nY");
-}
\ No newline at end of file
+}
+
+pub mod associated_token_program {
+	use solana_program::declare_id;
+	declare_id!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+}
@@ -0,0 +1,233 @@
+use bytemuck::Zeroable;
+use fixed::types::I80F48;
+
+use crate::book::OrderBook;
+use crate::types::{PerpAccount, PerpMarket, Side};
+
+/// A candidate quote, price in quote lots per base lot and quantity in base lots
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct QuotePlacement {
+	pub side: Side,
+	pub price: i64,
+	pub quantity: i64,
+}
+
+/// Most ticks away from the edge price `optimal_quote` scans before giving up
+const MAX_SCAN_TICKS: i64 = 10_000;
+
+/// The placement that earns the most MNGO over the horizon, `depth_bps` measured from the mid
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OptimalQuote {
+	pub placement: QuotePlacement,
+	pub depth_bps: f64,
+	pub mngo_per_sec: f64,
+}
+
+/// Client side copy of the program's liquidity mining math, run on copies of the market
+/// so a maker can compare the MNGO expected from different quote placements
+pub struct IncentiveEstimator {
+	pub perp_market: PerpMarket,
+}
+
+impl IncentiveEstimator {
+	pub fn new(perp_market: PerpMarket) -> Self {
+		Self { perp_market }
+	}
+
+	/// MNGO (native) earned if `placement` rests for `rest_secs` and then fills, with the book unchanged.
+	/// Version 0 markets reward distance to the best price, later ones the size resting ahead
+	pub fn expected_mngo(&self, placement: &QuotePlacement, book: &OrderBook, rest_secs: u64, now_ts: u64) -> u64 {
+		let mut perp_market = self.perp_market;
+		let mut perp_account = PerpAccount::zeroed();
+		let time_initial = now_ts.saturating_sub(rest_secs);
+		let result = if perp_market.meta_data.version == 0 {
+			let best = match placement.side {
+				Side::Bid => book.best_bid().unwrap_or(placement.price).max(placement.price),
+				Side::Ask => book.best_ask().unwrap_or(placement.price).min(placement.price),
+			};
+			perp_account.apply_price_incentives(&mut perp_market, placement.side, placement.price, best, best, time_initial, now_ts, placement.quantity)
+		} else {
+			let size_ahead = book.size_ahead(placement.side, placement.price);
+			perp_account.apply_size_incentives(&mut perp_market, size_ahead, size_ahead, time_initial, now_ts, placement.quantity)
+		};
+		match result {
+			Ok(()) => perp_account.mngo_accrued,
+			Err(_) => 0
+		}
+	}
+
+	/// The candidate with the most MNGO per unit of `risk`, e.g. notional or distance from fair value
+	pub fn best_placement<F: Fn(&QuotePlacement) -> f64>(&self, candidates: &[QuotePlacement], book: &OrderBook, rest_secs: u64, now_ts: u64, risk: F) -> Option<(QuotePlacement, f64)> {
+		candidates.iter()
+			  .filter(|candidate| risk(candidate) > 0.0)
+			  .map(|candidate| (*candidate, self.expected_mngo(candidate, book, rest_secs, now_ts) as f64 / risk(candidate)))
+			  .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+	}
+
+	/// Seconds until `placement` fills when `traded_lots_per_sec` base lots a second trade against its
+	/// side, everything resting at its price or better fills first
+	pub fn fill_secs(placement: &QuotePlacement, book: &OrderBook, traded_lots_per_sec: f64) -> f64 {
+		(book.size_ahead(placement.side, placement.price) + placement.quantity) as f64 / traded_lots_per_sec.max(f64::EPSILON)
+	}
+
+	/// Scans `side` tick by tick away from the mid for the price `quantity` base lots earn the most
+	/// MNGO at over `horizon_secs`. A quote earns until it fills: near the mid it earns faster but
+	/// fills sooner behind little size, deeper it rests longer behind the size ahead of it. Quotes
+	/// nearer the mid than `min_edge_bps` are mostly taken by informed flow and aren't considered.
+	/// The scan stops past the market's max depth, where nothing is earned
+	pub fn optimal_quote(&self, side: Side, quantity: i64, book: &OrderBook, horizon_secs: u64, now_ts: u64, min_edge_bps: f64, traded_lots_per_sec: f64) -> Option<OptimalQuote> {
+		let mid = book.mid()?;
+		let horizon_secs = horizon_secs.max(1);
+		let (edge_price, step) = match side {
+			Side::Bid => ((mid * (1.0 - min_edge_bps / 10_000.0)).floor() as i64, -1),
+			Side::Ask => ((mid * (1.0 + min_edge_bps / 10_000.0)).ceil() as i64, 1),
+		};
+		let mut best: Option<(QuotePlacement, u64)> = None;
+		for tick in 0..MAX_SCAN_TICKS {
+			let price = edge_price + step * tick;
+			if price <= 0 {
+				break;
+			}
+			let placement = QuotePlacement { side, price, quantity };
+			let rest_secs = (Self::fill_secs(&placement, book, traded_lots_per_sec).ceil() as u64).clamp(1, horizon_secs);
+			let mngo = self.expected_mngo(&placement, book, rest_secs, now_ts);
+			if mngo == 0 && best.is_some() {
+				break;
+			}
+			if mngo > 0 && best.map(|(_, best_mngo)| mngo > best_mngo).unwrap_or(true) {
+				best = Some((placement, mngo));
+			}
+		}
+		best.map(|(placement, mngo)| OptimalQuote {
+			placement,
+			depth_bps: (placement.price as f64 - mid).abs() / mid * 10_000.0,
+			mngo_per_sec: mngo as f64 / horizon_secs as f64,
+		})
+	}
+
+	/// Identifies the incentive period the market is in at `now_ts`: the rate, the period's start
+	/// and how many target lengths have passed since, which moves once the period runs over
+	pub fn period_key(&self, now_ts: u64) -> (I80F48, u64, u64) {
+		let info = &self.perp_market.liquidity_mining_info;
+		let elapsed = now_ts.saturating_sub(info.period_start) / info.target_period_length.max(1);
+		(info.rate, info.period_start, elapsed)
+	}
+}
+
+/// Keeps the incentive-optimal depth of each side and order size, rescanning the book only once
+/// the market's mining rate changes, its period rolls over or the traded rate moves
+#[derive(Clone, Debug)]
+pub struct IncentivePlacer {
+	/// How long a quote is left resting at most
+	pub rest_secs: u64,
+	pub min_edge_bps: f64,
+	/// Base lots a second trading against each side, used without a measured rate
+	pub traded_lots_per_sec: f64,
+	/// Furthest a quote is moved from where the strategy wanted it, in bps of its price
+	pub max_move_bps: Option<f64>,
+	period: Option<(I80F48, u64, u64)>,
+	/// (side, quantity, traded rate, depth bps)
+	depths: Vec<(Side, i64, f64, f64)>,
+}
+
+/// A traded rate this far from the one a depth was found with rescans it
+const RATE_TOLERANCE: f64 = 0.25;
+
+impl IncentivePlacer {
+	pub fn new(rest_secs: u64, min_edge_bps: f64, traded_lots_per_sec: f64) -> Self {
+		Self { rest_secs, min_edge_bps, traded_lots_per_sec, max_move_bps: None, period: None, depths: vec![] }
+	}
+
+	pub fn with_max_move_bps(mut self, max_move_bps: f64) -> Self {
+		self.max_move_bps = Some(max_move_bps);
+		self
+	}
+
+	/// Depth from the mid in bps `quantity` base lots on `side` should rest at, None when no
+	/// depth outside the edge earns anything. `traded_lots_per_sec` overrides the configured rate
+	pub fn optimal_depth_bps(&mut self, estimator: &IncentiveEstimator, side: Side, quantity: i64, book: &OrderBook, now_ts: u64, traded_lots_per_sec: Option<f64>) -> Option<f64> {
+		let period = estimator.period_key(now_ts);
+		if self.period != Some(period) {
+			self.period = Some(period);
+			self.depths.clear();
+		}
+		let rate = traded_lots_per_sec.unwrap_or(self.traded_lots_per_sec);
+		let cached = self.depths.iter().position(|(cached_side, cached_quantity, _, _)| *cached_side == side && *cached_quantity == quantity);
+		if let Some(i) = cached {
+			let (_, _, cached_rate, depth_bps) = self.depths[i];
+			if (rate - cached_rate).abs() <= cached_rate * RATE_TOLERANCE {
+				return Some(depth_bps);
+			}
+			self.depths.remove(i);
+		}
+		let depth_bps = estimator.optimal_quote(side, quantity, book, self.rest_secs, now_ts, self.min_edge_bps, rate)?.depth_bps;
+		self.depths.push((side, quantity, rate, depth_bps));
+		Some(depth_bps)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use bytemuck::Zeroable;
+	use fixed::types::I80F48;
+	use solana_program::pubkey::Pubkey;
+	use crate::book::{BookOrder, OrderBook};
+	use crate::incentives::{IncentiveEstimator, IncentivePlacer, QuotePlacement};
+	use crate::types::{PerpMarket, Side};
+
+	fn order(price: i64, quantity: i64) -> BookOrder {
+		BookOrder { key: (price as i128) << 64, owner: Pubkey::default(), owner_slot: 0, order_type: 0, time_in_force: 0, price, quantity, client_order_id: 0, timestamp: 0 }
+	}
+
+	#[test]
+	fn closer_quotes_earn_more() {
+		let perp_market = incentivised_market();
+		let estimator = IncentiveEstimator::new(perp_market);
+		let book = OrderBook { bids: vec![order(10_000, 5)], asks: vec![order(10_010, 5)] };
+		let near = QuotePlacement { side: Side::Bid, price: 9_990, quantity: 10 };
+		let far = QuotePlacement { side: Side::Bid, price: 9_900, quantity: 10 };
+		let too_far = QuotePlacement { side: Side::Bid, price: 9_700, quantity: 10 };
+		let near_mngo = estimator.expected_mngo(&near, &book, 60, 10_000);
+		assert!(near_mngo > estimator.expected_mngo(&far, &book, 60, 10_000));
+		assert_eq!(estimator.expected_mngo(&too_far, &book, 60, 10_000), 0);
+		let (best, _) = estimator.best_placement(&[far, near], &book, 60, 10_000, |placement| placement.quantity as f64).unwrap();
+		assert_eq!(best, near);
+	}
+
+	#[test]
+	fn optimal_depth_trades_rate_for_resting_time() {
+		let mut perp_market = incentivised_market();
+		let estimator = IncentiveEstimator::new(perp_market);
+		// behind a thin book every quote fills as fast, the nearest earns the most
+		let thin = OrderBook { bids: vec![order(10_000, 5)], asks: vec![order(10_010, 5)] };
+		let quote = estimator.optimal_quote(Side::Bid, 10, &thin, 600, 10_000, 20.0, 1.0).unwrap();
+		assert!(quote.depth_bps >= 20.0 && quote.depth_bps < 21.0);
+		// deeper levels keep the quote resting long enough to outearn the edge
+		let deep = OrderBook { bids: vec![order(10_000, 5), order(9_980, 100), order(9_960, 400)], asks: vec![order(10_010, 5)] };
+		let quote = estimator.optimal_quote(Side::Bid, 10, &deep, 600, 10_000, 5.0, 1.0).unwrap();
+		assert_eq!(quote.placement.price, 9_960);
+		// a market trading fast enough to fill anywhere before the horizon prefers the edge again
+		let quote = estimator.optimal_quote(Side::Bid, 10, &deep, 600, 10_000, 5.0, 1_000.0).unwrap();
+		assert!(quote.placement.price > 9_980);
+
+		let mut placer = IncentivePlacer::new(600, 20.0, 1.0);
+		let depth = placer.optimal_depth_bps(&estimator, Side::Bid, 10, &thin, 10_000, None).unwrap();
+		// a tighter edge is only picked up once the period rolls over
+		placer.min_edge_bps = 5.0;
+		assert_eq!(placer.optimal_depth_bps(&estimator, Side::Bid, 10, &thin, 10_100, Some(1.1)), Some(depth));
+		perp_market.liquidity_mining_info.rate = I80F48::from_num(0.0002);
+		let estimator = IncentiveEstimator::new(perp_market);
+		assert!(placer.optimal_depth_bps(&estimator, Side::Bid, 10, &thin, 10_100, None).unwrap() < depth);
+	}
+
+	fn incentivised_market() -> PerpMarket {
+		let mut perp_market = PerpMarket::zeroed();
+		perp_market.liquidity_mining_info.rate = I80F48::from_num(0.0001);
+		perp_market.liquidity_mining_info.max_depth_bps = I80F48::from_num(200);
+		perp_market.liquidity_mining_info.mngo_per_period = 1_000_000_000;
+		perp_market.liquidity_mining_info.mngo_left = 1_000_000_000;
+		perp_market.liquidity_mining_info.target_period_length = 3_600;
+		perp_market.meta_data.extra_info[0] = 2;
+		perp_market
+	}
+}
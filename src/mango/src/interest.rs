@@ -0,0 +1,103 @@
+use fixed::types::I80F48;
+use mangol_common::errors::{MangolError, MangolResult};
+use serde::{Deserialize, Serialize};
+
+use crate::banks::TokenBanks;
+use crate::types::{MangoAccount, MangoCache, MAX_TOKENS, ZERO_I80F48};
+use crate::utils::compute_deposit_rate;
+
+/// Current lending market of one token, rates are APR
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TokenRates {
+	pub token_index: usize,
+	pub utilization: I80F48,
+	pub deposit_rate: I80F48,
+	pub borrow_rate: I80F48,
+}
+
+pub fn token_rates(banks: &TokenBanks) -> TokenRates {
+	let (deposits, borrows) = banks.native_totals();
+	let utilization = if deposits.is_zero() { ZERO_I80F48 } else { borrows / deposits };
+	let (deposit_rate, borrow_rate) = compute_deposit_rate(&banks.root_bank, utilization).unwrap_or((ZERO_I80F48, ZERO_I80F48));
+	TokenRates {
+		token_index: banks.token_index,
+		utilization,
+		deposit_rate,
+		borrow_rate,
+	}
+}
+
+/// Interest earned and paid on one token since tracking started, native units
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TokenAccrual {
+	pub token_index: usize,
+	pub earned: I80F48,
+	pub paid: I80F48,
+	/// (earned - paid) at the current cache price, native quote
+	pub net_value: I80F48,
+}
+
+/// Indexes at the start of tracking. Static balances grow with the index,
+/// so the interest on a balance is static * (index_now - index_then)
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct InterestTracker {
+	pub started_at: u64,
+	pub deposit_indexes: Vec<I80F48>,
+	pub borrow_indexes: Vec<I80F48>,
+}
+
+impl InterestTracker {
+	pub fn new(mango_cache: &MangoCache, started_at: u64) -> Self {
+		Self {
+			started_at,
+			deposit_indexes: mango_cache.root_bank_cache.iter().map(|cache| cache.deposit_index).collect(),
+			borrow_indexes: mango_cache.root_bank_cache.iter().map(|cache| cache.borrow_index).collect(),
+		}
+	}
+
+	pub fn load(path: &str) -> MangolResult<Self> {
+		serde_json::from_str(&std::fs::read_to_string(path)?).map_err(|e| MangolError::SerializationError(e.to_string()))
+	}
+
+	pub fn save(&self, path: &str) -> MangolResult<()> {
+		std::fs::write(path, serde_json::to_string_pretty(self).map_err(|e| MangolError::SerializationError(e.to_string()))?)?;
+		Ok(())
+	}
+
+	/// Accrual on the account's current balances, assumes they were held since `started_at`
+	pub fn accrued(&self, mango_account: &MangoAccount, mango_cache: &MangoCache) -> Vec<TokenAccrual> {
+		(0..MAX_TOKENS).filter_map(|i| {
+			let cache = &mango_cache.root_bank_cache[i];
+			let earned = mango_account.deposits[i] * (cache.deposit_index - self.deposit_indexes[i]);
+			let paid = mango_account.borrows[i] * (cache.borrow_index - self.borrow_indexes[i]);
+			if earned.is_zero() && paid.is_zero() {
+				return None;
+			}
+			let price = I80F48::from_num(mango_cache.get_price(i));
+			Some(TokenAccrual { token_index: i, earned, paid, net_value: (earned - paid) * price })
+		}).collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use bytemuck::Zeroable;
+	use fixed::types::I80F48;
+	use crate::interest::InterestTracker;
+	use crate::types::{MangoAccount, MangoCache, QUOTE_INDEX};
+
+	#[test]
+	fn accrues_on_index_growth() {
+		let mut mango_cache = MangoCache::zeroed();
+		mango_cache.root_bank_cache[QUOTE_INDEX].deposit_index = I80F48::from_num(1);
+		mango_cache.root_bank_cache[QUOTE_INDEX].borrow_index = I80F48::from_num(1);
+		let tracker = InterestTracker::new(&mango_cache, 0);
+		let mut mango_account = MangoAccount::zeroed();
+		mango_account.deposits[QUOTE_INDEX] = I80F48::from_num(1_000_000);
+		mango_cache.root_bank_cache[QUOTE_INDEX].deposit_index = I80F48::from_num(1.05);
+		let accrued = tracker.accrued(&mango_account, &mango_cache);
+		assert_eq!(accrued.len(), 1);
+		assert_eq!(accrued[0].earned.round(), I80F48::from_num(50_000));
+		assert!(accrued[0].paid.is_zero());
+	}
+}
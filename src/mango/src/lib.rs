@@ -2,5 +2,33 @@ pub mod types;
 pub mod ids;
 pub mod error;
 pub mod utils;
+#[cfg(feature = "client")]
 pub mod client;
-pub mod instructions;
\ No newline at end of file
+pub mod instructions;
+#[cfg(feature = "liquidator")]
+pub mod liquidation;
+pub mod queue;
+#[cfg(feature = "client")]
+pub mod mock;
+pub mod fees;
+#[cfg(feature = "client")]
+pub mod accounts;
+pub mod book;
+pub mod logs;
+pub mod banks;
+pub mod interest;
+pub mod guards;
+pub mod sizing;
+#[cfg(feature = "client")]
+pub mod stream;
+pub mod snapshot;
+pub mod incentives;
+pub mod health;
+pub mod locks;
+pub mod registry;
+#[cfg(feature = "client")]
+pub mod venue;
+pub mod oracle;
+pub mod profiles;
+#[cfg(feature = "client")]
+pub mod history;
@@ -0,0 +1,153 @@
+use fixed::types::I80F48;
+use mangol_common::errors::{MangolError, MangolResult, SolanaError};
+use mangol_solana::connection::SolanaConnection;
+use mangol_solana::subscription::decode_account_data;
+use solana_account_decoder::UiAccount;
+use solana_program::instruction::Instruction;
+use solana_program::program_error::ProgramError;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::transaction::Transaction;
+
+use crate::health::decode_mango_account;
+use crate::types::{MangoAccount, ONE_I80F48, ZERO_I80F48};
+
+/// Maximum number of simulations before giving up on a liquidation
+pub const MAX_SIZING_SIMULATIONS: usize = 8;
+/// Factor applied to `max_liab_transfer` after every failed simulation
+pub const TRANSFER_REDUCTION_FACTOR: f64 = 0.5;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LiquidationSimulation {
+	pub max_liab_transfer: I80F48,
+	pub liqee_health: Option<I80F48>,
+	pub liqor_health: Option<I80F48>,
+	pub error: Option<String>,
+	pub logs: Vec<String>,
+}
+
+impl LiquidationSimulation {
+	/// The simulation passed and left the liqor with non-negative health. A liqor health the
+	/// simulation didn't return doesn't pass, the transfer is shrunk rather than sent blind
+	pub fn passes(&self) -> bool {
+		self.error.is_none() && self.liqor_health.map_or(false, |health| health >= ZERO_I80F48)
+	}
+}
+
+/// Health of a mango account as a simulation returned it, None when it came back missing or undecodable
+pub fn simulated_health<H: Fn(&MangoAccount) -> MangolResult<I80F48>>(ui_account: Option<&UiAccount>, health: &H) -> Option<I80F48> {
+	let mut data = vec![];
+	if !decode_account_data(&ui_account?.data, &mut data) {
+		return None;
+	}
+	health(&decode_mango_account(&data).ok()?).ok()
+}
+
+/// Simulates `instructions` and reads the liqee's and liqor's health from the accounts as the
+/// liquidation leaves them, `health` scores a decoded mango account
+pub fn simulate_liquidation<H: Fn(&MangoAccount) -> MangolResult<I80F48>>(
+	connection: &SolanaConnection,
+	signer: &Keypair,
+	instructions: &[Instruction],
+	max_liab_transfer: I80F48,
+	liqee_pk: &Pubkey,
+	liqor_pk: &Pubkey,
+	health: &H,
+) -> MangolResult<LiquidationSimulation> {
+	let transaction = Transaction::new_with_payer(instructions, Some(&signer.pubkey()));
+	let result = connection.simulate_tx_with_accounts(transaction, signer, &[*liqee_pk, *liqor_pk])?;
+	let accounts = result.accounts.unwrap_or_default();
+	let account = |i: usize| accounts.get(i).and_then(|account| account.as_ref());
+	Ok(LiquidationSimulation {
+		max_liab_transfer,
+		liqee_health: simulated_health(account(0), health),
+		liqor_health: simulated_health(account(1), health),
+		error: result.err.map(|e| format!("{:?}", e)),
+		logs: result.logs.unwrap_or_default(),
+	})
+}
+
+/// Whole lots of the transfers to simulate, `initial_liab_transfer` floored then halved until it is
+/// below one lot, at most MAX_SIZING_SIMULATIONS of them
+pub fn transfer_sizes(initial_liab_transfer: I80F48) -> Vec<I80F48> {
+	let mut sizes = vec![];
+	let mut max_liab_transfer = initial_liab_transfer.floor();
+	while max_liab_transfer >= ONE_I80F48 && sizes.len() < MAX_SIZING_SIMULATIONS {
+		sizes.push(max_liab_transfer);
+		max_liab_transfer = (max_liab_transfer * I80F48::from_num(TRANSFER_REDUCTION_FACTOR)).floor();
+	}
+	sizes
+}
+
+/// Simulates the full liquidation instruction sequence built by `build_instructions`,
+/// shrinking `max_liab_transfer` until the simulation passes. Transfers are whole lots, a
+/// fraction would be truncated by the instruction, down to zero below one lot
+///
+/// Returns the last passing simulation so the caller can send the exact same sequence.
+pub fn size_liquidation_with_simulation<F, H>(
+	connection: &SolanaConnection,
+	signer: &Keypair,
+	initial_liab_transfer: I80F48,
+	liqee_pk: &Pubkey,
+	liqor_pk: &Pubkey,
+	health: &H,
+	build_instructions: F,
+) -> MangolResult<LiquidationSimulation>
+	where F: Fn(I80F48) -> Result<Vec<Instruction>, ProgramError>, H: Fn(&MangoAccount) -> MangolResult<I80F48>
+{
+	let sizes = transfer_sizes(initial_liab_transfer);
+	let mut last_error = format!("max_liab_transfer {} is below one lot", initial_liab_transfer);
+	for max_liab_transfer in sizes {
+		let instructions = build_instructions(max_liab_transfer)
+			  .map_err(|e| MangolError::SolanaError(SolanaError::SimulationFailed(e.to_string())))?;
+		let simulation = simulate_liquidation(connection, signer, &instructions, max_liab_transfer, liqee_pk, liqor_pk, health)?;
+		if simulation.passes() {
+			return Ok(simulation);
+		}
+		println!("[-] Liquidation simulation failed for max_liab_transfer {} {:?} liqor health {:?}", max_liab_transfer, simulation.error, simulation.liqor_health);
+		last_error = simulation.error.unwrap_or_else(|| "liqor health negative".to_string());
+	}
+	Err(SolanaError::SimulationFailed(last_error).into())
+}
+
+#[cfg(test)]
+mod tests {
+	use bytemuck::Zeroable;
+	use fixed::types::I80F48;
+	use mangol_common::errors::MangolError;
+	use solana_account_decoder::{UiAccount, UiAccountData, UiAccountEncoding};
+	use crate::liquidation::{simulated_health, transfer_sizes, LiquidationSimulation, MAX_SIZING_SIMULATIONS};
+	use crate::types::MangoAccount;
+
+	#[test]
+	fn reads_health_from_simulated_accounts() {
+		let mut mango_account = MangoAccount::zeroed();
+		mango_account.perp_accounts[1].base_position = -3;
+		let ui_account = UiAccount {
+			lamports: 1,
+			data: UiAccountData::Binary(base64::encode(bytemuck::bytes_of(&mango_account)), UiAccountEncoding::Base64),
+			owner: String::new(),
+			executable: false,
+			rent_epoch: 0,
+		};
+		let health = |mango_account: &MangoAccount| Ok(I80F48::from_num(mango_account.perp_accounts[1].base_position));
+		assert_eq!(simulated_health(Some(&ui_account), &health), Some(I80F48::from_num(-3)));
+		assert_eq!(simulated_health(None, &health), None);
+		let failing = |_: &MangoAccount| Err(MangolError::MangoError("no cache".to_string()));
+		assert_eq!(simulated_health(Some(&ui_account), &failing), None);
+
+		let simulation = |liqor_health: Option<I80F48>| LiquidationSimulation { max_liab_transfer: I80F48::from_num(10), liqee_health: None, liqor_health, error: None, logs: vec![] };
+		assert!(simulation(Some(I80F48::from_num(5))).passes());
+		assert!(!simulation(Some(I80F48::from_num(-5))).passes());
+		assert!(!simulation(None).passes());
+	}
+
+	#[test]
+	fn sizes_transfers_in_whole_lots() {
+		// an odd position never simulates half a lot nor a transfer truncated to zero
+		assert_eq!(transfer_sizes(I80F48::from_num(3)), vec![I80F48::from_num(3), I80F48::from_num(1)]);
+		assert_eq!(transfer_sizes(I80F48::from_num(7.5)), vec![I80F48::from_num(7), I80F48::from_num(3), I80F48::from_num(1)]);
+		assert!(transfer_sizes(I80F48::from_num(0.75)).is_empty());
+		assert_eq!(transfer_sizes(I80F48::from_num(1_000)).len(), MAX_SIZING_SIMULATIONS);
+	}
+}
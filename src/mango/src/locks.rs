@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use mangol_common::errors::{MangolError, MangolResult};
+
+#[derive(Default)]
+struct LockState {
+	in_flight: HashMap<usize, Instant>,
+	released_at: HashMap<usize, Instant>,
+}
+
+/// One order in flight per perp market. Clones share the locks, so a strategy and a stop loss
+/// manager holding clients built `with_order_locks` of the same MarketLocks never send
+/// overlapping orders for a market
+#[derive(Clone)]
+pub struct MarketLocks {
+	/// How long acquire waits for the market before giving up
+	pub wait: Duration,
+	/// Minimum time between one order landing and the next being sent for the same market
+	pub spacing: Duration,
+	state: Arc<(Mutex<LockState>, Condvar)>,
+}
+
+impl Default for MarketLocks {
+	fn default() -> Self {
+		Self {
+			wait: Duration::from_secs(60),
+			spacing: Duration::from_millis(300),
+			state: Arc::new((Mutex::new(LockState::default()), Condvar::new())),
+		}
+	}
+}
+
+impl MarketLocks {
+	pub fn with_wait(mut self, wait: Duration) -> Self {
+		self.wait = wait;
+		self
+	}
+
+	pub fn with_spacing(mut self, spacing: Duration) -> Self {
+		self.spacing = spacing;
+		self
+	}
+
+	/// Waits until no order is in flight for `market_index` and `spacing` passed since the last one,
+	/// the market stays locked until the guard is dropped
+	pub fn acquire(&self, market_index: usize) -> MangolResult<MarketLockGuard> {
+		let (lock, released) = &*self.state;
+		let deadline = Instant::now() + self.wait;
+		let mut state = lock.lock().unwrap();
+		loop {
+			let now = Instant::now();
+			let ready_at = state.released_at.get(&market_index).map(|at| *at + self.spacing).unwrap_or(now);
+			if !state.in_flight.contains_key(&market_index) && ready_at <= now {
+				state.in_flight.insert(market_index, now);
+				return Ok(MarketLockGuard { market_index, state: self.state.clone() });
+			}
+			if now >= deadline {
+				return Err(MangolError::OrderInFlight(market_index));
+			}
+			let wake_at = if state.in_flight.contains_key(&market_index) { deadline } else { ready_at.min(deadline) };
+			state = released.wait_timeout(state, wake_at - now).unwrap().0;
+		}
+	}
+
+	pub fn is_locked(&self, market_index: usize) -> bool {
+		self.state.0.lock().unwrap().in_flight.contains_key(&market_index)
+	}
+}
+
+/// Releases its market when dropped
+pub struct MarketLockGuard {
+	pub market_index: usize,
+	state: Arc<(Mutex<LockState>, Condvar)>,
+}
+
+impl Drop for MarketLockGuard {
+	fn drop(&mut self) {
+		let (lock, released) = &*self.state;
+		let mut state = lock.lock().unwrap();
+		state.in_flight.remove(&self.market_index);
+		state.released_at.insert(self.market_index, Instant::now());
+		released.notify_all();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::{Duration, Instant};
+	use crate::locks::MarketLocks;
+
+	#[test]
+	fn serializes_orders_per_market() {
+		let locks = MarketLocks::default().with_wait(Duration::ZERO).with_spacing(Duration::from_millis(50));
+		let guard = locks.acquire(3).unwrap();
+		assert!(locks.clone().acquire(3).is_err());
+		assert!(locks.acquire(4).is_ok());
+		drop(guard);
+		assert!(!locks.is_locked(3));
+		// still inside the spacing after the release
+		assert!(locks.acquire(3).is_err());
+		let waiting = locks.clone().with_wait(Duration::from_secs(1));
+		let started = Instant::now();
+		assert!(waiting.acquire(3).is_ok());
+		assert!(started.elapsed() >= Duration::from_millis(40));
+	}
+}
@@ -0,0 +1,256 @@
+use std::convert::TryFrom;
+
+use fixed::types::I80F48;
+use solana_program::hash::hashv;
+use solana_program::pubkey::Pubkey;
+
+use crate::types::Side;
+
+/// Marker logged by mango_emit! right before the "Program data:" line of an event
+const MANGO_LOG_MARKER: &str = "Program log: mango-log";
+const PROGRAM_DATA_PREFIX: &str = "Program data: ";
+const PROGRAM_LOG_PREFIX: &str = "Program log: ";
+const POST_ONLY_REJECTED: &str = "not be placed due to PostOnly";
+
+/// FillLog as emitted by mango-logs, a borsh serialized FillEvent
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FillLog {
+	pub mango_group: Pubkey,
+	pub market_index: u64,
+	pub taker_side: Side,
+	pub maker_slot: u8,
+	pub maker_out: bool,
+	pub timestamp: u64,
+	pub seq_num: u64,
+	pub maker: Pubkey,
+	pub maker_order_id: i128,
+	pub maker_client_order_id: u64,
+	pub maker_fee: I80F48,
+	pub best_initial: i64,
+	pub maker_timestamp: u64,
+	pub taker: Pubkey,
+	pub taker_order_id: i128,
+	pub taker_client_order_id: u64,
+	pub taker_fee: I80F48,
+	pub price: i64,    // quote lots per base lot
+	pub quantity: i64, // base lots
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CancelAllPerpOrdersLog {
+	pub mango_group: Pubkey,
+	pub mango_account: Pubkey,
+	pub market_index: u64,
+	pub all_order_ids: Vec<i128>,
+	pub canceled_order_ids: Vec<i128>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MangoLogEvent {
+	Fill(FillLog),
+	CancelAllPerpOrders(CancelAllPerpOrdersLog),
+	/// A PostOnly order that would have crossed and was dropped by the program
+	PostOnlyRejected,
+	/// "Program <id> failed: <reason>"
+	Error(String),
+}
+
+/// First 8 bytes of sha256("event:<name>"), the anchor event discriminator mango-logs uses
+fn discriminator(name: &str) -> [u8; 8] {
+	let mut discriminator = [0u8; 8];
+	discriminator.copy_from_slice(&hashv(&[format!("event:{}", name).as_bytes()]).to_bytes()[..8]);
+	discriminator
+}
+
+/// Borsh reader over an event body, every read returns None once the data runs out
+struct LogReader<'a> {
+	data: &'a [u8],
+}
+
+impl<'a> LogReader<'a> {
+	fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+		if self.data.len() < N {
+			return None;
+		}
+		let (head, tail) = self.data.split_at(N);
+		self.data = tail;
+		<[u8; N]>::try_from(head).ok()
+	}
+
+	fn u8(&mut self) -> Option<u8> {
+		Some(self.take::<1>()?[0])
+	}
+
+	fn u64(&mut self) -> Option<u64> {
+		Some(u64::from_le_bytes(self.take()?))
+	}
+
+	fn i64(&mut self) -> Option<i64> {
+		Some(i64::from_le_bytes(self.take()?))
+	}
+
+	fn i128(&mut self) -> Option<i128> {
+		Some(i128::from_le_bytes(self.take()?))
+	}
+
+	fn pubkey(&mut self) -> Option<Pubkey> {
+		Some(Pubkey::new_from_array(self.take()?))
+	}
+
+	fn i128_vec(&mut self) -> Option<Vec<i128>> {
+		let len = u32::from_le_bytes(self.take()?);
+		(0..len).map(|_| self.i128()).collect()
+	}
+}
+
+impl FillLog {
+	fn read(reader: &mut LogReader) -> Option<Self> {
+		Some(Self {
+			mango_group: reader.pubkey()?,
+			market_index: reader.u64()?,
+			taker_side: if reader.u8()? == 0 { Side::Bid } else { Side::Ask },
+			maker_slot: reader.u8()?,
+			maker_out: reader.u8()? != 0,
+			timestamp: reader.u64()?,
+			seq_num: reader.u64()?,
+			maker: reader.pubkey()?,
+			maker_order_id: reader.i128()?,
+			maker_client_order_id: reader.u64()?,
+			maker_fee: I80F48::from_bits(reader.i128()?),
+			best_initial: reader.i64()?,
+			maker_timestamp: reader.u64()?,
+			taker: reader.pubkey()?,
+			taker_order_id: reader.i128()?,
+			taker_client_order_id: reader.u64()?,
+			taker_fee: I80F48::from_bits(reader.i128()?),
+			price: reader.i64()?,
+			quantity: reader.i64()?,
+		})
+	}
+}
+
+impl CancelAllPerpOrdersLog {
+	fn read(reader: &mut LogReader) -> Option<Self> {
+		Some(Self {
+			mango_group: reader.pubkey()?,
+			mango_account: reader.pubkey()?,
+			market_index: reader.u64()?,
+			all_order_ids: reader.i128_vec()?,
+			canceled_order_ids: reader.i128_vec()?,
+		})
+	}
+}
+
+/// Decodes one "Program data:" payload, None for events we don't track
+pub fn parse_event_data(encoded: &str) -> Option<MangoLogEvent> {
+	let data = base64::decode(encoded).ok()?;
+	if data.len() < 8 {
+		return None;
+	}
+	let (tag, body) = data.split_at(8);
+	let mut reader = LogReader { data: body };
+	if tag == discriminator("FillLog") {
+		FillLog::read(&mut reader).map(MangoLogEvent::Fill)
+	} else if tag == discriminator("CancelAllPerpOrdersLog") {
+		CancelAllPerpOrdersLog::read(&mut reader).map(MangoLogEvent::CancelAllPerpOrders)
+	} else {
+		None
+	}
+}
+
+/// Quantity weighted price in lots per base lot and total base lots of the fills on `market_index`,
+/// None without any
+pub fn average_fill(events: &[MangoLogEvent], market_index: usize) -> Option<(f64, i64)> {
+	let (cost, quantity) = events.iter()
+		  .filter_map(|event| match event {
+			  MangoLogEvent::Fill(fill) if fill.market_index as usize == market_index => Some((fill.price as f64 * fill.quantity as f64, fill.quantity)),
+			  _ => None
+		  })
+		  .fold((0.0, 0), |(cost, quantity), (fill_cost, fill_quantity)| (cost + fill_cost, quantity + fill_quantity));
+	if quantity == 0 {
+		return None;
+	}
+	Some((cost / quantity as f64, quantity))
+}
+
+/// Extracts the mango events of a confirmed transaction from its log messages, in log order
+pub fn parse_logs(log_messages: &[String]) -> Vec<MangoLogEvent> {
+	let mut events = vec![];
+	let mut expect_data = false;
+	for message in log_messages {
+		if message == MANGO_LOG_MARKER {
+			expect_data = true;
+			continue;
+		}
+		if let Some(encoded) = message.strip_prefix(PROGRAM_DATA_PREFIX) {
+			if expect_data {
+				events.extend(parse_event_data(encoded));
+			}
+		} else if message.starts_with(PROGRAM_LOG_PREFIX) && message.contains(POST_ONLY_REJECTED) {
+			events.push(MangoLogEvent::PostOnlyRejected);
+		} else if let Some((_, reason)) = message.split_once(" failed: ") {
+			events.push(MangoLogEvent::Error(reason.to_string()));
+		}
+		expect_data = false;
+	}
+	events
+}
+
+pub fn fills(events: &[MangoLogEvent]) -> Vec<FillLog> {
+	events.iter().filter_map(|event| match event {
+		MangoLogEvent::Fill(fill) => Some(*fill),
+		_ => None
+	}).collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use solana_program::pubkey::Pubkey;
+	use crate::logs::{discriminator, parse_logs, MangoLogEvent};
+	use crate::types::Side;
+
+	fn fill_log_data(price: i64, quantity: i64) -> String {
+		let mut data = discriminator("FillLog").to_vec();
+		data.extend_from_slice(Pubkey::new_unique().as_ref());
+		data.extend_from_slice(&3u64.to_le_bytes());
+		data.extend_from_slice(&[1, 0, 1]);
+		data.extend_from_slice(&1_650_000_000u64.to_le_bytes());
+		data.extend_from_slice(&42u64.to_le_bytes());
+		data.extend_from_slice(Pubkey::new_unique().as_ref());
+		data.extend_from_slice(&7i128.to_le_bytes());
+		data.extend_from_slice(&0u64.to_le_bytes());
+		data.extend_from_slice(&0i128.to_le_bytes());
+		data.extend_from_slice(&0i64.to_le_bytes());
+		data.extend_from_slice(&1_650_000_000u64.to_le_bytes());
+		data.extend_from_slice(Pubkey::new_unique().as_ref());
+		data.extend_from_slice(&8i128.to_le_bytes());
+		data.extend_from_slice(&0u64.to_le_bytes());
+		data.extend_from_slice(&0i128.to_le_bytes());
+		data.extend_from_slice(&price.to_le_bytes());
+		data.extend_from_slice(&quantity.to_le_bytes());
+		base64::encode(data)
+	}
+
+	#[test]
+	fn parses_fills_rejections_and_errors() {
+		let logs = vec![
+			"Program mv3ekLzLbnVPNxjSKvqBpU3ZeZXPQdEC3bp5MDEBG68 invoke [1]".to_string(),
+			"Program log: mango-log".to_string(),
+			format!("Program data: {}", fill_log_data(4_000, 121)),
+			"Program log: Order could not be placed due to PostOnly".to_string(),
+			"Program mv3ekLzLbnVPNxjSKvqBpU3ZeZXPQdEC3bp5MDEBG68 failed: custom program error: 0x1".to_string(),
+		];
+		let events = parse_logs(&logs);
+		assert_eq!(events.len(), 3);
+		match &events[0] {
+			MangoLogEvent::Fill(fill) => {
+				assert_eq!(fill.taker_side, Side::Ask);
+				assert_eq!(fill.seq_num, 42);
+				assert_eq!((fill.price, fill.quantity), (4_000, 121));
+			}
+			other => panic!("expected fill, got {:?}", other)
+		}
+		assert_eq!(events[1], MangoLogEvent::PostOnlyRejected);
+		assert_eq!(events[2], MangoLogEvent::Error("custom program error: 0x1".to_string()));
+	}
+}
@@ -0,0 +1,212 @@
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+
+use bytemuck::Zeroable;
+use fixed::types::I80F48;
+use mangol_common::errors::{MangolError, MangolResult};
+use mangol_solana::expenses::TxExpense;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+
+use crate::book::OrderBook;
+use crate::client::MangoClientApi;
+use crate::types::{HealthType, MangoAccount, MangoCache, MangoGroup, OrderType, PerpMarket, PerpMarketData, PerpMarketInfo, Side};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MockOrder {
+	pub side: Side,
+	pub price: f64,
+	pub quantity: i64,
+	pub order_type: OrderType,
+	pub reduce_only: bool,
+	pub expiry_timestamp: Option<u64>,
+	pub with_base: bool,
+}
+
+/// A scriptable MangoClientApi for driving strategies in tests.
+///
+/// Every call to `update` pops the next scripted price and fill and applies them
+/// to the cached state, orders are only recorded and never change the account.
+pub struct MockMangoClient {
+	pub mango_account: MangoAccount,
+	pub mango_account_pk: Pubkey,
+	pub mango_cache: MangoCache,
+	pub mango_group: MangoGroup,
+	pub market_index: usize,
+	pub prices: VecDeque<f64>,
+	pub fills: VecDeque<i64>,
+	pub transaction_logs: Vec<String>,
+	pub order_book: OrderBook,
+	pub placed_orders: RefCell<Vec<MockOrder>>,
+	pub cancel_all_count: Cell<usize>,
+	pub equity: I80F48,
+	/// Returned for every health type
+	pub health: I80F48,
+	/// Returned for every transaction
+	pub expense: TxExpense,
+	pub perp_market: PerpMarket,
+	pub signer: Pubkey,
+	pub backup_signer: Option<Pubkey>,
+	/// Orders accepted before every further one fails, None to accept them all
+	pub max_orders: Option<usize>,
+}
+
+impl MockMangoClient {
+	pub fn new(market_index: usize, base_lot_size: i64, quote_lot_size: i64) -> Self {
+		let mut mango_group = MangoGroup::zeroed();
+		mango_group.perp_markets[market_index].base_lot_size = base_lot_size;
+		mango_group.perp_markets[market_index].quote_lot_size = quote_lot_size;
+		Self {
+			mango_account: MangoAccount::zeroed(),
+			mango_account_pk: Pubkey::default(),
+			mango_cache: MangoCache::zeroed(),
+			mango_group,
+			market_index,
+			prices: VecDeque::new(),
+			fills: VecDeque::new(),
+			transaction_logs: vec![],
+			order_book: OrderBook::default(),
+			placed_orders: RefCell::new(vec![]),
+			cancel_all_count: Cell::new(0),
+			equity: I80F48::from_num(1_000_000_000),
+			health: I80F48::from_num(1_000_000_000),
+			expense: TxExpense::default(),
+			perp_market: PerpMarket::zeroed(),
+			signer: Pubkey::default(),
+			backup_signer: None,
+			max_orders: None,
+		}
+	}
+
+	pub fn set_price(&mut self, price: f64) {
+		self.mango_cache.price_cache[self.market_index].price = I80F48::from_num(price);
+	}
+
+	pub fn set_base_position(&mut self, base_position: i64) {
+		self.mango_account.perp_accounts[self.market_index].base_position = base_position;
+	}
+
+	/// Price applied on the next update, the current price is kept once the script runs out
+	pub fn push_price(&mut self, price: f64) {
+		self.prices.push_back(price);
+	}
+
+	/// Base lots added to the position on the next update, 0 for no fill
+	pub fn push_fill(&mut self, base_change: i64) {
+		self.fills.push_back(base_change);
+	}
+
+	pub fn last_order(&self) -> Option<MockOrder> {
+		self.placed_orders.borrow().last().cloned()
+	}
+
+	fn record_order(&self, order: MockOrder) -> MangolResult<String> {
+		let mut placed_orders = self.placed_orders.borrow_mut();
+		if self.max_orders.map(|max_orders| placed_orders.len() >= max_orders).unwrap_or(false) {
+			return Err(MangolError::MangoError(format!("mock rejected order {}", placed_orders.len() + 1)));
+		}
+		placed_orders.push(order);
+		Ok(format!("mock-{}", placed_orders.len()))
+	}
+}
+
+impl MangoClientApi for MockMangoClient {
+	fn update(&mut self) -> MangolResult<()> {
+		if let Some(price) = self.prices.pop_front() {
+			self.set_price(price);
+		}
+		if let Some(base_change) = self.fills.pop_front() {
+			self.mango_account.perp_accounts[self.market_index].base_position += base_change;
+		}
+		Ok(())
+	}
+
+	fn mango_account(&self) -> &MangoAccount {
+		&self.mango_account
+	}
+
+	fn mango_account_pk(&self) -> Pubkey {
+		self.mango_account_pk
+	}
+
+	fn mango_cache(&self) -> &MangoCache {
+		&self.mango_cache
+	}
+
+	fn mango_group(&self) -> &MangoGroup {
+		&self.mango_group
+	}
+
+	fn place_perp_order(&self, _perp_market: &PerpMarketInfo, _perp_market_data: &PerpMarketData, side: Side, price: f64, quantity: i64, order_type: OrderType, reduce_only: bool, expiry_timestamp: Option<u64>) -> MangolResult<String> {
+		self.record_order(MockOrder { side, price, quantity, order_type, reduce_only, expiry_timestamp, with_base: false })
+	}
+
+	fn place_perp_order_with_base(&self, _perp_market: &PerpMarketInfo, _perp_market_data: &PerpMarketData, side: Side, price: f64, quantity: i64, order_type: OrderType, reduce_only: bool, expiry_timestamp: Option<u64>) -> MangolResult<String> {
+		self.record_order(MockOrder { side, price, quantity, order_type, reduce_only, expiry_timestamp, with_base: true })
+	}
+
+	fn get_transaction_logs(&self, _tx_hash: &str) -> MangolResult<Vec<String>> {
+		Ok(self.transaction_logs.clone())
+	}
+
+	fn fetch_mango_account(&self) -> MangolResult<Option<MangoAccount>> {
+		Ok(Some(self.mango_account))
+	}
+
+	fn load_order_book(&self, _perp_market_data: &PerpMarketData) -> MangolResult<OrderBook> {
+		Ok(self.order_book.clone())
+	}
+
+	fn cancel_all_perp_orders(&self, _perp_market_data: &PerpMarketData) -> MangolResult<String> {
+		self.cancel_all_count.set(self.cancel_all_count.get() + 1);
+		Ok(format!("mock-cancel-{}", self.cancel_all_count.get()))
+	}
+
+	fn get_equity(&self) -> MangolResult<I80F48> {
+		Ok(self.equity)
+	}
+
+	fn get_health(&self, _health_type: HealthType) -> MangolResult<I80F48> {
+		Ok(self.health)
+	}
+
+	fn get_transaction_expense(&self, _tx_hash: &str) -> MangolResult<TxExpense> {
+		Ok(self.expense)
+	}
+
+	fn load_perp_market(&self, _perp_market_data: &PerpMarketData) -> MangolResult<PerpMarket> {
+		Ok(self.perp_market)
+	}
+
+	fn signer(&self) -> Pubkey {
+		self.signer
+	}
+
+	fn rotate_signer(&mut self, signer: Option<Keypair>) -> MangolResult<Pubkey> {
+		let replaced = self.signer;
+		match signer {
+			Some(signer) => self.signer = signer.pubkey(),
+			None => {
+				self.signer = self.backup_signer.take().ok_or_else(|| MangolError::KeyStoreError("No backup signer to rotate to".to_string()))?;
+				self.backup_signer = Some(replaced);
+			}
+		}
+		Ok(self.signer)
+	}
+}
+
+/// Mainnet SOL-PERP at market index 3, what tests trade on. MockMangoClient::new(3, 10_000_000, 100)
+/// has its lot sizes
+pub fn sol_perp() -> PerpMarketData {
+	PerpMarketData {
+		name: "SOL-PERP".to_string(),
+		pubkey: "58vac8i9QXStG1hpaa4ouwE1X7ngeDjY9oY7R15hcbKJ".to_string(),
+		base_symbol: "SOL".to_string(),
+		base_decimals: 9,
+		quote_decimals: 6,
+		market_index: 3,
+		bids_key: "Fu8q5EiFunGwSRrjFKjRUoMABj5yCoMEPccMbUiAT6PD".to_string(),
+		asks_key: "9qUxMSWBGAeNmXusQHuLfgSuYJqADyYoNLwZ63JJSi6V".to_string(),
+		events_key: "31cKs646dt1YkA3zPyxZ7rUAkxTBz279w4XEobFXcAKP".to_string()
+	}
+}
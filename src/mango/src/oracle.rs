@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use fixed::types::I80F48;
+
+use crate::types::PYTH_CONF_FILTER;
+
+const PYTH_MAGIC: u32 = 0xa1b2c3d4;
+const PYTH_PRICE_ACCOUNT: u32 = 3;
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+	Some(u32::from_le_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+	Some(u64::from_le_bytes(data.get(offset..offset + 8)?.try_into().ok()?))
+}
+
+/// Aggregate price of a Pyth v2 price account, in the account's own exponent
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PythPrice {
+	pub price: i64,
+	pub conf: u64,
+	pub expo: i32,
+	pub slot: u64,
+}
+
+impl PythPrice {
+	/// None for anything that isn't a Pyth price account, e.g. a Switchboard or stub oracle
+	pub fn load(data: &[u8]) -> Option<Self> {
+		if read_u32(data, 0)? != PYTH_MAGIC || read_u32(data, 8)? != PYTH_PRICE_ACCOUNT {
+			return None;
+		}
+		Some(Self {
+			expo: read_u32(data, 20)? as i32,
+			price: read_u64(data, 208)? as i64,
+			conf: read_u64(data, 216)?,
+			slot: read_u64(data, 232)?,
+		})
+	}
+
+	/// conf / price, infinite for a price the program can't use at all
+	pub fn conf_ratio(&self) -> f64 {
+		if self.price <= 0 {
+			return f64::INFINITY;
+		}
+		self.conf as f64 / self.price as f64
+	}
+
+	/// Whether mango rejects the price, the same comparison the program makes when caching it
+	pub fn exceeds_conf_filter(&self) -> bool {
+		self.price <= 0 || I80F48::from_num(self.conf) > PYTH_CONF_FILTER * I80F48::from_num(self.price)
+	}
+}
+
+/// Latest Pyth confidence per market, shared between the stream writing it and whoever gates orders on it.
+/// Markets on other oracle types are never recorded and never breach
+#[derive(Clone, Debug, Default)]
+pub struct OracleConfidence {
+	prices: Arc<RwLock<HashMap<usize, PythPrice>>>,
+}
+
+impl OracleConfidence {
+	pub fn update(&self, market_index: usize, price: PythPrice) {
+		self.prices.write().unwrap().insert(market_index, price);
+	}
+
+	pub fn latest(&self, market_index: usize) -> Option<PythPrice> {
+		self.prices.read().unwrap().get(&market_index).copied()
+	}
+
+	/// The market's conf / price while it is over PYTH_CONF_FILTER
+	pub fn breach(&self, market_index: usize) -> Option<f64> {
+		self.latest(market_index).filter(|price| price.exceeds_conf_filter()).map(|price| price.conf_ratio())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::oracle::{OracleConfidence, PythPrice, PYTH_MAGIC, PYTH_PRICE_ACCOUNT};
+
+	fn price_account(price: i64, conf: u64) -> Vec<u8> {
+		let mut data = vec![0_u8; 3312];
+		data[0..4].copy_from_slice(&PYTH_MAGIC.to_le_bytes());
+		data[8..12].copy_from_slice(&PYTH_PRICE_ACCOUNT.to_le_bytes());
+		data[20..24].copy_from_slice(&(-8_i32).to_le_bytes());
+		data[208..216].copy_from_slice(&price.to_le_bytes());
+		data[216..224].copy_from_slice(&conf.to_le_bytes());
+		data[232..240].copy_from_slice(&42_u64.to_le_bytes());
+		data
+	}
+
+	#[test]
+	fn flags_prices_over_the_conf_filter() {
+		let tight = PythPrice::load(&price_account(3_000_000_000, 2_000_000)).unwrap();
+		assert_eq!(tight, PythPrice { price: 3_000_000_000, conf: 2_000_000, expo: -8, slot: 42 });
+		assert!(!tight.exceeds_conf_filter());
+		let wide = PythPrice::load(&price_account(3_000_000_000, 400_000_000)).unwrap();
+		assert!(wide.exceeds_conf_filter());
+		assert!(PythPrice::load(&[0_u8; 3312]).is_none());
+
+		let confidence = OracleConfidence::default();
+		assert_eq!(confidence.breach(1), None);
+		confidence.update(1, tight);
+		assert_eq!(confidence.breach(1), None);
+		confidence.update(1, wide);
+		assert!((confidence.breach(1).unwrap() - 0.1333).abs() < 0.001);
+	}
+}
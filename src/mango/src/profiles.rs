@@ -0,0 +1,130 @@
+use std::str::FromStr;
+
+use mangol_common::errors::{MangolError, MangolResult};
+use serde::{Deserialize, Serialize};
+use solana_program::pubkey::Pubkey;
+
+pub const DEFAULT_PROFILE: &str = "mainnet.1";
+
+/// A mango group on a cluster, everything the stack needs to be pointed at it. The cache and
+/// the group's markets are read from the group account itself
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct GroupProfile {
+	/// e.g. `mainnet.1` or `devnet.2`, the cluster followed by the group's number on it
+	pub name: String,
+	pub rpc_url: String,
+	pub mango_program: String,
+	pub mango_group: String,
+	/// Market registry of the group, `./files/perpMarkets.json` when None
+	#[serde(default)]
+	pub markets_file: Option<String>,
+	/// Build the registry from the perp markets the group lists instead of `markets_file`,
+	/// for groups nobody keeps a registry file of
+	#[serde(default)]
+	pub markets_on_chain: bool,
+	/// Market traded when none is picked, `SOL-PERP` when None
+	#[serde(default)]
+	pub default_market: Option<String>,
+}
+
+impl GroupProfile {
+	pub fn mango_program_id(&self) -> MangolResult<Pubkey> {
+		Pubkey::from_str(&self.mango_program).map_err(|e| MangolError::MangoError(format!("{} mango program {}", self.name, e)))
+	}
+
+	pub fn mango_group_pk(&self) -> MangolResult<Pubkey> {
+		Pubkey::from_str(&self.mango_group).map_err(|e| MangolError::MangoError(format!("{} mango group {}", self.name, e)))
+	}
+
+	pub fn markets_file(&self) -> &str {
+		self.markets_file.as_deref().unwrap_or("./files/perpMarkets.json")
+	}
+
+	pub fn default_market(&self) -> &str {
+		self.default_market.as_deref().unwrap_or("SOL-PERP")
+	}
+
+	pub fn is_devnet(&self) -> bool {
+		self.name.starts_with("devnet")
+	}
+}
+
+/// The groups mango v3 deployed, what a profiles file is merged over
+pub fn builtin_profiles() -> Vec<GroupProfile> {
+	vec![
+		GroupProfile {
+			name: "mainnet.1".to_string(),
+			rpc_url: "https://ninja.genesysgo.net".to_string(),
+			mango_program: "mv3ekLzLbnVPNxjSKvqBpU3ZeZXPQdEC3bp5MDEBG68".to_string(),
+			mango_group: "98pjRuQjK3qA6gXts96PqZT4Ze5QmnCmt3QYjhbUSPue".to_string(),
+			markets_file: None,
+			markets_on_chain: false,
+			default_market: None,
+		},
+		GroupProfile {
+			name: "devnet.2".to_string(),
+			rpc_url: "https://api.devnet.solana.com".to_string(),
+			mango_program: "4skJ85cdxQAFVKbcGgfun8iZPL7BadVYXG3kGEGkufqA".to_string(),
+			mango_group: "Ec2enZyoC4nGpEfu2sUNAa2nUGJHWxoUWYSEJ2hNTWTA".to_string(),
+			markets_file: None,
+			markets_on_chain: true,
+			// SOL-PERP, the group lists it at the same index as mainnet
+			default_market: Some("PERP-3".to_string()),
+		},
+	]
+}
+
+/// Profiles by name, resolved at runtime so one build runs against any of them
+#[derive(Clone, Debug)]
+pub struct GroupProfiles {
+	pub profiles: Vec<GroupProfile>,
+}
+
+impl Default for GroupProfiles {
+	fn default() -> Self {
+		Self { profiles: builtin_profiles() }
+	}
+}
+
+impl GroupProfiles {
+	/// The builtin profiles with the json list at `path` added, a profile there replaces the builtin of the same name
+	pub fn load(path: &str) -> MangolResult<Self> {
+		let loaded: Vec<GroupProfile> = serde_json::from_str(&std::fs::read_to_string(path)?).map_err(|e| MangolError::SerializationError(e.to_string()))?;
+		let mut profiles = Self::default();
+		for profile in loaded {
+			profiles.insert(profile);
+		}
+		Ok(profiles)
+	}
+
+	pub fn insert(&mut self, profile: GroupProfile) {
+		self.profiles.retain(|existing| existing.name != profile.name);
+		self.profiles.push(profile);
+	}
+
+	pub fn get(&self, name: &str) -> MangolResult<GroupProfile> {
+		self.profiles.iter().find(|profile| profile.name == name).cloned().ok_or_else(|| {
+			let names: Vec<&str> = self.profiles.iter().map(|profile| profile.name.as_str()).collect();
+			MangolError::MangoError(format!("Unknown group profile {}, known: {}", name, names.join(", ")))
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::profiles::{GroupProfile, GroupProfiles, DEFAULT_PROFILE};
+
+	#[test]
+	fn resolves_builtin_and_overridden_profiles() {
+		let mut profiles = GroupProfiles::default();
+		let mainnet = profiles.get(DEFAULT_PROFILE).unwrap();
+		assert_eq!(mainnet.mango_program_id().unwrap().to_string(), "mv3ekLzLbnVPNxjSKvqBpU3ZeZXPQdEC3bp5MDEBG68");
+		assert_eq!(mainnet.markets_file(), "./files/perpMarkets.json");
+		assert!(profiles.get("devnet.2").unwrap().is_devnet());
+		assert!(profiles.get("devnet.2").unwrap().markets_on_chain);
+		assert!(profiles.get("testnet.0").is_err());
+		profiles.insert(GroupProfile { rpc_url: "http://127.0.0.1:8899".to_string(), ..profiles.get("devnet.2").unwrap() });
+		assert_eq!(profiles.profiles.len(), 2);
+		assert_eq!(profiles.get("devnet.2").unwrap().rpc_url, "http://127.0.0.1:8899");
+	}
+}
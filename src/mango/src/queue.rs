@@ -0,0 +1,183 @@
+use arrayref::{array_ref, array_refs};
+use fixed::types::I80F48;
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+use solana_program::pubkey::Pubkey;
+use std::convert::TryFrom;
+
+use crate::error::MangoResult;
+use crate::types::Side;
+
+pub const EVENT_SIZE: usize = 200;
+pub const EVENT_QUEUE_HEADER_SIZE: usize = 32;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, IntoPrimitive, TryFromPrimitive)]
+#[repr(u8)]
+pub enum EventType {
+	Fill,
+	Out,
+	Liquidate,
+}
+
+/// Header of a perp market EventQueue, followed by a ring buffer of EVENT_SIZE events
+#[derive(Copy, Clone, Debug)]
+pub struct EventQueueHeader {
+	pub head: usize,
+	pub count: usize,
+	pub seq_num: usize,
+}
+
+impl EventQueueHeader {
+	pub fn load_from_bytes(data: &[u8]) -> MangoResult<Self> {
+		let header = array_ref![data, 0, EVENT_QUEUE_HEADER_SIZE];
+		let (_meta_data, head, count, seq_num) = array_refs![header, 8, 8, 8, 8];
+		Ok(Self {
+			head: u64::from_le_bytes(*head) as usize,
+			count: u64::from_le_bytes(*count) as usize,
+			seq_num: u64::from_le_bytes(*seq_num) as usize,
+		})
+	}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FillEvent {
+	pub taker_side: Side, // side from the taker's POV
+	pub maker_slot: u8,
+	pub maker_out: bool, // true if maker order quantity == 0
+	pub version: u8,
+	pub market_fees_applied: bool,
+	pub timestamp: u64,
+	pub seq_num: usize,
+	pub maker: Pubkey,
+	pub maker_order_id: i128,
+	pub maker_client_order_id: u64,
+	pub maker_fee: I80F48,
+	pub best_initial: i64,
+	pub maker_timestamp: u64,
+	pub taker: Pubkey,
+	pub taker_order_id: i128,
+	pub taker_client_order_id: u64,
+	pub taker_fee: I80F48,
+	pub price: i64,    // quote lots per base lot
+	pub quantity: i64, // number of base lots
+}
+
+impl FillEvent {
+	/// Decodes a FillEvent field by field, returns None for any other event type
+	pub fn from_bytes(data: &[u8; EVENT_SIZE]) -> Option<Self> {
+		let (
+			event_type,
+			taker_side,
+			maker_slot,
+			maker_out,
+			version,
+			market_fees_applied,
+			_padding,
+			timestamp,
+			seq_num,
+			maker,
+			maker_order_id,
+			maker_client_order_id,
+			maker_fee,
+			best_initial,
+			maker_timestamp,
+			taker,
+			taker_order_id,
+			taker_client_order_id,
+			taker_fee,
+			price,
+			quantity,
+		) = array_refs![data, 1, 1, 1, 1, 1, 1, 2, 8, 8, 32, 16, 8, 16, 8, 8, 32, 16, 8, 16, 8, 8];
+		if EventType::try_from(event_type[0]).ok()? != EventType::Fill {
+			return None;
+		}
+		Some(Self {
+			taker_side: Side::try_from(taker_side[0]).ok()?,
+			maker_slot: maker_slot[0],
+			maker_out: maker_out[0] != 0,
+			version: version[0],
+			market_fees_applied: market_fees_applied[0] != 0,
+			timestamp: u64::from_le_bytes(*timestamp),
+			seq_num: u64::from_le_bytes(*seq_num) as usize,
+			maker: Pubkey::new_from_array(*maker),
+			maker_order_id: i128::from_le_bytes(*maker_order_id),
+			maker_client_order_id: u64::from_le_bytes(*maker_client_order_id),
+			maker_fee: I80F48::from_le_bytes(*maker_fee),
+			best_initial: i64::from_le_bytes(*best_initial),
+			maker_timestamp: u64::from_le_bytes(*maker_timestamp),
+			taker: Pubkey::new_from_array(*taker),
+			taker_order_id: i128::from_le_bytes(*taker_order_id),
+			taker_client_order_id: u64::from_le_bytes(*taker_client_order_id),
+			taker_fee: I80F48::from_le_bytes(*taker_fee),
+			price: i64::from_le_bytes(*price),
+			quantity: i64::from_le_bytes(*quantity),
+		})
+	}
+}
+
+/// Returns the raw events currently in the queue, oldest first
+pub fn load_events(data: &[u8]) -> MangoResult<(EventQueueHeader, Vec<[u8; EVENT_SIZE]>)> {
+	let header = EventQueueHeader::load_from_bytes(data)?;
+	let capacity = (data.len() - EVENT_QUEUE_HEADER_SIZE) / EVENT_SIZE;
+	let mut events = Vec::with_capacity(header.count);
+	for i in 0..header.count {
+		let slot = (header.head + i) % capacity;
+		let offset = EVENT_QUEUE_HEADER_SIZE + slot * EVENT_SIZE;
+		events.push(*array_ref![data, offset, EVENT_SIZE]);
+	}
+	Ok((header, events))
+}
+
+/// Returns the fill events pushed after `last_seq_num`, oldest first
+pub fn load_fills_since(data: &[u8], last_seq_num: usize) -> MangoResult<(usize, Vec<FillEvent>)> {
+	let (header, events) = load_events(data)?;
+	let new_events = header.seq_num.saturating_sub(last_seq_num).min(events.len());
+	let fills = events[events.len() - new_events..]
+		  .iter()
+		  .filter_map(FillEvent::from_bytes)
+		  .collect();
+	Ok((header.seq_num, fills))
+}
+
+/// Mango accounts a consume_events of the first `limit` events has to be passed, in queue order
+/// without duplicates. The program stops at the first event whose accounts are missing
+pub fn event_accounts(events: &[[u8; EVENT_SIZE]], limit: usize) -> Vec<Pubkey> {
+	let mut accounts = vec![];
+	for event in events.iter().take(limit) {
+		let event_accounts = match EventType::try_from(event[0]) {
+			Ok(EventType::Fill) => FillEvent::from_bytes(event).map(|fill| vec![fill.maker, fill.taker]).unwrap_or_default(),
+			// owner after type, side, slot, padding, timestamp and seq_num
+			Ok(EventType::Out) => vec![Pubkey::new_from_array(*array_ref![event, 24, 32])],
+			// liqee then liqor after type, padding, timestamp and seq_num
+			Ok(EventType::Liquidate) => vec![Pubkey::new_from_array(*array_ref![event, 24, 32]), Pubkey::new_from_array(*array_ref![event, 56, 32])],
+			Err(_) => vec![],
+		};
+		for account in event_accounts {
+			if !accounts.contains(&account) {
+				accounts.push(account);
+			}
+		}
+	}
+	accounts
+}
+
+#[cfg(test)]
+mod tests {
+	use solana_program::pubkey::Pubkey;
+	use crate::queue::{event_accounts, EventType, EVENT_SIZE};
+
+	#[test]
+	fn lists_accounts_of_queued_events() {
+		let (maker, taker, owner) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+		let mut fill = [0u8; EVENT_SIZE];
+		fill[0] = EventType::Fill as u8;
+		fill[24..56].copy_from_slice(maker.as_ref());
+		fill[112..144].copy_from_slice(taker.as_ref());
+		let mut out = [0u8; EVENT_SIZE];
+		out[0] = EventType::Out as u8;
+		out[24..56].copy_from_slice(owner.as_ref());
+		let mut self_trade = fill;
+		self_trade[112..144].copy_from_slice(maker.as_ref());
+		assert_eq!(event_accounts(&[fill, out, self_trade], 10), vec![maker, taker, owner]);
+		assert_eq!(event_accounts(&[out, fill], 1), vec![owner]);
+	}
+}
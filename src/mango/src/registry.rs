@@ -0,0 +1,181 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use mangol_common::errors::{MangolError, MangolResult};
+
+use solana_program::pubkey::Pubkey;
+
+use crate::types::{MangoAccount, MangoCache, MangoGroup, PerpAccount, PerpMarket, PerpMarketCache, PerpMarketData, PerpMarketInfo, PriceCache, MAX_PAIRS, QUOTE_INDEX};
+
+/// The group's perp markets by name, e.g. "SOL-PERP". Strategies look markets up here and read
+/// group, cache and account state through the market instead of carrying its index around,
+/// where an off by one trades the wrong market
+#[derive(Clone, Debug, Default)]
+pub struct MarketRegistry {
+	pub markets: Vec<PerpMarketData>,
+}
+
+impl MarketRegistry {
+	/// Errors on duplicate names or indexes the group can't have
+	pub fn new(markets: Vec<PerpMarketData>) -> MangolResult<Self> {
+		let mut names = HashSet::new();
+		for market in &markets {
+			if market.market_index >= MAX_PAIRS {
+				return Err(MangolError::MangoError(format!("{} has market index {}, groups have {} markets", market.name, market.market_index, MAX_PAIRS)));
+			}
+			if !names.insert(market.name.as_str()) {
+				return Err(MangolError::MangoError(format!("{} is listed twice", market.name)));
+			}
+		}
+		Ok(Self { markets })
+	}
+
+	/// Reads a registry in the format of files/perpMarkets.json
+	pub fn load<P: AsRef<Path>>(path: P) -> MangolResult<Self> {
+		let markets = serde_json::from_str(&std::fs::read_to_string(path)?)
+			  .map_err(|e| MangolError::SerializationError(e.to_string()))?;
+		Self::new(markets)
+	}
+
+	/// The perp markets `mango_group` lists, for groups without a registry file. The chain has
+	/// no market names, markets are named `PERP-<index>` after their index in the group
+	pub fn from_group(mango_group: &MangoGroup, perp_markets: &[(usize, Pubkey, PerpMarket)]) -> MangolResult<Self> {
+		Self::new(perp_markets.iter().map(|(market_index, perp_market_pk, perp_market)| PerpMarketData::from_group(mango_group, *market_index, perp_market_pk, perp_market)).collect())
+	}
+
+	pub fn get(&self, name: &str) -> MangolResult<&PerpMarketData> {
+		self.markets.iter().find(|market| market.name == name)
+			  .ok_or_else(|| MangolError::MangoError(format!("Unknown perp market {}", name)))
+	}
+
+	pub fn names(&self) -> impl Iterator<Item = &str> {
+		self.markets.iter().map(|market| market.name.as_str())
+	}
+}
+
+impl PerpMarketData {
+	/// Registry entry of the market at `market_index`, decimals come from the group's tokens
+	pub fn from_group(mango_group: &MangoGroup, market_index: usize, perp_market_pk: &Pubkey, perp_market: &PerpMarket) -> Self {
+		Self {
+			name: format!("PERP-{}", market_index),
+			pubkey: perp_market_pk.to_string(),
+			base_symbol: format!("TOKEN-{}", market_index),
+			base_decimals: mango_group.tokens[market_index].decimals,
+			quote_decimals: mango_group.tokens[QUOTE_INDEX].decimals,
+			market_index,
+			bids_key: perp_market.bids.to_string(),
+			asks_key: perp_market.asks.to_string(),
+			events_key: perp_market.event_queue.to_string(),
+		}
+	}
+
+	pub fn perp_market_info<'a>(&self, mango_group: &'a MangoGroup) -> &'a PerpMarketInfo {
+		&mango_group.perp_markets[self.market_index]
+	}
+
+	pub fn perp_market_cache<'a>(&self, mango_cache: &'a MangoCache) -> &'a PerpMarketCache {
+		&mango_cache.perp_market_cache[self.market_index]
+	}
+
+	pub fn price_cache<'a>(&self, mango_cache: &'a MangoCache) -> &'a PriceCache {
+		&mango_cache.price_cache[self.market_index]
+	}
+
+	/// Oracle price in native quote per native base
+	pub fn oracle_price(&self, mango_cache: &MangoCache) -> f64 {
+		mango_cache.get_price(self.market_index)
+	}
+
+	/// Native quote per native base to UI quote per UI base
+	pub fn ui_price(&self, native_price: f64) -> f64 {
+		native_price * 10_f64.powi(self.base_decimals as i32 - self.quote_decimals as i32)
+	}
+
+	/// Base lots to UI base
+	pub fn ui_base_size(&self, perp_market_info: &PerpMarketInfo, base_lots: i64) -> f64 {
+		(base_lots * perp_market_info.base_lot_size) as f64 / 10_f64.powi(self.base_decimals as i32)
+	}
+
+	pub fn perp_account<'a>(&self, mango_account: &'a MangoAccount) -> &'a PerpAccount {
+		&mango_account.perp_accounts[self.market_index]
+	}
+}
+
+impl MangoCache {
+	pub fn price_for(&self, registry: &MarketRegistry, name: &str) -> MangolResult<f64> {
+		Ok(registry.get(name)?.oracle_price(self))
+	}
+}
+
+impl MangoGroup {
+	pub fn perp_market_for(&self, registry: &MarketRegistry, name: &str) -> MangolResult<&PerpMarketInfo> {
+		Ok(registry.get(name)?.perp_market_info(self))
+	}
+}
+
+impl MangoAccount {
+	pub fn perp_account_for(&self, registry: &MarketRegistry, name: &str) -> MangolResult<&PerpAccount> {
+		Ok(registry.get(name)?.perp_account(self))
+	}
+}
+
+// the market fixture lives in mock, built with the client
+#[cfg(all(test, feature = "client"))]
+mod tests {
+	use std::mem::size_of;
+	use fixed::types::I80F48;
+	use solana_program::pubkey::Pubkey;
+	use crate::health::{decode_mango_cache, decode_mango_group};
+	use crate::mock::sol_perp;
+	use crate::profiles::builtin_profiles;
+	use crate::registry::MarketRegistry;
+	use crate::types::{MangoCache, MangoGroup, PerpMarket, PerpMarketData, MAX_PAIRS, QUOTE_INDEX};
+
+	fn market(name: &str, market_index: usize) -> PerpMarketData {
+		PerpMarketData {
+			name: name.to_string(),
+			base_symbol: name.trim_end_matches("-PERP").to_string(),
+			market_index,
+			..sol_perp()
+		}
+	}
+
+	#[test]
+	fn looks_prices_up_by_name() {
+		let registry = MarketRegistry::new(vec![market("BTC-PERP", 1), market("SOL-PERP", 3)]).unwrap();
+		let mut mango_cache = decode_mango_cache(&vec![0u8; size_of::<MangoCache>()]).unwrap();
+		mango_cache.price_cache[3].price = I80F48::from_num(25.5);
+		assert_eq!(mango_cache.price_for(&registry, "SOL-PERP").unwrap(), 25.5);
+		assert!(mango_cache.price_for(&registry, "ETH-PERP").is_err());
+		assert!(MarketRegistry::new(vec![market("SOL-PERP", 3), market("SOL-PERP", 4)]).is_err());
+		assert!(MarketRegistry::new(vec![market("SOL-PERP", 99)]).is_err());
+	}
+
+	#[test]
+	fn builds_registry_from_the_group() {
+		let mut mango_group = decode_mango_group(&vec![0u8; size_of::<MangoGroup>()]).unwrap();
+		mango_group.tokens[3].decimals = 9;
+		mango_group.tokens[QUOTE_INDEX].decimals = 6;
+		let mut perp_market: PerpMarket = bytemuck::Zeroable::zeroed();
+		perp_market.bids = Pubkey::new_unique();
+		let registry = MarketRegistry::from_group(&mango_group, &[(3, Pubkey::new_unique(), perp_market)]).unwrap();
+		let market = registry.get("PERP-3").unwrap();
+		assert_eq!((market.base_decimals, market.quote_decimals), (9, 6));
+		assert_eq!(market.bids_key, perp_market.bids.to_string());
+	}
+
+	#[test]
+	fn resolves_the_default_market_of_every_builtin_profile() {
+		let mango_group = decode_mango_group(&vec![0u8; size_of::<MangoGroup>()]).unwrap();
+		let perp_market: PerpMarket = bytemuck::Zeroable::zeroed();
+		for profile in builtin_profiles() {
+			let registry = if profile.markets_on_chain {
+				let listed: Vec<_> = (0..MAX_PAIRS).map(|market_index| (market_index, Pubkey::new_unique(), perp_market)).collect();
+				MarketRegistry::from_group(&mango_group, &listed).unwrap()
+			} else {
+				MarketRegistry::load(std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../..").join(profile.markets_file())).unwrap()
+			};
+			assert!(registry.get(profile.default_market()).is_ok(), "{} has no market {}", profile.name, profile.default_market());
+		}
+	}
+}
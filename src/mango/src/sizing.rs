@@ -0,0 +1,219 @@
+use mangol_common::errors::{MangolResult, SizingError};
+
+use crate::types::{OrderType, PerpMarketInfo, Side};
+
+/// Lot prices closer than this to a whole tick are float noise of a price that was on the tick
+const TICK_EPSILON: f64 = 1e-6;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Rounding {
+	Down,
+	Up,
+	Nearest,
+}
+
+impl Rounding {
+	fn apply(&self, value: f64) -> f64 {
+		match self {
+			Rounding::Down => value.floor(),
+			Rounding::Up => value.ceil(),
+			Rounding::Nearest => value.round(),
+		}
+	}
+}
+
+/// Which way a price is moved onto the market's tick, one quote lot per base lot
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TickRounding {
+	/// Passive for orders that can rest, aggressive for orders that only take
+	ByOrderType,
+	/// Away from the other side of the book, bids down and asks up, so a resting order never crosses by a tick
+	Passive,
+	/// Toward the other side, bids up and asks down, so a taker doesn't stop a tick short of its fill
+	Aggressive,
+	Nearest,
+}
+
+impl Default for TickRounding {
+	fn default() -> Self {
+		TickRounding::ByOrderType
+	}
+}
+
+impl TickRounding {
+	pub fn rounding(&self, side: Side, order_type: OrderType) -> Rounding {
+		let passive = match self {
+			TickRounding::ByOrderType => !matches!(order_type, OrderType::ImmediateOrCancel | OrderType::Market),
+			TickRounding::Passive => true,
+			TickRounding::Aggressive => false,
+			TickRounding::Nearest => return Rounding::Nearest,
+		};
+		match (side, passive) {
+			(Side::Bid, true) | (Side::Ask, false) => Rounding::Down,
+			(Side::Bid, false) | (Side::Ask, true) => Rounding::Up,
+		}
+	}
+}
+
+fn to_lots(value: f64, rounding: Rounding, what: &str) -> MangolResult<i64> {
+	if !value.is_finite() {
+		return Err(SizingError::Overflow(what.to_string()).into());
+	}
+	let rounded = rounding.apply(value);
+	if rounded >= i64::MAX as f64 || rounded <= i64::MIN as f64 {
+		return Err(SizingError::Overflow(what.to_string()).into());
+	}
+	if rounded as i64 <= 0 {
+		return Err(SizingError::ZeroSize(what.to_string()).into());
+	}
+	Ok(rounded as i64)
+}
+
+/// How a strategy rounds computed sizes and prices to lots and the smallest order it is willing to send
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SizingPolicy {
+	pub rounding: Rounding,
+	pub min_base_lots: i64,
+	pub tick_rounding: TickRounding,
+}
+
+impl Default for SizingPolicy {
+	fn default() -> Self {
+		Self {
+			rounding: Rounding::Nearest,
+			min_base_lots: 1,
+			tick_rounding: TickRounding::default(),
+		}
+	}
+}
+
+/// Converts prices and sizes between ui, native and lot units of one perp market.
+/// Every conversion rounds explicitly and fails instead of producing zero or overflowing lots
+#[derive(Copy, Clone, Debug)]
+pub struct OrderSizer {
+	pub base_lot_size: i64,
+	pub quote_lot_size: i64,
+}
+
+impl OrderSizer {
+	pub fn new(perp_market: &PerpMarketInfo) -> Self {
+		Self {
+			base_lot_size: perp_market.base_lot_size,
+			quote_lot_size: perp_market.quote_lot_size,
+		}
+	}
+
+	/// Native price (native quote per native base) to quote lots per base lot
+	pub fn price_lots(&self, price: f64, rounding: Rounding) -> MangolResult<i64> {
+		if !price.is_finite() || price <= 0.0 {
+			return Err(SizingError::InvalidPrice(price).into());
+		}
+		to_lots(price * self.base_lot_size as f64 / self.quote_lot_size as f64, rounding, &format!("price {}", price))
+	}
+
+	/// Native price of one tick
+	pub fn tick_size(&self) -> f64 {
+		self.quote_lot_size as f64 / self.base_lot_size as f64
+	}
+
+	/// Native price to lots on a whole tick, rounded the way `tick_rounding` says for an order of
+	/// `order_type` on `side`
+	pub fn tick_price_lots(&self, price: f64, side: Side, order_type: OrderType, tick_rounding: TickRounding) -> MangolResult<i64> {
+		if !price.is_finite() || price <= 0.0 {
+			return Err(SizingError::InvalidPrice(price).into());
+		}
+		let lots = price * self.base_lot_size as f64 / self.quote_lot_size as f64;
+		let rounding = if (lots - lots.round()).abs() < TICK_EPSILON { Rounding::Nearest } else { tick_rounding.rounding(side, order_type) };
+		to_lots(lots, rounding, &format!("price {}", price))
+	}
+
+	/// Native price moved onto a tick, see tick_price_lots
+	pub fn round_to_tick(&self, price: f64, side: Side, order_type: OrderType, tick_rounding: TickRounding) -> MangolResult<f64> {
+		Ok(self.tick_price_lots(price, side, order_type, tick_rounding)? as f64 * self.tick_size())
+	}
+
+	pub fn base_lots_from_quote_lots(&self, quote_lots: i64, price_lots: i64, rounding: Rounding) -> MangolResult<i64> {
+		if price_lots <= 0 {
+			return Err(SizingError::InvalidPrice(price_lots as f64).into());
+		}
+		to_lots(quote_lots as f64 / price_lots as f64, rounding, &format!("{} quote lots at {} lots", quote_lots, price_lots))
+	}
+
+	pub fn quote_lots_from_base_lots(&self, base_lots: i64, price_lots: i64) -> MangolResult<i64> {
+		let quote_lots = base_lots.checked_mul(price_lots)
+			  .ok_or_else(|| SizingError::Overflow(format!("{} base lots at {} lots", base_lots, price_lots)))?;
+		if quote_lots <= 0 {
+			return Err(SizingError::ZeroSize(format!("{} base lots at {} lots", base_lots, price_lots)).into());
+		}
+		Ok(quote_lots)
+	}
+
+	/// Checks a quote lot order fills at least `policy.min_base_lots` at `price_lots`,
+	/// the order book drops anything that converts to zero base lots
+	pub fn check_min_order(&self, quote_lots: i64, price_lots: i64, policy: &SizingPolicy) -> MangolResult<i64> {
+		if price_lots <= 0 {
+			return Err(SizingError::InvalidPrice(price_lots as f64).into());
+		}
+		let base_lots = quote_lots / price_lots;
+		if base_lots < policy.min_base_lots.max(1) {
+			return Err(SizingError::BelowMinimum(base_lots, policy.min_base_lots.max(1)).into());
+		}
+		Ok(quote_lots)
+	}
+
+	pub fn base_lots_from_ui(&self, ui_size: f64, base_decimals: u8, rounding: Rounding) -> MangolResult<i64> {
+		to_lots(ui_size * 10_f64.powi(base_decimals as i32) / self.base_lot_size as f64, rounding, &format!("size {}", ui_size))
+	}
+
+	pub fn quote_lots_from_ui(&self, ui_amount: f64, quote_decimals: u8, rounding: Rounding) -> MangolResult<i64> {
+		to_lots(ui_amount * 10_f64.powi(quote_decimals as i32) / self.quote_lot_size as f64, rounding, &format!("amount {}", ui_amount))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use mangol_common::errors::{MangolError, SizingError};
+	use crate::sizing::{OrderSizer, Rounding, SizingPolicy, TickRounding};
+	use crate::types::{OrderType, Side};
+
+	#[test]
+	fn converts_with_explicit_rounding() {
+		let sizer = OrderSizer { base_lot_size: 10_000_000, quote_lot_size: 100 };
+		assert_eq!(sizer.price_lots(0.04, Rounding::Nearest).unwrap(), 4_000);
+		assert_eq!(sizer.base_lots_from_quote_lots(485_000, 4_000, Rounding::Down).unwrap(), 121);
+		assert_eq!(sizer.base_lots_from_quote_lots(485_000, 4_000, Rounding::Up).unwrap(), 122);
+		assert_eq!(sizer.quote_lots_from_ui(48.54, 6, Rounding::Nearest).unwrap(), 485_400);
+	}
+
+	#[test]
+	fn errors_instead_of_zero_or_overflow() {
+		let sizer = OrderSizer { base_lot_size: 100, quote_lot_size: 10 };
+		assert!(matches!(sizer.base_lots_from_quote_lots(10, 40_000, Rounding::Down), Err(MangolError::SizingError(SizingError::ZeroSize(_)))));
+		assert!(matches!(sizer.quote_lots_from_base_lots(i64::MAX, 2), Err(MangolError::SizingError(SizingError::Overflow(_)))));
+		assert!(matches!(sizer.price_lots(-1.0, Rounding::Nearest), Err(MangolError::SizingError(SizingError::InvalidPrice(_)))));
+	}
+
+	#[test]
+	fn rejects_orders_below_minimum() {
+		let sizer = OrderSizer { base_lot_size: 10_000_000, quote_lot_size: 100 };
+		let policy = SizingPolicy { rounding: Rounding::Down, min_base_lots: 5, ..SizingPolicy::default() };
+		assert_eq!(sizer.check_min_order(20_000, 4_000, &policy).unwrap(), 20_000);
+		assert!(matches!(sizer.check_min_order(19_999, 4_000, &policy), Err(MangolError::SizingError(SizingError::BelowMinimum(4, 5)))));
+		assert!(matches!(sizer.check_min_order(3_999, 4_000, &SizingPolicy::default()), Err(MangolError::SizingError(SizingError::BelowMinimum(0, 1)))));
+	}
+
+	#[test]
+	fn rounds_resting_orders_passive_and_takers_aggressive() {
+		let sizer = OrderSizer { base_lot_size: 10_000_000, quote_lot_size: 100 };
+		let policy = TickRounding::default();
+		assert_eq!(sizer.tick_price_lots(0.04005, Side::Bid, OrderType::PostOnly, policy).unwrap(), 4_005);
+		assert_eq!(sizer.tick_price_lots(0.040056, Side::Bid, OrderType::PostOnly, policy).unwrap(), 4_005);
+		assert_eq!(sizer.tick_price_lots(0.040051, Side::Ask, OrderType::PostOnlySlide, policy).unwrap(), 4_006);
+		assert_eq!(sizer.tick_price_lots(0.040051, Side::Bid, OrderType::ImmediateOrCancel, policy).unwrap(), 4_006);
+		assert_eq!(sizer.tick_price_lots(0.040059, Side::Ask, OrderType::Market, policy).unwrap(), 4_005);
+		assert_eq!(sizer.tick_price_lots(0.040059, Side::Ask, OrderType::Market, TickRounding::Nearest).unwrap(), 4_006);
+		// 0.07 in lots is 7000.000000000001, rounding it up would move a passive ask a tick away
+		assert_eq!(sizer.tick_price_lots(0.07, Side::Ask, OrderType::PostOnly, policy).unwrap(), 7_000);
+		assert!((sizer.round_to_tick(0.040056, Side::Bid, OrderType::Limit, policy).unwrap() - 0.04005).abs() < 1e-12);
+	}
+}
@@ -0,0 +1,213 @@
+use std::fmt::Display;
+
+use mangol_common::errors::{MangolError, MangolResult};
+use serde::{Deserialize, Serialize};
+use solana_program::pubkey::Pubkey;
+
+use crate::types::{MangoGroup, MAX_PAIRS, MAX_TOKENS};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TokenParams {
+	pub token_index: usize,
+	pub mint: String,
+	pub root_bank: String,
+	pub decimals: u8,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SpotParams {
+	pub market_index: usize,
+	pub spot_market: String,
+	pub maint_asset_weight: f64,
+	pub init_asset_weight: f64,
+	pub maint_liab_weight: f64,
+	pub init_liab_weight: f64,
+	pub liquidation_fee: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PerpParams {
+	pub market_index: usize,
+	pub perp_market: String,
+	pub maint_asset_weight: f64,
+	pub init_asset_weight: f64,
+	pub maint_liab_weight: f64,
+	pub init_liab_weight: f64,
+	pub liquidation_fee: f64,
+	pub maker_fee: f64,
+	pub taker_fee: f64,
+	pub base_lot_size: i64,
+	pub quote_lot_size: i64,
+}
+
+/// The group parameters strategies cache assumptions on, in a form that can be stored and compared
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct GroupSnapshot {
+	pub mango_group: String,
+	pub taken_at: u64,
+	pub tokens: Vec<TokenParams>,
+	pub spot_markets: Vec<SpotParams>,
+	pub perp_markets: Vec<PerpParams>,
+	pub oracles: Vec<String>,
+	pub ref_surcharge_centibps: u32,
+	pub ref_share_centibps: u32,
+	pub ref_mngo_required: u64,
+}
+
+/// A parameter that differs between two snapshots
+#[derive(Clone, Debug, PartialEq)]
+pub struct GroupChange {
+	pub path: String,
+	pub before: String,
+	pub after: String,
+	/// Weights, fees, lot sizes and decimals, which the fee model and sizing depend on
+	pub risk: bool,
+}
+
+impl GroupSnapshot {
+	pub fn capture(mango_group_pk: &Pubkey, mango_group: &MangoGroup, taken_at: u64) -> Self {
+		let tokens = (0..MAX_TOKENS).filter(|i| !mango_group.tokens[*i].is_empty()).map(|i| {
+			let token = &mango_group.tokens[i];
+			TokenParams { token_index: i, mint: token.mint.to_string(), root_bank: token.root_bank.to_string(), decimals: token.decimals }
+		}).collect();
+		let spot_markets = (0..MAX_PAIRS).filter(|i| !mango_group.spot_markets[*i].is_empty()).map(|i| {
+			let market = &mango_group.spot_markets[i];
+			SpotParams {
+				market_index: i,
+				spot_market: market.spot_market.to_string(),
+				maint_asset_weight: market.maint_asset_weight.to_num(),
+				init_asset_weight: market.init_asset_weight.to_num(),
+				maint_liab_weight: market.maint_liab_weight.to_num(),
+				init_liab_weight: market.init_liab_weight.to_num(),
+				liquidation_fee: market.liquidation_fee.to_num(),
+			}
+		}).collect();
+		let perp_markets = (0..MAX_PAIRS).filter(|i| !mango_group.perp_markets[*i].is_empty()).map(|i| {
+			let market = &mango_group.perp_markets[i];
+			PerpParams {
+				market_index: i,
+				perp_market: market.perp_market.to_string(),
+				maint_asset_weight: market.maint_asset_weight.to_num(),
+				init_asset_weight: market.init_asset_weight.to_num(),
+				maint_liab_weight: market.maint_liab_weight.to_num(),
+				init_liab_weight: market.init_liab_weight.to_num(),
+				liquidation_fee: market.liquidation_fee.to_num(),
+				maker_fee: market.maker_fee.to_num(),
+				taker_fee: market.taker_fee.to_num(),
+				base_lot_size: market.base_lot_size,
+				quote_lot_size: market.quote_lot_size,
+			}
+		}).collect();
+		Self {
+			mango_group: mango_group_pk.to_string(),
+			taken_at,
+			tokens,
+			spot_markets,
+			perp_markets,
+			oracles: mango_group.oracles[..mango_group.num_oracles].iter().map(|oracle| oracle.to_string()).collect(),
+			ref_surcharge_centibps: mango_group.ref_surcharge_centibps,
+			ref_share_centibps: mango_group.ref_share_centibps,
+			ref_mngo_required: mango_group.ref_mngo_required,
+		}
+	}
+
+	pub fn load(path: &str) -> MangolResult<Self> {
+		serde_json::from_str(&std::fs::read_to_string(path)?).map_err(|e| MangolError::SerializationError(e.to_string()))
+	}
+
+	pub fn save(&self, path: &str) -> MangolResult<()> {
+		let data = serde_json::to_string_pretty(self).map_err(|e| MangolError::SerializationError(e.to_string()))?;
+		std::fs::write(path, data)?;
+		Ok(())
+	}
+}
+
+fn compare<T: PartialEq + Display>(changes: &mut Vec<GroupChange>, path: String, before: T, after: T, risk: bool) {
+	if before != after {
+		changes.push(GroupChange { path, before: before.to_string(), after: after.to_string(), risk });
+	}
+}
+
+fn find<'a, T>(items: &'a [T], index: usize, index_of: fn(&T) -> usize) -> Option<&'a T> {
+	items.iter().find(|item| index_of(item) == index)
+}
+
+/// Every parameter that changed from `before` to `after`, markets matched by index
+pub fn diff_snapshots(before: &GroupSnapshot, after: &GroupSnapshot) -> Vec<GroupChange> {
+	let mut changes = vec![];
+	for i in 0..MAX_TOKENS {
+		match (find(&before.tokens, i, |t| t.token_index), find(&after.tokens, i, |t| t.token_index)) {
+			(Some(b), Some(a)) => {
+				compare(&mut changes, format!("tokens[{}].mint", i), &b.mint, &a.mint, false);
+				compare(&mut changes, format!("tokens[{}].root_bank", i), &b.root_bank, &a.root_bank, false);
+				compare(&mut changes, format!("tokens[{}].decimals", i), b.decimals, a.decimals, true);
+			}
+			(None, Some(a)) => compare(&mut changes, format!("tokens[{}]", i), "none", &a.mint, false),
+			(Some(b), None) => compare(&mut changes, format!("tokens[{}]", i), &b.mint, "none", true),
+			(None, None) => {}
+		}
+	}
+	for i in 0..MAX_PAIRS {
+		match (find(&before.spot_markets, i, |m| m.market_index), find(&after.spot_markets, i, |m| m.market_index)) {
+			(Some(b), Some(a)) => {
+				compare(&mut changes, format!("spot_markets[{}].spot_market", i), &b.spot_market, &a.spot_market, false);
+				compare(&mut changes, format!("spot_markets[{}].maint_asset_weight", i), b.maint_asset_weight, a.maint_asset_weight, true);
+				compare(&mut changes, format!("spot_markets[{}].init_asset_weight", i), b.init_asset_weight, a.init_asset_weight, true);
+				compare(&mut changes, format!("spot_markets[{}].maint_liab_weight", i), b.maint_liab_weight, a.maint_liab_weight, true);
+				compare(&mut changes, format!("spot_markets[{}].init_liab_weight", i), b.init_liab_weight, a.init_liab_weight, true);
+				compare(&mut changes, format!("spot_markets[{}].liquidation_fee", i), b.liquidation_fee, a.liquidation_fee, true);
+			}
+			(None, Some(a)) => compare(&mut changes, format!("spot_markets[{}]", i), "none", &a.spot_market, false),
+			(Some(b), None) => compare(&mut changes, format!("spot_markets[{}]", i), &b.spot_market, "none", true),
+			(None, None) => {}
+		}
+		match (find(&before.perp_markets, i, |m| m.market_index), find(&after.perp_markets, i, |m| m.market_index)) {
+			(Some(b), Some(a)) => {
+				compare(&mut changes, format!("perp_markets[{}].perp_market", i), &b.perp_market, &a.perp_market, false);
+				compare(&mut changes, format!("perp_markets[{}].maint_asset_weight", i), b.maint_asset_weight, a.maint_asset_weight, true);
+				compare(&mut changes, format!("perp_markets[{}].init_asset_weight", i), b.init_asset_weight, a.init_asset_weight, true);
+				compare(&mut changes, format!("perp_markets[{}].maint_liab_weight", i), b.maint_liab_weight, a.maint_liab_weight, true);
+				compare(&mut changes, format!("perp_markets[{}].init_liab_weight", i), b.init_liab_weight, a.init_liab_weight, true);
+				compare(&mut changes, format!("perp_markets[{}].liquidation_fee", i), b.liquidation_fee, a.liquidation_fee, true);
+				compare(&mut changes, format!("perp_markets[{}].maker_fee", i), b.maker_fee, a.maker_fee, true);
+				compare(&mut changes, format!("perp_markets[{}].taker_fee", i), b.taker_fee, a.taker_fee, true);
+				compare(&mut changes, format!("perp_markets[{}].base_lot_size", i), b.base_lot_size, a.base_lot_size, true);
+				compare(&mut changes, format!("perp_markets[{}].quote_lot_size", i), b.quote_lot_size, a.quote_lot_size, true);
+			}
+			(None, Some(a)) => compare(&mut changes, format!("perp_markets[{}]", i), "none", &a.perp_market, false),
+			(Some(b), None) => compare(&mut changes, format!("perp_markets[{}]", i), &b.perp_market, "none", true),
+			(None, None) => {}
+		}
+		compare(&mut changes, format!("oracles[{}]", i), before.oracles.get(i).map(|o| o.as_str()).unwrap_or("none"), after.oracles.get(i).map(|o| o.as_str()).unwrap_or("none"), false);
+	}
+	compare(&mut changes, "ref_surcharge_centibps".to_string(), before.ref_surcharge_centibps, after.ref_surcharge_centibps, true);
+	compare(&mut changes, "ref_share_centibps".to_string(), before.ref_share_centibps, after.ref_share_centibps, true);
+	compare(&mut changes, "ref_mngo_required".to_string(), before.ref_mngo_required, after.ref_mngo_required, true);
+	changes
+}
+
+#[cfg(test)]
+mod tests {
+	use bytemuck::Zeroable;
+	use fixed::types::I80F48;
+	use solana_program::pubkey::Pubkey;
+	use crate::snapshot::{diff_snapshots, GroupSnapshot};
+	use crate::types::MangoGroup;
+
+	#[test]
+	fn flags_risk_parameter_changes() {
+		let mut mango_group = MangoGroup::zeroed();
+		mango_group.perp_markets[3].perp_market = Pubkey::new_unique();
+		mango_group.perp_markets[3].taker_fee = I80F48::from_num(0.0005);
+		mango_group.perp_markets[3].base_lot_size = 10_000_000;
+		let before = GroupSnapshot::capture(&Pubkey::default(), &mango_group, 0);
+		assert!(diff_snapshots(&before, &before).is_empty());
+
+		mango_group.perp_markets[3].taker_fee = I80F48::from_num(0.001);
+		let after = GroupSnapshot::capture(&Pubkey::default(), &mango_group, 60);
+		let changes = diff_snapshots(&before, &after);
+		assert_eq!(changes.len(), 1);
+		assert_eq!(changes[0].path, "perp_markets[3].taker_fee");
+		assert!(changes[0].risk);
+	}
+}
@@ -0,0 +1,414 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use fixed::types::I80F48;
+use mangol_solana::cache::AccountCache;
+use mangol_solana::subscription::{AccountUpdate, ResilientSubscription};
+use serum_dex::state::OpenOrders;
+use solana_client::rpc_client::RpcClient;
+use solana_program::pubkey::Pubkey;
+
+use crate::book::{BookSideState, OrderBook};
+use crate::queue::{load_fills_since, FillEvent};
+use crate::oracle::{OracleConfidence, PythPrice};
+use crate::health::{account_health, decode_mango_cache};
+use crate::types::{HealthType, MangoAccount, MangoCache, MangoGroup, PerpMarketData, Side, MAX_PAIRS, MAX_PERP_OPEN_ORDERS};
+use crate::utils::invert_side;
+
+/// Changes to the bot's own MangoAccount, derived from account snapshots and event queue fills
+#[derive(Clone, Debug, PartialEq)]
+pub enum OwnAccountEvent {
+	OrderPlaced { market_index: usize, order_id: i128, client_order_id: u64, side: Side },
+	/// price in quote lots per base lot, size in base lots
+	OrderFilled { market_index: usize, order_id: i128, side: Side, price: i64, size: i64 },
+	/// Left the book without a position change, expired or cancelled
+	OrderExpired { market_index: usize, order_id: i128, client_order_id: u64 },
+	PositionChanged { market_index: usize, base_position_before: i64, base_position: i64 },
+	HealthChanged { before: I80F48, after: I80F48 },
+}
+
+fn open_orders(mango_account: &MangoAccount) -> HashMap<i128, usize> {
+	(0..MAX_PERP_OPEN_ORDERS).filter(|i| mango_account.orders[*i] != 0).map(|i| (mango_account.orders[i], i)).collect()
+}
+
+/// Order and position events between two snapshots of the same account.
+/// Orders that left the book while their market's position moved are reported by the fill stream instead
+pub fn diff_accounts(before: &MangoAccount, after: &MangoAccount) -> Vec<OwnAccountEvent> {
+	let mut events = vec![];
+	let orders_before = open_orders(before);
+	let orders_after = open_orders(after);
+	for (order_id, slot) in &orders_after {
+		if !orders_before.contains_key(order_id) {
+			events.push(OwnAccountEvent::OrderPlaced {
+				market_index: after.order_market[*slot] as usize,
+				order_id: *order_id,
+				client_order_id: after.client_order_ids[*slot],
+				side: after.order_side[*slot],
+			});
+		}
+	}
+	for (order_id, slot) in &orders_before {
+		let market_index = before.order_market[*slot] as usize;
+		let position_moved = before.perp_accounts[market_index].base_position != after.perp_accounts[market_index].base_position;
+		if !orders_after.contains_key(order_id) && !position_moved {
+			events.push(OwnAccountEvent::OrderExpired { market_index, order_id: *order_id, client_order_id: before.client_order_ids[*slot] });
+		}
+	}
+	for market_index in 0..MAX_PAIRS {
+		let base_position_before = before.perp_accounts[market_index].base_position;
+		let base_position = after.perp_accounts[market_index].base_position;
+		if base_position_before != base_position {
+			events.push(OwnAccountEvent::PositionChanged { market_index, base_position_before, base_position });
+		}
+	}
+	events
+}
+
+/// The side of `fill` the account took part in, None when it wasn't involved
+pub fn own_fill(fill: &FillEvent, mango_account_pk: &Pubkey, market_index: usize) -> Option<OwnAccountEvent> {
+	if fill.maker == *mango_account_pk {
+		Some(OwnAccountEvent::OrderFilled { market_index, order_id: fill.maker_order_id, side: invert_side(fill.taker_side), price: fill.price, size: fill.quantity })
+	} else if fill.taker == *mango_account_pk {
+		Some(OwnAccountEvent::OrderFilled { market_index, order_id: fill.taker_order_id, side: fill.taker_side, price: fill.price, size: fill.quantity })
+	} else {
+		None
+	}
+}
+
+/// Subscribes to the account and the event queues of `markets` and streams OwnAccountEvents,
+/// so strategies consume one channel instead of diffing snapshots themselves
+pub struct OwnAccountStream {
+	pub mango_account_pk: Pubkey,
+	pub mango_group_pk: Pubkey,
+	pub mango_program_id: Pubkey,
+	pub rpc_url: String,
+	pub ws_url: String,
+	pub markets: Vec<PerpMarketData>,
+	pub health_type: HealthType,
+}
+
+impl OwnAccountStream {
+	pub fn start(self) -> Receiver<OwnAccountEvent> {
+		let (sender, receiver) = channel();
+		let (updates_sender, updates) = channel();
+		let mut queues: HashMap<Pubkey, usize> = HashMap::new();
+		let mut subscribed = vec![self.mango_account_pk];
+		for market in &self.markets {
+			let events_pk = Pubkey::from_str(&market.events_key).unwrap();
+			queues.insert(events_pk, market.market_index);
+			subscribed.push(events_pk);
+		}
+		for pubkey in subscribed {
+			forward(ResilientSubscription::new(pubkey, &self.rpc_url, &self.ws_url), updates_sender.clone());
+		}
+		std::thread::spawn(move || {
+			let rpc_client = RpcClient::new(self.rpc_url.clone());
+			let account_cache = Arc::new(AccountCache::new(Duration::from_secs(5)));
+			let mut last_account: Option<MangoAccount> = None;
+			let mut last_health: Option<I80F48> = None;
+			let mut last_seq_nums: HashMap<Pubkey, usize> = HashMap::new();
+			for update in updates {
+				let events = if update.pubkey == self.mango_account_pk {
+					let mango_account = match MangoAccount::load_from_vec(update.account.data) {
+						Ok(mango_account) => mango_account,
+						Err(_) => continue
+					};
+					let mut events = last_account.map(|before| diff_accounts(&before, &mango_account)).unwrap_or_default();
+					if let Some(health) = self.health(&rpc_client, &account_cache, &mango_account) {
+						if let Some(before) = last_health.filter(|before| *before != health) {
+							events.push(OwnAccountEvent::HealthChanged { before, after: health });
+						}
+						last_health = Some(health);
+					}
+					last_account = Some(mango_account);
+					events
+				} else if let Some(market_index) = queues.get(&update.pubkey) {
+					let (seq_num, fills) = match load_fills_since(&update.account.data, *last_seq_nums.get(&update.pubkey).unwrap_or(&usize::MAX)) {
+						Ok(loaded) => loaded,
+						Err(_) => continue
+					};
+					// the first snapshot only sets the cursor
+					let fills = if last_seq_nums.contains_key(&update.pubkey) { fills } else { vec![] };
+					last_seq_nums.insert(update.pubkey, seq_num);
+					fills.iter().filter_map(|fill| own_fill(fill, &self.mango_account_pk, *market_index)).collect()
+				} else {
+					vec![]
+				};
+				for event in events {
+					if sender.send(event).is_err() {
+						return;
+					}
+				}
+			}
+		});
+		receiver
+	}
+
+	/// Health without spot open orders, the strategies only trade perps
+	fn health(&self, rpc_client: &RpcClient, account_cache: &AccountCache, mango_account: &MangoAccount) -> Option<I80F48> {
+		let mango_group = MangoGroup::load_checked(account_cache.get_or_fetch(rpc_client, &self.mango_group_pk).ok()?, &self.mango_program_id).ok()?;
+		let mango_cache = MangoCache::load_checked(account_cache.get_or_fetch(rpc_client, &mango_group.mango_cache).ok()?, &self.mango_program_id, &mango_group).ok()?;
+		let open_orders: Vec<Option<OpenOrders>> = vec![None; MAX_PAIRS];
+		account_health(&mango_group, &mango_cache, mango_account, &open_orders, self.health_type).ok()
+	}
+}
+
+/// Subscribes to the event queues of `markets` and streams every fill pushed after it started,
+/// (market index, fill). Keepers consume fills within seconds, only a subscription sees them all;
+/// a fill pushed and consumed between two notifications is still missed
+pub struct FillStream {
+	pub markets: Vec<PerpMarketData>,
+	pub rpc_url: String,
+	pub ws_url: String,
+}
+
+impl FillStream {
+	pub fn start(self) -> Receiver<(usize, FillEvent)> {
+		let (sender, receiver) = channel();
+		let (updates_sender, updates) = channel();
+		let mut queues: HashMap<Pubkey, usize> = HashMap::new();
+		for market in &self.markets {
+			let events_pk = Pubkey::from_str(&market.events_key).unwrap();
+			queues.insert(events_pk, market.market_index);
+			forward(ResilientSubscription::new(events_pk, &self.rpc_url, &self.ws_url), updates_sender.clone());
+		}
+		std::thread::spawn(move || {
+			let mut last_seq_nums: HashMap<Pubkey, usize> = HashMap::new();
+			for update in updates {
+				let market_index = match queues.get(&update.pubkey) {
+					Some(market_index) => *market_index,
+					None => continue
+				};
+				let (seq_num, fills) = match load_fills_since(&update.account.data, *last_seq_nums.get(&update.pubkey).unwrap_or(&usize::MAX)) {
+					Ok(loaded) => loaded,
+					Err(_) => continue
+				};
+				// the first snapshot only sets the cursor
+				let fills = if last_seq_nums.contains_key(&update.pubkey) { fills } else { vec![] };
+				last_seq_nums.insert(update.pubkey, seq_num);
+				for fill in fills {
+					if sender.send((market_index, fill)).is_err() {
+						return;
+					}
+				}
+			}
+		});
+		receiver
+	}
+}
+
+/// A market's oracle price changed in the MangoCache
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PriceUpdated {
+	pub market_index: usize,
+	/// Native quote per native base
+	pub price: I80F48,
+	pub slot: u64,
+}
+
+/// Prices of the first `num_oracles` markets that differ between two cache snapshots,
+/// every one of them without a previous snapshot. Cache writes that only refresh
+/// `last_update` are not price changes
+pub fn diff_prices(before: Option<&MangoCache>, after: &MangoCache, num_oracles: usize, slot: u64) -> Vec<PriceUpdated> {
+	(0..num_oracles.min(MAX_PAIRS))
+		  .filter(|i| before.map(|before| before.price_cache[*i].price != after.price_cache[*i].price).unwrap_or(true))
+		  .map(|market_index| PriceUpdated { market_index, price: after.price_cache[market_index].price, slot })
+		  .collect()
+}
+
+/// Subscribes to the MangoCache and streams the PriceUpdated of every market whose price moved,
+/// one batch per cache update, so consumers re-evaluate only the affected markets
+pub struct PriceStream {
+	pub mango_cache_pk: Pubkey,
+	pub num_oracles: usize,
+	pub rpc_url: String,
+	pub ws_url: String,
+	/// Keeps the cache it streams prices from, so health read through it sees the same prices
+	pub account_cache: Option<Arc<AccountCache>>,
+}
+
+impl PriceStream {
+	pub fn with_account_cache(mut self, account_cache: Arc<AccountCache>) -> Self {
+		self.account_cache = Some(account_cache);
+		self
+	}
+
+	pub fn start(self) -> Receiver<Vec<PriceUpdated>> {
+		let (sender, receiver) = channel();
+		let (updates_sender, updates) = channel();
+		// an unchanged cache moves no price
+		forward(ResilientSubscription::new(self.mango_cache_pk, &self.rpc_url, &self.ws_url).with_skip_unchanged(true), updates_sender);
+		std::thread::spawn(move || {
+			let mut last_cache: Option<MangoCache> = None;
+			for update in updates {
+				let mango_cache = match decode_mango_cache(&update.account.data) {
+					Ok(mango_cache) => mango_cache,
+					Err(_) => continue
+				};
+				if let Some(account_cache) = &self.account_cache {
+					account_cache.apply_update(&update);
+				}
+				let prices = diff_prices(last_cache.as_ref(), &mango_cache, self.num_oracles, update.slot);
+				last_cache = Some(mango_cache);
+				if !prices.is_empty() && sender.send(prices).is_err() {
+					return;
+				}
+			}
+		});
+		receiver
+	}
+}
+
+/// Subscribes to the Pyth price accounts of `oracles`, (market index, oracle), and keeps their
+/// latest confidence in an OracleConfidence
+pub struct OracleConfidenceStream {
+	pub oracles: Vec<(usize, Pubkey)>,
+	pub rpc_url: String,
+	pub ws_url: String,
+}
+
+impl OracleConfidenceStream {
+	pub fn start(self) -> OracleConfidence {
+		let confidence = OracleConfidence::default();
+		let (updates_sender, updates) = channel();
+		let markets: HashMap<Pubkey, usize> = self.oracles.iter().map(|(market_index, oracle)| (*oracle, *market_index)).collect();
+		for (_, oracle) in &self.oracles {
+			forward(ResilientSubscription::new(*oracle, &self.rpc_url, &self.ws_url), updates_sender.clone());
+		}
+		let shared = confidence.clone();
+		std::thread::spawn(move || {
+			for update in updates {
+				let market_index = match markets.get(&update.pubkey) {
+					Some(market_index) => *market_index,
+					None => continue
+				};
+				if let Some(price) = PythPrice::load(&update.account.data) {
+					let was_breached = shared.breach(market_index).is_some();
+					if price.exceeds_conf_filter() != was_breached {
+						println!("[?] Market {} oracle confidence {:.2}% of price, {}", market_index, price.conf_ratio() * 100.0, if was_breached { "back within the filter" } else { "over the filter" });
+					}
+					shared.update(market_index, price);
+				}
+			}
+		});
+		confidence
+	}
+}
+
+#[derive(Debug)]
+struct LiveBookSides {
+	bids: BookSideState,
+	asks: BookSideState,
+	bids_slot: Option<u64>,
+	asks_slot: Option<u64>,
+}
+
+/// Always warm OrderBook of one perp market, kept in memory from subscriptions to its bids and
+/// asks instead of fetching both accounts on every read
+#[derive(Clone, Debug)]
+pub struct LiveOrderBook {
+	pub market_index: usize,
+	sides: Arc<RwLock<LiveBookSides>>,
+}
+
+impl LiveOrderBook {
+	pub fn start(market: &PerpMarketData, rpc_url: &str, ws_url: &str) -> Self {
+		let book = Self {
+			market_index: market.market_index,
+			sides: Arc::new(RwLock::new(LiveBookSides {
+				bids: BookSideState::new(Side::Bid),
+				asks: BookSideState::new(Side::Ask),
+				bids_slot: None,
+				asks_slot: None,
+			})),
+		};
+		let bids_pk = Pubkey::from_str(&market.bids_key).unwrap();
+		let asks_pk = Pubkey::from_str(&market.asks_key).unwrap();
+		let (updates_sender, updates) = channel();
+		forward(ResilientSubscription::new(bids_pk, rpc_url, ws_url), updates_sender.clone());
+		forward(ResilientSubscription::new(asks_pk, rpc_url, ws_url), updates_sender);
+		let sides = book.sides.clone();
+		std::thread::spawn(move || {
+			for update in updates {
+				let mut sides = sides.write().unwrap();
+				let (side, slot) = if update.pubkey == bids_pk {
+					let LiveBookSides { bids, bids_slot, .. } = &mut *sides;
+					(bids, bids_slot)
+				} else {
+					let LiveBookSides { asks, asks_slot, .. } = &mut *sides;
+					(asks, asks_slot)
+				};
+				// a reconnect can replay an update older than the one already applied
+				if slot.map(|slot| update.slot < slot).unwrap_or(false) {
+					continue;
+				}
+				side.apply(&update.account.data);
+				*slot = Some(update.slot);
+			}
+		});
+		book
+	}
+
+	/// The book at `now_ts` and the older slot of its two sides, None until both sides arrived
+	pub fn book(&self, now_ts: u64) -> Option<(u64, OrderBook)> {
+		let sides = self.sides.read().unwrap();
+		let slot = sides.bids_slot?.min(sides.asks_slot?);
+		Some((slot, OrderBook::from_sides(&sides.bids, &sides.asks, now_ts)))
+	}
+}
+
+pub(crate) fn forward(subscription: ResilientSubscription, sender: Sender<AccountUpdate>) {
+	std::thread::spawn(move || {
+		let (_subscription_handle, updates) = subscription.start();
+		for update in updates {
+			if sender.send(update).is_err() {
+				return;
+			}
+		}
+	});
+}
+
+#[cfg(test)]
+mod tests {
+	use bytemuck::Zeroable;
+	use fixed::types::I80F48;
+	use crate::stream::{diff_accounts, diff_prices, OwnAccountEvent, PriceUpdated};
+	use crate::types::{MangoAccount, MangoCache, Side};
+
+	#[test]
+	fn diffs_orders_and_positions() {
+		let mut before = MangoAccount::zeroed();
+		before.orders[0] = 11;
+		before.order_market[0] = 3;
+		before.orders[1] = 12;
+		before.order_market[1] = 3;
+		let mut after = before;
+		after.orders[0] = 0;
+		after.orders[2] = 13;
+		after.order_market[2] = 3;
+		after.order_side[2] = Side::Ask;
+		after.client_order_ids[2] = 7;
+		let events = diff_accounts(&before, &after);
+		assert!(events.contains(&OwnAccountEvent::OrderPlaced { market_index: 3, order_id: 13, client_order_id: 7, side: Side::Ask }));
+		assert!(events.contains(&OwnAccountEvent::OrderExpired { market_index: 3, order_id: 11, client_order_id: 0 }));
+
+		// a removed order with a position change is a fill, reported from the event queue
+		after.perp_accounts[3].base_position = -5;
+		let events = diff_accounts(&before, &after);
+		assert!(!events.iter().any(|event| matches!(event, OwnAccountEvent::OrderExpired { .. })));
+		assert!(events.contains(&OwnAccountEvent::PositionChanged { market_index: 3, base_position_before: 0, base_position: -5 }));
+	}
+
+	#[test]
+	fn emits_only_changed_prices() {
+		let before = MangoCache::zeroed();
+		let mut after = before;
+		after.price_cache[1].price = I80F48::from_num(25);
+		after.price_cache[2].last_update = 100;
+		assert_eq!(diff_prices(Some(&before), &after, 3, 7), vec![PriceUpdated { market_index: 1, price: I80F48::from_num(25), slot: 7 }]);
+		assert_eq!(diff_prices(None, &after, 3, 7).len(), 3);
+	}
+}
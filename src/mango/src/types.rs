@@ -4,7 +4,7 @@ use std::convert::{identity, TryFrom};
 use std::mem::size_of;
 use std::ops::Deref;
 
-use bytemuck::{cast_ref, from_bytes, from_bytes_mut, try_from_bytes_mut};
+use bytemuck::{cast_ref, from_bytes, from_bytes_mut, pod_read_unaligned, try_from_bytes_mut};
 use enumflags2::BitFlags;
 use fixed::types::I80F48;
 use fixed_macro::types::I80F48;
@@ -247,16 +247,27 @@ impl PerpMarketInfo {
 	// 		  .unwrap()
 	// }
 	
+	pub fn lot_to_native_price_quantity(&self, price: f64, quantity: u64) -> (i64, i64) {
+		let native_price = (price * self.base_lot_size as f64) / self.quote_lot_size as f64;
+		let native_quantity = quantity as f64 / self.base_lot_size as f64;
+		(native_price.round() as i64, native_quantity.round() as i64)
+	}
+	
+	#[deprecated(note = "use lot_to_native_price_quantity")]
+	#[allow(non_snake_case)]
 	pub fn lotToNativePriceQuantity(&self, price: f64, quantity: u64) -> (i64, i64) {
-		let nativePrice = (price * self.base_lot_size as f64) / self.quote_lot_size as f64;
-		let nativeQuantity = quantity as f64 / self.base_lot_size as f64;
-		return (nativePrice.round().to_string().parse::<i64>().unwrap(), nativeQuantity.round().to_string().parse::<i64>().unwrap());
+		self.lot_to_native_price_quantity(price, quantity)
 	}
 	
 	pub fn lot_to_native_price(&self, price: f64) -> i64 {
 		((price * self.base_lot_size as f64) / self.quote_lot_size as f64).round().to_string().parse::<i64>().unwrap()
 	}
 	
+	/// Inverse of lot_to_native_price
+	pub fn lots_to_price(&self, lot_price: i64) -> f64 {
+		(lot_price as f64 * self.quote_lot_size as f64) / self.base_lot_size as f64
+	}
+	
 	
 }
 
@@ -614,9 +625,6 @@ impl PerpMarketData {
 	pub fn ui_to_base_units(&self, quantity: f64) -> f64{
 		return 10_f64.powf(self.base_decimals as f64) * quantity
 	}
-	pub fn lotToNativePriceQuantity(&self, price: u64, quantity: u64) -> (i64, i64) {
-		return (0 as i64, 0 as i64);
-	}
 }
 #[derive(Copy, Clone, Pod)]
 #[repr(C)]
@@ -758,19 +766,33 @@ pub struct HealthCache {
 	health: [Option<I80F48>; NUM_HEALTHS],
 }
 
-fn strip_dex_padding(acc: AccountInfo) -> MangoResult<Vec<u8>> {
-	let data = acc.data;
-	let data_len = data.len() - 12;
-	let (_, rest) = data.split_at(5);
-	let (mid, _) = rest.split_at(data_len);
-	Ok(Vec::from(mid))
+/// The account data between serum's 5 byte head and 7 byte tail padding, borrowed
+fn strip_dex_padding(data: &[u8]) -> MangoResult<&[u8]> {
+	let expected = size_of::<OpenOrders>() + 12;
+	if data.len() != expected {
+		return Err(MangoError::UnexpectedSize { account: "OpenOrders", expected, actual: data.len() });
+	}
+	Ok(&data[5..data.len() - 7])
 }
 
 pub fn load_open_orders(
 	acc: AccountInfo,
 ) -> Result<serum_dex::state::OpenOrders, ProgramError> {
-	Ok(from_bytes::<OpenOrders>(&strip_dex_padding(acc)?).clone())
+	load_open_orders_from_bytes(&acc.data)
 }
+
+/// `load_open_orders` on raw account data. The orders start 5 bytes in, unaligned, so they are
+/// read straight onto the stack instead of through an aligned heap copy
+pub fn load_open_orders_from_bytes(data: &[u8]) -> Result<serum_dex::state::OpenOrders, ProgramError> {
+	Ok(pod_read_unaligned::<OpenOrders>(strip_dex_padding(data)?))
+}
+
+impl Default for HealthCache {
+	fn default() -> Self {
+		Self::new(UserActiveAssets { spot: [false; MAX_PAIRS], perps: [false; MAX_PAIRS] })
+	}
+}
+
 impl HealthCache {
 	pub fn new(active_assets: UserActiveAssets) -> Self {
 		Self {
@@ -782,6 +804,15 @@ impl HealthCache {
 		}
 	}
 	
+	/// Clears the cache for another account or another state of the same one, keeping its buffers
+	pub fn reset(&mut self, active_assets: UserActiveAssets) {
+		self.active_assets = active_assets;
+		self.spot.iter_mut().for_each(|val| *val = (ZERO_I80F48, ZERO_I80F48));
+		self.perp.iter_mut().for_each(|val| *val = (ZERO_I80F48, ZERO_I80F48));
+		self.quote = ZERO_I80F48;
+		self.health = [None; NUM_HEALTHS];
+	}
+	
 	// Accept T = &OpenOrders as well as Ref<OpenOrders>
 	pub fn init_vals_with_orders_vec(
 		&mut self,
@@ -1110,9 +1141,32 @@ pub struct MangoAccount {
 	pub padding: [u8; 5],
 }
 
+/// Size of a MangoAccount on chain, the layout `MangoAccount` mirrors
+pub const MANGO_ACCOUNT_SIZE: usize = 4296;
+/// MetaData versions of MangoAccount with that layout, 1 being accounts upgraded to be closable
+pub const SUPPORTED_MANGO_ACCOUNT_VERSIONS: [u8; 2] = [0, 1];
+// a changed field or alignment shifts every offset after it, so refuse to build instead
+const_assert_eq!(size_of::<MangoAccount>(), MANGO_ACCOUNT_SIZE);
+
 impl MangoAccount {
+	/// Decodes account data after checking its size, data type and version, so an upgraded
+	/// program's accounts fail with an error instead of being read as garbage
+	pub fn load_versioned(data: &[u8]) -> MangoResult<Self> {
+		if data.len() != MANGO_ACCOUNT_SIZE {
+			return Err(MangoError::UnexpectedSize { account: "MangoAccount", expected: MANGO_ACCOUNT_SIZE, actual: data.len() });
+		}
+		let meta_data: &MetaData = from_bytes(&data[..size_of::<MetaData>()]);
+		if meta_data.data_type != DataType::MangoAccount as u8 {
+			return Err(MangoError::UnexpectedDataType { account: "MangoAccount", data_type: meta_data.data_type });
+		}
+		if !SUPPORTED_MANGO_ACCOUNT_VERSIONS.contains(&meta_data.version) {
+			return Err(MangoError::UnsupportedVersion { account: "MangoAccount", version: meta_data.version });
+		}
+		Ok(*Self::load_from_bytes(data)?)
+	}
+	
 	pub fn load_from_vec(data: Vec<u8>) -> MangoResult<Self> {
-		Ok(Self::load_from_bytes(&data).unwrap().clone())
+		Self::load_versioned(&data)
 	}
 	pub fn load_mut_checked(
 		account: AccountInfo,
@@ -1127,9 +1181,7 @@ impl MangoAccount {
 		account: AccountInfo,
 		program_id: &Pubkey,
 	) -> MangoResult<Self> {
-		let mango_account = Self::load_from_bytes(&account.data)?;
-		
-		Ok(mango_account.clone())
+		Self::load_versioned(&account.data)
 	}
 	pub fn get_native_deposit(
 		&self,
@@ -1724,10 +1776,16 @@ impl PerpMarket {
 			  .checked_div(I80F48::from_num(self.base_lot_size))
 			  .unwrap()
 	}
+	pub fn lot_to_native_price_quantity(&self, price: u64, quantity: u64) -> (i64, i64) {
+		let native_price = (price * self.base_lot_size as u64) / self.quote_lot_size as u64;
+		let native_quantity = quantity / self.base_lot_size as u64;
+		(native_price as i64, native_quantity as i64)
+	}
+	
+	#[deprecated(note = "use lot_to_native_price_quantity")]
+	#[allow(non_snake_case)]
 	pub fn lotToNativePriceQuantity(&self, price: u64, quantity: u64) -> (i64, i64) {
-	let nativePrice = (price * self.base_lot_size as u64) / self.quote_lot_size as u64;
-	let nativeQuantity = quantity / self.base_lot_size as u64;
-	return (nativePrice as i64, nativeQuantity as i64);
+		self.lot_to_native_price_quantity(price, quantity)
 	}
 	
 	/// Socialize the loss in this account across all longs and shorts
@@ -1827,4 +1885,22 @@ pub struct ReferrerIdRecord {
 
 impl ReferrerIdRecord {
 
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+	use crate::error::MangoError;
+	use crate::types::{DataType, MangoAccount, MANGO_ACCOUNT_SIZE};
+
+	#[test]
+	fn refuses_unknown_mango_account_layouts() {
+		assert_eq!(
+			MangoAccount::load_versioned(&[0; 4000]).unwrap_err(),
+			MangoError::UnexpectedSize { account: "MangoAccount", expected: MANGO_ACCOUNT_SIZE, actual: 4000 }
+		);
+		let mut data = vec![0; MANGO_ACCOUNT_SIZE];
+		assert!(matches!(MangoAccount::load_versioned(&data), Err(MangoError::UnexpectedDataType { data_type: 0, .. })));
+		data[0] = DataType::MangoAccount as u8;
+		data[1] = 2;
+		assert!(matches!(MangoAccount::load_versioned(&data), Err(MangoError::UnsupportedVersion { version: 2, .. })));
+	}
+}
@@ -127,4 +127,11 @@ pub fn interest_rate_curve_calculator(
 		let slope = optimal_rate / optimal_util;
 		slope * utilization
 	}
-}
\ No newline at end of file
+}
+
+pub fn get_associated_token_address(wallet_pk: &Pubkey, mint_pk: &Pubkey) -> Pubkey {
+	Pubkey::find_program_address(
+		&[wallet_pk.as_ref(), spl_token::ID.as_ref(), mint_pk.as_ref()],
+		&crate::ids::associated_token_program::id(),
+	).0
+}
@@ -0,0 +1,119 @@
+use mangol_common::errors::{MangolError, MangolResult};
+use mangol_common::venue::{ExecutionVenue, OrderKind, OrderSide, VenueBalance, VenueOrder, VenuePosition};
+
+use crate::client::{MangoClient, MangoClientApi};
+use crate::sizing::{OrderSizer, Rounding};
+use crate::types::{OrderType, PerpMarketData, Side};
+
+/// Mango v3 perps as an ExecutionVenue, markets addressed by their registry name (e.g. "SOL-PERP")
+pub struct MangoPerpVenue<C: MangoClientApi = MangoClient> {
+	pub mango_client: C,
+	pub markets: Vec<PerpMarketData>,
+}
+
+impl<C: MangoClientApi> MangoPerpVenue<C> {
+	pub fn new(mango_client: C, markets: Vec<PerpMarketData>) -> Self {
+		Self { mango_client, markets }
+	}
+
+	pub fn market(&self, name: &str) -> MangolResult<&PerpMarketData> {
+		self.markets.iter().find(|market| market.name == name)
+			  .ok_or_else(|| MangolError::MangoError(format!("Unknown perp market {}", name)))
+	}
+
+	/// Ui price to native quote per native base, what the cache and place_perp_order use
+	fn native_price(market: &PerpMarketData, price: f64) -> f64 {
+		price * 10_f64.powi(market.quote_decimals as i32) / 10_f64.powi(market.base_decimals as i32)
+	}
+}
+
+impl<C: MangoClientApi> ExecutionVenue for MangoPerpVenue<C> {
+	fn name(&self) -> &str {
+		"mango"
+	}
+
+	fn refresh(&mut self) -> MangolResult<()> {
+		self.mango_client.update()
+	}
+
+	fn place_order(&self, order: &VenueOrder) -> MangolResult<String> {
+		let market = self.market(&order.market)?;
+		let perp_market = &self.mango_client.mango_group().perp_markets[market.market_index];
+		let base_lots = OrderSizer::new(perp_market).base_lots_from_ui(order.size, market.base_decimals, Rounding::Down)?;
+		let price = match order.kind {
+			OrderKind::Market => self.mango_client.mango_cache().get_price(market.market_index),
+			_ => Self::native_price(market, order.price),
+		};
+		let side = match order.side {
+			OrderSide::Buy => Side::Bid,
+			OrderSide::Sell => Side::Ask,
+		};
+		let order_type = match order.kind {
+			OrderKind::Limit => OrderType::Limit,
+			OrderKind::PostOnly => OrderType::PostOnly,
+			OrderKind::ImmediateOrCancel => OrderType::ImmediateOrCancel,
+			OrderKind::Market => OrderType::Market,
+		};
+		self.mango_client.place_perp_order_with_base(perp_market, market, side, price, base_lots, order_type, order.reduce_only, order.expiry_secs)
+	}
+
+	fn cancel_all(&self, market: &str) -> MangolResult<String> {
+		self.mango_client.cancel_all_perp_orders(self.market(market)?)
+	}
+
+	fn positions(&self) -> MangolResult<Vec<VenuePosition>> {
+		let mango_account = self.mango_client.mango_account();
+		let perp_markets = &self.mango_client.mango_group().perp_markets;
+		Ok(self.markets.iter().filter_map(|market| {
+			let perp_account = &mango_account.perp_accounts[market.market_index];
+			if perp_account.base_position == 0 && perp_account.quote_position.is_zero() {
+				return None;
+			}
+			let base_lot_size = perp_markets[market.market_index].base_lot_size;
+			Some(VenuePosition {
+				market: market.name.clone(),
+				base_size: (perp_account.base_position * base_lot_size) as f64 / 10_f64.powi(market.base_decimals as i32),
+				quote_position: perp_account.quote_position.to_num::<f64>() / 10_f64.powi(market.quote_decimals as i32),
+			})
+		}).collect())
+	}
+
+	fn balances(&self) -> MangolResult<Vec<VenueBalance>> {
+		let mango_account = self.mango_client.mango_account();
+		let mango_cache = self.mango_client.mango_cache();
+		Ok(self.mango_client.mango_group().tokens.iter().enumerate()
+			  .filter(|(_, token)| !token.is_empty())
+			  .map(|(token_index, token)| VenueBalance {
+				  asset: token.mint.to_string(),
+				  amount: mango_account.get_net(&mango_cache.root_bank_cache[token_index], token_index).to_num::<f64>() / 10_f64.powi(token.decimals as i32),
+			  })
+			  .filter(|balance| balance.amount != 0.0)
+			  .collect())
+	}
+
+	fn equity(&self) -> MangolResult<f64> {
+		let quote_decimals = self.markets.first().map(|market| market.quote_decimals).unwrap_or(6);
+		Ok(self.mango_client.get_equity()?.to_num::<f64>() / 10_f64.powi(quote_decimals as i32))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use mangol_common::venue::{ExecutionVenue, OrderKind, OrderSide, VenueOrder};
+	use crate::mock::{sol_perp, MockMangoClient};
+	use crate::types::{OrderType, Side};
+	use crate::venue::MangoPerpVenue;
+
+	#[test]
+	fn places_ui_orders_in_lots() {
+		let mut mango_client = MockMangoClient::new(3, 10_000_000, 100);
+		mango_client.set_base_position(-50);
+		let venue = MangoPerpVenue::new(mango_client, vec![sol_perp()]);
+		let order = VenueOrder { market: "SOL-PERP".to_string(), side: OrderSide::Sell, price: 40.0, size: 1.5, kind: OrderKind::PostOnly, reduce_only: false, expiry_secs: Some(30) };
+		assert_eq!(venue.place_order(&order).unwrap(), "mock-1");
+		let placed = venue.mango_client.last_order().unwrap();
+		assert_eq!((placed.side, placed.price, placed.quantity, placed.order_type, placed.with_base), (Side::Ask, 0.04, 150, OrderType::PostOnly, true));
+		assert_eq!(venue.positions().unwrap()[0].base_size, -0.5);
+		assert!(venue.place_order(&VenueOrder { market: "BTC-PERP".to_string(), ..order }).is_err());
+	}
+}
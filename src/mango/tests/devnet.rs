@@ -0,0 +1,137 @@
+//! End to end checks of the instruction builders against the devnet.2 group, with real transactions.
+//!
+//! `MANGOL_DEVNET_KEYSTORE=./devnet-key.txt cargo test -p mangol-mango --features devnet-tests --test devnet -- --test-threads 1`
+//!
+//! The key pays for everything and needs devnet SOL and devnet USDC in its associated token
+//! account. Its mango account number `MANGOL_DEVNET_ACCOUNT_NUM` (default 0) is created on the
+//! first run and reused after, `MANGOL_DEVNET_MARKET_INDEX` (default 3) is the perp market orders go to.
+//! Without the keystore every test returns early.
+#![cfg(feature = "devnet-tests")]
+
+use mangol_mango::client::MangoClient;
+use mangol_mango::profiles::GroupProfiles;
+use mangol_mango::types::{MangoAccount, MangoCache, MangoGroup, OrderType, PerpMarket, PerpMarketData, Side, MAX_PERP_OPEN_ORDERS, QUOTE_INDEX};
+use mangol_solana::connection::SolanaConnection;
+use mangol_solana::keystore::KeyStore;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::system_program;
+use solana_sdk::transaction::Transaction;
+
+struct Devnet {
+	connection: SolanaConnection,
+	mango_program: Pubkey,
+	mango_group_pk: Pubkey,
+	mango_group: MangoGroup,
+	signer: Keypair,
+}
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+	std::env::var(name).ok().and_then(|value| value.parse::<T>().ok()).unwrap_or(default)
+}
+
+/// None when no funded key is configured
+fn devnet() -> Option<Devnet> {
+	let keystore = match std::env::var("MANGOL_DEVNET_KEYSTORE") {
+		Ok(keystore) => keystore,
+		Err(_) => {
+			println!("MANGOL_DEVNET_KEYSTORE not set, skipping");
+			return None;
+		}
+	};
+	let profile = GroupProfiles::default().get("devnet.2").unwrap();
+	let connection = SolanaConnection::new(&profile.rpc_url).unwrap();
+	let mango_program = profile.mango_program_id().unwrap();
+	let mango_group_pk = profile.mango_group_pk().unwrap();
+	let mango_group = MangoGroup::load_checked(connection.rpc_client.get_account(&mango_group_pk).unwrap(), &mango_program).unwrap();
+	Some(Devnet { connection, mango_program, mango_group_pk, mango_group, signer: KeyStore::load(keystore).unwrap() })
+}
+
+impl Devnet {
+	fn mango_account_pk(&self) -> (Pubkey, u64) {
+		let account_num = env_or("MANGOL_DEVNET_ACCOUNT_NUM", 0_u64);
+		let seeds = [self.mango_group_pk.as_ref(), self.signer.pubkey().as_ref(), &account_num.to_le_bytes()];
+		(Pubkey::find_program_address(&seeds, &self.mango_program).0, account_num)
+	}
+
+	/// Creates the test account on the first run
+	fn ensure_mango_account(&self) -> Pubkey {
+		let (mango_account_pk, account_num) = self.mango_account_pk();
+		if self.connection.rpc_client.get_account(&mango_account_pk).is_err() {
+			let instruction = mangol_mango::instructions::create_mango_account(
+				&self.mango_program,
+				&self.mango_group_pk,
+				&mango_account_pk,
+				&self.signer.pubkey(),
+				&system_program::id(),
+				&self.signer.pubkey(),
+				account_num).unwrap();
+			let transaction = Transaction::new_with_payer(&[instruction], Some(&self.signer.pubkey()));
+			self.connection.try_tx_once(transaction, &self.signer).unwrap();
+		}
+		mango_account_pk
+	}
+
+	fn client(&self) -> MangoClient {
+		let mango_account_pk = self.ensure_mango_account();
+		let mango_account = MangoAccount::load_checked(self.connection.rpc_client.get_account(&mango_account_pk).unwrap(), &self.mango_program).unwrap();
+		let mango_cache = MangoCache::load_checked(self.connection.rpc_client.get_account(&self.mango_group.mango_cache).unwrap(), &self.mango_program, &self.mango_group).unwrap();
+		let signer = Keypair::from_bytes(&self.signer.to_bytes()).unwrap();
+		MangoClient::new(&self.connection, self.mango_group, self.mango_group_pk, mango_account_pk, self.mango_group.mango_cache, mango_account, mango_cache, self.mango_program, signer).unwrap()
+	}
+
+	/// Market data read from the group and the perp market account, as the devnet.2 profile's registry is built
+	fn perp_market_data(&self, market_index: usize) -> PerpMarketData {
+		let perp_market_pk = self.mango_group.perp_markets[market_index].perp_market;
+		let perp_market = PerpMarket::load_checked(self.connection.rpc_client.get_account(&perp_market_pk).unwrap(), &self.mango_program, &self.mango_group_pk).unwrap();
+		PerpMarketData::from_group(&self.mango_group, market_index, &perp_market_pk, &perp_market)
+	}
+}
+
+fn resting_orders(mango_account: &MangoAccount, market_index: usize) -> usize {
+	(0..MAX_PERP_OPEN_ORDERS).filter(|i| mango_account.orders[*i] != 0 && mango_account.order_market[*i] as usize == market_index).count()
+}
+
+#[test]
+fn creates_account_and_deposits() {
+	let devnet = match devnet() {
+		Some(devnet) => devnet,
+		None => return
+	};
+	let mut mango_client = devnet.client();
+	assert_eq!(mango_client.mango_account.owner, devnet.signer.pubkey());
+	assert_eq!(mango_client.mango_account.mango_group, devnet.mango_group_pk);
+	let before = mango_client.mango_account.deposits[QUOTE_INDEX];
+	// one ui usdc
+	let quantity = 10_u64.pow(devnet.mango_group.tokens[QUOTE_INDEX].decimals as u32);
+	mango_client.deposit(QUOTE_INDEX, quantity).unwrap();
+	mango_client.update().unwrap();
+	assert!(mango_client.mango_account.deposits[QUOTE_INDEX] > before);
+}
+
+#[test]
+fn places_and_cancels_a_resting_order() {
+	let devnet = match devnet() {
+		Some(devnet) => devnet,
+		None => return
+	};
+	let market_index = env_or("MANGOL_DEVNET_MARKET_INDEX", 3_usize);
+	let perp_market_data = devnet.perp_market_data(market_index);
+	let mut mango_client = devnet.client();
+	mango_client.cancel_all_perp_orders(&perp_market_data).unwrap();
+	mango_client.update().unwrap();
+	assert_eq!(resting_orders(&mango_client.mango_account, market_index), 0);
+
+	// post only and far enough below the oracle to rest instead of fill
+	let price = mango_client.mango_cache.get_price(market_index) * 0.8;
+	let perp_market_info = devnet.mango_group.perp_markets[market_index];
+	mango_client.place_perp_order_with_base(&perp_market_info, &perp_market_data, Side::Bid, price, 1, OrderType::PostOnly, false, Some(300)).unwrap();
+	mango_client.update().unwrap();
+	assert_eq!(resting_orders(&mango_client.mango_account, market_index), 1);
+	assert_eq!(mango_client.mango_account.perp_accounts[market_index].bids_quantity, 1);
+
+	mango_client.cancel_all_perp_orders(&perp_market_data).unwrap();
+	mango_client.update().unwrap();
+	assert_eq!(resting_orders(&mango_client.mango_account, market_index), 0);
+	assert_eq!(mango_client.mango_account.perp_accounts[market_index].bids_quantity, 0);
+}
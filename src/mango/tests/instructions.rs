@@ -0,0 +1,103 @@
+//! Golden vectors for the instruction builders, instruction data and account metas as the reference
+//! TypeScript client (@blockworks-foundation/mango-client) encodes them for the same arguments.
+//! A failure here means the program would read a different instruction than the one we meant to send,
+//! regenerate a vector only after checking the new bytes against the ts client.
+//!
+//! `cargo test -p mangol-mango --no-default-features --test instructions`
+
+use mangol_mango::instructions::{cancel_all_perp_orders, cancel_perp_order, consume_events, liquidate_perp_market, place_perp_order2, MangoInstruction};
+use mangol_mango::types::{ExpiryType, OrderType, Side};
+use solana_program::instruction::Instruction;
+use solana_program::pubkey::Pubkey;
+
+fn key(n: u8) -> Pubkey {
+	Pubkey::new_from_array([n; 32])
+}
+
+fn hex(bytes: &str) -> Vec<u8> {
+	let digits: Vec<char> = bytes.chars().filter(|c| !c.is_whitespace()).collect();
+	digits.chunks(2).map(|pair| u8::from_str_radix(&pair.iter().collect::<String>(), 16).unwrap()).collect()
+}
+
+/// (key byte, is_signer, is_writable) of every account meta, in order
+fn metas(instruction: &Instruction) -> Vec<(u8, bool, bool)> {
+	instruction.accounts.iter().map(|meta| (meta.pubkey.to_bytes()[0], meta.is_signer, meta.is_writable)).collect()
+}
+
+fn assert_golden(instruction: &Instruction, data: &str, accounts: &[(u8, bool, bool)]) {
+	let golden = hex(data);
+	assert_eq!(instruction.program_id, key(0xaa));
+	assert_eq!(instruction.data, golden);
+	assert_eq!(metas(instruction), accounts);
+	// the unpacker reads the same layout back
+	assert_eq!(MangoInstruction::unpack(&golden).map(|unpacked| unpacked.pack()), Some(golden));
+}
+
+#[test]
+fn place_perp_order2_post_only_bid() {
+	let instruction = place_perp_order2(
+		&key(0xaa), &key(1), &key(2), &key(3), &key(4), &key(5), &key(6), &key(7), &key(8),
+		None, &[key(9), key(10)],
+		Side::Bid, 2_500, 15, 37_500, 42, OrderType::PostOnly, false, Some(1_700_000_000), 10, ExpiryType::Absolute,
+	).unwrap();
+	assert_golden(&instruction, "
+		40000000
+		c409000000000000 0f00000000000000 7c92000000000000 2a00000000000000 00f1536500000000
+		00 02 00 0a 00
+	", &[(1, false, false), (2, false, true), (3, true, false), (4, false, false), (5, false, true), (6, false, true),
+		(7, false, true), (8, false, true), (2, false, true), (9, false, false), (10, false, false)]);
+}
+
+#[test]
+fn place_perp_order2_reduce_only_market_ask() {
+	let instruction = place_perp_order2(
+		&key(0xaa), &key(1), &key(2), &key(3), &key(4), &key(5), &key(6), &key(7), &key(8),
+		Some(&key(11)), &[],
+		Side::Ask, 2_400, 7, i64::MAX, 0, OrderType::Market, true, Some(30), 20, ExpiryType::Relative,
+	).unwrap();
+	assert_golden(&instruction, "
+		40000000
+		6009000000000000 0700000000000000 ffffffffffffff7f 0000000000000000 1e00000000000000
+		01 03 01 14 01
+	", &[(1, false, false), (2, false, true), (3, true, false), (4, false, false), (5, false, true), (6, false, true),
+		(7, false, true), (8, false, true), (11, false, true)]);
+}
+
+#[test]
+fn consume_events_sorts_accounts() {
+	let instruction = consume_events(&key(0xaa), &key(1), &key(2), &key(3), &key(4), &mut [key(6), key(5)], 8).unwrap();
+	assert_golden(&instruction, "
+		0f000000
+		0800000000000000
+	", &[(1, false, false), (2, false, false), (3, false, true), (4, false, true), (5, false, true), (6, false, true)]);
+}
+
+#[test]
+fn cancel_perp_order_by_order_id() {
+	let order_id = (2_500i128 << 64) | 7;
+	let instruction = cancel_perp_order(&key(0xaa), &key(1), &key(2), &key(3), &key(4), &key(5), &key(6), order_id, true).unwrap();
+	assert_golden(&instruction, "
+		0e000000
+		0700000000000000 c409000000000000
+		01
+	", &[(1, false, false), (2, false, true), (3, true, false), (4, false, true), (5, false, true), (6, false, true)]);
+}
+
+#[test]
+fn cancel_all_perp_orders_with_limit() {
+	let instruction = cancel_all_perp_orders(&key(0xaa), &key(1), &key(2), &key(3), &key(4), &key(5), &key(6), 20).unwrap();
+	assert_golden(&instruction, "
+		27000000
+		14
+	", &[(1, false, false), (2, false, true), (3, true, false), (4, false, true), (5, false, true), (6, false, true)]);
+}
+
+#[test]
+fn liquidate_perp_market_short_transfer() {
+	let instruction = liquidate_perp_market(&key(0xaa), &key(1), &key(2), &key(3), &key(4), &key(5), &key(6), &key(7), &[key(8)], &[key(9)], -5).unwrap();
+	assert_golden(&instruction, "
+		1c000000
+		fbffffffffffffff
+	", &[(1, false, false), (2, false, false), (3, false, true), (4, false, true), (5, false, true), (6, false, true),
+		(7, true, false), (8, false, false), (9, false, false)]);
+}
@@ -0,0 +1,204 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use mangol_common::errors::{MangolError, MangolResult};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use solana_program::hash::{hashv, Hash};
+use solana_sdk::transaction::Transaction;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AuditedAccount {
+	pub pubkey: String,
+	pub is_signer: bool,
+	pub is_writable: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AuditedInstruction {
+	pub program_id: String,
+	pub accounts: Vec<AuditedAccount>,
+	/// Base64 instruction data
+	pub data: String,
+}
+
+/// One signed transaction. `hash` covers every other field and the previous entry's hash,
+/// so editing or dropping a line breaks the chain from there on
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AuditEntry {
+	pub seq: u64,
+	pub timestamp: u64,
+	pub prev_hash: String,
+	pub signature: String,
+	pub fee_payer: String,
+	pub recent_blockhash: String,
+	pub instructions: Vec<AuditedInstruction>,
+	/// Whatever the caller set with AuditLog::set_context before sending, e.g. the decision behind an order
+	pub context: Value,
+	pub hash: String,
+}
+
+impl AuditEntry {
+	/// Hash of the entry serialized with an empty `hash`, which is what gets written with the hash
+	/// filled in. Verification hashes the line as read instead of serializing again, floats in
+	/// the context wouldn't come back byte for byte
+	fn compute_hash(&self) -> MangolResult<(String, Hash)> {
+		let mut unhashed = self.clone();
+		unhashed.hash = String::new();
+		let body = serde_json::to_string(&unhashed).map_err(|e| MangolError::SerializationError(e.to_string()))?;
+		let hash = hash_line(&self.prev_hash, &body);
+		Ok((body, hash))
+	}
+}
+
+/// `hash` is the last field, an unhashed line ends with an empty one
+const UNHASHED_SUFFIX: &str = "\"hash\":\"\"}";
+
+fn hash_line(prev_hash: &str, unhashed_line: &str) -> Hash {
+	hashv(&[prev_hash.as_bytes(), unhashed_line.as_bytes()])
+}
+
+/// The line as it was hashed, its hash emptied and every other byte as written
+fn unhashed_line(line: &str, hash: &str) -> Option<String> {
+	let hashed_suffix = format!("\"hash\":\"{}\"}}", hash);
+	line.strip_suffix(&hashed_suffix).map(|body| format!("{}{}", body, UNHASHED_SUFFIX))
+}
+
+struct AuditState {
+	seq: u64,
+	last_hash: String,
+	context: Value,
+}
+
+/// Append-only, hash-chained JSONL log of every transaction the bot signs, for answering
+/// what exactly it did and why after the fact. Clones share the file and the pending context
+#[derive(Clone)]
+pub struct AuditLog {
+	pub path: PathBuf,
+	state: Arc<Mutex<AuditState>>,
+}
+
+/// Every entry with the line it was read from
+fn read_entries(path: &PathBuf) -> MangolResult<Vec<(AuditEntry, String)>> {
+	let data = match std::fs::read_to_string(path) {
+		Ok(data) => data,
+		Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+		Err(e) => return Err(e.into())
+	};
+	data.lines().filter(|line| !line.is_empty())
+		  .map(|line| serde_json::from_str(line).map(|entry| (entry, line.to_string())).map_err(|e| MangolError::SerializationError(e.to_string())))
+		  .collect()
+}
+
+impl AuditLog {
+	/// Opens `path`, continuing the chain of an existing log after verifying it
+	pub fn open(path: &str) -> MangolResult<Self> {
+		let path = PathBuf::from(path);
+		let entries = read_entries(&path)?;
+		verify_entries(&entries)?;
+		let (seq, last_hash) = entries.last().map(|(entry, _)| (entry.seq + 1, entry.hash.clone())).unwrap_or((0, Hash::default().to_string()));
+		Ok(Self {
+			path,
+			state: Arc::new(Mutex::new(AuditState { seq, last_hash, context: Value::Null })),
+		})
+	}
+
+	/// Context recorded with the next signed transaction, cleared once it is
+	pub fn set_context(&self, context: Value) {
+		self.state.lock().unwrap().context = context;
+	}
+
+	pub fn record(&self, transaction: &Transaction) -> MangolResult<AuditEntry> {
+		let mut state = self.state.lock().unwrap();
+		let message = &transaction.message;
+		let instructions = message.instructions.iter().map(|instruction| AuditedInstruction {
+			program_id: message.account_keys[instruction.program_id_index as usize].to_string(),
+			accounts: instruction.accounts.iter().map(|index| AuditedAccount {
+				pubkey: message.account_keys[*index as usize].to_string(),
+				is_signer: message.is_signer(*index as usize),
+				is_writable: message.is_writable(*index as usize),
+			}).collect(),
+			data: base64::encode(&instruction.data),
+		}).collect();
+		let mut entry = AuditEntry {
+			seq: state.seq,
+			timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+			prev_hash: state.last_hash.clone(),
+			signature: transaction.signatures.first().map(|signature| signature.to_string()).unwrap_or_default(),
+			fee_payer: message.account_keys.first().map(|key| key.to_string()).unwrap_or_default(),
+			recent_blockhash: message.recent_blockhash.to_string(),
+			instructions,
+			context: std::mem::take(&mut state.context),
+			hash: String::new(),
+		};
+		let (body, hash) = entry.compute_hash()?;
+		entry.hash = hash.to_string();
+		let line = body.strip_suffix(UNHASHED_SUFFIX).map(|body| format!("{}\"hash\":\"{}\"}}", body, entry.hash))
+			  .ok_or_else(|| MangolError::SerializationError("Audit entry doesn't end with its hash".to_string()))?;
+		let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+		writeln!(file, "{}", line)?;
+		state.seq += 1;
+		state.last_hash = entry.hash.clone();
+		Ok(entry)
+	}
+}
+
+fn verify_entries(entries: &[(AuditEntry, String)]) -> MangolResult<()> {
+	let mut prev_hash = Hash::default().to_string();
+	for (seq, (entry, line)) in entries.iter().enumerate() {
+		let hashed = unhashed_line(line, &entry.hash).map(|unhashed| hash_line(&entry.prev_hash, &unhashed).to_string());
+		if entry.seq != seq as u64 || entry.prev_hash != prev_hash || hashed.as_deref() != Some(entry.hash.as_str()) {
+			return Err(MangolError::SerializationError(format!("Audit log chain broken at entry {}", seq)));
+		}
+		prev_hash = entry.hash.clone();
+	}
+	Ok(())
+}
+
+/// Number of entries in the log at `path`, errors at the first entry that doesn't chain
+pub fn verify(path: &str) -> MangolResult<usize> {
+	let entries = read_entries(&PathBuf::from(path))?;
+	verify_entries(&entries)?;
+	Ok(entries.len())
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+	use solana_program::hash::Hash;
+	use solana_program::pubkey::Pubkey;
+	use solana_sdk::signature::{Keypair, Signer};
+	use solana_sdk::system_instruction;
+	use solana_sdk::transaction::Transaction;
+	use crate::audit::{verify, AuditLog};
+
+	fn signed_transfer(payer: &Keypair) -> Transaction {
+		let instruction = system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1);
+		let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+		transaction.sign(&[payer], Hash::default());
+		transaction
+	}
+
+	#[test]
+	fn chains_entries_and_detects_tampering() {
+		let path = std::env::temp_dir().join(format!("mangol-audit-{}.jsonl", Pubkey::new_unique()));
+		let path = path.to_str().unwrap();
+		let payer = Keypair::new();
+		let audit_log = AuditLog::open(path).unwrap();
+		// floats don't survive a parse and serialize byte for byte, the line is hashed as written
+		audit_log.set_context(json!({"leg": "ScaleIn", "depth": 3, "oracle_price": 23.456789012345678, "quantity": 1e-7}));
+		let first = audit_log.record(&signed_transfer(&payer)).unwrap();
+		assert_eq!(first.context["depth"], 3);
+		assert!(first.instructions[0].accounts[0].is_signer);
+		let second = AuditLog::open(path).unwrap().record(&signed_transfer(&payer)).unwrap();
+		assert_eq!((second.seq, second.prev_hash.as_str(), second.context.is_null()), (1, first.hash.as_str(), true));
+		assert_eq!(verify(path).unwrap(), 2);
+		let tampered = std::fs::read_to_string(path).unwrap().replacen("\"depth\":3", "\"depth\":4", 1);
+		std::fs::write(path, tampered).unwrap();
+		assert!(verify(path).is_err());
+		std::fs::remove_file(path).unwrap();
+	}
+}
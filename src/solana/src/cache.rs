@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use solana_client::rpc_client::RpcClient;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::account::Account;
+use mangol_common::errors::{MangolResult, SolanaError};
+
+use crate::subscription::{AccountUpdate, ResilientSubscription};
+
+#[derive(Clone, Debug)]
+pub struct CachedAccount {
+	pub slot: u64,
+	pub account: Account,
+	pub fetched_at: Instant,
+}
+
+/// Accounts shared between components, keyed by pubkey and versioned by slot.
+/// Entries older than `max_age` are refetched, subscriptions keep hot accounts fresh
+pub struct AccountCache {
+	pub max_age: Duration,
+	accounts: RwLock<HashMap<Pubkey, CachedAccount>>,
+}
+
+impl AccountCache {
+	pub fn new(max_age: Duration) -> Self {
+		Self {
+			max_age,
+			accounts: RwLock::new(HashMap::new()),
+		}
+	}
+
+	/// The cached account if it is younger than `max_age`
+	pub fn get(&self, pubkey: &Pubkey) -> Option<CachedAccount> {
+		self.accounts.read().unwrap().get(pubkey)
+			  .filter(|cached| cached.fetched_at.elapsed() < self.max_age)
+			  .cloned()
+	}
+
+	/// Stores `account` unless a newer slot is already cached, returns whether it was stored
+	pub fn insert(&self, pubkey: Pubkey, slot: u64, account: Account) -> bool {
+		let mut accounts = self.accounts.write().unwrap();
+		if let Some(cached) = accounts.get(&pubkey) {
+			if cached.slot > slot {
+				return false;
+			}
+		}
+		accounts.insert(pubkey, CachedAccount { slot, account, fetched_at: Instant::now() });
+		true
+	}
+
+	pub fn invalidate(&self, pubkey: &Pubkey) {
+		self.accounts.write().unwrap().remove(pubkey);
+	}
+
+	pub fn apply_update(&self, update: &AccountUpdate) -> bool {
+		self.insert(update.pubkey, update.slot, update.account.clone())
+	}
+
+	pub fn get_or_fetch(&self, rpc_client: &RpcClient, pubkey: &Pubkey) -> MangolResult<Account> {
+		if let Some(cached) = self.get(pubkey) {
+			return Ok(cached.account);
+		}
+		let response = rpc_client.get_account_with_commitment(pubkey, rpc_client.commitment())?;
+		let account = response.value.ok_or(SolanaError::ProgramAccountsNotFound)?;
+		self.insert(*pubkey, response.context.slot, account.clone());
+		Ok(account)
+	}
+
+	/// Keeps `subscription`'s account in the cache for as long as the subscription runs
+	pub fn follow(self: &Arc<Self>, subscription: ResilientSubscription) -> JoinHandle<()> {
+		let cache = self.clone();
+		std::thread::spawn(move || {
+			let (_subscription_handle, updates) = subscription.start();
+			for update in updates {
+				cache.apply_update(&update);
+			}
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+	use solana_program::pubkey::Pubkey;
+	use solana_sdk::account::Account;
+	use crate::cache::AccountCache;
+
+	fn account(lamports: u64) -> Account {
+		Account { lamports, ..Account::default() }
+	}
+
+	#[test]
+	fn keeps_newest_slot_and_expires() {
+		let pubkey = Pubkey::new_unique();
+		let cache = AccountCache::new(Duration::from_secs(60));
+		assert!(cache.insert(pubkey, 10, account(1)));
+		assert!(!cache.insert(pubkey, 9, account(2)));
+		assert_eq!(cache.get(&pubkey).unwrap().account.lamports, 1);
+		cache.invalidate(&pubkey);
+		assert!(cache.get(&pubkey).is_none());
+
+		let expired = AccountCache::new(Duration::ZERO);
+		expired.insert(pubkey, 10, account(1));
+		assert!(expired.get(&pubkey).is_none());
+	}
+}
@@ -0,0 +1,114 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use mangol_common::clock::Clock;
+use mangol_common::errors::MangolResult;
+use solana_client::rpc_client::RpcClient;
+use solana_program::clock::UnixTimestamp;
+use solana_sdk::commitment_config::CommitmentConfig;
+
+/// Local time corrected by how far the cluster's block times drift from it, so absolute order
+/// expiries line up with the unix timestamp the program reads from the Clock sysvar.
+/// Clones share the estimate, `start` keeps refining it from recent slots
+#[derive(Clone)]
+pub struct ClusterClock {
+	pub rpc_addr: String,
+	pub poll_interval: Duration,
+	/// Offset samples the estimate is the median of
+	pub window: usize,
+	local: Arc<dyn Clock>,
+	samples: Arc<RwLock<VecDeque<i64>>>,
+	offset_millis: Arc<AtomicI64>,
+}
+
+impl ClusterClock {
+	pub fn new(rpc_addr: &str, local: Arc<dyn Clock>) -> Self {
+		Self {
+			rpc_addr: rpc_addr.to_string(),
+			poll_interval: Duration::from_secs(30),
+			window: 15,
+			local,
+			samples: Arc::new(RwLock::new(VecDeque::new())),
+			offset_millis: Arc::new(AtomicI64::new(0)),
+		}
+	}
+
+	/// Cluster time minus local time, 0 until the first sample
+	pub fn offset_millis(&self) -> i64 {
+		self.offset_millis.load(Ordering::SeqCst)
+	}
+
+	/// Adds a block time seen at `local_millis` and returns the refined offset. Block times are
+	/// whole seconds, the middle of the second is taken
+	pub fn observe(&self, block_time: UnixTimestamp, local_millis: u64) -> i64 {
+		let mut samples = self.samples.write().unwrap();
+		samples.push_back(block_time * 1000 + 500 - local_millis as i64);
+		while samples.len() > self.window.max(1) {
+			samples.pop_front();
+		}
+		let mut sorted: Vec<i64> = samples.iter().copied().collect();
+		sorted.sort_unstable();
+		let offset = sorted[sorted.len() / 2];
+		self.offset_millis.store(offset, Ordering::SeqCst);
+		offset
+	}
+
+	fn sample(&self, rpc_client: &RpcClient) -> MangolResult<i64> {
+		let slot = rpc_client.get_slot()?;
+		let local_millis = self.local.now_millis();
+		let block_time = rpc_client.get_block_time(slot)?;
+		Ok(self.observe(block_time, local_millis))
+	}
+
+	pub fn start(&self) -> JoinHandle<()> {
+		let clock = self.clone();
+		std::thread::spawn(move || {
+			let rpc_client = RpcClient::new_with_timeout_and_commitment(&clock.rpc_addr, Duration::from_secs(30), CommitmentConfig::confirmed());
+			let mut previous_offset = 0;
+			loop {
+				match clock.sample(&rpc_client) {
+					Ok(offset) if (offset - previous_offset).abs() >= 1000 => {
+						println!("[?] Cluster clock is {}ms off local time", offset);
+						previous_offset = offset;
+					}
+					Ok(_) => {}
+					Err(e) => eprintln!("[-] Cluster clock failed to sample block time {:?}", e)
+				}
+				std::thread::sleep(clock.poll_interval);
+			}
+		})
+	}
+}
+
+impl Clock for ClusterClock {
+	fn now_millis(&self) -> u64 {
+		(self.local.now_millis() as i64 + self.offset_millis()).max(0) as u64
+	}
+
+	fn sleep(&self, duration: Duration) {
+		self.local.sleep(duration)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use mangol_common::clock::{Clock, SimulatedClock};
+	use crate::cluster_time::ClusterClock;
+
+	#[test]
+	fn offsets_local_time_by_median_drift() {
+		let local = Arc::new(SimulatedClock::new(1_000));
+		let clock = ClusterClock::new("http://localhost:8899", local.clone());
+		assert_eq!(clock.now_ts(), 1_000);
+		// cluster runs about 3s behind, one sample caught a skipped slot
+		clock.observe(997, 1_000_000);
+		clock.observe(997, 1_000_100);
+		clock.observe(1_010, 1_000_200);
+		assert_eq!(clock.offset_millis(), -2_500);
+		assert_eq!(clock.now_millis(), 997_500);
+	}
+}
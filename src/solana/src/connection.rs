@@ -1,12 +1,17 @@
 use std::sync::Arc;
+#[cfg(feature = "tpu")]
+use std::sync::Mutex;
 use std::time::Duration;
 use solana_client::rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClient};
-use solana_client::rpc_config::RpcProgramAccountsConfig;
+use solana_client::rpc_config::{RpcProgramAccountsConfig, RpcSimulateTransactionAccountsConfig, RpcSimulateTransactionConfig};
+use solana_client::rpc_response::RpcSimulateTransactionResult;
+#[cfg(feature = "tpu")]
 use solana_client::tpu_client::{TpuClient, TpuClientConfig};
 use solana_client::client_error::ClientErrorKind;
 use solana_client::rpc_request;
 use solana_program::pubkey::Pubkey;
 use solana_sdk::account::Account;
+use solana_sdk::epoch_schedule::EpochSchedule;
 use itertools::Itertools;
 use solana_sdk::commitment_config::CommitmentConfig;
 use mangol_common::errors::{MangolError, MangolResult, SolanaError};
@@ -16,29 +21,233 @@ use solana_account_decoder::{UiAccountData, UiAccountEncoding};
 use solana_client::pubsub_client::{AccountSubscription, PubsubClientError};
 use solana_sdk::transaction::{Transaction, TransactionError};
 use std::time::Instant;
-use solana_sdk::signature::{Keypair, Signature};
+use solana_sdk::signature::{Keypair, Signature, Signer};
 use std::thread::sleep;
 use solana_program::hash::hash;
 use solana_program::instruction::InstructionError as IError;
 use solana_sdk::transaction::TransactionError::InstructionError;
+use crate::scan::{ProgramAccountScan, MAX_MULTIPLE_ACCOUNTS};
+use crate::expenses::{tx_expense, TxExpense};
+use crate::audit::AuditLog;
+use crate::disk_cache::{self, DiskCache};
+use crate::endpoints::{EndpointConfig, EndpointPool, OperationClass, TpuConfig};
+use crate::payer_lock::FeePayerLock;
+use crate::consistency::{min_context_slot_not_reached, WriteSlot};
+use solana_client::rpc_request::RpcResponseErrorData;
+use solana_client::rpc_response::Response;
+use solana_client::client_error::Result as ClientResult;
+use solana_transaction_status::UiTransactionEncoding;
+use std::str::FromStr;
+
+/// Json rpc error of a transaction whose signatures don't verify
+pub const SIGNATURE_VERIFICATION_FAILURE: i64 = -32003;
+
+/// Whether the node refused a transaction because a signature did not verify, directly or in preflight
+pub fn is_signature_failure(kind: &ClientErrorKind) -> bool {
+	match kind {
+		ClientErrorKind::TransactionError(TransactionError::SignatureFailure) => true,
+		ClientErrorKind::RpcError(rpc_request::RpcError::RpcResponseError { code, data, .. }) => {
+			*code == SIGNATURE_VERIFICATION_FAILURE || matches!(data, RpcResponseErrorData::SendTransactionPreflightFailure(RpcSimulateTransactionResult { err: Some(TransactionError::SignatureFailure), .. }))
+		}
+		_ => false
+	}
+}
 
 pub struct SolanaConnection {
 	pub rpc_client: RpcClient,
-	
-	//TODO: experiment with tpu clients and sending txs to the next leader
-	pub tpu_client: TpuClient,
-
+	/// Whether and how transactions are also sent to the upcoming leaders
+	pub tpu: TpuConfig,
+	/// Built on the first send with tpu enabled, it opens a websocket
+	#[cfg(feature = "tpu")]
+	tpu_client: Mutex<Option<Arc<TpuClient>>>,
+	/// Every transaction signed by try_tx_once is appended here
+	pub audit_log: Option<AuditLog>,
+	/// Routes scans, submissions and subscriptions to their own endpoints, everything goes to rpc_client without it
+	pub endpoints: Option<Arc<EndpointPool>>,
+	/// Held from signing until the transaction is sent, for fee payers shared with other processes
+	pub payer_lock: Option<FeePayerLock>,
+	/// Slot of the last transaction try_tx_once confirmed, the floor for account reads after it
+	pub write_slot: WriteSlot,
+	/// Mints, epoch schedule and market static data kept across restarts
+	pub disk_cache: Option<DiskCache>,
 }
 
+/// Retries of a read the node is not caught up for, a slot apart
+const MIN_CONTEXT_SLOT_RETRIES: usize = 10;
+
 impl SolanaConnection {
 	pub fn new(rpc_addr: &str) -> MangolResult<Self> {
 		let rpc_client = RpcClient::new_with_timeout_and_commitment(rpc_addr, Duration::from_secs(120), CommitmentConfig::confirmed());
-		let tpu_client = TpuClient::new(Arc::new(RpcClient::new(rpc_addr)), "wss://ninja.genesysgo.net", TpuClientConfig { fanout_slots: 50 }).unwrap();
-
-		Ok(Self {
+		Ok(Self::from_rpc_client(rpc_client))
+	}
+	
+	/// Connection to `config.default` that sends every other operation class to its configured endpoint
+	pub fn from_endpoints(config: EndpointConfig) -> MangolResult<Self> {
+		let tpu = config.tpu.clone();
+		Ok(Self::new(&config.default)?.with_endpoints(Arc::new(EndpointPool::new(config))).with_tpu(tpu))
+	}
+	
+	pub fn with_endpoints(mut self, endpoints: Arc<EndpointPool>) -> Self {
+		self.endpoints = Some(endpoints);
+		self
+	}
+	
+	pub fn with_tpu(mut self, tpu: TpuConfig) -> Self {
+		self.tpu = tpu;
+		self
+	}
+	
+	/// New connection to the same endpoints, sharing the endpoint pool and audit log
+	pub fn try_clone(&self) -> MangolResult<Self> {
+		let mut connection = Self::new(&self.rpc_client.url())?;
+		connection.endpoints = self.endpoints.clone();
+		connection.audit_log = self.audit_log.clone();
+		connection.payer_lock = self.payer_lock.clone();
+		connection.write_slot = self.write_slot.clone();
+		connection.tpu = self.tpu.clone();
+		connection.disk_cache = self.disk_cache.clone();
+		Ok(connection)
+	}
+	
+	/// Rpc client for `class`, rpc_client unless the endpoint pool routes it elsewhere
+	pub fn rpc(&self, class: OperationClass) -> &RpcClient {
+		match &self.endpoints {
+			Some(endpoints) => endpoints.client(class),
+			None => &self.rpc_client
+		}
+	}
+	
+	/// Websocket endpoint for subscriptions
+	pub fn ws_url(&self) -> String {
+		match &self.endpoints {
+			Some(endpoints) => endpoints.ws_url(),
+			None => EndpointConfig::new(&self.rpc_client.url()).ws_url()
+		}
+	}
+	
+	pub fn with_payer_lock(mut self, payer_lock: FeePayerLock) -> Self {
+		self.payer_lock = Some(payer_lock);
+		self
+	}
+	
+	pub fn with_audit_log(mut self, audit_log: AuditLog) -> Self {
+		self.audit_log = Some(audit_log);
+		self
+	}
+	
+	pub fn with_disk_cache(mut self, disk_cache: DiskCache) -> Self {
+		self.disk_cache = Some(disk_cache);
+		self
+	}
+	
+	/// `pubkey` from the disk cache when one is set and the entry is younger than `ttl`, from the rpc otherwise
+	pub fn get_static_account(&self, pubkey: &Pubkey, ttl: Option<Duration>) -> MangolResult<Account> {
+		let rpc = self.rpc(OperationClass::Scan);
+		match &self.disk_cache {
+			Some(disk_cache) => disk_cache.account(rpc, pubkey, ttl),
+			None => Ok(rpc.get_account_with_commitment(pubkey, rpc.commitment())?.value.ok_or(SolanaError::ProgramAccountsNotFound)?)
+		}
+	}
+	
+	/// Decimals of `mint`, they never change so the disk cache keeps them for good
+	pub fn mint_decimals(&self, mint: &Pubkey) -> MangolResult<u8> {
+		let rpc = self.rpc(OperationClass::Scan);
+		match &self.disk_cache {
+			Some(disk_cache) => disk_cache.mint_decimals(rpc, mint),
+			None => disk_cache::mint_decimals(&rpc.get_account(mint)?.data)
+		}
+	}
+	
+	pub fn epoch_schedule(&self) -> MangolResult<EpochSchedule> {
+		let rpc = self.rpc(OperationClass::Scan);
+		match &self.disk_cache {
+			Some(disk_cache) => disk_cache.epoch_schedule(rpc),
+			None => Ok(rpc.get_epoch_schedule()?)
+		}
+	}
+	
+	fn audit(&self, signed_transaction: &Transaction) {
+		if let Some(audit_log) = &self.audit_log {
+			if let Err(e) = audit_log.record(signed_transaction) {
+				eprintln!("[-] Failed to write audit log {:?}", e);
+			}
+		}
+	}
+	
+	/// Connection over an already built rpc client, e.g. one with injected faults
+	pub fn from_rpc_client(rpc_client: RpcClient) -> Self {
+		Self {
 			rpc_client,
-			tpu_client
-		})
+			tpu: TpuConfig::default(),
+			#[cfg(feature = "tpu")]
+			tpu_client: Mutex::new(None),
+			audit_log: None,
+			endpoints: None,
+			payer_lock: None,
+			write_slot: WriteSlot::default(),
+			disk_cache: None,
+		}
+	}
+	
+	/// The tpu client, built on first use. None while tpu is disabled
+	#[cfg(feature = "tpu")]
+	pub fn tpu_client(&self) -> MangolResult<Option<Arc<TpuClient>>> {
+		if !self.tpu.enabled {
+			return Ok(None);
+		}
+		let mut tpu_client = self.tpu_client.lock().unwrap();
+		if tpu_client.is_none() {
+			let ws_url = self.tpu.ws_url.clone().unwrap_or_else(|| self.ws_url());
+			let client = TpuClient::new(Arc::new(RpcClient::new(self.rpc(OperationClass::Submit).url())), &ws_url, TpuClientConfig { fanout_slots: self.tpu.fanout_slots })
+				  .map_err(|e| SolanaError::RpcClientError(ClientErrorKind::Custom(format!("tpu client on {} {:?}", ws_url, e))))?;
+			*tpu_client = Some(Arc::new(client));
+		}
+		Ok(tpu_client.clone())
+	}
+	
+	/// Sends `signed_transaction` to the upcoming leaders, false when tpu is disabled or the send failed
+	fn send_to_leaders(&self, signed_transaction: &Transaction) -> bool {
+		#[cfg(feature = "tpu")]
+		match self.tpu_client() {
+			Ok(Some(tpu_client)) => return tpu_client.send_transaction(signed_transaction),
+			Ok(None) => {}
+			Err(e) => eprintln!("[-] Failed to build tpu client {:?}", e),
+		}
+		#[cfg(not(feature = "tpu"))]
+		let _ = signed_transaction;
+		false
+	}
+	
+	/// Runs `read` with the last write slot as its min context slot, waiting out nodes that are behind it
+	fn read_after_writes<T>(&self, read: impl Fn(Option<u64>) -> ClientResult<T>) -> MangolResult<T> {
+		let min_context_slot = self.write_slot.min_context_slot();
+		for _ in 0..MIN_CONTEXT_SLOT_RETRIES {
+			match read(min_context_slot) {
+				Err(e) if min_context_slot_not_reached(&e) => sleep(Duration::from_millis(400)),
+				result => return Ok(result?)
+			}
+		}
+		Ok(read(min_context_slot)?)
+	}
+	
+	/// The account as of at least the last confirmed write
+	pub fn get_account_after_writes(&self, pubkey: &Pubkey, commitment: CommitmentConfig) -> MangolResult<Response<Option<Account>>> {
+		self.read_after_writes(|min_context_slot| self.rpc_client.get_account_with_config(pubkey, RpcAccountInfoConfig {
+			encoding: Some(UiAccountEncoding::Base64Zstd),
+			data_slice: None,
+			commitment: Some(commitment),
+			min_context_slot
+		}))
+	}
+	
+	/// The accounts as of at least the last confirmed write, read in the same slot
+	pub fn get_multiple_accounts_after_writes(&self, pubkeys: &[Pubkey], commitment: CommitmentConfig) -> MangolResult<Response<Vec<Option<Account>>>> {
+		self.read_after_writes(|min_context_slot| self.rpc_client.get_multiple_accounts_with_config(pubkeys, RpcAccountInfoConfig {
+			encoding: Some(UiAccountEncoding::Base64Zstd),
+			data_slice: None,
+			commitment: Some(commitment),
+			min_context_slot
+		}))
 	}
 	
 	pub fn get_leader(&self) -> MangolResult<bool> {
@@ -53,7 +262,7 @@ impl SolanaConnection {
 		pubkey: &Pubkey,
 		config: &RpcProgramAccountsConfig
 	) -> MangolResult<Vec<(Pubkey, Account)>> {
-		let response = self.rpc_client.get_program_accounts_with_config(pubkey, config.clone());
+		let response = self.rpc(OperationClass::Scan).get_program_accounts_with_config(pubkey, config.clone());
 		if let Ok(accounts) = response {
 			Ok(accounts)
 		} else {
@@ -61,6 +270,24 @@ impl SolanaConnection {
 		}
 	}
 	
+	/// Fee, priority fee and rent a confirmed transaction cost its payer
+	pub fn get_transaction_expense(&self, signature: &str) -> MangolResult<TxExpense> {
+		let signature = Signature::from_str(signature).map_err(|e| MangolError::SerializationError(e.to_string()))?;
+		let transaction = self.rpc_client.get_transaction(&signature, UiTransactionEncoding::Base64)?;
+		let signatures = transaction.transaction.transaction.decode().map(|decoded| decoded.signatures.len()).unwrap_or(1);
+		let meta = transaction.transaction.meta.ok_or(SolanaError::TransactionStatusUnknown)?;
+		Ok(tx_expense(meta.fee, signatures, &meta.pre_balances, &meta.post_balances))
+	}
+	
+	/// Streams matching program accounts in batches instead of one getProgramAccounts response
+	pub fn scan_program_accounts(
+		&self,
+		pubkey: &Pubkey,
+		config: &RpcProgramAccountsConfig
+	) -> MangolResult<ProgramAccountScan> {
+		ProgramAccountScan::new(self.rpc(OperationClass::Scan), pubkey, config, MAX_MULTIPLE_ACCOUNTS)
+	}
+	
 	pub fn get_first_program_account_with_config(
 		&self,
 		pubkey: &Pubkey,
@@ -88,7 +315,7 @@ impl SolanaConnection {
 		} else {
 			let mut account_times = vec![];
 			for (pubkey, account) in &accounts {
-				let last_sigs = self.rpc_client.get_signatures_for_address_with_config(&pubkey, GetConfirmedSignaturesForAddress2Config {
+				let last_sigs = self.rpc(OperationClass::Scan).get_signatures_for_address_with_config(&pubkey, GetConfirmedSignaturesForAddress2Config {
 					before: None,
 					commitment: Some(CommitmentConfig::finalized()),
 					until: None,
@@ -111,26 +338,63 @@ impl SolanaConnection {
 		return solana_client::pubsub_client::PubsubClient::account_subscribe(ws_url, account, Some(RpcAccountInfoConfig { encoding: Some(UiAccountEncoding::JsonParsed), data_slice: None, commitment: Some(CommitmentConfig::finalized()), min_context_slot: None }));
 	}
 	
+	/// Signs the transaction with a fresh blockhash and simulates it without sending
+	pub fn simulate_tx(&self, transaction: Transaction, signer: &Keypair) -> MangolResult<RpcSimulateTransactionResult> {
+		let recent_blockhash = self.rpc_client.get_latest_blockhash()?;
+		let mut signed_transaction = transaction.clone();
+		signed_transaction.sign(&[signer], recent_blockhash);
+		let response = self.rpc_client.simulate_transaction(&signed_transaction)?;
+		Ok(response.value)
+	}
+
+	/// `simulate_tx` returning `addresses` as the transaction would leave them, base64 in that order
+	pub fn simulate_tx_with_accounts(&self, transaction: Transaction, signer: &Keypair, addresses: &[Pubkey]) -> MangolResult<RpcSimulateTransactionResult> {
+		let recent_blockhash = self.rpc_client.get_latest_blockhash()?;
+		let mut signed_transaction = transaction.clone();
+		signed_transaction.sign(&[signer], recent_blockhash);
+		let config = RpcSimulateTransactionConfig {
+			accounts: Some(RpcSimulateTransactionAccountsConfig {
+				encoding: Some(UiAccountEncoding::Base64),
+				addresses: addresses.iter().map(|address| address.to_string()).collect(),
+			}),
+			..RpcSimulateTransactionConfig::default()
+		};
+		let response = self.rpc_client.simulate_transaction_with_config(&signed_transaction, config)?;
+		Ok(response.value)
+	}
+	
 	pub fn try_tx_once(&self, transaction: Transaction, signer: &Keypair) -> MangolResult<String> {
 		const SEND_RETRIES: usize = 15;
 		const GET_STATUS_RETRIES: usize = 155;
 		let now = Instant::now();
-		let recent_blockhash = self.rpc_client.get_latest_blockhash().unwrap();
+		// the fee payer signs first
+		let mut payer_guard = match (&self.payer_lock, transaction.message.account_keys.first()) {
+			(Some(payer_lock), Some(payer)) => Some(payer_lock.acquire(payer)?),
+			_ => None
+		};
+		let recent_blockhash = self.rpc_client.get_latest_blockhash()?;
 		
 		let mut signed_transaction = transaction.clone();
 		signed_transaction.sign(&[signer], recent_blockhash);
+		self.audit(&signed_transaction);
 		'sending: for _ in 0..SEND_RETRIES {
-			let sig = self.rpc_client.send_transaction(&signed_transaction);
+			// leaders get it directly too, the rpc send below still returns the signature to confirm
+			self.send_to_leaders(&signed_transaction);
+			let sig = self.rpc(OperationClass::Submit).send_transaction(&signed_transaction);
+			payer_guard.take();
 			if let Ok(signature) = sig {
 				
 				
 				'confirmation: for status_retry in 0..usize::MAX {
 					let result: Result<Signature, Option<TransactionError>> =
-						  match self.rpc_client.get_signature_status_with_commitment(&signature,CommitmentConfig::finalized()) {
+						  match self.rpc_client.get_signature_statuses(&[signature]).map(|statuses| statuses.value.into_iter().next().flatten().filter(|status| status.satisfies_commitment(CommitmentConfig::finalized()))) {
 							  Ok(res) => {
-								  match res {
-									  Some(Ok(_)) => Ok(signature),
-									  Some(Err(e)) => Err(Some(e.into())),
+								  match res.map(|status| (status.slot, status.status)) {
+									  Some((slot, Ok(_))) => {
+										  self.write_slot.record(slot);
+										  Ok(signature)
+									  }
+									  Some((_, Err(e))) => Err(Some(e.into())),
 									  None => {
 										  if status_retry < GET_STATUS_RETRIES
 										  {
@@ -185,6 +449,9 @@ impl SolanaConnection {
 			} else {
 				let err = sig.unwrap_err();
 				eprintln!("[-] An Error Occurred While sending tx: {:?}", &err );
+				if is_signature_failure(&err.kind) {
+					return Err(MangolError::SolanaError(SolanaError::SignatureVerificationFailed(signer.pubkey().to_string())));
+				}
 				match &err.kind {
 					ClientErrorKind::RpcError(e) => {
 						match e {
@@ -193,10 +460,12 @@ impl SolanaConnection {
 								..
 							} => {
 								if *code == -32002 {
-									// update blockhash
-									let recent_blockhash = self.rpc_client.get_latest_blockhash().unwrap();
-									signed_transaction = transaction.clone();
-									signed_transaction.sign(&[signer], recent_blockhash);
+									// update blockhash, keep the old one if the rpc can't give a new one right now
+									if let Ok(recent_blockhash) = self.rpc_client.get_latest_blockhash() {
+										signed_transaction = transaction.clone();
+										signed_transaction.sign(&[signer], recent_blockhash);
+										self.audit(&signed_transaction);
+									}
 									
 								}
 							}
@@ -0,0 +1,55 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_client::rpc_request::RpcError;
+
+/// Json rpc error of a node that has not yet processed the requested `min_context_slot`
+pub const MIN_CONTEXT_SLOT_NOT_REACHED: i64 = -32016;
+
+/// Slot of the latest transaction this process confirmed, shared by every clone of a connection.
+/// Reads pass it as `min_context_slot` so a lagging node can't answer with pre-trade state
+#[derive(Clone, Debug, Default)]
+pub struct WriteSlot {
+	slot: Arc<AtomicU64>,
+}
+
+impl WriteSlot {
+	/// Keeps the highest slot recorded, confirmations can come back out of order
+	pub fn record(&self, slot: u64) {
+		self.slot.fetch_max(slot, Ordering::SeqCst);
+	}
+
+	/// None until something was written
+	pub fn min_context_slot(&self) -> Option<u64> {
+		match self.slot.load(Ordering::SeqCst) {
+			0 => None,
+			slot => Some(slot)
+		}
+	}
+}
+
+/// Whether the node answered `err` because it is behind the slot the read asked for
+pub fn min_context_slot_not_reached(err: &ClientError) -> bool {
+	matches!(&err.kind, ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. }) if *code == MIN_CONTEXT_SLOT_NOT_REACHED)
+}
+
+#[cfg(test)]
+mod tests {
+	use solana_client::client_error::{ClientError, ClientErrorKind};
+	use solana_client::rpc_request::{RpcError, RpcResponseErrorData};
+	use crate::consistency::{min_context_slot_not_reached, WriteSlot, MIN_CONTEXT_SLOT_NOT_REACHED};
+
+	#[test]
+	fn keeps_latest_write_slot() {
+		let write_slot = WriteSlot::default();
+		assert_eq!(write_slot.min_context_slot(), None);
+		write_slot.record(120);
+		write_slot.clone().record(100);
+		assert_eq!(write_slot.min_context_slot(), Some(120));
+
+		let lagging = |code| ClientError::from(ClientErrorKind::RpcError(RpcError::RpcResponseError { code, message: String::new(), data: RpcResponseErrorData::Empty }));
+		assert!(min_context_slot_not_reached(&lagging(MIN_CONTEXT_SLOT_NOT_REACHED)));
+		assert!(!min_context_slot_not_reached(&lagging(-32002)));
+	}
+}
@@ -0,0 +1,135 @@
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use mangol_common::errors::{MangolError, MangolResult, SolanaError};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use solana_client::rpc_client::RpcClient;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::account::Account;
+use solana_sdk::epoch_schedule::EpochSchedule;
+
+/// Program ids, mints and other accounts that can't change once deployed
+pub const IMMUTABLE: Option<Duration> = None;
+/// Market static data, changed only by rare admin instructions
+pub const MARKET_STATIC_TTL: Duration = Duration::from_secs(6 * 60 * 60);
+/// Token lists of external providers
+pub const TOKEN_LIST_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Offset of `decimals` in an spl token mint
+const MINT_DECIMALS_OFFSET: usize = 44;
+
+#[derive(Serialize, Deserialize)]
+struct Entry<T> {
+	/// Unix seconds
+	stored_at: u64,
+	/// Never expires when None
+	ttl_secs: Option<u64>,
+	value: T,
+}
+
+/// Decimals of an spl token mint from its account data
+pub fn mint_decimals(data: &[u8]) -> MangolResult<u8> {
+	data.get(MINT_DECIMALS_OFFSET).copied().ok_or_else(|| SolanaError::TokenMintNotFound.into())
+}
+
+fn now_secs() -> u64 {
+	SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs()).unwrap_or_default()
+}
+
+/// Lookups that never or rarely change, kept as json files under `dir` so restarts and new
+/// subsystems reuse them instead of spending the rpc rate limit the hot path needs. Each entry
+/// expires after the ttl it was stored with, a corrupt or unreadable entry counts as missing
+#[derive(Clone, Debug)]
+pub struct DiskCache {
+	pub dir: PathBuf,
+}
+
+impl DiskCache {
+	pub fn new(dir: PathBuf) -> MangolResult<Self> {
+		std::fs::create_dir_all(&dir)?;
+		Ok(Self { dir })
+	}
+
+	fn path(&self, key: &str) -> PathBuf {
+		let file_name: String = key.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '_' }).collect();
+		self.dir.join(format!("{}.json", file_name))
+	}
+
+	pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+		let entry: Entry<T> = serde_json::from_str(&std::fs::read_to_string(self.path(key)).ok()?).ok()?;
+		let fresh = entry.ttl_secs.map(|ttl_secs| now_secs() < entry.stored_at + ttl_secs).unwrap_or(true);
+		fresh.then(|| entry.value)
+	}
+
+	pub fn put<T: Serialize>(&self, key: &str, value: &T, ttl: Option<Duration>) -> MangolResult<()> {
+		let entry = Entry { stored_at: now_secs(), ttl_secs: ttl.map(|ttl| ttl.as_secs()), value };
+		let data = serde_json::to_string(&entry).map_err(|e| MangolError::SerializationError(e.to_string()))?;
+		// written aside and renamed so a reader never sees half an entry
+		let path = self.path(key);
+		let partial = path.with_extension("json.partial");
+		std::fs::write(&partial, data)?;
+		std::fs::rename(partial, path)?;
+		Ok(())
+	}
+
+	pub fn invalidate(&self, key: &str) {
+		let _ = std::fs::remove_file(self.path(key));
+	}
+
+	/// The cached value of `key`, or what `fetch` returns stored for `ttl`
+	pub fn get_or_fetch<T: Serialize + DeserializeOwned, F: FnOnce() -> MangolResult<T>>(&self, key: &str, ttl: Option<Duration>, fetch: F) -> MangolResult<T> {
+		if let Some(value) = self.get(key) {
+			return Ok(value);
+		}
+		let value = fetch()?;
+		if let Err(e) = self.put(key, &value, ttl) {
+			eprintln!("[-] Failed to cache {} {:?}", key, e);
+		}
+		Ok(value)
+	}
+
+	/// Keyed by cluster too, the same pubkey can hold different data on mainnet and devnet
+	pub fn account(&self, rpc_client: &RpcClient, pubkey: &Pubkey, ttl: Option<Duration>) -> MangolResult<Account> {
+		let key = format!("account-{}-{}", self.genesis_hash(rpc_client)?, pubkey);
+		self.get_or_fetch(&key, ttl, || {
+			Ok(rpc_client.get_account_with_commitment(pubkey, rpc_client.commitment())?.value.ok_or(SolanaError::ProgramAccountsNotFound)?)
+		})
+	}
+
+	pub fn mint_decimals(&self, rpc_client: &RpcClient, mint: &Pubkey) -> MangolResult<u8> {
+		let key = format!("decimals-{}-{}", self.genesis_hash(rpc_client)?, mint);
+		self.get_or_fetch(&key, IMMUTABLE, || mint_decimals(&rpc_client.get_account(mint)?.data))
+	}
+
+	/// The genesis hash tells clusters apart, so entries of mainnet and devnet don't mix
+	pub fn genesis_hash(&self, rpc_client: &RpcClient) -> MangolResult<String> {
+		self.get_or_fetch(&format!("genesis-{}", rpc_client.url()), IMMUTABLE, || Ok(rpc_client.get_genesis_hash()?.to_string()))
+	}
+
+	pub fn epoch_schedule(&self, rpc_client: &RpcClient) -> MangolResult<EpochSchedule> {
+		let key = format!("epoch-schedule-{}", self.genesis_hash(rpc_client)?);
+		self.get_or_fetch(&key, IMMUTABLE, || Ok(rpc_client.get_epoch_schedule()?))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+	use crate::disk_cache::{DiskCache, IMMUTABLE};
+
+	#[test]
+	fn survives_reopening_and_expires() {
+		let dir = std::env::temp_dir().join(format!("mangol-disk-cache-{}", std::process::id()));
+		let cache = DiskCache::new(dir.clone()).unwrap();
+		let fetched: u8 = cache.get_or_fetch("decimals-So11111111111111111111111111111111111111112", IMMUTABLE, || Ok(9)).unwrap();
+		assert_eq!(fetched, 9);
+		let reopened = DiskCache::new(dir.clone()).unwrap();
+		assert_eq!(reopened.get_or_fetch::<u8, _>("decimals-So11111111111111111111111111111111111111112", IMMUTABLE, || panic!("refetched")).unwrap(), 9);
+		reopened.put("token-list", &vec!["SOL".to_string()], Some(Duration::ZERO)).unwrap();
+		assert!(reopened.get::<Vec<String>>("token-list").is_none());
+		reopened.invalidate("decimals-So11111111111111111111111111111111111111112");
+		assert!(reopened.get::<u8>("decimals-So11111111111111111111111111111111111111112").is_none());
+		std::fs::remove_dir_all(dir).unwrap();
+	}
+}
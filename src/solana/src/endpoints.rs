@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use mangol_common::errors::{MangolError, MangolResult};
+use serde::{Deserialize, Serialize};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+
+/// What a request is for, so each class can go to the endpoint that handles it best
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationClass {
+	/// Everything without a dedicated endpoint: account reads, statuses, blockhashes
+	Default,
+	/// getProgramAccounts and other heavy reads, e.g. an archival node
+	Scan,
+	/// sendTransaction, e.g. a staked low latency node
+	Submit,
+	/// Websocket subscriptions
+	Subscribe,
+}
+
+/// Sending transactions straight to the upcoming leaders' TPU ports, besides the rpc node.
+/// Only takes effect with the `tpu` feature
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TpuConfig {
+	pub enabled: bool,
+	/// Upcoming leaders every transaction is sent to
+	pub fanout_slots: u64,
+	/// Websocket the client follows slots on, the connection's subscription endpoint when None
+	#[serde(default)]
+	pub ws_url: Option<String>,
+}
+
+impl Default for TpuConfig {
+	fn default() -> Self {
+		Self { enabled: false, fanout_slots: 12, ws_url: None }
+	}
+}
+
+/// Endpoint per operation class, classes left out use `default` (or `default_ws` for subscriptions).
+///
+/// ```json
+/// { "default": "https://rpc.example", "default_ws": "wss://rpc.example",
+///   "routes": { "scan": "https://archive.example", "submit": "https://staked.example", "subscribe": "wss://ws.example" },
+///   "tpu": { "enabled": true, "fanout_slots": 12 } }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct EndpointConfig {
+	pub default: String,
+	#[serde(default)]
+	pub default_ws: Option<String>,
+	#[serde(default)]
+	pub routes: HashMap<OperationClass, String>,
+	#[serde(default)]
+	pub tpu: TpuConfig,
+}
+
+impl EndpointConfig {
+	pub fn new(default: &str) -> Self {
+		Self { default: default.to_string(), ..Default::default() }
+	}
+
+	pub fn with_route(mut self, class: OperationClass, url: &str) -> Self {
+		self.routes.insert(class, url.to_string());
+		self
+	}
+
+	pub fn load(path: &str) -> MangolResult<Self> {
+		serde_json::from_str(&std::fs::read_to_string(path)?).map_err(|e| MangolError::SerializationError(e.to_string()))
+	}
+
+	pub fn url(&self, class: OperationClass) -> &str {
+		self.routes.get(&class).map(|url| url.as_str()).unwrap_or(&self.default)
+	}
+
+	/// Websocket endpoint, derived from the default rpc url when none is configured
+	pub fn ws_url(&self) -> String {
+		if let Some(url) = self.routes.get(&OperationClass::Subscribe).or(self.default_ws.as_ref()) {
+			return url.clone();
+		}
+		self.default.replacen("https://", "wss://", 1).replacen("http://", "ws://", 1)
+	}
+}
+
+/// One rpc client per distinct endpoint in an EndpointConfig, shared by the classes routed to it
+pub struct EndpointPool {
+	pub config: EndpointConfig,
+	clients: HashMap<String, RpcClient>,
+}
+
+impl EndpointPool {
+	pub fn new(config: EndpointConfig) -> Self {
+		let mut clients = HashMap::new();
+		for url in std::iter::once(&config.default).chain(config.routes.iter().filter(|(class, _)| **class != OperationClass::Subscribe).map(|(_, url)| url)) {
+			clients.entry(url.clone()).or_insert_with(|| {
+				RpcClient::new_with_timeout_and_commitment(url.clone(), Duration::from_secs(120), CommitmentConfig::confirmed())
+			});
+		}
+		Self { config, clients }
+	}
+
+	pub fn client(&self, class: OperationClass) -> &RpcClient {
+		self.clients.get(self.config.url(class)).unwrap_or(&self.clients[&self.config.default])
+	}
+
+	pub fn ws_url(&self) -> String {
+		self.config.ws_url()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::endpoints::{EndpointConfig, OperationClass, TpuConfig};
+
+	#[test]
+	fn routes_classes_and_falls_back_to_default() {
+		let config: EndpointConfig = serde_json::from_str(r#"{"default": "https://rpc.example", "routes": {"scan": "https://archive.example"}}"#).unwrap();
+		assert_eq!(config.url(OperationClass::Scan), "https://archive.example");
+		assert_eq!(config.url(OperationClass::Submit), "https://rpc.example");
+		assert_eq!(config.ws_url(), "wss://rpc.example");
+		let config = config.with_route(OperationClass::Subscribe, "wss://ws.example");
+		assert_eq!(config.ws_url(), "wss://ws.example");
+		assert_eq!(config.tpu, TpuConfig::default());
+		let config: EndpointConfig = serde_json::from_str(r#"{"default": "https://rpc.example", "tpu": {"enabled": true, "fanout_slots": 4}}"#).unwrap();
+		assert_eq!(config.tpu, TpuConfig { enabled: true, fanout_slots: 4, ws_url: None });
+	}
+}
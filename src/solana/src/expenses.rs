@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+
+/// Base fee the cluster charges per signature
+pub const LAMPORTS_PER_SIGNATURE: u64 = 5_000;
+
+/// Lamports a transaction cost the fee payer
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, PartialEq)]
+pub struct TxExpense {
+	pub fee: u64,
+	/// Part of `fee` above the base signature fee, paid for compute unit price
+	pub priority_fee: u64,
+	/// Lamports moved into accounts the transaction created
+	pub rent: u64,
+}
+
+impl TxExpense {
+	pub fn total(&self) -> u64 {
+		self.fee + self.rent
+	}
+}
+
+/// Expense from a transaction's status meta. Any account that held no lamports before the
+/// transaction and some after was created by it, its balance is the rent paid
+pub fn tx_expense(fee: u64, signatures: usize, pre_balances: &[u64], post_balances: &[u64]) -> TxExpense {
+	let rent = pre_balances.iter().zip(post_balances)
+		  .filter(|(pre, post)| **pre == 0 && **post > 0)
+		  .map(|(_, post)| *post)
+		  .sum();
+	TxExpense {
+		fee,
+		priority_fee: fee.saturating_sub(LAMPORTS_PER_SIGNATURE * signatures as u64),
+		rent,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::expenses::{tx_expense, TxExpense};
+
+	#[test]
+	fn splits_priority_fee_and_rent() {
+		let expense = tx_expense(15_000, 1, &[1_000_000_000, 0, 500], &[999_982_960, 2_040, 500]);
+		assert_eq!(expense, TxExpense { fee: 15_000, priority_fee: 10_000, rent: 2_040 });
+		assert_eq!(expense.total(), 17_040);
+	}
+}
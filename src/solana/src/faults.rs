@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde_json::Value;
+use solana_client::client_error::Result as ClientResult;
+use solana_client::mock_sender::{MockSender, Mocks};
+use solana_client::rpc_client::{RpcClient, RpcClientConfig};
+use solana_client::rpc_request::{RpcError, RpcRequest, RpcResponseErrorData};
+use solana_client::rpc_sender::{RpcSender, RpcTransportStats};
+use solana_sdk::commitment_config::CommitmentConfig;
+
+/// Code the rpc answers sendTransaction with once the transaction's blockhash expired
+pub const BLOCKHASH_NOT_FOUND: i64 = -32002;
+
+/// Failures injected into rpc calls, seeded so test runs are reproducible
+#[derive(Clone, Debug, Default)]
+pub struct FaultConfig {
+	/// Chance a call fails with a transport error
+	pub drop_rate: f64,
+	/// Added to every call
+	pub latency: Duration,
+	/// Chance a call returns the previous response to the same request
+	pub stale_rate: f64,
+	/// Number of sendTransaction calls answered with BLOCKHASH_NOT_FOUND before sends go through
+	pub expired_blockhash_sends: usize,
+	/// Only these methods (e.g. "sendTransaction") see faults, all of them when empty
+	pub methods: Vec<String>,
+	pub seed: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FaultStats {
+	pub calls: usize,
+	pub dropped: usize,
+	pub stale: usize,
+	pub expired_blockhashes: usize,
+}
+
+struct FaultState {
+	rng: StdRng,
+	last_responses: HashMap<String, Value>,
+	expired_blockhash_sends: usize,
+	stats: FaultStats,
+}
+
+/// RpcSender over MockSender that drops, delays, replays stale responses and expires blockhashes,
+/// so retry logic can be exercised without mainnet
+pub struct FaultySender {
+	inner: MockSender,
+	config: FaultConfig,
+	state: Mutex<FaultState>,
+}
+
+impl FaultySender {
+	pub fn new(config: FaultConfig, mocks: Mocks) -> Self {
+		Self {
+			inner: MockSender::new_with_mocks("succeeds", mocks),
+			state: Mutex::new(FaultState {
+				rng: StdRng::seed_from_u64(config.seed),
+				last_responses: HashMap::new(),
+				expired_blockhash_sends: config.expired_blockhash_sends,
+				stats: FaultStats::default(),
+			}),
+			config,
+		}
+	}
+
+	pub fn stats(&self) -> FaultStats {
+		self.state.lock().unwrap().stats.clone()
+	}
+
+	fn applies_to(&self, method: &str) -> bool {
+		self.config.methods.is_empty() || self.config.methods.iter().any(|m| m == method)
+	}
+}
+
+/// A blocking RpcClient talking to a FaultySender
+pub fn faulty_rpc_client(config: FaultConfig, mocks: Mocks) -> RpcClient {
+	RpcClient::new_sender(FaultySender::new(config, mocks), RpcClientConfig::with_commitment(CommitmentConfig::confirmed()))
+}
+
+#[async_trait]
+impl RpcSender for FaultySender {
+	async fn send(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+		let method = request.to_string();
+		let key = format!("{}{}", method, params);
+		let faulty = self.applies_to(&method);
+		if faulty && !self.config.latency.is_zero() {
+			std::thread::sleep(self.config.latency);
+		}
+		{
+			let mut state = self.state.lock().unwrap();
+			state.stats.calls += 1;
+			if faulty {
+				if state.rng.gen_bool(self.config.drop_rate.clamp(0.0, 1.0)) {
+					state.stats.dropped += 1;
+					return Err(RpcError::RpcRequestError(format!("injected drop of {}", method)).into());
+				}
+				if method == "sendTransaction" && state.expired_blockhash_sends > 0 {
+					state.expired_blockhash_sends -= 1;
+					state.stats.expired_blockhashes += 1;
+					return Err(RpcError::RpcResponseError {
+						code: BLOCKHASH_NOT_FOUND,
+						message: "Transaction simulation failed: Blockhash not found".to_string(),
+						data: RpcResponseErrorData::Empty,
+					}.into());
+				}
+				if state.rng.gen_bool(self.config.stale_rate.clamp(0.0, 1.0)) {
+					if let Some(stale) = state.last_responses.get(&key).cloned() {
+						state.stats.stale += 1;
+						return Ok(stale);
+					}
+				}
+			}
+		}
+		let response = self.inner.send(request, params).await?;
+		self.state.lock().unwrap().last_responses.insert(key, response.clone());
+		Ok(response)
+	}
+
+	fn get_transport_stats(&self) -> RpcTransportStats {
+		self.inner.get_transport_stats()
+	}
+
+	fn url(&self) -> String {
+		"faulty".to_string()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashMap;
+	use std::time::{Duration, Instant};
+	use serde_json::json;
+	use solana_client::rpc_request::RpcRequest;
+	use solana_program::pubkey::Pubkey;
+	use solana_sdk::signature::{Keypair, Signer};
+	use solana_sdk::system_instruction;
+	use solana_sdk::transaction::Transaction;
+	use crate::cache::AccountCache;
+	use crate::connection::SolanaConnection;
+	use crate::faults::{faulty_rpc_client, FaultConfig};
+
+	fn transfer(payer: &Keypair) -> Transaction {
+		let instruction = system_instruction::transfer(&payer.pubkey(), &Pubkey::new_unique(), 1);
+		Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()))
+	}
+
+	#[test]
+	fn try_tx_once_survives_drops_and_expired_blockhashes() {
+		let config = FaultConfig {
+			drop_rate: 0.3,
+			expired_blockhash_sends: 2,
+			methods: vec!["sendTransaction".to_string()],
+			seed: 7,
+			..Default::default()
+		};
+		let connection = SolanaConnection::from_rpc_client(faulty_rpc_client(config, HashMap::new()));
+		let payer = Keypair::new();
+		let signature = connection.try_tx_once(transfer(&payer), &payer).unwrap();
+		assert!(!signature.is_empty());
+	}
+
+	#[test]
+	fn try_tx_once_errors_instead_of_panicking_without_blockhash() {
+		let config = FaultConfig { drop_rate: 1.0, methods: vec!["getLatestBlockhash".to_string()], ..Default::default() };
+		let connection = SolanaConnection::from_rpc_client(faulty_rpc_client(config, HashMap::new()));
+		let payer = Keypair::new();
+		assert!(connection.try_tx_once(transfer(&payer), &payer).is_err());
+	}
+
+	#[test]
+	fn replays_stale_responses() {
+		let mut mocks = HashMap::new();
+		mocks.insert(RpcRequest::GetSlot, json!(5));
+		let fresh = faulty_rpc_client(FaultConfig::default(), mocks.clone());
+		assert_eq!(fresh.get_slot().unwrap(), 5);
+		assert_eq!(fresh.get_slot().unwrap(), 0);
+		let stale = faulty_rpc_client(FaultConfig { stale_rate: 1.0, ..Default::default() }, mocks);
+		assert_eq!(stale.get_slot().unwrap(), 5);
+		assert_eq!(stale.get_slot().unwrap(), 5);
+	}
+
+	#[test]
+	fn cache_fetch_fails_cleanly_under_drops_and_latency() {
+		let rpc_client = faulty_rpc_client(FaultConfig { drop_rate: 1.0, latency: Duration::from_millis(50), ..Default::default() }, HashMap::new());
+		let cache = AccountCache::new(Duration::from_secs(5));
+		let started = Instant::now();
+		assert!(cache.get_or_fetch(&rpc_client, &Pubkey::new_unique()).is_err());
+		assert!(started.elapsed() >= Duration::from_millis(50));
+	}
+}
@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use futures::{SinkExt, StreamExt};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::account::Account;
+use yellowstone_grpc_client::GeyserGrpcClient;
+use yellowstone_grpc_proto::geyser::subscribe_update::UpdateOneof;
+use yellowstone_grpc_proto::geyser::{CommitmentLevel, SubscribeRequest, SubscribeRequestFilterAccounts};
+
+use crate::subscription::{AccountUpdate, UpdateSource};
+
+/// Last (slot, write_version) seen per account, drops the replays and out of order
+/// updates a resubscribe delivers
+#[derive(Default)]
+pub struct WriteVersions {
+	latest: HashMap<Pubkey, (u64, u64)>,
+}
+
+impl WriteVersions {
+	pub fn is_newer(&mut self, pubkey: Pubkey, slot: u64, write_version: u64) -> bool {
+		match self.latest.get(&pubkey) {
+			Some(latest) if *latest >= (slot, write_version) => false,
+			_ => {
+				self.latest.insert(pubkey, (slot, write_version));
+				true
+			}
+		}
+	}
+}
+
+/// Every account update of the accounts owned by `owners` from a Geyser (Yellowstone) gRPC
+/// endpoint, one stream for the whole program instead of a websocket per account.
+/// Reconnects with a backoff when the stream ends
+pub struct GeyserSubscription {
+	pub endpoint: String,
+	pub x_token: Option<String>,
+	pub owners: Vec<Pubkey>,
+	pub commitment: CommitmentLevel,
+	pub reconnect_interval: Duration,
+}
+
+impl GeyserSubscription {
+	pub fn new(endpoint: &str, x_token: Option<String>, owners: Vec<Pubkey>) -> Self {
+		Self {
+			endpoint: endpoint.to_string(),
+			x_token,
+			owners,
+			commitment: CommitmentLevel::Confirmed,
+			reconnect_interval: Duration::from_secs(1),
+		}
+	}
+
+	/// Streams updates until the receiver is dropped
+	pub fn start(self) -> (JoinHandle<()>, Receiver<AccountUpdate>) {
+		let (sender, receiver) = channel();
+		let handle = std::thread::spawn(move || {
+			let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+			let mut write_versions = WriteVersions::default();
+			loop {
+				match runtime.block_on(self.run(&sender, &mut write_versions)) {
+					Ok(false) => return,
+					Ok(true) => eprintln!("[-] Geyser stream {} ended, reconnecting...", self.endpoint),
+					Err(e) => eprintln!("[-] Geyser stream {} failed {}, reconnecting...", self.endpoint, e),
+				}
+				std::thread::sleep(self.reconnect_interval);
+			}
+		});
+		(handle, receiver)
+	}
+
+	fn request(&self) -> SubscribeRequest {
+		let mut accounts = HashMap::new();
+		accounts.insert("mangol".to_string(), SubscribeRequestFilterAccounts {
+			account: vec![],
+			owner: self.owners.iter().map(|owner| owner.to_string()).collect(),
+			filters: vec![],
+		});
+		SubscribeRequest {
+			accounts,
+			commitment: Some(self.commitment as i32),
+			..Default::default()
+		}
+	}
+
+	/// Returns false once nobody listens anymore
+	async fn run(&self, sender: &Sender<AccountUpdate>, write_versions: &mut WriteVersions) -> Result<bool, String> {
+		let mut client = GeyserGrpcClient::connect(self.endpoint.clone(), self.x_token.clone(), None).map_err(|e| e.to_string())?;
+		let (mut subscribe_tx, mut stream) = client.subscribe().await.map_err(|e| e.to_string())?;
+		subscribe_tx.send(self.request()).await.map_err(|e| e.to_string())?;
+		while let Some(message) = stream.next().await {
+			let update = match message.map_err(|e| e.to_string())?.update_oneof {
+				Some(UpdateOneof::Account(update)) => update,
+				_ => continue
+			};
+			let info = match update.account {
+				Some(info) => info,
+				None => continue
+			};
+			let (pubkey, owner) = match (Pubkey::try_from(info.pubkey.as_slice()), Pubkey::try_from(info.owner.as_slice())) {
+				(Ok(pubkey), Ok(owner)) => (pubkey, owner),
+				_ => continue
+			};
+			if !write_versions.is_newer(pubkey, update.slot, info.write_version) {
+				continue;
+			}
+			let account = Account {
+				lamports: info.lamports,
+				data: info.data,
+				owner,
+				executable: info.executable,
+				rent_epoch: info.rent_epoch,
+			};
+			if sender.send(AccountUpdate { pubkey, slot: update.slot, account, source: UpdateSource::Geyser }).is_err() {
+				return Ok(false);
+			}
+		}
+		Ok(true)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use solana_program::pubkey::Pubkey;
+	use crate::geyser::WriteVersions;
+
+	#[test]
+	fn drops_replayed_and_stale_updates() {
+		let mut write_versions = WriteVersions::default();
+		let account = Pubkey::new_unique();
+		assert!(write_versions.is_newer(account, 10, 5));
+		assert!(!write_versions.is_newer(account, 10, 5));
+		assert!(!write_versions.is_newer(account, 9, 8));
+		assert!(write_versions.is_newer(account, 10, 6));
+		assert!(write_versions.is_newer(Pubkey::new_unique(), 1, 0));
+	}
+}
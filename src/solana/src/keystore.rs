@@ -0,0 +1,125 @@
+use std::path::Path;
+
+use argon2::Argon2;
+use chacha20poly1305::aead::{Aead, NewAead};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use solana_sdk::signature::Keypair;
+use mangol_common::errors::{MangolError, MangolResult};
+
+/// Env var checked for the passphrase before prompting on the terminal
+pub const PASSPHRASE_ENV: &str = "MANGOL_KEY_PASSPHRASE";
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+#[derive(Serialize, Deserialize, Debug)]
+struct EncryptedKeypair {
+	version: u8,
+	kdf: String,
+	salt: String,
+	nonce: String,
+	ciphertext: String,
+}
+
+fn keystore_error<E: ToString>(e: E) -> MangolError {
+	MangolError::KeyStoreError(e.to_string())
+}
+
+/// Loads and stores signer keypairs.
+///
+/// Supported formats, detected from the file contents:
+/// - encrypted json written by `KeyStore::encrypt` (argon2id + xchacha20poly1305)
+/// - Solana CLI json keypair (`[u8; 64]`)
+/// - legacy plaintext base58 `key.txt`
+pub struct KeyStore;
+
+impl KeyStore {
+	pub fn load<P: AsRef<Path>>(path: P) -> MangolResult<Keypair> {
+		let contents = std::fs::read_to_string(&path)?;
+		let contents = contents.trim();
+		if let Ok(encrypted) = serde_json::from_str::<EncryptedKeypair>(contents) {
+			let passphrase = Self::passphrase()?;
+			Self::decrypt(&encrypted, &passphrase)
+		} else if let Ok(bytes) = serde_json::from_str::<Vec<u8>>(contents) {
+			Keypair::from_bytes(&bytes).map_err(keystore_error)
+		} else {
+			eprintln!("[?] Loading plaintext key from {}, consider encrypting it", path.as_ref().display());
+			let bytes = bs58::decode(contents).into_vec().map_err(|e| MangolError::KeyStoreError(format!("{} is no keystore format {}", path.as_ref().display(), e)))?;
+			Keypair::from_bytes(&bytes).map_err(keystore_error)
+		}
+	}
+
+	/// Writes `keypair` to `path` encrypted with `passphrase`
+	pub fn encrypt<P: AsRef<Path>>(keypair: &Keypair, passphrase: &str, path: P) -> MangolResult<()> {
+		let mut salt = [0u8; SALT_LEN];
+		let mut nonce = [0u8; NONCE_LEN];
+		OsRng.fill_bytes(&mut salt);
+		OsRng.fill_bytes(&mut nonce);
+		let cipher = XChaCha20Poly1305::new(&Self::derive_key(passphrase, &salt)?);
+		let ciphertext = cipher.encrypt(XNonce::from_slice(&nonce), keypair.to_bytes().as_ref()).map_err(keystore_error)?;
+		let encrypted = EncryptedKeypair {
+			version: 1,
+			kdf: "argon2id".to_string(),
+			salt: base64::encode(salt),
+			nonce: base64::encode(nonce),
+			ciphertext: base64::encode(ciphertext),
+		};
+		std::fs::write(path, serde_json::to_string_pretty(&encrypted).map_err(keystore_error)?)?;
+		Ok(())
+	}
+
+	fn decrypt(encrypted: &EncryptedKeypair, passphrase: &str) -> MangolResult<Keypair> {
+		let salt = base64::decode(&encrypted.salt).map_err(keystore_error)?;
+		let nonce = base64::decode(&encrypted.nonce).map_err(keystore_error)?;
+		let ciphertext = base64::decode(&encrypted.ciphertext).map_err(keystore_error)?;
+		let cipher = XChaCha20Poly1305::new(&Self::derive_key(passphrase, &salt)?);
+		let bytes = cipher.decrypt(XNonce::from_slice(&nonce), ciphertext.as_ref())
+			  .map_err(|_| MangolError::KeyStoreError("wrong passphrase or corrupted keystore".to_string()))?;
+		Keypair::from_bytes(&bytes).map_err(keystore_error)
+	}
+
+	fn derive_key(passphrase: &str, salt: &[u8]) -> MangolResult<Key> {
+		let mut key = [0u8; 32];
+		Argon2::default().hash_password_into(passphrase.as_bytes(), salt, &mut key).map_err(keystore_error)?;
+		Ok(*Key::from_slice(&key))
+	}
+
+	fn passphrase() -> MangolResult<String> {
+		if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+			return Ok(passphrase);
+		}
+		rpassword::prompt_password("Keystore passphrase: ").map_err(keystore_error)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use solana_sdk::signature::{Keypair, Signer};
+	use crate::keystore::{EncryptedKeypair, KeyStore};
+
+	#[test]
+	fn encrypted_keystore_roundtrip() {
+		let keypair = Keypair::new();
+		let path = std::env::temp_dir().join(format!("mangol-keystore-{}.json", keypair.pubkey()));
+		KeyStore::encrypt(&keypair, "hunter2", &path).unwrap();
+		let encrypted: EncryptedKeypair = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+		std::fs::remove_file(&path).unwrap();
+		assert_eq!(KeyStore::decrypt(&encrypted, "hunter2").unwrap().pubkey(), keypair.pubkey());
+		assert!(KeyStore::decrypt(&encrypted, "hunter3").is_err());
+	}
+
+	#[test]
+	fn rejects_unknown_key_files() {
+		let keypair = Keypair::new();
+		let path = std::env::temp_dir().join(format!("mangol-keystore-{}.txt", keypair.pubkey()));
+		std::fs::write(&path, keypair.to_base58_string()).unwrap();
+		assert_eq!(KeyStore::load(&path).unwrap().pubkey(), keypair.pubkey());
+		// an encrypted keystore with broken json falls through to base58
+		std::fs::write(&path, "{\"version\": 1, \"kdf\": \"argon2id\",}").unwrap();
+		assert!(KeyStore::load(&path).is_err());
+		std::fs::remove_file(&path).unwrap();
+	}
+}
@@ -5,8 +5,26 @@ use solana_program::program_pack::Pack;
 use solana_program::pubkey::Pubkey;
 use solana_sdk::account::Account;
 use serde::{Serialize, Deserialize};
-use mangol_common::errors::{MangolResult, SolanaError};
+use mangol_common::errors::{MangolError, MangolResult, SolanaError};
 pub mod connection;
+pub mod swap;
+pub mod keystore;
+pub mod network;
+pub mod subscription;
+pub mod cache;
+pub mod scan;
+pub mod expenses;
+pub mod audit;
+pub mod endpoints;
+pub mod cluster_time;
+pub mod lock_file;
+pub mod payer_lock;
+pub mod consistency;
+pub mod disk_cache;
+#[cfg(feature = "geyser")]
+pub mod geyser;
+#[cfg(any(test, feature = "fault-injection"))]
+pub mod faults;
 pub struct TokenMint {
 	pub decimals: u8,
 	pub address: Pubkey,
@@ -16,7 +34,7 @@ pub struct TokenMint {
 pub struct TokenAccount {
 
 }
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct Token {
 	chainId: u64,
 	address: String,
@@ -31,6 +49,20 @@ impl TokenMint {
 		
 		let resp = reqwest::blocking::get("https://cache.jup.ag/tokens").unwrap()
 		                                                                .json::<Vec<Token>>().unwrap();
+		Self::from_token_list(account, resp)
+	}
+	
+	/// Like from_pubkey, the token list is only downloaded once it expired from `disk_cache`
+	pub fn from_pubkey_cached(account: &Pubkey, disk_cache: &disk_cache::DiskCache) -> MangolResult<TokenMint> {
+		let resp = disk_cache.get_or_fetch("token-list-jup", Some(disk_cache::TOKEN_LIST_TTL), || {
+			reqwest::blocking::get("https://cache.jup.ag/tokens")
+				  .and_then(|response| response.json::<Vec<Token>>())
+				  .map_err(|e| MangolError::SerializationError(e.to_string()))
+		})?;
+		Self::from_token_list(account, resp)
+	}
+	
+	fn from_token_list(account: &Pubkey, resp: Vec<Token>) -> MangolResult<TokenMint> {
 		let token = resp.into_iter().find(|t| t.address == account.to_string());
 		if let Some(token_mint) = token {
 			Ok(TokenMint {
@@ -0,0 +1,110 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use mangol_common::errors::MangolResult;
+
+/// A file created exclusively as a lock between processes, removed when dropped. The volume has
+/// to honour exclusive creates across hosts, local filesystems and NFSv3 and later do
+#[derive(Debug)]
+pub struct LockFile {
+	path: PathBuf,
+}
+
+fn is_stale(path: &Path, stale_after: Duration) -> bool {
+	std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+		  .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+		  .map(|age| age >= stale_after)
+		  .unwrap_or(false)
+}
+
+/// None when `path` already exists
+fn create(path: &Path) -> MangolResult<Option<LockFile>> {
+	match OpenOptions::new().write(true).create_new(true).open(path) {
+		Ok(mut file) => {
+			writeln!(file, "{}", std::process::id())?;
+			Ok(Some(LockFile { path: path.to_path_buf() }))
+		}
+		Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Ok(None),
+		Err(e) => Err(e.into())
+	}
+}
+
+/// Removes the lock at `path` if it is stale, under `<path>.takeover` so of two processes finding
+/// it stale only one removes it, the other finds the lock created in its place fresh. Whether it
+/// was removed
+fn take_over(path: &Path, stale_after: Duration) -> MangolResult<bool> {
+	let takeover_path = PathBuf::from(format!("{}.takeover", path.display()));
+	let _takeover = match create(&takeover_path)? {
+		Some(takeover) => takeover,
+		None => {
+			// held for a few syscalls only, a stale one was left by a process that died taking over
+			if is_stale(&takeover_path, stale_after) {
+				let _ = std::fs::remove_file(&takeover_path);
+			}
+			return Ok(false);
+		}
+	};
+	if !is_stale(path, stale_after) {
+		return Ok(false);
+	}
+	eprintln!("[-] Taking over stale lock {:?}", path);
+	let _ = std::fs::remove_file(path);
+	Ok(true)
+}
+
+impl LockFile {
+	/// Creates `path`, waiting up to `wait` while another process holds it, None if it still does
+	/// then. A lock older than `stale_after` was left by a process that died holding it and is taken over
+	pub fn acquire(path: &Path, wait: Duration, stale_after: Duration) -> MangolResult<Option<Self>> {
+		let deadline = Instant::now() + wait;
+		loop {
+			if let Some(lock) = create(path)? {
+				return Ok(Some(lock));
+			}
+			if take_over(path, stale_after)? {
+				continue;
+			}
+			if Instant::now() >= deadline {
+				return Ok(None);
+			}
+			std::thread::sleep(Duration::from_millis(20));
+		}
+	}
+}
+
+impl Drop for LockFile {
+	fn drop(&mut self) {
+		let _ = std::fs::remove_file(&self.path);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::path::PathBuf;
+	use std::time::Duration;
+	use crate::lock_file::{take_over, LockFile};
+
+	#[test]
+	fn takes_over_only_stale_locks() {
+		let dir = std::env::temp_dir().join(format!("mangol-lock-file-{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let path = dir.join("test.lock");
+		let held = LockFile::acquire(&path, Duration::ZERO, Duration::from_secs(60)).unwrap().unwrap();
+		assert!(LockFile::acquire(&path, Duration::ZERO, Duration::from_secs(60)).unwrap().is_none());
+		// a waiter that found the lock stale before it was replaced leaves the fresh one alone
+		assert!(!take_over(&path, Duration::from_secs(60)).unwrap());
+		assert!(path.exists());
+		// so does one finding another process taking over, a takeover left by a crash is cleared
+		let takeover = PathBuf::from(format!("{}.takeover", path.display()));
+		std::fs::write(&takeover, "").unwrap();
+		assert!(!take_over(&path, Duration::ZERO).unwrap());
+		assert!(path.exists());
+		assert!(!takeover.exists());
+		// a crashed holder never releases
+		std::mem::forget(held);
+		let _taken = LockFile::acquire(&path, Duration::ZERO, Duration::ZERO).unwrap().unwrap();
+		std::fs::remove_dir_all(dir).unwrap();
+	}
+}
@@ -0,0 +1,178 @@
+use std::sync::{Arc, RwLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum NetworkStatus {
+	Healthy,
+	/// Slow slots, low tps or slow rpc: widen, pay more priority fees, avoid new risk
+	Degraded,
+	/// Slots stopped advancing or the rpc stopped answering: stop trading
+	Down,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct NetworkThresholds {
+	/// Below this many slots per second the cluster is degraded, mainnet targets ~2.5
+	pub min_slot_rate: f64,
+	pub min_tps: f64,
+	pub max_rpc_latency: Duration,
+	/// No slot progress for this long counts as an outage
+	pub max_stall: Duration,
+}
+
+impl Default for NetworkThresholds {
+	fn default() -> Self {
+		Self {
+			min_slot_rate: 1.5,
+			min_tps: 1_000.0,
+			max_rpc_latency: Duration::from_secs(2),
+			max_stall: Duration::from_secs(30),
+		}
+	}
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct NetworkSnapshot {
+	pub slot: u64,
+	pub slot_rate: f64,
+	pub tps: f64,
+	pub rpc_latency: Duration,
+	/// Time since the slot last advanced
+	pub stalled_for: Duration,
+	/// Consecutive polls in which the rpc returned an error
+	pub failed_polls: u32,
+}
+
+impl NetworkSnapshot {
+	pub fn status(&self, thresholds: &NetworkThresholds) -> NetworkStatus {
+		if self.failed_polls >= 3 || self.stalled_for >= thresholds.max_stall {
+			NetworkStatus::Down
+		} else if self.slot_rate < thresholds.min_slot_rate
+			  || self.tps < thresholds.min_tps
+			  || self.rpc_latency > thresholds.max_rpc_latency {
+			NetworkStatus::Degraded
+		} else {
+			NetworkStatus::Healthy
+		}
+	}
+}
+
+/// Polls slot progression, recent tps and rpc latency in the background.
+/// Clones share the same snapshot so strategies can hold their own handle
+#[derive(Clone)]
+pub struct NetworkMonitor {
+	pub rpc_addr: String,
+	pub poll_interval: Duration,
+	pub thresholds: NetworkThresholds,
+	snapshot: Arc<RwLock<Option<NetworkSnapshot>>>,
+	last_ok: Arc<RwLock<Option<Instant>>>,
+}
+
+impl NetworkMonitor {
+	pub fn new(rpc_addr: &str) -> Self {
+		Self {
+			rpc_addr: rpc_addr.to_string(),
+			poll_interval: Duration::from_secs(5),
+			thresholds: NetworkThresholds::default(),
+			snapshot: Arc::new(RwLock::new(None)),
+			last_ok: Arc::new(RwLock::new(None)),
+		}
+	}
+
+	/// When the rpc last answered a poll
+	pub fn last_ok(&self) -> Option<Instant> {
+		*self.last_ok.read().unwrap()
+	}
+
+	pub fn snapshot(&self) -> Option<NetworkSnapshot> {
+		*self.snapshot.read().unwrap()
+	}
+
+	/// Healthy until the first poll completes
+	pub fn status(&self) -> NetworkStatus {
+		self.snapshot().map(|snapshot| snapshot.status(&self.thresholds)).unwrap_or(NetworkStatus::Healthy)
+	}
+
+	pub fn start(&self) -> JoinHandle<()> {
+		let monitor = self.clone();
+		std::thread::spawn(move || {
+			let rpc_client = RpcClient::new_with_timeout_and_commitment(&monitor.rpc_addr, Duration::from_secs(30), CommitmentConfig::confirmed());
+			let mut last_progress: Option<(Instant, u64)> = None;
+			let mut previous_status = NetworkStatus::Healthy;
+			loop {
+				monitor.poll(&rpc_client, &mut last_progress);
+				let status = monitor.status();
+				if status != previous_status {
+					println!("[?] Network status changed {:?} -> {:?} {:?}", previous_status, status, monitor.snapshot());
+					previous_status = status;
+				}
+				std::thread::sleep(monitor.poll_interval);
+			}
+		})
+	}
+
+	fn poll(&self, rpc_client: &RpcClient, last_progress: &mut Option<(Instant, u64)>) {
+		let mut snapshot = self.snapshot().unwrap_or(NetworkSnapshot {
+			slot: 0,
+			slot_rate: f64::MAX,
+			tps: f64::MAX,
+			rpc_latency: Duration::ZERO,
+			stalled_for: Duration::ZERO,
+			failed_polls: 0,
+		});
+		let request_start = Instant::now();
+		let slot = match rpc_client.get_slot() {
+			Ok(slot) => slot,
+			Err(e) => {
+				eprintln!("[-] Network monitor failed to fetch slot {:?}", e);
+				snapshot.failed_polls += 1;
+				*self.snapshot.write().unwrap() = Some(snapshot);
+				return;
+			}
+		};
+		snapshot.rpc_latency = request_start.elapsed();
+		snapshot.failed_polls = 0;
+		let now = Instant::now();
+		*self.last_ok.write().unwrap() = Some(now);
+		match *last_progress {
+			Some((progress_at, progress_slot)) if slot > progress_slot => {
+				snapshot.slot_rate = (slot - progress_slot) as f64 / now.duration_since(progress_at).as_secs_f64();
+				snapshot.stalled_for = Duration::ZERO;
+				*last_progress = Some((now, slot));
+			}
+			Some((progress_at, _)) => {
+				snapshot.stalled_for = now.duration_since(progress_at);
+				snapshot.slot_rate = 0.0;
+			}
+			None => *last_progress = Some((now, slot))
+		}
+		snapshot.slot = slot;
+		if let Ok(samples) = rpc_client.get_recent_performance_samples(Some(1)) {
+			if let Some(sample) = samples.first() {
+				snapshot.tps = sample.num_transactions as f64 / sample.sample_period_secs.max(1) as f64;
+			}
+		}
+		*self.snapshot.write().unwrap() = Some(snapshot);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+	use crate::network::{NetworkSnapshot, NetworkStatus, NetworkThresholds};
+
+	#[test]
+	fn classifies_snapshots() {
+		let thresholds = NetworkThresholds::default();
+		let healthy = NetworkSnapshot { slot: 1, slot_rate: 2.4, tps: 2_500.0, rpc_latency: Duration::from_millis(200), stalled_for: Duration::ZERO, failed_polls: 0 };
+		assert_eq!(healthy.status(&thresholds), NetworkStatus::Healthy);
+		assert_eq!(NetworkSnapshot { slot_rate: 0.8, ..healthy }.status(&thresholds), NetworkStatus::Degraded);
+		assert_eq!(NetworkSnapshot { rpc_latency: Duration::from_secs(5), ..healthy }.status(&thresholds), NetworkStatus::Degraded);
+		assert_eq!(NetworkSnapshot { stalled_for: Duration::from_secs(60), ..healthy }.status(&thresholds), NetworkStatus::Down);
+		assert_eq!(NetworkSnapshot { failed_polls: 3, ..healthy }.status(&thresholds), NetworkStatus::Down);
+	}
+}
@@ -0,0 +1,73 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use mangol_common::errors::{MangolError, MangolResult};
+use solana_program::pubkey::Pubkey;
+
+use crate::lock_file::LockFile;
+
+/// Serializes signing and sending per fee payer across processes, e.g. a liquidator and a trader
+/// paying from the same wallet, through a lock file per payer in `dir`. A lock older than
+/// `stale_after` is left by a process that died while holding it and is taken over
+#[derive(Clone, Debug)]
+pub struct FeePayerLock {
+	pub dir: PathBuf,
+	/// How long acquire waits for the other process before giving up
+	pub wait: Duration,
+	pub stale_after: Duration,
+}
+
+impl FeePayerLock {
+	pub fn new(dir: PathBuf) -> MangolResult<Self> {
+		std::fs::create_dir_all(&dir)?;
+		Ok(Self { dir, wait: Duration::from_secs(30), stale_after: Duration::from_secs(60) })
+	}
+
+	pub fn with_wait(mut self, wait: Duration) -> Self {
+		self.wait = wait;
+		self
+	}
+
+	pub fn with_stale_after(mut self, stale_after: Duration) -> Self {
+		self.stale_after = stale_after;
+		self
+	}
+
+	fn path(&self, payer: &Pubkey) -> PathBuf {
+		self.dir.join(format!("{}.lock", payer))
+	}
+
+	/// Waits until no other process holds `payer`, the lock is released when the guard is dropped
+	pub fn acquire(&self, payer: &Pubkey) -> MangolResult<FeePayerGuard> {
+		LockFile::acquire(&self.path(payer), self.wait, self.stale_after)?
+			  .map(|lock| FeePayerGuard { _lock: lock })
+			  .ok_or_else(|| MangolError::FeePayerBusy(payer.to_string()))
+	}
+}
+
+/// Holds the payer's lock file until dropped
+pub struct FeePayerGuard {
+	_lock: LockFile,
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+	use solana_program::pubkey::Pubkey;
+	use crate::payer_lock::FeePayerLock;
+
+	#[test]
+	fn one_holder_per_payer_until_stale() {
+		let dir = std::env::temp_dir().join(format!("mangol-payer-lock-{}", std::process::id()));
+		let lock = FeePayerLock::new(dir.clone()).unwrap().with_wait(Duration::ZERO);
+		let payer = Pubkey::new_unique();
+		let guard = lock.acquire(&payer).unwrap();
+		assert!(lock.acquire(&payer).is_err());
+		assert!(lock.acquire(&Pubkey::new_unique()).is_ok());
+		drop(guard);
+		let _held = lock.acquire(&payer).unwrap();
+		// a crashed holder never releases
+		assert!(lock.clone().with_stale_after(Duration::ZERO).acquire(&payer).is_ok());
+		std::fs::remove_dir_all(dir).unwrap();
+	}
+}
@@ -0,0 +1,77 @@
+use std::collections::VecDeque;
+
+use mangol_common::errors::MangolResult;
+use solana_account_decoder::UiDataSliceConfig;
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcProgramAccountsConfig;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::account::Account;
+
+/// getMultipleAccounts accepts at most 100 keys
+pub const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// Streams program accounts in two passes so big scans stay under public rpc limits:
+/// pubkeys first with an empty dataSlice, then bodies `batch_size` at a time with getMultipleAccounts
+pub struct ProgramAccountScan<'a> {
+	rpc_client: &'a RpcClient,
+	pubkeys: Vec<Pubkey>,
+	next: usize,
+	batch_size: usize,
+	batch: VecDeque<(Pubkey, Account)>,
+}
+
+impl<'a> ProgramAccountScan<'a> {
+	pub fn new(rpc_client: &'a RpcClient, program_id: &Pubkey, config: &RpcProgramAccountsConfig, batch_size: usize) -> MangolResult<Self> {
+		let mut keys_config = config.clone();
+		keys_config.account_config.data_slice = Some(UiDataSliceConfig { offset: 0, length: 0 });
+		let pubkeys = rpc_client.get_program_accounts_with_config(program_id, keys_config)?.into_iter().map(|(pubkey, _)| pubkey).collect();
+		Ok(Self {
+			rpc_client,
+			pubkeys,
+			next: 0,
+			batch_size: batch_size.clamp(1, MAX_MULTIPLE_ACCOUNTS),
+			batch: VecDeque::new(),
+		})
+	}
+
+	/// Number of accounts matched by the filters, known after the first pass
+	pub fn len(&self) -> usize {
+		self.pubkeys.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.pubkeys.is_empty()
+	}
+
+	fn hydrate_next_batch(&mut self) -> MangolResult<()> {
+		let end = (self.next + self.batch_size).min(self.pubkeys.len());
+		let pubkeys = &self.pubkeys[self.next..end];
+		let accounts = self.rpc_client.get_multiple_accounts(pubkeys)?;
+		// accounts closed between the two passes come back as None
+		for (pubkey, account) in pubkeys.iter().zip(accounts) {
+			if let Some(account) = account {
+				self.batch.push_back((*pubkey, account));
+			}
+		}
+		self.next = end;
+		Ok(())
+	}
+}
+
+impl<'a> Iterator for ProgramAccountScan<'a> {
+	type Item = MangolResult<(Pubkey, Account)>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		while self.batch.is_empty() {
+			if self.next >= self.pubkeys.len() {
+				return None;
+			}
+			if let Err(e) = self.hydrate_next_batch() {
+				// skip the failed batch so callers can keep going
+				self.next = (self.next + self.batch_size).min(self.pubkeys.len());
+				return Some(Err(e));
+			}
+		}
+		self.batch.pop_front().map(Ok)
+	}
+}
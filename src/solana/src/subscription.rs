@@ -0,0 +1,312 @@
+use std::str::FromStr;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use solana_account_decoder::{UiAccount, UiAccountData, UiAccountEncoding};
+use solana_client::pubsub_client::PubsubClient;
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcAccountInfoConfig;
+use solana_program::pubkey::Pubkey;
+use solana_sdk::account::Account;
+use solana_sdk::commitment_config::CommitmentConfig;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum UpdateSource {
+	Websocket,
+	Polling,
+	Geyser,
+}
+
+#[derive(Clone, Debug)]
+pub struct AccountUpdate {
+	pub pubkey: Pubkey,
+	pub slot: u64,
+	pub account: Account,
+	pub source: UpdateSource,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SubscriptionMode {
+	Websocket,
+	/// Websocket kept failing, poll over rpc until the next upgrade attempt
+	Polling,
+}
+
+/// Websocket until `max_ws_failures` consecutive failures, then polling
+pub fn subscription_mode(consecutive_failures: u32, max_ws_failures: u32) -> SubscriptionMode {
+	if consecutive_failures >= max_ws_failures {
+		SubscriptionMode::Polling
+	} else {
+		SubscriptionMode::Websocket
+	}
+}
+
+/// Decodes base64 account data into `buffer`, reusing its allocation across notifications
+pub fn decode_account_data(data: &UiAccountData, buffer: &mut Vec<u8>) -> bool {
+	buffer.clear();
+	match data {
+		UiAccountData::Binary(blob, UiAccountEncoding::Base64) => base64::decode_config_buf(blob, base64::STANDARD, buffer).is_ok(),
+		_ => false
+	}
+}
+
+/// An account subscription that reconnects on websocket errors and falls back to
+/// interval polling when the websocket keeps failing, so consumers never go blind.
+/// While polling it retries the websocket every `upgrade_interval`
+pub struct ResilientSubscription {
+	pub account: Pubkey,
+	pub rpc_url: String,
+	pub ws_url: String,
+	pub commitment: CommitmentConfig,
+	pub max_ws_failures: u32,
+	pub poll_interval: Duration,
+	pub upgrade_interval: Duration,
+	/// Drops websocket notifications whose data and lamports equal the previous one's, which
+	/// then cost no allocation at all
+	pub skip_unchanged: bool,
+}
+
+impl ResilientSubscription {
+	pub fn new(account: Pubkey, rpc_url: &str, ws_url: &str) -> Self {
+		Self {
+			account,
+			rpc_url: rpc_url.to_string(),
+			ws_url: ws_url.to_string(),
+			commitment: CommitmentConfig::finalized(),
+			max_ws_failures: 5,
+			poll_interval: Duration::from_secs(2),
+			upgrade_interval: Duration::from_secs(60),
+			skip_unchanged: false,
+		}
+	}
+
+	pub fn with_skip_unchanged(mut self, skip_unchanged: bool) -> Self {
+		self.skip_unchanged = skip_unchanged;
+		self
+	}
+
+	/// Streams updates until the receiver is dropped
+	pub fn start(self) -> (JoinHandle<()>, Receiver<AccountUpdate>) {
+		let (sender, receiver) = channel();
+		let handle = std::thread::spawn(move || {
+			let rpc_client = RpcClient::new_with_commitment(self.rpc_url.clone(), self.commitment);
+			let mut failures = 0;
+			let mut last_slot = 0;
+			loop {
+				let keep_going = match subscription_mode(failures, self.max_ws_failures) {
+					SubscriptionMode::Websocket => self.run_websocket(&sender, &mut failures, &mut last_slot),
+					SubscriptionMode::Polling => {
+						eprintln!("[-] Websocket for {} failed {} times, polling every {:?}", self.account, failures, self.poll_interval);
+						let keep_going = self.run_polling(&rpc_client, &sender, &mut last_slot);
+						// one websocket attempt before falling back again
+						failures = self.max_ws_failures - 1;
+						keep_going
+					}
+				};
+				if !keep_going {
+					return;
+				}
+			}
+		});
+		(handle, receiver)
+	}
+
+	/// Returns false once nobody listens anymore
+	fn run_websocket(&self, sender: &Sender<AccountUpdate>, failures: &mut u32, last_slot: &mut u64) -> bool {
+		let config = RpcAccountInfoConfig {
+			encoding: Some(UiAccountEncoding::Base64),
+			data_slice: None,
+			commitment: Some(self.commitment),
+			min_context_slot: None
+		};
+		let (mut subscription, receiver) = match PubsubClient::account_subscribe(&self.ws_url, &self.account, Some(config)) {
+			Ok(subscription) => subscription,
+			Err(e) => {
+				*failures += 1;
+				eprintln!("[-] Failed to subscribe to {} {:?}", self.account, e);
+				std::thread::sleep(Duration::from_secs(1));
+				return true;
+			}
+		};
+		if *failures > 0 {
+			println!("[?] Reconnected websocket for {}", self.account);
+		}
+		// the previous notification's data, swapped with the buffer so neither reallocates
+		let mut buffer = vec![];
+		let mut previous = vec![];
+		let mut previous_lamports = None;
+		loop {
+			match receiver.recv() {
+				Ok(response) => {
+					*failures = 0;
+					if response.context.slot < *last_slot || !decode_account_data(&response.value.data, &mut buffer) {
+						continue;
+					}
+					*last_slot = response.context.slot;
+					if self.skip_unchanged && previous_lamports == Some(response.value.lamports) && previous == buffer {
+						continue;
+					}
+					let account = match to_account(&response.value, &buffer) {
+						Some(account) => account,
+						None => continue
+					};
+					if self.skip_unchanged {
+						std::mem::swap(&mut buffer, &mut previous);
+						previous_lamports = Some(account.lamports);
+					}
+					let update = AccountUpdate { pubkey: self.account, slot: response.context.slot, account, source: UpdateSource::Websocket };
+					if sender.send(update).is_err() {
+						let _ = subscription.shutdown();
+						return false;
+					}
+				}
+				Err(_) => {
+					*failures += 1;
+					eprintln!("[-] Websocket for {} dropped, reconnecting...", self.account);
+					let _ = subscription.shutdown();
+					return true;
+				}
+			}
+		}
+	}
+
+	fn run_polling(&self, rpc_client: &RpcClient, sender: &Sender<AccountUpdate>, last_slot: &mut u64) -> bool {
+		let started = Instant::now();
+		while started.elapsed() < self.upgrade_interval {
+			match rpc_client.get_account_with_commitment(&self.account, self.commitment) {
+				Ok(response) => {
+					if let Some(account) = response.value {
+						if response.context.slot > *last_slot {
+							*last_slot = response.context.slot;
+							let update = AccountUpdate { pubkey: self.account, slot: response.context.slot, account, source: UpdateSource::Polling };
+							if sender.send(update).is_err() {
+								return false;
+							}
+						}
+					}
+				}
+				Err(e) => eprintln!("[-] Polling {} failed {:?}", self.account, e)
+			}
+			std::thread::sleep(self.poll_interval);
+		}
+		true
+	}
+}
+
+fn to_account(ui_account: &UiAccount, data: &[u8]) -> Option<Account> {
+	Some(Account {
+		lamports: ui_account.lamports,
+		data: data.to_vec(),
+		owner: Pubkey::from_str(&ui_account.owner).ok()?,
+		executable: ui_account.executable,
+		rent_epoch: ui_account.rent_epoch,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::{Read, Write};
+	use std::net::TcpListener;
+	use std::sync::atomic::{AtomicU64, Ordering};
+	use std::sync::Arc;
+	use std::time::{Duration, Instant};
+	use solana_account_decoder::{UiAccountData, UiAccountEncoding};
+	use solana_program::pubkey::Pubkey;
+	use crate::subscription::{decode_account_data, subscription_mode, ResilientSubscription, SubscriptionMode, UpdateSource};
+
+	/// Counts connections to a websocket endpoint that closes every one before the handshake
+	fn failing_websocket() -> (String, Arc<AtomicU64>) {
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let url = format!("ws://{}", listener.local_addr().unwrap());
+		let attempts = Arc::new(AtomicU64::new(0));
+		let counted = attempts.clone();
+		std::thread::spawn(move || {
+			for stream in listener.incoming() {
+				counted.fetch_add(1, Ordering::SeqCst);
+				drop(stream);
+			}
+		});
+		(url, attempts)
+	}
+
+	/// Answers every rpc request with the account holding [1, 2, 3], one slot later each time
+	fn polled_rpc() -> String {
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let url = format!("http://{}", listener.local_addr().unwrap());
+		std::thread::spawn(move || {
+			for (slot, stream) in listener.incoming().enumerate() {
+				let mut stream = match stream {
+					Ok(stream) => stream,
+					Err(_) => continue
+				};
+				let mut request = vec![];
+				let mut chunk = [0_u8; 4096];
+				// headers, then as much body as they announce
+				loop {
+					let read = stream.read(&mut chunk).unwrap_or(0);
+					request.extend_from_slice(&chunk[..read]);
+					let text = String::from_utf8_lossy(&request).to_string();
+					let headers_end = match text.find("\r\n\r\n") {
+						Some(headers_end) => headers_end,
+						None if read > 0 => continue,
+						None => break
+					};
+					let content_length = text.lines()
+						  .find_map(|line| line.split_once(':').filter(|(name, _)| name.eq_ignore_ascii_case("content-length")).map(|(_, value)| value.trim().parse::<usize>().unwrap_or(0)))
+						  .unwrap_or(0);
+					if read == 0 || request.len() >= headers_end + 4 + content_length {
+						break;
+					}
+				}
+				let body = format!(
+					r#"{{"jsonrpc":"2.0","result":{{"context":{{"slot":{}}},"value":{{"data":["{}","base64"],"executable":false,"lamports":1,"owner":"11111111111111111111111111111111","rentEpoch":0}}}},"id":1}}"#,
+					slot + 1, base64::encode([1u8, 2, 3])
+				);
+				let _ = write!(stream, "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body);
+			}
+		});
+		url
+	}
+
+	#[test]
+	fn polls_while_the_websocket_fails_and_retries_it() {
+		let (ws_url, ws_attempts) = failing_websocket();
+		let mut subscription = ResilientSubscription::new(Pubkey::new_unique(), &polled_rpc(), &ws_url);
+		subscription.max_ws_failures = 2;
+		subscription.poll_interval = Duration::from_millis(10);
+		subscription.upgrade_interval = Duration::from_millis(200);
+		let (_handle, updates) = subscription.start();
+		let update = updates.recv_timeout(Duration::from_secs(10)).unwrap();
+		assert_eq!(update.source, UpdateSource::Polling);
+		assert_eq!(update.account.data, vec![1, 2, 3]);
+		assert!(ws_attempts.load(Ordering::SeqCst) >= 2);
+		// slots only move forward
+		assert!(updates.recv_timeout(Duration::from_secs(10)).unwrap().slot > update.slot);
+		// after upgrade_interval of polling the websocket is tried again
+		let deadline = Instant::now() + Duration::from_secs(10);
+		while ws_attempts.load(Ordering::SeqCst) < 3 && Instant::now() < deadline {
+			let _ = updates.recv_timeout(Duration::from_millis(100));
+		}
+		assert!(ws_attempts.load(Ordering::SeqCst) >= 3);
+	}
+
+	#[test]
+	fn falls_back_to_polling_after_max_failures() {
+		assert_eq!(subscription_mode(0, 5), SubscriptionMode::Websocket);
+		assert_eq!(subscription_mode(4, 5), SubscriptionMode::Websocket);
+		assert_eq!(subscription_mode(5, 5), SubscriptionMode::Polling);
+	}
+
+	#[test]
+	fn decodes_into_the_same_buffer() {
+		let mut buffer = Vec::with_capacity(64);
+		let capacity = buffer.capacity();
+		assert!(decode_account_data(&UiAccountData::Binary(base64::encode([1u8, 2, 3]), UiAccountEncoding::Base64), &mut buffer));
+		assert_eq!(buffer, vec![1, 2, 3]);
+		assert!(decode_account_data(&UiAccountData::Binary(base64::encode([4u8]), UiAccountEncoding::Base64), &mut buffer));
+		assert_eq!(buffer, vec![4]);
+		assert_eq!(buffer.capacity(), capacity);
+		assert!(!decode_account_data(&UiAccountData::LegacyBinary("11".to_string()), &mut buffer));
+	}
+}
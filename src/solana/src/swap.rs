@@ -0,0 +1,72 @@
+use serde::Deserialize;
+use serde_json::{json, Value};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::transaction::Transaction;
+use mangol_common::errors::{MangolError, MangolResult, SwapServiceError};
+
+use crate::connection::SolanaConnection;
+
+const JUPITER_QUOTE_URL: &str = "https://quote-api.jup.ag/v1/quote";
+const JUPITER_SWAP_URL: &str = "https://quote-api.jup.ag/v1/swap";
+
+#[derive(Deserialize, Debug)]
+struct QuoteResponse {
+	data: Vec<Value>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SwapResponse {
+	setup_transaction: Option<String>,
+	swap_transaction: String,
+	cleanup_transaction: Option<String>,
+}
+
+/// Swaps through the best Jupiter route
+pub struct JupiterSwap<'a> {
+	pub solana_connection: &'a SolanaConnection,
+	pub slippage_percent: f64,
+}
+
+impl<'a> JupiterSwap<'a> {
+	pub fn new(solana_connection: &'a SolanaConnection) -> Self {
+		Self {
+			solana_connection,
+			slippage_percent: 0.5,
+		}
+	}
+
+	/// Returns the best route for swapping `amount` native units of `input_mint`
+	pub fn quote(&self, input_mint: &Pubkey, output_mint: &Pubkey, amount: u64) -> MangolResult<Value> {
+		let url = format!("{}?inputMint={}&outputMint={}&amount={}&slippage={}", JUPITER_QUOTE_URL, input_mint, output_mint, amount, self.slippage_percent);
+		let resp = reqwest::blocking::get(url)
+			  .and_then(|r| r.json::<QuoteResponse>())
+			  .map_err(|e| MangolError::SerializationError(e.to_string()))?;
+		resp.data.into_iter().next().ok_or_else(|| SwapServiceError::MarketNotFound(input_mint.to_string(), output_mint.to_string(), "Jupiter".to_string()).into())
+	}
+
+	/// Swaps `amount` native units of `input_mint` into `output_mint`, returns the signatures of the sent transactions
+	pub fn swap(&self, input_mint: &Pubkey, output_mint: &Pubkey, amount: u64, signer: &Keypair) -> MangolResult<Vec<String>> {
+		let route = self.quote(input_mint, output_mint, amount)?;
+		let body = json!({
+			"route": route,
+			"userPublicKey": signer.pubkey().to_string(),
+			"wrapUnwrapSOL": true
+		});
+		let resp = reqwest::blocking::Client::new()
+			  .post(JUPITER_SWAP_URL)
+			  .json(&body)
+			  .send()
+			  .and_then(|r| r.json::<SwapResponse>())
+			  .map_err(|e| MangolError::SerializationError(e.to_string()))?;
+
+		let mut signatures = vec![];
+		for encoded in [resp.setup_transaction, Some(resp.swap_transaction), resp.cleanup_transaction].into_iter().flatten() {
+			let data = base64::decode(encoded).map_err(|e| MangolError::SerializationError(e.to_string()))?;
+			let transaction: Transaction = bincode::deserialize(&data).map_err(|e| MangolError::SerializationError(e.to_string()))?;
+			signatures.push(self.solana_connection.try_tx_once(transaction, signer)?);
+		}
+		Ok(signatures)
+	}
+}
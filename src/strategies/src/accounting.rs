@@ -0,0 +1,139 @@
+use std::collections::VecDeque;
+use std::ops::Range;
+
+use mangol_mango::types::Side;
+use serde::{Deserialize, Serialize};
+
+use crate::replay::ExecutionRecord;
+
+/// Which open lots a closing fill is matched against when realizing pnl
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CostBasis {
+	/// Against the average price of the position, what `FibStrat::get_average_price` does
+	AverageCost,
+	/// Against the oldest open lots first
+	Fifo,
+}
+
+impl Default for CostBasis {
+	fn default() -> Self {
+		CostBasis::AverageCost
+	}
+}
+
+impl CostBasis {
+	pub fn parse(name: &str) -> Option<Self> {
+		match name.to_ascii_lowercase().as_str() {
+			"average" | "average_cost" | "avg" => Some(CostBasis::AverageCost),
+			"fifo" => Some(CostBasis::Fifo),
+			_ => None
+		}
+	}
+}
+
+/// Open lots of one position and the pnl realized closing them. Quantities are signed, negative
+/// when short, and pnl comes out in price times quantity units
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LotLedger {
+	pub cost_basis: CostBasis,
+	/// (quantity, price) oldest first, all on the same side. Average cost keeps a single lot
+	lots: VecDeque<(f64, f64)>,
+	pub realized: f64,
+}
+
+impl LotLedger {
+	pub fn new(cost_basis: CostBasis) -> Self {
+		Self { cost_basis, lots: VecDeque::new(), realized: 0.0 }
+	}
+
+	pub fn position(&self) -> f64 {
+		self.lots.iter().map(|(quantity, _)| quantity).sum()
+	}
+
+	/// Price of the open position, None when flat
+	pub fn average_price(&self) -> Option<f64> {
+		let position = self.position();
+		(position != 0.0).then(|| self.lots.iter().map(|(quantity, price)| quantity * price).sum::<f64>() / position)
+	}
+
+	/// Applies a fill of signed `quantity` at `price`, returns the pnl it realized
+	pub fn fill(&mut self, quantity: f64, price: f64) -> f64 {
+		let mut remaining = quantity;
+		let mut realized = 0.0;
+		// close opposite lots first, oldest first
+		while remaining != 0.0 {
+			let (lot_quantity, lot_price) = match self.lots.front_mut() {
+				Some(lot) if lot.0.signum() != remaining.signum() => lot,
+				_ => break
+			};
+			let closed = remaining.abs().min(lot_quantity.abs()) * lot_quantity.signum();
+			realized += closed * (price - *lot_price);
+			*lot_quantity -= closed;
+			remaining += closed;
+			if *lot_quantity == 0.0 {
+				self.lots.pop_front();
+			}
+		}
+		if remaining != 0.0 {
+			match (self.cost_basis, self.lots.back_mut()) {
+				(CostBasis::AverageCost, Some((lot_quantity, lot_price))) => {
+					*lot_price = (*lot_quantity * *lot_price + remaining * price) / (*lot_quantity + remaining);
+					*lot_quantity += remaining;
+				}
+				_ => self.lots.push_back((remaining, price)),
+			}
+		}
+		self.realized += realized;
+		realized
+	}
+
+	/// Pnl the open lots would realize closing at `mark_price`
+	pub fn unrealized(&self, mark_price: f64) -> f64 {
+		self.lots.iter().map(|(quantity, price)| quantity * (mark_price - price)).sum()
+	}
+}
+
+/// Native quote realized by the fills timestamped in `range`, matching them against lots opened by
+/// every earlier fill. `base_lot_size` turns the records' base lots into native base
+pub fn realized_pnl(executions: &[ExecutionRecord], cost_basis: CostBasis, base_lot_size: i64, range: Range<u64>) -> f64 {
+	let mut executions: Vec<&ExecutionRecord> = executions.iter().filter(|execution| execution.timestamp < range.end).collect();
+	executions.sort_by_key(|execution| execution.timestamp);
+	let mut ledger = LotLedger::new(cost_basis);
+	let mut realized = 0.0;
+	for execution in executions {
+		let quantity = (execution.base_filled.abs() * base_lot_size) as f64;
+		let signed = match execution.side {
+			Side::Bid => quantity,
+			Side::Ask => -quantity,
+		};
+		let fill_realized = ledger.fill(signed, execution.fill_price);
+		if range.contains(&execution.timestamp) {
+			realized += fill_realized;
+		}
+	}
+	realized
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::accounting::{CostBasis, LotLedger};
+
+	#[test]
+	fn realizes_by_the_chosen_convention() {
+		let fills = [(-10.0, 100.0), (-10.0, 120.0), (5.0, 90.0), (15.0, 110.0)];
+		let mut average = LotLedger::new(CostBasis::AverageCost);
+		let mut fifo = LotLedger::new(CostBasis::Fifo);
+		let realized = |ledger: &mut LotLedger| fills.iter().map(|(quantity, price)| ledger.fill(*quantity, *price)).collect::<Vec<f64>>();
+		// the first buy closes 5 at the 110 average, or 5 of the lots sold at 100
+		assert_eq!(realized(&mut average), vec![0.0, 0.0, 100.0, 0.0]);
+		assert_eq!(realized(&mut fifo), vec![0.0, 0.0, 50.0, 50.0]);
+		assert_eq!(average.realized, fifo.realized);
+		assert_eq!((average.position(), fifo.position()), (0.0, 0.0));
+
+		let mut flipped = LotLedger::new(CostBasis::Fifo);
+		flipped.fill(-10.0, 100.0);
+		assert_eq!(flipped.fill(15.0, 90.0), 100.0);
+		assert_eq!((flipped.position(), flipped.average_price()), (5.0, Some(90.0)));
+		assert_eq!(flipped.unrealized(94.0), 20.0);
+	}
+}
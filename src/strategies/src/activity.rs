@@ -0,0 +1,207 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use mangol_common::errors::MangolResult;
+use mangol_mango::client::MangoClient;
+use mangol_mango::queue::FillEvent;
+use mangol_mango::stream::FillStream;
+use mangol_mango::types::PerpMarketData;
+use serde::{Deserialize, Serialize};
+
+/// A perp market's activity when it was sampled
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, PartialEq)]
+pub struct ActivitySample {
+	pub timestamp: u64,
+	/// Base lots
+	pub open_interest: i64,
+	/// Native quote the market collected in fees since it launched
+	pub fees_accrued: f64,
+	/// Base lots filled since the previous sample
+	pub volume: i64,
+	pub fills: usize,
+}
+
+/// How a market moved over a window, what strategies judge it by
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, PartialEq)]
+pub struct ActivitySnapshot {
+	pub window_secs: u64,
+	pub open_interest: i64,
+	pub open_interest_change: i64,
+	pub fees_accrued_change: f64,
+	pub volume: i64,
+	pub fills: usize,
+}
+
+/// Samples of every tracked market kept for `retention`, shared between the tracker writing them
+/// and the strategies reading them
+#[derive(Clone, Debug)]
+pub struct MarketStats {
+	pub retention: Duration,
+	samples: Arc<RwLock<HashMap<usize, VecDeque<ActivitySample>>>>,
+}
+
+impl Default for MarketStats {
+	fn default() -> Self {
+		Self::new(Duration::from_secs(24 * 60 * 60))
+	}
+}
+
+impl MarketStats {
+	pub fn new(retention: Duration) -> Self {
+		Self { retention, samples: Arc::new(RwLock::new(HashMap::new())) }
+	}
+
+	pub fn record(&self, market_index: usize, sample: ActivitySample) {
+		let mut samples = self.samples.write().unwrap();
+		let market_samples = samples.entry(market_index).or_default();
+		market_samples.push_back(sample);
+		let oldest = sample.timestamp.saturating_sub(self.retention.as_secs());
+		while market_samples.front().map(|sample| sample.timestamp < oldest).unwrap_or(false) {
+			market_samples.pop_front();
+		}
+	}
+
+	pub fn latest(&self, market_index: usize) -> Option<ActivitySample> {
+		self.samples.read().unwrap().get(&market_index).and_then(|samples| samples.back().copied())
+	}
+
+	/// Oldest first
+	pub fn samples(&self, market_index: usize) -> Vec<ActivitySample> {
+		self.samples.read().unwrap().get(&market_index).map(|samples| samples.iter().copied().collect()).unwrap_or_default()
+	}
+
+	/// Activity over the `window` before `now_ts`, None until the samples reach that far back
+	pub fn snapshot(&self, market_index: usize, window: Duration, now_ts: u64) -> Option<ActivitySnapshot> {
+		let samples = self.samples.read().unwrap();
+		let samples = samples.get(&market_index)?;
+		let start = now_ts.saturating_sub(window.as_secs());
+		let baseline = samples.iter().rev().find(|sample| sample.timestamp <= start)?;
+		let latest = samples.back()?;
+		let in_window = samples.iter().filter(|sample| sample.timestamp > start && sample.timestamp <= now_ts);
+		let (volume, fills) = in_window.fold((0, 0), |(volume, fills), sample| (volume + sample.volume, fills + sample.fills));
+		Some(ActivitySnapshot {
+			window_secs: window.as_secs(),
+			open_interest: latest.open_interest,
+			open_interest_change: latest.open_interest - baseline.open_interest,
+			fees_accrued_change: latest.fees_accrued - baseline.fees_accrued,
+			volume,
+			fills,
+		})
+	}
+}
+
+/// A market too quiet for the ladder to ever get lifted, judged over `window`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DeadMarketFilter {
+	pub window: Duration,
+	/// Base lots
+	pub min_volume: i64,
+	pub min_fills: usize,
+}
+
+impl DeadMarketFilter {
+	pub fn is_dead(&self, snapshot: &ActivitySnapshot) -> bool {
+		snapshot.volume < self.min_volume || snapshot.fills < self.min_fills
+	}
+}
+
+/// Samples open interest and fees of `markets` every `check_interval` into `stats`, with the fills
+/// a FillStream on their event queues saw since the previous sample. Polling the queue would only
+/// see what the keepers hadn't consumed yet, a fraction of what a busy market trades
+pub struct MarketStatsTracker {
+	pub mango_client: MangoClient,
+	pub markets: Vec<PerpMarketData>,
+	pub stats: MarketStats,
+	pub check_interval: Duration,
+	/// (base lots, fills) streamed per market since its last sample
+	pending_fills: Arc<Mutex<HashMap<usize, (i64, usize)>>>,
+}
+
+impl MarketStatsTracker {
+	pub fn new(mango_client: MangoClient, markets: Vec<PerpMarketData>, stats: MarketStats) -> Self {
+		Self { mango_client, markets, stats, check_interval: Duration::from_secs(60), pending_fills: Arc::new(Mutex::new(HashMap::new())) }
+	}
+
+	pub fn record_fill(&self, market_index: usize, fill: &FillEvent) {
+		add_fill(&self.pending_fills, market_index, fill);
+	}
+
+	/// Samples `market` at `now_ts` with the fills recorded since its previous sample
+	pub fn sample(&self, market: &PerpMarketData, now_ts: u64) -> MangolResult<ActivitySample> {
+		let perp_market = self.mango_client.load_perp_market(market)?;
+		let (volume, fills) = self.pending_fills.lock().unwrap().remove(&market.market_index).unwrap_or_default();
+		Ok(ActivitySample {
+			timestamp: now_ts,
+			open_interest: perp_market.open_interest,
+			fees_accrued: perp_market.fees_accrued.to_num::<f64>(),
+			volume,
+			fills,
+		})
+	}
+
+	pub fn check_and_record(&self, now_ts: u64) {
+		for market in &self.markets {
+			match self.sample(market, now_ts) {
+				Ok(sample) => self.stats.record(market.market_index, sample),
+				Err(e) => eprintln!("[-] Failed to sample activity of {} {:?}", market.name, e),
+			}
+		}
+	}
+
+	pub fn start(self) -> JoinHandle<()> {
+		let fill_stream = FillStream {
+			markets: self.markets.clone(),
+			rpc_url: self.mango_client.solana_connection.rpc_client.url(),
+			ws_url: self.mango_client.solana_connection.ws_url(),
+		};
+		let fills = fill_stream.start();
+		let pending_fills = self.pending_fills.clone();
+		std::thread::spawn(move || {
+			for (market_index, fill) in fills {
+				add_fill(&pending_fills, market_index, &fill);
+			}
+		});
+		std::thread::spawn(move || {
+			loop {
+				let now_ts = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+				self.check_and_record(now_ts);
+				std::thread::sleep(self.check_interval);
+			}
+		})
+	}
+}
+
+fn add_fill(pending_fills: &Mutex<HashMap<usize, (i64, usize)>>, market_index: usize, fill: &FillEvent) {
+	let mut pending_fills = pending_fills.lock().unwrap();
+	let (volume, fills) = pending_fills.entry(market_index).or_default();
+	*volume += fill.quantity;
+	*fills += 1;
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+	use crate::activity::{ActivitySample, DeadMarketFilter, MarketStats};
+
+	#[test]
+	fn summarizes_the_window_once_history_covers_it() {
+		let stats = MarketStats::new(Duration::from_secs(600));
+		let sample = |timestamp: u64, open_interest: i64, fees_accrued: f64, volume: i64| ActivitySample { timestamp, open_interest, fees_accrued, volume, fills: volume.signum() as usize };
+		stats.record(3, sample(1_000, 500, 10.0, 0));
+		let window = Duration::from_secs(120);
+		assert_eq!(stats.snapshot(3, window, 1_060), None);
+		stats.record(3, sample(1_060, 520, 12.0, 20));
+		stats.record(3, sample(1_120, 510, 13.5, 15));
+		let snapshot = stats.snapshot(3, window, 1_120).unwrap();
+		assert_eq!((snapshot.open_interest, snapshot.open_interest_change, snapshot.volume, snapshot.fills), (510, 10, 35, 2));
+		assert_eq!(snapshot.fees_accrued_change, 3.5);
+
+		let filter = DeadMarketFilter { window, min_volume: 50, min_fills: 1 };
+		assert!(filter.is_dead(&snapshot));
+		// past retention the oldest samples go
+		stats.record(3, sample(1_700, 510, 13.5, 0));
+		assert_eq!(stats.samples(3).len(), 2);
+	}
+}
@@ -0,0 +1,206 @@
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use mangol_common::errors::{MangolError, MangolResult};
+use mangol_mailer::notification::Notification;
+use mangol_mango::client::MangoClient;
+use mangol_mango::interest::TokenRates;
+use mangol_mango::types::{MangoAccount, MangoCache, QUOTE_INDEX};
+use mangol_solana::swap::JupiterSwap;
+use mangol_solana::TokenMint;
+
+use crate::leader::LeaderElection;
+use crate::watchdog::Heartbeats;
+
+/// A spot borrow whose interest is worth repaying
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BorrowRepayment {
+	pub token_index: usize,
+	/// Native token
+	pub borrow: f64,
+	/// Native quote per day at the current borrow rate
+	pub daily_interest: f64,
+}
+
+/// Non quote borrows of `mango_account` costing more than `max_daily_interest` native quote a day,
+/// most expensive first. Quote borrows are netted against quote deposits by the program already
+pub fn repayments(mango_account: &MangoAccount, mango_cache: &MangoCache, rates: &[TokenRates], max_daily_interest: f64) -> Vec<BorrowRepayment> {
+	let mut repayments: Vec<BorrowRepayment> = rates.iter()
+		  .filter(|rates| rates.token_index != QUOTE_INDEX)
+		  .filter_map(|rates| {
+			  let borrow = -mango_account.get_net(&mango_cache.root_bank_cache[rates.token_index], rates.token_index).to_num::<f64>();
+			  if borrow <= 0.0 {
+				  return None;
+			  }
+			  let daily_interest = borrow * mango_cache.get_price(rates.token_index) * rates.borrow_rate.to_num::<f64>() / 365.0;
+			  Some(BorrowRepayment { token_index: rates.token_index, borrow, daily_interest })
+		  })
+		  .filter(|repayment| repayment.daily_interest > max_daily_interest)
+		  .collect();
+	repayments.sort_by(|a, b| b.daily_interest.partial_cmp(&a.daily_interest).unwrap_or(std::cmp::Ordering::Equal));
+	repayments
+}
+
+pub const BORROW_REPAYER_NAME: &str = "borrow_repayer";
+
+/// Watches the account for spot borrows, left behind by settlement or fees, and repays the ones
+/// bleeding interest: first from the token already in the wallet, then by swapping quote deposits
+pub struct BorrowRepayer {
+	pub mango_client: MangoClient,
+	/// Borrows costing less than this native quote a day are left alone
+	pub max_daily_interest: f64,
+	/// Ratio of the oracle price accepted on the quote to token swap
+	pub max_slippage: f64,
+	pub check_interval: Duration,
+	pub heartbeats: Option<Heartbeats>,
+	/// Shared with the trader, a standby leaves the account to the instance holding the lease
+	pub leader_election: Option<LeaderElection>,
+}
+
+impl BorrowRepayer {
+	pub fn new(mango_client: MangoClient) -> Self {
+		Self {
+			mango_client,
+			max_daily_interest: 100_000.0,
+			max_slippage: 0.01,
+			check_interval: Duration::from_secs(30 * 60),
+			heartbeats: None,
+			leader_election: None,
+		}
+	}
+
+	pub fn with_max_daily_interest(mut self, max_daily_interest: f64) -> Self {
+		self.max_daily_interest = max_daily_interest;
+		self
+	}
+
+	pub fn with_heartbeats(mut self, heartbeats: Heartbeats) -> Self {
+		self.heartbeats = Some(heartbeats);
+		self
+	}
+
+	pub fn with_leader_election(mut self, leader_election: LeaderElection) -> Self {
+		self.leader_election = Some(leader_election);
+		self
+	}
+
+	fn is_leader(&self) -> bool {
+		self.leader_election.as_ref().map(|leader_election| leader_election.is_leader()).unwrap_or(true)
+	}
+
+	/// Repays every borrow over the threshold, returns the deposit signatures
+	pub fn check_and_repay(&mut self) -> MangolResult<Vec<String>> {
+		self.mango_client.update()?;
+		let rates = self.mango_client.interest_rates()?;
+		let mut signatures = vec![];
+		for repayment in repayments(&self.mango_client.mango_account, &self.mango_client.mango_cache, &rates, self.max_daily_interest) {
+			println!("[?] Repaying borrow of {} native token {}, {:.0} native quote interest a day", repayment.borrow, repayment.token_index, repayment.daily_interest);
+			match self.repay(&repayment) {
+				Ok(repaid) => {
+					signatures.extend(repaid.iter().map(|(signature, _)| signature.clone()));
+					let amount: u64 = repaid.iter().map(|(_, amount)| amount).sum();
+					mangol_mailer::notify(&Notification::BorrowRepaid {
+						token: self.token_name(repayment.token_index),
+						repaid: amount as f64 / 10_f64.powi(self.mango_client.mango_group.tokens[repayment.token_index].decimals as i32),
+						daily_interest: repayment.daily_interest / 10_f64.powi(self.mango_client.mango_group.tokens[QUOTE_INDEX].decimals as i32),
+					});
+				}
+				Err(e) => eprintln!("[-] Failed to repay borrow of token {} {:?}", repayment.token_index, e),
+			}
+		}
+		Ok(signatures)
+	}
+
+	/// The token's symbol from the cached token list, its mint when there is no disk cache or the list doesn't have it
+	fn token_name(&self, token_index: usize) -> String {
+		let mint = self.mango_client.mango_group.tokens[token_index].mint;
+		self.mango_client.solana_connection.disk_cache.as_ref()
+			  .and_then(|disk_cache| TokenMint::from_pubkey_cached(&mint, disk_cache).ok())
+			  .map(|token_mint| token_mint.symbol)
+			  .unwrap_or_else(|| mint.to_string())
+	}
+
+	/// (signature, native amount) of every deposit that went toward the borrow
+	fn repay(&self, repayment: &BorrowRepayment) -> MangolResult<Vec<(String, u64)>> {
+		let token_mint = self.mango_client.mango_group.tokens[repayment.token_index].mint;
+		let mut remaining = repayment.borrow.ceil() as u64;
+		let mut deposits = vec![];
+		let in_wallet = self.mango_client.get_wallet_token_balance(&token_mint)?.min(remaining);
+		if in_wallet > 0 {
+			deposits.push((self.mango_client.deposit(repayment.token_index, in_wallet)?, in_wallet));
+			remaining -= in_wallet;
+		}
+		if remaining == 0 {
+			return Ok(deposits);
+		}
+		let quote_deposit = self.mango_client.mango_account.get_net(&self.mango_client.mango_cache.root_bank_cache[QUOTE_INDEX], QUOTE_INDEX).to_num::<f64>();
+		// enough quote to buy the rest back at the worst accepted price, never borrowing quote for it
+		let quote_needed = remaining as f64 * self.mango_client.mango_cache.get_price(repayment.token_index) * (1.0 + self.max_slippage);
+		let quote_amount = quote_needed.min(quote_deposit.max(0.0)).floor() as u64;
+		if quote_amount == 0 {
+			return Err(MangolError::MangoError(format!("No quote deposits to repay {} native token {}", remaining, repayment.token_index)));
+		}
+		let quote_mint = self.mango_client.mango_group.tokens[QUOTE_INDEX].mint;
+		let before = self.mango_client.get_wallet_token_balance(&token_mint)?;
+		self.mango_client.withdraw(QUOTE_INDEX, quote_amount, false)?;
+		let mut swap = JupiterSwap::new(&self.mango_client.solana_connection);
+		swap.slippage_percent = self.max_slippage * 100.0;
+		swap.swap(&quote_mint, &token_mint, quote_amount, &self.mango_client.signer)?;
+		let received = self.mango_client.get_wallet_token_balance(&token_mint)?.saturating_sub(before);
+		if received == 0 {
+			return Err(MangolError::MangoError(format!("swap of {} native quote returned nothing, left in the wallet", quote_amount)));
+		}
+		// any excess over the borrow stays deposited and earns instead
+		deposits.push((self.mango_client.deposit(repayment.token_index, received)?, received.min(remaining)));
+		Ok(deposits)
+	}
+
+	pub fn start(mut self) -> JoinHandle<()> {
+		std::thread::spawn(move || {
+			loop {
+				if let Some(heartbeats) = &self.heartbeats {
+					heartbeats.beat(BORROW_REPAYER_NAME);
+				}
+				if self.is_leader() {
+					if let Err(e) = self.check_and_repay() {
+						eprintln!("[-] Borrow repayment failed {:?}", e);
+					}
+				}
+				std::thread::sleep(self.check_interval);
+			}
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use fixed::types::I80F48;
+	use mangol_mango::interest::TokenRates;
+	use mangol_mango::mock::MockMangoClient;
+	use mangol_mango::types::QUOTE_INDEX;
+	use crate::borrow_repay::repayments;
+
+	#[test]
+	fn repays_expensive_non_quote_borrows() {
+		let mut mango_client = MockMangoClient::new(1, 10_000_000, 100);
+		for cache in mango_client.mango_cache.root_bank_cache.iter_mut() {
+			cache.borrow_index = I80F48::from_num(1);
+			cache.deposit_index = I80F48::from_num(1);
+		}
+		mango_client.mango_cache.price_cache[1].price = I80F48::from_num(0.04);
+		mango_client.mango_cache.price_cache[2].price = I80F48::from_num(0.04);
+		mango_client.mango_account.borrows[1] = I80F48::from_num(1_000_000_000_000_u64);
+		mango_client.mango_account.borrows[2] = I80F48::from_num(1_000_000_u64);
+		mango_client.mango_account.borrows[QUOTE_INDEX] = I80F48::from_num(5_000_000_u64);
+		let rates: Vec<TokenRates> = [1, 2, QUOTE_INDEX].iter().map(|&token_index| TokenRates {
+			token_index,
+			utilization: I80F48::from_num(0.5),
+			deposit_rate: I80F48::from_num(0.05),
+			borrow_rate: I80F48::from_num(0.365),
+		}).collect();
+		let repayments = repayments(&mango_client.mango_account, &mango_client.mango_cache, &rates, 100_000.0);
+		assert_eq!(repayments.len(), 1);
+		assert_eq!(repayments[0].token_index, 1);
+		assert!((repayments[0].daily_interest - 40_000_000.0).abs() < 1.0);
+	}
+}
@@ -0,0 +1,183 @@
+use std::io::{BufRead, BufReader, Write};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, RwLock};
+use std::thread::JoinHandle;
+
+use mangol_common::errors::MangolResult;
+use serde::{Deserialize, Serialize};
+
+use crate::dashboard::FillState;
+use crate::fib_state::FibState;
+use crate::trade_feed::Trade;
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Topic {
+	Price,
+	Trade,
+	Fill,
+	Health,
+	Decision,
+}
+
+/// What subsystems tell each other, ui units
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "topic", rename_all = "snake_case")]
+pub enum BusEvent {
+	Price { market: String, oracle_price: f64, timestamp: u64 },
+	/// A fill of anyone on a market's event queue
+	Trade(Trade),
+	/// A fill of a strategy's own order
+	Fill { strategy: String, market: String, fill: FillState },
+	/// UI quote
+	Health { strategy: String, market: String, init_health: Option<f64>, maint_health: Option<f64>, equity: Option<f64>, timestamp: u64 },
+	/// The state a strategy ended its round in, `paused` with why it placed nothing
+	Decision { strategy: String, market: String, state: FibState, paused: Option<String>, timestamp: u64 },
+}
+
+impl BusEvent {
+	pub fn topic(&self) -> Topic {
+		match self {
+			BusEvent::Price { .. } => Topic::Price,
+			BusEvent::Trade(_) => Topic::Trade,
+			BusEvent::Fill { .. } => Topic::Fill,
+			BusEvent::Health { .. } => Topic::Health,
+			BusEvent::Decision { .. } => Topic::Decision,
+		}
+	}
+}
+
+/// Fans published events out to every subscriber of their topic, so the keeper, liquidator,
+/// strategies and dashboards share one source instead of each opening its own subscription.
+/// `serve_socket` carries the same events to other processes as json lines, `connect_socket`
+/// republishes another process' events on this bus
+#[derive(Clone, Default)]
+pub struct EventBus {
+	subscribers: Arc<RwLock<Vec<(Vec<Topic>, Sender<BusEvent>)>>>,
+}
+
+impl EventBus {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn publish(&self, event: BusEvent) {
+		let topic = event.topic();
+		// subscribers that went away dropped their receiver
+		self.subscribers.write().unwrap().retain(|(topics, subscriber)| {
+			!(topics.is_empty() || topics.contains(&topic)) || subscriber.send(event.clone()).is_ok()
+		});
+	}
+
+	/// Events of `topics`, every event when empty
+	pub fn subscribe(&self, topics: &[Topic]) -> Receiver<BusEvent> {
+		let (sender, receiver) = channel();
+		self.subscribers.write().unwrap().push((topics.to_vec(), sender));
+		receiver
+	}
+
+	pub fn subscribers(&self) -> usize {
+		self.subscribers.read().unwrap().len()
+	}
+
+	/// Streams every event to the processes connected on the unix socket at `path`
+	#[cfg(unix)]
+	pub fn serve_socket(&self, path: &str) -> MangolResult<JoinHandle<()>> {
+		// a socket file left behind by a previous run refuses the bind
+		let _ = std::fs::remove_file(path);
+		let listener = UnixListener::bind(path)?;
+		println!("[+] Event bus on {}", path);
+		let bus = self.clone();
+		Ok(std::thread::spawn(move || {
+			for stream in listener.incoming() {
+				let mut stream = match stream {
+					Ok(stream) => stream,
+					Err(e) => {
+						eprintln!("[-] Event bus: failed to accept connection {:?}", e);
+						continue;
+					}
+				};
+				let receiver = bus.subscribe(&[]);
+				std::thread::spawn(move || {
+					for event in receiver {
+						let line = match serde_json::to_string(&event) {
+							Ok(line) => line,
+							Err(e) => {
+								eprintln!("[-] Event bus: failed to serialize event {:?}", e);
+								continue;
+							}
+						};
+						if writeln!(stream, "{}", line).is_err() {
+							// the process went away, dropping the receiver unregisters it on the next publish
+							break;
+						}
+					}
+				});
+			}
+		}))
+	}
+
+	/// Republishes here what the process serving `path` publishes, until it closes the socket
+	#[cfg(unix)]
+	pub fn connect_socket(&self, path: &str) -> MangolResult<JoinHandle<()>> {
+		let stream = UnixStream::connect(path)?;
+		let bus = self.clone();
+		Ok(std::thread::spawn(move || {
+			for line in BufReader::new(stream).lines() {
+				let line = match line {
+					Ok(line) => line,
+					Err(e) => {
+						eprintln!("[-] Event bus: connection closed {:?}", e);
+						break;
+					}
+				};
+				match serde_json::from_str::<BusEvent>(&line) {
+					Ok(event) => bus.publish(event),
+					Err(e) => eprintln!("[-] Event bus: skipping malformed event {:?}", e),
+				}
+			}
+		}))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+	use crate::bus::{BusEvent, EventBus, Topic};
+
+	fn price(oracle_price: f64) -> BusEvent {
+		BusEvent::Price { market: "SOL-PERP".to_string(), oracle_price, timestamp: 1_000 }
+	}
+
+	#[test]
+	fn delivers_by_topic_within_and_across_processes() {
+		let bus = EventBus::new();
+		let prices = bus.subscribe(&[Topic::Price]);
+		let fills = bus.subscribe(&[Topic::Fill]);
+		let everything = bus.subscribe(&[]);
+		bus.publish(price(40.5));
+		assert_eq!(prices.try_recv().ok(), Some(price(40.5)));
+		assert_eq!(everything.try_recv().ok(), Some(price(40.5)));
+		assert!(fills.try_recv().is_err());
+		// a dropped receiver is gone once an event of its topic is published
+		drop(prices);
+		bus.publish(price(40.6));
+		assert_eq!(bus.subscribers(), 2);
+
+		let path = std::env::temp_dir().join(format!("mangol-bus-{}.sock", std::process::id()));
+		let path = path.to_str().unwrap();
+		bus.serve_socket(path).unwrap();
+		let remote = EventBus::new();
+		let remote_prices = remote.subscribe(&[Topic::Price]);
+		remote.connect_socket(path).unwrap();
+		// the connection registers asynchronously
+		while bus.subscribers() < 3 {
+			std::thread::sleep(Duration::from_millis(5));
+		}
+		bus.publish(price(41.0));
+		assert_eq!(remote_prices.recv_timeout(Duration::from_secs(5)).ok(), Some(price(41.0)));
+		let _ = std::fs::remove_file(path);
+	}
+}
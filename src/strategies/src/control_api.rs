@@ -0,0 +1,248 @@
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use mangol_mango::accounts::{AccountManager, AccountsReport};
+use serde_json::{json, Value};
+
+use crate::explain::ExplanationLog;
+use crate::kill_switch::KillSwitch;
+use crate::quarantine::QuarantineRelease;
+use crate::watch_list::{parse_pubkey, WatchList};
+
+/// Runtime control over http, bind it to localhost or a private interface.
+///
+/// - `GET /watchlists` lists the watch list names
+/// - `GET /watchlists/<name>` lists its accounts
+/// - `PUT /watchlists/<name>/<pubkey>` watches an account
+/// - `DELETE /watchlists/<name>/<pubkey>` stops watching it
+/// - `GET /explanations` lists the strategy's latest decision explanations, oldest first
+/// - `POST /quarantine/release` lets the strategy send its quarantined orders again
+/// - `POST /kill/<strategy>` stops the strategy placing orders, `DELETE /kill/<strategy>` lets it again
+/// - `GET /accounts` reports equity and health of every managed account and their total
+///
+/// With a token every request needs `Authorization: Bearer <token>`
+#[derive(Clone, Default)]
+pub struct ControlApi {
+	pub watch_lists: BTreeMap<String, WatchList>,
+	pub explanations: Option<ExplanationLog>,
+	pub quarantine_release: Option<QuarantineRelease>,
+	/// By strategy name
+	pub kill_switches: BTreeMap<String, KillSwitch>,
+	pub accounts: Option<Arc<AccountManager>>,
+	pub token: Option<String>,
+}
+
+impl ControlApi {
+	/// `name` is how the list is addressed, e.g. `liquidator` or `traders`
+	pub fn with_watch_list(mut self, name: &str, watch_list: WatchList) -> Self {
+		self.watch_lists.insert(name.to_string(), watch_list);
+		self
+	}
+
+	pub fn with_explanations(mut self, explanations: ExplanationLog) -> Self {
+		self.explanations = Some(explanations);
+		self
+	}
+
+	pub fn with_quarantine_release(mut self, quarantine_release: QuarantineRelease) -> Self {
+		self.quarantine_release = Some(quarantine_release);
+		self
+	}
+
+	pub fn with_kill_switch(mut self, kill_switch: KillSwitch) -> Self {
+		self.kill_switches.insert(kill_switch.strategy.clone(), kill_switch);
+		self
+	}
+
+	pub fn with_accounts(mut self, accounts: Arc<AccountManager>) -> Self {
+		self.accounts = Some(accounts);
+		self
+	}
+
+	pub fn with_token(mut self, token: &str) -> Self {
+		self.token = Some(token.to_string());
+		self
+	}
+
+	/// Status code and json body for a request
+	pub fn handle(&self, method: &str, path: &str, authorization: Option<&str>) -> (u16, Value) {
+		if let Some(token) = &self.token {
+			if authorization != Some(&format!("Bearer {}", token)) {
+				return (401, json!({ "error": "unauthorized" }));
+			}
+		}
+		let segments: Vec<&str> = path.trim_matches('/').split('/').filter(|segment| !segment.is_empty()).collect();
+		match (method, segments.as_slice()) {
+			("GET", ["explanations"]) => match &self.explanations {
+				Some(explanations) => (200, json!(explanations.recent())),
+				None => (404, json!({ "error": "no strategy explains its decisions here" }))
+			},
+			("POST", ["quarantine", "release"]) => match &self.quarantine_release {
+				Some(quarantine_release) => {
+					quarantine_release.request();
+					(200, json!({ "requested": true }))
+				}
+				None => (404, json!({ "error": "no retry budget quarantines orders here" }))
+			},
+			("POST", ["kill", strategy]) | ("DELETE", ["kill", strategy]) => match self.kill_switches.get(*strategy) {
+				Some(kill_switch) => {
+					if method == "POST" { kill_switch.engage() } else { kill_switch.release() }
+					// a kill file or env var keeps it engaged after a release
+					(200, json!({ "strategy": strategy, "engaged": kill_switch.is_engaged() }))
+				}
+				None => (404, json!({ "error": format!("no kill switch for {}", strategy) }))
+			},
+			("GET", ["accounts"]) => match &self.accounts {
+				Some(accounts) => match accounts.report() {
+					Ok(report) => (200, report_json(&report)),
+					Err(e) => (500, json!({ "error": format!("{:?}", e) }))
+				},
+				None => (404, json!({ "error": "no accounts are managed here" }))
+			},
+			("GET", ["watchlists"]) => (200, json!(self.watch_lists.keys().collect::<Vec<_>>())),
+			(_, ["watchlists", name, ..]) if !self.watch_lists.contains_key(*name) => (404, json!({ "error": format!("no watch list {}", name) })),
+			("GET", ["watchlists", name]) => {
+				let accounts: Vec<String> = self.watch_lists[*name].list().iter().map(|pubkey| pubkey.to_string()).collect();
+				(200, json!(accounts))
+			}
+			("PUT", ["watchlists", name, pubkey]) | ("DELETE", ["watchlists", name, pubkey]) => {
+				let pubkey = match parse_pubkey(pubkey) {
+					Ok(pubkey) => pubkey,
+					Err(e) => return (400, json!({ "error": e.to_string() }))
+				};
+				let watch_list = &self.watch_lists[*name];
+				let changed = if method == "PUT" { watch_list.add(&pubkey) } else { watch_list.remove(&pubkey) };
+				match changed {
+					Ok(changed) => (200, json!({ "account": pubkey.to_string(), "changed": changed, "watched": watch_list.len() })),
+					Err(e) => (500, json!({ "error": format!("{:?}", e) }))
+				}
+			}
+			_ => (404, json!({ "error": format!("no route {} {}", method, path) }))
+		}
+	}
+
+	pub fn serve(self, addr: &str) -> JoinHandle<()> {
+		let addr = addr.to_string();
+		std::thread::spawn(move || {
+			let listener = match TcpListener::bind(&addr) {
+				Ok(listener) => listener,
+				Err(e) => {
+					eprintln!("[-] Control api: failed to bind {} {:?}", addr, e);
+					return;
+				}
+			};
+			for stream in listener.incoming() {
+				let mut stream = match stream {
+					Ok(stream) => stream,
+					Err(_) => continue
+				};
+				// requests carry no body, the route is all there is
+				let mut request = [0_u8; 4096];
+				let read = stream.read(&mut request).unwrap_or(0);
+				let request = String::from_utf8_lossy(&request[..read]);
+				let mut request_line = request.lines().next().unwrap_or("").split_whitespace();
+				let (method, path) = (request_line.next().unwrap_or(""), request_line.next().unwrap_or(""));
+				let authorization = request.lines()
+					  .find_map(|line| line.split_once(':').filter(|(name, _)| name.eq_ignore_ascii_case("authorization")).map(|(_, value)| value.trim()));
+				let (code, body) = self.handle(method, path, authorization);
+				let reason = match code {
+					200 => "OK",
+					400 => "Bad Request",
+					401 => "Unauthorized",
+					404 => "Not Found",
+					_ => "Internal Server Error",
+				};
+				let body = body.to_string();
+				let _ = write!(stream, "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", code, reason, body.len(), body);
+			}
+		})
+	}
+}
+
+fn report_json(report: &AccountsReport) -> Value {
+	let accounts: Vec<Value> = report.accounts.iter().map(|account| json!({
+		"name": account.name,
+		"mango_account": account.mango_account_pk.to_string(),
+		"equity": account.equity.to_num::<f64>(),
+		"init_health": account.init_health.to_num::<f64>(),
+		"maint_health": account.maint_health.to_num::<f64>(),
+	})).collect();
+	let rates: Vec<Value> = report.rates.iter().map(|rates| json!({
+		"token_index": rates.token_index,
+		"utilization": rates.utilization.to_num::<f64>(),
+		"deposit_rate": rates.deposit_rate.to_num::<f64>(),
+		"borrow_rate": rates.borrow_rate.to_num::<f64>(),
+	})).collect();
+	json!({ "accounts": accounts, "total_equity": report.total_equity.to_num::<f64>(), "rates": rates })
+}
+
+#[cfg(test)]
+mod tests {
+	use solana_sdk::pubkey::Pubkey;
+	use crate::control_api::ControlApi;
+	use crate::explain::{DecisionExplanation, ExplanationLog};
+	use crate::kill_switch::KillSwitch;
+	use crate::quarantine::{QuarantineRelease, RetryBudget};
+	use crate::watch_list::WatchList;
+
+	#[test]
+	fn adds_and_removes_watched_accounts() {
+		let traders = WatchList::new(&[]);
+		let api = ControlApi::default().with_watch_list("traders", traders.clone()).with_token("secret");
+		let pubkey = Pubkey::new_unique();
+		let path = format!("/watchlists/traders/{}", pubkey);
+		assert_eq!(api.handle("PUT", &path, None).0, 401);
+		assert_eq!(api.handle("PUT", &path, Some("Bearer secret")).0, 200);
+		assert!(traders.contains(&pubkey));
+		assert_eq!(api.handle("GET", "/watchlists/traders", Some("Bearer secret")).1[0], pubkey.to_string());
+		assert_eq!(api.handle("PUT", "/watchlists/traders/not-a-key", Some("Bearer secret")).0, 400);
+		assert_eq!(api.handle("GET", "/watchlists/liquidator", Some("Bearer secret")).0, 404);
+		assert_eq!(api.handle("DELETE", &path, Some("Bearer secret")).1["changed"], true);
+		assert!(traders.is_empty());
+	}
+
+	#[test]
+	fn lists_latest_explanations() {
+		assert_eq!(ControlApi::default().handle("GET", "/explanations", None).0, 404);
+		let explanations = ExplanationLog::new(2);
+		let api = ControlApi::default().with_explanations(explanations.clone());
+		for rule in ["position closed", "book imbalance against scale-in", "risk manager refused the scale-in"] {
+			explanations.push(DecisionExplanation { rule: rule.to_string(), ..Default::default() });
+		}
+		let (code, body) = api.handle("GET", "/explanations", None);
+		assert_eq!(code, 200);
+		assert_eq!(body.as_array().unwrap().len(), 2);
+		assert_eq!(body[1]["rule"], "risk manager refused the scale-in");
+	}
+
+	#[test]
+	fn engages_and_releases_kill_switches() {
+		let kill_switch = KillSwitch::new("control-api-kill-test", std::env::temp_dir());
+		let api = ControlApi::default().with_kill_switch(kill_switch.clone());
+		assert_eq!(api.handle("POST", "/kill/fib", None).0, 404);
+		assert_eq!(api.handle("POST", "/kill/control-api-kill-test", None).1["engaged"], true);
+		assert!(kill_switch.is_engaged());
+		assert_eq!(api.handle("DELETE", "/kill/control-api-kill-test", None).1["engaged"], false);
+		assert!(!kill_switch.is_engaged());
+	}
+
+	#[test]
+	fn reports_accounts_only_when_managed() {
+		assert_eq!(ControlApi::default().handle("GET", "/accounts", None).0, 404);
+	}
+
+	#[test]
+	fn releases_quarantined_orders() {
+		assert_eq!(ControlApi::default().handle("POST", "/quarantine/release", None).0, 404);
+		let release = QuarantineRelease::new("control-api-release-test", std::env::temp_dir());
+		let mut budget = RetryBudget::new(1, std::time::Duration::from_secs(10)).with_release(release.clone());
+		assert!(budget.record_failure("ScaleIn Ask depth 4", "custom program error: 0x1", 1_000));
+		let api = ControlApi::default().with_quarantine_release(release);
+		assert_eq!(api.handle("POST", "/quarantine/release", None).0, 200);
+		assert!(budget.release_if_requested());
+		assert!(budget.quarantined().is_empty());
+	}
+}
@@ -0,0 +1,184 @@
+use std::net::TcpListener;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, RwLock};
+use std::thread::JoinHandle;
+
+use mangol_mango::types::{MangoAccount, PerpMarketData, PerpMarketInfo, Side, MAX_PERP_OPEN_ORDERS};
+use serde::{Deserialize, Serialize};
+use tungstenite::Message;
+
+use crate::explain::DecisionExplanation;
+use crate::fib_state::FibState;
+
+/// A resting order of the bot's account, ui units
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct OpenOrderState {
+	pub side: String,
+	pub price: f64,
+	pub order_id: String,
+}
+
+/// A committed or pending rung of the fib ladder, ui units
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LadderLevel {
+	pub side: String,
+	pub depth: u16,
+	pub price: f64,
+	pub size: f64,
+	pub state: String,
+}
+
+/// A fill of the strategy's own orders, ui units
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FillState {
+	pub timestamp: u64,
+	pub side: String,
+	pub price: f64,
+	pub size: f64,
+	pub vs_decision_bps: f64,
+}
+
+/// What a dashboard shows of a running strategy, one json message per decision round
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BotState {
+	pub strategy: String,
+	pub market: String,
+	pub timestamp: u64,
+	pub oracle_price: f64,
+	/// UI base, negative when short
+	pub base_position: f64,
+	pub open_orders: Vec<OpenOrderState>,
+	/// UI quote
+	pub init_health: Option<f64>,
+	pub maint_health: Option<f64>,
+	pub equity: Option<f64>,
+	pub current_state: FibState,
+	/// Latest committed fib levels, oldest first
+	pub last_decisions: Vec<FibState>,
+	/// The committed ladder followed by the order being waited on
+	#[serde(default)]
+	pub ladder: Vec<LadderLevel>,
+	/// Newest last
+	#[serde(default)]
+	pub recent_fills: Vec<FillState>,
+	/// Why the strategy is not placing orders, None while trading
+	pub paused: Option<String>,
+	/// Inputs and rule behind the latest decision round
+	#[serde(default)]
+	pub last_explanation: Option<DecisionExplanation>,
+}
+
+/// The account's resting orders on `market`, the order id carries the lot price in its upper 64 bits
+pub fn open_orders(market: &PerpMarketData, perp_market_info: &PerpMarketInfo, mango_account: &MangoAccount) -> Vec<OpenOrderState> {
+	(0..MAX_PERP_OPEN_ORDERS)
+		  .filter(|i| mango_account.orders[*i] != 0 && mango_account.order_market[*i] as usize == market.market_index)
+		  .map(|i| OpenOrderState {
+			  side: match mango_account.order_side[i] {
+				  Side::Bid => "buy".to_string(),
+				  Side::Ask => "sell".to_string(),
+			  },
+			  price: market.ui_price(perp_market_info.lots_to_price((mango_account.orders[i] >> 64) as i64)),
+			  order_id: mango_account.orders[i].to_string(),
+		  })
+		  .collect()
+}
+
+/// Pushes bot state as json to every websocket client connected on `bind_addr`. Dashboards get
+/// the latest state as soon as they connect, then every update as it is published
+#[derive(Clone)]
+pub struct StateBroadcaster {
+	pub bind_addr: String,
+	subscribers: Arc<RwLock<Vec<Sender<String>>>>,
+	latest: Arc<RwLock<Option<String>>>,
+}
+
+impl StateBroadcaster {
+	pub fn new(bind_addr: &str) -> Self {
+		Self {
+			bind_addr: bind_addr.to_string(),
+			subscribers: Arc::new(RwLock::new(vec![])),
+			latest: Arc::new(RwLock::new(None)),
+		}
+	}
+
+	pub fn publish(&self, state: &BotState) {
+		let message = match serde_json::to_string(state) {
+			Ok(message) => message,
+			Err(e) => {
+				eprintln!("[-] Dashboard: failed to serialize state {:?}", e);
+				return;
+			}
+		};
+		*self.latest.write().unwrap() = Some(message.clone());
+		// clients that went away dropped their receiver
+		self.subscribers.write().unwrap().retain(|subscriber| subscriber.send(message.clone()).is_ok());
+	}
+
+	pub fn clients(&self) -> usize {
+		self.subscribers.read().unwrap().len()
+	}
+
+	pub fn start(&self) -> JoinHandle<()> {
+		let broadcaster = self.clone();
+		std::thread::spawn(move || {
+			let listener = match TcpListener::bind(&broadcaster.bind_addr) {
+				Ok(listener) => listener,
+				Err(e) => {
+					eprintln!("[-] Dashboard: failed to bind {} {:?}", broadcaster.bind_addr, e);
+					return;
+				}
+			};
+			println!("[+] Dashboard state on ws://{}", broadcaster.bind_addr);
+			for stream in listener.incoming() {
+				let stream = match stream {
+					Ok(stream) => stream,
+					Err(e) => {
+						eprintln!("[-] Dashboard: failed to accept connection {:?}", e);
+						continue;
+					}
+				};
+				let (sender, receiver) = channel::<String>();
+				if let Some(latest) = broadcaster.latest.read().unwrap().clone() {
+					let _ = sender.send(latest);
+				}
+				broadcaster.subscribers.write().unwrap().push(sender);
+				std::thread::spawn(move || {
+					let mut websocket = match tungstenite::accept(stream) {
+						Ok(websocket) => websocket,
+						Err(e) => {
+							eprintln!("[-] Dashboard: websocket handshake failed {:?}", e);
+							return;
+						}
+					};
+					for message in receiver {
+						if websocket.write_message(Message::Text(message)).is_err() {
+							break;
+						}
+					}
+				});
+			}
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use mangol_mango::mock::{sol_perp, MockMangoClient};
+	use mangol_mango::types::Side;
+	use crate::dashboard::open_orders;
+
+	#[test]
+	fn lists_resting_orders_of_the_market() {
+		let market = sol_perp();
+		let mut mango_client = MockMangoClient::new(3, 10_000_000, 100);
+		mango_client.mango_account.orders[0] = (4_050_i128 << 64) | 7;
+		mango_client.mango_account.order_market[0] = 3;
+		mango_client.mango_account.order_side[0] = Side::Ask;
+		mango_client.mango_account.orders[1] = (100_i128 << 64) | 8;
+		mango_client.mango_account.order_market[1] = 1;
+		let orders = open_orders(&market, &mango_client.mango_group.perp_markets[3], &mango_client.mango_account);
+		assert_eq!(orders.len(), 1);
+		assert_eq!(orders[0].side, "sell");
+		assert!((orders[0].price - 40.5).abs() < 1e-9);
+	}
+}
@@ -0,0 +1,130 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::{Arc, RwLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use mangol_solana::network::NetworkMonitor;
+use solana_client::pubsub_client::PubsubClient;
+
+/// Cancels the strategy's orders after the bot was blind, so stale quotes don't sit on the book.
+///
+/// Rpc is watched through the NetworkMonitor, the websocket through a slot subscription. Once
+/// both were silent for `max_blind` a cancel-all is queued, `take_pending_cancel` hands it out
+/// the moment either comes back. While only one is silent orders can use a shorter expiry
+#[derive(Clone)]
+pub struct DeadMansSwitch {
+	pub ws_url: String,
+	pub max_blind: Duration,
+	/// Silence after which rpc or websocket counts as lost
+	pub stale_after: Duration,
+	/// Order expiry while connectivity is shaky, None keeps the strategy's own
+	pub degraded_expiry_secs: Option<u64>,
+	network_monitor: NetworkMonitor,
+	started_at: Instant,
+	ws_seen: Arc<RwLock<Option<Instant>>>,
+	pending_cancel: Arc<AtomicBool>,
+}
+
+impl DeadMansSwitch {
+	pub fn new(network_monitor: NetworkMonitor, ws_url: &str, max_blind: Duration) -> Self {
+		Self {
+			ws_url: ws_url.to_string(),
+			max_blind,
+			stale_after: Duration::from_secs(10),
+			degraded_expiry_secs: None,
+			network_monitor,
+			started_at: Instant::now(),
+			ws_seen: Arc::new(RwLock::new(None)),
+			pending_cancel: Arc::new(AtomicBool::new(false)),
+		}
+	}
+
+	pub fn with_degraded_expiry(mut self, degraded_expiry_secs: u64) -> Self {
+		self.degraded_expiry_secs = Some(degraded_expiry_secs);
+		self
+	}
+
+	fn silent_for(&self, seen: Option<Instant>) -> Duration {
+		seen.unwrap_or(self.started_at).elapsed()
+	}
+
+	/// How long the rpc and the websocket have been silent
+	pub fn silence(&self) -> (Duration, Duration) {
+		(self.silent_for(self.network_monitor.last_ok()), self.silent_for(*self.ws_seen.read().unwrap()))
+	}
+
+	/// Queues a cancel-all once both have been silent for `max_blind`, returns whether one is queued
+	pub fn evaluate(&self, rpc_silence: Duration, ws_silence: Duration) -> bool {
+		if rpc_silence.min(ws_silence) >= self.max_blind && !self.pending_cancel.swap(true, Ordering::SeqCst) {
+			eprintln!("[-] No rpc or websocket for {:?}, cancelling orders once connectivity returns", self.max_blind);
+		}
+		self.pending_cancel.load(Ordering::SeqCst)
+	}
+
+	/// Rpc or websocket is lost but not both
+	pub fn is_degraded(&self) -> bool {
+		let (rpc_silence, ws_silence) = self.silence();
+		rpc_silence.max(ws_silence) >= self.stale_after
+	}
+
+	/// Order expiry to use instead of `expiry_secs` right now
+	pub fn order_expiry_secs(&self, expiry_secs: u64) -> u64 {
+		match self.degraded_expiry_secs {
+			Some(degraded_expiry_secs) if self.is_degraded() => degraded_expiry_secs.min(expiry_secs),
+			_ => expiry_secs
+		}
+	}
+
+	/// True once per blind period, as soon as rpc or websocket is back. The caller sends the cancels
+	pub fn take_pending_cancel(&self) -> bool {
+		let (rpc_silence, ws_silence) = self.silence();
+		if !self.evaluate(rpc_silence, ws_silence) || rpc_silence.min(ws_silence) >= self.stale_after {
+			return false;
+		}
+		self.pending_cancel.swap(false, Ordering::SeqCst)
+	}
+
+	/// Keeps a slot subscription open to track the websocket, resubscribing whenever it goes quiet
+	pub fn start(&self) -> JoinHandle<()> {
+		let switch = self.clone();
+		std::thread::spawn(move || {
+			loop {
+				if let Ok((_subscription, slots)) = PubsubClient::slot_subscribe(&switch.ws_url) {
+					loop {
+						match slots.recv_timeout(Duration::from_secs(1)) {
+							Ok(_) => *switch.ws_seen.write().unwrap() = Some(Instant::now()),
+							Err(RecvTimeoutError::Timeout) => {}
+							Err(RecvTimeoutError::Disconnected) => break
+						}
+						let (rpc_silence, ws_silence) = switch.silence();
+						switch.evaluate(rpc_silence, ws_silence);
+						if ws_silence >= switch.stale_after {
+							break;
+						}
+					}
+				}
+				let (rpc_silence, ws_silence) = switch.silence();
+				switch.evaluate(rpc_silence, ws_silence);
+				std::thread::sleep(Duration::from_secs(1));
+			}
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+	use mangol_solana::network::NetworkMonitor;
+	use crate::dead_man::DeadMansSwitch;
+
+	#[test]
+	fn queues_cancel_only_when_both_are_silent() {
+		let switch = DeadMansSwitch::new(NetworkMonitor::new("http://localhost:8899"), "ws://localhost:8900", Duration::from_secs(30));
+		assert!(!switch.evaluate(Duration::from_secs(60), Duration::from_secs(1)));
+		assert!(!switch.evaluate(Duration::from_secs(1), Duration::from_secs(60)));
+		assert!(switch.evaluate(Duration::from_secs(31), Duration::from_secs(45)));
+		// still queued after the silence ends, until taken
+		assert!(switch.evaluate(Duration::ZERO, Duration::ZERO));
+	}
+}
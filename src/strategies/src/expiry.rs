@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+use mangol_common::clock::Clock;
+use mangol_common::errors::{MangolError, MangolResult};
+use mangol_mango::client::MangoClientApi;
+use mangol_mango::types::{MangoAccount, PerpMarketData, MAX_PERP_OPEN_ORDERS};
+
+/// Whether the account has resting orders or unprocessed fills on the perp market
+pub fn has_open_orders(mango_account: &MangoAccount, market_index: usize) -> bool {
+	let perp_account = &mango_account.perp_accounts[market_index];
+	perp_account.bids_quantity != 0
+		  || perp_account.asks_quantity != 0
+		  || (0..MAX_PERP_OPEN_ORDERS).any(|i| mango_account.orders[i] != 0 && mango_account.order_market[i] as usize == market_index)
+}
+
+/// Cancels the strategy's resting orders right before a decision round instead of letting their
+/// time in force lapse, and waits for the account to show them gone so nothing fills while the
+/// next action is computed
+#[derive(Copy, Clone, Debug)]
+pub struct ExpiryManager {
+	/// How long to wait for the cancel to show in the account
+	pub confirm_timeout: Duration,
+	pub poll_interval: Duration,
+}
+
+impl Default for ExpiryManager {
+	fn default() -> Self {
+		Self {
+			confirm_timeout: Duration::from_secs(30),
+			poll_interval: Duration::from_secs(1),
+		}
+	}
+}
+
+impl ExpiryManager {
+	/// Cancels every order on `market` and returns the cancel signature, None when nothing was open.
+	/// Errors when orders are still open after `confirm_timeout`
+	pub fn cancel_and_confirm<C: MangoClientApi>(&self, mango_client: &C, market: &PerpMarketData, clock: &dyn Clock) -> MangolResult<Option<String>> {
+		let open = match mango_client.fetch_mango_account()? {
+			Some(mango_account) => has_open_orders(&mango_account, market.market_index),
+			None => has_open_orders(mango_client.mango_account(), market.market_index)
+		};
+		if !open {
+			return Ok(None);
+		}
+		let signature = mango_client.cancel_all_perp_orders(market)?;
+		let started = clock.now_millis();
+		loop {
+			if let Some(mango_account) = mango_client.fetch_mango_account()? {
+				if !has_open_orders(&mango_account, market.market_index) {
+					return Ok(Some(signature));
+				}
+			}
+			if clock.now_millis() - started >= self.confirm_timeout.as_millis() as u64 {
+				return Err(MangolError::MangoError(format!("Orders on {} still open {:?} after cancelling", market.name, self.confirm_timeout)));
+			}
+			clock.sleep(self.poll_interval);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+	use mangol_common::clock::SimulatedClock;
+	use mangol_mango::mock::{sol_perp, MockMangoClient};
+	use crate::expiry::{has_open_orders, ExpiryManager};
+
+	#[test]
+	fn cancels_only_open_orders_and_waits_for_them() {
+		let clock = SimulatedClock::new(1_000);
+		let manager = ExpiryManager { confirm_timeout: Duration::from_secs(5), poll_interval: Duration::from_secs(1) };
+		let mut mango_client = MockMangoClient::new(3, 10_000_000, 100);
+		assert_eq!(manager.cancel_and_confirm(&mango_client, &sol_perp(), &clock).unwrap(), None);
+		assert_eq!(mango_client.cancel_all_count.get(), 0);
+
+		mango_client.mango_account.orders[0] = 7;
+		mango_client.mango_account.order_market[0] = 3;
+		assert!(has_open_orders(&mango_client.mango_account, 3));
+		assert!(!has_open_orders(&mango_client.mango_account, 2));
+		// the mock never applies the cancel, so it is never confirmed
+		assert!(manager.cancel_and_confirm(&mango_client, &sol_perp(), &clock).is_err());
+		assert_eq!(mango_client.cancel_all_count.get(), 1);
+	}
+}
@@ -0,0 +1,90 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, RwLock};
+
+use mangol_mango::book::OrderBook;
+use serde::{Deserialize, Serialize};
+
+/// Top of the book a decision saw, ui price
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct BookSummary {
+	pub best_bid: Option<f64>,
+	pub best_ask: Option<f64>,
+	/// (bids - asks) / (bids + asks) within 50 bps of the mid
+	pub imbalance: Option<f64>,
+}
+
+impl BookSummary {
+	/// `ui_price` converts a lot price to ui price
+	pub fn new<F: Fn(i64) -> f64>(book: &OrderBook, ui_price: F) -> Self {
+		Self {
+			best_bid: book.best_bid().map(&ui_price),
+			best_ask: book.best_ask().map(&ui_price),
+			imbalance: book.imbalance(50.0),
+		}
+	}
+}
+
+/// What the strategy looked at when deciding, ui units
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct DecisionInputs {
+	pub oracle_price: f64,
+	pub reference_price: f64,
+	/// None without a committed level to average
+	pub average_price: Option<f64>,
+	/// Negative when short
+	pub base_position: f64,
+	pub depth: u16,
+	pub furthest_position: u16,
+	pub init_health: Option<f64>,
+	pub book: Option<BookSummary>,
+}
+
+/// Why a decision round ended the way it did, for operators rather than the replay
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct DecisionExplanation {
+	pub timestamp: u64,
+	pub inputs: DecisionInputs,
+	/// The rule or filter that decided the round
+	pub rule: String,
+	/// What was done about it, "none" when nothing was sent
+	pub action: String,
+}
+
+impl DecisionExplanation {
+	/// One line for the console and log sinks
+	pub fn summary(&self) -> String {
+		format!("{} -> {} (oracle {}, average {}, depth {})",
+			self.rule,
+			self.action,
+			self.inputs.oracle_price,
+			self.inputs.average_price.map(|price| price.to_string()).unwrap_or_else(|| "-".to_string()),
+			self.inputs.depth)
+	}
+}
+
+/// The latest explanations of a running strategy, shared with whatever serves them to operators
+#[derive(Clone, Debug)]
+pub struct ExplanationLog {
+	pub capacity: usize,
+	explanations: Arc<RwLock<VecDeque<DecisionExplanation>>>,
+}
+
+impl ExplanationLog {
+	/// Keeps the newest `capacity` explanations
+	pub fn new(capacity: usize) -> Self {
+		Self { capacity, explanations: Arc::new(RwLock::new(VecDeque::with_capacity(capacity))) }
+	}
+
+	pub fn push(&self, explanation: DecisionExplanation) {
+		let mut explanations = self.explanations.write().unwrap();
+		if explanations.len() == self.capacity {
+			explanations.pop_front();
+		}
+		explanations.push_back(explanation);
+	}
+
+	/// Oldest first
+	pub fn recent(&self) -> Vec<DecisionExplanation> {
+		self.explanations.read().unwrap().iter().cloned().collect()
+	}
+}
@@ -0,0 +1,221 @@
+use mangol_mango::types::Side;
+use serde::{Deserialize, Serialize};
+
+use crate::fib_trader::{PriceSide, RISK_TOLERANCE, PROFIT_PRICE_DEPTH};
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum FibStratOrderState {
+	Filled,
+	PartiallyFilled,
+	Waiting,
+	Initial
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FibStratOrder {
+	pub(crate) depth: u16,
+	pub(crate) state: FibStratOrderState,
+	pub(crate) price: f64,
+	pub(crate) base_size: u64,
+	pub(crate) tx_hash: Option<String>,
+	/// Fib levels batched into this order, the deepest one is `depth`
+	#[serde(default = "single_leg")]
+	pub(crate) legs: u16
+}
+
+fn single_leg() -> u16 {
+	1
+}
+
+/// State of the fib strategy. Transitions are pure and return the Actions FibStrat applies,
+/// so new states can be added and tested without a mango client
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum FibState {
+	Selling(FibStratOrder),
+	Buying(FibStratOrder),
+	Neutral
+}
+
+/// Which rung of the ladder an order is for
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Leg {
+	/// Add to the position at the next depth
+	ScaleIn,
+	/// Close part of the position back towards the average price
+	TakeProfit,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OrderIntent {
+	pub leg: Leg,
+	pub side: Side,
+	pub depth: u16,
+	/// Levels batched into the order, ending at `depth`
+	pub legs: u16,
+	pub reduce_only: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+	/// The position reached this depth
+	ReachDepth(u16),
+	/// Push a synced order to the position history
+	Commit(FibState),
+	/// Replace the current state
+	Transition(FibState),
+	/// Price, size and place an order, then wait on it
+	Place(OrderIntent),
+}
+
+impl FibState {
+	/// Fresh state for a position about to be opened with a market order
+	pub fn initial(sentiment: PriceSide) -> Self {
+		let order = FibStratOrder {
+			depth: 1,
+			state: FibStratOrderState::Initial,
+			price: 0.0,
+			base_size: 0,
+			tx_hash: None,
+			legs: 1
+		};
+		match sentiment {
+			PriceSide::Sell => FibState::Selling(order),
+			PriceSide::Buy => FibState::Buying(order),
+		}
+	}
+
+	/// Waiting on an order placed for `intent`
+	pub fn waiting(intent: &OrderIntent, price: f64, tx_hash: String) -> Self {
+		let order = FibStratOrder {
+			depth: intent.depth,
+			state: FibStratOrderState::Waiting,
+			price,
+			tx_hash: Some(tx_hash),
+			base_size: 0,
+			legs: intent.legs
+		};
+		match intent.side {
+			Side::Ask => FibState::Selling(order),
+			Side::Bid => FibState::Buying(order),
+		}
+	}
+
+	pub fn order(&self) -> Option<&FibStratOrder> {
+		match self {
+			FibState::Selling(order) | FibState::Buying(order) => Some(order),
+			FibState::Neutral => None
+		}
+	}
+
+	/// Bearish sync transition, compares the order waited on with how much base actually filled.
+	/// Sells scale into the short, buys take profit on it and bring the depth back down
+	pub fn on_bearish_fill(&self, expected_base_filled: i64, actual_base_filled: i64) -> Vec<Action> {
+		if actual_base_filled == 0 {
+			// order was not filled
+			return vec![];
+		}
+		let partial = expected_base_filled > actual_base_filled;
+		match self {
+			FibState::Selling(order) => {
+				let mut order = order.clone();
+				order.base_size = actual_base_filled as u64;
+				order.state = if partial { FibStratOrderState::PartiallyFilled } else { FibStratOrderState::Filled };
+				vec![Action::ReachDepth(order.depth), Action::Commit(FibState::Selling(order))]
+			}
+			FibState::Buying(order) => {
+				if partial && order.depth == 1 {
+					return vec![Action::Transition(FibState::Neutral)];
+				}
+				let mut actions = vec![Action::ReachDepth(order.depth)];
+				let closed = order.depth <= 1;
+				let mut order = order.clone();
+				order.base_size = actual_base_filled as u64;
+				order.state = FibStratOrderState::Filled;
+				// a full take profit closed the previous 1 sell or the last RISK_TOLERANCE depths,
+				// a partial one only the deepest
+				order.depth = match (partial, order.depth) {
+					(_, depth) if depth <= 1 => 1,
+					(true, depth) if depth > RISK_TOLERANCE => depth - 1,
+					(true, depth) => depth,
+					(false, depth) if depth > RISK_TOLERANCE => depth - RISK_TOLERANCE,
+					(false, depth) => depth - 1,
+				};
+				if closed {
+					println!("Last order for position filled, setting to Neutral state");
+					actions.push(Action::Transition(FibState::Neutral));
+				}
+				actions.push(Action::Commit(FibState::Buying(order)));
+				actions
+			}
+			FibState::Neutral => vec![]
+		}
+	}
+
+	/// Bearish decision transition from the last committed state: scale in while the oracle is
+	/// above the average short price, take profit below it
+	pub fn on_bearish_decision(&self, oracle_price: f64, average_price: f64) -> Vec<Action> {
+		let order = match self.order() {
+			Some(order) => order,
+			None => return vec![]
+		};
+		let intent = if oracle_price > average_price {
+			OrderIntent { leg: Leg::ScaleIn, side: Side::Ask, depth: order.depth + 1, legs: 1, reduce_only: order.depth == 0 }
+		} else {
+			OrderIntent { leg: Leg::TakeProfit, side: Side::Bid, depth: order.depth, legs: 1, reduce_only: order.depth == 1 }
+		};
+		vec![Action::Place(intent)]
+	}
+}
+
+/// Whether a position change made the position bigger or flipped its side,
+/// which a take profit should never do
+pub fn increased_exposure(base_position_before: i64, base_position_after: i64) -> bool {
+	base_position_after.abs() > base_position_before.abs() || base_position_before.signum() * base_position_after.signum() < 0
+}
+
+/// Depth of the fib price target for a take profit, closer to the average the deeper the position
+pub fn take_profit_price_depth(depth: u16, furthest_position: u16) -> u16 {
+	if depth >= PROFIT_PRICE_DEPTH || (depth == 1 && furthest_position > RISK_TOLERANCE) {
+		1
+	} else {
+		PROFIT_PRICE_DEPTH - depth
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use mangol_mango::types::Side;
+	use crate::fib_state::{increased_exposure, Action, FibState, FibStratOrder, FibStratOrderState, Leg, OrderIntent};
+
+	fn order(depth: u16, state: FibStratOrderState, base_size: u64) -> FibStratOrder {
+		FibStratOrder { depth, state, price: 0.04, base_size, tx_hash: None, legs: 1 }
+	}
+
+	#[test]
+	fn full_take_profit_steps_depth_back() {
+		let waiting = FibState::Buying(order(4, FibStratOrderState::Waiting, 0));
+		assert_eq!(waiting.on_bearish_fill(100, 100), vec![
+			Action::ReachDepth(4),
+			Action::Commit(FibState::Buying(order(2, FibStratOrderState::Filled, 100)))
+		]);
+		let last = FibState::Buying(order(1, FibStratOrderState::Waiting, 0));
+		assert_eq!(last.on_bearish_fill(100, 100)[1], Action::Transition(FibState::Neutral));
+		assert_eq!(last.on_bearish_fill(100, 40), vec![Action::Transition(FibState::Neutral)]);
+	}
+
+	#[test]
+	fn decides_leg_from_average() {
+		let filled = FibState::Selling(order(2, FibStratOrderState::Filled, 100));
+		assert_eq!(filled.on_bearish_decision(0.041, 0.04), vec![Action::Place(OrderIntent { leg: Leg::ScaleIn, side: Side::Ask, depth: 3, legs: 1, reduce_only: false })]);
+		assert_eq!(filled.on_bearish_decision(0.039, 0.04), vec![Action::Place(OrderIntent { leg: Leg::TakeProfit, side: Side::Bid, depth: 2, legs: 1, reduce_only: false })]);
+		assert!(FibState::Neutral.on_bearish_decision(0.039, 0.04).is_empty());
+	}
+
+	#[test]
+	fn detects_take_profit_adding_exposure() {
+		assert!(!increased_exposure(-100, -40));
+		assert!(!increased_exposure(-100, 0));
+		assert!(increased_exposure(-100, -140));
+		assert!(increased_exposure(-100, 20));
+	}
+}
@@ -2,94 +2,251 @@
 	// https://play.rust-lang.org/?version=stable&mode=debug&edition=2021&gist=32e2e59946ca35ed2b31d2272b4f7823
 	// https://play.rust-lang.org/?version=stable&mode=debug&edition=2021&gist=32e2e59946ca35ed2b31d2272b4f7823
 use std::cmp::max;
-use mangol_common::errors::MangolResult;
+use mangol_common::errors::{MangolError, MangolResult};
 use mangol_solana::{Token, TokenMint};
-use mangol_mango::client::MangoClient;
-use mangol_mango::types::{OrderType, PerpAccount, PerpMarket, PerpMarketData, PerpMarketInfo, Side, MangoAccount};
+use mangol_solana::network::{NetworkMonitor, NetworkStatus};
+use mangol_solana::audit::AuditLog;
+use serde_json::json;
+use mangol_mango::client::{MangoClient, MangoClientApi};
+use mangol_mango::fees::FeeModel;
+use mangol_mango::sizing::{OrderSizer, Rounding, SizingPolicy};
+use mangol_mango::stream::OwnAccountEvent;
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+use mangol_common::clock::{Clock, SystemClock};
+use mangol_mango::logs::{average_fill, parse_logs, MangoLogEvent};
+use mangol_mango::ids::wrapped_sol;
+use mangol_mango::types::{HealthType, OrderType, PerpAccount, PerpMarket, PerpMarketData, PerpMarketInfo, Side, MangoAccount};
+use fixed::types::I80F48;
 use num_traits::pow::Pow;
 use solana_sdk::pubkey::Pubkey;
 use std::time::Duration;
 use colored::Colorize;
 use solana_sdk::signature::Signature;
-use std::time::Instant;
 use std::str::FromStr;
 	use std::thread::sleep;
 	use solana_transaction_status::UiTransactionEncoding;
 	use solana_sdk::commitment_config::CommitmentConfig;
-#[derive(Copy, Clone, Debug)]
+	use serde::{Deserialize, Serialize};
+	use crate::replay::{EquitySnapshot, ExecutionRecord, ExpenseRecord, SessionRecorder};
+	use crate::schedule::TradingSchedule;
+	use crate::halt::{HaltDetector, MarketActivity, MarketHalt};
+	use crate::reconcile::{LedgerPosition, ReconciliationAlarm};
+	use crate::activity::{ActivitySnapshot, DeadMarketFilter, MarketStats};
+	use crate::quarantine::RetryBudget;
+	use crate::leader::{LeaderElection, Leadership};
+	#[cfg(feature = "backtest")]
+	use crate::shadow::{ShadowReport, ShadowRun};
+	#[cfg(feature = "backtest")]
+	use crate::market_data::L2Snapshot;
+	use mangol_mango::incentives::{IncentiveEstimator, IncentivePlacer};
+	use mangol_mango::interest::InterestTracker;
+	use mangol_mango::book::OrderBook;
+	use mangol_mailer::notification::{Notification, Notifier};
+	use mangol_mailer::shipping::LogShipper;
+	use solana_sdk::signature::Keypair;
+	use crate::dashboard::{open_orders, BotState, FillState, LadderLevel, StateBroadcaster};
+	use crate::bus::{BusEvent, EventBus};
+	use std::collections::VecDeque;
+	use crate::risk::RiskManager;
+	use crate::liquidity::LiquidityHistory;
+	use crate::explain::{BookSummary, DecisionExplanation, DecisionInputs, ExplanationLog};
+	use crate::watchdog::Heartbeats;
+	use crate::kill_switch::KillSwitch;
+	use crate::signer_rotation::{is_signature_failure, SignerRotation};
+	use mangol_solana::keystore::KeyStore;
+	use crate::dead_man::DeadMansSwitch;
+	use crate::expiry::ExpiryManager;
+	use crate::timing::RoundTiming;
+	use crate::position_transfer::{PositionExport, POSITION_EXPORT_VERSION};
+	use std::path::PathBuf;
+	use crate::strategy::Strategy;
+	use crate::fib_state::{increased_exposure, take_profit_price_depth, Action, FibState, FibStratOrder, FibStratOrderState, Leg, OrderIntent};
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub enum PriceSide {
 	Sell,
 	Buy
 }
 
+/// Delays scale-ins while the top of the book leans hard against the order
+#[derive(Copy, Clone, Debug)]
+pub struct ImbalanceFilter {
+	pub within_bps: f64,
+	/// Imbalance in [0, 1] against the order side above which the scale-in waits a round
+	pub max_adverse_imbalance: f64,
+}
+
+/// Splits market entries and exits the book can't absorb at once into IOC clips
+#[derive(Copy, Clone, Debug)]
+pub struct EntryImpactLimit {
+	/// Expected slippage of the average fill from the best price above which the order is split
+	pub max_impact_bps: f64,
+	pub max_clips: usize,
+	/// Wait between clips for the book to refill
+	pub clip_interval: Duration,
+}
+
+/// Price fib targets are computed against
 #[derive(Copy, Clone, Debug, PartialEq)]
-pub enum FibStratOrderState {
-	Filled,
-	PartiallyFilled,
-	Waiting,
-	Initial
+pub enum ReferencePrice {
+	/// Price in the MangoCache, can lag the market by a few hundred ms
+	Oracle,
+	/// Size weighted mid of the live book, falls back to the oracle when a side is empty
+	Microprice,
 }
 
-#[derive(Clone, Debug, PartialEq)]
-pub struct FibStratOrder {
-	depth: u16,
-	state: FibStratOrderState,
-	price: f64,
-	base_size: u64,
-	tx_hash: Option<String>
-	
+/// What to do with a PostOnly order whose price would cross the live book
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PostOnlyCrossPolicy {
+	/// Move the price one tick inside the opposite best
+	OneTickInside,
+	/// Keep the price and let the program slide it with PostOnlySlide
+	Slide,
+}
+
+/// Quote amount the first fib level is sized from, deeper levels scale it by FIB_RATIO
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TradeAmount {
+	/// UI quote, e.g. 30.0 USDC
+	Fixed(f64),
+	/// Fraction of account equity, re-evaluated every time a new position is opened
+	EquityFraction(f64),
 }
 
-#[derive(Clone, Debug, PartialEq)]
-pub enum FibStratPositionState {
-	Selling(FibStratOrder),
-	Buying(FibStratOrder),
-	Neutral
+/// Shape of the fib ladder: level n moves `price_fib_ratio * fib_ratio^n` percent away from the
+/// average price and is sized `fib_ratio^n` times the trade amount
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FibParams {
+	pub fib_ratio: f64,
+	pub price_fib_ratio: f64,
 }
-#[derive( Clone, Debug)]
+
+impl Default for FibParams {
+	fn default() -> Self {
+		Self { fib_ratio: FIB_RATIO, price_fib_ratio: PRICE_FIB_RATIO }
+	}
+}
+
+#[derive( Clone, Debug, Serialize, Deserialize)]
 pub struct FibStratPosition {
-	pub state_history: Vec<FibStratPositionState>,
-	pub current_state: FibStratPositionState,
+	pub state_history: Vec<FibState>,
+	pub current_state: FibState,
 	pub max_position_depth: u16,
 	pub furthest_position: u16,
 	pub starting_position_size: f64,
 }
-pub struct FibStrat {
+impl FibStratPosition {
+	/// Signed base lots the committed ladder adds up to, negative when short
+	pub fn base_size(&self) -> i64 {
+		self.state_history.iter().map(|state| match state {
+			FibState::Selling(order) => -(order.base_size as i64),
+			FibState::Buying(order) => order.base_size as i64,
+			FibState::Neutral => 0
+		}).sum()
+	}
+}
+
+pub struct FibStrat<C: MangoClientApi = MangoClient> {
 	pub position: FibStratPosition,
 	pub action_interval_secs: u64,
-	pub mango_client: MangoClient,
+	pub mango_client: C,
 	pub starting_sentiment: PriceSide,
 	pub market: PerpMarketData,
-	pub sentiment: PriceSide
+	pub sentiment: PriceSide,
+	pub recorder: Option<SessionRecorder>,
+	/// Interest indexes when recording started, for the interest part of the equity snapshots
+	pub interest_tracker: Option<InterestTracker>,
+	pub imbalance_filter: Option<ImbalanceFilter>,
+	pub schedule: Option<TradingSchedule>,
+	pub post_only_policy: Option<PostOnlyCrossPolicy>,
+	pub network_monitor: Option<NetworkMonitor>,
+	pub risk_manager: Option<RiskManager>,
+	/// Own account events, replaces polling the account while waiting on an order
+	pub account_events: Option<Receiver<OwnAccountEvent>>,
+	pub kill_switch: Option<KillSwitch>,
+	/// Where `mangol rotate-signer` requests are picked up from
+	pub signer_rotation: Option<SignerRotation>,
+	/// Forces take profits reduce-only and alerts when one still added exposure
+	pub ensure_reduce_only: bool,
+	/// Most fib levels a scale-in catches up on when the oracle gapped past them
+	pub max_batched_levels: Option<u16>,
+	pub clock: Arc<dyn Clock>,
+	/// Rounding of fib sizes to quote lots and the smallest order placed
+	pub sizing_policy: SizingPolicy,
+	/// Shared with the client's connection, gets the decision behind each order
+	pub audit_log: Option<AuditLog>,
+	/// Set while a schedule window is active and orders have been cancelled
+	pub standing_down: bool,
+	/// Cancels the market's orders after losing both rpc and websocket
+	pub dead_mans_switch: Option<DeadMansSwitch>,
+	/// Position export rewritten after every decision
+	pub state_file: Option<PathBuf>,
+	pub trade_amount: TradeAmount,
+	/// UI quote amount `trade_amount` resolved to for the current position
+	pub base_trade_amount: f64,
+	/// Cancels resting orders before each decision round instead of relying on their expiry
+	pub expiry_manager: Option<ExpiryManager>,
+	/// Alignment and jitter of the wait between decision rounds
+	pub round_timing: RoundTiming,
+	pub entry_impact: Option<EntryImpactLimit>,
+	/// Book snapshots take profit targets are pushed deeper with, until one rests where the size fits
+	pub profit_liquidity: Option<LiquidityHistory>,
+	pub reference_price: ReferencePrice,
+	/// Why the latest decision round ended the way it did
+	pub last_explanation: Option<DecisionExplanation>,
+	/// Earlier explanations too, for the control api
+	pub explanation_log: Option<ExplanationLog>,
+	/// Benchmarks of the order being waited on, recorded once it fills
+	pub pending_execution: Option<ExecutionRecord>,
+	pub halt_detector: Option<HaltDetector>,
+	/// Set while the market looks halted and orders have been cancelled
+	pub market_halt: Option<MarketHalt>,
+	/// Compares the ledger with the perp account, pauses while they disagree when set to halt
+	pub reconciliation: Option<ReconciliationAlarm>,
+	/// Open interest, fees and volume of the market, sampled by a MarketStatsTracker
+	pub market_stats: Option<MarketStats>,
+	/// Holds off opening a new position while the market is this quiet
+	pub dead_market_filter: Option<DeadMarketFilter>,
+	/// Backs off orders that fail to land and quarantines them once the budget is spent
+	pub retry_budget: Option<RetryBudget>,
+	/// A parameter variant decided on paper next to the live ladder, for comparing before promoting it
+	#[cfg(feature = "backtest")]
+	pub shadow: Option<ShadowRun>,
+	/// Moves take profits from their fib target toward the depth earning the most MNGO
+	pub incentive_placer: Option<IncentivePlacer>,
+	/// The perp market's liquidity mining state and when it was loaded, refreshed every INCENTIVE_MARKET_REFRESH_SECS
+	incentive_market: Option<(u64, PerpMarket)>,
+	/// Trades only while holding the lease, standing by for another instance otherwise
+	pub leader_election: Option<LeaderElection>,
+	pub notifier: Notifier,
+	/// Beaten every round and every second of the wait, a watchdog alerts when it stops
+	pub heartbeats: Option<Heartbeats>,
+	/// Dashboards the state is pushed to every round
+	pub state_broadcaster: Option<StateBroadcaster>,
+	/// Remote sink the round's position, health and equity are shipped to as metrics
+	pub log_shipper: Option<LogShipper>,
+	/// Prices, fills, health and decisions are published here for other subsystems
+	pub event_bus: Option<EventBus>,
+	/// Latest own fills for dashboards, newest last
+	pub recent_fills: VecDeque<FillState>,
+	pub fib_params: FibParams
 }
 
+pub const FIB_STRATEGY_NAME: &str = "fib";
 const FIB_RATIO: f64 = 1.618;
 const PRICE_FIB_RATIO: f64 = 0.1618;
 const TRADE_AMOUNT: f64 = 30.0;
-const RISK_TOLERANCE: u16 = 2;
-const PROFIT_PRICE_DEPTH: u16 = 6;
-impl FibStrat {
-	pub fn new(max_position_depth: u16, action_interval_secs: u64, mango_client: MangoClient, sentiment: PriceSide, market: PerpMarketData) -> MangolResult<Self>{
-		let current_state = match sentiment {
-			PriceSide::Sell => {
-				FibStratPositionState::Selling(FibStratOrder {
-					depth: 1,
-					state: FibStratOrderState::Initial,
-					price: 0.0,
-					base_size: 0,
-					tx_hash: None
-				})
-			}
-			PriceSide::Buy => {
-				FibStratPositionState::Buying(FibStratOrder {
-					depth: 1,
-					state: FibStratOrderState::Initial,
-					price: 0.0,
-					base_size: 0,
-					tx_hash: None
-				})
-			}
-		};
+pub(crate) const RISK_TOLERANCE: u16 = 2;
+pub(crate) const PROFIT_PRICE_DEPTH: u16 = 6;
+/// Annual rate the periodic performance report measures excess returns against
+const RISK_FREE_RATE: f64 = 0.04;
+/// How long the liquidity mining state loaded for incentive placement is reused
+const INCENTIVE_MARKET_REFRESH_SECS: u64 = 60;
+/// Window the traded rate incentive placement models fills with is measured over
+const INCENTIVE_RATE_WINDOW_SECS: u64 = 60 * 60;
+impl<C: MangoClientApi> FibStrat<C> {
+	pub fn new(max_position_depth: u16, action_interval_secs: u64, mango_client: C, sentiment: PriceSide, market: PerpMarketData) -> MangolResult<Self>{
+		let current_state = FibState::initial(sentiment);
 		Ok(Self {
 			position: FibStratPosition {
 				state_history: vec![],
@@ -104,104 +261,1232 @@ impl FibStrat {
 			starting_sentiment: sentiment,
 			market,
 			sentiment,
+			recorder: None,
+			interest_tracker: None,
+			imbalance_filter: None,
+			schedule: None,
+			post_only_policy: None,
+			network_monitor: None,
+			risk_manager: None,
+			account_events: None,
+			kill_switch: None,
+			signer_rotation: None,
+			max_batched_levels: None,
+			ensure_reduce_only: false,
+			clock: Arc::new(SystemClock),
+			sizing_policy: SizingPolicy::default(),
+			audit_log: None,
+			standing_down: false,
+			dead_mans_switch: None,
+			state_file: None,
+			trade_amount: TradeAmount::Fixed(TRADE_AMOUNT),
+			base_trade_amount: TRADE_AMOUNT,
+			expiry_manager: None,
+			round_timing: RoundTiming::default(),
+			entry_impact: None,
+			profit_liquidity: None,
+			reference_price: ReferencePrice::Oracle,
+			last_explanation: None,
+			explanation_log: None,
+			pending_execution: None,
+			halt_detector: None,
+			market_halt: None,
+			reconciliation: None,
+			market_stats: None,
+			dead_market_filter: None,
+			retry_budget: None,
+			incentive_placer: None,
+			incentive_market: None,
+			leader_election: None,
+			#[cfg(feature = "backtest")]
+			shadow: None,
+			notifier: Notifier::default(),
+			heartbeats: None,
+			state_broadcaster: None,
+			log_shipper: None,
+			event_bus: None,
+			recent_fills: VecDeque::new(),
+			fib_params: FibParams::default(),
+		})
+	}
+	
+	/// Ladder ratios other than the defaults, e.g. the best set found by the optimizer
+	pub fn with_fib_params(mut self, fib_params: FibParams) -> Self {
+		self.fib_params = fib_params;
+		self.position.starting_position_size = fib_params.fib_ratio;
+		self
+	}
+	
+	/// Time source for the trading loop, a SimulatedClock fast-forwards through sleeps
+	pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+		self.clock = clock;
+		self
+	}
+	
+	pub fn with_trade_amount(mut self, trade_amount: TradeAmount) -> Self {
+		self.trade_amount = trade_amount;
+		if let TradeAmount::Fixed(amount) = trade_amount {
+			self.base_trade_amount = amount;
+		}
+		self
+	}
+	
+	/// Resolves `trade_amount` against the current equity, sizes stay fixed until the next position
+	pub fn refresh_trade_amount(&mut self) -> MangolResult<f64> {
+		self.base_trade_amount = match self.trade_amount {
+			TradeAmount::Fixed(amount) => amount,
+			TradeAmount::EquityFraction(fraction) => {
+				let equity = self.mango_client.get_equity()?.to_num::<f64>() / 10f64.powi(self.market.quote_decimals as i32);
+				equity.max(0.0) * fraction
+			}
+		};
+		Ok(self.base_trade_amount)
+	}
+	
+	pub fn with_sizing_policy(mut self, sizing_policy: SizingPolicy) -> Self {
+		self.sizing_policy = sizing_policy;
+		self
+	}
+	
+	pub fn with_audit_log(mut self, audit_log: AuditLog) -> Self {
+		self.audit_log = Some(audit_log);
+		self
+	}
+	
+	pub fn with_ensure_reduce_only(mut self) -> Self {
+		self.ensure_reduce_only = true;
+		self
+	}
+	
+	/// Templates and units of the strategy's alerts
+	pub fn with_notifier(mut self, notifier: Notifier) -> Self {
+		let shipper = notifier.shipper.clone().or_else(|| self.notifier.shipper.take());
+		self.notifier = Notifier { shipper, ..notifier };
+		self
+	}
+	
+	pub fn with_state_broadcaster(mut self, state_broadcaster: StateBroadcaster) -> Self {
+		self.state_broadcaster = Some(state_broadcaster);
+		self
+	}
+
+	pub fn with_event_bus(mut self, event_bus: EventBus) -> Self {
+		self.event_bus = Some(event_bus);
+		self
+	}
+
+	pub fn with_explanation_log(mut self, explanation_log: ExplanationLog) -> Self {
+		self.explanation_log = Some(explanation_log);
+		self
+	}
+
+	/// Ships notifications as log lines along with the round metrics
+	pub fn with_log_shipper(mut self, log_shipper: LogShipper) -> Self {
+		self.notifier = self.notifier.with_shipper(log_shipper.clone());
+		self.log_shipper = Some(log_shipper);
+		self
+	}
+	
+	/// Position, orders, health and the latest decisions as dashboards show them
+	pub fn bot_state(&self, now_ts: u64) -> BotState {
+		let perp_market_info = self.market.perp_market_info(self.mango_client.mango_group());
+		let ui_quote = |health: I80F48| health.to_num::<f64>() / 10_f64.powi(self.market.quote_decimals as i32);
+		// one load of the account's open orders for all three
+		let healths = self.mango_client.get_healths().ok();
+		let paused = if self.is_killed() {
+			Some("kill switch engaged".to_string())
+		} else if let Some(halt) = self.market_halt {
+			Some(format!("market halted {:?}", halt))
+		} else if self.reconciliation.as_ref().map(|alarm| alarm.halt && alarm.divergence.is_some()).unwrap_or(false) {
+			Some("ledger diverged from chain".to_string())
+		} else if self.standing_down {
+			Some("scheduled stand down".to_string())
+		} else if let Some(quarantined) = self.quarantined_order() {
+			Some(format!("order quarantined, {}", quarantined))
+		} else {
+			None
+		};
+		BotState {
+			strategy: FIB_STRATEGY_NAME.to_string(),
+			market: self.market.name.clone(),
+			timestamp: now_ts,
+			oracle_price: self.market.ui_price(self.market.oracle_price(self.mango_client.mango_cache())),
+			base_position: self.ui_base_size(self.market.perp_account(self.mango_client.mango_account()).base_position),
+			open_orders: open_orders(&self.market, perp_market_info, self.mango_client.mango_account()),
+			init_health: healths.map(|healths| ui_quote(healths.init)),
+			maint_health: healths.map(|healths| ui_quote(healths.maint)),
+			equity: healths.map(|healths| ui_quote(healths.equity)),
+			current_state: self.position.current_state.clone(),
+			last_decisions: self.position.state_history.iter().rev().take(5).rev().cloned().collect(),
+			ladder: self.position.state_history.iter().chain(std::iter::once(&self.position.current_state))
+				  .filter_map(|state| self.ladder_level(state))
+				  .collect(),
+			recent_fills: self.recent_fills.iter().cloned().collect(),
+			paused,
+			last_explanation: self.last_explanation.clone(),
+		}
+	}
+	
+	fn ladder_level(&self, state: &FibState) -> Option<LadderLevel> {
+		let (side, order) = match state {
+			FibState::Selling(order) => ("sell", order),
+			FibState::Buying(order) => ("buy", order),
+			FibState::Neutral => return None
+		};
+		Some(LadderLevel {
+			side: side.to_string(),
+			depth: order.depth,
+			price: self.market.ui_price(order.price),
+			size: self.ui_base_size(order.base_size as i64),
+			state: format!("{:?}", order.state),
+		})
+	}
+	
+	fn publish_state(&self, now_ts: u64) {
+		if self.state_broadcaster.is_none() && self.log_shipper.is_none() && self.event_bus.is_none() {
+			return;
+		}
+		let state = self.bot_state(now_ts);
+		if let Some(event_bus) = &self.event_bus {
+			event_bus.publish(BusEvent::Price { market: state.market.clone(), oracle_price: state.oracle_price, timestamp: now_ts });
+			event_bus.publish(BusEvent::Health {
+				strategy: state.strategy.clone(),
+				market: state.market.clone(),
+				init_health: state.init_health,
+				maint_health: state.maint_health,
+				equity: state.equity,
+				timestamp: now_ts,
+			});
+			event_bus.publish(BusEvent::Decision {
+				strategy: state.strategy.clone(),
+				market: state.market.clone(),
+				state: state.current_state.clone(),
+				paused: state.paused.clone(),
+				timestamp: now_ts,
+			});
+		}
+		if let Some(state_broadcaster) = &self.state_broadcaster {
+			state_broadcaster.publish(&state);
+		}
+		if let Some(log_shipper) = &self.log_shipper {
+			let metric = |name: &str| format!("{}_{}_{}", FIB_STRATEGY_NAME, state.market, name);
+			log_shipper.metric(&metric("oracle_price"), state.oracle_price);
+			log_shipper.metric(&metric("base_position"), state.base_position);
+			log_shipper.metric(&metric("open_orders"), state.open_orders.len() as f64);
+			log_shipper.metric(&metric("paused"), if state.paused.is_some() { 1.0 } else { 0.0 });
+			for (name, value) in [("init_health", state.init_health), ("maint_health", state.maint_health), ("equity", state.equity)] {
+				if let Some(value) = value {
+					log_shipper.metric(&metric(name), value);
+				}
+			}
+		}
+	}
+	
+	/// Keeps why the round ended the way it did with what it was decided on, recorded with the decision
+	fn explain(&mut self, rule: &str, action: String) {
+		let perp_market_info = self.market.perp_market_info(self.mango_client.mango_group());
+		let ui_lot_price = |lot_price: i64| self.market.ui_price(perp_market_info.lots_to_price(lot_price));
+		let explanation = DecisionExplanation {
+			timestamp: self.clock.now_ts(),
+			inputs: DecisionInputs {
+				oracle_price: self.market.ui_price(self.market.oracle_price(self.mango_client.mango_cache())),
+				reference_price: self.market.ui_price(self.reference_price()),
+				average_price: self.get_average_price().ok().filter(|price| price.is_finite()).map(|price| self.market.ui_price(price)),
+				base_position: self.ui_base_size(self.market.perp_account(self.mango_client.mango_account()).base_position),
+				depth: self.position.current_state.order().map(|order| order.depth).unwrap_or(0),
+				furthest_position: self.position.furthest_position,
+				init_health: self.mango_client.get_health(HealthType::Init).ok().map(|health| health.to_num::<f64>() / 10_f64.powi(self.market.quote_decimals as i32)),
+				book: self.mango_client.load_order_book(&self.market).ok().map(|book| BookSummary::new(&book, ui_lot_price)),
+			},
+			rule: rule.to_string(),
+			action,
+		};
+		println!("Decision: {}", explanation.summary());
+		if let Some(explanation_log) = &self.explanation_log {
+			explanation_log.push(explanation.clone());
+		}
+		self.last_explanation = Some(explanation);
+	}
+	
+	/// Base lots of the strategy's market in UI base
+	fn ui_base_size(&self, base_lots: i64) -> f64 {
+		self.market.ui_base_size(self.market.perp_market_info(self.mango_client.mango_group()), base_lots)
+	}
+	
+	/// Alerts when a take profit fill grew or flipped the position
+	pub fn audit_take_profit(&self, base_position_before: i64, base_position_after: i64) -> bool {
+		if !increased_exposure(base_position_before, base_position_after) {
+			return true;
+		}
+		let message = format!("{} take profit increased exposure, base position {} -> {}", self.market.name, base_position_before, base_position_after);
+		println!("{}", message.red());
+		self.notifier.send(&Notification::PartialFillReversed {
+			market: self.market.name.clone(),
+			position_before: self.ui_base_size(base_position_before),
+			position_after: self.ui_base_size(base_position_after),
+		});
+		false
+	}
+	
+	pub fn with_scale_in_batching(mut self, max_batched_levels: u16) -> Self {
+		self.max_batched_levels = Some(max_batched_levels);
+		self
+	}
+	
+	/// Scale-in levels from `depth` on whose price the oracle already moved past, at least 1
+	pub fn skipped_levels(&self, depth: u16, average_price: f64, oracle_price: f64, direction: i8) -> MangolResult<u16> {
+		let max_levels = match self.max_batched_levels {
+			Some(max_levels) => max_levels.min(self.position.max_position_depth.saturating_sub(depth) + 1),
+			None => return Ok(1)
+		};
+		let mut levels = 0;
+		while levels < max_levels {
+			let level_price = fib_calculator::get_price_at_n(&self.fib_params, depth + levels, average_price, direction)?;
+			if (level_price - oracle_price) * (direction as f64) >= 0.0 {
+				break
+			}
+			levels += 1;
+		}
+		Ok(max(1, levels))
+	}
+	
+	pub fn with_kill_switch(mut self, kill_switch: KillSwitch) -> Self {
+		self.kill_switch = Some(kill_switch);
+		self
+	}
+	
+	pub fn with_signer_rotation(mut self, signer_rotation: SignerRotation) -> Self {
+		self.signer_rotation = Some(signer_rotation);
+		self
+	}
+	
+	/// Switches to `signer`, or fails over to the backup when None, and alerts with `reason`
+	pub fn rotate_signer(&mut self, signer: Option<Keypair>, reason: &str) -> MangolResult<()> {
+		let old_signer = self.mango_client.signer();
+		let new_signer = self.mango_client.rotate_signer(signer)?;
+		println!("{}", format!("Signer rotated to {}: {}", new_signer, reason).yellow());
+		self.notifier.send(&Notification::SignerRotated { market: self.market.name.clone(), old_signer: old_signer.to_string(), new_signer: new_signer.to_string(), reason: reason.to_string() });
+		Ok(())
+	}
+	
+	/// Applies a pending `mangol rotate-signer` request, a key that fails to load or can't sign
+	/// for the account is reported and the current signer kept
+	fn check_signer_rotation(&mut self) {
+		let request = match self.signer_rotation.as_ref().map(|signer_rotation| signer_rotation.take_request()) {
+			Some(Ok(Some(request))) => request,
+			Some(Ok(None)) | None => return,
+			Some(Err(e)) => {
+				eprintln!("[-] Failed to read signer rotation request {:?}", e);
+				return;
+			}
+		};
+		let signer = match &request {
+			Some(keystore) => match KeyStore::load(keystore) {
+				Ok(signer) => Some(signer),
+				Err(e) => {
+					eprintln!("[-] Not rotating, failed to load {} {:?}", keystore, e);
+					return;
+				}
+			},
+			None => None
+		};
+		let reason = request.map(|keystore| format!("requested, key from {}", keystore)).unwrap_or_else(|| "requested, backup key".to_string());
+		if let Err(e) = self.rotate_signer(signer, &reason) {
+			eprintln!("[-] Signer rotation failed, keeping the current signer {:?}", e);
+		}
+	}
+	
+	pub fn is_killed(&self) -> bool {
+		self.kill_switch.as_ref().map(|kill_switch| kill_switch.is_engaged()).unwrap_or(false)
+	}
+	
+	pub fn with_account_events(mut self, account_events: Receiver<OwnAccountEvent>) -> Self {
+		self.account_events = Some(account_events);
+		self
+	}
+	
+	/// Whether `event` resolves the order the strategy is waiting on
+	pub fn ends_wait(&self, event: &OwnAccountEvent) -> bool {
+		match event {
+			OwnAccountEvent::OrderFilled { market_index, .. } | OwnAccountEvent::OrderExpired { market_index, .. } => *market_index == self.market.market_index,
+			_ => false
+		}
+	}
+	
+	pub fn with_risk_manager(mut self, risk_manager: RiskManager) -> Self {
+		self.risk_manager = Some(risk_manager);
+		self
+	}
+	
+	/// Whether the risk manager allows adding `quantity` quote lots on `side`, true without one
+	pub fn risk_allows(&self, side: Side, quantity: i64) -> bool {
+		let risk_manager = match &self.risk_manager {
+			Some(risk_manager) => risk_manager,
+			None => return true
+		};
+		let quote_lot_size = self.market.perp_market_info(self.mango_client.mango_group()).quote_lot_size;
+		match risk_manager.check_order(FIB_STRATEGY_NAME, &self.mango_client, self.market.market_index, side, (quantity * quote_lot_size) as f64) {
+			Ok(()) => true,
+			Err(e) => {
+				println!("{}", format!("Order refused by risk manager {:?}", e).red());
+				false
+			}
+		}
+	}
+	
+	pub fn with_network_monitor(mut self, network_monitor: NetworkMonitor) -> Self {
+		self.network_monitor = Some(network_monitor);
+		self
+	}
+	
+	pub fn with_round_timing(mut self, round_timing: RoundTiming) -> Self {
+		self.round_timing = round_timing;
+		self
+	}
+	
+	pub fn with_expiry_manager(mut self, expiry_manager: ExpiryManager) -> Self {
+		self.expiry_manager = Some(expiry_manager);
+		self
+	}
+	
+	pub fn with_dead_mans_switch(mut self, dead_mans_switch: DeadMansSwitch) -> Self {
+		self.dead_mans_switch = Some(dead_mans_switch);
+		self
+	}
+	
+	/// Orders expire after a round, sooner while the dead man's switch sees shaky connectivity
+	pub fn order_expiry_secs(&self) -> u64 {
+		match &self.dead_mans_switch {
+			Some(dead_mans_switch) => dead_mans_switch.order_expiry_secs(self.action_interval_secs),
+			None => self.action_interval_secs
+		}
+	}
+	
+	pub fn network_status(&self) -> NetworkStatus {
+		self.network_monitor.as_ref().map(|monitor| monitor.status()).unwrap_or(NetworkStatus::Healthy)
+	}
+	
+	pub fn with_post_only_policy(mut self, post_only_policy: PostOnlyCrossPolicy) -> Self {
+		self.post_only_policy = Some(post_only_policy);
+		self
+	}
+	
+	/// Checks a PostOnly price against the live book so it isn't rejected for crossing
+	pub fn post_only_price(&self, side: Side, price: f64) -> (f64, OrderType) {
+		if self.post_only_policy.is_none() {
+			return (price, OrderType::PostOnly);
+		}
+		match self.mango_client.load_order_book(&self.market) {
+			Ok(book) => self.post_only_price_in(side, price, &book),
+			Err(e) => {
+				eprintln!("Failed to load book for PostOnly check {:?}", e);
+				(price, OrderType::PostOnly)
+			}
+		}
+	}
+	
+	/// `post_only_price` against a book already loaded
+	pub fn post_only_price_in(&self, side: Side, price: f64, book: &OrderBook) -> (f64, OrderType) {
+		let policy = match self.post_only_policy {
+			Some(policy) => policy,
+			None => return (price, OrderType::PostOnly)
+		};
+		let perp_market_info = self.market.perp_market_info(self.mango_client.mango_group());
+		match book.post_only_lot_price(side, perp_market_info.lot_to_native_price(price)) {
+			Some(lot_price) => match policy {
+				PostOnlyCrossPolicy::OneTickInside => {
+					println!("PostOnly {:?} at {} crosses the book, moving one tick inside", side, price);
+					(perp_market_info.lots_to_price(lot_price), OrderType::PostOnly)
+				}
+				PostOnlyCrossPolicy::Slide => (price, OrderType::PostOnlySlide)
+			},
+			None => (price, OrderType::PostOnly)
+		}
+	}
+	
+	pub fn with_entry_impact(mut self, entry_impact: EntryImpactLimit) -> Self {
+		self.entry_impact = Some(entry_impact);
+		self
+	}
+	
+	pub fn with_heartbeats(mut self, heartbeats: Heartbeats) -> Self {
+		self.heartbeats = Some(heartbeats);
+		self
+	}
+	
+	fn beat(&self) {
+		if let Some(heartbeats) = &self.heartbeats {
+			heartbeats.beat(FIB_STRATEGY_NAME);
+		}
+	}
+	
+	pub fn with_profit_liquidity(mut self, profit_liquidity: LiquidityHistory) -> Self {
+		self.profit_liquidity = Some(profit_liquidity);
+		self
+	}
+	
+	/// Adds this round's book to the take profit liquidity history
+	pub fn observe_liquidity(&mut self) {
+		if self.profit_liquidity.is_none() {
+			return;
+		}
+		match self.mango_client.load_order_book(&self.market) {
+			Ok(book) => self.profit_liquidity.as_mut().unwrap().observe(&book),
+			Err(e) => eprintln!("Failed to load order book for liquidity history {:?}", e)
+		}
+	}
+	
+	/// Shallowest take profit depth from `default_depth` on whose level held resting size comparable
+	/// to `quantity` quote lots, `default_depth` without history or when no level did
+	fn liquid_profit_depth(&self, side: Side, default_depth: u16, average_price: f64, direction: i8, quantity: i64) -> MangolResult<u16> {
+		let history = match &self.profit_liquidity {
+			Some(history) if !history.is_empty() => history,
+			_ => return Ok(default_depth)
+		};
+		let sizer = OrderSizer::new(self.market.perp_market_info(self.mango_client.mango_group()));
+		let mut candidates = vec![];
+		for depth in default_depth..=PROFIT_PRICE_DEPTH.max(default_depth) {
+			let price_lots = sizer.tick_price_lots(fib_calculator::get_price_at_n(&self.fib_params, depth, average_price, direction)?, side, OrderType::PostOnly, self.sizing_policy.tick_rounding)?;
+			candidates.push((depth, price_lots, sizer.base_lots_from_quote_lots(quantity, price_lots, Rounding::Up)?));
+		}
+		match history.shallowest_liquid(side, &candidates) {
+			Some(depth) => {
+				if depth != default_depth {
+					println!("Book too thin at profit depth {}, targeting depth {}", default_depth, depth);
+				}
+				Ok(depth)
+			}
+			None => Ok(default_depth)
+		}
+	}
+	
+	/// Market order of `quantity` quote lots. With an impact limit it is sized in base lots at the
+	/// oracle price and split into clips, their signatures pushed to `signatures` in the order sent
+	fn market_order(&self, side: Side, quantity: i64, reduce_only: bool, signatures: &mut Vec<String>) -> MangolResult<()> {
+		let perp_market = *self.market.perp_market_info(self.mango_client.mango_group());
+		let oracle_price = self.market.oracle_price(self.mango_client.mango_cache());
+		if self.entry_impact.is_none() {
+			signatures.push(self.mango_client.place_perp_order(&perp_market, &self.market, side, oracle_price, quantity, OrderType::Market, reduce_only, None)?);
+			return Ok(());
+		}
+		let sizer = OrderSizer::new(&perp_market);
+		let base_lots = sizer.base_lots_from_quote_lots(quantity, sizer.tick_price_lots(oracle_price, side, OrderType::Market, self.sizing_policy.tick_rounding)?, self.sizing_policy.rounding)?;
+		self.market_order_with_base(side, base_lots, reduce_only, signatures)
+	}
+	
+	/// Market order of `base_lots`, sent as IOC clips at the worst expected price of each when the
+	/// book would move more than the impact limit allows. When a clip fails the ones already sent
+	/// stay in `signatures`
+	fn market_order_with_base(&self, side: Side, base_lots: i64, reduce_only: bool, signatures: &mut Vec<String>) -> MangolResult<()> {
+		let perp_market = *self.market.perp_market_info(self.mango_client.mango_group());
+		let limit = match self.entry_impact {
+			Some(limit) => limit,
+			None => {
+				let oracle_price = self.market.oracle_price(self.mango_client.mango_cache());
+				signatures.push(self.mango_client.place_perp_order_with_base(&perp_market, &self.market, side, oracle_price, base_lots, OrderType::Market, reduce_only, None)?);
+				return Ok(());
+			}
+		};
+		let mut book = self.mango_client.load_order_book(&self.market)?;
+		let clips = book.clip_sizes(side, base_lots, limit.max_impact_bps, limit.max_clips);
+		for (i, clip) in clips.iter().enumerate() {
+			if i > 0 {
+				self.clock.sleep(limit.clip_interval);
+				book = self.mango_client.load_order_book(&self.market)?;
+			}
+			let estimate = match book.estimate_fill(side, *clip) {
+				Some(estimate) => estimate,
+				None => return Err(MangolError::MangoError(format!("No liquidity on {} for a {:?} of {} base lots", self.market.name, side, clip)))
+			};
+			if clips.len() > 1 {
+				println!("{:?} clip {}/{} of {} base lots, expected impact {:.1} bps", side, i + 1, clips.len(), clip, estimate.impact_bps());
+			}
+			signatures.push(self.mango_client.place_perp_order_with_base(
+				&perp_market,
+				&self.market,
+				side,
+				perp_market.lots_to_price(estimate.worst_price),
+				*clip,
+				OrderType::ImmediateOrCancel,
+				reduce_only,
+				None
+			)?);
+		}
+		Ok(())
+	}
+	
+	pub fn with_reference_price(mut self, reference_price: ReferencePrice) -> Self {
+		self.reference_price = reference_price;
+		self
+	}
+	
+	/// Price decisions and fib targets are based on
+	pub fn reference_price(&self) -> f64 {
+		let oracle_price = self.market.oracle_price(self.mango_client.mango_cache());
+		if self.reference_price == ReferencePrice::Oracle {
+			return oracle_price;
+		}
+		match self.mango_client.load_order_book(&self.market).map(|book| book.microprice()) {
+			Ok(Some(microprice)) => self.market.perp_market_info(self.mango_client.mango_group()).lots_to_price(microprice.round() as i64),
+			Ok(None) => oracle_price,
+			Err(e) => {
+				eprintln!("Failed to load book for microprice, using the oracle {:?}", e);
+				oracle_price
+			}
+		}
+	}
+	
+	pub fn with_schedule(mut self, schedule: TradingSchedule) -> Self {
+		self.schedule = Some(schedule);
+		self
+	}
+	
+	/// Cancels resting orders once when entering a schedule window, returns true while inside one
+	pub fn check_schedule(&mut self, now_ts: u64) -> MangolResult<bool> {
+		let in_window = match &self.schedule {
+			Some(schedule) => schedule.is_standing_down(now_ts),
+			None => false
+		};
+		if in_window && !self.standing_down {
+			println!("{}", "Entering scheduled stand down, cancelling orders".yellow());
+			self.mango_client.cancel_all_perp_orders(&self.market)?;
+		} else if !in_window && self.standing_down {
+			println!("{}", "Scheduled stand down ended, resuming".green());
+		}
+		self.standing_down = in_window;
+		Ok(in_window)
+	}
+	
+	pub fn with_halt_detector(mut self, halt_detector: HaltDetector) -> Self {
+		self.halt_detector = Some(halt_detector);
+		self
+	}
+	
+	/// Cancels resting orders and alerts once when the market looks halted, returns true while it does
+	pub fn check_halt(&mut self, now_ts: u64) -> MangolResult<bool> {
+		let detector = match &mut self.halt_detector {
+			Some(detector) => detector,
+			None => return Ok(false)
+		};
+		let perp_market = match self.mango_client.load_perp_market(&self.market) {
+			Ok(perp_market) => perp_market,
+			Err(e) => {
+				// connectivity is the network monitor's call, keep the last verdict
+				eprintln!("Failed to load perp market for halt detection {:?}", e);
+				return Ok(self.market_halt.is_some());
+			}
+		};
+		let halt = detector.observe(&MarketActivity::read(&self.market, self.mango_client.mango_cache(), &perp_market), now_ts);
+		match (self.market_halt, halt) {
+			(None, Some(halt)) => {
+				let message = format!("{} looks halted {:?}, cancelling orders and pausing", self.market.name, halt);
+				println!("{}", message.red());
+				self.notifier.send(&Notification::CircuitBreakerTripped { market: self.market.name.clone(), breaker: "halt detector".to_string(), reason: format!("{:?}", halt) });
+				let signature = self.mango_client.cancel_all_perp_orders(&self.market)?;
+				self.track_expense(&signature);
+			}
+			(Some(_), None) => {
+				let message = format!("{} is trading again, resuming", self.market.name);
+				println!("{}", message.green());
+				self.notifier.send(&Notification::CircuitBreakerCleared { market: self.market.name.clone(), breaker: "halt detector".to_string() });
+			}
+			_ => {}
+		}
+		self.market_halt = halt;
+		Ok(halt.is_some())
+	}
+	
+	pub fn with_market_stats(mut self, market_stats: MarketStats) -> Self {
+		self.market_stats = Some(market_stats);
+		self
+	}
+	
+	pub fn with_dead_market_filter(mut self, dead_market_filter: DeadMarketFilter) -> Self {
+		self.dead_market_filter = Some(dead_market_filter);
+		self
+	}
+	
+	pub fn with_retry_budget(mut self, retry_budget: RetryBudget) -> Self {
+		self.retry_budget = Some(retry_budget);
+		self
+	}
+	
+	#[cfg(feature = "backtest")]
+	pub fn with_shadow(mut self, shadow: ShadowRun) -> Self {
+		self.shadow = Some(shadow);
+		self
+	}
+	
+	#[cfg(feature = "backtest")]
+	pub fn shadow_report(&self) -> Option<ShadowReport> {
+		self.shadow.as_ref().map(|shadow| shadow.report())
+	}
+	
+	/// Feeds the round's oracle price and book to the shadow run, reports whenever the variant
+	/// decided something. Never fails the live round
+	#[cfg(feature = "backtest")]
+	fn check_shadow(&mut self, now_ts: u64) {
+		if self.shadow.is_none() {
+			return;
+		}
+		let price = self.market.oracle_price(self.mango_client.mango_cache());
+		let book = self.mango_client.load_order_book(&self.market).ok().map(|book| L2Snapshot::new(&self.market.name, 0, now_ts, &book, 10));
+		let shadow = self.shadow.as_mut().unwrap();
+		let decisions = shadow.decisions.len();
+		if let Err(e) = shadow.observe(now_ts, price, book.as_ref()) {
+			eprintln!("[-] Shadow round failed {:?}", e);
+			return;
+		}
+		if shadow.decisions.len() > decisions {
+			println!("{}", format!("{} {}", self.market.name, shadow.report().summary()).cyan());
+		}
+	}
+	
+	pub fn with_leader_election(mut self, leader_election: LeaderElection) -> Self {
+		self.leader_election = Some(leader_election);
+		self
+	}
+	
+	/// Renews the lease, announcing a failover to or away from this instance. None without an election
+	fn renew_leadership(&mut self, now_ts: u64) -> MangolResult<Option<Leadership>> {
+		let election = match &mut self.leader_election {
+			Some(election) => election,
+			None => return Ok(None)
+		};
+		let instance = election.instance.clone();
+		let leadership = election.renew(now_ts)?;
+		match &leadership {
+			Leadership::Acquired { previous: Some(previous) } => {
+				println!("{}", format!("Took over trading from {}", previous).green());
+				self.notifier.send(&Notification::FailedOver { market: self.market.name.clone(), instance, previous: previous.clone() });
+			}
+			Leadership::Lost { holder } => {
+				println!("{}", format!("{} holds the lease now, standing by", holder).red());
+				self.notifier.send(&Notification::LeadershipLost { market: self.market.name.clone(), instance, holder: holder.clone() });
+			}
+			_ => {}
+		}
+		Ok(Some(leadership))
+	}
+	
+	/// Returns true while another instance holds the lease. Taking it over mid run resumes the
+	/// position the previous leader persisted, this instance's own may be rounds behind
+	pub fn check_leadership(&mut self, now_ts: u64) -> MangolResult<bool> {
+		match self.renew_leadership(now_ts)? {
+			None | Some(Leadership::Leading) => Ok(false),
+			Some(Leadership::Acquired { .. }) => {
+				let state_file = self.state_file.clone().filter(|state_file| state_file.exists());
+				if let Some(state_file) = state_file {
+					self.import_position(PositionExport::load(&state_file.to_string_lossy())?, false)?;
+				}
+				Ok(false)
+			}
+			Some(Leadership::Standby { .. }) | Some(Leadership::Lost { .. }) => Ok(true)
+		}
+	}
+	
+	/// Blocks a standby until it gets the lease, before the position is loaded and trading starts
+	pub fn await_leadership(&mut self) -> MangolResult<()> {
+		let mut announced = false;
+		loop {
+			self.beat();
+			match self.renew_leadership(self.clock.now_ts())? {
+				None | Some(Leadership::Leading) | Some(Leadership::Acquired { .. }) => return Ok(()),
+				Some(Leadership::Standby { holder }) | Some(Leadership::Lost { holder }) => {
+					if !announced {
+						println!("{}", format!("{} holds the lease, standing by", holder).yellow());
+						announced = true;
+					}
+					let retry_interval = self.leader_election.as_ref().unwrap().retry_interval();
+					self.clock.sleep(retry_interval);
+				}
+			}
+		}
+	}
+	
+	pub fn with_incentive_placer(mut self, incentive_placer: IncentivePlacer) -> Self {
+		self.incentive_placer = Some(incentive_placer);
+		self
+	}
+	
+	/// `target_price` moved toward the mid up to the incentive-optimal depth for `quantity` quote lots,
+	/// never further from the mid than the fib target nor by more than the placer's max move
+	pub fn incentive_price(&mut self, side: Side, target_price: f64, quantity: i64, book: &OrderBook) -> f64 {
+		if self.incentive_placer.is_none() {
+			return target_price;
+		}
+		let now_ts = self.clock.now_ts();
+		let perp_market = match self.incentive_market {
+			Some((loaded_ts, perp_market)) if now_ts < loaded_ts + INCENTIVE_MARKET_REFRESH_SECS => perp_market,
+			_ => match self.mango_client.load_perp_market(&self.market) {
+				Ok(perp_market) => {
+					self.incentive_market = Some((now_ts, perp_market));
+					perp_market
+				}
+				Err(e) => {
+					eprintln!("Failed to load market for incentive placement {:?}", e);
+					return target_price
+				}
+			}
+		};
+		let mid = match book.mid() {
+			Some(mid) => mid,
+			None => return target_price
+		};
+		// half of what traded went against each side
+		let traded_lots_per_sec = self.market_activity(Duration::from_secs(INCENTIVE_RATE_WINDOW_SECS), now_ts)
+			  .filter(|activity| activity.volume > 0)
+			  .map(|activity| activity.volume as f64 / 2.0 / activity.window_secs as f64);
+		let perp_market_info = self.market.perp_market_info(self.mango_client.mango_group());
+		let target_lots = perp_market_info.lot_to_native_price(target_price);
+		let base_quantity = (quantity / target_lots.max(1)).max(1);
+		let placer = self.incentive_placer.as_mut().unwrap();
+		let depth_bps = match placer.optimal_depth_bps(&IncentiveEstimator::new(perp_market), side, base_quantity, book, now_ts, traded_lots_per_sec) {
+			Some(depth_bps) => depth_bps,
+			None => return target_price
+		};
+		let mut incentive_lots = match side {
+			Side::Bid => (mid * (1.0 - depth_bps / 10_000.0)).round() as i64,
+			Side::Ask => (mid * (1.0 + depth_bps / 10_000.0)).round() as i64,
+		};
+		if let Some(max_move_bps) = placer.max_move_bps {
+			let max_move_lots = (target_lots as f64 * max_move_bps / 10_000.0).floor() as i64;
+			incentive_lots = match side {
+				Side::Bid => incentive_lots.min(target_lots + max_move_lots),
+				Side::Ask => incentive_lots.max(target_lots - max_move_lots),
+			};
+		}
+		let closer = match side {
+			Side::Bid => incentive_lots > target_lots,
+			Side::Ask => incentive_lots < target_lots,
+		};
+		if !closer {
+			return target_price;
+		}
+		println!("Moving {:?} {} bps off the mid for liquidity mining", side, (incentive_lots as f64 - mid).abs() / mid * 10_000.0);
+		perp_market_info.lots_to_price(incentive_lots)
+	}
+	
+	/// The first quarantined order and why it failed, None while every order may be sent
+	pub fn quarantined_order(&self) -> Option<String> {
+		let retry_budget = self.retry_budget.as_ref()?;
+		retry_budget.quarantined().first().map(|(order, failures)| format!("{} after {} attempts: {}", order, failures.attempts, failures.last_error))
+	}
+	
+	/// The market's activity over `window` before `now_ts`, None without stats covering it
+	pub fn market_activity(&self, window: Duration, now_ts: u64) -> Option<ActivitySnapshot> {
+		self.market_stats.as_ref()?.snapshot(self.market.market_index, window, now_ts)
+	}
+	
+	/// Whether the market has been too quiet for a new ladder, false until the stats cover the filter's window
+	pub fn is_market_dead(&self, now_ts: u64) -> bool {
+		let filter = match &self.dead_market_filter {
+			Some(filter) => filter,
+			None => return false
+		};
+		self.market_activity(filter.window, now_ts).map(|activity| filter.is_dead(&activity)).unwrap_or(false)
+	}
+	
+	pub fn with_reconciliation_alarm(mut self, reconciliation: ReconciliationAlarm) -> Self {
+		self.reconciliation = Some(reconciliation);
+		self
+	}
+	
+	/// Committed ladder as base lots and the UI quote paid for it
+	pub fn ledger_position(&self) -> LedgerPosition {
+		let quote = self.position.state_history.iter().map(|state| match state {
+			FibState::Selling(order) => order.price * self.ui_base_size(order.base_size as i64),
+			FibState::Buying(order) => -order.price * self.ui_base_size(order.base_size as i64),
+			FibState::Neutral => 0.0
+		}).sum();
+		LedgerPosition { base_lots: self.position.base_size(), quote }
+	}
+	
+	/// Base lots and UI quote of the market's perp account
+	pub fn chain_position(&self) -> LedgerPosition {
+		let perp_account = self.market.perp_account(self.mango_client.mango_account());
+		LedgerPosition {
+			base_lots: perp_account.base_position,
+			quote: perp_account.quote_position.to_num::<f64>() / 10_f64.powi(self.market.quote_decimals as i32),
+		}
+	}
+	
+	/// Alerts once when the ledger and the chain diverge and, when the alarm halts, cancels orders.
+	/// Returns true while the strategy should stay paused
+	pub fn check_reconciliation(&mut self, now_ts: u64) -> MangolResult<bool> {
+		let due = match &self.reconciliation {
+			Some(alarm) => alarm.due(now_ts),
+			None => return Ok(false)
+		};
+		if due {
+			let in_flight = match &self.position.current_state {
+				FibState::Selling(order) if order.tx_hash.is_some() => -(order.base_size as i64),
+				FibState::Buying(order) if order.tx_hash.is_some() => order.base_size as i64,
+				_ => 0
+			};
+			let (ledger, chain) = (self.ledger_position(), self.chain_position());
+			let alarm = self.reconciliation.as_mut().unwrap();
+			let halt = alarm.halt;
+			match alarm.check(ledger, in_flight, chain, now_ts) {
+				(None, Some(divergence)) => {
+					let action = if halt { "cancelling orders and pausing" } else { "alert only" };
+					let message = format!("{} ledger diverged from chain {:?}, {}", self.market.name, divergence, action);
+					println!("{}", message.red());
+					self.notifier.send(&Notification::LedgerDiverged {
+						market: self.market.name.clone(),
+						ledger_position: self.ui_base_size(divergence.ledger.base_lots),
+						chain_position: self.ui_base_size(divergence.chain.base_lots),
+						ledger_quote: divergence.ledger.quote,
+						chain_quote: divergence.chain.quote,
+						action: action.to_string(),
+					});
+					if halt {
+						let signature = self.mango_client.cancel_all_perp_orders(&self.market)?;
+						self.track_expense(&signature);
+					}
+				}
+				(Some(_), None) => {
+					println!("{}", format!("{} ledger matches the chain again", self.market.name).green());
+					self.notifier.send(&Notification::LedgerReconciled { market: self.market.name.clone() });
+				}
+				_ => {}
+			}
+		}
+		Ok(self.reconciliation.as_ref().map(|alarm| alarm.halt && alarm.divergence.is_some()).unwrap_or(false))
+	}
+	
+	pub fn with_imbalance_filter(mut self, imbalance_filter: ImbalanceFilter) -> Self {
+		self.imbalance_filter = Some(imbalance_filter);
+		self
+	}
+	
+	/// Book imbalance near the mid, positive when bids dominate
+	pub fn order_book_imbalance(&self, within_bps: f64) -> Option<f64> {
+		self.mango_client.load_order_book(&self.market).ok()?.imbalance(within_bps)
+	}
+	
+	pub fn should_delay_scale_in(&self, side: Side) -> bool {
+		let filter = match self.imbalance_filter {
+			Some(filter) => filter,
+			None => return false
+		};
+		match self.order_book_imbalance(filter.within_bps) {
+			Some(imbalance) => match side {
+				// heavy bids while we add to a short, heavy asks while we add to a long
+				Side::Ask => imbalance > filter.max_adverse_imbalance,
+				Side::Bid => imbalance < -filter.max_adverse_imbalance,
+			},
+			None => false
+		}
+	}
+	
+	/// Record every decision round so the session can be replayed later
+	pub fn with_recorder(mut self, recorder: SessionRecorder) -> Self {
+		self.recorder = Some(recorder);
+		self
+	}
+	
+	pub fn fee_model(&self) -> FeeModel {
+		FeeModel::new(self.mango_client.mango_group(), self.mango_client.mango_cache(), self.mango_client.mango_account(), self.market.market_index)
+	}
+	
+	fn begin_recording(&mut self) -> MangolResult<()> {
+		if let Some(recorder) = &mut self.recorder {
+			let oracle_price = self.market.oracle_price(self.mango_client.mango_cache());
+			let base_position = self.market.perp_account(self.mango_client.mango_account()).base_position;
+			let perp_market_info = self.market.perp_market_info(self.mango_client.mango_group());
+			recorder.begin(&self.market, self.sentiment, &self.position, perp_market_info.base_lot_size, perp_market_info.quote_lot_size, oracle_price, base_position)?;
+			self.interest_tracker = Some(InterestTracker::new(self.mango_client.mango_cache(), self.clock.now_ts()));
+		}
+		Ok(())
+	}
+	
+	/// Snapshots account equity when the recorder's interval is due
+	fn record_equity(&mut self, now_ts: u64) -> MangolResult<()> {
+		if self.recorder.as_ref().map_or(false, |recorder| recorder.equity_due(now_ts)) {
+			let perp_market_cache = *self.market.perp_market_cache(self.mango_client.mango_cache());
+			let snapshot = EquitySnapshot {
+				timestamp: now_ts,
+				equity: self.mango_client.get_equity()?.to_num::<f64>(),
+				base_position: self.market.perp_account(self.mango_client.mango_account()).base_position,
+				long_funding: perp_market_cache.long_funding.to_num::<f64>(),
+				short_funding: perp_market_cache.short_funding.to_num::<f64>(),
+				interest_earned: self.interest_tracker.as_ref()
+					  .map(|interest_tracker| interest_tracker.accrued(self.mango_client.mango_account(), self.mango_client.mango_cache()).iter().map(|accrual| accrual.net_value.to_num::<f64>()).sum())
+					  .unwrap_or(0.0),
+			};
+			self.recorder.as_mut().unwrap().record_equity(snapshot)?;
+		}
+		if let Some(recorder) = self.recorder.as_mut() {
+			if let Some(stats) = recorder.take_report(now_ts, RISK_FREE_RATE)? {
+				println!("{}", format!("{} {}", self.market.name, stats.summary()).cyan());
+				self.notifier.send(&Notification::PerformanceReport { market: self.market.name.clone(), summary: stats.summary() });
+			}
+		}
+		Ok(())
+	}
+	
+	/// Native quote a lamport is worth at SOL's oracle price, None when the group lists no wrapped SOL
+	fn lamport_price(&self) -> Option<f64> {
+		let sol_index = self.mango_client.mango_group().find_token_index(&wrapped_sol::id())?;
+		Some(self.mango_client.mango_cache().get_price(sol_index))
+	}
+	
+	/// Records what a sent transaction cost in fees and rent, never fails the trading loop.
+	/// Nothing is recorded in a group without SOL to price the lamports
+	fn track_expense(&mut self, signature: &str) {
+		if self.recorder.is_none() {
+			return;
+		}
+		let lamport_price = match self.lamport_price() {
+			Some(lamport_price) => lamport_price,
+			None => return
+		};
+		let expense = match self.mango_client.get_transaction_expense(signature) {
+			Ok(expense) => expense,
+			Err(e) => {
+				eprintln!("[-] Failed to fetch expense of {} {:?}", signature, e);
+				return;
+			}
+		};
+		let record = ExpenseRecord {
+			strategy: FIB_STRATEGY_NAME.to_string(),
+			timestamp: self.clock.now_ts(),
+			signature: signature.to_string(),
+			expense,
+			quote_value: expense.total() as f64 * lamport_price,
+		};
+		if let Err(e) = self.recorder.as_mut().unwrap().record_expense(&record) {
+			eprintln!("[-] Failed to record expense of {} {:?}", signature, e);
+		}
+	}
+	
+	/// Prices an order on `side` is judged against, taken before it is sent. None without a recorder
+	fn execution_benchmark(&self, side: Side, decision_price: f64) -> Option<ExecutionRecord> {
+		self.recorder.as_ref()?;
+		let perp_market_info = self.market.perp_market_info(self.mango_client.mango_group());
+		let best_quote = self.mango_client.load_order_book(&self.market).ok()
+			  .and_then(|book| match side {
+				  Side::Bid => book.best_ask(),
+				  Side::Ask => book.best_bid(),
+			  })
+			  .map(|lot_price| perp_market_info.lots_to_price(lot_price));
+		Some(ExecutionRecord {
+			strategy: FIB_STRATEGY_NAME.to_string(),
+			timestamp: self.clock.now_ts(),
+			signature: String::new(),
+			side,
+			decision_price,
+			oracle_price: self.market.oracle_price(self.mango_client.mango_cache()),
+			best_quote,
+			fill_price: 0.0,
+			base_filled: 0,
 		})
 	}
 	
+	fn record_execution(&mut self, mut execution: ExecutionRecord, fill_price: f64, base_filled: i64) {
+		execution.fill_price = fill_price;
+		execution.base_filled = base_filled;
+		println!("Filled {} base lots at {}, {:.1} bps vs decision", base_filled, fill_price, execution.vs_decision_bps());
+		self.notifier.send(&Notification::OrderFilled {
+			market: self.market.name.clone(),
+			side: match execution.side { Side::Bid => "buy", Side::Ask => "sell" }.to_string(),
+			size: self.ui_base_size(base_filled.abs()),
+			price: self.market.ui_price(fill_price),
+		});
+		let fill = FillState {
+			timestamp: execution.timestamp,
+			side: match execution.side { Side::Bid => "buy", Side::Ask => "sell" }.to_string(),
+			price: self.market.ui_price(fill_price),
+			size: self.ui_base_size(base_filled.abs()),
+			vs_decision_bps: execution.vs_decision_bps(),
+		};
+		if let Some(event_bus) = &self.event_bus {
+			event_bus.publish(BusEvent::Fill { strategy: FIB_STRATEGY_NAME.to_string(), market: self.market.name.clone(), fill: fill.clone() });
+		}
+		self.recent_fills.push_back(fill);
+		while self.recent_fills.len() > 20 {
+			self.recent_fills.pop_front();
+		}
+		if let Some(recorder) = &mut self.recorder {
+			if let Err(e) = recorder.record_execution(&execution) {
+				eprintln!("[-] Failed to record execution of {} {:?}", execution.signature, e);
+			}
+		}
+	}
+	
+	/// Records the taker fills of a market entry from its transaction logs
+	fn record_market_execution(&mut self, benchmark: Option<ExecutionRecord>, signatures: &[String]) {
+		let mut benchmark = match benchmark {
+			Some(benchmark) => benchmark,
+			None => return
+		};
+		let mut events = vec![];
+		for signature in signatures {
+			match self.mango_client.get_transaction_logs(signature) {
+				Ok(log_messages) => events.extend(parse_logs(&log_messages)),
+				Err(e) => eprintln!("[-] Failed to fetch logs of {} {:?}", signature, e)
+			}
+		}
+		if let Some((lot_price, base_filled)) = average_fill(&events, self.market.market_index) {
+			benchmark.signature = signatures.last().cloned().unwrap_or_default();
+			let fill_price = lot_price * self.market.perp_market_info(self.mango_client.mango_group()).lots_to_price(1);
+			self.record_execution(benchmark, fill_price, base_filled);
+		}
+	}
+	
+	fn record_decision(&mut self) -> MangolResult<()> {
+		if let Some(recorder) = &mut self.recorder {
+			let oracle_price = self.market.oracle_price(self.mango_client.mango_cache());
+			let base_position = self.market.perp_account(self.mango_client.mango_account()).base_position;
+			recorder.record(self.clock.now_ts(), oracle_price, base_position, &self.position.current_state, self.last_explanation.as_ref())?;
+		}
+		Ok(())
+	}
+	
 	pub fn print_position(&mut self) -> MangolResult<()> {
 		self.mango_client.update()?;
-		let perp_account: PerpAccount = self.mango_client.mango_account.perp_accounts[self.market.market_index];
+		let perp_account: PerpAccount = *self.market.perp_account(self.mango_client.mango_account());
 		println!("{:?}", perp_account);
 		Ok(())
 	}
 	
 	pub fn init_position(&mut self) -> MangolResult<bool> {
 		self.mango_client.update();
-		let oracle_price = self.mango_client.mango_cache.get_price(self.market.market_index);
-		let perp_market: PerpMarketInfo = self.mango_client.mango_group.perp_markets.get(self.market.market_index as usize).unwrap().clone();
-		let quantity = self.market.ui_to_quote_units(fib_calculator::get_quantity_at_n(1, TRADE_AMOUNT)?) / self.mango_client.mango_group.perp_markets[self.market.market_index].quote_lot_size as f64;
-		let perp_account: PerpAccount = self.mango_client.mango_account.perp_accounts[self.market.market_index];
+		self.refresh_trade_amount()?;
+		let oracle_price = self.market.oracle_price(self.mango_client.mango_cache());
+		let perp_market: PerpMarketInfo = self.market.perp_market_info(self.mango_client.mango_group()).clone();
+		let quantity = self.get_quantity_lots_at_n(1)?;
+		self.check_order_size(quantity, oracle_price)?;
+		let perp_account: PerpAccount = *self.market.perp_account(self.mango_client.mango_account());
+		let entry_side = match self.position.current_state {
+			FibState::Selling(_) => Some((Side::Ask, false)),
+			FibState::Buying(_) => Some((Side::Bid, false)),
+			_ => None
+		};
+		if let Some((side, _)) = entry_side {
+			// the entry opens exposure like any scale-in, the oracle and correlation checks apply
+			if !self.risk_allows(side, quantity) {
+				self.explain("risk manager refused the entry", "none, retrying next round".to_string());
+				return Ok(false);
+			}
+		}
+		let entry_signatures = match entry_side {
+			Some((side, reduce_only)) => {
+				let benchmark = self.execution_benchmark(side, self.reference_price());
+				let mut signatures = vec![];
+				let sent = self.market_order(side, quantity, reduce_only, &mut signatures);
+				self.record_market_execution(benchmark, &signatures);
+				if let Err(e) = sent {
+					self.record_partial_entry(perp_account.base_position, &signatures, oracle_price);
+					return Err(e);
+				}
+				signatures
+			}
+			None => vec![]
+		};
 		match &mut self.position.current_state {
 			// this is initial state start with sell if sentiment is selling and buy otherwise
-			FibStratPositionState::Selling(order) => {
-				let order_hash = self.mango_client.place_perp_order(
-					&perp_market,
-					&self.market,
-					Side::Ask,
-					oracle_price,
-					quantity.round().to_string().parse::<i64>().unwrap(),
-					OrderType::Market,
-					false,
-					None
-				)?;
-				order.tx_hash = Some(order_hash);
+			FibState::Selling(order) => {
+				order.tx_hash = entry_signatures.last().cloned();
 				order.price = oracle_price;
 				order.state = FibStratOrderState::Filled;
 				loop {
 					self.mango_client.update()?;
-					let perp_account_after: PerpAccount = self.mango_client.mango_account.perp_accounts[self.market.market_index];
+					let perp_account_after: PerpAccount = *self.market.perp_account(self.mango_client.mango_account());
 					if perp_account_after.base_position != 0 {
 						break
 					}
 				}
-				let trade_quantity = (self.market.ui_to_quote_units(fib_calculator::get_quantity_at_n( 1, TRADE_AMOUNT)?)/ self.mango_client.mango_group.perp_markets[self.market.market_index].quote_lot_size as f64).round().to_string().parse::<i64>().unwrap();
 				let native_price = perp_market.lot_to_native_price(oracle_price);
-				order.base_size = (trade_quantity / native_price) as u64;
+				order.base_size = (quantity / native_price) as u64;
 				//order.base_size = (perp_account.base_position - perp_account_after.base_position).abs() as u64;
 				self.position.state_history.push(self.position.current_state.clone());
 				
 				// calculate next price target and size
-				let target_price = fib_calculator::get_price_at_n(4, oracle_price, -1)?;
-				let next_quantity = self.market.ui_to_quote_units(fib_calculator::get_quantity_at_n(1, TRADE_AMOUNT)?)/ self.mango_client.mango_group.perp_markets[self.market.market_index].quote_lot_size as f64;;
-				
+				let fee_model = self.fee_model();
+				// the initial sell is a market order, the take profit rests on the book
+				let target_price = fib_calculator::get_price_at_n(&self.fib_params, 4, oracle_price, -1)?.min(fee_model.max_profitable_bid(oracle_price, true, false));
+				let (target_price, order_type) = self.post_only_price(Side::Bid, target_price);
+				let target_price = self.tick_price(Side::Bid, target_price, order_type)?;
 				let next_order_hash = self.mango_client.place_perp_order(
 					&perp_market,
 					&self.market,
 					Side::Bid,
 					target_price,
-					next_quantity.round().to_string().parse::<i64>().unwrap(),
-					OrderType::PostOnly,
+					quantity,
+					order_type,
 					true,
 					Some(self.action_interval_secs as u64 - 1)
 				)?;
-				self.position.current_state = FibStratPositionState::Buying(FibStratOrder {
+				self.position.current_state = FibState::Buying(FibStratOrder {
 					depth: 1,
 					state: FibStratOrderState::Waiting,
 					price: target_price,
 					tx_hash: Some(next_order_hash),
-					base_size: 0
+					base_size: 0,
+					legs: 1
 				});
 			}
 			
-			FibStratPositionState::Buying(order) => {
-				let order_hash = self.mango_client.place_perp_order(
-					&perp_market,
-					&self.market,
-					Side::Bid,
-					oracle_price,
-					quantity.round().to_string().parse::<i64>().unwrap(),
-					OrderType::Market,
-					true,
-					None
-				)?;
-				order.tx_hash = Some(order_hash);
+			FibState::Buying(order) => {
+				order.tx_hash = entry_signatures.last().cloned();
 				order.price = oracle_price;
 			}
 			_ => {}
 		}
+		// earlier clips of a split entry aren't kept on the order
+		let mut signatures: Vec<String> = entry_signatures.iter().rev().skip(1).cloned().collect();
+		signatures.extend(self.position.state_history.iter().chain(std::iter::once(&self.position.current_state))
+			  .filter_map(|state| state.order()?.tx_hash.clone()));
+		for signature in signatures {
+			self.track_expense(&signature);
+		}
 		
 		Ok(true)
 	}
 	
+	/// Keeps the clips of an entry that failed midway as a partially filled first order, so the
+	/// position they opened is managed and persisted instead of entered again
+	fn record_partial_entry(&mut self, base_position_before: i64, signatures: &[String], price: f64) {
+		if signatures.is_empty() {
+			return;
+		}
+		for signature in signatures {
+			self.track_expense(signature);
+		}
+		if let Err(e) = self.mango_client.update() {
+			eprintln!("[-] Failed to load what the partial entry filled {:?}", e);
+			return;
+		}
+		let base_filled = (self.market.perp_account(self.mango_client.mango_account()).base_position - base_position_before).abs();
+		if base_filled == 0 {
+			return;
+		}
+		let order = match &mut self.position.current_state {
+			FibState::Selling(order) | FibState::Buying(order) => order,
+			FibState::Neutral => return
+		};
+		order.tx_hash = signatures.last().cloned();
+		order.price = price;
+		order.base_size = base_filled as u64;
+		order.state = FibStratOrderState::PartiallyFilled;
+		println!("Entry failed after {} clips, keeping the {} base lots they filled", signatures.len(), base_filled);
+		self.position.furthest_position = max(self.position.furthest_position, order.depth);
+		self.position.state_history.push(self.position.current_state.clone());
+		self.persist_position();
+	}
+	
 	pub fn get_average_price(&self) -> MangolResult<f64> {
 		let mut position_value = 0.0;
 		let mut position_size = 0.0;
 		for past_state in &self.position.state_history {
 			match past_state {
-				FibStratPositionState::Selling(order) => {
+				FibState::Selling(order) => {
 					position_value -= (order.price * order.base_size as f64);
 					position_size = position_size - order.base_size as f64
 				}
-				FibStratPositionState::Buying(order) => {
+				FibState::Buying(order) => {
 					position_value += (order.price * order.base_size as f64);
 					position_size = position_size + order.base_size as f64
 					
@@ -212,97 +1497,111 @@ impl FibStrat {
 		}
 		
 		if position_size == 0.0 {
-			Ok(self.mango_client.mango_cache.get_price(self.market.market_index))
+			Ok(self.market.oracle_price(self.mango_client.mango_cache()))
 		} else {
 			Ok(position_value / position_size)
 		}
 	}
 	
 	pub fn get_position_size(&self) -> MangolResult<i64> {
-		let mut position_size: i64 = 0;
-		for past_state in &self.position.state_history {
-			match past_state {
-				FibStratPositionState::Selling(order) => {
-					position_size = position_size - order.base_size as i64
-				}
-				FibStratPositionState::Buying(order) => {
-					position_size = position_size + order.base_size as i64
-				}
-				_ => {}
-				
+		Ok(self.position.base_size().abs())
+	}
+	
+	/// The position state with what the account held, for `import_position` on another instance
+	pub fn export_position(&self) -> MangolResult<PositionExport> {
+		Ok(PositionExport {
+			version: POSITION_EXPORT_VERSION,
+			strategy: FIB_STRATEGY_NAME.to_string(),
+			market: self.market.name.clone(),
+			market_index: self.market.market_index,
+			sentiment: self.sentiment,
+			mango_account: self.mango_client.mango_account_pk().to_string(),
+			exported_at: self.clock.now_ts(),
+			base_position: self.market.perp_account(self.mango_client.mango_account()).base_position,
+			average_price: self.get_average_price()?,
+			base_trade_amount: Some(self.base_trade_amount),
+			position: self.position.clone(),
+		})
+	}
+	
+	/// Continues an exported position instead of opening a new one. Fails when the account's base
+	/// position doesn't match the ladder unless `force`. The order waited on belongs to the exporting
+	/// instance, on another account it is dropped and the next round places a fresh one
+	pub fn import_position(&mut self, export: PositionExport, force: bool) -> MangolResult<()> {
+		if export.strategy != FIB_STRATEGY_NAME || export.market != self.market.name {
+			return Err(MangolError::MangoError(format!("Export is for {} on {}, not {} on {}", export.strategy, export.market, FIB_STRATEGY_NAME, self.market.name)));
+		}
+		self.mango_client.update()?;
+		let base_position = self.market.perp_account(self.mango_client.mango_account()).base_position;
+		if base_position != export.position.base_size() && !force {
+			return Err(MangolError::MangoError(format!("Account holds {} base lots, the imported ladder {}", base_position, export.position.base_size())));
+		}
+		let mut position = export.position;
+		if export.mango_account != self.mango_client.mango_account_pk().to_string() {
+			if let FibState::Selling(order) | FibState::Buying(order) = &mut position.current_state {
+				order.tx_hash = None;
 			}
 		}
-		Ok(position_size.abs())
-		
+		self.position = position;
+		self.sentiment = export.sentiment;
+		// the remaining legs keep the size the ladder was entered with
+		if let Some(base_trade_amount) = export.base_trade_amount {
+			self.base_trade_amount = base_trade_amount;
+		}
+		self.persist_position();
+		Ok(())
+	}
+	
+	pub fn with_state_file(mut self, state_file: PathBuf) -> Self {
+		self.state_file = Some(state_file);
+		self
+	}
+	
+	/// Writes the position to the state file so `mangol position export` can read it while the bot runs
+	pub fn persist_position(&self) {
+		let state_file = match &self.state_file {
+			Some(state_file) => state_file,
+			None => return
+		};
+		if let Err(e) = self.export_position().and_then(|export| export.save(&state_file.to_string_lossy())) {
+			eprintln!("[-] Failed to persist position to {:?} {:?}", state_file, e);
+		}
 	}
 	
-	pub fn reset(&mut self) -> MangolResult<()> {
+	/// Neutralizes the position and enters a new ladder, false when the entry was refused
+	pub fn reset(&mut self) -> MangolResult<bool> {
 		self.mango_client.update()?;
-		let perp_account: PerpAccount = self.mango_client.mango_account.perp_accounts[self.market.market_index];
-		let perp_market: PerpMarketInfo = self.mango_client.mango_group.perp_markets.get(self.market.market_index as usize).unwrap().clone();
+		let perp_account: PerpAccount = *self.market.perp_account(self.mango_client.mango_account());
 		
-		let oracle_price = self.mango_client.mango_cache.get_price(self.market.market_index);
-		
-		if perp_account.base_position > 0 {
-			// sell and return to 0
-			let order_hash = self.mango_client.place_perp_order_with_base(
-				&perp_market,
-				&self.market,
-				Side::Ask,
-				oracle_price,
-				perp_account.base_position,
-				OrderType::Market,
-				true,
-				None
-			)?;
-			println!("Neutralized position")
-		} else if perp_account.base_position < 0 {
-			// sentiment buy handle here
-			let order_hash = self.mango_client.place_perp_order_with_base(
-				&perp_market,
-				&self.market,
-				Side::Bid,
-				oracle_price,
-				perp_account.base_position.abs(),
-				OrderType::Market,
-				true,
-				None
-			)?;
+		if perp_account.base_position != 0 {
+			// sell a long and buy back a short to return to 0
+			let side = if perp_account.base_position > 0 { Side::Ask } else { Side::Bid };
+			let mut order_hashes = vec![];
+			let sent = self.market_order_with_base(side, perp_account.base_position.abs(), true, &mut order_hashes);
+			for order_hash in &order_hashes {
+				self.track_expense(order_hash);
+			}
+			sent?;
 			println!("Neutralized position")
-			
+		}
+		if perp_account.base_position != 0 {
+			self.notifier.send(&Notification::PositionReset { market: self.market.name.clone(), position: self.ui_base_size(perp_account.base_position) });
+		}
+		// a new ladder sends new orders, give them a fresh budget
+		if let Some(retry_budget) = &mut self.retry_budget {
+			retry_budget.release_all();
 		}
 		// TODO: store previous position state somewhere for analysis
-		let current_state = match self.sentiment {
-			PriceSide::Sell => {
-				FibStratPositionState::Selling(FibStratOrder {
-					depth: 1,
-					state: FibStratOrderState::Initial,
-					price: 0.0,
-					base_size: 0,
-					tx_hash: None
-				})
-			}
-			PriceSide::Buy => {
-				FibStratPositionState::Buying(FibStratOrder {
-					depth: 1,
-					state: FibStratOrderState::Initial,
-					price: 0.0,
-					base_size: 0,
-					tx_hash: None
-				})
-			}
-		};
+		let current_state = FibState::initial(self.sentiment);
 		self.position =  FibStratPosition {
 				state_history: vec![],
 				current_state,
 				max_position_depth: self.position.max_position_depth,
-				starting_position_size: FIB_RATIO,
+				starting_position_size: self.fib_params.fib_ratio,
 			furthest_position: 1
 			
 		};
-		self.init_position()?;
-		
-		Ok(())
+		self.init_position()
 	}
 	
 	pub fn get_profit_size_at_n(&self, depth: u16) -> MangolResult<i64> {
@@ -319,8 +1618,31 @@ impl FibStrat {
 		Ok(trade_quantity)
 	}
 	
+	/// Combined size of `legs` levels ending at `depth`
+	pub fn get_quantity_lots_for_legs(&self, depth: u16, legs: u16) -> MangolResult<i64> {
+		let mut trade_quantity = 0;
+		for leg_depth in (depth + 1).saturating_sub(legs).max(1)..=depth {
+			trade_quantity += self.get_quantity_lots_at_n(leg_depth)?;
+		}
+		Ok(trade_quantity)
+	}
+	
 	pub fn get_quantity_lots_at_n(&self, depth: u16) -> MangolResult<i64> {
-		Ok((self.market.ui_to_quote_units(fib_calculator::get_quantity_at_n( depth, TRADE_AMOUNT)?)/ self.mango_client.mango_group.perp_markets[self.market.market_index].quote_lot_size as f64).round().to_string().parse::<i64>().unwrap())
+		let sizer = OrderSizer::new(self.market.perp_market_info(self.mango_client.mango_group()));
+		sizer.quote_lots_from_ui(fib_calculator::get_quantity_at_n(&self.fib_params, depth, self.base_trade_amount)?, self.market.quote_decimals, self.sizing_policy.rounding)
+	}
+	
+	/// `price` on the market's tick, rounded as the sizing policy says for an order of `order_type` on `side`,
+	/// so the price recorded in the state is the one the order rests at
+	pub fn tick_price(&self, side: Side, price: f64, order_type: OrderType) -> MangolResult<f64> {
+		let sizer = OrderSizer::new(self.market.perp_market_info(self.mango_client.mango_group()));
+		sizer.round_to_tick(price, side, order_type, self.sizing_policy.tick_rounding)
+	}
+	
+	/// Fails when `quantity` quote lots at `price` is under the sizing policy's minimum order
+	pub fn check_order_size(&self, quantity: i64, price: f64) -> MangolResult<i64> {
+		let sizer = OrderSizer::new(self.market.perp_market_info(self.mango_client.mango_group()));
+		sizer.check_min_order(quantity, sizer.price_lots(price, Rounding::Nearest)?, &self.sizing_policy)
 	}
 	
 	pub fn sync_bearish(&mut self) -> MangolResult<()> {
@@ -328,136 +1650,33 @@ impl FibStrat {
 		
 		
 		// sync onchain state
-		let prev_perp_market_info: &PerpMarketInfo = self.mango_client.mango_group.perp_markets.get(self.market.market_index as usize).unwrap();
-		let prev_perp_account: PerpAccount = self.mango_client.mango_account.perp_accounts[self.market.market_index];
-		let prev_mango_cache = self.mango_client.mango_cache.clone();
+		let prev_perp_account: PerpAccount = *self.market.perp_account(self.mango_client.mango_account());
 		self.mango_client.update()?;
-		let curr_perp_market_info: &PerpMarketInfo = self.mango_client.mango_group.perp_markets.get(self.market.market_index as usize).unwrap();
-		let curr_perp_account: PerpAccount = self.mango_client.mango_account.perp_accounts[self.market.market_index];
-		let curr_mango_cache = self.mango_client.mango_cache.clone();
-		let curr_position_size = self.get_position_size()?;
-		let oracle_price = self.mango_client.mango_cache.get_price(self.market.market_index);
-		
-		let mut previous_state = self.position.current_state.clone();
+		let curr_perp_account: PerpAccount = *self.market.perp_account(self.mango_client.mango_account());
 		
-		match &mut previous_state {
-			FibStratPositionState::Selling(order) => {
-				let trade_quantity =  self.get_quantity_lots_at_n(order.depth)?;
-				let native_price = curr_perp_market_info.lot_to_native_price(order.price);
-				let expected_base_filled = trade_quantity / native_price;
-				let actual_base_filled = (prev_perp_account.base_position - curr_perp_account.base_position).abs();
-				println!("Previous state SELLING Expected to be filled: {} Actual filled: {}", expected_base_filled, actual_base_filled);
-				println!();
-				if actual_base_filled == 0 {
-					// order was not filled
-				}
-				else if expected_base_filled > actual_base_filled   {
-					// handle partially filled order here
-					// save it to a partially filled list and do sth
-					// mangol_mailer::send_text_with_content(format!("Buying back partial fill of {}", actual_base_filled));
-					// let order_hash = self.mango_client.place_perp_order_with_base(
-					// 	curr_perp_market_info,
-					// 	&self.market,
-					// 	Side::Bid,
-					// 	oracle_price,
-					// 	actual_base_filled,
-					// 	OrderType::Market,
-					// 	false,
-					// 	None
-					// )?;
-					// let message = format!("Bought back {} https://explorer.solana.com/tx/{}", actual_base_filled, order_hash);
-					// mangol_mailer::send_text_with_content(message.clone());
-					// println!("{}", message);
-					// self.mango_client.update()?;
-					if order.depth > self.position.furthest_position {
-						self.position.furthest_position = order.depth
-					}
-					order.base_size = actual_base_filled as u64;
-					order.state = FibStratOrderState::PartiallyFilled;
-					self.position.state_history.push(previous_state.clone());
-					
-				}
-				
-				else if expected_base_filled <= actual_base_filled {
-					if order.depth > self.position.furthest_position {
-						self.position.furthest_position = order.depth
-					}
-					// order was succesful
-					order.base_size = actual_base_filled as u64;
-					order.state = FibStratOrderState::Filled;
-					
-					self.position.state_history.push(previous_state.clone());
-				}
-			}
-			FibStratPositionState::Buying(order) => {
-				// in bearish sentiment mode previous buying state always corresponds with orders to take profit,
-				//
-				let trade_quantity =  self.get_profit_size_at_n(order.depth)?;
-				let native_price = curr_perp_market_info.lot_to_native_price(order.price);
-				let expected_base_filled = trade_quantity / native_price;
-				let actual_base_filled = (prev_perp_account.base_position - curr_perp_account.base_position).abs();
-				println!("Previous state BUYING Expected to be filled: {} Actual filled: {}", expected_base_filled, actual_base_filled);
-				if actual_base_filled == 0 {
-					// order was not filled
-				}
-				else if expected_base_filled > actual_base_filled  {
-					if order.depth == 1 {
-						self.position.current_state = FibStratPositionState::Neutral;
-						return Ok(())
-					}
-					// handle partially filled order here
-					// save it to a partially filled list and do sth
-					// mangol_mailer::send_text_with_content(format!("Selling back partial fill of {}", actual_base_filled));
-					// let order_hash = self.mango_client.place_perp_order_with_base(
-					// 	curr_perp_market_info,
-					// 	&self.market,
-					// 	Side::Ask,
-					// 	oracle_price,
-					// 	actual_base_filled,
-					// 	OrderType::Market,
-					// 	false,
-					// 	None
-					// )?;
-					// let message = format!("Bought back {} https://explorer.solana.com/tx/{}", actual_base_filled, order_hash);
-					// mangol_mailer::send_text_with_content(message.clone());
-					// println!("{}", message);
-					// self.mango_client.update()?;
-					if order.depth > self.position.furthest_position {
-						self.position.furthest_position = order.depth
-					}
-					// order was partially succesful
-					
-					order.base_size = actual_base_filled as u64;
-					order.state = FibStratOrderState::Filled;
-					order.depth = if order.depth > RISK_TOLERANCE { order.depth - 1} else if order.depth > 1 {order.depth } else {
-						println!("Last order for position Partially filled, setting to Neutral state");
-						self.position.current_state = FibStratPositionState::Neutral;
-						1
-					};
-					
-					self.position.state_history.push(previous_state.clone());
-					
-				}
-				else if expected_base_filled <= actual_base_filled {
-					if order.depth > self.position.furthest_position {
-						self.position.furthest_position = order.depth
-					}
-					// order was succesful
-					// meaning previous 1 sell order or previous n - RISK_TOLERANCE depth orders have been profited on and closed
-					// therefore adjust depth to reflect current position size for the decision round
-					order.base_size = actual_base_filled as u64;
-					order.state = FibStratOrderState::Filled;
-					order.depth = if order.depth > RISK_TOLERANCE { order.depth - RISK_TOLERANCE} else if order.depth > 1 {order.depth - 1} else {
-						println!("Last order for position filled, setting to Neutral state");
-						self.position.current_state = FibStratPositionState::Neutral;
-						1
-					};
-					
-					self.position.state_history.push(previous_state.clone());
-				}
+		let (label, trade_quantity) = match &self.position.current_state {
+			FibState::Selling(order) => ("SELLING", self.get_quantity_lots_for_legs(order.depth, order.legs)?),
+			// in bearish sentiment mode previous buying state always corresponds with orders to take profit
+			FibState::Buying(order) => ("BUYING", self.get_profit_size_at_n(order.depth)?),
+			FibState::Neutral => return Ok(())
+		};
+		let order_price = self.position.current_state.order().unwrap().price;
+		let native_price = self.market.perp_market_info(self.mango_client.mango_group()).lot_to_native_price(order_price);
+		let expected_base_filled = trade_quantity / native_price;
+		let actual_base_filled = (prev_perp_account.base_position - curr_perp_account.base_position).abs();
+		println!("Previous state {} Expected to be filled: {} Actual filled: {}", label, expected_base_filled, actual_base_filled);
+		if actual_base_filled != 0 {
+			let tx_hash = self.position.current_state.order().unwrap().tx_hash.clone();
+			if let Some(pending) = self.pending_execution.take().filter(|pending| Some(&pending.signature) == tx_hash.as_ref()) {
+				// resting orders fill at their limit price
+				self.record_execution(pending, order_price, actual_base_filled);
 			}
-			_ => {}
-			
+		}
+		if self.ensure_reduce_only && matches!(self.position.current_state, FibState::Buying(_)) && actual_base_filled != 0 {
+			self.audit_take_profit(prev_perp_account.base_position, curr_perp_account.base_position);
+		}
+		for action in self.position.current_state.on_bearish_fill(expected_base_filled, actual_base_filled) {
+			self.apply_action(action)?;
 		}
 		Ok(())
 		
@@ -468,113 +1687,277 @@ impl FibStrat {
 		Ok(())
 	}
 		
-		pub fn decide_bearish(&mut self) -> MangolResult<()> {
-			println!("{}", format!("\n>>>>>>> Bearish Decision <<<<<<<<").green());
-		let mango_cache = self.mango_client.mango_cache.clone();
-		let perp_market_info: &PerpMarketInfo = self.mango_client.mango_group.perp_markets.get(self.market.market_index as usize).unwrap();
-		
+	pub fn decide_bearish(&mut self) -> MangolResult<()> {
+		println!("{}", format!("\n>>>>>>> Bearish Decision <<<<<<<<").green());
+		self.last_explanation = None;
 		let average_price = self.get_average_price()?;
 		let curr_position_size = self.get_position_size()?;
-		if self.position.current_state == FibStratPositionState::Neutral || curr_position_size == 0 {
+		if self.position.current_state == FibState::Neutral || curr_position_size == 0 {
 			// position is closed reset on next iteration
+			self.explain("position closed", "none, resetting next round".to_string());
 			return Ok(())
 		}
-		let oracle_price = mango_cache.get_price(self.market.market_index);
-		println!("Using average price: {} oracle price: {} and position size: {}", average_price * 1000.0, oracle_price * 1000.0, curr_position_size);
+		let reference_price = self.reference_price();
+		println!("Using average price: {} reference price: {} and position size: {}", average_price * 1000.0, reference_price * 1000.0, curr_position_size);
 		
-		let last_committed_state = self.position.state_history.get(self.position.state_history.len() - 1).unwrap();
-		///println!("Last Known state: {:?}", last_committed_state);
-		if oracle_price > average_price {
-			match last_committed_state {
-				FibStratPositionState::Selling(order) | FibStratPositionState::Buying(order) => {
-					// calculate next price target and size
-					let mut target_price = fib_calculator::get_price_at_n(order.depth + 1, average_price, 1)?;
-					let next_quantity = self.get_quantity_lots_at_n(order.depth + 1)?;
-					if target_price < oracle_price {
-						target_price = fib_calculator::get_price_at_n( 1, oracle_price, 1)?;
-					}
-					let next_order_hash = self.mango_client.place_perp_order(
-						perp_market_info,
-						&self.market,
-						Side::Ask,
-						target_price,
-						next_quantity,
-						OrderType::PostOnly,
-						order.depth == 0,
-						Some(self.action_interval_secs as u64)
-					)?;
-					self.position.current_state = FibStratPositionState::Selling(FibStratOrder {
-						depth: order.depth + 1,
-						state: FibStratOrderState::Waiting,
-						price: target_price,
-						tx_hash: Some(next_order_hash),
-						base_size: 0
-					});
+		let last_committed_state = self.position.state_history.last().unwrap().clone();
+		for action in last_committed_state.on_bearish_decision(reference_price, average_price) {
+			self.apply_action(action)?;
+		}
+		if self.last_explanation.is_none() {
+			self.explain("no committed order to decide from", "none".to_string());
+		}
+		
+		Ok(())
+	}
+	
+	/// Applies an Action returned by a FibState transition
+	fn apply_action(&mut self, action: Action) -> MangolResult<()> {
+		match action {
+			Action::ReachDepth(depth) => self.position.furthest_position = max(self.position.furthest_position, depth),
+			Action::Commit(state) => self.position.state_history.push(state),
+			Action::Transition(state) => self.position.current_state = state,
+			Action::Place(intent) => self.place_intent(intent)?,
+		}
+		Ok(())
+	}
+	
+	/// Prices and sizes the order for `intent` and waits on it, unless a filter holds the scale-in back
+	fn place_intent(&mut self, mut intent: OrderIntent) -> MangolResult<()> {
+		let order_key = format!("{:?} {:?} depth {}", intent.leg, intent.side, intent.depth);
+		if let Some(retry_budget) = &mut self.retry_budget {
+			if retry_budget.release_if_requested() {
+				println!("[+] Released the quarantined orders as requested");
+			}
+		}
+		if let Some(retry_budget) = &self.retry_budget {
+			if !retry_budget.ready(&order_key, self.clock.now_ts()) {
+				let reason = if retry_budget.failures(&order_key).map(|failures| failures.quarantined).unwrap_or(false) { "order quarantined" } else { "order backing off after failing" };
+				self.explain(reason, format!("none, {} not sent", order_key));
+				return Ok(())
+			}
+		}
+		let average_price = self.get_average_price()?;
+		let oracle_price = self.market.oracle_price(self.mango_client.mango_cache());
+		let reference_price = self.reference_price();
+		// scale-in asks and take profit bids both move away from the reference price in this direction
+		let direction: i8 = match intent.side {
+			Side::Ask => 1,
+			Side::Bid => -1,
+		};
+		let (target_price, next_quantity) = match intent.leg {
+			Leg::ScaleIn => {
+				// catch up on the levels a gap skipped with one order sized for all of them,
+				// the fills still enter the average at the price they got
+				let legs = self.skipped_levels(intent.depth, average_price, reference_price, direction)?;
+				if legs > 1 {
+					println!("Oracle gapped past {} levels, batching them into one scale-in", legs);
 				}
-				_ => {}
-				
-				
+				intent.depth += legs - 1;
+				intent.legs = legs;
+				let mut target_price = fib_calculator::get_price_at_n(&self.fib_params, intent.depth, average_price, direction)?;
+				let next_quantity = self.get_quantity_lots_for_legs(intent.depth, intent.legs)?;
+				if (target_price - reference_price) * (direction as f64) < 0.0 {
+					target_price = fib_calculator::get_price_at_n(&self.fib_params, 1, reference_price, direction)?;
+				}
+				if self.should_delay_scale_in(intent.side) {
+					println!("Book imbalance against scale-in, waiting a round");
+					self.explain("book imbalance against scale-in", "none, waiting a round".to_string());
+					return Ok(())
+				}
+				if !self.risk_allows(intent.side, next_quantity) {
+					self.explain("risk manager refused the scale-in", "none".to_string());
+					return Ok(())
+				}
+				(target_price, next_quantity)
 			}
-		} else {
-			match last_committed_state {
-				FibStratPositionState::Selling(order) | FibStratPositionState::Buying(order) => {
-					
-					// calculate next price target and size
-					let target_price_depth = if order.depth >= PROFIT_PRICE_DEPTH || ( order.depth == 1 && self.position.furthest_position > RISK_TOLERANCE ){
-						1
-					} else  {
-						PROFIT_PRICE_DEPTH - order.depth
-					};
-					let mut target_price = fib_calculator::get_price_at_n(target_price_depth, average_price, -1)?;
-					if target_price > oracle_price {
-						target_price = fib_calculator::get_price_at_n(1, oracle_price, -1)?;
-					}
-					let next_quantity = self.get_profit_size_at_n(order.depth)?;
-					let next_order_hash = self.mango_client.place_perp_order(
-						perp_market_info,
-						&self.market,
-						Side::Bid,
-						target_price,
-						next_quantity,
-						OrderType::PostOnly,
-						order.depth == 1,
-						Some(self.action_interval_secs as u64)
-					)?;
-					self.position.current_state = FibStratPositionState::Buying(FibStratOrder {
-						depth: order.depth,
-						state: FibStratOrderState::Waiting,
-						price: target_price,
-						tx_hash: Some(next_order_hash),
-						base_size: 0
-					});
+			Leg::TakeProfit => {
+				let profit_quantity = self.get_profit_size_at_n(intent.depth)?;
+				let target_price_depth = take_profit_price_depth(intent.depth, self.position.furthest_position);
+				let target_price_depth = self.liquid_profit_depth(intent.side, target_price_depth, average_price, direction, profit_quantity)?;
+				let mut target_price = fib_calculator::get_price_at_n(&self.fib_params, target_price_depth, average_price, direction)?;
+				if (target_price - reference_price) * (direction as f64) < 0.0 {
+					target_price = fib_calculator::get_price_at_n(&self.fib_params, 1, reference_price, direction)?;
 				}
-				_ => {}
-				
+				if self.ensure_reduce_only {
+					intent.reduce_only = true;
+				}
+				// the position always contains the initial market sell, so assume a taker entry
+				target_price = target_price.min(self.fee_model().max_profitable_bid(average_price, true, false));
+				(target_price, profit_quantity)
 			}
+		};
+		// one book for the incentive placement and the PostOnly check
+		let book = if self.incentive_placer.is_some() || self.post_only_policy.is_some() {
+			self.mango_client.load_order_book(&self.market).ok()
+		} else {
+			None
+		};
+		// scale-ins stay on the fib ladder, only take profits chase liquidity mining
+		let target_price = match (&intent.leg, &book) {
+			(Leg::TakeProfit, Some(book)) => self.incentive_price(intent.side, target_price, next_quantity, book),
+			_ => target_price
+		};
+		let benchmark = self.execution_benchmark(intent.side, target_price);
+		let (target_price, order_type) = match &book {
+			Some(book) => self.post_only_price_in(intent.side, target_price, book),
+			None => self.post_only_price(intent.side, target_price),
+		};
+		let target_price = self.tick_price(intent.side, target_price, order_type)?;
+		self.check_order_size(next_quantity, target_price)?;
+		if let Some(audit_log) = &self.audit_log {
+			audit_log.set_context(json!({
+				"strategy": FIB_STRATEGY_NAME,
+				"market": self.market.name,
+				"leg": format!("{:?}", intent.leg),
+				"side": format!("{:?}", intent.side),
+				"depth": intent.depth,
+				"legs": intent.legs,
+				"oracle_price": oracle_price,
+				"reference_price": reference_price,
+				"average_price": average_price,
+				"target_price": target_price,
+				"quantity": next_quantity,
+			}));
 		}
-		
+		let perp_market_info: &PerpMarketInfo = self.market.perp_market_info(self.mango_client.mango_group());
+		let placed = self.mango_client.place_perp_order(
+			perp_market_info,
+			&self.market,
+			intent.side,
+			target_price,
+			next_quantity,
+			order_type,
+			intent.reduce_only,
+			Some(self.order_expiry_secs())
+		);
+		let next_order_hash = match placed {
+			Ok(next_order_hash) => {
+				if let Some(retry_budget) = &mut self.retry_budget {
+					retry_budget.record_success(&order_key);
+				}
+				next_order_hash
+			}
+			// signature failures are the signer's, not the order's
+			Err(e) if self.retry_budget.is_some() && !is_signature_failure(&e) => {
+				self.order_failed(&order_key, &e);
+				return Ok(())
+			}
+			Err(e) => return Err(e)
+		};
+		self.track_expense(&next_order_hash);
+		let rule = format!("reference {} average, {}", if reference_price > average_price { "above" } else { "below" }, match intent.leg {
+			Leg::ScaleIn => "scale in",
+			Leg::TakeProfit => "take profit",
+		});
+		self.explain(&rule, format!("placed {:?} {:?} at depth {} over {} legs, {} quote lots at {}", order_type, intent.side, intent.depth, intent.legs, next_quantity, self.market.ui_price(target_price)));
+		self.pending_execution = benchmark.map(|benchmark| ExecutionRecord { signature: next_order_hash.clone(), ..benchmark });
+		self.position.current_state = FibState::waiting(&intent, target_price, next_order_hash);
 		Ok(())
 	}
 	
+	/// Spends the retry budget of `order_key`, alerting once it is quarantined
+	fn order_failed(&mut self, order_key: &str, error: &MangolError) {
+		let now_ts = self.clock.now_ts();
+		let retry_budget = self.retry_budget.as_mut().unwrap();
+		let quarantined = retry_budget.record_failure(order_key, &format!("{:?}", error), now_ts);
+		let failures = retry_budget.failures(order_key).unwrap().clone();
+		eprintln!("[-] {} failed {} times, retrying after {} {:?}", order_key, failures.attempts, failures.retry_at, error);
+		if quarantined {
+			println!("{}", format!("{} {} quarantined after {} attempts", self.market.name, order_key, failures.attempts).red());
+			self.notifier.send(&Notification::OrderQuarantined {
+				market: self.market.name.clone(),
+				order: order_key.to_string(),
+				attempts: failures.attempts,
+				error: failures.last_error.clone(),
+			});
+		}
+		self.explain("order failed to land", format!("none, {} attempt {} failed", order_key, failures.attempts));
+	}
+	
 	pub fn decide_bullish(&mut self) -> MangolResult<()> {
 		Ok(())
 	}
 	pub fn start_trading(&mut self) -> MangolResult<()> {
+		self.begin_recording()?;
 		'trading_loop: loop {
 			// sleep every iteration and make decisions after
+			let now_ts = self.clock.now_ts();
+			self.beat();
+			if self.check_leadership(now_ts)? {
+				let retry_interval = self.leader_election.as_ref().unwrap().retry_interval();
+				self.clock.sleep(retry_interval);
+				continue;
+			}
+			self.check_signer_rotation();
+			// a missed snapshot isn't worth stopping trading over
+			if let Err(e) = self.record_equity(now_ts) {
+				eprintln!("[-] Failed to record equity {:?}", e);
+			}
+			self.publish_state(now_ts);
+			#[cfg(feature = "backtest")]
+			self.check_shadow(now_ts);
+			if self.check_schedule(now_ts)? {
+				self.clock.sleep(Duration::from_secs(self.action_interval_secs));
+				self.mango_client.update()?;
+				continue;
+			}
+			if self.check_halt(now_ts)? {
+				self.clock.sleep(Duration::from_secs(self.action_interval_secs));
+				self.mango_client.update()?;
+				continue;
+			}
+			if self.check_reconciliation(now_ts)? {
+				self.clock.sleep(Duration::from_secs(self.action_interval_secs));
+				self.mango_client.update()?;
+				continue;
+			}
+			if self.is_killed() {
+				println!("{}", "Kill switch engaged, not placing orders".red());
+				self.clock.sleep(Duration::from_secs(self.action_interval_secs));
+				self.mango_client.update()?;
+				continue;
+			}
+			if self.dead_mans_switch.as_ref().map(|switch| switch.take_pending_cancel()).unwrap_or(false) {
+				println!("{}", "Connectivity is back after a blind period, cancelling orders".red());
+				let signature = self.mango_client.cancel_all_perp_orders(&self.market)?;
+				self.track_expense(&signature);
+			}
+			match self.network_status() {
+				NetworkStatus::Down => {
+					println!("{}", "Network is down, pausing decisions".red());
+					self.clock.sleep(Duration::from_secs(self.action_interval_secs));
+					continue;
+				}
+				NetworkStatus::Degraded => println!("{}", "Network is degraded".yellow()),
+				NetworkStatus::Healthy => {}
+			}
 			
-			let perp_account: PerpAccount = self.mango_client.mango_account.perp_accounts[self.market.market_index];
+			self.observe_liquidity();
+			let perp_account: PerpAccount = *self.market.perp_account(self.mango_client.mango_account());
 			let curr_position_size = self.get_position_size()?;
-			if self.position.current_state == FibStratPositionState::Neutral || curr_position_size == 0{
+			if (self.position.current_state == FibState::Neutral || curr_position_size == 0) && perp_account.base_position == 0 && self.is_market_dead(now_ts) {
+				// nothing open to manage, a new ladder would sit there unfilled
+				println!("{}", format!("{} is too quiet, not opening a position", self.market.name).yellow());
+				self.clock.sleep(Duration::from_secs(self.action_interval_secs));
+				self.mango_client.update()?;
+				continue;
+			}
+			if self.position.current_state == FibState::Neutral || curr_position_size == 0{
 				// The position has been closed, reset
 				println!("Position in neutral state, resetting... {:?} {:?}", perp_account, self.position);
-				self.reset()?;
+				if !self.reset()? {
+					self.clock.sleep(Duration::from_secs(self.action_interval_secs));
+					self.mango_client.update()?;
+					continue;
+				}
+				self.begin_recording()?;
 				continue;
 			}
 			let mut should_not_sleep = false;
 			// check if order is on book and sleep
 			match &self.position.current_state {
-				FibStratPositionState::Selling(order) | FibStratPositionState::Buying(order) => {
+				FibState::Selling(order) | FibState::Buying(order) => {
 					if order.tx_hash.is_some() {
 						
 						let mut fetch_tries = 10;
@@ -583,11 +1966,13 @@ impl FibStrat {
 							should_not_sleep = true
 						}
 						while fetch_tries > 0 {
-							if let Ok(order_tx) = self.mango_client.solana_connection.rpc_client.get_transaction(&Signature::from_str(&order.tx_hash.as_ref().unwrap()).unwrap(), UiTransactionEncoding::Base64 ) {
+							if let Ok(log_messages) = self.mango_client.get_transaction_logs(order.tx_hash.as_ref().unwrap()) {
 								fetch_tries = 0;
-								for message in order_tx.transaction.meta.unwrap().log_messages.unwrap() {
-									if message.contains("not be placed due to PostOnly") {
-										should_not_sleep = true;
+								for event in parse_logs(&log_messages) {
+									match event {
+										MangoLogEvent::PostOnlyRejected => should_not_sleep = true,
+										MangoLogEvent::Error(reason) => eprintln!("Order transaction failed: {}", reason),
+										_ => {}
 									}
 								}
 							} else {
@@ -603,20 +1988,31 @@ impl FibStrat {
 			}
 			
 			if !should_not_sleep {
-				let sleep_start = Instant::now();
-				println!("Sleeping for {} secs", self.action_interval_secs);
+				let sleep_start = self.clock.now_millis();
+				let round_millis = self.round_timing.next_round_millis(sleep_start, self.action_interval_secs);
+				println!("Sleeping for {} secs", round_millis as f64 / 1000.0);
 				let mut sure_count = 0;
 				'sleep: loop {
-					let elapsed_secs = sleep_start.elapsed().as_secs();
-					if elapsed_secs > self.action_interval_secs {
+					self.beat();
+					if self.clock.now_millis() - sleep_start >= round_millis {
 						println!("Sleep time ended");
 						break 'sleep
 					}
-					let mango_account_info_result = self.mango_client.solana_connection.rpc_client.get_account_with_commitment(&self.mango_client.mango_account_pk, CommitmentConfig::finalized());
-					if let Ok(mango_account_info) = mango_account_info_result {
-						if mango_account_info.value.is_some() {
-							let mango_account = MangoAccount::load_checked(mango_account_info.value.unwrap(), &self.mango_client.mango_program_id).unwrap();
-							let perp_account = mango_account.perp_accounts[self.market.market_index];
+					if let Some(account_events) = &self.account_events {
+						// drain what arrived and let the clock pace the wait
+						while let Ok(event) = account_events.try_recv() {
+							println!("Account event {:?}", event);
+							if self.ends_wait(&event) {
+								println!("Order is filled or expired aborting sleep");
+								break 'sleep;
+							}
+						}
+						self.clock.sleep(Duration::from_secs(1));
+						continue 'sleep;
+					}
+					if let Ok(mango_account_result) = self.mango_client.fetch_mango_account() {
+						if let Some(mango_account) = mango_account_result {
+							let perp_account = *self.market.perp_account(&mango_account);
 							println!("Asks: {} Bids: {} TAsks: {} TBids: {} Orders: {:?}", perp_account.asks_quantity, perp_account.bids_quantity, perp_account.taker_base, perp_account.taker_quote, mango_account.orders);
 							if perp_account.taker_base == 0 && perp_account.taker_quote == 0 && perp_account.asks_quantity == 0 && perp_account.bids_quantity == 0 && !mango_account.orders.iter().any(|order| *order != 0_i128){
 								sure_count += 1;
@@ -629,11 +2025,25 @@ impl FibStrat {
 						println!("Order is filled or expired aborting sleep");
 						break 'sleep;
 					}
-					std::thread::sleep(Duration::from_secs(1))
+					self.clock.sleep(Duration::from_secs(1))
 					
 				}
 				
 			}
+			if let Some(expiry_manager) = self.expiry_manager {
+				match expiry_manager.cancel_and_confirm(&self.mango_client, &self.market, self.clock.as_ref()) {
+					Ok(Some(signature)) => {
+						println!("Cancelled resting orders before deciding");
+						self.track_expense(&signature);
+						self.mango_client.update()?;
+					}
+					Ok(None) => {}
+					Err(e) => {
+						eprintln!("Skipping decision round, orders not cancelled {:?}", e);
+						continue 'trading_loop;
+					}
+				}
+			}
 			let mut previous_state = self.position.current_state.clone();
 			
 			
@@ -660,7 +2070,16 @@ impl FibStrat {
 					> if last sure state was selling place buy order and take profit on 1 depth with floor(n/2), 1 size
 					> if last sure state was buying place buy order and scale in on n+1 depth with n+1 size
 			 */
-					self.decide_bearish()?;
+					if let Err(e) = self.decide_bearish() {
+						if !is_signature_failure(&e) {
+							return Err(e);
+						}
+						// nothing was placed, decide again next round with the backup key
+						self.rotate_signer(None, &format!("{:?}", e))?;
+						continue 'trading_loop;
+					}
+					self.record_decision()?;
+					self.persist_position();
 				}
 				
 				PriceSide::Buy => {
@@ -678,16 +2097,338 @@ impl FibStrat {
 
 mod fib_calculator {
 	use mangol_common::errors::MangolResult;
-	use crate::fib_trader::{PRICE_FIB_RATIO, FIB_RATIO};
-	pub fn get_price_at_n(n: u16, price: f64, direction: i8) -> MangolResult<f64> {
+	use crate::fib_trader::FibParams;
+	pub fn get_price_at_n(fib_params: &FibParams, n: u16, price: f64, direction: i8) -> MangolResult<f64> {
 		// TODO: tweak this to find the best curve of increasing price targets
 		// could be different for different markets
-		let move_percent = PRICE_FIB_RATIO * FIB_RATIO.powf(n as f64);
+		let move_percent = fib_params.price_fib_ratio * fib_params.fib_ratio.powf(n as f64);
 		let price_change_increment = (price * move_percent) / 100.0;
 		return if direction > 0 { Ok(price + price_change_increment)} else {Ok(price - price_change_increment)};
 	}
-	pub fn get_quantity_at_n(n: u16, quantity: f64) -> MangolResult<f64> {
-			Ok(FIB_RATIO.powf(n as f64) * quantity)
+	pub fn get_quantity_at_n(fib_params: &FibParams, n: u16, quantity: f64) -> MangolResult<f64> {
+			Ok(fib_params.fib_ratio.powf(n as f64) * quantity)
+	}
+}
+
+impl<C: MangoClientApi> Strategy for FibStrat<C> {
+	fn name(&self) -> &str {
+		FIB_STRATEGY_NAME
+	}
+	
+	fn init(&mut self) -> MangolResult<()> {
+		self.init_position()?;
+		self.persist_position();
+		Ok(())
+	}
+	
+	fn run(&mut self) -> MangolResult<()> {
+		self.start_trading()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use fixed::types::I80F48;
+	use mangol_mango::mock::{sol_perp, MockMangoClient};
+	use mangol_mango::ids::wrapped_sol;
+	use mangol_mango::types::{OrderType, Side};
+	use mangol_mango::book::{BookOrder, OrderBook};
+	use solana_sdk::pubkey::Pubkey;
+	use crate::fib_state::{FibState, FibStratOrder, FibStratOrderState};
+	use crate::fib_trader::{EntryImpactLimit, FibStrat, ImbalanceFilter, PostOnlyCrossPolicy, PriceSide, ReferencePrice, TradeAmount, FIB_STRATEGY_NAME};
+	use std::time::Duration;
+	use crate::schedule::{EventWindow, TradingSchedule};
+	use crate::risk::{RiskLimits, RiskManager};
+	
+	const MARKET_INDEX: usize = 3;
+	
+	fn book_order(price: i64, quantity: i64) -> BookOrder {
+		BookOrder { key: (price as i128) << 64, owner: Pubkey::default(), owner_slot: 0, order_type: 0, time_in_force: 0, price, quantity, client_order_id: 0, timestamp: 0 }
+	}
+	
+	fn test_strat(state_history: Vec<FibState>, current_state: FibState) -> FibStrat<MockMangoClient> {
+		let mut mango_client = MockMangoClient::new(MARKET_INDEX, 10_000_000, 100);
+		mango_client.set_price(0.04);
+		let mut strat = FibStrat::new(10, 43, mango_client, PriceSide::Sell, sol_perp()).unwrap();
+		strat.position.state_history = state_history;
+		strat.position.current_state = current_state;
+		strat
+	}
+	
+	fn order(depth: u16, state: FibStratOrderState, price: f64, base_size: u64) -> FibStratOrder {
+		FibStratOrder { depth, state, price, base_size, tx_hash: Some("mock-0".to_string()), legs: 1 }
+	}
+	
+	#[test]
+	fn sync_bearish_records_filled_sell() {
+		let mut strat = test_strat(vec![], FibState::Selling(order(1, FibStratOrderState::Waiting, 0.04, 0)));
+		strat.mango_client.push_fill(-121);
+		strat.sync_bearish().unwrap();
+		assert_eq!(strat.position.state_history, vec![FibState::Selling(order(1, FibStratOrderState::Filled, 0.04, 121))]);
+	}
+	
+	#[test]
+	fn sync_bearish_records_partially_filled_sell() {
+		let mut strat = test_strat(vec![], FibState::Selling(order(1, FibStratOrderState::Waiting, 0.04, 0)));
+		strat.mango_client.push_fill(-60);
+		strat.sync_bearish().unwrap();
+		assert_eq!(strat.position.state_history, vec![FibState::Selling(order(1, FibStratOrderState::PartiallyFilled, 0.04, 60))]);
+	}
+	
+	#[test]
+	fn sync_bearish_ignores_unfilled_order() {
+		let mut strat = test_strat(vec![], FibState::Selling(order(1, FibStratOrderState::Waiting, 0.04, 0)));
+		strat.mango_client.push_fill(0);
+		strat.sync_bearish().unwrap();
+		assert!(strat.position.state_history.is_empty());
+	}
+	
+	#[test]
+	fn decide_bearish_takes_profit_below_average() {
+		let filled = FibState::Selling(order(1, FibStratOrderState::Filled, 0.04, 121));
+		let mut strat = test_strat(vec![filled.clone()], filled);
+		strat.mango_client.set_price(0.039);
+		strat.decide_bearish().unwrap();
+		let placed = strat.mango_client.last_order().unwrap();
+		assert_eq!(placed.side, Side::Bid);
+		assert_eq!(placed.order_type, OrderType::PostOnly);
+		assert!(placed.reduce_only);
+		assert!(placed.price < 0.039);
+		assert_eq!(placed.quantity, strat.get_profit_size_at_n(1).unwrap());
+		assert!(matches!(strat.position.current_state, FibState::Buying(FibStratOrder { depth: 1, state: FibStratOrderState::Waiting, .. })));
+	}
+	
+	#[test]
+	fn decide_bearish_take_profit_covers_fees() {
+		let filled = FibState::Selling(order(1, FibStratOrderState::Filled, 0.04, 121));
+		let mut strat = test_strat(vec![filled.clone()], filled);
+		strat.mango_client.mango_group.perp_markets[MARKET_INDEX].taker_fee = I80F48::from_num(0.05);
+		strat.mango_client.set_price(0.0399);
+		strat.decide_bearish().unwrap();
+		let placed = strat.mango_client.last_order().unwrap();
+		assert_eq!(placed.side, Side::Bid);
+		assert!(placed.price <= strat.fee_model().max_profitable_bid(0.04, true, false));
+	}
+	
+	#[test]
+	fn take_profit_moves_inside_crossed_book() {
+		let filled = FibState::Selling(order(1, FibStratOrderState::Filled, 0.04, 121));
+		let mut strat = test_strat(vec![filled.clone()], filled).with_post_only_policy(PostOnlyCrossPolicy::OneTickInside);
+		strat.mango_client.order_book = OrderBook { bids: vec![book_order(3_700, 10)], asks: vec![book_order(3_800, 10)] };
+		strat.mango_client.set_price(0.039);
+		strat.decide_bearish().unwrap();
+		let placed = strat.mango_client.last_order().unwrap();
+		assert_eq!(placed.order_type, OrderType::PostOnly);
+		assert!((placed.price - 0.03799).abs() < 1e-12);
+	}
+	
+	#[test]
+	fn take_profit_slides_on_crossed_book() {
+		let filled = FibState::Selling(order(1, FibStratOrderState::Filled, 0.04, 121));
+		let mut strat = test_strat(vec![filled.clone()], filled).with_post_only_policy(PostOnlyCrossPolicy::Slide);
+		strat.mango_client.order_book = OrderBook { bids: vec![book_order(3_700, 10)], asks: vec![book_order(3_800, 10)] };
+		strat.mango_client.set_price(0.039);
+		strat.decide_bearish().unwrap();
+		let placed = strat.mango_client.last_order().unwrap();
+		assert_eq!(placed.order_type, OrderType::PostOnlySlide);
+		assert!(placed.price > 0.038);
+	}
+	
+	#[test]
+	fn decide_bearish_delays_scale_in_against_bid_pressure() {
+		let filled = FibState::Selling(order(1, FibStratOrderState::Filled, 0.04, 121));
+		let mut strat = test_strat(vec![filled.clone()], filled.clone())
+			  .with_imbalance_filter(ImbalanceFilter { within_bps: 100.0, max_adverse_imbalance: 0.6 });
+		strat.mango_client.order_book = OrderBook { bids: vec![book_order(4_090, 500)], asks: vec![book_order(4_110, 20)] };
+		strat.mango_client.set_price(0.041);
+		strat.decide_bearish().unwrap();
+		assert!(strat.mango_client.last_order().is_none());
+		assert_eq!(strat.position.current_state, filled);
+	}
+	
+	#[test]
+	fn schedule_cancels_once_and_resumes() {
+		let filled = FibState::Selling(order(1, FibStratOrderState::Filled, 0.04, 121));
+		let mut strat = test_strat(vec![filled.clone()], filled)
+			  .with_schedule(TradingSchedule { daily: vec![], events: vec![EventWindow { name: "cpi".to_string(), starts_at: 100, ends_at: 200 }] });
+		assert!(!strat.check_schedule(50).unwrap());
+		assert!(strat.check_schedule(100).unwrap());
+		assert!(strat.check_schedule(150).unwrap());
+		assert_eq!(strat.mango_client.cancel_all_count.get(), 1);
+		assert!(!strat.check_schedule(200).unwrap());
+		assert!(!strat.standing_down);
+	}
+	
+	#[test]
+	fn decide_bearish_refuses_scale_in_over_max_leverage() {
+		let filled = FibState::Selling(order(1, FibStratOrderState::Filled, 0.04, 121));
+		let mut strat = test_strat(vec![filled.clone()], filled.clone())
+			  .with_risk_manager(RiskManager::default().with_limits(FIB_STRATEGY_NAME, RiskLimits { max_leverage: 1.0 }));
+		strat.mango_client.equity = I80F48::from_num(10_000_000);
+		strat.mango_client.set_base_position(-121);
+		strat.mango_client.set_price(0.041);
+		strat.decide_bearish().unwrap();
+		assert!(strat.mango_client.last_order().is_none());
+		assert_eq!(strat.position.current_state, filled);
+	}
+	
+	#[test]
+	fn decide_bearish_explains_the_round() {
+		let filled = FibState::Selling(order(1, FibStratOrderState::Filled, 0.04, 121));
+		let mut strat = test_strat(vec![filled.clone()], filled.clone())
+			  .with_risk_manager(RiskManager::default().with_limits(FIB_STRATEGY_NAME, RiskLimits { max_leverage: 1.0 }));
+		strat.mango_client.equity = I80F48::from_num(10_000_000);
+		strat.mango_client.set_base_position(-121);
+		strat.mango_client.set_price(0.041);
+		strat.decide_bearish().unwrap();
+		let explanation = strat.last_explanation.clone().unwrap();
+		assert_eq!(explanation.rule, "risk manager refused the scale-in");
+		assert_eq!(explanation.inputs.depth, 1);
+		assert_eq!(strat.bot_state(0).last_explanation, Some(explanation));
+		
+		strat.mango_client.set_price(0.039);
+		strat.decide_bearish().unwrap();
+		let explanation = strat.last_explanation.unwrap();
+		assert_eq!(explanation.rule, "reference below average, take profit");
+		assert!(explanation.action.starts_with("placed"));
+	}
+	
+	#[test]
+	fn ensure_reduce_only_forces_take_profits() {
+		let filled = FibState::Selling(order(2, FibStratOrderState::Filled, 0.04, 121));
+		let mut strat = test_strat(vec![filled.clone()], filled).with_ensure_reduce_only();
+		strat.mango_client.set_price(0.039);
+		strat.decide_bearish().unwrap();
+		assert!(strat.mango_client.last_order().unwrap().reduce_only);
+		assert!(strat.audit_take_profit(-121, -60));
+	}
+	
+	#[test]
+	fn decide_bearish_batches_skipped_levels() {
+		let filled = FibState::Selling(order(1, FibStratOrderState::Filled, 0.04, 121));
+		let mut strat = test_strat(vec![filled.clone()], filled).with_scale_in_batching(4);
+		// past the depth 2 and 3 prices but not depth 4
+		strat.mango_client.set_price(0.0403);
+		strat.decide_bearish().unwrap();
+		let placed = strat.mango_client.last_order().unwrap();
+		assert_eq!(placed.side, Side::Ask);
+		assert_eq!(placed.quantity, strat.get_quantity_lots_at_n(2).unwrap() + strat.get_quantity_lots_at_n(3).unwrap());
+		assert!(matches!(strat.position.current_state, FibState::Selling(FibStratOrder { depth: 3, legs: 2, .. })));
+	}
+	
+	#[test]
+	fn scales_trade_amount_with_equity() {
+		let mut strat = test_strat(vec![], FibState::Neutral);
+		let fixed_quantity = strat.get_quantity_lots_at_n(2).unwrap();
+		// 1000 USDC of equity
+		strat = strat.with_trade_amount(TradeAmount::EquityFraction(0.03));
+		assert_eq!(strat.refresh_trade_amount().unwrap(), 30.0);
+		assert_eq!(strat.get_quantity_lots_at_n(2).unwrap(), fixed_quantity);
+		strat.mango_client.equity = I80F48::from_num(2_000_000_000);
+		strat.refresh_trade_amount().unwrap();
+		assert!(strat.get_quantity_lots_at_n(2).unwrap() > fixed_quantity);
+	}
+	
+	#[test]
+	fn imports_exported_position_on_another_account() {
+		let filled = FibState::Selling(order(2, FibStratOrderState::Filled, 0.04, 121));
+		let waiting = FibState::Buying(order(2, FibStratOrderState::Waiting, 0.039, 0));
+		let mut strat = test_strat(vec![filled.clone(), filled], waiting);
+		strat.base_trade_amount = 42.0;
+		let export = strat.export_position().unwrap();
+		let mut other = test_strat(vec![], FibState::Neutral);
+		other.mango_client.mango_account_pk = Pubkey::new_unique();
+		assert!(other.import_position(export.clone(), false).is_err());
+		other.mango_client.set_base_position(-242);
+		other.import_position(export, false).unwrap();
+		assert_eq!(other.position.state_history.len(), 2);
+		assert_eq!(other.position.current_state.order().unwrap().tx_hash, None);
+		assert_eq!(other.base_trade_amount, 42.0);
+	}
+	
+	#[test]
+	fn splits_market_exit_into_ioc_clips() {
+		let mut strat = test_strat(vec![], FibState::Neutral);
+		strat.mango_client.order_book = OrderBook { bids: vec![book_order(4_000, 10), book_order(3_990, 10), book_order(3_900, 100)], asks: vec![] };
+		strat.market_order_with_base(Side::Ask, 50, true, &mut vec![]).unwrap();
+		assert_eq!(strat.mango_client.placed_orders.borrow().len(), 1);
+		assert_eq!(strat.mango_client.last_order().unwrap().order_type, OrderType::Market);
+		
+		strat.mango_client.placed_orders.borrow_mut().clear();
+		strat = strat.with_entry_impact(EntryImpactLimit { max_impact_bps: 30.0, max_clips: 5, clip_interval: Duration::ZERO });
+		let mut signatures = vec![];
+		strat.market_order_with_base(Side::Ask, 50, true, &mut signatures).unwrap();
+		let placed = strat.mango_client.placed_orders.borrow().clone();
+		assert_eq!(signatures.len(), 3);
+		assert_eq!(placed.iter().map(|order| order.quantity).collect::<Vec<_>>(), vec![21, 21, 8]);
+		assert!(placed.iter().all(|order| order.order_type == OrderType::ImmediateOrCancel && order.reduce_only));
+		assert_eq!(placed[0].price, 0.039);
+	}
+	
+	#[test]
+	fn keeps_what_a_failed_entry_filled() {
+		let mut strat = test_strat(vec![], FibState::initial(PriceSide::Sell))
+			  .with_entry_impact(EntryImpactLimit { max_impact_bps: 30.0, max_clips: 5, clip_interval: Duration::ZERO });
+		strat.mango_client.order_book = OrderBook { bids: vec![book_order(4_000, 10), book_order(3_990, 10), book_order(3_900, 1_000)], asks: vec![] };
+		// the first clip sells 21 lots, the second one fails
+		strat.mango_client.max_orders = Some(1);
+		strat.mango_client.push_fill(0);
+		strat.mango_client.push_fill(-21);
+		assert!(strat.init_position().is_err());
+		assert_eq!(strat.position.state_history.len(), 1);
+		let entry = strat.position.state_history[0].order().unwrap();
+		assert_eq!((entry.state, entry.base_size, entry.tx_hash.as_deref()), (FibStratOrderState::PartiallyFilled, 21, Some("mock-1")));
+		assert_eq!(strat.position.base_size(), -21);
+	}
+	
+	#[test]
+	fn prices_lamports_with_the_groups_sol() {
+		let mut strat = test_strat(vec![], FibState::initial(PriceSide::Sell));
+		assert_eq!(strat.lamport_price(), None);
+		// SOL sits wherever the group listed it, not at mainnet's index
+		strat.mango_client.mango_group.tokens[5].mint = wrapped_sol::id();
+		strat.mango_client.mango_cache.price_cache[5].price = I80F48::from_num(0.02);
+		assert_eq!(strat.lamport_price(), Some(0.02));
+	}
+	
+	#[test]
+	fn buy_entry_opens_a_long() {
+		let mut strat = test_strat(vec![], FibState::initial(PriceSide::Buy));
+		assert!(strat.init_position().unwrap());
+		let entry = strat.mango_client.placed_orders.borrow()[0].clone();
+		assert_eq!((entry.side, entry.order_type), (Side::Bid, OrderType::Market));
+		// flat, a reduce only bid would be cancelled instead of opening the position
+		assert!(!entry.reduce_only);
+	}
+	
+	#[test]
+	fn targets_follow_microprice_when_selected() {
+		let filled = FibState::Selling(order(1, FibStratOrderState::Filled, 0.04, 121));
+		let mut strat = test_strat(vec![filled.clone()], filled).with_reference_price(ReferencePrice::Microprice);
+		// the cache still shows 0.04 while the book moved up to 0.041
+		strat.mango_client.order_book = OrderBook { bids: vec![book_order(4_090, 10)], asks: vec![book_order(4_110, 10)] };
+		assert_eq!(strat.reference_price(), 0.041);
+		strat.decide_bearish().unwrap();
+		let placed = strat.mango_client.last_order().unwrap();
+		assert_eq!(placed.side, Side::Ask);
+		assert!(placed.price > 0.041);
+		strat.mango_client.order_book = OrderBook::default();
+		assert_eq!(strat.reference_price(), 0.04);
+	}
+	
+	#[test]
+	fn decide_bearish_scales_in_above_average() {
+		let filled = FibState::Selling(order(1, FibStratOrderState::Filled, 0.04, 121));
+		let mut strat = test_strat(vec![filled.clone()], filled);
+		strat.mango_client.set_price(0.041);
+		strat.decide_bearish().unwrap();
+		let placed = strat.mango_client.last_order().unwrap();
+		assert_eq!(placed.side, Side::Ask);
+		assert_eq!(placed.order_type, OrderType::PostOnly);
+		assert!(!placed.reduce_only);
+		assert!(placed.price > 0.041);
+		assert_eq!(placed.quantity, strat.get_quantity_lots_at_n(2).unwrap());
+		assert!(matches!(strat.position.current_state, FibState::Selling(FibStratOrder { depth: 2, state: FibStratOrderState::Waiting, .. })));
 	}
 }
 
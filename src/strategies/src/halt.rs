@@ -0,0 +1,95 @@
+use std::time::Duration;
+
+use mangol_mango::types::{MangoCache, PerpMarket, PerpMarketData};
+
+/// What a halted or de-listed market shows in, sampled every decision round
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MarketActivity {
+	/// Last oracle price and funding updates the keepers wrote to the mango cache, unix seconds
+	pub price_updated_at: u64,
+	pub funding_updated_at: u64,
+	pub open_interest: i64,
+	/// Grows with every order placed on the market by anyone
+	pub seq_num: u64,
+}
+
+impl MarketActivity {
+	pub fn read(market: &PerpMarketData, mango_cache: &MangoCache, perp_market: &PerpMarket) -> Self {
+		Self {
+			price_updated_at: market.price_cache(mango_cache).last_update,
+			funding_updated_at: market.perp_market_cache(mango_cache).last_update,
+			open_interest: perp_market.open_interest,
+			seq_num: perp_market.seq_num,
+		}
+	}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MarketHalt {
+	/// Keepers stopped updating the market's price or funding
+	StaleCache { age_secs: u64 },
+	/// No order placed and no change in open interest
+	Frozen { since_secs: u64 },
+}
+
+/// Flags a market whose cache stopped updating or whose trading froze, so strategies stop
+/// resting PostOnly orders in a dead book
+#[derive(Clone, Debug)]
+pub struct HaltDetector {
+	pub max_cache_age: Duration,
+	pub max_frozen: Duration,
+	/// Open interest and seq num last seen changing, with when
+	last_change: Option<(i64, u64, u64)>,
+}
+
+impl Default for HaltDetector {
+	fn default() -> Self {
+		Self { max_cache_age: Duration::from_secs(5 * 60), max_frozen: Duration::from_secs(2 * 60 * 60), last_change: None }
+	}
+}
+
+impl HaltDetector {
+	pub fn new(max_cache_age: Duration, max_frozen: Duration) -> Self {
+		Self { max_cache_age, max_frozen, last_change: None }
+	}
+
+	/// The halt `activity` shows at `now_ts`, None while the market is live
+	pub fn observe(&mut self, activity: &MarketActivity, now_ts: u64) -> Option<MarketHalt> {
+		let changed = match self.last_change {
+			Some((open_interest, seq_num, _)) => open_interest != activity.open_interest || seq_num != activity.seq_num,
+			None => true
+		};
+		if changed {
+			self.last_change = Some((activity.open_interest, activity.seq_num, now_ts));
+		}
+		let cache_age = now_ts.saturating_sub(activity.price_updated_at.min(activity.funding_updated_at));
+		if cache_age > self.max_cache_age.as_secs() {
+			return Some(MarketHalt::StaleCache { age_secs: cache_age });
+		}
+		let frozen_for = now_ts.saturating_sub(self.last_change.map(|(_, _, changed_at)| changed_at).unwrap_or(now_ts));
+		if frozen_for > self.max_frozen.as_secs() {
+			return Some(MarketHalt::Frozen { since_secs: frozen_for });
+		}
+		None
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+	use crate::halt::{HaltDetector, MarketActivity, MarketHalt};
+
+	#[test]
+	fn flags_stale_cache_and_frozen_trading() {
+		let mut detector = HaltDetector::new(Duration::from_secs(60), Duration::from_secs(600));
+		let mut activity = MarketActivity { price_updated_at: 1_000, funding_updated_at: 1_000, open_interest: 50, seq_num: 7 };
+		assert_eq!(detector.observe(&activity, 1_030), None);
+		assert_eq!(detector.observe(&activity, 1_100), Some(MarketHalt::StaleCache { age_secs: 100 }));
+		// keepers keep cranking but nobody trades
+		activity.price_updated_at = 1_700;
+		activity.funding_updated_at = 1_700;
+		assert_eq!(detector.observe(&activity, 1_700), Some(MarketHalt::Frozen { since_secs: 670 }));
+		activity.seq_num += 1;
+		assert_eq!(detector.observe(&activity, 1_710), None);
+	}
+}
@@ -0,0 +1,66 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Pauses new order placement for one strategy while its monitoring keeps running.
+///
+/// Engaged when any of these is set:
+/// - the file `<dir>/<strategy>.kill` exists, for cron and ops scripts
+/// - the env var `MANGOL_KILL_<STRATEGY>` is `1` or `true`
+/// - `engage` was called on any clone, for an in-process control plane
+#[derive(Clone, Debug)]
+pub struct KillSwitch {
+	pub strategy: String,
+	pub dir: PathBuf,
+	engaged: Arc<AtomicBool>,
+}
+
+impl KillSwitch {
+	pub fn new(strategy: &str, dir: PathBuf) -> Self {
+		Self {
+			strategy: strategy.to_string(),
+			dir,
+			engaged: Arc::new(AtomicBool::new(false)),
+		}
+	}
+
+	pub fn file_path(&self) -> PathBuf {
+		self.dir.join(format!("{}.kill", self.strategy))
+	}
+
+	pub fn env_var(&self) -> String {
+		format!("MANGOL_KILL_{}", self.strategy.to_uppercase())
+	}
+
+	pub fn engage(&self) {
+		self.engaged.store(true, Ordering::SeqCst);
+	}
+
+	pub fn release(&self) {
+		self.engaged.store(false, Ordering::SeqCst);
+	}
+
+	pub fn is_engaged(&self) -> bool {
+		self.engaged.load(Ordering::SeqCst)
+			  || self.file_path().exists()
+			  || std::env::var(self.env_var()).map(|value| value == "1" || value.eq_ignore_ascii_case("true")).unwrap_or(false)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::kill_switch::KillSwitch;
+
+	#[test]
+	fn engages_from_file_and_flag() {
+		let kill_switch = KillSwitch::new("kill-switch-test", std::env::temp_dir());
+		assert!(!kill_switch.is_engaged());
+		std::fs::write(kill_switch.file_path(), "").unwrap();
+		assert!(kill_switch.is_engaged());
+		std::fs::remove_file(kill_switch.file_path()).unwrap();
+		kill_switch.clone().engage();
+		assert!(kill_switch.is_engaged());
+		kill_switch.release();
+		assert!(!kill_switch.is_engaged());
+	}
+}
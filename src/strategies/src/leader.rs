@@ -0,0 +1,176 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use mangol_common::errors::{MangolError, MangolResult};
+use mangol_solana::lock_file::LockFile;
+use serde::{Deserialize, Serialize};
+
+/// Only `holder` trades until `expires_at`, unix seconds
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Lease {
+	pub holder: String,
+	pub expires_at: u64,
+}
+
+/// Where instances of one strategy on different hosts agree on who trades. try_acquire has to be
+/// atomic for every instance sharing the store, e.g. a file on a shared volume, redis `SET NX PX`
+/// or a consul session
+pub trait LeaseStore: Send + Sync {
+	/// Takes the lease for `holder` until `now_ts + ttl_secs` when it is free, expired or already
+	/// `holder`'s. Returns the lease found before and the one in force after
+	fn try_acquire(&self, holder: &str, now_ts: u64, ttl_secs: u64) -> MangolResult<(Option<Lease>, Lease)>;
+	/// Gives the lease up if `holder` has it, a standby then takes over without waiting for it to expire
+	fn release(&self, holder: &str) -> MangolResult<()>;
+}
+
+/// The lease as json in `path`, every read-modify-write under `<path>.lock`. The volume has to
+/// honour exclusive creates across hosts, NFSv3 and later do
+#[derive(Clone, Debug)]
+pub struct FileLeaseStore {
+	pub path: PathBuf,
+	/// How long to wait for another instance's read-modify-write
+	pub lock_wait: Duration,
+}
+
+/// A lock file older than this was left by an instance that died mid-update
+const LOCK_STALE_AFTER: Duration = Duration::from_secs(10);
+
+impl FileLeaseStore {
+	pub fn new(path: PathBuf) -> Self {
+		Self { path, lock_wait: Duration::from_secs(5) }
+	}
+
+	fn lock_path(&self) -> PathBuf {
+		self.path.with_extension("lock")
+	}
+
+	fn locked<T, F: FnOnce() -> MangolResult<T>>(&self, update: F) -> MangolResult<T> {
+		let lock_path = self.lock_path();
+		let _lock = LockFile::acquire(&lock_path, self.lock_wait, LOCK_STALE_AFTER)?
+			  .ok_or_else(|| MangolError::MangoError(format!("Lease lock {:?} is busy", lock_path)))?;
+		update()
+	}
+
+	fn read(&self) -> Option<Lease> {
+		serde_json::from_str(&std::fs::read_to_string(&self.path).ok()?).ok()
+	}
+
+	fn write(&self, lease: &Lease) -> MangolResult<()> {
+		let data = serde_json::to_string(lease).map_err(|e| MangolError::SerializationError(e.to_string()))?;
+		let partial = self.path.with_extension("partial");
+		std::fs::write(&partial, data)?;
+		std::fs::rename(partial, &self.path)?;
+		Ok(())
+	}
+}
+
+impl LeaseStore for FileLeaseStore {
+	fn try_acquire(&self, holder: &str, now_ts: u64, ttl_secs: u64) -> MangolResult<(Option<Lease>, Lease)> {
+		self.locked(|| {
+			let current = self.read();
+			match &current {
+				Some(lease) if lease.holder != holder && lease.expires_at > now_ts => Ok((current.clone(), lease.clone())),
+				_ => {
+					let lease = Lease { holder: holder.to_string(), expires_at: now_ts + ttl_secs };
+					self.write(&lease)?;
+					Ok((current, lease))
+				}
+			}
+		})
+	}
+
+	fn release(&self, holder: &str) -> MangolResult<()> {
+		self.locked(|| {
+			if self.read().map(|lease| lease.holder == holder).unwrap_or(false) {
+				std::fs::remove_file(&self.path)?;
+			}
+			Ok(())
+		})
+	}
+}
+
+/// What renewing the lease changed for this instance
+#[derive(Clone, Debug, PartialEq)]
+pub enum Leadership {
+	Leading,
+	Standby { holder: String },
+	/// Just took the lease, `previous` is the instance that held it and let it expire
+	Acquired { previous: Option<String> },
+	/// Another instance took the lease while this one still thought it led, e.g. after a stall
+	Lost { holder: String },
+}
+
+/// A hot instance and warm standbys sharing the persisted state: whoever holds the lease trades,
+/// the others renew until it expires and the first to get it takes over. Renewed once a round, so
+/// a leader stuck for longer than the ttl is replaced and finds out on its next round. Clones share
+/// whether this instance leads, tasks sending transactions next to the trader check it on theirs
+#[derive(Clone)]
+pub struct LeaderElection {
+	pub instance: String,
+	pub ttl_secs: u64,
+	store: Arc<dyn LeaseStore>,
+	leading: Arc<AtomicBool>,
+}
+
+impl LeaderElection {
+	pub fn new(instance: &str, store: Arc<dyn LeaseStore>, ttl_secs: u64) -> Self {
+		Self { instance: instance.to_string(), ttl_secs, store, leading: Arc::new(AtomicBool::new(false)) }
+	}
+
+	pub fn is_leader(&self) -> bool {
+		self.leading.load(Ordering::SeqCst)
+	}
+
+	/// How often a standby retries, a third of the ttl
+	pub fn retry_interval(&self) -> Duration {
+		Duration::from_secs((self.ttl_secs / 3).max(1))
+	}
+
+	pub fn renew(&mut self, now_ts: u64) -> MangolResult<Leadership> {
+		let (previous, lease) = self.store.try_acquire(&self.instance, now_ts, self.ttl_secs)?;
+		let holds = lease.holder == self.instance;
+		let leadership = match (self.is_leader(), holds) {
+			(true, true) => Leadership::Leading,
+			(false, true) => Leadership::Acquired { previous: previous.map(|lease| lease.holder).filter(|holder| *holder != self.instance) },
+			(true, false) => Leadership::Lost { holder: lease.holder },
+			(false, false) => Leadership::Standby { holder: lease.holder },
+		};
+		self.leading.store(holds, Ordering::SeqCst);
+		Ok(leadership)
+	}
+
+	/// Hands the lease over on a clean shutdown
+	pub fn resign(&mut self) -> MangolResult<()> {
+		self.leading.store(false, Ordering::SeqCst);
+		self.store.release(&self.instance)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use crate::leader::{FileLeaseStore, LeaderElection, Leadership};
+
+	#[test]
+	fn standby_takes_over_once_the_lease_expires() {
+		let dir = std::env::temp_dir().join(format!("mangol-leader-{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let store = Arc::new(FileLeaseStore::new(dir.join("fib.lease")));
+		let mut primary = LeaderElection::new("primary", store.clone(), 60);
+		let mut standby = LeaderElection::new("standby", store, 60);
+		let standby_task = standby.clone();
+		assert_eq!(primary.renew(1_000).unwrap(), Leadership::Acquired { previous: None });
+		assert_eq!(standby.renew(1_010).unwrap(), Leadership::Standby { holder: "primary".to_string() });
+		assert_eq!(primary.renew(1_030).unwrap(), Leadership::Leading);
+		// the primary stalls past its lease
+		assert!(!standby_task.is_leader());
+		assert_eq!(standby.renew(1_091).unwrap(), Leadership::Acquired { previous: Some("primary".to_string()) });
+		assert!(standby_task.is_leader());
+		assert_eq!(primary.renew(1_095).unwrap(), Leadership::Lost { holder: "standby".to_string() });
+		standby.resign().unwrap();
+		assert_eq!(primary.renew(1_100).unwrap(), Leadership::Acquired { previous: None });
+		std::fs::remove_dir_all(dir).unwrap();
+	}
+}
@@ -1,3 +1,49 @@
 pub mod watch_mango_traders;
+#[cfg(feature = "liquidator")]
 pub mod watch_and_liquidate;
+#[cfg(feature = "liquidator")]
+pub mod scanner;
+#[cfg(feature = "liquidator")]
+pub mod liquidator_mode;
 pub mod fib_trader;
+pub mod fib_state;
+pub mod trade_feed;
+pub mod replay;
+pub mod mngo_maintenance;
+pub mod schedule;
+pub mod pnl_settlement;
+pub mod risk;
+pub mod kill_switch;
+pub mod dead_man;
+pub mod expiry;
+pub mod timing;
+pub mod stats;
+pub mod market_data;
+pub mod strategy;
+pub mod position_transfer;
+pub mod preflight;
+pub mod halt;
+pub mod dashboard;
+#[cfg(feature = "liquidator")]
+pub mod unwind;
+#[cfg(feature = "backtest")]
+pub mod optimizer;
+#[cfg(feature = "backtest")]
+pub mod paper;
+#[cfg(feature = "backtest")]
+pub mod risk_of_ruin;
+pub mod signer_rotation;
+pub mod borrow_repay;
+pub mod liquidity;
+pub mod explain;
+pub mod watchdog;
+pub mod reconcile;
+pub mod activity;
+pub mod accounting;
+pub mod bus;
+pub mod quarantine;
+pub mod leader;
+#[cfg(feature = "backtest")]
+pub mod shadow;
+pub mod watch_list;
+pub mod control_api;
@@ -0,0 +1,111 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+/// What the liquidator does with an account it finds liquidatable
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LiquidationMode {
+	/// Only notify
+	AlertOnly,
+	/// Build and simulate the liquidation and log its expected profit, never send it
+	DryRun,
+	/// Send the liquidation once its simulation passes
+	Execute,
+}
+
+impl Default for LiquidationMode {
+	fn default() -> Self {
+		LiquidationMode::AlertOnly
+	}
+}
+
+impl LiquidationMode {
+	pub fn parse(mode: &str) -> Option<Self> {
+		match mode.trim() {
+			"alert-only" | "alert" => Some(LiquidationMode::AlertOnly),
+			"dry-run" => Some(LiquidationMode::DryRun),
+			"execute" => Some(LiquidationMode::Execute),
+			_ => None
+		}
+	}
+
+	fn from_u8(mode: u8) -> Self {
+		match mode {
+			1 => LiquidationMode::DryRun,
+			2 => LiquidationMode::Execute,
+			_ => LiquidationMode::AlertOnly,
+		}
+	}
+
+	fn as_u8(&self) -> u8 {
+		match self {
+			LiquidationMode::AlertOnly => 0,
+			LiquidationMode::DryRun => 1,
+			LiquidationMode::Execute => 2,
+		}
+	}
+}
+
+/// The liquidator's mode, switchable while it runs. The file `<dir>/liquidator.mode` holding
+/// `alert-only`, `dry-run` or `execute` overrides the mode set in process, so ops can fall back to
+/// alerts without a restart
+#[derive(Clone, Debug)]
+pub struct ModeSwitch {
+	pub dir: Option<PathBuf>,
+	mode: Arc<AtomicU8>,
+}
+
+impl ModeSwitch {
+	pub fn new(mode: LiquidationMode) -> Self {
+		Self { dir: None, mode: Arc::new(AtomicU8::new(mode.as_u8())) }
+	}
+
+	pub fn with_dir(mut self, dir: PathBuf) -> Self {
+		self.dir = Some(dir);
+		self
+	}
+
+	pub fn file_path(&self) -> Option<PathBuf> {
+		self.dir.as_ref().map(|dir| dir.join("liquidator.mode"))
+	}
+
+	/// Switches every clone, a mode file still takes precedence
+	pub fn set(&self, mode: LiquidationMode) {
+		self.mode.store(mode.as_u8(), Ordering::SeqCst);
+	}
+
+	pub fn mode(&self) -> LiquidationMode {
+		let from_file = self.file_path()
+			  .and_then(|path| std::fs::read_to_string(path).ok())
+			  .and_then(|mode| LiquidationMode::parse(&mode));
+		from_file.unwrap_or_else(|| LiquidationMode::from_u8(self.mode.load(Ordering::SeqCst)))
+	}
+}
+
+impl Default for ModeSwitch {
+	fn default() -> Self {
+		Self::new(LiquidationMode::default())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::liquidator_mode::{LiquidationMode, ModeSwitch};
+
+	#[test]
+	fn file_overrides_the_mode_set_in_process() {
+		let dir = std::env::temp_dir().join(format!("mangol-liquidator-mode-{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let switch = ModeSwitch::default().with_dir(dir.clone());
+		assert_eq!(switch.mode(), LiquidationMode::AlertOnly);
+		switch.clone().set(LiquidationMode::Execute);
+		assert_eq!(switch.mode(), LiquidationMode::Execute);
+		std::fs::write(switch.file_path().unwrap(), "dry-run\n").unwrap();
+		assert_eq!(switch.mode(), LiquidationMode::DryRun);
+		std::fs::remove_dir_all(dir).unwrap();
+		assert_eq!(switch.mode(), LiquidationMode::Execute);
+	}
+}
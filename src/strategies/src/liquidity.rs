@@ -0,0 +1,97 @@
+use std::collections::VecDeque;
+
+use mangol_mango::book::OrderBook;
+use mangol_mango::types::Side;
+
+/// Resting size around each price over the last snapshots of the book, sampled every decision
+/// round. A level that keeps holding orders comparable to ours is one the market trades at in
+/// size, instead of one our order would be most of
+#[derive(Clone, Debug)]
+pub struct LiquidityHistory {
+	pub max_snapshots: usize,
+	/// Orders within this distance of a price count toward its level
+	pub band_bps: f64,
+	/// Resting size counted at a level over the intended order size for it to qualify
+	pub min_coverage: f64,
+	/// (bids, asks) as (lot price, base lots) of each snapshot, oldest first
+	snapshots: VecDeque<(Vec<(i64, i64)>, Vec<(i64, i64)>)>,
+}
+
+impl Default for LiquidityHistory {
+	fn default() -> Self {
+		Self::new(60, 25.0, 1.0)
+	}
+}
+
+impl LiquidityHistory {
+	pub fn new(max_snapshots: usize, band_bps: f64, min_coverage: f64) -> Self {
+		Self { max_snapshots: max_snapshots.max(1), band_bps, min_coverage, snapshots: VecDeque::new() }
+	}
+
+	pub fn observe(&mut self, book: &OrderBook) {
+		if self.snapshots.len() == self.max_snapshots {
+			self.snapshots.pop_front();
+		}
+		self.snapshots.push_back((book.levels(Side::Bid, usize::MAX), book.levels(Side::Ask, usize::MAX)));
+	}
+
+	pub fn len(&self) -> usize {
+		self.snapshots.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.snapshots.is_empty()
+	}
+
+	/// Average base lots resting on `side` within `band_bps` of `lot_price`, None before the first snapshot
+	pub fn resting_near(&self, side: Side, lot_price: i64) -> Option<f64> {
+		if self.snapshots.is_empty() {
+			return None;
+		}
+		let band = lot_price as f64 * self.band_bps / 10_000.0;
+		let total: i64 = self.snapshots.iter().map(|(bids, asks)| {
+			let levels = match side {
+				Side::Bid => bids,
+				Side::Ask => asks,
+			};
+			levels.iter().filter(|(price, _)| (*price - lot_price).abs() as f64 <= band).map(|(_, quantity)| quantity).sum::<i64>()
+		}).sum();
+		Some(total as f64 / self.snapshots.len() as f64)
+	}
+
+	/// First of `candidates`, (depth, lot price, base lots) shallowest first, whose level held enough
+	/// resting size for the order on `side`. None when none did or there is no history yet
+	pub fn shallowest_liquid(&self, side: Side, candidates: &[(u16, i64, i64)]) -> Option<u16> {
+		candidates.iter()
+			  .find(|(_, lot_price, size)| self.resting_near(side, *lot_price).map(|resting| resting >= *size as f64 * self.min_coverage).unwrap_or(false))
+			  .map(|(depth, _, _)| *depth)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use mangol_mango::book::{BookOrder, OrderBook};
+	use mangol_mango::types::Side;
+	use solana_sdk::pubkey::Pubkey;
+	use crate::liquidity::LiquidityHistory;
+
+	fn bid(price: i64, quantity: i64) -> BookOrder {
+		BookOrder { key: (price as i128) << 64, owner: Pubkey::default(), owner_slot: 0, order_type: 0, time_in_force: 0, price, quantity, client_order_id: 0, timestamp: 0 }
+	}
+
+	#[test]
+	fn picks_shallowest_level_with_resting_size() {
+		let mut history = LiquidityHistory::new(2, 10.0, 1.0);
+		let candidates = [(4, 3_900, 50), (5, 3_800, 50), (6, 3_600, 50)];
+		assert_eq!(history.shallowest_liquid(Side::Bid, &candidates), None);
+		history.observe(&OrderBook { bids: vec![bid(3_900, 10), bid(3_802, 40), bid(3_800, 80), bid(3_600, 500)], asks: vec![] });
+		assert_eq!(history.resting_near(Side::Bid, 3_800), Some(120.0));
+		assert_eq!(history.shallowest_liquid(Side::Bid, &candidates), Some(5));
+		// averaged over the window, the level emptied out on the second snapshot
+		history.observe(&OrderBook { bids: vec![bid(3_900, 10), bid(3_600, 500)], asks: vec![] });
+		assert_eq!(history.shallowest_liquid(Side::Bid, &candidates), Some(5));
+		history.observe(&OrderBook { bids: vec![bid(3_900, 10), bid(3_600, 500)], asks: vec![] });
+		assert_eq!(history.len(), 2);
+		assert_eq!(history.shallowest_liquid(Side::Bid, &candidates), Some(6));
+	}
+}
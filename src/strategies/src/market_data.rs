@@ -0,0 +1,94 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use mangol_common::errors::{MangolError, MangolResult};
+use mangol_mango::book::OrderBook;
+use mangol_mango::client::MangoClient;
+use mangol_mango::types::{PerpMarketData, Side};
+use serde::{Deserialize, Serialize};
+
+/// Top of one perp market's book, levels as (lot price, base lots) best first
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct L2Snapshot {
+	pub market: String,
+	pub slot: u64,
+	pub timestamp: u64,
+	pub bids: Vec<(i64, i64)>,
+	pub asks: Vec<(i64, i64)>,
+}
+
+impl L2Snapshot {
+	pub fn new(market: &str, slot: u64, timestamp: u64, book: &OrderBook, depth: usize) -> Self {
+		Self {
+			market: market.to_string(),
+			slot,
+			timestamp,
+			bids: book.levels(Side::Bid, depth),
+			asks: book.levels(Side::Ask, depth),
+		}
+	}
+}
+
+/// Appends L2 snapshots of `markets` to `dir/book-<market>.jsonl` every `interval`,
+/// for fill simulation in backtests and slippage analysis after the fact
+pub struct BookRecorder {
+	pub mango_client: MangoClient,
+	pub markets: Vec<PerpMarketData>,
+	pub dir: PathBuf,
+	pub depth: usize,
+	pub interval: Duration,
+}
+
+fn book_path(dir: &PathBuf, market: &str) -> PathBuf {
+	dir.join(format!("book-{}.jsonl", market))
+}
+
+/// Recorded snapshots of `market` in `dir`, oldest first
+pub fn load_snapshots(dir: &str, market: &str) -> MangolResult<Vec<L2Snapshot>> {
+	let data = std::fs::read_to_string(book_path(&PathBuf::from(dir), market))?;
+	data.lines().filter(|line| !line.is_empty())
+		  .map(|line| serde_json::from_str(line).map_err(|e| MangolError::SerializationError(e.to_string())))
+		  .collect()
+}
+
+impl BookRecorder {
+	pub fn new(mango_client: MangoClient, markets: Vec<PerpMarketData>, dir: &str) -> MangolResult<Self> {
+		std::fs::create_dir_all(dir)?;
+		Ok(Self {
+			mango_client,
+			markets,
+			dir: PathBuf::from(dir),
+			depth: 10,
+			interval: Duration::from_secs(5),
+		})
+	}
+
+	pub fn append(&self, snapshot: &L2Snapshot) -> MangolResult<()> {
+		let line = serde_json::to_string(snapshot).map_err(|e| MangolError::SerializationError(e.to_string()))?;
+		let mut file = OpenOptions::new().create(true).append(true).open(book_path(&self.dir, &snapshot.market))?;
+		writeln!(file, "{}", line)?;
+		Ok(())
+	}
+
+	pub fn record_once(&self) -> MangolResult<()> {
+		for market in &self.markets {
+			let (slot, book) = self.mango_client.load_order_book_with_slot(market)?;
+			self.append(&L2Snapshot::new(&market.name, slot, self.mango_client.clock.now_ts(), &book, self.depth))?;
+		}
+		Ok(())
+	}
+
+	pub fn start(self) -> JoinHandle<()> {
+		std::thread::spawn(move || {
+			loop {
+				if let Err(e) = self.record_once() {
+					eprintln!("[-] Failed to record order books {:?}", e);
+				}
+				self.mango_client.clock.sleep(self.interval);
+			}
+		})
+	}
+}
@@ -0,0 +1,139 @@
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use mangol_common::errors::MangolResult;
+use mangol_mango::client::MangoClient;
+use mangol_mango::ids::mngo_token;
+use mangol_solana::swap::JupiterSwap;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::leader::LeaderElection;
+use crate::watchdog::Heartbeats;
+
+pub const MNGO_MAINTAINER_NAME: &str = "mngo_maintainer";
+
+/// Keeps the account's MNGO deposits above the group's `ref_mngo_required`,
+/// below it every taker fill pays the referral surcharge
+pub struct MngoDepositMaintainer {
+	pub mango_client: MangoClient,
+	/// Mint swapped into MNGO when topping up, usually USDC
+	pub quote_mint: Pubkey,
+	pub check_interval: Duration,
+	/// Top up to `ref_mngo_required * (1 + buffer_ratio)` so index drift doesn't drop us below again
+	pub buffer_ratio: f64,
+	/// Extra quote spent on the swap to cover slippage and price movement
+	pub slippage_ratio: f64,
+	pub heartbeats: Option<Heartbeats>,
+	/// Shared with the trader, a standby leaves the account to the instance holding the lease
+	pub leader_election: Option<LeaderElection>,
+}
+
+/// Native MNGO needed to bring `deposits` to the buffered requirement, 0 when already above `required`
+pub fn top_up_amount(deposits: f64, required: u64, buffer_ratio: f64) -> u64 {
+	if deposits >= required as f64 {
+		0
+	} else {
+		(required as f64 * (1.0 + buffer_ratio) - deposits).ceil() as u64
+	}
+}
+
+/// Native MNGO to deposit after a swap, what the wallet gained capped at the `deficit` it was bought for
+pub fn swapped_amount(balance_before: u64, balance_after: u64, deficit: u64) -> u64 {
+	balance_after.saturating_sub(balance_before).min(deficit)
+}
+
+impl MngoDepositMaintainer {
+	pub fn new(mango_client: MangoClient, quote_mint: Pubkey) -> Self {
+		Self {
+			mango_client,
+			quote_mint,
+			check_interval: Duration::from_secs(60 * 60),
+			buffer_ratio: 0.05,
+			slippage_ratio: 0.02,
+			heartbeats: None,
+			leader_election: None,
+		}
+	}
+
+	pub fn with_heartbeats(mut self, heartbeats: Heartbeats) -> Self {
+		self.heartbeats = Some(heartbeats);
+		self
+	}
+
+	pub fn with_leader_election(mut self, leader_election: LeaderElection) -> Self {
+		self.leader_election = Some(leader_election);
+		self
+	}
+
+	fn is_leader(&self) -> bool {
+		self.leader_election.as_ref().map(|leader_election| leader_election.is_leader()).unwrap_or(true)
+	}
+
+	/// Returns (mngo token index, native MNGO to top up), None if the group has no MNGO token
+	pub fn mngo_deficit(&self) -> Option<(usize, u64)> {
+		let mango_group = &self.mango_client.mango_group;
+		let mngo_index = mango_group.find_token_index(&mngo_token::id())?;
+		let mngo_cache = &self.mango_client.mango_cache.root_bank_cache[mngo_index];
+		let deposits = self.mango_client.mango_account.get_native_deposit(mngo_cache, mngo_index).ok()?;
+		Some((mngo_index, top_up_amount(deposits.to_num::<f64>(), mango_group.ref_mngo_required, self.buffer_ratio)))
+	}
+
+	/// Swaps quote into MNGO and deposits it when below the requirement, returns the deposited amount
+	pub fn check_and_top_up(&mut self) -> MangolResult<Option<u64>> {
+		self.mango_client.update()?;
+		let (mngo_index, deficit) = match self.mngo_deficit() {
+			Some((mngo_index, deficit)) if deficit > 0 => (mngo_index, deficit),
+			_ => return Ok(None)
+		};
+		let mngo_mint = self.mango_client.mango_group.tokens[mngo_index].mint;
+		let mngo_price = self.mango_client.mango_cache.get_price(mngo_index);
+		let quote_amount = (deficit as f64 * mngo_price * (1.0 + self.slippage_ratio)).ceil() as u64;
+		println!("[?] MNGO deposits below ref_mngo_required by {}, swapping {} quote", deficit, quote_amount);
+
+		// MNGO already in the wallet isn't ours to deposit, only what the swap brought in
+		let balance_before = self.mango_client.get_wallet_token_balance(&mngo_mint)?;
+		JupiterSwap::new(&self.mango_client.solana_connection).swap(&self.quote_mint, &mngo_mint, quote_amount, &self.mango_client.signer)?;
+		let swapped = swapped_amount(balance_before, self.mango_client.get_wallet_token_balance(&mngo_mint)?, deficit);
+		if swapped == 0 {
+			eprintln!("[-] MNGO swap returned nothing, skipping deposit");
+			return Ok(None);
+		}
+		let tx_hash = self.mango_client.deposit(mngo_index, swapped)?;
+		println!("[+] Deposited {} MNGO https://explorer.solana.com/tx/{}", swapped, tx_hash);
+		Ok(Some(swapped))
+	}
+
+	pub fn start(mut self) -> JoinHandle<()> {
+		std::thread::spawn(move || {
+			loop {
+				if let Some(heartbeats) = &self.heartbeats {
+					heartbeats.beat(MNGO_MAINTAINER_NAME);
+				}
+				if self.is_leader() {
+					if let Err(e) = self.check_and_top_up() {
+						eprintln!("[-] MNGO deposit maintenance failed {:?}", e);
+					}
+				}
+				std::thread::sleep(self.check_interval);
+			}
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::mngo_maintenance::{swapped_amount, top_up_amount};
+
+	#[test]
+	fn tops_up_only_below_requirement() {
+		assert_eq!(top_up_amount(10_000_000_000.0, 10_000_000_000, 0.05), 0);
+		assert_eq!(top_up_amount(9_000_000_000.0, 10_000_000_000, 0.05), 1_500_000_000);
+	}
+
+	#[test]
+	fn deposits_only_what_the_swap_brought_in() {
+		assert_eq!(swapped_amount(5_000, 6_500, 2_000), 1_500);
+		assert_eq!(swapped_amount(5_000, 9_000, 2_000), 2_000);
+		assert_eq!(swapped_amount(5_000, 5_000, 2_000), 0);
+	}
+}
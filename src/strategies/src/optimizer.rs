@@ -0,0 +1,563 @@
+use std::ops::Range;
+
+use mangol_common::errors::{MangolError, MangolResult};
+use mangol_mango::mock::MockMangoClient;
+use mangol_mango::types::PerpMarketData;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use mangol_mango::types::Side;
+
+use crate::fib_state::{FibState, FibStratOrderState};
+use crate::fib_trader::{FibParams, FibStrat, PriceSide};
+use crate::market_data::L2Snapshot;
+use crate::paper::PaperOrder;
+use crate::replay::RecordedSession;
+use crate::risk_of_ruin::{simulate, RuinConfig, RuinEstimate, TradeOutcome};
+
+/// Oracle price at a unix timestamp, native quote per native base
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+pub struct PricePoint {
+	pub timestamp: u64,
+	pub price: f64,
+}
+
+/// Every oracle price the recorded sessions saw, oldest first
+pub fn price_series(sessions: &[RecordedSession]) -> Vec<PricePoint> {
+	let mut prices: Vec<PricePoint> = sessions.iter()
+		  .flat_map(|session| std::iter::once(PricePoint { timestamp: session.started_at, price: session.initial_oracle_price })
+			  .chain(session.steps.iter().map(|step| PricePoint { timestamp: step.timestamp, price: step.oracle_price })))
+		  // sessions recorded before steps had timestamps can't be placed in time
+		  .filter(|point| point.timestamp > 0)
+		  .collect();
+	prices.sort_by_key(|point| point.timestamp);
+	prices
+}
+
+/// The price of every decision round in `range`, the latest one recorded at or before the round
+pub fn sample_rounds(prices: &[PricePoint], range: Range<u64>, interval_secs: u64) -> Vec<f64> {
+	sample_timed_rounds(prices, range, interval_secs).into_iter().map(|(_, price)| price).collect()
+}
+
+/// `sample_rounds` with the timestamp of each round
+pub fn sample_timed_rounds(prices: &[PricePoint], range: Range<u64>, interval_secs: u64) -> Vec<(u64, f64)> {
+	let mut rounds = vec![];
+	let mut next = 0;
+	let mut last = None;
+	let mut round_ts = range.start;
+	while round_ts < range.end {
+		while next < prices.len() && prices[next].timestamp <= round_ts {
+			last = Some(prices[next].price);
+			next += 1;
+		}
+		if let Some(price) = last {
+			rounds.push((round_ts, price));
+		}
+		round_ts += interval_secs.max(1);
+	}
+	rounds
+}
+
+/// The market a backtest trades, as recorded with the sessions
+#[derive(Clone, Debug)]
+pub struct BacktestMarket {
+	pub market: PerpMarketData,
+	pub base_lot_size: i64,
+	pub quote_lot_size: i64,
+	/// BookRecorder snapshots resting orders are matched against, oldest first. Without them
+	/// orders fill in full as soon as the oracle touches their price
+	pub books: Vec<L2Snapshot>,
+}
+
+impl BacktestMarket {
+	pub fn from_session(session: &RecordedSession) -> Self {
+		Self { market: session.market.clone(), base_lot_size: session.base_lot_size, quote_lot_size: session.quote_lot_size, books: vec![] }
+	}
+
+	pub fn with_books(mut self, mut books: Vec<L2Snapshot>) -> Self {
+		books.sort_by_key(|book| book.timestamp);
+		self.books = books;
+		self
+	}
+
+	/// The latest snapshot at or before `timestamp`
+	pub fn book_at(&self, timestamp: u64) -> Option<&L2Snapshot> {
+		let after = self.books.partition_point(|book| book.timestamp <= timestamp);
+		after.checked_sub(1).map(|i| &self.books[i])
+	}
+}
+
+/// One FibStrat parameter set under test
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+pub struct FibCandidate {
+	pub fib_params: FibParams,
+	pub max_position_depth: u16,
+	pub action_interval_secs: u64,
+}
+
+impl FibCandidate {
+	/// The environment main reads the parameters from
+	pub fn config_snippet(&self) -> String {
+		format!(
+			"MANGOL_FIB_RATIO={}\nMANGOL_PRICE_FIB_RATIO={}\nMANGOL_MAX_POSITION_DEPTH={}\nMANGOL_ACTION_INTERVAL_SECS={}",
+			self.fib_params.fib_ratio, self.fib_params.price_fib_ratio, self.max_position_depth, self.action_interval_secs
+		)
+	}
+}
+
+/// Values searched per parameter
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParameterGrid {
+	pub fib_ratios: Vec<f64>,
+	pub price_fib_ratios: Vec<f64>,
+	pub depths: Vec<u16>,
+	pub intervals: Vec<u64>,
+}
+
+impl Default for ParameterGrid {
+	fn default() -> Self {
+		Self {
+			fib_ratios: vec![1.382, 1.618, 2.0],
+			price_fib_ratios: vec![0.1, 0.1618, 0.25],
+			depths: vec![6, 8, 10],
+			intervals: vec![15, 43, 120],
+		}
+	}
+}
+
+fn bounds<T: Copy + PartialOrd>(values: &[T]) -> Option<(T, T)> {
+	let mut values = values.iter().copied();
+	let first = values.next()?;
+	Some(values.fold((first, first), |(low, high), value| (if value < low { value } else { low }, if value > high { value } else { high })))
+}
+
+impl ParameterGrid {
+	/// Every combination of the grid's values
+	pub fn candidates(&self) -> Vec<FibCandidate> {
+		let mut candidates = vec![];
+		for &fib_ratio in &self.fib_ratios {
+			for &price_fib_ratio in &self.price_fib_ratios {
+				for &max_position_depth in &self.depths {
+					for &action_interval_secs in &self.intervals {
+						candidates.push(FibCandidate { fib_params: FibParams { fib_ratio, price_fib_ratio }, max_position_depth, action_interval_secs });
+					}
+				}
+			}
+		}
+		candidates
+	}
+
+	/// `samples` sets drawn uniformly between each parameter's smallest and largest grid value,
+	/// for grids too large to search exhaustively
+	pub fn random_candidates(&self, samples: usize, seed: u64) -> Vec<FibCandidate> {
+		let (fib_ratios, price_fib_ratios, depths, intervals) = match (bounds(&self.fib_ratios), bounds(&self.price_fib_ratios), bounds(&self.depths), bounds(&self.intervals)) {
+			(Some(fib_ratios), Some(price_fib_ratios), Some(depths), Some(intervals)) => (fib_ratios, price_fib_ratios, depths, intervals),
+			_ => return vec![]
+		};
+		let mut rng = StdRng::seed_from_u64(seed);
+		let mut uniform = |(low, high): (f64, f64)| if high > low { rng.gen_range(low, high) } else { low };
+		(0..samples).map(|_| FibCandidate {
+			fib_params: FibParams { fib_ratio: uniform(fib_ratios), price_fib_ratio: uniform(price_fib_ratios) },
+			max_position_depth: uniform((depths.0 as f64, depths.1 as f64 + 1.0)).floor() as u16,
+			action_interval_secs: uniform((intervals.0 as f64, intervals.1 as f64 + 1.0)).floor() as u64,
+		}).collect()
+	}
+}
+
+/// Native quote amounts
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct BacktestResult {
+	/// Closed positions plus the open one at the last price
+	pub pnl: f64,
+	pub max_drawdown: f64,
+	pub fills: usize,
+	pub positions: usize,
+	/// Every position, the one still open marked at the last price
+	pub trades: Vec<TradeOutcome>,
+}
+
+impl BacktestResult {
+	fn add(&mut self, other: &BacktestResult) {
+		self.pnl += other.pnl;
+		self.max_drawdown = self.max_drawdown.max(other.max_drawdown);
+		self.fills += other.fills;
+		self.positions += other.positions;
+		self.trades.extend_from_slice(&other.trades);
+	}
+}
+
+fn base_position(strat: &FibStrat<MockMangoClient>) -> i64 {
+	strat.market.perp_account(&strat.mango_client.mango_account).base_position
+}
+
+/// Opens a bearish position at `price` on a fresh mock account
+fn open_position(market: &BacktestMarket, candidate: &FibCandidate, price: f64) -> MangolResult<FibStrat<MockMangoClient>> {
+	let mut mango_client = MockMangoClient::new(market.market.market_index, market.base_lot_size, market.quote_lot_size);
+	mango_client.set_price(price);
+	// fills the entry so init_position stops waiting on it, then set to the size it committed
+	mango_client.push_fill(-1);
+	let mut strat = FibStrat::new(candidate.max_position_depth, candidate.action_interval_secs, mango_client, PriceSide::Sell, market.market.clone())?
+		  .with_fib_params(candidate.fib_params);
+	strat.init_position()?;
+	let entry = strat.position.base_size();
+	strat.mango_client.set_base_position(entry);
+	Ok(strat)
+}
+
+/// (signature, side, lot price, base lots, price) of the order the strategy waits on, base lots
+/// sized the way sync_bearish expects the fill
+fn waiting_order(strat: &FibStrat<MockMangoClient>) -> Option<(String, Side, i64, i64, f64)> {
+	let (order, side) = match &strat.position.current_state {
+		FibState::Selling(order) => (order, Side::Ask),
+		FibState::Buying(order) => (order, Side::Bid),
+		FibState::Neutral => return None
+	};
+	if order.state != FibStratOrderState::Waiting {
+		return None;
+	}
+	let quantity = strat.mango_client.placed_orders.borrow().last()?.quantity;
+	let lot_price = strat.market.perp_market_info(&strat.mango_client.mango_group).lot_to_native_price(order.price);
+	if lot_price <= 0 {
+		return None;
+	}
+	Some((order.tx_hash.clone().unwrap_or_default(), side, lot_price, quantity / lot_price, order.price))
+}
+
+fn signed(side: Side, base_lots: i64) -> i64 {
+	match side {
+		Side::Bid => base_lots,
+		Side::Ask => -base_lots,
+	}
+}
+
+/// Signed base lots and price the waiting order fills with once the oracle trades through it
+fn resting_fill(strat: &FibStrat<MockMangoClient>, price: f64) -> Option<(i64, f64)> {
+	let (_, side, _, base_lots, order_price) = waiting_order(strat)?;
+	let crossed = match side {
+		Side::Ask => price >= order_price,
+		Side::Bid => price <= order_price,
+	};
+	crossed.then(|| (signed(side, base_lots), order_price))
+}
+
+/// The waiting order queued in the recorded book, with its signature and the snapshot it was last advanced to
+struct PaperMatch {
+	signature: String,
+	order: PaperOrder,
+	book: L2Snapshot,
+}
+
+/// Queues an order the strategy just placed behind the size resting at its price in `book`
+fn queue_paper(strat: &FibStrat<MockMangoClient>, paper: &mut Option<PaperMatch>, book: &L2Snapshot) {
+	*paper = match waiting_order(strat) {
+		Some((signature, _, _, _, _)) if paper.as_ref().map(|matched| matched.signature == signature).unwrap_or(false) => return,
+		Some((signature, side, lot_price, base_lots, _)) => Some(PaperMatch { signature, order: PaperOrder::place(side, lot_price, base_lots, book), book: book.clone() }),
+		None => None
+	};
+}
+
+/// Signed base lots and price the queued order fills with as the recorded book moves to `book`.
+/// Whatever fills is committed by the strategy, so the match ends there
+fn paper_fill(strat: &FibStrat<MockMangoClient>, paper: &mut Option<PaperMatch>, book: &L2Snapshot) -> Option<(i64, f64)> {
+	let (signature, side, _, _, order_price) = waiting_order(strat)?;
+	let matched = paper.as_mut().filter(|matched| matched.signature == signature)?;
+	let filled = matched.order.advance(&matched.book, book);
+	matched.book = book.clone();
+	if filled == 0 {
+		return None;
+	}
+	*paper = None;
+	Some((signed(side, filled), order_price))
+}
+
+/// Drives FibStrat through one price per round on a mock account. Resting orders fill in full at
+/// their price once the oracle reaches it, without fees or queue position, and a position the
+/// strategy resets is closed at the oracle before the next one opens
+pub fn backtest(market: &BacktestMarket, rounds: &[f64], candidate: &FibCandidate) -> MangolResult<BacktestResult> {
+	run_backtest(market, rounds.iter().map(|price| (None, *price)), candidate)
+}
+
+/// `backtest` on rounds of (timestamp, price). With recorded books resting orders queue behind the
+/// size at their price and fill, partially too, only once the book trades through it
+pub fn backtest_timed(market: &BacktestMarket, rounds: &[(u64, f64)], candidate: &FibCandidate) -> MangolResult<BacktestResult> {
+	run_backtest(market, rounds.iter().map(|(timestamp, price)| (Some(*timestamp), *price)), candidate)
+}
+
+fn run_backtest<I: Iterator<Item = (Option<u64>, f64)>>(market: &BacktestMarket, rounds: I, candidate: &FibCandidate) -> MangolResult<BacktestResult> {
+	let mut session = PaperSession::new(market, *candidate);
+	for (timestamp, price) in rounds {
+		session.step(price, timestamp.and_then(|timestamp| market.book_at(timestamp)))?;
+	}
+	Ok(session.result())
+}
+
+/// A candidate trading on paper one round at a time. Backtests replay recorded rounds through it,
+/// shadow runs feed it the live ones
+pub struct PaperSession {
+	pub candidate: FibCandidate,
+	market: BacktestMarket,
+	result: BacktestResult,
+	cash: f64,
+	peak: f64,
+	strat: Option<FibStrat<MockMangoClient>>,
+	// equity when the current position opened and how it has gone since
+	trade_start: f64,
+	trade: TradeOutcome,
+	paper: Option<PaperMatch>,
+}
+
+impl PaperSession {
+	pub fn new(market: &BacktestMarket, candidate: FibCandidate) -> Self {
+		Self {
+			candidate,
+			market: BacktestMarket { market: market.market.clone(), base_lot_size: market.base_lot_size, quote_lot_size: market.quote_lot_size, books: vec![] },
+			result: BacktestResult::default(),
+			cash: 0.0,
+			peak: 0.0,
+			strat: None,
+			trade_start: 0.0,
+			trade: TradeOutcome::default(),
+			paper: None,
+		}
+	}
+
+	/// The paper strategy of the open position, None between positions
+	pub fn strat(&self) -> Option<&FibStrat<MockMangoClient>> {
+		self.strat.as_ref()
+	}
+
+	/// Results so far, the open position marked at the last price
+	pub fn result(&self) -> BacktestResult {
+		let mut result = self.result.clone();
+		if self.strat.is_some() {
+			result.trades.push(self.trade);
+		}
+		result
+	}
+
+	/// One decision round at `price`, resting orders matched against `book` when there is one
+	pub fn step(&mut self, price: f64, book: Option<&L2Snapshot>) -> MangolResult<()> {
+		let base_lot_size = self.market.base_lot_size as f64;
+		let mut closed = false;
+		match &mut self.strat {
+			None => {
+				let opened = open_position(&self.market, &self.candidate, price)?;
+				self.trade_start = self.cash;
+				self.trade = TradeOutcome::default();
+				self.cash -= base_position(&opened) as f64 * base_lot_size * price;
+				self.result.fills += 1;
+				self.result.positions += 1;
+				if let Some(book) = book {
+					queue_paper(&opened, &mut self.paper, book);
+				}
+				self.strat = Some(opened);
+			}
+			Some(current) => {
+				let fill = match book {
+					Some(book) => paper_fill(current, &mut self.paper, book),
+					None => resting_fill(current, price)
+				};
+				if let Some((lots, fill_price)) = fill {
+					self.cash -= lots as f64 * base_lot_size * fill_price;
+					self.result.fills += 1;
+				}
+				current.mango_client.push_price(price);
+				current.mango_client.push_fill(fill.map(|(lots, _)| lots).unwrap_or(0));
+				current.sync_bearish()?;
+				current.decide_bearish()?;
+				if let Some(book) = book {
+					queue_paper(current, &mut self.paper, book);
+				}
+				if current.position.current_state == FibState::Neutral || current.get_position_size()? == 0 {
+					self.cash += base_position(current) as f64 * base_lot_size * price;
+					closed = true;
+				}
+			}
+		}
+		if closed {
+			self.strat = None;
+			self.paper = None;
+		}
+		let notional = self.strat.as_ref().map(|current| base_position(current) as f64 * base_lot_size * price).unwrap_or(0.0);
+		let equity = self.cash + notional;
+		if self.strat.is_some() || closed {
+			self.trade.pnl = equity - self.trade_start;
+			self.trade.worst_excursion = self.trade.worst_excursion.min(self.trade.pnl);
+			self.trade.peak_notional = self.trade.peak_notional.max(notional.abs());
+		}
+		if closed {
+			self.result.trades.push(self.trade);
+		}
+		self.peak = self.peak.max(equity);
+		self.result.max_drawdown = self.result.max_drawdown.max(self.peak - equity);
+		self.result.pnl = equity;
+		Ok(())
+	}
+}
+
+/// Rolling train and test windows, the parameters picked on a train window are judged on the test
+/// window right after it
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct WalkForward {
+	pub train_secs: u64,
+	pub test_secs: u64,
+}
+
+impl Default for WalkForward {
+	fn default() -> Self {
+		Self { train_secs: 7 * 86_400, test_secs: 2 * 86_400 }
+	}
+}
+
+impl WalkForward {
+	/// (train, test) windows over `range`, stepping by the test length
+	pub fn windows(&self, range: Range<u64>) -> Vec<(Range<u64>, Range<u64>)> {
+		let mut windows = vec![];
+		let mut start = range.start;
+		while self.test_secs > 0 && start + self.train_secs + self.test_secs <= range.end {
+			let split = start + self.train_secs;
+			windows.push((start..split, split..split + self.test_secs));
+			start += self.test_secs;
+		}
+		windows
+	}
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LeaderboardEntry {
+	pub candidate: FibCandidate,
+	/// Summed over the train windows
+	pub in_sample: BacktestResult,
+	/// Summed over the test windows
+	pub out_of_sample: BacktestResult,
+	/// Train windows this candidate had the best pnl on
+	pub selected_folds: usize,
+	/// Out of sample trades resampled against the account size the optimization was given
+	pub risk_of_ruin: Option<RuinEstimate>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Optimization {
+	/// Best in sample pnl first, out of sample results are only ever read, never ranked on
+	pub leaderboard: Vec<LeaderboardEntry>,
+	/// Candidates whose backtest errored, with the error
+	pub failed: Vec<(FibCandidate, String)>,
+	pub folds: usize,
+	/// The candidate with the best pnl on each fold's train window, in fold order
+	pub picks: Vec<FibCandidate>,
+	/// Each fold's pick judged on its test window, summed: what walking forward would have made
+	pub walk_forward: BacktestResult,
+}
+
+impl Optimization {
+	/// The pick of the latest train window, the set to run next
+	pub fn best(&self) -> Option<&LeaderboardEntry> {
+		let latest = self.picks.last()?;
+		self.leaderboard.iter().find(|entry| entry.candidate == *latest)
+	}
+
+	/// The top `rows` of the leaderboard and the config of the best set
+	pub fn report(&self, rows: usize) -> String {
+		let mut lines = vec![format!("{} candidates over {} walk-forward folds, {} failed", self.leaderboard.len() + self.failed.len(), self.folds, self.failed.len())];
+		lines.push(format!("walk-forward out of sample: pnl {:.0} drawdown {:.0} fills {}", self.walk_forward.pnl, self.walk_forward.max_drawdown, self.walk_forward.fills));
+		lines.push("rank fib_ratio price_fib_ratio depth interval | is pnl | oos pnl oos drawdown oos fills | picked | ruin % breaker % liq %".to_string());
+		let percent = |probability: f64| format!("{:.1}", probability * 100.0);
+		for (rank, entry) in self.leaderboard.iter().take(rows).enumerate() {
+			let (ruin, drawdown, liquidation) = match &entry.risk_of_ruin {
+				Some(estimate) => (percent(estimate.ruin_probability), percent(estimate.drawdown_probability), percent(estimate.liquidation_probability)),
+				None => ("-".to_string(), "-".to_string(), "-".to_string())
+			};
+			lines.push(format!(
+				"{:>4} {:>9.4} {:>15.4} {:>5} {:>8} | {:>6.0} | {:>7.0} {:>12.0} {:>9} | {:>6} | {:>6} {:>9} {:>5}",
+				rank + 1, entry.candidate.fib_params.fib_ratio, entry.candidate.fib_params.price_fib_ratio, entry.candidate.max_position_depth, entry.candidate.action_interval_secs,
+				entry.in_sample.pnl, entry.out_of_sample.pnl, entry.out_of_sample.max_drawdown, entry.out_of_sample.fills, entry.selected_folds, ruin, drawdown, liquidation
+			));
+		}
+		if let Some(best) = self.best() {
+			lines.push(String::new());
+			lines.push(best.candidate.config_snippet());
+		}
+		lines.join("\n")
+	}
+}
+
+/// Backtests every candidate on every walk-forward window of `prices`, candidates in parallel, and
+/// estimates each one's risk of ruin on `ruin_config` from its out of sample trades
+pub fn optimize(market: &BacktestMarket, prices: &[PricePoint], candidates: &[FibCandidate], walk_forward: &WalkForward, ruin_config: &RuinConfig) -> MangolResult<Optimization> {
+	let range = match (prices.first(), prices.last()) {
+		(Some(first), Some(last)) => first.timestamp..last.timestamp + 1,
+		_ => return Err(MangolError::MangoError("No recorded prices to optimize on".to_string()))
+	};
+	let windows = walk_forward.windows(range);
+	if windows.is_empty() {
+		return Err(MangolError::MangoError(format!("Recorded prices are shorter than one {}s train and {}s test window", walk_forward.train_secs, walk_forward.test_secs)));
+	}
+	let evaluated: Vec<(FibCandidate, MangolResult<Vec<(BacktestResult, BacktestResult)>>)> = candidates.par_iter()
+		  .map(|candidate| (*candidate, windows.iter().map(|(train, test)| {
+			  let in_sample = backtest_timed(market, &sample_timed_rounds(prices, train.clone(), candidate.action_interval_secs), candidate)?;
+			  let out_of_sample = backtest_timed(market, &sample_timed_rounds(prices, test.clone(), candidate.action_interval_secs), candidate)?;
+			  Ok((in_sample, out_of_sample))
+		  }).collect()))
+		  .collect();
+
+	let mut optimization = Optimization { folds: windows.len(), ..Optimization::default() };
+	let mut fold_results = vec![];
+	for (candidate, results) in evaluated {
+		match results {
+			Ok(results) => {
+				let mut entry = LeaderboardEntry { candidate, in_sample: BacktestResult::default(), out_of_sample: BacktestResult::default(), selected_folds: 0, risk_of_ruin: None };
+				for (in_sample, out_of_sample) in &results {
+					entry.in_sample.add(in_sample);
+					entry.out_of_sample.add(out_of_sample);
+				}
+				entry.risk_of_ruin = simulate(&entry.out_of_sample.trades, ruin_config);
+				fold_results.push(results);
+				optimization.leaderboard.push(entry);
+			}
+			Err(e) => optimization.failed.push((candidate, format!("{:?}", e))),
+		}
+	}
+	for fold in 0..windows.len() {
+		let picked = pick_fold(&fold_results, fold);
+		if let Some(picked) = picked {
+			optimization.leaderboard[picked].selected_folds += 1;
+			optimization.picks.push(optimization.leaderboard[picked].candidate);
+			optimization.walk_forward.add(&fold_results[picked][fold].1);
+		}
+	}
+	optimization.leaderboard.sort_by(|a, b| b.in_sample.pnl.partial_cmp(&a.in_sample.pnl).unwrap_or(std::cmp::Ordering::Equal));
+	Ok(optimization)
+}
+
+/// Index of the candidate with the best in sample pnl on `fold`, the test window plays no part
+fn pick_fold(fold_results: &[Vec<(BacktestResult, BacktestResult)>], fold: usize) -> Option<usize> {
+	(0..fold_results.len()).max_by(|a, b| fold_results[*a][fold].0.pnl.partial_cmp(&fold_results[*b][fold].0.pnl).unwrap_or(std::cmp::Ordering::Equal))
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::optimizer::{pick_fold, sample_rounds, BacktestResult, ParameterGrid, PricePoint, WalkForward};
+
+	#[test]
+	fn builds_grid_walk_forward_windows_and_rounds() {
+		let grid = ParameterGrid { fib_ratios: vec![1.5, 1.618], price_fib_ratios: vec![0.1618], depths: vec![6, 10], intervals: vec![43] };
+		assert_eq!(grid.candidates().len(), 4);
+		let sampled = grid.random_candidates(20, 7);
+		assert_eq!(sampled.len(), 20);
+		assert!(sampled.iter().all(|candidate| (1.5..1.618).contains(&candidate.fib_params.fib_ratio) && (6..=10).contains(&candidate.max_position_depth)));
+		assert_eq!(sampled, grid.random_candidates(20, 7));
+
+		let walk_forward = WalkForward { train_secs: 100, test_secs: 50 };
+		assert_eq!(walk_forward.windows(0..260), vec![(0..100, 100..150), (50..150, 150..200), (100..200, 200..250)]);
+
+		let prices = vec![PricePoint { timestamp: 10, price: 1.0 }, PricePoint { timestamp: 25, price: 2.0 }];
+		assert_eq!(sample_rounds(&prices, 0..40, 10), vec![1.0, 1.0, 2.0]);
+
+		// the second candidate wins out of sample but the first trained better, the first is picked
+		let result = |pnl: f64| BacktestResult { pnl, ..BacktestResult::default() };
+		let fold_results = vec![vec![(result(10.0), result(-5.0))], vec![(result(4.0), result(50.0))]];
+		assert_eq!(pick_fold(&fold_results, 0), Some(0));
+	}
+}
@@ -0,0 +1,95 @@
+use mangol_mango::types::Side;
+
+use crate::market_data::L2Snapshot;
+
+fn levels(book: &L2Snapshot, side: Side) -> &[(i64, i64)] {
+	match side {
+		Side::Bid => &book.bids,
+		Side::Ask => &book.asks,
+	}
+}
+
+/// Base lots resting on `side` of `book` at `lot_price` or better, which fill before an order there
+pub fn size_at_or_better(book: &L2Snapshot, side: Side, lot_price: i64) -> i64 {
+	levels(book, side).iter().filter(|(price, _)| match side {
+		Side::Bid => *price >= lot_price,
+		Side::Ask => *price <= lot_price,
+	}).map(|(_, quantity)| quantity).sum()
+}
+
+/// A paper order matched against recorded book snapshots. It joins the back of its level, size
+/// ahead of it leaves the queue as the level shrinks, and it only fills once the book traded
+/// through its price, by the size that went past it or in full when the other side now rests there.
+/// Cancels ahead of it look like trades in snapshots, so the queue moves up on them too
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PaperOrder {
+	pub side: Side,
+	pub lot_price: i64,
+	/// Base lots not filled yet
+	pub remaining: i64,
+	/// Base lots still queued ahead of it
+	pub queue_ahead: i64,
+}
+
+impl PaperOrder {
+	pub fn place(side: Side, lot_price: i64, base_lots: i64, book: &L2Snapshot) -> Self {
+		Self { side, lot_price, remaining: base_lots, queue_ahead: size_at_or_better(book, side, lot_price) }
+	}
+
+	/// Base lots filled between the `previous` and `next` snapshots
+	pub fn advance(&mut self, previous: &L2Snapshot, next: &L2Snapshot) -> i64 {
+		if self.remaining <= 0 {
+			return 0;
+		}
+		let before = size_at_or_better(previous, self.side, self.lot_price);
+		let after = size_at_or_better(next, self.side, self.lot_price);
+		let consumed = (before - after).max(0);
+		let opposite = match self.side {
+			Side::Bid => Side::Ask,
+			Side::Ask => Side::Bid,
+		};
+		let filled = if size_at_or_better(next, opposite, self.lot_price) > 0 {
+			// the other side rests at our price, nothing left between it and the order
+			self.remaining
+		} else if after == 0 {
+			(consumed - self.queue_ahead).clamp(0, self.remaining)
+		} else {
+			0
+		};
+		self.queue_ahead = (self.queue_ahead - consumed).max(0).min(after);
+		self.remaining -= filled;
+		filled
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use mangol_mango::types::Side;
+	use crate::market_data::L2Snapshot;
+	use crate::paper::PaperOrder;
+
+	fn book(bids: Vec<(i64, i64)>, asks: Vec<(i64, i64)>) -> L2Snapshot {
+		L2Snapshot { market: "SOL-PERP".to_string(), slot: 0, timestamp: 0, bids, asks }
+	}
+
+	#[test]
+	fn fills_behind_the_queue_and_partially() {
+		let placed = book(vec![(100, 5)], vec![(102, 30), (103, 50)]);
+		let mut ask = PaperOrder::place(Side::Ask, 102, 20, &placed);
+		assert_eq!(ask.queue_ahead, 30);
+		// trading at the price isn't enough while the queue ahead is still there, 10 lots join behind
+		let touched = book(vec![(101, 5)], vec![(102, 40), (103, 50)]);
+		assert_eq!(ask.advance(&placed, &touched), 0);
+		assert_eq!(ask.queue_ahead, 30);
+		// the level cleared, 10 lots traded past the queue into the order
+		let through = book(vec![(101, 5)], vec![(103, 45)]);
+		assert_eq!(ask.advance(&touched, &through), 10);
+		assert_eq!((ask.remaining, ask.queue_ahead), (10, 0));
+		// bids moving up to the price take the rest
+		assert_eq!(ask.advance(&through, &book(vec![(102, 40)], vec![(103, 45)])), 10);
+		assert_eq!(ask.remaining, 0);
+
+		let mut bid = PaperOrder::place(Side::Bid, 99, 20, &placed);
+		assert_eq!(bid.advance(&placed, &book(vec![(99, 3)], vec![(102, 30)])), 0);
+	}
+}
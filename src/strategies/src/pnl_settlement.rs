@@ -0,0 +1,114 @@
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use fixed::types::I80F48;
+use mangol_common::errors::MangolResult;
+use mangol_mango::client::MangoClient;
+
+use crate::leader::LeaderElection;
+use crate::watchdog::Heartbeats;
+
+pub const PNL_SETTLER_NAME: &str = "pnl_settler";
+
+/// Settles perp pnl into the quote balance once it grows past a threshold,
+/// unsettled pnl can't back the next rung of the fib ladder until it is settled
+pub struct PnlSettler {
+	pub mango_client: MangoClient,
+	pub market_indexes: Vec<usize>,
+	/// Minimum absolute unsettled pnl in native quote worth a settle transaction
+	pub settle_threshold: f64,
+	pub check_interval: Duration,
+	pub heartbeats: Option<Heartbeats>,
+	/// Shared with the trader, a standby leaves the account to the instance holding the lease
+	pub leader_election: Option<LeaderElection>,
+}
+
+/// Whether `pnl` native quote is large enough to settle
+pub fn should_settle(pnl: I80F48, settle_threshold: f64) -> bool {
+	pnl.abs().to_num::<f64>() >= settle_threshold
+}
+
+impl PnlSettler {
+	pub fn new(mango_client: MangoClient, market_indexes: Vec<usize>) -> Self {
+		Self {
+			mango_client,
+			market_indexes,
+			settle_threshold: 10_000_000.0,
+			check_interval: Duration::from_secs(10 * 60),
+			heartbeats: None,
+			leader_election: None,
+		}
+	}
+
+	pub fn with_settle_threshold(mut self, settle_threshold: f64) -> Self {
+		self.settle_threshold = settle_threshold;
+		self
+	}
+
+	pub fn with_heartbeats(mut self, heartbeats: Heartbeats) -> Self {
+		self.heartbeats = Some(heartbeats);
+		self
+	}
+
+	pub fn with_leader_election(mut self, leader_election: LeaderElection) -> Self {
+		self.leader_election = Some(leader_election);
+		self
+	}
+
+	fn is_leader(&self) -> bool {
+		self.leader_election.as_ref().map(|leader_election| leader_election.is_leader()).unwrap_or(true)
+	}
+
+	/// Settles every configured market over the threshold, returns the settle signatures
+	pub fn check_and_settle(&mut self) -> MangolResult<Vec<String>> {
+		self.mango_client.update()?;
+		let mut signatures = vec![];
+		for market_index in &self.market_indexes {
+			let pnl = self.mango_client.get_unsettled_pnl(*market_index);
+			if !should_settle(pnl, self.settle_threshold) {
+				continue;
+			}
+			let (counterparty, counterparty_pnl) = match self.mango_client.find_settle_counterparty(*market_index, pnl)? {
+				Some(counterparty) => counterparty,
+				None => {
+					eprintln!("[-] No counterparty to settle {} pnl on market {}", pnl, market_index);
+					continue;
+				}
+			};
+			println!("[?] Settling {} pnl on market {} against {} ({})", pnl, market_index, counterparty, counterparty_pnl);
+			let tx_hash = self.mango_client.settle_pnl(*market_index, &counterparty)?;
+			println!("[+] Settled pnl https://explorer.solana.com/tx/{}", tx_hash);
+			signatures.push(tx_hash);
+		}
+		Ok(signatures)
+	}
+
+	pub fn start(mut self) -> JoinHandle<()> {
+		std::thread::spawn(move || {
+			loop {
+				if let Some(heartbeats) = &self.heartbeats {
+					heartbeats.beat(PNL_SETTLER_NAME);
+				}
+				if self.is_leader() {
+					if let Err(e) = self.check_and_settle() {
+						eprintln!("[-] Pnl settlement failed {:?}", e);
+					}
+				}
+				std::thread::sleep(self.check_interval);
+			}
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use fixed::types::I80F48;
+	use crate::pnl_settlement::should_settle;
+
+	#[test]
+	fn settles_either_sign_over_threshold() {
+		assert!(should_settle(I80F48::from_num(-12_000_000), 10_000_000.0));
+		assert!(should_settle(I80F48::from_num(10_000_000), 10_000_000.0));
+		assert!(!should_settle(I80F48::from_num(9_999_999), 10_000_000.0));
+	}
+}
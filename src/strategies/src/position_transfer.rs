@@ -0,0 +1,43 @@
+use mangol_common::errors::{MangolError, MangolResult};
+use serde::{Deserialize, Serialize};
+
+use crate::fib_trader::{FibStratPosition, PriceSide};
+
+pub const POSITION_EXPORT_VERSION: u32 = 1;
+
+/// A strategy's position state, the ladder with its history, written by one instance and
+/// imported by another to move the position between servers or accounts without flattening it
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PositionExport {
+	pub version: u32,
+	pub strategy: String,
+	pub market: String,
+	pub market_index: usize,
+	pub sentiment: PriceSide,
+	/// Account the position was exported from
+	pub mango_account: String,
+	pub exported_at: u64,
+	/// Base lots the account held at export
+	pub base_position: i64,
+	pub average_price: f64,
+	/// UI quote amount the ladder was sized with, absent in exports written before it was kept
+	#[serde(default)]
+	pub base_trade_amount: Option<f64>,
+	pub position: FibStratPosition,
+}
+
+impl PositionExport {
+	pub fn save(&self, path: &str) -> MangolResult<()> {
+		let json = serde_json::to_string_pretty(self).map_err(|e| MangolError::SerializationError(e.to_string()))?;
+		std::fs::write(path, json)?;
+		Ok(())
+	}
+
+	pub fn load(path: &str) -> MangolResult<Self> {
+		let export: Self = serde_json::from_str(&std::fs::read_to_string(path)?).map_err(|e| MangolError::SerializationError(e.to_string()))?;
+		if export.version != POSITION_EXPORT_VERSION {
+			return Err(MangolError::SerializationError(format!("position export version {} is not {}", export.version, POSITION_EXPORT_VERSION)));
+		}
+		Ok(export)
+	}
+}
@@ -0,0 +1,202 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use colored::Colorize;
+use mangol_common::errors::{MangolError, MangolResult};
+use mangol_mango::client::{MangoClient, MangoClientApi};
+use mangol_mango::types::{HealthType, MangoAccount, MangoGroup, PerpMarket, PerpMarketData, QUOTE_INDEX};
+use solana_client::pubsub_client::PubsubClient;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signer;
+
+use crate::fib_trader::{FibStrat, TradeAmount};
+
+/// A check that failed before trading started and what to do about it
+#[derive(Clone, Debug, PartialEq)]
+pub struct PreflightFailure {
+	pub check: &'static str,
+	pub reason: String,
+	pub fix: String,
+}
+
+impl PreflightFailure {
+	fn new(check: &'static str, reason: String, fix: &str) -> Self {
+		Self { check, reason, fix: fix.to_string() }
+	}
+}
+
+/// Checks run once before the trading loop so misconfiguration fails loudly at startup instead
+/// of as rejected transactions or silently wrong orders later
+#[derive(Copy, Clone, Debug)]
+pub struct Preflight {
+	/// Oldest solana-core the rpc node may run, (major, minor)
+	pub min_solana_version: (u64, u64),
+	pub ws_timeout: Duration,
+}
+
+impl Default for Preflight {
+	fn default() -> Self {
+		Self { min_solana_version: (1, 9), ws_timeout: Duration::from_secs(10) }
+	}
+}
+
+impl Preflight {
+	/// Every failed check, empty when trading can start
+	pub fn run(&self, mango_client: &MangoClient, markets: &[PerpMarketData], ws_url: &str) -> Vec<PreflightFailure> {
+		let rpc_client = &mango_client.solana_connection.rpc_client;
+		let mut failures = self.check_rpc(rpc_client);
+		failures.extend(self.check_ws(ws_url));
+		failures.extend(check_signer(&mango_client.mango_account, &mango_client.signer.pubkey()));
+		for market in markets {
+			let perp_market = Pubkey::from_str(&market.pubkey).ok()
+				  .and_then(|perp_market_pk| rpc_client.get_account(&perp_market_pk).ok())
+				  .and_then(|account| PerpMarket::load_checked(account, &mango_client.mango_program_id, &mango_client.mango_group_pk).ok());
+			match perp_market {
+				Some(perp_market) => failures.extend(check_market(&mango_client.mango_group, &mango_client.mango_group_pk, market, &perp_market)),
+				None => failures.push(PreflightFailure::new("market", format!("{} perp market {} could not be loaded", market.name, market.pubkey), "fix the pubkey in files/perpMarkets.json"))
+			}
+			// perp only markets have no spot token to check the base against
+			let mint_decimals = |token_index: usize| {
+				let mint = mango_client.mango_group.tokens[token_index].mint;
+				if mint == Pubkey::default() {
+					return None;
+				}
+				mango_client.solana_connection.mint_decimals(&mint).ok()
+			};
+			failures.extend(check_decimals(market, mint_decimals(market.market_index), mint_decimals(QUOTE_INDEX)));
+		}
+		match mango_client.get_health(HealthType::Init) {
+			Ok(health) if health.is_positive() => {}
+			Ok(health) => failures.push(PreflightFailure::new("health", format!("init health is {}", health), "deposit collateral or reduce positions before starting")),
+			Err(e) => failures.push(PreflightFailure::new("health", format!("health could not be computed {:?}", e), "check the account's open orders and the group's cache"))
+		}
+		failures
+	}
+
+	fn check_rpc(&self, rpc_client: &RpcClient) -> Vec<PreflightFailure> {
+		let mut failures = vec![];
+		if let Err(e) = rpc_client.get_health() {
+			failures.push(PreflightFailure::new("rpc", format!("{} is unhealthy {:?}", rpc_client.url(), e), "wait for the node to catch up or set another endpoint in MANGOL_ENDPOINTS"));
+		}
+		match rpc_client.get_version() {
+			Ok(version) => failures.extend(check_solana_version(&version.solana_core, self.min_solana_version)),
+			Err(e) => failures.push(PreflightFailure::new("rpc", format!("{} did not report its version {:?}", rpc_client.url(), e), "check the rpc url"))
+		}
+		failures
+	}
+
+	fn check_ws(&self, ws_url: &str) -> Option<PreflightFailure> {
+		let fix = "check the websocket url, some providers serve it on a separate port";
+		match PubsubClient::slot_subscribe(ws_url) {
+			Ok((_subscription, slots)) => slots.recv_timeout(self.ws_timeout).err()
+				  .map(|_| PreflightFailure::new("websocket", format!("no slot from {} in {:?}", ws_url, self.ws_timeout), fix)),
+			Err(e) => Some(PreflightFailure::new("websocket", format!("could not subscribe to {} {:?}", ws_url, e), fix))
+		}
+	}
+}
+
+pub fn check_solana_version(solana_core: &str, (min_major, min_minor): (u64, u64)) -> Option<PreflightFailure> {
+	let mut parts = solana_core.split('.').map(|part| part.parse::<u64>().unwrap_or(0));
+	let version = (parts.next().unwrap_or(0), parts.next().unwrap_or(0));
+	if version >= (min_major, min_minor) {
+		return None;
+	}
+	Some(PreflightFailure::new("rpc", format!("node runs solana-core {}, {}.{} or newer is required", solana_core, min_major, min_minor), "use an up to date rpc node"))
+}
+
+/// The keystore's key has to own the account or be its delegate to sign orders
+pub fn check_signer(mango_account: &MangoAccount, signer: &Pubkey) -> Option<PreflightFailure> {
+	if mango_account.owner == *signer || mango_account.delegate == *signer {
+		return None;
+	}
+	Some(PreflightFailure::new(
+		"signer",
+		format!("{} is neither owner {} nor delegate {} of the mango account", signer, mango_account.owner, mango_account.delegate),
+		"point MANGOL_KEYSTORE at the owner's key or set it as delegate",
+	))
+}
+
+/// The registry entry has to describe the on-chain market the group lists at its index
+pub fn check_market(mango_group: &MangoGroup, mango_group_pk: &Pubkey, market: &PerpMarketData, perp_market: &PerpMarket) -> Vec<PreflightFailure> {
+	let fix = "regenerate files/perpMarkets.json from the group's ids.json";
+	let mut failures = vec![];
+	let listed = market.perp_market_info(mango_group);
+	if listed.perp_market.to_string() != market.pubkey {
+		failures.push(PreflightFailure::new("market", format!("group lists {} at index {}, registry has {} for {}", listed.perp_market, market.market_index, market.pubkey, market.name), fix));
+	}
+	if perp_market.mango_group != *mango_group_pk {
+		failures.push(PreflightFailure::new("market", format!("{} belongs to group {}", market.name, perp_market.mango_group), fix));
+	}
+	for (name, registry, on_chain) in [("bids", &market.bids_key, perp_market.bids), ("asks", &market.asks_key, perp_market.asks), ("event queue", &market.events_key, perp_market.event_queue)] {
+		if *registry != on_chain.to_string() {
+			failures.push(PreflightFailure::new("market", format!("{} {} is {} in the registry, {} on chain", market.name, name, registry, on_chain), fix));
+		}
+	}
+	if listed.base_lot_size <= 0 || listed.quote_lot_size <= 0 {
+		failures.push(PreflightFailure::new("market", format!("{} lot sizes are {} base {} quote", market.name, listed.base_lot_size, listed.quote_lot_size), "the market is not initialized, pick another"));
+	}
+	failures
+}
+
+/// The registry's decimals have to be the mints', sizes and prices are scaled by them
+pub fn check_decimals(market: &PerpMarketData, base_mint_decimals: Option<u8>, quote_mint_decimals: Option<u8>) -> Vec<PreflightFailure> {
+	let fix = "regenerate files/perpMarkets.json from the group's ids.json";
+	[("base", market.base_decimals, base_mint_decimals), ("quote", market.quote_decimals, quote_mint_decimals)].into_iter()
+		  .filter_map(|(name, registry, mint)| mint.filter(|mint| *mint != registry).map(|mint| {
+			  PreflightFailure::new("market", format!("{} {} decimals are {} in the registry, the mint has {}", market.name, name, registry, mint), fix)
+		  }))
+		  .collect()
+}
+
+/// Strategy settings outside of what the fib ladder was tuned for
+pub fn check_fib_config<C: MangoClientApi>(strat: &FibStrat<C>) -> Vec<PreflightFailure> {
+	let mut failures = vec![];
+	if !(5..=3600).contains(&strat.action_interval_secs) {
+		failures.push(PreflightFailure::new("config", format!("action interval is {}s", strat.action_interval_secs), "use between 5 and 3600 seconds"));
+	}
+	if !(1..=20).contains(&strat.position.max_position_depth) {
+		failures.push(PreflightFailure::new("config", format!("max position depth is {}", strat.position.max_position_depth), "use a depth between 1 and 20"));
+	}
+	let sane = match strat.trade_amount {
+		TradeAmount::Fixed(amount) => amount.is_finite() && amount > 0.0,
+		TradeAmount::EquityFraction(fraction) => fraction > 0.0 && fraction <= 1.0,
+	};
+	if !sane {
+		failures.push(PreflightFailure::new("config", format!("trade amount is {:?}", strat.trade_amount), "use a positive amount or an equity fraction up to 1"));
+	}
+	failures
+}
+
+/// Prints every failure with its fix and errors when there is any
+pub fn abort_on_failures(failures: &[PreflightFailure]) -> MangolResult<()> {
+	if failures.is_empty() {
+		println!("{}", "[+] Preflight passed".green());
+		return Ok(());
+	}
+	for failure in failures {
+		eprintln!("{}", format!("[-] Preflight {}: {}, {}", failure.check, failure.reason, failure.fix).red());
+	}
+	Err(MangolError::MangoError(format!("{} preflight checks failed", failures.len())))
+}
+
+#[cfg(test)]
+mod tests {
+	use mangol_mango::mock::MockMangoClient;
+	use solana_sdk::pubkey::Pubkey;
+	use crate::preflight::{check_signer, check_solana_version};
+
+	#[test]
+	fn rejects_foreign_signer_and_old_nodes() {
+		let mut mango_account = MockMangoClient::new(3, 10_000_000, 100).mango_account;
+		let owner = Pubkey::new_unique();
+		let delegate = Pubkey::new_unique();
+		mango_account.owner = owner;
+		assert!(check_signer(&mango_account, &owner).is_none());
+		assert!(check_signer(&mango_account, &delegate).is_some());
+		mango_account.delegate = delegate;
+		assert!(check_signer(&mango_account, &delegate).is_none());
+		assert!(check_solana_version("1.10.26", (1, 9)).is_none());
+		assert!(check_solana_version("1.8.14", (1, 9)).is_some());
+	}
+}
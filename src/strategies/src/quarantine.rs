@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Failures of one logical order since it last landed
+#[derive(Clone, Debug, PartialEq)]
+pub struct OrderFailures {
+	pub attempts: u32,
+	pub last_error: String,
+	/// Unix seconds the order may be sent again
+	pub retry_at: u64,
+	pub quarantined: bool,
+}
+
+/// Asks a running strategy to release its quarantined orders once the cause is fixed.
+///
+/// Requested when any of these is set, and cleared once the strategy honoured it:
+/// - the file `<dir>/<strategy>.release` exists, for ops scripts
+/// - `request` was called on any clone, for the control api
+#[derive(Clone, Debug)]
+pub struct QuarantineRelease {
+	pub strategy: String,
+	pub dir: PathBuf,
+	requested: Arc<AtomicBool>,
+}
+
+impl QuarantineRelease {
+	pub fn new(strategy: &str, dir: PathBuf) -> Self {
+		Self {
+			strategy: strategy.to_string(),
+			dir,
+			requested: Arc::new(AtomicBool::new(false)),
+		}
+	}
+
+	pub fn file_path(&self) -> PathBuf {
+		self.dir.join(format!("{}.release", self.strategy))
+	}
+
+	pub fn request(&self) {
+		self.requested.store(true, Ordering::SeqCst);
+	}
+
+	/// Whether a release was requested since the last call, the request file is removed
+	pub fn take(&self) -> bool {
+		let file_requested = std::fs::remove_file(self.file_path()).is_ok();
+		self.requested.swap(false, Ordering::SeqCst) || file_requested
+	}
+}
+
+/// Attempts per logical order before it is quarantined, with a backoff doubling from
+/// `base_backoff` up to `max_backoff` between them. A quarantined order is not sent again until
+/// released, so an order the program always rejects pauses the strategy instead of looping
+#[derive(Clone, Debug)]
+pub struct RetryBudget {
+	pub max_attempts: u32,
+	pub base_backoff: Duration,
+	pub max_backoff: Duration,
+	pub release: Option<QuarantineRelease>,
+	failures: HashMap<String, OrderFailures>,
+}
+
+impl RetryBudget {
+	pub fn new(max_attempts: u32, base_backoff: Duration) -> Self {
+		Self { max_attempts, base_backoff, max_backoff: Duration::from_secs(60 * 60), release: None, failures: HashMap::new() }
+	}
+
+	pub fn with_max_backoff(mut self, max_backoff: Duration) -> Self {
+		self.max_backoff = max_backoff;
+		self
+	}
+
+	pub fn with_release(mut self, release: QuarantineRelease) -> Self {
+		self.release = Some(release);
+		self
+	}
+
+	/// Whether `order` may be sent at `now_ts`
+	pub fn ready(&self, order: &str, now_ts: u64) -> bool {
+		self.failures.get(order).map(|failures| !failures.quarantined && now_ts >= failures.retry_at).unwrap_or(true)
+	}
+
+	pub fn failures(&self, order: &str) -> Option<&OrderFailures> {
+		self.failures.get(order)
+	}
+
+	/// Counts a failed send of `order`, returns true when this failure quarantined it
+	pub fn record_failure(&mut self, order: &str, error: &str, now_ts: u64) -> bool {
+		let failures = self.failures.entry(order.to_string()).or_insert(OrderFailures { attempts: 0, last_error: String::new(), retry_at: now_ts, quarantined: false });
+		failures.attempts += 1;
+		failures.last_error = error.to_string();
+		let backoff = self.base_backoff.saturating_mul(2_u32.saturating_pow(failures.attempts - 1)).min(self.max_backoff);
+		failures.retry_at = now_ts + backoff.as_secs();
+		let quarantined = !failures.quarantined && failures.attempts >= self.max_attempts.max(1);
+		failures.quarantined |= quarantined;
+		quarantined
+	}
+
+	/// The order landed, its budget starts over
+	pub fn record_success(&mut self, order: &str) {
+		self.failures.remove(order);
+	}
+
+	pub fn quarantined(&self) -> Vec<(&str, &OrderFailures)> {
+		let mut quarantined: Vec<(&str, &OrderFailures)> = self.failures.iter().filter(|(_, failures)| failures.quarantined).map(|(order, failures)| (order.as_str(), failures)).collect();
+		quarantined.sort_by_key(|(order, _)| *order);
+		quarantined
+	}
+
+	/// Forgets every failure, quarantined or not
+	pub fn release_all(&mut self) {
+		self.failures.clear();
+	}
+
+	/// Releases every order when the operator asked for it, returns whether it did
+	pub fn release_if_requested(&mut self) -> bool {
+		let requested = self.release.as_ref().map(|release| release.take()).unwrap_or(false);
+		if requested {
+			self.release_all();
+		}
+		requested
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+	use crate::quarantine::{QuarantineRelease, RetryBudget};
+
+	#[test]
+	fn backs_off_then_quarantines() {
+		let mut budget = RetryBudget::new(3, Duration::from_secs(10)).with_max_backoff(Duration::from_secs(15));
+		let order = "ScaleIn Ask depth 4";
+		assert!(!budget.record_failure(order, "custom program error: 0x1", 1_000));
+		assert!(!budget.ready(order, 1_009));
+		assert!(budget.ready(order, 1_010));
+		assert!(!budget.record_failure(order, "custom program error: 0x1", 1_010));
+		// doubled to 20s, capped at 15s
+		assert_eq!(budget.failures(order).unwrap().retry_at, 1_025);
+		assert!(budget.ready("TakeProfit Bid depth 1", 1_010));
+		assert!(budget.record_failure(order, "custom program error: 0x1", 1_025));
+		assert!(!budget.ready(order, 10_000));
+		assert_eq!(budget.quarantined().len(), 1);
+		budget.release_all();
+		assert!(budget.ready(order, 1_025));
+	}
+
+	#[test]
+	fn releases_when_the_operator_asks() {
+		let release = QuarantineRelease::new("quarantine-release-test", std::env::temp_dir());
+		let mut budget = RetryBudget::new(1, Duration::from_secs(10)).with_release(release.clone());
+		let order = "ScaleIn Ask depth 4";
+		assert!(budget.record_failure(order, "custom program error: 0x1", 1_000));
+		assert!(!budget.release_if_requested());
+		release.request();
+		assert!(budget.release_if_requested());
+		assert!(budget.ready(order, 1_000));
+		assert!(budget.record_failure(order, "custom program error: 0x1", 1_000));
+		std::fs::write(release.file_path(), "").unwrap();
+		assert!(budget.release_if_requested());
+		assert!(budget.ready(order, 1_000));
+		assert!(!release.file_path().exists());
+	}
+}
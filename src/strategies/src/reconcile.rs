@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+/// A strategy's position as its ledger or the chain has it, base lots and UI quote
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct LedgerPosition {
+	/// Negative when short
+	pub base_lots: i64,
+	/// Quote paid for the position, negative when long
+	pub quote: f64,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Divergence {
+	pub ledger: LedgerPosition,
+	pub chain: LedgerPosition,
+}
+
+/// Compares the strategy's ledger with the on chain perp account every `interval`. Fills the
+/// ledger missed or double counted show up here instead of at the next reset
+#[derive(Clone, Debug)]
+pub struct ReconciliationAlarm {
+	pub interval: Duration,
+	/// Base lots the two may differ by
+	pub base_tolerance: i64,
+	/// Fraction of the ledger's quote the chain may differ by, None compares base only.
+	/// Fees, funding and settled pnl all move the chain's quote away from the ledger's
+	pub quote_tolerance: Option<f64>,
+	/// Cancels orders and pauses the strategy while they disagree, alerts only otherwise
+	pub halt: bool,
+	/// Last check, unix seconds
+	last_check: Option<u64>,
+	pub divergence: Option<Divergence>,
+}
+
+impl ReconciliationAlarm {
+	pub fn new(interval: Duration, base_tolerance: i64) -> Self {
+		Self { interval, base_tolerance, quote_tolerance: None, halt: false, last_check: None, divergence: None }
+	}
+
+	pub fn with_quote_tolerance(mut self, quote_tolerance: f64) -> Self {
+		self.quote_tolerance = Some(quote_tolerance);
+		self
+	}
+
+	pub fn with_halt(mut self, halt: bool) -> Self {
+		self.halt = halt;
+		self
+	}
+
+	pub fn due(&self, now_ts: u64) -> bool {
+		self.last_check.map(|last_check| now_ts >= last_check + self.interval.as_secs()).unwrap_or(true)
+	}
+
+	/// The divergence past tolerance, None when the two agree. `in_flight` signed base lots of a
+	/// resting order may have filled on chain without reaching the ledger yet, quote is only
+	/// compared without one
+	pub fn compare(&self, ledger: LedgerPosition, in_flight: i64, chain: LedgerPosition) -> Option<Divergence> {
+		let (low, high) = if in_flight < 0 { (ledger.base_lots + in_flight, ledger.base_lots) } else { (ledger.base_lots, ledger.base_lots + in_flight) };
+		let base_diverged = chain.base_lots < low - self.base_tolerance || chain.base_lots > high + self.base_tolerance;
+		let quote_diverged = self.quote_tolerance
+			  .filter(|_| in_flight == 0)
+			  .map(|tolerance| (ledger.quote - chain.quote).abs() > ledger.quote.abs() * tolerance)
+			  .unwrap_or(false);
+		(base_diverged || quote_diverged).then(|| Divergence { ledger, chain })
+	}
+
+	/// Records the comparison made at `now_ts`, returns (previous, current) divergence
+	pub fn check(&mut self, ledger: LedgerPosition, in_flight: i64, chain: LedgerPosition, now_ts: u64) -> (Option<Divergence>, Option<Divergence>) {
+		self.last_check = Some(now_ts);
+		let previous = self.divergence;
+		self.divergence = self.compare(ledger, in_flight, chain);
+		(previous, self.divergence)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+	use crate::reconcile::{LedgerPosition, ReconciliationAlarm};
+
+	#[test]
+	fn flags_positions_past_tolerance() {
+		let mut alarm = ReconciliationAlarm::new(Duration::from_secs(300), 1).with_quote_tolerance(0.02);
+		let ledger = LedgerPosition { base_lots: -120, quote: 4_000.0 };
+		assert!(alarm.due(1_000));
+		assert_eq!(alarm.check(ledger, 0, LedgerPosition { base_lots: -121, quote: 3_950.0 }, 1_000), (None, None));
+		assert!(!alarm.due(1_299));
+		// a fill the ledger never recorded
+		let chain = LedgerPosition { base_lots: -180, quote: 6_000.0 };
+		let (previous, current) = alarm.check(ledger, 0, chain, 1_300);
+		assert_eq!((previous, current.map(|divergence| divergence.chain)), (None, Some(chain)));
+		// unless the resting sell order filled that much
+		assert!(alarm.compare(ledger, -60, chain).is_none());
+		assert!(alarm.compare(ledger, 60, chain).is_some());
+		// quote alone past 2%
+		assert!(alarm.compare(ledger, 0, LedgerPosition { base_lots: -120, quote: 3_900.0 }).is_some());
+		assert!(ReconciliationAlarm::new(Duration::from_secs(300), 1).compare(ledger, 0, LedgerPosition { base_lots: -120, quote: 3_900.0 }).is_none());
+	}
+}
@@ -0,0 +1,452 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use mangol_common::errors::{MangolError, MangolResult};
+use mangol_mango::mock::MockMangoClient;
+use mangol_mango::types::{PerpMarketData, Side};
+use mangol_solana::expenses::TxExpense;
+use serde::{Deserialize, Serialize};
+
+use crate::accounting::{realized_pnl, CostBasis};
+use crate::explain::DecisionExplanation;
+use crate::fib_state::FibState;
+use crate::fib_trader::{FibStrat, FibStratPosition, PriceSide};
+use crate::stats::{performance, PerformanceStats};
+
+/// One decision round as seen by the strategy: the inputs after syncing and the state it decided on
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RecordedStep {
+	#[serde(default)]
+	pub timestamp: u64,
+	pub oracle_price: f64,
+	pub base_position: i64,
+	pub decision: FibState,
+	#[serde(default)]
+	pub explanation: Option<DecisionExplanation>,
+}
+
+/// Everything needed to rebuild a strategy from the start of a position and drive it through its decisions
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RecordedSession {
+	pub market: PerpMarketData,
+	pub sentiment: PriceSide,
+	pub base_lot_size: i64,
+	pub quote_lot_size: i64,
+	pub initial_position: FibStratPosition,
+	pub initial_oracle_price: f64,
+	pub initial_base_position: i64,
+	#[serde(default)]
+	pub started_at: u64,
+	pub steps: Vec<RecordedStep>,
+}
+
+impl RecordedSession {
+	pub fn load(path: &str) -> MangolResult<Self> {
+		serde_json::from_str(&std::fs::read_to_string(path)?).map_err(|e| MangolError::SerializationError(e.to_string()))
+	}
+}
+
+/// Account equity in native quote at a unix timestamp, with the strategy market's position
+/// and funding indexes so funding paid between snapshots can be separated from trading pnl
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, PartialEq)]
+pub struct EquitySnapshot {
+	pub timestamp: u64,
+	pub equity: f64,
+	#[serde(default)]
+	pub base_position: i64,
+	#[serde(default)]
+	pub long_funding: f64,
+	#[serde(default)]
+	pub short_funding: f64,
+	/// Native quote the account's balances earned in interest net of what its borrows paid since recording started
+	#[serde(default)]
+	pub interest_earned: f64,
+}
+
+/// Lamports one transaction of a strategy cost, valued in native quote when it was sent
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ExpenseRecord {
+	pub strategy: String,
+	pub timestamp: u64,
+	pub signature: String,
+	pub expense: TxExpense,
+	pub quote_value: f64,
+}
+
+/// A filled order with the prices it can be judged against, all in UI price
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ExecutionRecord {
+	pub strategy: String,
+	pub timestamp: u64,
+	pub signature: String,
+	pub side: Side,
+	/// Price the strategy decided on before any adjustment to the book
+	pub decision_price: f64,
+	/// Oracle when the order was sent
+	pub oracle_price: f64,
+	/// Best price a taker on `side` would have got when the order was sent, None on an empty side
+	pub best_quote: Option<f64>,
+	pub fill_price: f64,
+	pub base_filled: i64,
+}
+
+/// How much worse `fill_price` is than `reference` for an order on `side`, in basis points.
+/// Negative when the fill was better
+pub fn slippage_bps(side: Side, reference: f64, fill_price: f64) -> f64 {
+	let adverse = match side {
+		Side::Bid => fill_price - reference,
+		Side::Ask => reference - fill_price,
+	};
+	adverse / reference * 10_000.0
+}
+
+impl ExecutionRecord {
+	pub fn vs_decision_bps(&self) -> f64 {
+		slippage_bps(self.side, self.decision_price, self.fill_price)
+	}
+
+	pub fn vs_oracle_bps(&self) -> f64 {
+		slippage_bps(self.side, self.oracle_price, self.fill_price)
+	}
+
+	pub fn vs_best_quote_bps(&self) -> Option<f64> {
+		self.best_quote.map(|best_quote| slippage_bps(self.side, best_quote, self.fill_price))
+	}
+}
+
+/// Writes one json file per position lifecycle into `dir`, rewritten after every step,
+/// and appends equity snapshots to `dir/equity.jsonl`, transaction expenses to
+/// `dir/expenses.jsonl` and fills to `dir/executions.jsonl` across lifecycles
+pub struct SessionRecorder {
+	pub dir: PathBuf,
+	pub equity_interval_secs: u64,
+	pub report_interval_secs: u64,
+	/// How reports match closing fills against open lots
+	pub cost_basis: CostBasis,
+	session: Option<(PathBuf, RecordedSession)>,
+	last_equity_at: Option<u64>,
+	last_report_at: Option<u64>,
+}
+
+impl SessionRecorder {
+	pub fn new(dir: &str) -> MangolResult<Self> {
+		std::fs::create_dir_all(dir)?;
+		Ok(Self {
+			dir: PathBuf::from(dir),
+			equity_interval_secs: 60,
+			report_interval_secs: 86_400,
+			cost_basis: CostBasis::default(),
+			session: None,
+			last_equity_at: None,
+			last_report_at: None,
+		})
+	}
+
+	pub fn with_equity_interval(mut self, equity_interval_secs: u64) -> Self {
+		self.equity_interval_secs = equity_interval_secs;
+		self
+	}
+
+	pub fn with_cost_basis(mut self, cost_basis: CostBasis) -> Self {
+		self.cost_basis = cost_basis;
+		self
+	}
+
+	fn equity_path(&self) -> PathBuf {
+		self.dir.join("equity.jsonl")
+	}
+
+	/// Whether a snapshot taken at `now_ts` would be kept, check it before paying for the equity
+	pub fn equity_due(&self, now_ts: u64) -> bool {
+		self.last_equity_at.map_or(true, |last_equity_at| now_ts >= last_equity_at + self.equity_interval_secs)
+	}
+
+	/// Appends a snapshot unless the last one is younger than the equity interval, returns whether it did
+	pub fn record_equity(&mut self, snapshot: EquitySnapshot) -> MangolResult<bool> {
+		if !self.equity_due(snapshot.timestamp) {
+			return Ok(false);
+		}
+		let line = serde_json::to_string(&snapshot).map_err(|e| MangolError::SerializationError(e.to_string()))?;
+		let mut file = OpenOptions::new().create(true).append(true).open(self.equity_path())?;
+		writeln!(file, "{}", line)?;
+		self.last_equity_at = Some(snapshot.timestamp);
+		Ok(true)
+	}
+
+	fn expenses_path(&self) -> PathBuf {
+		self.dir.join("expenses.jsonl")
+	}
+	
+	pub fn record_expense(&mut self, record: &ExpenseRecord) -> MangolResult<()> {
+		let line = serde_json::to_string(record).map_err(|e| MangolError::SerializationError(e.to_string()))?;
+		let mut file = OpenOptions::new().create(true).append(true).open(self.expenses_path())?;
+		writeln!(file, "{}", line)?;
+		Ok(())
+	}
+	
+	/// Recorded expenses with a timestamp in `range`
+	pub fn expenses(&self, range: Range<u64>) -> MangolResult<Vec<ExpenseRecord>> {
+		let data = match std::fs::read_to_string(self.expenses_path()) {
+			Ok(data) => data,
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+			Err(e) => return Err(e.into())
+		};
+		let mut expenses = vec![];
+		for line in data.lines().filter(|line| !line.is_empty()) {
+			let record: ExpenseRecord = serde_json::from_str(line).map_err(|e| MangolError::SerializationError(e.to_string()))?;
+			if range.contains(&record.timestamp) {
+				expenses.push(record);
+			}
+		}
+		Ok(expenses)
+	}
+	
+	fn executions_path(&self) -> PathBuf {
+		self.dir.join("executions.jsonl")
+	}
+	
+	pub fn record_execution(&mut self, record: &ExecutionRecord) -> MangolResult<()> {
+		let line = serde_json::to_string(record).map_err(|e| MangolError::SerializationError(e.to_string()))?;
+		let mut file = OpenOptions::new().create(true).append(true).open(self.executions_path())?;
+		writeln!(file, "{}", line)?;
+		Ok(())
+	}
+	
+	/// Recorded fills with a timestamp in `range`
+	pub fn executions(&self, range: Range<u64>) -> MangolResult<Vec<ExecutionRecord>> {
+		let data = match std::fs::read_to_string(self.executions_path()) {
+			Ok(data) => data,
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+			Err(e) => return Err(e.into())
+		};
+		let mut executions = vec![];
+		for line in data.lines().filter(|line| !line.is_empty()) {
+			let record: ExecutionRecord = serde_json::from_str(line).map_err(|e| MangolError::SerializationError(e.to_string()))?;
+			if range.contains(&record.timestamp) {
+				executions.push(record);
+			}
+		}
+		Ok(executions)
+	}
+	
+	/// Every session file in `dir`, oldest first
+	pub fn sessions(&self) -> MangolResult<Vec<RecordedSession>> {
+		let mut sessions = vec![];
+		for entry in std::fs::read_dir(&self.dir)? {
+			let path = entry?.path();
+			let is_session = path.file_name().and_then(|name| name.to_str()).map(|name| name.starts_with("session-") && name.ends_with(".json")).unwrap_or(false);
+			if is_session {
+				sessions.push(RecordedSession::load(path.to_str().unwrap())?);
+			}
+		}
+		sessions.sort_by_key(|session| session.started_at);
+		Ok(sessions)
+	}
+
+	/// Stats over the last report interval once it has passed since the previous report
+	pub fn take_report(&mut self, now_ts: u64, annual_risk_free_rate: f64) -> MangolResult<Option<PerformanceStats>> {
+		let last_report_at = *self.last_report_at.get_or_insert(now_ts);
+		if now_ts < last_report_at + self.report_interval_secs {
+			return Ok(None);
+		}
+		self.last_report_at = Some(now_ts);
+		let curve = self.equity_curve(last_report_at..now_ts + 1)?;
+		let all_sessions = self.sessions()?;
+		let sessions: Vec<RecordedSession> = all_sessions.iter().filter(|session| session.started_at >= last_report_at).cloned().collect();
+		let expenses = self.expenses(last_report_at..now_ts + 1)?;
+		// fills before the report still opened the lots the ones in it close
+		let all_executions = self.executions(0..now_ts + 1)?;
+		let executions: Vec<ExecutionRecord> = all_executions.iter().filter(|record| record.timestamp >= last_report_at).cloned().collect();
+		let mut stats = performance(&curve, &sessions, &expenses, &executions, annual_risk_free_rate);
+		if let Some(session) = all_sessions.last() {
+			stats.realized_pnl = Some((self.cost_basis, realized_pnl(&all_executions, self.cost_basis, session.base_lot_size, last_report_at..now_ts + 1)));
+		}
+		Ok(Some(stats))
+	}
+
+	/// Recorded snapshots with a timestamp in `range`, shared by the circuit breaker, digests and backtest stats
+	pub fn equity_curve(&self, range: Range<u64>) -> MangolResult<Vec<EquitySnapshot>> {
+		let data = match std::fs::read_to_string(self.equity_path()) {
+			Ok(data) => data,
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+			Err(e) => return Err(e.into())
+		};
+		let mut curve = vec![];
+		for line in data.lines().filter(|line| !line.is_empty()) {
+			let snapshot: EquitySnapshot = serde_json::from_str(line).map_err(|e| MangolError::SerializationError(e.to_string()))?;
+			if range.contains(&snapshot.timestamp) {
+				curve.push(snapshot);
+			}
+		}
+		Ok(curve)
+	}
+
+	pub fn begin(&mut self, market: &PerpMarketData, sentiment: PriceSide, position: &FibStratPosition, base_lot_size: i64, quote_lot_size: i64, oracle_price: f64, base_position: i64) -> MangolResult<()> {
+		let started_at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
+		let path = self.dir.join(format!("session-{}-{}.json", market.name, started_at));
+		self.session = Some((path, RecordedSession {
+			market: market.clone(),
+			sentiment,
+			base_lot_size,
+			quote_lot_size,
+			initial_position: position.clone(),
+			initial_oracle_price: oracle_price,
+			initial_base_position: base_position,
+			started_at: (started_at / 1000) as u64,
+			steps: vec![],
+		}));
+		self.flush()
+	}
+
+	pub fn record(&mut self, timestamp: u64, oracle_price: f64, base_position: i64, decision: &FibState, explanation: Option<&DecisionExplanation>) -> MangolResult<()> {
+		if let Some((_, session)) = &mut self.session {
+			session.steps.push(RecordedStep { timestamp, oracle_price, base_position, decision: decision.clone(), explanation: explanation.cloned() });
+		}
+		self.flush()
+	}
+
+	fn flush(&self) -> MangolResult<()> {
+		if let Some((path, session)) = &self.session {
+			let data = serde_json::to_string_pretty(session).map_err(|e| MangolError::SerializationError(e.to_string()))?;
+			std::fs::write(path, data)?;
+		}
+		Ok(())
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct ReplayMismatch {
+	pub step: usize,
+	pub expected: FibState,
+	pub actual: FibState,
+}
+
+#[derive(Clone, Debug)]
+pub struct ReplayReport {
+	pub steps: usize,
+	pub mismatches: Vec<ReplayMismatch>,
+}
+
+impl ReplayReport {
+	pub fn is_deterministic(&self) -> bool {
+		self.mismatches.is_empty()
+	}
+}
+
+/// tx hashes come from the chain (or the mock) and are not part of the decision
+fn without_tx_hash(state: &FibState) -> FibState {
+	let mut state = state.clone();
+	match &mut state {
+		FibState::Selling(order) | FibState::Buying(order) => order.tx_hash = None,
+		FibState::Neutral => {}
+	}
+	state
+}
+
+/// Feeds a recorded session back through FibStrat on a mock client without sleeping
+/// and compares every decision against the recorded one
+pub fn replay_session(session: &RecordedSession) -> MangolResult<ReplayReport> {
+	let mut mango_client = MockMangoClient::new(session.market.market_index, session.base_lot_size, session.quote_lot_size);
+	mango_client.set_price(session.initial_oracle_price);
+	mango_client.set_base_position(session.initial_base_position);
+	let mut strat = FibStrat::new(session.initial_position.max_position_depth, 0, mango_client, session.sentiment, session.market.clone())?;
+	strat.position = session.initial_position.clone();
+
+	let mut base_position = session.initial_base_position;
+	let mut mismatches = vec![];
+	for (i, step) in session.steps.iter().enumerate() {
+		strat.mango_client.push_price(step.oracle_price);
+		strat.mango_client.push_fill(step.base_position - base_position);
+		base_position = step.base_position;
+		match strat.sentiment {
+			PriceSide::Sell => {
+				strat.sync_bearish()?;
+				strat.decide_bearish()?;
+			}
+			PriceSide::Buy => {
+				strat.sync_bullish()?;
+				strat.decide_bullish()?;
+			}
+		}
+		let expected = without_tx_hash(&step.decision);
+		let actual = without_tx_hash(&strat.position.current_state);
+		if expected != actual {
+			mismatches.push(ReplayMismatch { step: i, expected, actual });
+		}
+	}
+	Ok(ReplayReport { steps: session.steps.len(), mismatches })
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::fib_state::{FibState, FibStratOrder, FibStratOrderState};
+	use crate::fib_trader::{FibStratPosition, PriceSide};
+	use crate::replay::{replay_session, EquitySnapshot, RecordedSession, RecordedStep, SessionRecorder};
+	use mangol_mango::mock::sol_perp;
+	
+	fn sold(depth: u16, state: FibStratOrderState, price: f64, base_size: u64) -> FibState {
+		FibState::Selling(FibStratOrder { depth, state, price, base_size, tx_hash: None, legs: 1 })
+	}
+	
+	/// A short scaled in once, waiting on `current_state` when the recorded step moved the position to `base_position`
+	fn session(sentiment: PriceSide, current_state: FibState, base_position: i64, decision: FibState) -> RecordedSession {
+		RecordedSession {
+			market: sol_perp(),
+			sentiment,
+			base_lot_size: 10_000_000,
+			quote_lot_size: 100,
+			initial_position: FibStratPosition {
+				state_history: vec![sold(1, FibStratOrderState::Filled, 0.04, 121)],
+				current_state,
+				max_position_depth: 10,
+				furthest_position: 1,
+				starting_position_size: 1.618,
+			},
+			initial_oracle_price: 0.04,
+			initial_base_position: -121,
+			started_at: 0,
+			steps: vec![RecordedStep { timestamp: 0, oracle_price: 0.041, base_position, decision, explanation: None }],
+		}
+	}
+	
+	#[test]
+	fn replay_matches_recorded_decisions() {
+		// the last take profit filled, the position is closed and nothing is placed
+		let take_profit = FibState::Buying(FibStratOrder { depth: 1, state: FibStratOrderState::Waiting, price: 0.039, base_size: 0, tx_hash: None, legs: 1 });
+		let report = replay_session(&session(PriceSide::Sell, take_profit, 0, FibState::Neutral)).unwrap();
+		assert_eq!(report.steps, 1);
+		assert!(report.is_deterministic());
+		// bullish rounds leave the waiting order alone
+		let scale_in = sold(2, FibStratOrderState::Waiting, 0.0402, 0);
+		let report = replay_session(&session(PriceSide::Buy, scale_in.clone(), -121, scale_in.clone())).unwrap();
+		assert!(report.is_deterministic());
+		let report = replay_session(&session(PriceSide::Buy, scale_in.clone(), -121, FibState::Neutral)).unwrap();
+		assert_eq!(report.mismatches[0].actual, scale_in);
+	}
+	
+	#[test]
+	fn replay_reports_diverging_decisions() {
+		let report = replay_session(&session(PriceSide::Sell, sold(2, FibStratOrderState::Waiting, 0.0402, 0), -121, FibState::Neutral)).unwrap();
+		assert!(!report.is_deterministic());
+		assert_eq!(report.mismatches[0].expected, FibState::Neutral);
+		assert!(matches!(report.mismatches[0].actual, FibState::Selling(FibStratOrder { depth: 2, state: FibStratOrderState::Waiting, legs: 1, .. })));
+	}
+
+	#[test]
+	fn equity_curve_respects_interval_and_range() {
+		let dir = std::env::temp_dir().join("mangol-equity-curve-test");
+		let _ = std::fs::remove_dir_all(&dir);
+		let mut recorder = SessionRecorder::new(dir.to_str().unwrap()).unwrap().with_equity_interval(60);
+		let snapshot = |timestamp, equity| EquitySnapshot { timestamp, equity, ..Default::default() };
+		assert!(recorder.record_equity(snapshot(1_000, 10.0)).unwrap());
+		assert!(!recorder.equity_due(1_030));
+		assert!(!recorder.record_equity(snapshot(1_030, 11.0)).unwrap());
+		assert!(recorder.record_equity(snapshot(1_060, 12.0)).unwrap());
+		assert!(recorder.record_equity(snapshot(1_200, 9.0)).unwrap());
+		assert_eq!(recorder.equity_curve(1_000..1_200).unwrap(), vec![snapshot(1_000, 10.0), snapshot(1_060, 12.0)]);
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+}
@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+
+use mangol_common::errors::{MangolError, MangolResult};
+use mangol_mango::client::MangoClientApi;
+use mangol_mango::oracle::OracleConfidence;
+use mangol_mango::types::{MangoAccount, MangoCache, MangoGroup, Side, MAX_PAIRS, MAX_TOKENS};
+
+#[derive(Copy, Clone, Debug)]
+pub struct RiskLimits {
+	/// (perp notional + spot borrows) / equity allowed after an order fills
+	pub max_leverage: f64,
+}
+
+/// Exposure of an account in native quote: |perp positions| per market plus borrowed spot value
+pub fn account_exposure(mango_account: &MangoAccount, mango_group: &MangoGroup, mango_cache: &MangoCache) -> (Vec<f64>, f64) {
+	let perp_notionals = (0..MAX_PAIRS).map(|i| {
+		let base_native = mango_account.perp_accounts[i].base_position * mango_group.perp_markets[i].base_lot_size;
+		base_native as f64 * mango_cache.get_price(i)
+	}).collect();
+	let borrows = (0..MAX_TOKENS).filter(|i| !mango_account.borrows[*i].is_zero()).map(|i| {
+		let native_borrow = mango_account.get_native_borrow(&mango_cache.root_bank_cache[i], i).unwrap();
+		native_borrow.to_num::<f64>() * mango_cache.get_price(i)
+	}).sum();
+	(perp_notionals, borrows)
+}
+
+/// Leverage with `order_notional` native quote added to `market_index` on `side`
+pub fn leverage_after_order(perp_notionals: &[f64], borrows: f64, equity: f64, market_index: usize, side: Side, order_notional: f64) -> f64 {
+	if equity <= 0.0 {
+		return f64::INFINITY;
+	}
+	let signed_order = match side {
+		Side::Bid => order_notional,
+		Side::Ask => -order_notional,
+	};
+	let notional: f64 = perp_notionals.iter().enumerate().map(|(i, notional)| {
+		if i == market_index { (notional + signed_order).abs() } else { notional.abs() }
+	}).sum();
+	(notional + borrows) / equity
+}
+
+/// Markets whose prices move together, capped as one position. A market index covers both the
+/// perp market and the spot token at it, so SOL-PERP against SOL deposits nets out while SOL-PERP
+/// with MSOL deposits adds up
+#[derive(Clone, Debug, PartialEq)]
+pub struct CorrelationGroup {
+	pub name: String,
+	pub markets: Vec<usize>,
+	/// |net perp and spot notional| of the group allowed after an order fills, native quote
+	pub max_notional: f64,
+}
+
+/// Groups from `name=index,index:max ui notional` separated by `;`, e.g. `SOL=3,12:250000;BTC=1:100000`
+pub fn parse_correlation_groups(spec: &str, quote_decimals: u8) -> MangolResult<Vec<CorrelationGroup>> {
+	spec.split(';').filter(|group| !group.trim().is_empty()).map(|group| {
+		let invalid = || MangolError::MangoError(format!("Invalid correlation group {}", group));
+		let (name, rest) = group.trim().split_once('=').ok_or_else(invalid)?;
+		let (markets, max_notional) = rest.split_once(':').ok_or_else(invalid)?;
+		let markets = markets.split(',').map(|index| index.trim().parse::<usize>().ok().filter(|index| *index < MAX_PAIRS))
+			  .collect::<Option<Vec<usize>>>().ok_or_else(invalid)?;
+		let max_notional = max_notional.trim().parse::<f64>().map_err(|_| invalid())?;
+		Ok(CorrelationGroup { name: name.to_string(), markets, max_notional: max_notional * 10f64.powi(quote_decimals as i32) })
+	}).collect()
+}
+
+/// Net value of every spot token in native quote, deposits minus borrows, quote left out
+pub fn spot_positions(mango_account: &MangoAccount, mango_cache: &MangoCache) -> Vec<f64> {
+	(0..MAX_PAIRS).map(|i| {
+		let root_bank_cache = &mango_cache.root_bank_cache[i];
+		let deposit = mango_account.get_native_deposit(root_bank_cache, i).unwrap();
+		let borrow = mango_account.get_native_borrow(root_bank_cache, i).unwrap();
+		(deposit - borrow).to_num::<f64>() * mango_cache.get_price(i)
+	}).collect()
+}
+
+/// Net notional of `markets` with `order_notional` native quote added to the perp `market_index` on `side`.
+/// Longs and shorts across the group offset each other
+pub fn group_notional_after_order(perp_notionals: &[f64], spot_positions: &[f64], markets: &[usize], market_index: usize, side: Side, order_notional: f64) -> f64 {
+	let signed_order = match side {
+		Side::Bid => order_notional,
+		Side::Ask => -order_notional,
+	};
+	let net: f64 = markets.iter().map(|i| perp_notionals[*i] + spot_positions[*i]).sum();
+	if markets.contains(&market_index) { (net + signed_order).abs() } else { net.abs() }
+}
+
+/// Per strategy limits checked before placing orders, on top of what health allows
+#[derive(Clone, Debug, Default)]
+pub struct RiskManager {
+	pub limits: HashMap<String, RiskLimits>,
+	/// Pauses new orders on markets whose oracle is too uncertain for the program to cache a price
+	pub oracle_confidence: Option<OracleConfidence>,
+	/// Account wide caps, whichever strategy sends the order
+	pub correlation_groups: Vec<CorrelationGroup>,
+}
+
+impl RiskManager {
+	pub fn with_limits(mut self, strategy: &str, limits: RiskLimits) -> Self {
+		self.limits.insert(strategy.to_string(), limits);
+		self
+	}
+
+	pub fn with_oracle_confidence(mut self, oracle_confidence: OracleConfidence) -> Self {
+		self.oracle_confidence = Some(oracle_confidence);
+		self
+	}
+
+	pub fn with_correlation_group(mut self, correlation_group: CorrelationGroup) -> Self {
+		self.correlation_groups.push(correlation_group);
+		self
+	}
+
+	/// Errors when the order grows the net notional of a correlation group of `market_index` past its cap.
+	/// Orders that shrink it always pass
+	pub fn check_correlation_groups<C: MangoClientApi>(&self, strategy: &str, mango_client: &C, market_index: usize, side: Side, order_notional: f64) -> MangolResult<()> {
+		let groups: Vec<&CorrelationGroup> = self.correlation_groups.iter().filter(|group| group.markets.contains(&market_index)).collect();
+		if groups.is_empty() {
+			return Ok(());
+		}
+		let (perp_notionals, _) = account_exposure(mango_client.mango_account(), mango_client.mango_group(), mango_client.mango_cache());
+		let spot_positions = spot_positions(mango_client.mango_account(), mango_client.mango_cache());
+		for group in groups {
+			let before = group_notional_after_order(&perp_notionals, &spot_positions, &group.markets, market_index, side, 0.0);
+			let after = group_notional_after_order(&perp_notionals, &spot_positions, &group.markets, market_index, side, order_notional);
+			if after > group.max_notional && after > before {
+				return Err(MangolError::MangoError(format!("{} order would take {} exposure to {:.0}, max {:.0}", strategy, group.name, after, group.max_notional)));
+			}
+		}
+		Ok(())
+	}
+
+	pub fn leverage<C: MangoClientApi>(&self, mango_client: &C) -> MangolResult<f64> {
+		let (perp_notionals, borrows) = account_exposure(mango_client.mango_account(), mango_client.mango_group(), mango_client.mango_cache());
+		Ok(leverage_after_order(&perp_notionals, borrows, mango_client.get_equity()?.to_num::<f64>(), 0, Side::Bid, 0.0))
+	}
+
+	/// Errors when the market's oracle confidence is over PYTH_CONF_FILTER, the order would grow a
+	/// correlation group past its cap or take the account past the strategy's max leverage
+	pub fn check_order<C: MangoClientApi>(&self, strategy: &str, mango_client: &C, market_index: usize, side: Side, order_notional: f64) -> MangolResult<()> {
+		if let Some(conf_ratio) = self.oracle_confidence.as_ref().and_then(|confidence| confidence.breach(market_index)) {
+			return Err(MangolError::MangoError(format!("{} paused on market {}, oracle confidence at {:.2}% of price", strategy, market_index, conf_ratio * 100.0)));
+		}
+		self.check_correlation_groups(strategy, mango_client, market_index, side, order_notional)?;
+		let limits = match self.limits.get(strategy) {
+			Some(limits) => limits,
+			None => return Ok(())
+		};
+		let (perp_notionals, borrows) = account_exposure(mango_client.mango_account(), mango_client.mango_group(), mango_client.mango_cache());
+		let equity = mango_client.get_equity()?.to_num::<f64>();
+		let leverage = leverage_after_order(&perp_notionals, borrows, equity, market_index, side, order_notional);
+		if leverage > limits.max_leverage {
+			return Err(MangolError::MangoError(format!("{} order would take leverage to {:.2}x, max {:.2}x", strategy, leverage, limits.max_leverage)));
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use mangol_mango::types::Side;
+	use crate::risk::{group_notional_after_order, leverage_after_order, parse_correlation_groups, CorrelationGroup};
+
+	#[test]
+	fn counts_borrows_and_reducing_orders() {
+		let notionals = [-2_000.0, 500.0];
+		assert_eq!(leverage_after_order(&notionals, 500.0, 1_000.0, 0, Side::Ask, 1_000.0), 4.0);
+		assert_eq!(leverage_after_order(&notionals, 500.0, 1_000.0, 0, Side::Bid, 1_000.0), 2.0);
+		assert_eq!(leverage_after_order(&notionals, 0.0, 0.0, 0, Side::Bid, 0.0), f64::INFINITY);
+	}
+
+	#[test]
+	fn nets_correlated_markets_into_one_bucket() {
+		let groups = parse_correlation_groups("SOL=3,12:2000; BTC=1:500", 6).unwrap();
+		assert_eq!(groups[0], CorrelationGroup { name: "SOL".to_string(), markets: vec![3, 12], max_notional: 2_000_000_000.0 });
+		assert!(parse_correlation_groups("SOL=3,99:2000", 6).is_err());
+
+		let mut perp_notionals = [0.0; 15];
+		let mut spot_positions = [0.0; 15];
+		perp_notionals[3] = -1_500.0;
+		spot_positions[12] = 1_000.0;
+		// a short SOL-PERP against MSOL deposits is mostly hedged
+		assert_eq!(group_notional_after_order(&perp_notionals, &spot_positions, &[3, 12], 3, Side::Ask, 0.0), 500.0);
+		assert_eq!(group_notional_after_order(&perp_notionals, &spot_positions, &[3, 12], 3, Side::Bid, 1_000.0), 500.0);
+		assert_eq!(group_notional_after_order(&perp_notionals, &spot_positions, &[3, 12], 1, Side::Bid, 1_000.0), 500.0);
+	}
+}
@@ -0,0 +1,164 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+/// One backtested position from open to close, native quote
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, PartialEq)]
+pub struct TradeOutcome {
+	pub pnl: f64,
+	/// Lowest unrealized pnl while the position was open, zero or negative
+	pub worst_excursion: f64,
+	/// Largest notional the position reached
+	pub peak_notional: f64,
+}
+
+/// Account and limits the trade sequences are simulated against, native quote
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RuinConfig {
+	pub account_size: f64,
+	/// Drop from peak equity, as a ratio of the peak, at which the drawdown circuit breaker stops trading
+	pub max_drawdown: f64,
+	/// Maintenance margin as a ratio of notional, the account is liquidated once equity falls under it
+	pub maint_margin_ratio: f64,
+	pub trades_per_path: usize,
+	pub paths: usize,
+	pub seed: u64,
+}
+
+impl RuinConfig {
+	pub fn new(account_size: f64) -> Self {
+		Self {
+			account_size,
+			max_drawdown: 0.2,
+			maint_margin_ratio: 0.05,
+			trades_per_path: 250,
+			paths: 10_000,
+			seed: 0,
+		}
+	}
+
+	pub fn with_max_drawdown(mut self, max_drawdown: f64) -> Self {
+		self.max_drawdown = max_drawdown;
+		self
+	}
+
+	pub fn with_maint_margin_ratio(mut self, maint_margin_ratio: f64) -> Self {
+		self.maint_margin_ratio = maint_margin_ratio;
+		self
+	}
+
+	pub fn with_paths(mut self, paths: usize, trades_per_path: usize) -> Self {
+		self.paths = paths;
+		self.trades_per_path = trades_per_path;
+		self
+	}
+
+	pub fn with_seed(mut self, seed: u64) -> Self {
+		self.seed = seed;
+		self
+	}
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, PartialEq)]
+pub struct RuinEstimate {
+	pub paths: usize,
+	/// Share of paths that tripped the drawdown circuit breaker
+	pub drawdown_probability: f64,
+	/// Share of paths liquidated before the breaker could trip
+	pub liquidation_probability: f64,
+	/// Either of the two
+	pub ruin_probability: f64,
+	/// Equity at the end of the paths, ruined paths at the equity they stopped at, native quote
+	pub median_final_equity: f64,
+	pub p5_final_equity: f64,
+}
+
+enum PathEnd {
+	Survived(f64),
+	DrawdownBreaker(f64),
+	Liquidated(f64),
+}
+
+fn simulate_path(trades: &[TradeOutcome], config: &RuinConfig, rng: &mut StdRng) -> PathEnd {
+	let mut equity = config.account_size;
+	let mut peak = equity;
+	for _ in 0..config.trades_per_path {
+		let trade = &trades[rng.gen_range(0, trades.len())];
+		// the worst point of the trade decides whether the account survives it
+		let trough = equity + trade.worst_excursion.min(0.0);
+		if trough <= trade.peak_notional * config.maint_margin_ratio {
+			return PathEnd::Liquidated(trough.max(0.0));
+		}
+		if peak - trough >= peak * config.max_drawdown {
+			return PathEnd::DrawdownBreaker(trough);
+		}
+		equity += trade.pnl;
+		peak = peak.max(equity);
+	}
+	PathEnd::Survived(equity)
+}
+
+fn percentile(sorted: &[f64], ratio: f64) -> f64 {
+	sorted[((sorted.len() - 1) as f64 * ratio).round() as usize]
+}
+
+/// Resamples `trades` with replacement into `config.paths` sequences and counts how many hit the
+/// drawdown circuit breaker or liquidation. None without trades to draw from
+pub fn simulate(trades: &[TradeOutcome], config: &RuinConfig) -> Option<RuinEstimate> {
+	if trades.is_empty() || config.paths == 0 {
+		return None;
+	}
+	let mut rng = StdRng::seed_from_u64(config.seed);
+	let mut drawdowns = 0;
+	let mut liquidations = 0;
+	let mut final_equity = Vec::with_capacity(config.paths);
+	for _ in 0..config.paths {
+		match simulate_path(trades, config, &mut rng) {
+			PathEnd::Survived(equity) => final_equity.push(equity),
+			PathEnd::DrawdownBreaker(equity) => {
+				drawdowns += 1;
+				final_equity.push(equity);
+			}
+			PathEnd::Liquidated(equity) => {
+				liquidations += 1;
+				final_equity.push(equity);
+			}
+		}
+	}
+	final_equity.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+	let paths = config.paths as f64;
+	Some(RuinEstimate {
+		paths: config.paths,
+		drawdown_probability: drawdowns as f64 / paths,
+		liquidation_probability: liquidations as f64 / paths,
+		ruin_probability: (drawdowns + liquidations) as f64 / paths,
+		median_final_equity: percentile(&final_equity, 0.5),
+		p5_final_equity: percentile(&final_equity, 0.05),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::risk_of_ruin::{simulate, RuinConfig, TradeOutcome};
+
+	#[test]
+	fn estimates_ruin_from_trade_distribution() {
+		let winner = TradeOutcome { pnl: 10.0, worst_excursion: -5.0, peak_notional: 500.0 };
+		let loser = TradeOutcome { pnl: -150.0, worst_excursion: -150.0, peak_notional: 500.0 };
+		assert_eq!(simulate(&[], &RuinConfig::new(1_000.0)), None);
+
+		let safe = simulate(&[winner], &RuinConfig::new(1_000.0).with_paths(100, 50)).unwrap();
+		assert_eq!(safe.ruin_probability, 0.0);
+		assert_eq!(safe.median_final_equity, 1_500.0);
+
+		let config = RuinConfig::new(1_000.0).with_max_drawdown(0.25).with_paths(2_000, 50).with_seed(3);
+		let risky = simulate(&[winner, loser], &config).unwrap();
+		assert_eq!(risky.liquidation_probability, 0.0);
+		assert!(risky.drawdown_probability > 0.9);
+		assert_eq!(risky, simulate(&[winner, loser], &config).unwrap());
+
+		// too small an account for the notional is liquidated on the first dip
+		let liquidated = simulate(&[winner], &RuinConfig::new(30.0).with_paths(10, 5)).unwrap();
+		assert_eq!(liquidated.liquidation_probability, 1.0);
+	}
+}
@@ -0,0 +1,202 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, RwLock};
+
+use fixed::types::I80F48;
+use mangol_common::errors::{MangolError, MangolResult};
+use mangol_mango::health::{account_health_with, decode_mango_account};
+use mangol_mango::types::{HealthCache, HealthType, MangoAccount, MangoCache, MangoGroup, MAX_PAIRS};
+use mangol_solana::cache::AccountCache;
+use mangol_solana::connection::SolanaConnection;
+use mangol_solana::subscription::AccountUpdate;
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::watch_and_liquidate::{check_liquidatable, load_group_and_cache, Liqor};
+
+/// Whether `mango_account` is worth a full health check. Health without the spot open orders
+/// is a lower bound, open orders only hold assets, so accounts above zero can be skipped
+/// without fetching them
+pub fn is_candidate(health_cache: &mut HealthCache, mango_group: &MangoGroup, mango_cache: &MangoCache, mango_account: &MangoAccount) -> bool {
+	if mango_account.being_liquidated {
+		return true;
+	}
+	account_health_with(health_cache, mango_group, mango_cache, mango_account, &[], HealthType::Maint)
+		  .map(|health| health < I80F48::ZERO)
+		  .unwrap_or(true)
+}
+
+/// Whether a price change in any of `market_indexes` moves the account's health: it holds a spot
+/// balance or open orders there, or a perp position or orders
+pub fn exposed_to(mango_account: &MangoAccount, market_indexes: &[usize]) -> bool {
+	market_indexes.iter().filter(|i| **i < MAX_PAIRS).any(|i| {
+		let perp_account = &mango_account.perp_accounts[*i];
+		mango_account.in_margin_basket[*i]
+			  || mango_account.deposits[*i] != I80F48::ZERO
+			  || mango_account.borrows[*i] != I80F48::ZERO
+			  || perp_account.base_position != 0
+			  || perp_account.bids_quantity != 0
+			  || perp_account.asks_quantity != 0
+	})
+}
+
+/// Rescores in parallel the accounts exposed to the markets whose price changed, returning the ones to check.
+/// Each worker reuses one health cache for all the accounts it scores
+pub fn liquidation_candidates(accounts: &HashMap<Pubkey, MangoAccount>, mango_group: &MangoGroup, mango_cache: &MangoCache, market_indexes: &[usize]) -> Vec<Pubkey> {
+	accounts.par_iter()
+		  .filter(|(_, mango_account)| exposed_to(mango_account, market_indexes))
+		  .map_init(HealthCache::default, |health_cache, (pubkey, mango_account)| {
+			  is_candidate(health_cache, mango_group, mango_cache, mango_account).then(|| *pubkey)
+		  })
+		  .flatten()
+		  .collect()
+}
+
+/// Health checks for any number of accounts on a fixed number of threads.
+///
+/// Account and price updates queue a job per account that needs checking, an account already
+/// queued is not queued twice and the job reads its latest state when it runs. Jobs run on a
+/// rayon pool, idle workers steal queued jobs from busy ones
+#[derive(Clone)]
+pub struct LiquidationScanner {
+	connection: Arc<SolanaConnection>,
+	account_cache: Arc<AccountCache>,
+	/// Group the watched accounts belong to
+	mango_group_pk: Pubkey,
+	accounts: Arc<RwLock<HashMap<Pubkey, MangoAccount>>>,
+	queued: Arc<Mutex<HashSet<Pubkey>>>,
+	/// Kept per account so repeated updates and checks of it don't allocate a new one
+	health_caches: Arc<Mutex<HashMap<Pubkey, HealthCache>>>,
+	pool: Arc<ThreadPool>,
+	liqor: Option<Arc<Liqor>>,
+}
+
+impl LiquidationScanner {
+	pub fn new(connection: Arc<SolanaConnection>, account_cache: Arc<AccountCache>, mango_group_pk: Pubkey, workers: usize) -> MangolResult<Self> {
+		let pool = ThreadPoolBuilder::new()
+			  .num_threads(workers.max(1))
+			  .thread_name(|i| format!("liquidation-worker-{}", i))
+			  .build()
+			  .map_err(|e| MangolError::MangoError(format!("liquidation worker pool {}", e)))?;
+		Ok(Self {
+			connection,
+			account_cache,
+			mango_group_pk,
+			accounts: Arc::new(RwLock::new(HashMap::new())),
+			queued: Arc::new(Mutex::new(HashSet::new())),
+			health_caches: Arc::new(Mutex::new(HashMap::new())),
+			pool: Arc::new(pool),
+			liqor: None,
+		})
+	}
+
+	pub fn with_liqor(mut self, liqor: Liqor) -> Self {
+		self.liqor = Some(Arc::new(liqor));
+		self
+	}
+
+	pub fn is_watched(&self, pubkey: &Pubkey) -> bool {
+		self.accounts.read().unwrap().contains_key(pubkey)
+	}
+
+	/// Drops an account that is no longer watched, a check already queued for it finds nothing to check
+	pub fn forget(&self, pubkey: &Pubkey) {
+		self.accounts.write().unwrap().remove(pubkey);
+		self.health_caches.lock().unwrap().remove(pubkey);
+	}
+
+	pub fn len(&self) -> usize {
+		self.accounts.read().unwrap().len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// Jobs waiting for a worker
+	pub fn queued(&self) -> usize {
+		self.queued.lock().unwrap().len()
+	}
+
+	/// Runs `f` with the account's health cache, taken out of the map meanwhile so workers don't hold the lock
+	fn with_health_cache<T>(&self, pubkey: &Pubkey, f: impl FnOnce(&mut HealthCache) -> T) -> T {
+		let mut health_cache = self.health_caches.lock().unwrap().remove(pubkey).unwrap_or_default();
+		let result = f(&mut health_cache);
+		self.health_caches.lock().unwrap().insert(*pubkey, health_cache);
+		result
+	}
+
+	/// Stores the account and queues a check if it could be liquidatable. Updates that are not
+	/// mango accounts are ignored
+	pub fn on_account_update(&self, update: &AccountUpdate) {
+		let mango_account = match decode_mango_account(&update.account.data) {
+			Ok(mango_account) => mango_account,
+			Err(_) => return
+		};
+		self.accounts.write().unwrap().insert(update.pubkey, mango_account);
+		let candidate = match load_group_and_cache(&self.connection, &self.account_cache, &self.mango_group_pk) {
+			Ok((mango_group, mango_cache)) => self.with_health_cache(&update.pubkey, |health_cache| is_candidate(health_cache, &mango_group, &mango_cache, &mango_account)),
+			// let the worker surface the error
+			Err(_) => true
+		};
+		if candidate {
+			self.queue(update.pubkey);
+		}
+	}
+
+	/// Rescores the watched accounts exposed to `market_indexes` against the cached prices and queues the candidates
+	pub fn on_price_update(&self, market_indexes: &[usize]) -> MangolResult<usize> {
+		let (mango_group, mango_cache) = load_group_and_cache(&self.connection, &self.account_cache, &self.mango_group_pk)?;
+		let candidates = {
+			let accounts = self.accounts.read().unwrap();
+			self.pool.install(|| liquidation_candidates(&accounts, &mango_group, &mango_cache, market_indexes))
+		};
+		for pubkey in &candidates {
+			self.queue(*pubkey);
+		}
+		Ok(candidates.len())
+	}
+
+	fn queue(&self, pubkey: Pubkey) {
+		if !self.queued.lock().unwrap().insert(pubkey) {
+			return;
+		}
+		let scanner = self.clone();
+		self.pool.spawn(move || {
+			// dequeue first so an update arriving mid-check queues another one
+			scanner.queued.lock().unwrap().remove(&pubkey);
+			let mango_account = match scanner.accounts.read().unwrap().get(&pubkey) {
+				Some(mango_account) => *mango_account,
+				None => return
+			};
+			let checked = scanner.with_health_cache(&pubkey, |health_cache| check_liquidatable(&scanner.connection, &scanner.account_cache, &scanner.mango_group_pk, scanner.liqor.as_deref(), health_cache, &pubkey, &mango_account));
+			if let Err(e) = checked {
+				eprintln!("[-] Failed to check {} {:?}", pubkey, e);
+			}
+		});
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashMap;
+	use std::mem::size_of;
+	use mangol_mango::health::{decode_mango_account, decode_mango_cache, decode_mango_group};
+	use mangol_mango::types::{MangoAccount, MangoCache, MangoGroup};
+	use solana_sdk::pubkey::Pubkey;
+	use crate::scanner::liquidation_candidates;
+
+	#[test]
+	fn rescores_only_accounts_that_could_be_liquidated() {
+		let mango_group = decode_mango_group(&vec![0u8; size_of::<MangoGroup>()]).unwrap();
+		let mango_cache = decode_mango_cache(&vec![0u8; size_of::<MangoCache>()]).unwrap();
+		let healthy = decode_mango_account(&vec![0u8; size_of::<MangoAccount>()]).unwrap();
+		let mut being_liquidated = healthy;
+		being_liquidated.being_liquidated = true;
+		being_liquidated.perp_accounts[2].base_position = -10;
+		let flagged = Pubkey::new_unique();
+		let accounts = HashMap::from([(Pubkey::new_unique(), healthy), (flagged, being_liquidated)]);
+		assert_eq!(liquidation_candidates(&accounts, &mango_group, &mango_cache, &[2]), vec![flagged]);
+		assert!(liquidation_candidates(&accounts, &mango_group, &mango_cache, &[1]).is_empty());
+	}
+}
@@ -0,0 +1,67 @@
+use mangol_common::errors::{MangolError, MangolResult};
+use serde::{Deserialize, Serialize};
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// A daily UTC window given in minutes since midnight, wraps past midnight when `end_minute < start_minute`
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+pub struct DailyWindow {
+	pub start_minute: u16,
+	pub end_minute: u16,
+}
+
+impl DailyWindow {
+	pub fn contains(&self, now_ts: u64) -> bool {
+		let minute = ((now_ts % SECS_PER_DAY) / 60) as u16;
+		if self.start_minute <= self.end_minute {
+			minute >= self.start_minute && minute < self.end_minute
+		} else {
+			minute >= self.start_minute || minute < self.end_minute
+		}
+	}
+}
+
+/// A one-off window around a known event, unix timestamps
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct EventWindow {
+	pub name: String,
+	pub starts_at: u64,
+	pub ends_at: u64,
+}
+
+/// Windows in which strategies stand down: resting orders are cancelled and no new entries are made
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct TradingSchedule {
+	pub daily: Vec<DailyWindow>,
+	pub events: Vec<EventWindow>,
+}
+
+impl TradingSchedule {
+	pub fn load(path: &str) -> MangolResult<Self> {
+		serde_json::from_str(&std::fs::read_to_string(path)?).map_err(|e| MangolError::SerializationError(e.to_string()))
+	}
+
+	pub fn is_standing_down(&self, now_ts: u64) -> bool {
+		self.daily.iter().any(|window| window.contains(now_ts))
+			  || self.events.iter().any(|event| now_ts >= event.starts_at && now_ts < event.ends_at)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::schedule::{DailyWindow, EventWindow, TradingSchedule};
+
+	#[test]
+	fn stands_down_inside_windows_only() {
+		let schedule = TradingSchedule {
+			daily: vec![DailyWindow { start_minute: 23 * 60, end_minute: 60 }],
+			events: vec![EventWindow { name: "fomc".to_string(), starts_at: 1_000_000, ends_at: 1_003_600 }],
+		};
+		let day = 19_000 * 24 * 60 * 60;
+		assert!(schedule.is_standing_down(day + 23 * 60 * 60 + 30 * 60));
+		assert!(schedule.is_standing_down(day + 30 * 60));
+		assert!(!schedule.is_standing_down(day + 12 * 60 * 60));
+		assert!(schedule.is_standing_down(1_001_000));
+		assert!(!schedule.is_standing_down(1_003_600));
+	}
+}
@@ -0,0 +1,143 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+
+use mangol_common::errors::{MangolError, MangolResult};
+use serde::{Deserialize, Serialize};
+
+use crate::fib_state::FibState;
+use crate::market_data::L2Snapshot;
+use crate::optimizer::{BacktestMarket, BacktestResult, FibCandidate, PaperSession};
+
+/// A state the shadow variant moved to, what the live strategy would have done with its parameters
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ShadowDecision {
+	pub timestamp: u64,
+	pub price: f64,
+	/// None once the variant's position closed
+	pub state: Option<FibState>,
+}
+
+/// A FibStrat variant trading on paper next to the live strategy, on the same market and rounds.
+/// The live parameters trade on paper too, so the comparison isn't skewed by the paper fill model
+pub struct ShadowRun {
+	pub baseline: PaperSession,
+	pub variant: PaperSession,
+	/// Every state change of the variant, oldest first
+	pub decisions: Vec<ShadowDecision>,
+	/// Decisions are appended here as json lines
+	pub decision_log: Option<PathBuf>,
+	last_rounds: (Option<u64>, Option<u64>),
+	rounds: usize,
+}
+
+impl ShadowRun {
+	pub fn new(market: &BacktestMarket, baseline: FibCandidate, variant: FibCandidate) -> Self {
+		Self {
+			baseline: PaperSession::new(market, baseline),
+			variant: PaperSession::new(market, variant),
+			decisions: vec![],
+			decision_log: None,
+			last_rounds: (None, None),
+			rounds: 0,
+		}
+	}
+
+	pub fn with_decision_log(mut self, decision_log: PathBuf) -> Self {
+		self.decision_log = Some(decision_log);
+		self
+	}
+
+	/// Feeds a live round to both sessions, each decides on its own interval
+	pub fn observe(&mut self, now_ts: u64, price: f64, book: Option<&L2Snapshot>) -> MangolResult<()> {
+		self.rounds += 1;
+		if due(self.last_rounds.0, self.baseline.candidate.action_interval_secs, now_ts) {
+			self.last_rounds.0 = Some(now_ts);
+			self.baseline.step(price, book)?;
+		}
+		if due(self.last_rounds.1, self.variant.candidate.action_interval_secs, now_ts) {
+			self.last_rounds.1 = Some(now_ts);
+			let before = self.variant.strat().map(|strat| strat.position.current_state.clone());
+			self.variant.step(price, book)?;
+			let after = self.variant.strat().map(|strat| strat.position.current_state.clone());
+			if after != before {
+				self.record(ShadowDecision { timestamp: now_ts, price, state: after })?;
+			}
+		}
+		Ok(())
+	}
+
+	fn record(&mut self, decision: ShadowDecision) -> MangolResult<()> {
+		if let Some(path) = &self.decision_log {
+			let line = serde_json::to_string(&decision).map_err(|e| MangolError::SerializationError(e.to_string()))?;
+			let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+			writeln!(file, "{}", line)?;
+		}
+		self.decisions.push(decision);
+		Ok(())
+	}
+
+	pub fn report(&self) -> ShadowReport {
+		ShadowReport { baseline: self.baseline.result(), variant: self.variant.result(), rounds: self.rounds, decisions: self.decisions.len() }
+	}
+}
+
+fn due(last_round: Option<u64>, interval_secs: u64, now_ts: u64) -> bool {
+	last_round.map(|last_round| now_ts >= last_round + interval_secs).unwrap_or(true)
+}
+
+/// Paper results of the live parameters and the shadow variant over the same live rounds
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ShadowReport {
+	pub baseline: BacktestResult,
+	pub variant: BacktestResult,
+	pub rounds: usize,
+	pub decisions: usize,
+}
+
+impl ShadowReport {
+	/// Variant pnl over the baseline's, native quote
+	pub fn pnl_difference(&self) -> f64 {
+		self.variant.pnl - self.baseline.pnl
+	}
+
+	/// Whether the variant made more without a deeper drawdown, the bar for promoting it
+	pub fn variant_ahead(&self) -> bool {
+		self.pnl_difference() > 0.0 && self.variant.max_drawdown <= self.baseline.max_drawdown
+	}
+
+	pub fn summary(&self) -> String {
+		format!(
+			"shadow over {} rounds, {} decisions: pnl {:.0} vs live params {:.0} ({:+.0}), drawdown {:.0} vs {:.0}, fills {} vs {}, positions {} vs {}{}",
+			self.rounds, self.decisions, self.variant.pnl, self.baseline.pnl, self.pnl_difference(),
+			self.variant.max_drawdown, self.baseline.max_drawdown, self.variant.fills, self.baseline.fills,
+			self.variant.positions, self.baseline.positions,
+			if self.variant_ahead() { ", variant ahead" } else { "" }
+		)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use mangol_mango::mock::sol_perp;
+	use crate::fib_trader::FibParams;
+	use crate::optimizer::{BacktestMarket, FibCandidate};
+	use crate::shadow::ShadowRun;
+
+	#[test]
+	fn runs_both_parameter_sets_on_the_same_rounds() {
+		let market = BacktestMarket { market: sol_perp(), base_lot_size: 10_000_000, quote_lot_size: 100, books: vec![] };
+		let live = FibCandidate { fib_params: FibParams::default(), max_position_depth: 8, action_interval_secs: 10 };
+		let variant = FibCandidate { action_interval_secs: 20, fib_params: FibParams { fib_ratio: 2.0, price_fib_ratio: 0.25 }, ..live };
+		let mut shadow = ShadowRun::new(&market, live, variant);
+		for (round, price) in [0.1, 0.101, 0.103, 0.099, 0.095, 0.094].iter().enumerate() {
+			shadow.observe(1_000 + round as u64 * 10, *price, None).unwrap();
+		}
+		let report = shadow.report();
+		assert_eq!(report.rounds, 6);
+		assert!(report.baseline.positions >= 1 && report.variant.positions >= 1);
+		// the variant decides every other round, its first one opened a position
+		assert_eq!(shadow.decisions.first().map(|decision| decision.timestamp), Some(1_000));
+		assert!(report.summary().starts_with("shadow over 6 rounds"));
+	}
+}
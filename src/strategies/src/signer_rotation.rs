@@ -0,0 +1,61 @@
+use std::path::PathBuf;
+
+use mangol_common::errors::{MangolError, MangolResult, SolanaError};
+
+/// Rotation requests for a running strategy's signer, so keys change without a restart.
+///
+/// `mangol rotate-signer [keystore]` writes `<dir>/<strategy>.rotate`, holding the keystore to
+/// switch to or nothing to fail over to the configured backup, and the strategy takes it on its
+/// next round
+#[derive(Clone, Debug)]
+pub struct SignerRotation {
+	pub strategy: String,
+	pub dir: PathBuf,
+}
+
+impl SignerRotation {
+	pub fn new(strategy: &str, dir: PathBuf) -> Self {
+		Self { strategy: strategy.to_string(), dir }
+	}
+
+	pub fn file_path(&self) -> PathBuf {
+		self.dir.join(format!("{}.rotate", self.strategy))
+	}
+
+	pub fn request(&self, keystore: Option<&str>) -> MangolResult<()> {
+		std::fs::write(self.file_path(), keystore.unwrap_or(""))?;
+		Ok(())
+	}
+
+	/// The pending request, removed once read. Some(None) rotates to the backup signer
+	pub fn take_request(&self) -> MangolResult<Option<Option<String>>> {
+		let keystore = match std::fs::read_to_string(self.file_path()) {
+			Ok(keystore) => keystore.trim().to_string(),
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+			Err(e) => return Err(e.into())
+		};
+		std::fs::remove_file(self.file_path())?;
+		Ok(Some(if keystore.is_empty() { None } else { Some(keystore) }))
+	}
+}
+
+/// Whether `err` is the cluster rejecting the signer's signature, the cue to fail over
+pub fn is_signature_failure(err: &MangolError) -> bool {
+	matches!(err, MangolError::SolanaError(SolanaError::SignatureVerificationFailed(_)))
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::signer_rotation::SignerRotation;
+
+	#[test]
+	fn takes_rotation_requests_once() {
+		let rotation = SignerRotation::new(&format!("rotation-test-{}", std::process::id()), std::env::temp_dir());
+		assert_eq!(rotation.take_request().unwrap(), None);
+		rotation.request(None).unwrap();
+		assert_eq!(rotation.take_request().unwrap(), Some(None));
+		assert_eq!(rotation.take_request().unwrap(), None);
+		rotation.request(Some("./delegate.json")).unwrap();
+		assert_eq!(rotation.take_request().unwrap(), Some(Some("./delegate.json".to_string())));
+	}
+}
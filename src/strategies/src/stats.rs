@@ -0,0 +1,330 @@
+use std::collections::BTreeMap;
+
+use crate::accounting::CostBasis;
+use crate::replay::{EquitySnapshot, ExecutionRecord, ExpenseRecord, RecordedSession};
+
+const SECS_PER_YEAR: f64 = 365.0 * 86_400.0;
+
+/// Position lifecycle outcome, pnl read from the equity curve over the session
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SessionOutcome {
+	pub started_at: u64,
+	pub ended_at: u64,
+	pub pnl: f64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PerformanceStats {
+	pub periods: usize,
+	/// Equity change over the curve relative to the first snapshot
+	pub total_return: f64,
+	/// Native quote paid in funding over the curve, negative when funding was received
+	pub funding_paid: f64,
+	/// Native quote earned in deposit interest net of borrow interest over the curve
+	pub interest_earned: f64,
+	/// Return with funding and interest taken out, what the strategy made from trading alone
+	pub trading_return: f64,
+	/// Native quote value of the lamports spent on fees and rent
+	pub expenses: f64,
+	/// Return after expenses, paid from the fee payer's SOL and so missing from the equity curve
+	pub net_return: f64,
+	/// The expenses broken down per strategy and day
+	pub daily_expenses: Vec<DailyExpenses>,
+	/// Annualized, None without enough variance to measure
+	pub sharpe: Option<f64>,
+	pub sortino: Option<f64>,
+	pub sessions: usize,
+	pub win_rate: Option<f64>,
+	pub avg_holding_secs: Option<f64>,
+	/// Fill quality, None without fills
+	pub execution: Option<ExecutionQuality>,
+	/// Native quote realized by the fills and the convention it was matched by, None until computed
+	pub realized_pnl: Option<(CostBasis, f64)>,
+}
+
+/// Average slippage of fills in basis points, positive when fills were worse than the benchmark
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct ExecutionQuality {
+	pub fills: usize,
+	pub vs_decision_bps: f64,
+	pub vs_oracle_bps: f64,
+	/// Over the fills that had a best quote
+	pub vs_best_quote_bps: Option<f64>,
+	pub worst_vs_decision_bps: f64,
+}
+
+impl ExecutionQuality {
+	pub fn summary(&self) -> String {
+		format!(
+			"slippage over {} fills: {:.1} bps vs decision (worst {:.1}), {:.1} bps vs oracle, {} vs best quote",
+			self.fills,
+			self.vs_decision_bps,
+			self.worst_vs_decision_bps,
+			self.vs_oracle_bps,
+			self.vs_best_quote_bps.map(|bps| format!("{:.1} bps", bps)).unwrap_or_else(|| "n/a".to_string())
+		)
+	}
+}
+
+/// Fill weighted by base lots so a large bad fill isn't hidden by many small good ones
+pub fn execution_quality(executions: &[ExecutionRecord]) -> Option<ExecutionQuality> {
+	let filled: f64 = executions.iter().map(|record| record.base_filled.abs() as f64).sum();
+	if filled == 0.0 {
+		return None;
+	}
+	let weighted = |bps: &dyn Fn(&ExecutionRecord) -> f64| executions.iter().map(|record| bps(record) * record.base_filled.abs() as f64).sum::<f64>() / filled;
+	let quoted: Vec<&ExecutionRecord> = executions.iter().filter(|record| record.best_quote.is_some()).collect();
+	let quoted_filled: f64 = quoted.iter().map(|record| record.base_filled.abs() as f64).sum();
+	Some(ExecutionQuality {
+		fills: executions.len(),
+		vs_decision_bps: weighted(&|record| record.vs_decision_bps()),
+		vs_oracle_bps: weighted(&|record| record.vs_oracle_bps()),
+		vs_best_quote_bps: if quoted_filled > 0.0 {
+			Some(quoted.iter().map(|record| record.vs_best_quote_bps().unwrap() * record.base_filled.abs() as f64).sum::<f64>() / quoted_filled)
+		} else {
+			None
+		},
+		worst_vs_decision_bps: executions.iter().map(|record| record.vs_decision_bps()).fold(f64::MIN, f64::max),
+	})
+}
+
+impl PerformanceStats {
+	pub fn summary(&self) -> String {
+		let ratio = |value: Option<f64>| value.map(|value| format!("{:.2}", value)).unwrap_or_else(|| "n/a".to_string());
+		let summary = format!(
+			"return {:.2}% (trading {:.2}%, funding paid {:.2}, interest earned {:.2}, net of {:.2} fees and rent {:.2}%) sharpe {} sortino {} win rate {} over {} sessions, avg holding {}s",
+			self.total_return * 100.0,
+			self.trading_return * 100.0,
+			self.funding_paid,
+			self.interest_earned,
+			self.expenses,
+			self.net_return * 100.0,
+			ratio(self.sharpe),
+			ratio(self.sortino),
+			self.win_rate.map(|rate| format!("{:.0}%", rate * 100.0)).unwrap_or_else(|| "n/a".to_string()),
+			self.sessions,
+			self.avg_holding_secs.map(|secs| format!("{:.0}", secs)).unwrap_or_else(|| "n/a".to_string())
+		);
+		let summary = match &self.execution {
+			Some(execution) => format!("{}, {}", summary, execution.summary()),
+			None => summary
+		};
+		let summary = match self.realized_pnl {
+			Some((cost_basis, realized)) => format!("{}, realized {:.2} ({:?})", summary, realized, cost_basis),
+			None => summary
+		};
+		self.daily_expenses.iter().fold(summary, |summary, daily| format!("{}\n{}", summary, daily.summary()))
+	}
+}
+
+/// Simple returns between consecutive snapshots
+pub fn period_returns(curve: &[EquitySnapshot]) -> Vec<f64> {
+	curve.windows(2).filter(|pair| pair[0].equity > 0.0).map(|pair| pair[1].equity / pair[0].equity - 1.0).collect()
+}
+
+/// Funding the position paid between consecutive snapshots, from the change in the funding index on its side
+pub fn funding_paid(curve: &[EquitySnapshot]) -> f64 {
+	curve.windows(2).map(|pair| {
+		let (before, after) = (&pair[0], &pair[1]);
+		if before.base_position > 0 {
+			(after.long_funding - before.long_funding) * before.base_position as f64
+		} else {
+			(after.short_funding - before.short_funding) * before.base_position as f64
+		}
+	}).sum()
+}
+
+fn mean(values: &[f64]) -> f64 {
+	values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// Annualized (mean excess return) / deviation, the deviation taken from `deviation_of` the excess returns
+fn annualized_ratio(returns: &[f64], risk_free_per_period: f64, periods_per_year: f64, deviation_of: fn(&[f64]) -> f64) -> Option<f64> {
+	if returns.len() < 2 {
+		return None;
+	}
+	let excess: Vec<f64> = returns.iter().map(|r| r - risk_free_per_period).collect();
+	let deviation = deviation_of(&excess);
+	if deviation <= 0.0 {
+		return None;
+	}
+	Some(mean(&excess) / deviation * periods_per_year.sqrt())
+}
+
+fn std_dev(values: &[f64]) -> f64 {
+	let mean = mean(values);
+	(values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (values.len() - 1) as f64).sqrt()
+}
+
+fn downside_dev(values: &[f64]) -> f64 {
+	(values.iter().map(|v| v.min(0.0).powi(2)).sum::<f64>() / values.len() as f64).sqrt()
+}
+
+pub fn sharpe(returns: &[f64], risk_free_per_period: f64, periods_per_year: f64) -> Option<f64> {
+	annualized_ratio(returns, risk_free_per_period, periods_per_year, std_dev)
+}
+
+pub fn sortino(returns: &[f64], risk_free_per_period: f64, periods_per_year: f64) -> Option<f64> {
+	annualized_ratio(returns, risk_free_per_period, periods_per_year, downside_dev)
+}
+
+fn equity_at(curve: &[EquitySnapshot], timestamp: u64) -> Option<f64> {
+	curve.iter().take_while(|snapshot| snapshot.timestamp <= timestamp).last().or_else(|| curve.first()).map(|snapshot| snapshot.equity)
+}
+
+/// Outcome of every session with at least one recorded step inside the curve
+pub fn session_outcomes(sessions: &[RecordedSession], curve: &[EquitySnapshot]) -> Vec<SessionOutcome> {
+	sessions.iter().filter_map(|session| {
+		let ended_at = session.steps.last()?.timestamp;
+		let pnl = equity_at(curve, ended_at)? - equity_at(curve, session.started_at)?;
+		Some(SessionOutcome { started_at: session.started_at, ended_at, pnl })
+	}).collect()
+}
+
+/// Expenses of one strategy on one day
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DailyExpenses {
+	/// Days since the unix epoch
+	pub day: u64,
+	pub strategy: String,
+	pub transactions: usize,
+	pub fee: u64,
+	pub priority_fee: u64,
+	pub rent: u64,
+	pub quote_value: f64,
+}
+
+impl DailyExpenses {
+	pub fn summary(&self) -> String {
+		format!(
+			"day {} {}: {} transactions, {} lamports fees ({} priority) and {} rent, {:.2} quote",
+			self.day, self.strategy, self.transactions, self.fee, self.priority_fee, self.rent, self.quote_value
+		)
+	}
+}
+
+/// Expenses summed per strategy and day, ordered by day
+pub fn daily_expenses(expenses: &[ExpenseRecord]) -> Vec<DailyExpenses> {
+	let mut days: BTreeMap<(u64, String), DailyExpenses> = BTreeMap::new();
+	for record in expenses {
+		let day = record.timestamp / 86_400;
+		let daily = days.entry((day, record.strategy.clone())).or_insert_with(|| DailyExpenses { day, strategy: record.strategy.clone(), ..Default::default() });
+		daily.transactions += 1;
+		daily.fee += record.expense.fee;
+		daily.priority_fee += record.expense.priority_fee;
+		daily.rent += record.expense.rent;
+		daily.quote_value += record.quote_value;
+	}
+	days.into_values().collect()
+}
+
+pub fn performance(curve: &[EquitySnapshot], sessions: &[RecordedSession], expenses: &[ExpenseRecord], executions: &[ExecutionRecord], annual_risk_free_rate: f64) -> PerformanceStats {
+	let returns = period_returns(curve);
+	let outcomes = session_outcomes(sessions, curve);
+	let mut stats = PerformanceStats {
+		periods: returns.len(),
+		sessions: outcomes.len(),
+		expenses: expenses.iter().map(|record| record.quote_value).sum(),
+		daily_expenses: daily_expenses(expenses),
+		execution: execution_quality(executions),
+		..Default::default()
+	};
+	if let (Some(first), Some(last)) = (curve.first(), curve.last()) {
+		if first.equity > 0.0 {
+			stats.funding_paid = funding_paid(curve);
+			stats.interest_earned = last.interest_earned - first.interest_earned;
+			stats.total_return = last.equity / first.equity - 1.0;
+			stats.trading_return = (last.equity + stats.funding_paid - stats.interest_earned) / first.equity - 1.0;
+			stats.net_return = (last.equity - stats.expenses) / first.equity - 1.0;
+		}
+		if returns.len() > 0 && last.timestamp > first.timestamp {
+			let period_secs = (last.timestamp - first.timestamp) as f64 / returns.len() as f64;
+			let periods_per_year = SECS_PER_YEAR / period_secs;
+			let risk_free_per_period = (1.0 + annual_risk_free_rate).powf(1.0 / periods_per_year) - 1.0;
+			stats.sharpe = sharpe(&returns, risk_free_per_period, periods_per_year);
+			stats.sortino = sortino(&returns, risk_free_per_period, periods_per_year);
+		}
+	}
+	if !outcomes.is_empty() {
+		stats.win_rate = Some(outcomes.iter().filter(|outcome| outcome.pnl > 0.0).count() as f64 / outcomes.len() as f64);
+		stats.avg_holding_secs = Some(outcomes.iter().map(|outcome| (outcome.ended_at - outcome.started_at) as f64).sum::<f64>() / outcomes.len() as f64);
+	}
+	stats
+}
+
+#[cfg(test)]
+mod tests {
+	use mangol_solana::expenses::TxExpense;
+	use mangol_mango::types::Side;
+	use crate::replay::{EquitySnapshot, ExecutionRecord, ExpenseRecord};
+	use crate::stats::{daily_expenses, execution_quality, funding_paid, performance, sortino};
+
+	fn snapshot(timestamp: u64, equity: f64, base_position: i64, short_funding: f64) -> EquitySnapshot {
+		EquitySnapshot { timestamp, equity, base_position, long_funding: 0.0, short_funding, interest_earned: 0.0 }
+	}
+
+	#[test]
+	fn separates_funding_from_trading_returns() {
+		// short 100 lots while the short funding index drops by 0.5: the short pays 50
+		let curve = vec![snapshot(0, 1_000.0, -100, 1.0), snapshot(3_600, 1_010.0, -100, 0.5), snapshot(7_200, 1_000.0, -100, 0.5)];
+		assert_eq!(funding_paid(&curve), 50.0);
+		let stats = performance(&curve, &[], &[], &[], 0.0);
+		assert_eq!(stats.periods, 2);
+		assert_eq!(stats.total_return, 0.0);
+		assert!((stats.trading_return - 0.05).abs() < 1e-12);
+		assert!(stats.sharpe.is_some());
+		// deposits earning 10 over the curve was not trading either
+		let mut curve = curve;
+		curve[2].interest_earned = 10.0;
+		assert!((performance(&curve, &[], &[], &[], 0.0).trading_return - 0.04).abs() < 1e-12);
+	}
+
+	#[test]
+	fn nets_expenses_per_strategy_and_day() {
+		let expense = |strategy: &str, timestamp: u64, fee: u64| ExpenseRecord {
+			strategy: strategy.to_string(),
+			timestamp,
+			signature: String::new(),
+			expense: TxExpense { fee, priority_fee: fee - 5_000, rent: 0 },
+			quote_value: fee as f64 / 1_000.0,
+		};
+		let expenses = vec![expense("fib", 10, 5_000), expense("fib", 20, 10_000), expense("fib", 86_400, 5_000), expense("maker", 30, 5_000)];
+		let daily = daily_expenses(&expenses);
+		assert_eq!(daily.len(), 3);
+		assert_eq!((daily[0].strategy.as_str(), daily[0].transactions, daily[0].fee, daily[0].priority_fee), ("fib", 2, 15_000, 5_000));
+		let curve = vec![snapshot(0, 1_000.0, 0, 0.0), snapshot(3_600, 1_050.0, 0, 0.0)];
+		let stats = performance(&curve, &[], &expenses, &[], 0.0);
+		assert_eq!(stats.expenses, 25.0);
+		assert!((stats.net_return - 0.025).abs() < 1e-12);
+		assert_eq!(stats.daily_expenses, daily);
+		assert!(stats.summary().ends_with("day 1 fib: 1 transactions, 5000 lamports fees (0 priority) and 0 rent, 5.00 quote"));
+	}
+	
+	#[test]
+	fn weights_slippage_by_fill_size() {
+		let execution = |side: Side, fill_price: f64, base_filled: i64, best_quote: Option<f64>| ExecutionRecord {
+			strategy: "fib".to_string(),
+			timestamp: 0,
+			signature: String::new(),
+			side,
+			decision_price: 100.0,
+			oracle_price: 100.0,
+			best_quote,
+			fill_price,
+			base_filled,
+		};
+		// a buy 10 bps above the decision and a sell at it, three times the size
+		let quality = execution_quality(&[execution(Side::Bid, 100.1, 100, Some(100.05)), execution(Side::Ask, 100.0, 300, None)]).unwrap();
+		assert_eq!(quality.fills, 2);
+		assert!((quality.vs_decision_bps - 2.5).abs() < 1e-9);
+		assert!((quality.worst_vs_decision_bps - 10.0).abs() < 1e-9);
+		assert!((quality.vs_best_quote_bps.unwrap() - 4.9975).abs() < 1e-3);
+		assert_eq!(execution_quality(&[]), None);
+	}
+	
+	#[test]
+	fn sortino_ignores_upside_volatility() {
+		assert_eq!(sortino(&[0.01, 0.03, 0.02], 0.0, 1.0), None);
+		assert!(sortino(&[0.01, -0.02, 0.02], 0.0, 1.0).unwrap() > 0.0);
+	}
+}
@@ -0,0 +1,9 @@
+use mangol_common::errors::MangolResult;
+
+/// A strategy the runner drives: open the position once, then trade until an error
+pub trait Strategy {
+	/// Names the strategy's kill switch, recordings and alerts
+	fn name(&self) -> &str;
+	fn init(&mut self) -> MangolResult<()>;
+	fn run(&mut self) -> MangolResult<()>;
+}
@@ -0,0 +1,77 @@
+use std::time::Duration;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// When decision rounds end, so requotes don't land on a predictable beat
+#[derive(Clone, Debug)]
+pub struct RoundTiming {
+	/// Rounds end on multiples of this many seconds of wall clock, 60 for every minute at :00
+	pub align_secs: Option<u64>,
+	/// Up to this much random delay is added to the end of every round
+	pub max_jitter: Duration,
+	rng: StdRng,
+}
+
+impl Default for RoundTiming {
+	fn default() -> Self {
+		Self { align_secs: None, max_jitter: Duration::ZERO, rng: StdRng::from_entropy() }
+	}
+}
+
+impl RoundTiming {
+	pub fn aligned(mut self, align_secs: u64) -> Self {
+		self.align_secs = Some(align_secs).filter(|secs| *secs > 0);
+		self
+	}
+
+	pub fn with_jitter(mut self, max_jitter: Duration) -> Self {
+		self.max_jitter = max_jitter;
+		self
+	}
+
+	/// Reproducible jitter for backtests
+	pub fn with_seed(mut self, seed: u64) -> Self {
+		self.rng = StdRng::seed_from_u64(seed);
+		self
+	}
+
+	/// Length in milliseconds of a round starting at `now_millis`. Aligned rounds end on the
+	/// boundary nearest to `interval_secs` from now, `jitter` in [0, 1) scales `max_jitter`
+	pub fn round_millis(&self, now_millis: u64, interval_secs: u64, jitter: f64) -> u64 {
+		let mut end = now_millis + interval_secs * 1000;
+		if let Some(align_secs) = self.align_secs {
+			let align_millis = align_secs * 1000;
+			let earliest = end.saturating_sub(align_millis / 2).max(now_millis + 1);
+			end = (earliest + align_millis - 1) / align_millis * align_millis;
+		}
+		end + (self.max_jitter.as_millis() as f64 * jitter) as u64 - now_millis
+	}
+
+	pub fn next_round_millis(&mut self, now_millis: u64, interval_secs: u64) -> u64 {
+		let jitter = if self.max_jitter.is_zero() { 0.0 } else { self.rng.gen::<f64>() };
+		self.round_millis(now_millis, interval_secs, jitter)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+	use crate::timing::RoundTiming;
+
+	#[test]
+	fn aligns_rounds_to_wall_clock_and_adds_jitter() {
+		let plain = RoundTiming::default();
+		assert_eq!(plain.round_millis(500, 43, 0.0), 43_000);
+		let minutes = RoundTiming::default().aligned(60);
+		// 12:00:00.5 with a one minute interval ends at 12:01:00
+		assert_eq!(minutes.round_millis(43_200_500, 60, 0.0), 59_500);
+		assert_eq!(minutes.round_millis(43_200_500, 43, 0.0), 59_500);
+		assert_eq!(minutes.round_millis(43_200_500, 300, 0.0), 299_500);
+		let jittered = RoundTiming::default().with_jitter(Duration::from_secs(10));
+		assert_eq!(jittered.round_millis(0, 43, 0.5), 48_000);
+		let mut seeded = RoundTiming::default().with_jitter(Duration::from_secs(10)).with_seed(1);
+		let round = seeded.next_round_millis(0, 43);
+		assert!(round >= 43_000 && round < 53_000);
+	}
+}
@@ -0,0 +1,228 @@
+use std::net::TcpListener;
+use std::str::FromStr;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, RwLock};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use mangol_common::errors::{MangolError, MangolResult};
+use mangol_mango::queue::{load_fills_since, FillEvent};
+use mangol_mango::types::{MangoGroup, PerpMarketData, PerpMarketInfo, Side};
+use mangol_solana::connection::SolanaConnection;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use tungstenite::Message;
+
+use crate::bus::{BusEvent, EventBus};
+
+/// A fill event normalized to ui units
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Trade {
+	pub market: String,
+	pub price: f64,
+	pub size: f64,
+	pub taker_side: String,
+	pub maker: String,
+	pub taker: String,
+	pub timestamp: u64,
+	pub seq_num: usize,
+}
+
+impl Trade {
+	pub fn from_fill(fill: &FillEvent, market: &PerpMarketData, perp_market_info: &PerpMarketInfo) -> Self {
+		let native_price = fill.price as f64 * perp_market_info.quote_lot_size as f64 / perp_market_info.base_lot_size as f64;
+		let price = native_price * 10_f64.powi(market.base_decimals as i32 - market.quote_decimals as i32);
+		let size = (fill.quantity * perp_market_info.base_lot_size) as f64 / 10_f64.powi(market.base_decimals as i32);
+		Self {
+			market: market.name.clone(),
+			price,
+			size,
+			taker_side: match fill.taker_side {
+				Side::Bid => "buy".to_string(),
+				Side::Ask => "sell".to_string(),
+			},
+			maker: fill.maker.to_string(),
+			taker: fill.taker.to_string(),
+			timestamp: fill.timestamp,
+			seq_num: fill.seq_num,
+		}
+	}
+}
+
+/// The fills pushed to the event queue in `data` after `last_seq_num` as trades, with the queue's seq_num
+pub fn trades_since(data: &[u8], last_seq_num: usize, market: &PerpMarketData, perp_market_info: &PerpMarketInfo) -> MangolResult<(usize, Vec<Trade>)> {
+	let (seq_num, fills) = load_fills_since(data, last_seq_num)
+		  .map_err(|e| MangolError::MangoError(format!("Failed to decode event queue of {} {:?}", market.name, e)))?;
+	Ok((seq_num, fills.iter().map(|fill| Trade::from_fill(fill, market, perp_market_info)).collect()))
+}
+
+/// Polls the configured perp event queues and streams new fills as json
+/// to every websocket client connected on `bind_addr` and to in-process subscribers
+pub struct TradeFeedPublisher {
+	pub solana_connection: Arc<SolanaConnection>,
+	pub mango_group: MangoGroup,
+	pub markets: Vec<PerpMarketData>,
+	pub bind_addr: String,
+	pub poll_interval: Duration,
+	pub subscribers: Arc<RwLock<Vec<Sender<Trade>>>>,
+	/// Trades are also published here as they are polled
+	pub event_bus: Option<EventBus>,
+}
+
+impl TradeFeedPublisher {
+	pub fn new(solana_connection: &SolanaConnection, mango_group: MangoGroup, markets: Vec<PerpMarketData>, bind_addr: &str) -> MangolResult<Self> {
+		let my_connection = solana_connection.try_clone()?;
+		Ok(Self {
+			solana_connection: Arc::new(my_connection),
+			mango_group,
+			markets,
+			bind_addr: bind_addr.to_string(),
+			poll_interval: Duration::from_millis(500),
+			subscribers: Arc::new(RwLock::new(vec![])),
+			event_bus: None,
+		})
+	}
+
+	pub fn with_event_bus(mut self, event_bus: EventBus) -> Self {
+		self.event_bus = Some(event_bus);
+		self
+	}
+
+	/// In-process ticker source, e.g. for candle building
+	pub fn subscribe(&self) -> Receiver<Trade> {
+		let (sender, receiver) = channel();
+		self.subscribers.write().unwrap().push(sender);
+		receiver
+	}
+
+	pub fn start(&self) -> MangolResult<Vec<JoinHandle<()>>> {
+		Ok(vec![self.start_websocket_server()?, self.start_polling()])
+	}
+
+	/// Fails when `bind_addr` can't be listened on
+	fn start_websocket_server(&self) -> MangolResult<JoinHandle<()>> {
+		let bind_addr = self.bind_addr.clone();
+		let subscribers = self.subscribers.clone();
+		let listener = TcpListener::bind(&bind_addr)
+			  .map_err(|e| MangolError::MangoError(format!("Trade feed: failed to bind {} {:?}", bind_addr, e)))?;
+		Ok(std::thread::spawn(move || {
+			println!("[+] Trade feed listening on ws://{}", bind_addr);
+			for stream in listener.incoming() {
+				let stream = match stream {
+					Ok(stream) => stream,
+					Err(e) => {
+						eprintln!("[-] Trade feed: failed to accept connection {:?}", e);
+						continue;
+					}
+				};
+				let (sender, receiver) = channel::<Trade>();
+				subscribers.write().unwrap().push(sender);
+				std::thread::spawn(move || {
+					let mut websocket = match tungstenite::accept(stream) {
+						Ok(websocket) => websocket,
+						Err(e) => {
+							eprintln!("[-] Trade feed: websocket handshake failed {:?}", e);
+							return;
+						}
+					};
+					for trade in receiver {
+						if websocket.write_message(Message::Text(serde_json::to_string(&trade).unwrap())).is_err() {
+							// client went away, dropping the receiver unregisters it on the next publish
+							break;
+						}
+					}
+				});
+			}
+		}))
+	}
+
+	fn start_polling(&self) -> JoinHandle<()> {
+		let connection = self.solana_connection.clone();
+		let subscribers = self.subscribers.clone();
+		let markets = self.markets.clone();
+		let mango_group = self.mango_group.clone();
+		let poll_interval = self.poll_interval;
+		let event_bus = self.event_bus.clone();
+		std::thread::spawn(move || {
+			let mut last_seq_nums: Vec<Option<usize>> = vec![None; markets.len()];
+			loop {
+				for (i, market) in markets.iter().enumerate() {
+					let event_queue_pk = Pubkey::from_str(&market.events_key).unwrap();
+					let account = match connection.rpc_client.get_account(&event_queue_pk) {
+						Ok(account) => account,
+						Err(e) => {
+							eprintln!("[-] Trade feed: failed to fetch event queue for {} {:?}", market.name, e);
+							continue;
+						}
+					};
+					let last_seq_num = match last_seq_nums[i] {
+						Some(seq_num) => seq_num,
+						None => {
+							// don't replay whatever is already sitting in the queue on startup
+							if let Ok((seq_num, _)) = load_fills_since(&account.data, usize::MAX) {
+								last_seq_nums[i] = Some(seq_num);
+							}
+							continue;
+						}
+					};
+					if let Ok((seq_num, trades)) = trades_since(&account.data, last_seq_num, market, market.perp_market_info(&mango_group)) {
+						last_seq_nums[i] = Some(seq_num);
+						for trade in trades {
+							subscribers.write().unwrap().retain(|subscriber| subscriber.send(trade.clone()).is_ok());
+							if let Some(event_bus) = &event_bus {
+								event_bus.publish(BusEvent::Trade(trade.clone()));
+							}
+						}
+					}
+				}
+				std::thread::sleep(poll_interval);
+			}
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use mangol_mango::mock::{sol_perp, MockMangoClient};
+	use mangol_mango::queue::{EventType, EVENT_QUEUE_HEADER_SIZE, EVENT_SIZE};
+	use mangol_mango::types::Side;
+	use solana_sdk::pubkey::Pubkey;
+	use crate::trade_feed::trades_since;
+
+	fn fill(seq_num: u64, taker_side: Side, price: i64, quantity: i64) -> [u8; EVENT_SIZE] {
+		let mut event = [0u8; EVENT_SIZE];
+		event[0] = EventType::Fill as u8;
+		event[1] = taker_side as u8;
+		event[8..16].copy_from_slice(&1_700_000_000u64.to_le_bytes());
+		event[16..24].copy_from_slice(&seq_num.to_le_bytes());
+		event[24..56].copy_from_slice(Pubkey::new_unique().as_ref());
+		event[112..144].copy_from_slice(Pubkey::new_unique().as_ref());
+		event[184..192].copy_from_slice(&price.to_le_bytes());
+		event[192..200].copy_from_slice(&quantity.to_le_bytes());
+		event
+	}
+
+	#[test]
+	fn decodes_new_fills_of_a_wrapped_event_queue() {
+		let mango_client = MockMangoClient::new(3, 10_000_000, 100);
+		let perp_market_info = &mango_client.mango_group.perp_markets[3];
+		// three events starting at slot 2 of a 3 slot ring, seq nums 7 to 9
+		let mut data = vec![0u8; EVENT_QUEUE_HEADER_SIZE + 3 * EVENT_SIZE];
+		data[8..16].copy_from_slice(&2u64.to_le_bytes());
+		data[16..24].copy_from_slice(&3u64.to_le_bytes());
+		data[24..32].copy_from_slice(&10u64.to_le_bytes());
+		let mut out = [0u8; EVENT_SIZE];
+		out[0] = EventType::Out as u8;
+		for (slot, event) in [out, fill(9, Side::Ask, 4_000, 15), fill(7, Side::Bid, 4_100, 20)].iter().enumerate() {
+			let offset = EVENT_QUEUE_HEADER_SIZE + slot * EVENT_SIZE;
+			data[offset..offset + EVENT_SIZE].copy_from_slice(event);
+		}
+		let (seq_num, trades) = trades_since(&data, 8, &sol_perp(), perp_market_info).unwrap();
+		assert_eq!(seq_num, 10);
+		assert_eq!(trades.len(), 1);
+		assert_eq!((trades[0].seq_num, trades[0].taker_side.as_str(), trades[0].timestamp), (9, "sell", 1_700_000_000));
+		assert!((trades[0].price - 40.0).abs() < 1e-9);
+		assert!((trades[0].size - 0.15).abs() < 1e-9);
+		assert_eq!(trades_since(&data, 6, &sol_perp(), perp_market_info).unwrap().1.len(), 2);
+	}
+}
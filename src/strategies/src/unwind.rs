@@ -0,0 +1,194 @@
+use fixed::types::I80F48;
+use mangol_common::errors::{MangolError, MangolResult};
+use mangol_mailer::notification::Notification;
+use mangol_mango::client::MangoClient;
+use mangol_mango::registry::MarketRegistry;
+use mangol_mango::types::{HealthType, MangoAccount, MangoCache, OrderType, Side, MAX_PAIRS, QUOTE_INDEX};
+use mangol_solana::swap::JupiterSwap;
+
+/// What a liquidation moved into the liqor's account, the difference between its account before and after
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AcquiredInventory {
+	/// (market index, base lots), positive when the liquidation left the liqor longer
+	pub perps: Vec<(usize, i64)>,
+	/// (token index, native amount), negative for a borrow taken over from the liqee
+	pub tokens: Vec<(usize, f64)>,
+}
+
+impl AcquiredInventory {
+	/// Token amounts are compared at the indexes of `mango_cache`, the cache read after the liquidation
+	pub fn between(before: &MangoAccount, after: &MangoAccount, mango_cache: &MangoCache) -> Self {
+		let perps = (0..MAX_PAIRS)
+			  .map(|i| (i, after.perp_accounts[i].base_position - before.perp_accounts[i].base_position))
+			  .filter(|(_, lots)| *lots != 0)
+			  .collect();
+		let tokens = (0..QUOTE_INDEX)
+			  .map(|i| {
+				  let bank_cache = &mango_cache.root_bank_cache[i];
+				  (i, (after.get_net(bank_cache, i) - before.get_net(bank_cache, i)).to_num::<f64>())
+			  })
+			  // interest accrued between the two reads is not inventory
+			  .filter(|(_, amount)| amount.abs() >= 1.0)
+			  .collect();
+		Self { perps, tokens }
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.perps.is_empty() && self.tokens.is_empty()
+	}
+}
+
+/// Worst price a market order unwinding on `side` accepts, `max_slippage` away from the oracle
+pub fn limit_price(side: Side, oracle_price: f64, max_slippage: f64) -> f64 {
+	match side {
+		Side::Bid => oracle_price * (1.0 + max_slippage),
+		Side::Ask => oracle_price * (1.0 - max_slippage),
+	}
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UnwindReport {
+	pub liqee: String,
+	pub unwound: Vec<String>,
+	/// Inventory left in the account, with why
+	pub skipped: Vec<String>,
+	/// Liqor equity after the unwind minus before the liquidation, UI quote
+	pub realized_profit: f64,
+}
+
+/// Disposes of what a liquidation left the liqor holding: perp positions through IOC orders and
+/// token deposits or borrows through Jupiter swaps against quote, each within `max_slippage` of the oracle
+pub struct LiquidationUnwinder {
+	pub mango_client: MangoClient,
+	/// Perp markets the liqor can close positions in, positions in others are reported as skipped
+	pub registry: MarketRegistry,
+	/// Ratio of the oracle price, 0.01 accepts fills up to 1% worse than oracle
+	pub max_slippage: f64,
+	/// Inventory worth less than this, native quote, is left alone
+	pub min_value: f64,
+}
+
+impl LiquidationUnwinder {
+	pub fn new(mango_client: MangoClient, registry: MarketRegistry) -> Self {
+		Self {
+			mango_client,
+			registry,
+			max_slippage: 0.01,
+			min_value: 1_000_000.0,
+		}
+	}
+
+	pub fn with_max_slippage(mut self, max_slippage: f64) -> Self {
+		self.max_slippage = max_slippage;
+		self
+	}
+
+	pub fn with_min_value(mut self, min_value: f64) -> Self {
+		self.min_value = min_value;
+		self
+	}
+
+	/// Unwinds everything the liquidation of `liqee` added to the liqor since `before`, read with
+	/// `equity_before` native quote just ahead of the liquidation, and reports the realized profit
+	pub fn unwind(&mut self, liqee: &str, before: &MangoAccount, equity_before: I80F48) -> MangolResult<UnwindReport> {
+		self.mango_client.update()?;
+		let inventory = AcquiredInventory::between(before, &self.mango_client.mango_account, &self.mango_client.mango_cache);
+		let mut report = UnwindReport { liqee: liqee.to_string(), ..UnwindReport::default() };
+		for (market_index, lots) in &inventory.perps {
+			match self.unwind_perp(*market_index, *lots) {
+				Ok(Some(unwound)) => report.unwound.push(unwound),
+				Ok(None) => {}
+				Err(e) => report.skipped.push(format!("perp market {} {} lots: {:?}", market_index, lots, e)),
+			}
+		}
+		for (token_index, amount) in &inventory.tokens {
+			match self.unwind_token(*token_index, *amount) {
+				Ok(Some(unwound)) => report.unwound.push(unwound),
+				Ok(None) => {}
+				Err(e) => report.skipped.push(format!("token {} {}: {:?}", token_index, amount, e)),
+			}
+		}
+		self.mango_client.update()?;
+		let equity_after = self.mango_client.get_health(HealthType::Equity)?;
+		let quote_decimals = self.mango_client.mango_group.tokens[QUOTE_INDEX].decimals as i32;
+		report.realized_profit = (equity_after - equity_before).to_num::<f64>() / 10_f64.powi(quote_decimals);
+		println!("[+] Unwound liquidation of {}, realized {:.2} {:?} skipped {:?}", liqee, report.realized_profit, report.unwound, report.skipped);
+		mangol_mailer::notify(&Notification::LiquidationUnwound {
+			liqee: report.liqee.clone(),
+			realized_profit: report.realized_profit,
+			unwound: report.unwound.iter().chain(report.skipped.iter()).cloned().collect::<Vec<_>>().join("\n"),
+		});
+		Ok(report)
+	}
+
+	/// Trades `lots` back out of the market, back to the position the liqor had before
+	fn unwind_perp(&self, market_index: usize, lots: i64) -> MangolResult<Option<String>> {
+		let oracle_price = self.mango_client.mango_cache.get_price(market_index);
+		let perp_market_info = &self.mango_client.mango_group.perp_markets[market_index];
+		if (lots.abs() * perp_market_info.base_lot_size) as f64 * oracle_price < self.min_value {
+			return Ok(None);
+		}
+		let market = self.registry.markets.iter().find(|market| market.market_index == market_index)
+			  .ok_or_else(|| MangolError::MangoError(format!("perp market {} is not in the registry", market_index)))?;
+		let side = if lots > 0 { Side::Ask } else { Side::Bid };
+		let price = limit_price(side, oracle_price, self.max_slippage);
+		// not reduce only, the liqor may have held the opposite side before the liquidation
+		let tx_hash = self.mango_client.place_perp_order_with_base(perp_market_info, market, side, price, lots.abs(), OrderType::ImmediateOrCancel, false, None)?;
+		Ok(Some(format!("{} {} {} at up to {:.4} {}", market.name, if lots > 0 { "sold" } else { "bought" }, market.ui_base_size(perp_market_info, lots.abs()), market.ui_price(price), tx_hash)))
+	}
+
+	/// Withdraws and swaps a token deposit into quote, or swaps quote into the token to repay a borrow
+	fn unwind_token(&self, token_index: usize, amount: f64) -> MangolResult<Option<String>> {
+		let token_price = self.mango_client.mango_cache.get_price(token_index);
+		let value = amount.abs() * token_price;
+		if value < self.min_value {
+			return Ok(None);
+		}
+		let token_mint = self.mango_client.mango_group.tokens[token_index].mint;
+		let quote_mint = self.mango_client.mango_group.tokens[QUOTE_INDEX].mint;
+		let mut swap = JupiterSwap::new(&self.mango_client.solana_connection);
+		swap.slippage_percent = self.max_slippage * 100.0;
+		let (input_index, input_mint, input_amount, output_index, output_mint) = if amount > 0.0 {
+			(token_index, token_mint, amount.floor() as u64, QUOTE_INDEX, quote_mint)
+		} else {
+			// enough quote to buy back the borrow at the worst accepted price
+			(QUOTE_INDEX, quote_mint, (value * (1.0 + self.max_slippage)).ceil() as u64, token_index, token_mint)
+		};
+		let output_before = self.mango_client.get_wallet_token_balance(&output_mint)?;
+		self.mango_client.withdraw(input_index, input_amount, false)?;
+		swap.swap(&input_mint, &output_mint, input_amount, &self.mango_client.signer)?;
+		let received = self.mango_client.get_wallet_token_balance(&output_mint)?.saturating_sub(output_before);
+		if received == 0 {
+			return Err(MangolError::MangoError(format!("swap of {} native token {} returned nothing, left in the wallet", input_amount, input_index)));
+		}
+		let tx_hash = self.mango_client.deposit(output_index, received)?;
+		Ok(Some(format!("swapped {} native token {} into {} native token {} {}", input_amount, input_index, received, output_index, tx_hash)))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use fixed::types::I80F48;
+	use mangol_mango::mock::MockMangoClient;
+	use mangol_mango::types::Side;
+	use crate::unwind::{limit_price, AcquiredInventory};
+
+	#[test]
+	fn diffs_acquired_inventory() {
+		let mut mango_client = MockMangoClient::new(3, 10_000_000, 100);
+		mango_client.mango_cache.root_bank_cache[1].deposit_index = I80F48::from_num(1);
+		mango_client.mango_cache.root_bank_cache[2].borrow_index = I80F48::from_num(1);
+		let before = mango_client.mango_account;
+		let mut after = before;
+		after.perp_accounts[3].base_position = 12;
+		after.deposits[1] = I80F48::from_num(5_000_000_000_u64);
+		after.borrows[2] = I80F48::from_num(250_000);
+		let inventory = AcquiredInventory::between(&before, &after, &mango_client.mango_cache);
+		assert_eq!(inventory.perps, vec![(3, 12)]);
+		assert_eq!(inventory.tokens, vec![(1, 5_000_000_000.0), (2, -250_000.0)]);
+		assert!(AcquiredInventory::between(&before, &before, &mango_client.mango_cache).is_empty());
+
+		assert!((limit_price(Side::Ask, 40.0, 0.01) - 39.6).abs() < 1e-9);
+		assert!((limit_price(Side::Bid, 40.0, 0.01) - 40.4).abs() < 1e-9);
+	}
+}
@@ -1,188 +1,352 @@
-use std::str::FromStr;
-use std::sync::{Arc, RwLock};
+use std::collections::HashMap;
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex, RwLock};
 use std::thread::JoinHandle;
-use std::time::Instant;
+use std::time::Duration;
 
-use itertools::Itertools;
-use mangol_common::errors::MangolResult;
+use mangol_common::errors::{MangolError, MangolResult};
+use mangol_mango::instructions::liquidate_perp_market;
+use mangol_mango::liquidation::size_liquidation_with_simulation;
+use mangol_mango::health::{account_health, decode_mango_account, decode_mango_cache, decode_mango_group};
+use mangol_mango::stream::PriceStream;
+use mangol_mango::profiles::GroupProfile;
 use mangol_solana::connection::SolanaConnection;
-use solana_account_decoder::{UiAccountData, UiAccountEncoding};
+use mangol_solana::scan::MAX_MULTIPLE_ACCOUNTS;
+use mangol_solana::subscription::{AccountUpdate, UpdateSource};
+#[cfg(feature = "geyser")]
+use mangol_solana::geyser::GeyserSubscription;
+use mangol_solana::cache::AccountCache;
 use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::transaction::Transaction;
 
-use mangol_mango::types::{HealthCache, HealthType, load_open_orders, MangoAccount, MangoCache, MangoGroup, UserActiveAssets};
+use fixed::types::I80F48;
+use mangol_mailer::notification::Notification;
+use mangol_mango::types::{HealthCache, HealthType, load_open_orders_from_bytes, MangoAccount, MangoCache, MangoGroup, PerpMarket, UserActiveAssets, MAX_PAIRS, QUOTE_INDEX};
+
+use crate::liquidator_mode::{LiquidationMode, ModeSwitch};
+use crate::scanner::LiquidationScanner;
+use crate::unwind::LiquidationUnwinder;
+use crate::watch_list::WatchList;
 
 pub struct MangoLiquidator {
 	pub solana_connection: Arc<SolanaConnection>,
+	/// Group the watched accounts belong to and its program
+	pub mango_program_id: Pubkey,
+	pub mango_group_pk: Pubkey,
 	pub new_accounts_queue: Arc<RwLock<Vec<Arc<Pubkey>>>>,
-	pub watchers: Arc<RwLock<Vec<(JoinHandle<MangolResult<()>>, Arc<Pubkey>)>>>,
+	/// Group and cache accounts shared between every health check
+	pub account_cache: Arc<AccountCache>,
+	pub scanner: LiquidationScanner,
+	/// How often watched accounts are fetched, in batches of MAX_MULTIPLE_ACCOUNTS
+	pub poll_interval: Duration,
+	/// Accounts added and removed at runtime, replaces the queue as the source of watched accounts
+	pub watch_list: Option<WatchList>,
+}
+
+/// The account liquidations are taken over by and the mode deciding whether they are
+pub struct Liqor {
+	pub signer: Keypair,
+	pub mango_account_pk: Pubkey,
+	pub mango_program_id: Pubkey,
+	pub mode: ModeSwitch,
+	/// Trades what a sent liquidation left the liqor holding back into quote
+	pub unwinder: Option<Mutex<LiquidationUnwinder>>,
+}
+
+/// Open orders of the spot markets in `mango_account`'s margin basket, what the program checks health with
+fn basket_open_orders(mango_account: &MangoAccount) -> Vec<Pubkey> {
+	(0..MAX_PAIRS).filter(|i| mango_account.in_margin_basket[*i]).map(|i| mango_account.spot_open_orders[i]).collect()
+}
+
+impl Liqor {
+	pub fn new(signer: Keypair, mango_account_pk: Pubkey, mango_program_id: Pubkey, mode: ModeSwitch) -> Self {
+		Self { signer, mango_account_pk, mango_program_id, mode, unwinder: None }
+	}
+
+	pub fn with_unwinder(mut self, unwinder: LiquidationUnwinder) -> Self {
+		self.unwinder = Some(Mutex::new(unwinder));
+		self
+	}
+
+	/// Takes over the liqee's largest perp position, simulated and sized down until the liqor stays
+	/// healthy. Only logged in dry-run, sent in execute
+	fn liquidate(&self, connection: &SolanaConnection, account_cache: &AccountCache, mango_group_pk: &Pubkey, mango_group: &MangoGroup, mango_cache: &MangoCache, liqee_pk: &Pubkey, liqee: &MangoAccount) -> MangolResult<()> {
+		let mode = self.mode.mode();
+		if mode == LiquidationMode::AlertOnly {
+			return Ok(());
+		}
+		let market_index = match (0..MAX_PAIRS).filter(|i| liqee.perp_accounts[*i].base_position != 0).max_by_key(|i| liqee.perp_accounts[*i].base_position.abs()) {
+			Some(market_index) => market_index,
+			None => {
+				println!("[-] {} has no perp position to take over", liqee_pk);
+				return Ok(());
+			}
+		};
+		let perp_market_info = &mango_group.perp_markets[market_index];
+		let perp_market_account = account_cache.get_or_fetch(&connection.rpc_client, &perp_market_info.perp_market)?;
+		let perp_market = PerpMarket::load_checked(perp_market_account, &self.mango_program_id, mango_group_pk)
+			  .map_err(|e| MangolError::MangoError(format!("Failed to load perp market {} {:?}", market_index, e)))?;
+		let liqor_account = account_cache.get_or_fetch(&connection.rpc_client, &self.mango_account_pk)?;
+		let liqor = decode_mango_account(&liqor_account.data)?;
+		let base_position = liqee.perp_accounts[market_index].base_position;
+		let build = |max_base: I80F48| liquidate_perp_market(
+			&self.mango_program_id,
+			mango_group_pk,
+			&mango_group.mango_cache,
+			&perp_market_info.perp_market,
+			&perp_market.event_queue,
+			liqee_pk,
+			&self.mango_account_pk,
+			&self.signer.pubkey(),
+			&basket_open_orders(liqee),
+			&basket_open_orders(&liqor),
+			max_base.to_num::<i64>() * base_position.signum(),
+		).map(|instruction| vec![instruction]);
+		// health the way the program checks it, spot open orders don't change in a perp liquidation
+		let health_of = |mango_account: &MangoAccount, health_type: HealthType| {
+			let mut open_orders = vec![];
+			for open_orders_pk in &mango_account.spot_open_orders {
+				if *open_orders_pk == Pubkey::default() {
+					open_orders.push(None);
+				} else {
+					let open_orders_account = account_cache.get_or_fetch(&connection.rpc_client, open_orders_pk)?;
+					open_orders.push(Some(load_open_orders_from_bytes(&open_orders_account.data).map_err(|e| MangolError::MangoError(format!("{:?}", e)))?));
+				}
+			}
+			account_health(mango_group, mango_cache, mango_account, &open_orders, health_type)
+		};
+		let health = |mango_account: &MangoAccount| health_of(mango_account, HealthType::Init);
+		let simulation = size_liquidation_with_simulation(connection, &self.signer, I80F48::from_num(base_position.abs()), liqee_pk, &self.mango_account_pk, &health, &build)?;
+		let base_transfer = simulation.max_liab_transfer.to_num::<i64>() * base_position.signum();
+		let quote_decimals = mango_group.tokens[QUOTE_INDEX].decimals as i32;
+		let expected_profit = base_transfer.abs() as f64 * perp_market_info.base_lot_size as f64 * mango_cache.get_price(market_index)
+			  * perp_market_info.liquidation_fee.to_num::<f64>() / 10_f64.powi(quote_decimals);
+		if mode == LiquidationMode::DryRun {
+			println!("[dry-run] Would take over {} base lots of market {} from {}, expected profit {:.2} liqor health after {:?}", base_transfer, market_index, liqee_pk, expected_profit, simulation.liqor_health);
+			return Ok(());
+		}
+		let instructions = build(simulation.max_liab_transfer).map_err(|e| MangolError::MangoError(e.to_string()))?;
+		// what the unwind's realized profit is measured from
+		let equity_before = match &self.unwinder {
+			Some(_) => Some(health_of(&liqor, HealthType::Equity)?),
+			None => None
+		};
+		let signature = connection.try_tx_once(Transaction::new_with_payer(&instructions, Some(&self.signer.pubkey())), &self.signer)?;
+		println!("[+] Took over {} base lots of market {} from {}, expected profit {:.2} {}", base_transfer, market_index, liqee_pk, expected_profit, signature);
+		mangol_mailer::notify(&Notification::Liquidated {
+			liqee: liqee_pk.to_string(),
+			market_index: market_index as u32,
+			base_transfer,
+			expected_profit,
+			signature,
+		});
+		if let (Some(unwinder), Some(equity_before)) = (&self.unwinder, equity_before) {
+			if let Err(e) = unwinder.lock().unwrap().unwind(&liqee_pk.to_string(), &liqor, equity_before) {
+				eprintln!("[-] Failed to unwind the liquidation of {} {:?}", liqee_pk, e);
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Health check workers, independent of how many accounts are watched
+pub const LIQUIDATION_WORKERS: usize = 8;
+
+/// The group and its cache, through `account_cache`
+pub(crate) fn load_group_and_cache(connection: &SolanaConnection, account_cache: &AccountCache, mango_group_pk: &Pubkey) -> MangolResult<(MangoGroup, MangoCache)> {
+	// TODO: make this part async
+	let mango_group_account_info = account_cache.get_or_fetch(&connection.rpc_client, mango_group_pk)?;
+	let decoded_mango_group = decode_mango_group(&mango_group_account_info.data)?;
+	let mango_cache_account_info = account_cache.get_or_fetch(&connection.rpc_client, &decoded_mango_group.mango_cache)?;
+	let decoded_mango_cache = decode_mango_cache(&mango_cache_account_info.data)?;
+	Ok((decoded_mango_group, decoded_mango_cache))
 }
 
-const WS_URL: &str = "wss://ninja.genesysgo.net";
+/// Alerts when `mango_account` can be liquidated, and liquidates it when a liqor is set and its mode allows
+pub(crate) fn check_liquidatable(connection: &SolanaConnection, account_cache: &AccountCache, mango_group_pk: &Pubkey, liqor: Option<&Liqor>, user_health_cache: &mut HealthCache, account: &Pubkey, mango_account: &MangoAccount) -> MangolResult<()> {
+	let (decoded_mango_group, decoded_mango_cache) = load_group_and_cache(connection, account_cache, mango_group_pk)?;
+	user_health_cache.reset(UserActiveAssets::new(&decoded_mango_group, mango_account, vec![]));
+	let mut open_orders = vec![];
+	for open_orders_pk in &mango_account.spot_open_orders {
+		if *open_orders_pk == Pubkey::default() {
+			open_orders.push(None)
+		} else {
+			let open_orders_account = account_cache.get_or_fetch(&connection.rpc_client, open_orders_pk)?;
+			open_orders.push(Some(load_open_orders_from_bytes(&open_orders_account.data).map_err(|e| MangolError::MangoError(format!("{} {:?}", open_orders_pk, e)))?))
+		}
+	}
+	user_health_cache.init_vals_with_orders_vec(&decoded_mango_group, &decoded_mango_cache, mango_account, &open_orders);
+	let init_health = user_health_cache.get_health(&decoded_mango_group, HealthType::Init);
+	let maint_health = user_health_cache.get_health(&decoded_mango_group, HealthType::Maint);
+	let equity_health = user_health_cache.get_health(&decoded_mango_group, HealthType::Equity);
+	if mango_account.being_liquidated && init_health < 0 || maint_health < 0 {
+		println!("Account Liquidatable {} Your health {} {} {}", &account.to_string(), init_health, maint_health, equity_health);
+		let ui_quote = |health: I80F48| health.to_num::<f64>() / 10_f64.powi(decoded_mango_group.tokens[QUOTE_INDEX].decimals as i32);
+		mangol_mailer::notify(&Notification::Liquidatable {
+			account: account.to_string(),
+			init_health: ui_quote(init_health),
+			maint_health: ui_quote(maint_health),
+			equity: ui_quote(equity_health),
+		});
+		if let Some(liqor) = liqor {
+			liqor.liquidate(connection, account_cache, mango_group_pk, &decoded_mango_group, &decoded_mango_cache, account, mango_account)?;
+		}
+	}
+	Ok(())
+}
 
 impl MangoLiquidator {
-	pub fn new(solana_connection: SolanaConnection, accounts: Vec<Pubkey>) -> MangolResult<Self> {
-		let my_connection = SolanaConnection::new(&solana_connection.rpc_client.url())?;
+	pub fn new(solana_connection: SolanaConnection, profile: &GroupProfile, accounts: Vec<Pubkey>) -> MangolResult<Self> {
+		let my_connection = Arc::new(solana_connection.try_clone()?);
+		let account_cache = Arc::new(AccountCache::new(Duration::from_secs(2)));
+		let mango_group_pk = profile.mango_group_pk()?;
 		Ok(Self {
-			solana_connection: Arc::new(my_connection),
+			scanner: LiquidationScanner::new(my_connection.clone(), account_cache.clone(), mango_group_pk, LIQUIDATION_WORKERS)?,
+			solana_connection: my_connection,
+			mango_program_id: profile.mango_program_id()?,
+			mango_group_pk,
 			new_accounts_queue: Arc::new(RwLock::new(accounts.iter().map(|a| Arc::new(a.clone())).collect())),
-			watchers: Arc::new(RwLock::new(vec![])),
+			account_cache,
+			poll_interval: Duration::from_secs(2),
+			watch_list: None,
 		})
 	}
-	
+
+	/// Rescores the watched accounts exposed to a market whenever its price changes in the mango cache
+	pub fn follow_prices(&self) -> MangolResult<JoinHandle<()>> {
+		let (mango_group, _) = load_group_and_cache(&self.solana_connection, &self.account_cache, &self.mango_group_pk)?;
+		// the stream keeps the account cache current, rescoring reads the prices that moved
+		let prices = PriceStream {
+			mango_cache_pk: mango_group.mango_cache,
+			num_oracles: mango_group.num_oracles,
+			rpc_url: self.solana_connection.rpc_client.url(),
+			ws_url: self.solana_connection.ws_url(),
+			account_cache: None,
+		}.with_account_cache(self.account_cache.clone()).start();
+		let scanner = self.scanner.clone();
+		Ok(std::thread::spawn(move || {
+			for changed in prices {
+				let market_indexes: Vec<usize> = changed.iter().map(|price| price.market_index).collect();
+				if let Err(e) = scanner.on_price_update(&market_indexes) {
+					eprintln!("[-] Failed to rescore accounts {:?}", e);
+				}
+			}
+		}))
+	}
+
+	/// Fetches the watched accounts in batches and hands the changed ones to the scanner,
+	/// one thread and no sockets however many accounts are queued
 	pub fn watch_and_liquidate(&self) -> MangolResult<JoinHandle<()>> {
+		self.follow_prices()?;
 		let new_accounts = self.new_accounts_queue.clone();
-		let watchers = self.watchers.clone();
 		let connection = self.solana_connection.clone();
+		let scanner = self.scanner.clone();
+		let poll_interval = self.poll_interval;
+		let watch_list = self.watch_list.clone();
 		Ok(std::thread::spawn(move || {
+			let mut watched: Vec<Pubkey> = vec![];
+			let mut last_data: HashMap<Pubkey, Vec<u8>> = HashMap::new();
+			let mut generation = None;
 			loop {
-				let mut successfully_added: Vec<Arc<Pubkey>> = vec![];
-				// continuously iterate through queued accounts and watch for possible liquidation
-				match new_accounts.try_read() {
-					Ok(accounts) => {
-						if accounts.len() > 0 {
-							println!("[+] Starting watchers for {} accounts", accounts.len());
+				if let Some(watch_list) = &watch_list {
+					if generation != Some(watch_list.generation()) {
+						generation = Some(watch_list.generation());
+						let listed = watch_list.list();
+						for pubkey in watched.iter().filter(|pubkey| !listed.contains(pubkey)) {
+							scanner.forget(pubkey);
+							last_data.remove(pubkey);
 						}
-						
-						for account in &*accounts {
-							let watchers_guard = watchers.try_read().unwrap();
-							
-							let account_exists = watchers_guard.iter().find(|(j, a)| a.to_string().eq(&account.to_string()));
-							if let Some(acc) = account_exists {
-								println!("[-] Account {} already being monitored", account.to_string());
-								// account already being monitored
-							} else {
-								std::mem::drop(watchers_guard);
-								let t_account = account.clone();
-								let t_connection = connection.clone();
-								
-								let watch_handle: JoinHandle<MangolResult<()>> = std::thread::spawn(move || {
-									let mango_program = Pubkey::from_str("mv3ekLzLbnVPNxjSKvqBpU3ZeZXPQdEC3bp5MDEBG68").unwrap();
-									let mango_mainnet_group = Pubkey::from_str("98pjRuQjK3qA6gXts96PqZT4Ze5QmnCmt3QYjhbUSPue").unwrap();
-									// write account liquidation watching logic here
-									let mut registered = false;
-									while !registered {
-										let mut sub = SolanaConnection::account_subscribe(&t_account, WS_URL);
-										
-										
-										if let Ok((mut subscription, mut context)) = sub {
-											registered = true;
-											let mut errored = false;
-											
-											loop {
-												if errored {
-													let mut sub = SolanaConnection::account_subscribe(&t_account, WS_URL);
-													match sub {
-														Ok((s, c)) => {
-															subscription = s;
-															context = c;
-															println!("[?] Reconnected");
-															errored = false;
-														}
-														_ => {
-															continue;
-														}
-													}
-												}
-												
-												if let Ok(account_info) = context.recv() {
-													match account_info.value.data {
-														UiAccountData::Binary(data, encoding) => {
-															println!("[?] Account changed from account {} {:?}", t_account.to_string(), encoding);
-															
-															if encoding == UiAccountEncoding::Base64 {
-																let now = Instant::now();
-																let decoded_data = base64::decode(data).unwrap();
-																let decoded_mango_account = MangoAccount::load_from_vec(decoded_data).unwrap();
-																println!("Took: {} ms", now.elapsed().as_millis());
-																
-																if !decoded_mango_account.being_liquidated {
-																	continue;
-																}
-																
-																// TODO: make this part async
-																
-																let mango_group_account_info = t_connection.rpc_client.get_account(&mango_mainnet_group).unwrap();
-																let decoded_mango_group = MangoGroup::load_checked(mango_group_account_info, &mango_program).unwrap();
-																let mango_cache_account_info = t_connection.rpc_client.get_account(&decoded_mango_group.mango_cache)?;
-																let decoded_mango_cache = MangoCache::load_checked(mango_cache_account_info, &mango_program, &decoded_mango_group).unwrap();
-																let user_assets = UserActiveAssets::new(&decoded_mango_group, &decoded_mango_account, vec![]);
-																// println!("Assets {:?}", &user_assets);
-																let mut user_health_cache = HealthCache::new(user_assets);
-																let mut open_orders = vec![];
-																for open_orders_pk in &decoded_mango_account.spot_open_orders {
-																	if *open_orders_pk == Pubkey::default() {
-																		open_orders.push(None)
-																	} else {
-																		let open_orders_account = t_connection.rpc_client.get_account(open_orders_pk)?;
-																		open_orders.push(Some(load_open_orders(open_orders_account).unwrap()))
-																	}
-																}
-																user_health_cache.init_vals_with_orders_vec(&decoded_mango_group, &decoded_mango_cache, &decoded_mango_account, &open_orders);
-																let init_health = user_health_cache.get_health(&decoded_mango_group, HealthType::Init);
-																let maint_health = user_health_cache.get_health(&decoded_mango_group, HealthType::Maint);
-																let equity_health = user_health_cache.get_health(&decoded_mango_group, HealthType::Equity);
-																if decoded_mango_account.being_liquidated && init_health < 0 || maint_health < 0 {
-																	println!("Account Liquidatable {} Your health {} {} {}", &t_account.to_string(), init_health, maint_health, equity_health);
-																	mangol_mailer::send_text_with_content(format!("Account Liquidatable {} Your health {} {} {}", &t_account.to_string(), init_health, maint_health, equity_health));
-																}
-															}
-														}
-														UiAccountData::LegacyBinary(_) => {}
-														UiAccountData::Json(_) => {}
-													}
-												} else {
-													errored = true;
-													eprintln!("[-] Watcher: An error occurred while receiving reconnecting...");
-												}
-											}
-										} else {
-											println!("Failed to initiate connection for {} Retrying", t_account.to_string());
-										}
-									}
-									Ok(())
-								});
-								
-								let mut watchers_lock = watchers.write().unwrap();
-								
-								(*watchers_lock).push((watch_handle, account.clone()));
-								
-								// add to successful list to remove watched pubkey from new accounts queue later
-								successfully_added.push(account.clone());
-								// println!("[+] Started watching for liquidation on account {}", account.to_string());
-							}
-						}
-						if accounts.len() > 0 {
-							println!("[+] Started watching {} accounts", accounts.len())
+						println!("[+] Watch list changed, watching {} accounts", listed.len());
+						watched = listed;
+					}
+				}
+				{
+					let mut new_accounts_lock = new_accounts.write().unwrap();
+					if new_accounts_lock.len() > 0 {
+						println!("[+] Watching {} more accounts", new_accounts_lock.len());
+					}
+					for account in new_accounts_lock.drain(..) {
+						if !watched.contains(&*account) {
+							watched.push(*account);
 						}
 					}
-					Err(_) => {}
 				}
-				
-				// remove successfuly monitored pubkeys from queue
-				// new_accounts read guard is dropped when we went out of scope of the match block
-				let mut new_accounts_lock = new_accounts.write().unwrap();
-				let remove_indexes: Vec<usize> = new_accounts_lock.iter().enumerate().map(|(i, p)| i).sorted().rev().collect();
-				for remove_index in remove_indexes {
-					new_accounts_lock.remove(remove_index);
+
+				for pubkeys in watched.chunks(MAX_MULTIPLE_ACCOUNTS) {
+					let slot = connection.rpc_client.get_slot().unwrap_or_default();
+					let accounts = match connection.rpc_client.get_multiple_accounts(pubkeys) {
+						Ok(accounts) => accounts,
+						Err(e) => {
+							eprintln!("[-] Failed to fetch {} accounts {:?}", pubkeys.len(), e);
+							continue;
+						}
+					};
+					for (pubkey, account) in pubkeys.iter().zip(accounts) {
+						let account = match account {
+							Some(account) => account,
+							None => continue
+						};
+						if last_data.get(pubkey) == Some(&account.data) {
+							continue;
+						}
+						last_data.insert(*pubkey, account.data.clone());
+						scanner.on_account_update(&AccountUpdate { pubkey: *pubkey, slot, account, source: UpdateSource::Polling });
+					}
 				}
+				std::thread::sleep(poll_interval);
 			}
 		}))
 	}
-	
-	pub fn add_account(&self, account: &Pubkey) -> MangolResult<()> {
-		match self.watchers.try_read() {
-			Ok(guard) => {
-				let account_exists = guard.iter().find(|(j, a)| a.to_string().eq(&account.to_string()));
-				if let Some(acc) = account_exists {
-					// account already being monitored
-				} else {
-					let mut write_lock = self.new_accounts_queue.write().unwrap();
-					(*write_lock).push(Arc::new(account.clone()));
-				}
+
+	/// Checks every mango account in `updates`, one stream for the whole program
+	/// instead of a watcher thread per account
+	pub fn watch_program(&self, updates: Receiver<AccountUpdate>) -> MangolResult<JoinHandle<()>> {
+		self.follow_prices()?;
+		let scanner = self.scanner.clone();
+		Ok(std::thread::spawn(move || {
+			for update in updates {
+				scanner.on_account_update(&update);
 			}
-			Err(_) => {}
+		}))
+	}
+
+	/// `watch_program` fed by a Geyser gRPC stream of every mango program account
+	#[cfg(feature = "geyser")]
+	pub fn watch_with_geyser(&self, endpoint: &str, x_token: Option<String>) -> MangolResult<JoinHandle<()>> {
+		let subscription = GeyserSubscription::new(endpoint, x_token, vec![self.mango_program_id]);
+		let (_subscription_handle, updates) = subscription.start();
+		self.watch_program(updates)
+	}
+
+	/// Liquidates through `liqor` instead of only alerting, as far as its mode allows
+	pub fn with_liqor(mut self, liqor: Liqor) -> Self {
+		self.scanner = self.scanner.with_liqor(liqor);
+		self
+	}
+
+	/// Follows `watch_list`, the accounts passed to new and later to add_account are added to it
+	pub fn with_watch_list(mut self, watch_list: WatchList) -> MangolResult<Self> {
+		for account in self.new_accounts_queue.write().unwrap().drain(..) {
+			watch_list.add(&account)?;
+		}
+		self.watch_list = Some(watch_list);
+		Ok(self)
+	}
+
+	pub fn add_account(&self, account: &Pubkey) -> MangolResult<()> {
+		if let Some(watch_list) = &self.watch_list {
+			watch_list.add(account)?;
+			return Ok(());
+		}
+		if self.scanner.is_watched(account) {
+			// account already being monitored
+			return Ok(());
+		}
+		let mut write_lock = self.new_accounts_queue.write().unwrap();
+		if !write_lock.iter().any(|queued| **queued == *account) {
+			(*write_lock).push(Arc::new(account.clone()));
 		}
-		
 		Ok(())
 	}
-}
\ No newline at end of file
+}
@@ -0,0 +1,112 @@
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use mangol_common::errors::{MangolError, MangolResult};
+use solana_sdk::pubkey::Pubkey;
+
+/// Accounts a watcher follows, changed at runtime through the control api and kept in `path` as a
+/// json array of pubkeys, the format of the static account files it replaces. Clones share the list,
+/// watchers compare `generation` to notice changes
+#[derive(Clone, Debug)]
+pub struct WatchList {
+	pub path: Option<PathBuf>,
+	accounts: Arc<RwLock<BTreeSet<Pubkey>>>,
+	generation: Arc<AtomicU64>,
+}
+
+impl WatchList {
+	pub fn new(accounts: &[Pubkey]) -> Self {
+		Self { path: None, accounts: Arc::new(RwLock::new(accounts.iter().copied().collect())), generation: Arc::new(AtomicU64::new(0)) }
+	}
+
+	/// The list persisted in `path`, empty when the file doesn't exist yet
+	pub fn load(path: PathBuf) -> MangolResult<Self> {
+		let accounts = match std::fs::read_to_string(&path) {
+			Ok(data) => {
+				let listed: Vec<String> = serde_json::from_str(&data).map_err(|e| MangolError::SerializationError(format!("{:?} {}", path, e)))?;
+				listed.iter().map(|pubkey| parse_pubkey(pubkey)).collect::<MangolResult<Vec<_>>>()?
+			}
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec![],
+			Err(e) => return Err(e.into())
+		};
+		let mut watch_list = Self::new(&accounts);
+		watch_list.path = Some(path);
+		Ok(watch_list)
+	}
+
+	pub fn list(&self) -> Vec<Pubkey> {
+		self.accounts.read().unwrap().iter().copied().collect()
+	}
+
+	pub fn contains(&self, pubkey: &Pubkey) -> bool {
+		self.accounts.read().unwrap().contains(pubkey)
+	}
+
+	pub fn len(&self) -> usize {
+		self.accounts.read().unwrap().len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// Bumped by every add or remove that changed the list
+	pub fn generation(&self) -> u64 {
+		self.generation.load(Ordering::SeqCst)
+	}
+
+	/// False when it was already watched
+	pub fn add(&self, pubkey: &Pubkey) -> MangolResult<bool> {
+		self.change(|accounts| accounts.insert(*pubkey))
+	}
+
+	/// False when it wasn't watched
+	pub fn remove(&self, pubkey: &Pubkey) -> MangolResult<bool> {
+		self.change(|accounts| accounts.remove(pubkey))
+	}
+
+	fn change<F: FnOnce(&mut BTreeSet<Pubkey>) -> bool>(&self, change: F) -> MangolResult<bool> {
+		let mut accounts = self.accounts.write().unwrap();
+		if !change(&mut accounts) {
+			return Ok(false);
+		}
+		self.generation.fetch_add(1, Ordering::SeqCst);
+		if let Some(path) = &self.path {
+			let listed: Vec<String> = accounts.iter().map(|pubkey| pubkey.to_string()).collect();
+			let data = serde_json::to_string_pretty(&listed).map_err(|e| MangolError::SerializationError(e.to_string()))?;
+			let partial = path.with_extension("partial");
+			std::fs::write(&partial, data)?;
+			std::fs::rename(partial, path)?;
+		}
+		Ok(true)
+	}
+}
+
+pub fn parse_pubkey(pubkey: &str) -> MangolResult<Pubkey> {
+	Pubkey::from_str(pubkey.trim()).map_err(|e| MangolError::MangoError(format!("Invalid pubkey {} {}", pubkey, e)))
+}
+
+#[cfg(test)]
+mod tests {
+	use solana_sdk::pubkey::Pubkey;
+	use crate::watch_list::WatchList;
+
+	#[test]
+	fn changes_persist_across_reloads() {
+		let path = std::env::temp_dir().join(format!("mangol-watch-list-{}.json", std::process::id()));
+		let watch_list = WatchList::load(path.clone()).unwrap();
+		assert!(watch_list.is_empty());
+		let (first, second) = (Pubkey::new_unique(), Pubkey::new_unique());
+		assert!(watch_list.add(&first).unwrap());
+		assert!(watch_list.clone().add(&second).unwrap());
+		assert!(!watch_list.add(&first).unwrap());
+		assert_eq!(watch_list.generation(), 2);
+		assert!(watch_list.remove(&first).unwrap());
+		let reloaded = WatchList::load(path.clone()).unwrap();
+		assert_eq!(reloaded.list(), vec![second]);
+		std::fs::remove_file(path).unwrap();
+	}
+}
@@ -1,95 +1,136 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::Arc;
+use std::time::Duration;
+
+use mangol_common::errors::{MangolError, MangolResult};
 use mangol_solana::connection::SolanaConnection;
-use solana_account_decoder::{UiAccountData, UiAccountEncoding};
-use solana_client::pubsub_client::{AccountSubscription, PubsubClientError};
-use solana_client::rpc_config::RpcAccountInfoConfig;
-use solana_sdk::commitment_config::CommitmentConfig;
+use mangol_solana::subscription::ResilientSubscription;
 use solana_sdk::pubkey::Pubkey;
 use mangol_mango::types::MangoAccount;
 
+use crate::watch_list::WatchList;
+
 pub struct TraderWatcher {
 	pub trader_account: Pubkey,
 	pub state: MangoAccount,
-	pub solana_connection: SolanaConnection
+	pub solana_connection: SolanaConnection,
+	/// Set to stop watching, the subscription goes with the watcher
+	pub stop: Arc<AtomicBool>,
 }
 
 impl TraderWatcher {
-	pub fn new(trader_account: Pubkey, solana_connection: &SolanaConnection ) -> Self{
-		let account_info = solana_connection.rpc_client.get_account(&trader_account).unwrap();
-		
-		let decoded_mango_account = MangoAccount::load_checked(account_info, &trader_account).unwrap();
-		
-		let my_connection = SolanaConnection::new(&solana_connection.rpc_client.url()).unwrap();
-		Self {
+	pub fn new(trader_account: Pubkey, solana_connection: &SolanaConnection) -> MangolResult<Self> {
+		let account_info = solana_connection.rpc_client.get_account(&trader_account)?;
+		let decoded_mango_account = MangoAccount::load_from_vec(account_info.data)
+			  .map_err(|e| MangolError::MangoError(format!("{} is not a mango account {:?}", trader_account, e)))?;
+		Ok(Self {
 			trader_account,
 			state: decoded_mango_account,
-			solana_connection: my_connection
+			solana_connection: solana_connection.try_clone()?,
+			stop: Arc::new(AtomicBool::new(false)),
+		})
+	}
+
+	pub fn start_watch(self) -> std::thread::JoinHandle<()> {
+		std::thread::spawn(move || {
+			self.watch_mango_account(&self.trader_account);
+		})
+	}
+
+	fn watch_mango_account(&self, account: &Pubkey) {
+		let subscription = ResilientSubscription::new(*account, &self.solana_connection.rpc_client.url(), &self.solana_connection.ws_url());
+		let (_subscription_handle, updates) = subscription.start();
+		while !self.stop.load(Ordering::SeqCst) {
+			let update = match updates.recv_timeout(Duration::from_secs(1)) {
+				Ok(update) => update,
+				Err(RecvTimeoutError::Timeout) => continue,
+				Err(RecvTimeoutError::Disconnected) => return
+			};
+			println!("[?] Account changed from account {} {:?}", account.to_string(), update.source);
+			let decoded_mango_account = match MangoAccount::load_from_vec(update.account.data) {
+				Ok(decoded_mango_account) => decoded_mango_account,
+				Err(_) => continue
+			};
+			println!("------->> Old {:?}", self.state.orders);
+			println!("------->> New {:?}", decoded_mango_account.orders)
+			//mangol_mailer::send_text_with_content(format!("Account {} Updated Something is going on there", account.clone().to_string()));
 		}
 	}
-	
-	pub fn start_watch( self) -> std::thread::JoinHandle<()>{
-		let watch_thread = std::thread::spawn(move || {
-			let mut registered = false;
-			while !registered {
-				registered = self.watch_mango_account( &self.trader_account);
+}
+
+/// Stops the watchers of accounts no longer in `listed` and starts one with `start` for every
+/// account without one. An account whose watcher failed to start is left out, tried again next time
+pub fn sync_watchers(watchers: &mut HashMap<Pubkey, Arc<AtomicBool>>, listed: &[Pubkey], mut start: impl FnMut(&Pubkey) -> MangolResult<Arc<AtomicBool>>) {
+	watchers.retain(|trader, stop| {
+		let keep = listed.contains(trader);
+		if !keep {
+			println!("[+] Stopped watching {}", trader);
+			stop.store(true, Ordering::SeqCst);
+		}
+		keep
+	});
+	for trader in listed.iter().filter(|trader| !watchers.contains_key(trader)) {
+		match start(trader) {
+			Ok(stop) => {
+				watchers.insert(*trader, stop);
 			}
-		});
-		return watch_thread
-		
-		
+			Err(e) => eprintln!("[-] Failed to watch {} {:?}", trader, e)
+		}
 	}
-	fn watch_mango_account(&self, account: &Pubkey) -> bool {
-		let ws_url = "wss://ninja.genesysgo.net";
-		let mut sub = self.account_subscribe(&account, ws_url);
-		
-		
-		if let Ok((mut subscription, mut context)) = sub {
-			let mut errored = false;
-			
-			loop {
-				if errored {
-					let mut sub = self.account_subscribe(&account, ws_url);
-					match sub {
-						Ok((s, c)) => {
-							subscription = s;
-							context = c;
-							println!("[?] Reconnected");
-							errored = false;
-						}
-						_ => {
-							continue;
-						}
-					}
-				}
-				
-				if let Ok(account_info) = context.recv() {
-					
-					match account_info.value.data {
-						UiAccountData::Binary(data, encoding) => {
-							println!("[?] Account changed from account {} {:?}", account.to_string(), encoding);
-							
-							if encoding == UiAccountEncoding::Base64 {
-								let decoded_data = base64::decode(data).unwrap();
-								let decoded_mango_account = MangoAccount::load_from_vec(decoded_data).unwrap();
-								println!("------->> Old {:?}", self.state.orders);
-								println!("------->> New {:?}", decoded_mango_account.orders)
-								//mangol_mailer::send_text_with_content(format!("Account {} Updated Something is going on there", account.clone().to_string()));
-							}
-						}
-						UiAccountData::LegacyBinary(_) => {}
-						UiAccountData::Json(_) => {}
-					}
-				} else {
-					errored = true;
-					eprintln!("[-] Watcher: An error occurred while receiving reconnecting...");
-				}
+}
+
+/// Keeps one TraderWatcher per account in `watch_list`, starting and stopping them as the list changes
+pub fn watch_traders(watch_list: WatchList, solana_connection: SolanaConnection) -> std::thread::JoinHandle<()> {
+	std::thread::spawn(move || {
+		let mut watchers: HashMap<Pubkey, Arc<AtomicBool>> = HashMap::new();
+		let mut generation = None;
+		loop {
+			// failed watchers are retried on the next change of the list
+			if generation != Some(watch_list.generation()) {
+				generation = Some(watch_list.generation());
+				sync_watchers(&mut watchers, &watch_list.list(), |trader| {
+					let watcher = TraderWatcher::new(*trader, &solana_connection)?;
+					let stop = watcher.stop.clone();
+					watcher.start_watch();
+					Ok(stop)
+				});
 			}
-		} else {
-			println!("Failed to initiate connection for {} Retrying", account.to_string());
-			return false;
+			std::thread::sleep(Duration::from_secs(1));
 		}
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashMap;
+	use std::sync::atomic::{AtomicBool, Ordering};
+	use std::sync::Arc;
+	use mangol_common::errors::MangolError;
+	use solana_sdk::pubkey::Pubkey;
+	use crate::watch_mango_traders::sync_watchers;
+
+	#[test]
+	fn starts_and_stops_watchers_as_the_list_changes() {
+		let (kept, removed, failing) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+		let mut watchers = HashMap::new();
+		let mut started = vec![];
+		let mut start = |trader: &Pubkey| {
+			started.push(*trader);
+			if *trader == failing {
+				return Err(MangolError::MangoError(format!("{} is not a mango account", trader)));
+			}
+			Ok(Arc::new(AtomicBool::new(false)))
+		};
+		sync_watchers(&mut watchers, &[kept, removed, failing], &mut start);
+		assert_eq!(watchers.len(), 2);
+		let removed_stop = watchers[&removed].clone();
+		sync_watchers(&mut watchers, &[kept, failing], &mut start);
+		assert!(removed_stop.load(Ordering::SeqCst));
+		assert!(!watchers[&kept].load(Ordering::SeqCst));
+		assert!(!watchers.contains_key(&removed));
+		// kept is not started twice, failing is tried again
+		assert_eq!(started, vec![kept, removed, failing, failing]);
 	}
-	fn account_subscribe(&self, account: &Pubkey, ws_url: &str) -> Result<AccountSubscription, PubsubClientError> {
-		return solana_client::pubsub_client::PubsubClient::account_subscribe(ws_url, account, Some(RpcAccountInfoConfig { encoding: Some(UiAccountEncoding::JsonParsed), data_slice: None, commitment: Some(CommitmentConfig::finalized()), min_context_slot: None }));
-	}
-	
-}
\ No newline at end of file
+}
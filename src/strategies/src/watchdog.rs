@@ -0,0 +1,210 @@
+use std::collections::{BTreeMap, HashSet};
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use mangol_mailer::notification::Notification;
+use serde_json::json;
+
+fn now_ts() -> u64 {
+	SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+/// Last time each of the bot's loops made progress, unix seconds. Loops beat once per iteration,
+/// so one stuck in a send or confirmation stops beating
+#[derive(Clone, Debug, Default)]
+pub struct Heartbeats {
+	beats: Arc<RwLock<BTreeMap<String, u64>>>,
+}
+
+impl Heartbeats {
+	pub fn beat(&self, subsystem: &str) {
+		self.beat_at(subsystem, now_ts());
+	}
+
+	pub fn beat_at(&self, subsystem: &str, timestamp: u64) {
+		self.beats.write().unwrap().insert(subsystem.to_string(), timestamp);
+	}
+
+	pub fn last_beat(&self, subsystem: &str) -> Option<u64> {
+		self.beats.read().unwrap().get(subsystem).copied()
+	}
+
+	pub fn snapshot(&self) -> BTreeMap<String, u64> {
+		self.beats.read().unwrap().clone()
+	}
+}
+
+/// A loop the watchdog expects to beat at least every `max_silence`
+pub struct Watched {
+	pub subsystem: String,
+	pub max_silence: Duration,
+	/// Called once per stall, e.g. to spawn a fresh instance of the service or exit for the supervisor
+	restart: Option<Box<dyn Fn() + Send + Sync>>,
+}
+
+/// Alerts when a loop stops beating and optionally restarts it. The stuck thread can't be
+/// interrupted, a restart starts a replacement next to it.
+///
+/// Liveness is exposed as a json file rewritten every check and, with a probe address, as
+/// `GET /` answering 200 while every loop beats and 503 otherwise
+pub struct Watchdog {
+	pub heartbeats: Heartbeats,
+	pub check_interval: Duration,
+	pub heartbeat_file: Option<PathBuf>,
+	pub probe_addr: Option<String>,
+	watched: Vec<Watched>,
+	stalled: Arc<RwLock<HashSet<String>>>,
+	started_at: u64,
+}
+
+impl Watchdog {
+	pub fn new(heartbeats: Heartbeats) -> Self {
+		Self {
+			heartbeats,
+			check_interval: Duration::from_secs(10),
+			heartbeat_file: None,
+			probe_addr: None,
+			watched: vec![],
+			stalled: Arc::new(RwLock::new(HashSet::new())),
+			started_at: now_ts(),
+		}
+	}
+
+	pub fn watch(mut self, subsystem: &str, max_silence: Duration) -> Self {
+		self.watched.push(Watched { subsystem: subsystem.to_string(), max_silence, restart: None });
+		self
+	}
+
+	pub fn watch_with_restart<F: Fn() + Send + Sync + 'static>(mut self, subsystem: &str, max_silence: Duration, restart: F) -> Self {
+		self.watched.push(Watched { subsystem: subsystem.to_string(), max_silence, restart: Some(Box::new(restart)) });
+		self
+	}
+
+	pub fn with_heartbeat_file(mut self, heartbeat_file: PathBuf) -> Self {
+		self.heartbeat_file = Some(heartbeat_file);
+		self
+	}
+
+	pub fn with_probe(mut self, probe_addr: &str) -> Self {
+		self.probe_addr = Some(probe_addr.to_string());
+		self
+	}
+
+	/// (subsystem, silent secs) of every watched loop past its max silence at `now_ts`.
+	/// Loops that never beat count from when the watchdog was created
+	pub fn silent(&self, now_ts: u64) -> Vec<(String, u64)> {
+		self.watched.iter().filter_map(|watched| {
+			let silent_secs = now_ts.saturating_sub(self.heartbeats.last_beat(&watched.subsystem).unwrap_or(self.started_at));
+			(silent_secs > watched.max_silence.as_secs()).then(|| (watched.subsystem.clone(), silent_secs))
+		}).collect()
+	}
+
+	/// Alerts and restarts loops that newly stalled, and clears the ones beating again.
+	/// Returns the newly stalled ones
+	pub fn check(&self, now_ts: u64) -> Vec<String> {
+		let silent = self.silent(now_ts);
+		let mut stalled = self.stalled.write().unwrap();
+		let mut newly_stalled = vec![];
+		for (subsystem, silent_secs) in &silent {
+			if !stalled.insert(subsystem.clone()) {
+				continue;
+			}
+			let watched = self.watched.iter().find(|watched| &watched.subsystem == subsystem).unwrap();
+			let action = if watched.restart.is_some() { "restarting it" } else { "alert only" };
+			eprintln!("[-] Watchdog: {} silent for {}s, {}", subsystem, silent_secs, action);
+			mangol_mailer::notify(&Notification::SubsystemStalled { subsystem: subsystem.clone(), silent_secs: *silent_secs, action: action.to_string() });
+			if let Some(restart) = &watched.restart {
+				restart();
+			}
+			newly_stalled.push(subsystem.clone());
+		}
+		let recovered: Vec<String> = stalled.iter().filter(|subsystem| !silent.iter().any(|(silent, _)| silent == *subsystem)).cloned().collect();
+		for subsystem in recovered {
+			stalled.remove(&subsystem);
+			println!("[+] Watchdog: {} is beating again", subsystem);
+			mangol_mailer::notify(&Notification::SubsystemRecovered { subsystem });
+		}
+		newly_stalled
+	}
+
+	/// What the heartbeat file and the probe serve
+	pub fn status(&self, now_ts: u64) -> serde_json::Value {
+		let silent = self.silent(now_ts);
+		json!({
+			"timestamp": now_ts,
+			"healthy": silent.is_empty(),
+			"heartbeats": self.heartbeats.snapshot(),
+			"stalled": silent.iter().map(|(subsystem, _)| subsystem).collect::<Vec<_>>(),
+		})
+	}
+
+	fn write_heartbeat_file(&self, now_ts: u64) {
+		if let Some(heartbeat_file) = &self.heartbeat_file {
+			if let Err(e) = std::fs::write(heartbeat_file, self.status(now_ts).to_string()) {
+				eprintln!("[-] Watchdog: failed to write {:?} {:?}", heartbeat_file, e);
+			}
+		}
+	}
+
+	pub fn start(self) -> JoinHandle<()> {
+		let watchdog = Arc::new(self);
+		if let Some(probe_addr) = watchdog.probe_addr.clone() {
+			let probe = watchdog.clone();
+			std::thread::spawn(move || serve_probe(&probe_addr, &probe));
+		}
+		std::thread::spawn(move || {
+			loop {
+				let now_ts = now_ts();
+				watchdog.check(now_ts);
+				watchdog.write_heartbeat_file(now_ts);
+				std::thread::sleep(watchdog.check_interval);
+			}
+		})
+	}
+}
+
+fn serve_probe(probe_addr: &str, watchdog: &Watchdog) {
+	let listener = match TcpListener::bind(probe_addr) {
+		Ok(listener) => listener,
+		Err(e) => {
+			eprintln!("[-] Watchdog: failed to bind liveness probe on {} {:?}", probe_addr, e);
+			return;
+		}
+	};
+	for stream in listener.incoming() {
+		let mut stream = match stream {
+			Ok(stream) => stream,
+			Err(_) => continue
+		};
+		// the request doesn't matter, every path answers with the status
+		let mut request = [0_u8; 1024];
+		let _ = stream.read(&mut request);
+		let status = watchdog.status(now_ts());
+		let code = if status["healthy"].as_bool().unwrap_or(false) { "200 OK" } else { "503 Service Unavailable" };
+		let body = status.to_string();
+		let _ = write!(stream, "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", code, body.len(), body);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+	use crate::watchdog::{Heartbeats, Watchdog};
+
+	#[test]
+	fn reports_loops_past_their_max_silence() {
+		let heartbeats = Heartbeats::default();
+		let watchdog = Watchdog::new(heartbeats.clone()).watch("settler", Duration::from_secs(60));
+		heartbeats.beat_at("settler", 1_000);
+		assert!(watchdog.silent(1_060).is_empty());
+		assert_eq!(watchdog.silent(1_061), vec![("settler".to_string(), 61)]);
+		assert_eq!(watchdog.status(1_061)["healthy"], false);
+		heartbeats.beat_at("settler", 1_100);
+		assert_eq!(watchdog.status(1_120)["heartbeats"]["settler"], 1_100);
+		assert_eq!(watchdog.status(1_120)["healthy"], true);
+	}
+}
@@ -0,0 +1,204 @@
+//! Terminal dashboard for operators on ssh. Connects to the state broadcasters of one or more
+//! running bots (`MANGOL_DASHBOARD_ADDR`) and tails their log file, `q` quits and `tab` cycles
+//! the market the ladder, orders and fills are shown for.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::Duration;
+
+use crossterm::event::{self, Event, KeyCode};
+use crossterm::execute;
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use mangol_common::errors::{MangolError, MangolResult};
+use mangol_strategies::dashboard::BotState;
+use ratatui::backend::{Backend, CrosstermBackend};
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::style::{Color, Style};
+use ratatui::widgets::{Block, Borders, Cell, Paragraph, Row, Table};
+use ratatui::{Frame, Terminal};
+
+const REFRESH: Duration = Duration::from_millis(250);
+const LOG_LINES: usize = 12;
+
+/// The last `lines` lines of `path`, reading at most the trailing 64KiB
+pub fn tail_lines(path: &Path, lines: usize) -> Vec<String> {
+	let mut file = match File::open(path) {
+		Ok(file) => file,
+		Err(_) => return vec![]
+	};
+	let len = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+	let _ = file.seek(SeekFrom::Start(len.saturating_sub(64 * 1024)));
+	let mut tail = String::new();
+	if file.read_to_string(&mut tail).is_err() {
+		return vec![];
+	}
+	let all: Vec<&str> = tail.lines().collect();
+	all[all.len().saturating_sub(lines)..].iter().map(|line| line.to_string()).collect()
+}
+
+fn follow(addr: String, sender: Sender<BotState>) {
+	std::thread::spawn(move || loop {
+		match tungstenite::connect(format!("ws://{}", addr)) {
+			Ok((mut websocket, _)) => {
+				while let Ok(message) = websocket.read_message() {
+					if let Ok(state) = serde_json::from_str::<BotState>(&message.to_string()) {
+						if sender.send(state).is_err() {
+							return;
+						}
+					}
+				}
+			}
+			Err(_) => {}
+		}
+		// bot restarting or not up yet
+		std::thread::sleep(Duration::from_secs(2));
+	});
+}
+
+struct Dashboard {
+	states: BTreeMap<String, BotState>,
+	selected: usize,
+	log_file: Option<PathBuf>,
+	log_tail: Vec<String>,
+}
+
+impl Dashboard {
+	fn selected(&self) -> Option<&BotState> {
+		self.states.values().nth(self.selected % self.states.len().max(1))
+	}
+
+	fn draw<B: Backend>(&self, frame: &mut Frame<B>) {
+		let rows = Layout::default()
+			  .direction(Direction::Vertical)
+			  .constraints([Constraint::Length(self.states.len() as u16 + 3), Constraint::Min(8), Constraint::Length(8), Constraint::Length(LOG_LINES as u16 + 2)])
+			  .split(frame.size());
+		let middle = Layout::default()
+			  .direction(Direction::Horizontal)
+			  .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+			  .split(rows[1]);
+		let health = |value: Option<f64>| value.map(|value| format!("{:.2}", value)).unwrap_or_else(|| "-".to_string());
+
+		let markets: Vec<Row> = self.states.values().map(|state| {
+			let style = match state.paused {
+				Some(_) => Style::default().fg(Color::Yellow),
+				None if state.maint_health.map(|health| health < 0.0).unwrap_or(false) => Style::default().fg(Color::Red),
+				None => Style::default(),
+			};
+			Row::new(vec![
+				Cell::from(format!("{} {}", state.strategy, state.market)),
+				Cell::from(format!("{:.4}", state.base_position)),
+				Cell::from(format!("{:.4}", state.oracle_price)),
+				Cell::from(health(state.equity)),
+				Cell::from(health(state.init_health)),
+				Cell::from(health(state.maint_health)),
+				Cell::from(state.paused.clone().unwrap_or_else(|| "trading".to_string())),
+			]).style(style)
+		}).collect();
+		frame.render_widget(
+			Table::new(markets)
+				  .header(Row::new(vec!["strategy", "position", "oracle", "equity", "init health", "maint health", "status"]).style(Style::default().fg(Color::Cyan)))
+				  .block(Block::default().title("Markets").borders(Borders::ALL))
+				  .widths(&[Constraint::Percentage(20), Constraint::Percentage(12), Constraint::Percentage(12), Constraint::Percentage(12), Constraint::Percentage(12), Constraint::Percentage(12), Constraint::Percentage(20)]),
+			rows[0],
+		);
+
+		let selected = self.selected();
+		let title = |name: &str| format!("{} {}", name, selected.map(|state| state.market.as_str()).unwrap_or(""));
+		let ladder: Vec<Row> = selected.map(|state| state.ladder.iter().map(|level| {
+			Row::new(vec![level.depth.to_string(), level.side.clone(), format!("{:.4}", level.price), format!("{:.4}", level.size), level.state.clone()])
+		}).collect()).unwrap_or_default();
+		frame.render_widget(
+			Table::new(ladder)
+				  .header(Row::new(vec!["depth", "side", "price", "size", "state"]).style(Style::default().fg(Color::Cyan)))
+				  .block(Block::default().title(title("Ladder")).borders(Borders::ALL))
+				  .widths(&[Constraint::Percentage(10), Constraint::Percentage(15), Constraint::Percentage(25), Constraint::Percentage(25), Constraint::Percentage(25)]),
+			middle[0],
+		);
+		let orders: Vec<Row> = selected.map(|state| state.open_orders.iter().map(|order| {
+			Row::new(vec![order.side.clone(), format!("{:.4}", order.price)])
+		}).collect()).unwrap_or_default();
+		frame.render_widget(
+			Table::new(orders)
+				  .header(Row::new(vec!["side", "price"]).style(Style::default().fg(Color::Cyan)))
+				  .block(Block::default().title(title("Open orders")).borders(Borders::ALL))
+				  .widths(&[Constraint::Percentage(40), Constraint::Percentage(60)]),
+			middle[1],
+		);
+
+		let fills: Vec<Row> = selected.map(|state| state.recent_fills.iter().rev().map(|fill| {
+			Row::new(vec![fill.timestamp.to_string(), fill.side.clone(), format!("{:.4}", fill.size), format!("{:.4}", fill.price), format!("{:.1}", fill.vs_decision_bps)])
+		}).collect()).unwrap_or_default();
+		frame.render_widget(
+			Table::new(fills)
+				  .header(Row::new(vec!["time", "side", "size", "price", "bps vs decision"]).style(Style::default().fg(Color::Cyan)))
+				  .block(Block::default().title(title("Recent fills")).borders(Borders::ALL))
+				  .widths(&[Constraint::Percentage(25), Constraint::Percentage(15), Constraint::Percentage(20), Constraint::Percentage(20), Constraint::Percentage(20)]),
+			rows[2],
+		);
+
+		let log_title = self.log_file.as_ref().map(|path| format!("Log {}", path.display())).unwrap_or_else(|| "Log (pass a log file to tail it)".to_string());
+		frame.render_widget(
+			Paragraph::new(self.log_tail.join("\n")).block(Block::default().title(log_title).borders(Borders::ALL)),
+			rows[3],
+		);
+	}
+}
+
+fn run_dashboard<B: Backend>(terminal: &mut Terminal<B>, updates: Receiver<BotState>, log_file: Option<PathBuf>) -> MangolResult<()> {
+	let mut dashboard = Dashboard { states: BTreeMap::new(), selected: 0, log_file, log_tail: vec![] };
+	loop {
+		while let Ok(state) = updates.try_recv() {
+			dashboard.states.insert(format!("{}/{}", state.strategy, state.market), state);
+		}
+		if let Some(log_file) = &dashboard.log_file {
+			dashboard.log_tail = tail_lines(log_file, LOG_LINES);
+		}
+		terminal.draw(|frame| dashboard.draw(frame))?;
+		if event::poll(REFRESH)? {
+			if let Event::Key(key) = event::read()? {
+				match key.code {
+					KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+					KeyCode::Tab => dashboard.selected += 1,
+					_ => {}
+				}
+			}
+		}
+	}
+}
+
+/// `mangol tui <addr[,addr...]> [log file]`
+pub fn run(args: &[String]) -> MangolResult<()> {
+	let addrs = args.get(0).ok_or_else(|| MangolError::MangoError("Usage: mangol tui <addr[,addr...]> [log file]".to_string()))?;
+	let (sender, updates) = channel();
+	for addr in addrs.split(',') {
+		follow(addr.trim().to_string(), sender.clone());
+	}
+	enable_raw_mode()?;
+	let mut stdout = std::io::stdout();
+	execute!(stdout, EnterAlternateScreen)?;
+	let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+	let result = run_dashboard(&mut terminal, updates, args.get(1).map(PathBuf::from));
+	// leave the terminal usable whatever happened
+	disable_raw_mode()?;
+	execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+	terminal.show_cursor()?;
+	result
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::tui::tail_lines;
+
+	#[test]
+	fn tails_last_lines() {
+		let path = std::env::temp_dir().join(format!("mangol-tui-tail-{}.log", std::process::id()));
+		std::fs::write(&path, "one\ntwo\nthree\nfour\n").unwrap();
+		assert_eq!(tail_lines(&path, 2), vec!["three".to_string(), "four".to_string()]);
+		assert_eq!(tail_lines(&path, 10).len(), 4);
+		assert!(tail_lines(&path.with_extension("missing"), 2).is_empty());
+		std::fs::remove_file(path).unwrap();
+	}
+}